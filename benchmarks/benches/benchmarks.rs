@@ -5,11 +5,11 @@ use std::{
     io::{Cursor, Read},
 };
 
-use divan::counter::BytesCount;
+use divan::counter::{BytesCount, ItemsCount};
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
 
-use veil::{Digest, PrivateKey};
+use veil::{batch, Digest, EncryptOptions, Kdf, PrivateKey};
 
 const KB: u64 = 1024;
 const LENS: &[u64] = &[0, KB, 8 * KB, 32 * KB, 64 * KB, 128 * KB, KB * KB];
@@ -25,7 +25,8 @@ fn encrypt<const LEN: u64>(bencher: divan::Bencher) {
         })
         .counter(BytesCount::new(LEN))
         .bench_values(|(rng, pk_a, pk_b, plaintext, ciphertext)| {
-            pk_a.encrypt(rng, plaintext, ciphertext, &[pk_b.public_key()], None, None).unwrap()
+            pk_a.encrypt(rng, plaintext, ciphertext, &EncryptOptions::new(&[pk_b.public_key()]))
+                .unwrap()
         });
 }
 
@@ -41,9 +42,7 @@ fn decrypt<const LEN: u64>(bencher: divan::Bencher) {
                 &mut rng,
                 io::repeat(0).take(LEN),
                 &mut ciphertext,
-                &[pk_b.public_key()],
-                None,
-                None,
+                &EncryptOptions::new(&[pk_b.public_key()]),
             )
             .unwrap();
             (pk_a, pk_b, Cursor::new(ciphertext.into_inner()))
@@ -54,6 +53,95 @@ fn decrypt<const LEN: u64>(bencher: divan::Bencher) {
         });
 }
 
+const RECEIVERS: &[usize] = &[1, 10, 100];
+
+#[divan::bench(consts = RECEIVERS)]
+fn encrypt_receivers<const N: usize>(bencher: divan::Bencher) {
+    bencher
+        .with_inputs(|| {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let pk_a = PrivateKey::random(&mut rng);
+            let receivers =
+                (0..N).map(|_| PrivateKey::random(&mut rng).public_key()).collect::<Vec<_>>();
+            (rng, pk_a, receivers, io::repeat(0).take(64 * KB), io::sink())
+        })
+        .counter(ItemsCount::new(N))
+        .bench_values(|(rng, pk_a, receivers, plaintext, ciphertext)| {
+            pk_a.encrypt(rng, plaintext, ciphertext, &EncryptOptions::new(&receivers)).unwrap()
+        });
+}
+
+#[divan::bench(consts = RECEIVERS)]
+fn encrypt_with_set<const N: usize>(bencher: divan::Bencher) {
+    bencher
+        .with_inputs(|| {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let pk_a = PrivateKey::random(&mut rng);
+            let receivers =
+                (0..N).map(|_| PrivateKey::random(&mut rng).public_key()).collect::<Vec<_>>();
+            let set = pk_a.recipient_set(&receivers);
+            (rng, pk_a, set, io::repeat(0).take(KB), io::sink())
+        })
+        .counter(ItemsCount::new(N))
+        .bench_values(|(rng, pk_a, set, plaintext, ciphertext)| {
+            pk_a.encrypt_with_set(rng, plaintext, ciphertext, &set, &set.options()).unwrap()
+        });
+}
+
+#[divan::bench(consts = RECEIVERS)]
+fn decrypt_receivers<const N: usize>(bencher: divan::Bencher) {
+    bencher
+        .with_inputs(|| {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let pk_a = PrivateKey::random(&mut rng);
+            let receivers = (0..N).map(|_| PrivateKey::random(&mut rng)).collect::<Vec<_>>();
+            let public_keys = receivers.iter().map(PrivateKey::public_key).collect::<Vec<_>>();
+            let mut ciphertext = Cursor::new(Vec::new());
+            pk_a.encrypt(
+                &mut rng,
+                io::repeat(0).take(64 * KB),
+                &mut ciphertext,
+                &EncryptOptions::new(&public_keys),
+            )
+            .unwrap();
+
+            // Decrypt as the last receiver, whose header is found last.
+            let pk_b = receivers.into_iter().last().unwrap();
+            (pk_a, pk_b, Cursor::new(ciphertext.into_inner()))
+        })
+        .counter(ItemsCount::new(N))
+        .bench_refs(|(pk_a, pk_b, ciphertext)| {
+            pk_b.decrypt(ciphertext, io::sink(), &pk_a.public_key()).unwrap()
+        });
+}
+
+#[divan::bench(consts = RECEIVERS)]
+fn decrypt_receivers_indexed<const N: usize>(bencher: divan::Bencher) {
+    bencher
+        .with_inputs(|| {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let pk_a = PrivateKey::random(&mut rng);
+            let receivers = (0..N).map(|_| PrivateKey::random(&mut rng)).collect::<Vec<_>>();
+            let public_keys = receivers.iter().map(PrivateKey::public_key).collect::<Vec<_>>();
+            let mut ciphertext = Cursor::new(Vec::new());
+            pk_a.encrypt(
+                &mut rng,
+                io::repeat(0).take(64 * KB),
+                &mut ciphertext,
+                &EncryptOptions::new(&public_keys).indexed(),
+            )
+            .unwrap();
+
+            // Decrypt as the last receiver, whose header is found by its hint.
+            let pk_b = receivers.into_iter().last().unwrap();
+            (pk_a, pk_b, Cursor::new(ciphertext.into_inner()))
+        })
+        .counter(ItemsCount::new(N))
+        .bench_refs(|(pk_a, pk_b, ciphertext)| {
+            pk_b.decrypt(ciphertext, io::sink(), &pk_a.public_key()).unwrap()
+        });
+}
+
 #[divan::bench(consts = LENS)]
 fn sign<const LEN: u64>(bencher: divan::Bencher) {
     bencher
@@ -79,6 +167,29 @@ fn verify<const LEN: u64>(bencher: divan::Bencher) {
         .bench_refs(|(pk, message, sig)| pk.public_key().verify(message, sig));
 }
 
+const BATCH_SIZES: &[usize] = &[1, 16, 256, 4096];
+
+#[divan::bench(consts = BATCH_SIZES)]
+fn verify_batch<const N: usize>(bencher: divan::Bencher) {
+    bencher
+        .with_inputs(|| {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let pk = PrivateKey::random(&mut rng);
+            (0..N)
+                .map(|i| {
+                    let message = i.to_le_bytes();
+                    let sig = pk.sign(&mut rng, &message[..]).unwrap();
+                    (pk.public_key(), message, sig)
+                })
+                .collect::<Vec<_>>()
+        })
+        .counter(ItemsCount::new(N))
+        .bench_refs(|sigs| {
+            let sigs = sigs.iter().map(|(pk, m, sig)| (pk, &m[..], sig)).collect::<Vec<_>>();
+            batch::verify_all(&sigs)
+        });
+}
+
 #[divan::bench(consts = LENS)]
 fn digest<const LEN: u64>(bencher: divan::Bencher) {
     bencher
@@ -97,7 +208,7 @@ fn pbenc_time<const TIME: u8>(bencher: divan::Bencher) {
             let pk = PrivateKey::random(&mut rng);
             (rng, pk)
         })
-        .bench_values(|(rng, pk)| pk.store(io::sink(), rng, b"passphrase", TIME, 0));
+        .bench_values(|(rng, pk)| pk.store(io::sink(), rng, b"passphrase", TIME, 0, 1));
 }
 
 const MEMORY_COSTS: &[u8] = &[1, 2, 4, 6, 8];
@@ -110,7 +221,50 @@ fn pbenc_memory<const MEMORY: u8>(bencher: divan::Bencher) {
             let pk = PrivateKey::random(&mut rng);
             (rng, pk)
         })
-        .bench_values(|(rng, pk)| pk.store(io::sink(), rng, b"passphrase", 0, MEMORY));
+        .bench_values(|(rng, pk)| pk.store(io::sink(), rng, b"passphrase", 0, MEMORY, 1));
+}
+
+const PARALLELISMS: &[u8] = &[1, 2, 4, 8];
+
+#[divan::bench(consts = PARALLELISMS)]
+fn pbenc_parallelism<const PARALLELISM: u8>(bencher: divan::Bencher) {
+    bencher
+        .with_inputs(|| {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let pk = PrivateKey::random(&mut rng);
+            (rng, pk)
+        })
+        .bench_values(|(rng, pk)| pk.store(io::sink(), rng, b"passphrase", 0, 8, PARALLELISM));
+}
+
+/// A pair of `veil.pbenc` time and memory costs.
+trait PbencParams {
+    const TIME: u8;
+    const MEMORY: u8;
+}
+
+struct Pbenc<const TIME: u8, const MEMORY: u8>;
+
+impl<const T: u8, const M: u8> PbencParams for Pbenc<T, M> {
+    const TIME: u8 = T;
+    const MEMORY: u8 = M;
+}
+
+#[divan::bench(types = [
+    Pbenc<2, 2>, Pbenc<2, 4>, Pbenc<2, 6>, Pbenc<2, 8>,
+    Pbenc<4, 2>, Pbenc<4, 4>, Pbenc<4, 6>, Pbenc<4, 8>,
+    Pbenc<6, 2>, Pbenc<6, 4>, Pbenc<6, 6>, Pbenc<6, 8>,
+    Pbenc<8, 2>, Pbenc<8, 4>, Pbenc<8, 6>, Pbenc<8, 8>,
+])]
+fn pbenc_grid<P: PbencParams>(bencher: divan::Bencher) {
+    let kdf = Kdf::Balloon { time_cost: P::TIME, memory_cost: P::MEMORY, parallelism: 1 };
+    bencher
+        .with_inputs(|| {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let pk = PrivateKey::random(&mut rng);
+            (rng, pk)
+        })
+        .bench_values(|(rng, pk)| pk.store_with(io::sink(), rng, b"passphrase", kdf));
 }
 
 #[global_allocator]
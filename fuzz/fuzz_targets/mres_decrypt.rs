@@ -0,0 +1,88 @@
+//! Decrypts either arbitrary bytes or a valid `veil.mres` message with arbitrary mutations.
+
+#![no_main]
+
+use std::io;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+use veil::{EncryptOptions, PrivateKey};
+
+#[derive(Debug, Arbitrary)]
+enum Input {
+    /// Arbitrary bytes, which should almost never decrypt.
+    Raw(Vec<u8>),
+
+    /// A valid message, which is then mutated.
+    Mutated {
+        plaintext: Vec<u8>,
+        fakes: u8,
+        padding: u16,
+        indexed: bool,
+        seekable: bool,
+        compressed: bool,
+        mutations: Vec<Mutation>,
+    },
+}
+
+#[derive(Debug, Arbitrary)]
+enum Mutation {
+    Flip { idx: u16, mask: u8 },
+    Truncate { len: u16 },
+    Extend { data: Vec<u8> },
+}
+
+fuzz_target!(|input: Input| {
+    let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+    let sender = PrivateKey::random(&mut rng);
+    let receiver = PrivateKey::random(&mut rng);
+
+    let ciphertext = match input {
+        Input::Raw(ciphertext) => ciphertext,
+        Input::Mutated { plaintext, fakes, padding, indexed, seekable, compressed, mutations } => {
+            let receivers = [receiver.public_key()];
+            let mut options = EncryptOptions::new(&receivers)
+                .fakes(usize::from(fakes % 16))
+                .padding(usize::from(padding));
+            if indexed {
+                options = options.indexed();
+            }
+            if seekable {
+                options = options.seekable();
+            } else if compressed {
+                options = options.compress(1);
+            }
+            let mut ciphertext = Vec::new();
+            sender
+                .encrypt_deterministic(&plaintext, plaintext.as_slice(), &mut ciphertext, &options)
+                .expect("should encrypt");
+
+            // An unmodified message must decrypt to the original plaintext.
+            if mutations.is_empty() {
+                let mut decrypted = Vec::new();
+                receiver
+                    .decrypt(ciphertext.as_slice(), &mut decrypted, &sender.public_key())
+                    .expect("should decrypt");
+                assert_eq!(plaintext, decrypted);
+                return;
+            }
+
+            for mutation in mutations {
+                match mutation {
+                    Mutation::Flip { idx, mask } => {
+                        if let Some(b) = ciphertext.get_mut(usize::from(idx)) {
+                            *b ^= mask;
+                        }
+                    }
+                    Mutation::Truncate { len } => ciphertext.truncate(usize::from(len)),
+                    Mutation::Extend { data } => ciphertext.extend(data),
+                }
+            }
+            ciphertext
+        }
+    };
+
+    let _ = receiver.decrypt(ciphertext.as_slice(), io::sink(), &sender.public_key());
+});
@@ -0,0 +1,61 @@
+//! Decrypts either arbitrary bytes or a valid `veil.pbenc` ciphertext with arbitrary mutations.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+use veil::fuzzing;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    passphrase: Vec<u8>,
+    plaintext: Vec<u8>,
+    parallelism: u8,
+    raw: Option<Vec<u8>>,
+    flips: Vec<(u16, u8)>,
+}
+
+fuzz_target!(|input: Input| {
+    let encrypted = input.raw.is_none();
+    let mut ciphertext = input.raw.unwrap_or_else(|| {
+        fuzzing::pbenc_encrypt(
+            ChaChaRng::seed_from_u64(0xDEADBEEF),
+            &input.passphrase,
+            0,
+            2,
+            input.parallelism % 4,
+            &input.plaintext,
+        )
+    });
+    let mut modified = false;
+    for (idx, mask) in input.flips {
+        if let Some(b) = ciphertext.get_mut(usize::from(idx)) {
+            *b ^= mask;
+            modified |= mask != 0;
+        }
+    }
+
+    // Keep the time and memory costs small enough to fuzz quickly, for both tagged envelopes and
+    // untagged legacy envelopes. Costs which would overflow are rejected before any hashing, and are
+    // covered by unit tests.
+    match ciphertext.as_mut_slice() {
+        [0xBA, time_cost, memory_cost, parallelism, ..] => {
+            *time_cost %= 2;
+            *memory_cost %= 4;
+            *parallelism %= 8;
+        }
+        [time_cost, memory_cost, ..] => {
+            *time_cost %= 2;
+            *memory_cost %= 4;
+        }
+        _ => {}
+    }
+    let decrypted = fuzzing::pbenc_decrypt(&input.passphrase, &mut ciphertext);
+
+    // An unmodified ciphertext must decrypt to the original plaintext.
+    if encrypted && !modified {
+        assert_eq!(Some(input.plaintext.as_slice()), decrypted);
+    }
+});
@@ -0,0 +1,17 @@
+//! Parses arbitrary strings as public keys and public key URIs, checking that parsed public keys
+//! round-trip.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use veil::{uri, PublicKey};
+
+fuzz_target!(|s: &str| {
+    if let Ok(pk) = s.parse::<PublicKey>() {
+        assert_eq!(Ok(pk), pk.to_string().parse());
+        assert_eq!(Some(pk), PublicKey::decode(pk.encode()));
+    }
+    if let Ok(pk) = uri::decode(s) {
+        assert_eq!(Ok(pk), uri::decode(&uri::encode(&pk)));
+    }
+});
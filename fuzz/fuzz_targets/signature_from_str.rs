@@ -0,0 +1,13 @@
+//! Parses arbitrary strings as signatures, checking that parsed signatures round-trip.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use veil::Signature;
+
+fuzz_target!(|s: &str| {
+    if let Ok(sig) = s.parse::<Signature>() {
+        assert_eq!(Ok(sig), sig.to_string().parse());
+        assert_eq!(Some(sig), Signature::decode(sig.encode()));
+    }
+});
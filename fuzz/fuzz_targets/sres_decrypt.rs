@@ -0,0 +1,50 @@
+//! Decrypts either arbitrary bytes or a valid `veil.sres` ciphertext with arbitrary mutations.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+use veil::{fuzzing, PrivateKey};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    nonce: Vec<u8>,
+    plaintext: Vec<u8>,
+    raw: Option<Vec<u8>>,
+    flips: Vec<(u16, u8)>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+    let sender = PrivateKey::random(&mut rng);
+    let receiver = PrivateKey::random(&mut rng);
+
+    let encrypted = input.raw.is_none();
+    let mut ciphertext = input.raw.unwrap_or_else(|| {
+        fuzzing::sres_encrypt(
+            &mut rng,
+            &sender,
+            &receiver.public_key(),
+            &input.nonce,
+            &input.plaintext,
+        )
+    });
+    let mut modified = false;
+    for (idx, mask) in input.flips {
+        if let Some(b) = ciphertext.get_mut(usize::from(idx)) {
+            *b ^= mask;
+            modified |= mask != 0;
+        }
+    }
+
+    let decrypted =
+        fuzzing::sres_decrypt(&receiver, &sender.public_key(), &input.nonce, &mut ciphertext);
+
+    // An unmodified ciphertext must decrypt to the original plaintext.
+    if encrypted && !modified {
+        let (_, plaintext) = decrypted.expect("should decrypt");
+        assert_eq!(input.plaintext, plaintext);
+    }
+});
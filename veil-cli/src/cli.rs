@@ -1,29 +1,74 @@
 use std::{
+    env,
     error::Error,
-    fs::File,
-    io::{self, IsTerminal, Read, Write},
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, IsTerminal, Read, Seek, Write},
+    mem,
     path::{Path, PathBuf},
     process,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueHint};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
 use clap_complete::{generate_to, Shell};
 use console::Term;
-use rand::rngs::OsRng;
+use rand::{rngs::OsRng, Rng};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use veil::{DecryptError, Digest, PrivateKey, PublicKey, Signature};
+use tracing_subscriber::{filter::LevelFilter, fmt::format::FmtSpan, EnvFilter};
+#[cfg(unix)]
+use veil::agent;
+use veil::{
+    armor::{ArmorReader, ArmorWriter},
+    artifact::Artifact,
+    backend::{self, KemBackend},
+    batch::Batch,
+    ed25519,
+    envelope::{EnvelopeWriter, FileMetadata},
+    inspect::{self, Status},
+    keystore::Guard,
+    lockstitch::Protocol,
+    passphrase, passphrase_policy,
+    policy::VerificationPolicy,
+    recipients, seekable, testvectors, threshold, uri, DecryptError, Digest, EncryptOptions, Kdf,
+    KeyStore, KeyStoreError, Padding, ParseCertificationError, ParseKeyBundleError,
+    ParseOpenSshKeyError, ParsePaperKeyError, ParsePublicKeyError, ParseRecipientsError,
+    ParseSignatureError, PolicyError, PrivateKey, PublicKey, Revocation, Signature,
+    TestVectorError, TimestampedSignature,
+};
+use zeroize::Zeroizing;
+
+mod qr;
 
 fn main() {
     let opts = Opts::parse();
+    ATOMIC.store(opts.atomic, Ordering::Relaxed);
+    QUIET.store(opts.quiet, Ordering::Relaxed);
+    init_tracing(opts.verbose);
     if let Err(e) = match opts.cmd {
         Cmd::PrivateKey(cmd) => cmd.run(),
         Cmd::PublicKey(cmd) => cmd.run(),
         Cmd::Encrypt(cmd) => cmd.run(),
         Cmd::Decrypt(cmd) => cmd.run(),
+        Cmd::Inspect(cmd) => cmd.run(),
+        Cmd::PEncrypt(cmd) => cmd.run(),
+        Cmd::PDecrypt(cmd) => cmd.run(),
         Cmd::Sign(cmd) => cmd.run(),
         Cmd::Verify(cmd) => cmd.run(),
+        Cmd::Revoke(cmd) => cmd.run(),
+        Cmd::CheckRevocation(cmd) => cmd.run(),
+        Cmd::Certify(cmd) => cmd.run(),
+        Cmd::CheckCert(cmd) => cmd.run(),
         Cmd::Digest(cmd) => cmd.run(),
         Cmd::Complete(cmd) => cmd.run(),
+        Cmd::KeyStore(cmd) => cmd.run(),
+        #[cfg(unix)]
+        Cmd::Agent(cmd) => cmd.run(),
+        Cmd::Vectors(cmd) => cmd.run(),
+        Cmd::Bench(cmd) => cmd.run(),
     } {
         e.print();
         process::exit(-1);
@@ -35,6 +80,39 @@ fn main() {
 struct Opts {
     #[clap(subcommand)]
     cmd: Cmd,
+
+    /// Write each output file to a temporary file which is synced and renamed into place once it's
+    /// complete, so an interrupted command never leaves a partial file behind.
+    #[arg(long, global = true)]
+    atomic: bool,
+
+    /// Log what veil is doing to stderr: -v for each operation, -vv for how long each step of it
+    /// takes, and -vvv for every pass of passphrase hashing. `RUST_LOG` overrides this.
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Don't print warnings.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+}
+
+/// Installs a `tracing` subscriber which logs the library's spans to stderr at the level given by
+/// `-v` or by `RUST_LOG`, in color unless stderr isn't a terminal or `NO_COLOR` is set.
+fn init_tracing(verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::OFF,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let filter = EnvFilter::builder().with_default_directive(level.into()).from_env_lossy();
+    let ansi = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(ansi)
+        .with_writer(io::stderr)
+        .init();
 }
 
 trait Runnable {
@@ -45,356 +123,2991 @@ trait Runnable {
 enum Cmd {
     PrivateKey(PrivateKeyArgs),
     PublicKey(PublicKeyArgs),
+    #[command(name = "keystore")]
+    KeyStore(KeyStoreArgs),
+    #[cfg(unix)]
+    Agent(AgentArgs),
     Encrypt(EncryptArgs),
     Decrypt(DecryptArgs),
+    Inspect(InspectArgs),
+    #[command(name = "pencrypt")]
+    PEncrypt(PEncryptArgs),
+    #[command(name = "pdecrypt")]
+    PDecrypt(PDecryptArgs),
     Sign(SignArgs),
     Verify(VerifyArgs),
+    Revoke(RevokeArgs),
+    CheckRevocation(CheckRevocationArgs),
+    Certify(CertifyArgs),
+    CheckCert(CheckCertArgs),
     Digest(DigestArgs),
     Complete(CompleteArgs),
+    Vectors(VectorsArgs),
+    Bench(BenchArgs),
 }
 
 /// Generate a new private key.
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct PrivateKeyArgs {
+    #[command(subcommand)]
+    cmd: Option<PrivateKeyCmd>,
+
     /// The path to the encrypted private key file or '-' for stdout.
-    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
-    output: PathBuf,
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH", required = true)]
+    output: Option<PathBuf>,
 
-    /// The time cost for encryption (in 2^t iterations).
-    #[arg(long, default_value = "8")]
-    time_cost: u8,
+    /// Use the passphrase even if it is easy to guess.
+    #[arg(long)]
+    force: bool,
 
-    /// The memory cost for encryption (in 2^m KiB).
-    #[arg(long, default_value = "8")]
-    memory_cost: u8,
+    /// Derive the private key from the 64 bytes in the given file or '-' for stdin instead of
+    /// generating a random one.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    from_seed_file: Option<PathBuf>,
+
+    /// Derive the private key from the unencrypted OpenSSH Ed25519 private key in the given file
+    /// instead of generating a random one.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH", conflicts_with = "from_seed_file")]
+    from_ssh_key: Option<PathBuf>,
+
+    /// Write a guard next to the private key file which makes each passphrase guess after a wrong
+    /// one twice as slow as the one before it.
+    #[arg(long)]
+    hardened: bool,
 
     #[command(flatten)]
-    passphrase_input: PassphraseInput,
+    key_output: KeyOutput,
 }
 
 impl Runnable for PrivateKeyArgs {
     fn run(self) -> Result<(), CliError> {
-        let output = open_output(&self.output, true)?;
-        let passphrase = self.passphrase_input.read_passphrase()?;
-        let private_key = PrivateKey::random(OsRng);
-        private_key
-            .store(output, OsRng, &passphrase, self.time_cost, self.memory_cost)
-            .map_err(|e| CliError::WriteIo(e, self.output))?;
-        Ok(())
+        match self.cmd {
+            Some(PrivateKeyCmd::ExportWords(cmd)) => cmd.run(),
+            Some(PrivateKeyCmd::Recover(cmd)) => cmd.run(),
+            Some(PrivateKeyCmd::Passwd(cmd)) => cmd.run(),
+            Some(PrivateKeyCmd::Ceremony(cmd)) => cmd.run(),
+            None => {
+                let output = self.output.expect("output should be required");
+                let private_key = match (&self.from_seed_file, &self.from_ssh_key) {
+                    (Some(path), _) => {
+                        let mut seed = Vec::new();
+                        open_input(path)?
+                            .read_to_end(&mut seed)
+                            .map_err(|e| CliError::ReadIo(e, path.clone()))?;
+                        let seed = seed.try_into().map_err(|_| CliError::InvalidSeed)?;
+                        PrivateKey::from_seed(&seed)
+                    }
+                    (None, Some(path)) => {
+                        let key = fs::read_to_string(path)
+                            .map_err(|e| CliError::ReadIo(e, path.clone()))?;
+                        ed25519::from_openssh(&key)
+                            .map_err(|e| CliError::InvalidSshKey(path.clone(), e))?
+                    }
+                    (None, None) => PrivateKey::random(OsRng),
+                };
+                if self.hardened {
+                    return self.key_output.store_hardened(&private_key, output, self.force);
+                }
+                self.key_output.store(&private_key, output, self.force)
+            }
+        }
     }
 }
 
-/// Derive a public key from a private key.
+#[derive(Debug, Subcommand)]
+enum PrivateKeyCmd {
+    ExportWords(ExportWordsArgs),
+    Recover(RecoverArgs),
+    Passwd(PasswdArgs),
+    Ceremony(CeremonyArgs),
+}
+
+/// Export a private key as a paper key of 48 words.
 #[derive(Debug, Parser)]
-struct PublicKeyArgs {
+struct ExportWordsArgs {
     #[command(flatten)]
     private_key: PrivateKeyInput,
 
-    /// The path to the public key file or '-' for stdout.
+    /// The path to the paper key file or '-' for stdout.
     #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
     output: PathBuf,
 }
 
-impl Runnable for PublicKeyArgs {
+impl Runnable for ExportWordsArgs {
     fn run(self) -> Result<(), CliError> {
         let mut output = open_output(&self.output, false)?;
         let private_key = self.private_key.decrypt()?;
-        let public_key = private_key.public_key();
-        write!(output, "{public_key}").map_err(|e| CliError::WriteIo(e, self.output))
+        writeln!(output, "{}", private_key.export_paper_key())
+            .map_err(|e| CliError::WriteIo(e, self.output))?;
+        output.commit()
     }
 }
 
-/// Encrypt a message for a set of receivers.
+/// Recover a private key from a paper key.
 #[derive(Debug, Parser)]
-struct EncryptArgs {
-    #[command(flatten)]
-    private_key: PrivateKeyInput,
-
-    /// The path to the input file or '-' for stdin.
+struct RecoverArgs {
+    /// The path to the paper key file or '-' for stdin.
     #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
     input: PathBuf,
 
-    /// The path to the output file or '-' for stdout.
+    /// The path to the encrypted private key file or '-' for stdout.
     #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
     output: PathBuf,
 
-    /// The receivers' public keys.
-    #[arg(
-        short = 'r',
-        long = "receiver",
-        value_name = "KEY",
-        num_args(1..),
-        required = true,
-        action(ArgAction::Append),
-    )]
-    receivers: Vec<PublicKey>,
-
-    /// Add fake receivers.
-    #[arg(long, value_name = "COUNT")]
-    fakes: Option<usize>,
+    /// Use the passphrase even if it is easy to guess.
+    #[arg(long)]
+    force: bool,
 
-    /// Add random bytes of padding.
-    #[arg(long, value_name = "BYTES")]
-    padding: Option<usize>,
+    #[command(flatten)]
+    key_output: KeyOutput,
 }
 
-impl Runnable for EncryptArgs {
+impl Runnable for RecoverArgs {
     fn run(self) -> Result<(), CliError> {
-        let input = open_input(&self.input)?;
-        let output = open_output(&self.output, true)?;
-        let private_key = self.private_key.decrypt()?;
-        private_key
-            .encrypt(OsRng, input, output, &self.receivers, self.fakes, self.padding)
-            .map_err(|e| match e {
-                veil::EncryptError::ReadIo(e) => CliError::ReadIo(e, self.input),
-                veil::EncryptError::WriteIo(e) => CliError::WriteIo(e, self.output),
-            })?;
-        Ok(())
+        let mut words = String::new();
+        open_input(&self.input)?
+            .read_to_string(&mut words)
+            .map_err(|e| CliError::ReadIo(e, self.input))?;
+        let private_key =
+            PrivateKey::import_paper_key(&words).map_err(CliError::InvalidPaperKey)?;
+        self.key_output.store(&private_key, self.output, self.force)
     }
 }
 
-/// Decrypt and verify a message.
+/// Change the passphrase of a private key.
 #[derive(Debug, Parser)]
-struct DecryptArgs {
+struct PasswdArgs {
+    /// The path of the encrypted private key.
+    #[arg(short = 'k', long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    private_key: PathBuf,
+
     #[command(flatten)]
-    private_key: PrivateKeyInput,
+    passphrase_input: PassphraseInput,
 
-    /// The path to the input file or '-' for stdin.
+    /// The path to the re-encrypted private key file, or '-' for stdout. Defaults to overwriting
+    /// the private key file.
     #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
-    input: PathBuf,
+    output: Option<PathBuf>,
 
-    /// The path to the output file or '-' for stdout.
-    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
-    output: PathBuf,
+    /// The key derivation function for encryption.
+    #[arg(long, value_enum, default_value_t = KdfArg::Balloon)]
+    kdf: KdfArg,
 
-    /// The sender's public key.
-    #[arg(short, long, value_name = "KEY")]
-    sender: PublicKey,
-}
+    /// The time cost for encryption (in 2^t iterations, or t passes with Argon2id).
+    #[arg(long, default_value = "8")]
+    time_cost: u8,
 
-impl Runnable for DecryptArgs {
-    fn run(self) -> Result<(), CliError> {
-        let input = open_input(&self.input)?;
-        let output = open_output(&self.output, true)?;
-        let private_key = self.private_key.decrypt()?;
-        private_key.decrypt(input, output, &self.sender).map_err(|e| match e {
-            DecryptError::InvalidCiphertext => CliError::InvalidCiphertext,
-            DecryptError::ReadIo(e) => CliError::ReadIo(e, self.input),
-            DecryptError::WriteIo(e) => CliError::WriteIo(e, self.input),
-        })?;
-        Ok(())
-    }
-}
+    /// The memory cost for encryption (in 2^m KiB).
+    #[arg(long, default_value = "8")]
+    memory_cost: u8,
 
-/// Sign a message.
-#[derive(Debug, Parser)]
-struct SignArgs {
-    #[command(flatten)]
-    private_key: PrivateKeyInput,
+    /// The number of threads to use for encryption.
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u8).range(1..))]
+    parallelism: u8,
 
-    /// The path to the message file or '-' for stdin.
-    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
-    input: PathBuf,
+    /// Read the new passphrase from the given file descriptor.
+    #[arg(long, conflicts_with = "new_passphrase_env")]
+    #[cfg(unix)]
+    new_passphrase_fd: Option<std::os::unix::prelude::RawFd>,
 
-    /// The path to the signature file or '-' for stdout.
-    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
-    output: PathBuf,
+    /// Read the new passphrase from the given environment variable, which is then removed from the
+    /// environment.
+    #[arg(long, value_name = "VAR")]
+    new_passphrase_env: Option<String>,
+
+    /// Use the new passphrase even if it is easy to guess.
+    #[arg(long)]
+    force: bool,
 }
 
-impl Runnable for SignArgs {
+impl Runnable for PasswdArgs {
     fn run(self) -> Result<(), CliError> {
-        let input = open_input(&self.input)?;
-        let mut output = open_output(&self.output, false)?;
-        let private_key = self.private_key.decrypt()?;
-        let sig = private_key.sign(OsRng, input).map_err(|e| CliError::ReadIo(e, self.input))?;
-        write!(output, "{sig}").map_err(|e| CliError::WriteIo(e, self.output))?;
-        Ok(())
+        let path = &self.private_key;
+        let ciphertext = fs::read(path).map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+        let old_passphrase = self.passphrase_input.read_passphrase()?;
+        let (_, guard) = unlock_private_key(path, &ciphertext, &old_passphrase)?;
+        let new_passphrase = read_new_passphrase(
+            self.new_passphrase_fd,
+            self.new_passphrase_env.as_deref(),
+            "Enter new passphrase: ",
+            self.force,
+        )?;
+
+        // Re-encrypt the private key before opening the output, which may be the same file.
+        let ciphertext = PrivateKey::change_passphrase(
+            OsRng,
+            &ciphertext,
+            &old_passphrase,
+            &new_passphrase,
+            self.kdf.params(self.time_cost, self.memory_cost, self.parallelism),
+        )
+        .map_err(CliError::BadPassphrase)?;
+
+        // Replace the private key atomically, so a failed write can't destroy it.
+        let output = self.output.unwrap_or_else(|| path.to_path_buf());
+        let mut file = if output == *path {
+            open_output_atomic(&output, true)?
+        } else {
+            open_output(&output, true)?
+        };
+        file.write_all(&ciphertext).map_err(|e| CliError::WriteIo(e, output.clone()))?;
+        file.commit()?;
+
+        // Guard the re-encrypted private key as the original was guarded.
+        match guard {
+            Some(guard) if output.as_os_str() != "-" => store_guard(&guard, &output, &ciphertext),
+            _ => Ok(()),
+        }
     }
 }
 
-/// Verify a signature.
+/// Generate a root private key in a guided key ceremony which writes a signed transcript.
 #[derive(Debug, Parser)]
-struct VerifyArgs {
-    /// The signer's public key.
-    #[arg(long, value_name = "KEY")]
-    signer: PublicKey,
+struct CeremonyArgs {
+    /// The path to the encrypted private key file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    output: PathBuf,
 
-    /// The signature of the message.
-    #[arg(long, value_name = "SIG")]
-    signature: Signature,
+    /// The path to the transcript of the ceremony, signed by the new private key.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    transcript: PathBuf,
 
-    /// The path to the message file or '-' for stdin.
-    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
-    input: PathBuf,
-}
+    /// The number of dice rolls to gather as extra entropy.
+    #[arg(long, default_value = "50", value_name = "N")]
+    dice_rolls: usize,
 
-impl Runnable for VerifyArgs {
-    fn run(self) -> Result<(), CliError> {
-        let input = open_input(&self.input)?;
-        self.signer.verify(input, &self.signature).map_err(|e| match e {
-            veil::VerifyError::InvalidSignature => CliError::InvalidSignature,
-            veil::VerifyError::ReadIo(e) => CliError::ReadIo(e, self.input),
-        })?;
-        Ok(())
-    }
-}
+    /// The number of keystrokes whose timings to gather as extra entropy.
+    #[arg(long, default_value = "64", value_name = "N")]
+    keystrokes: usize,
 
-/// Calculate a message digest.
-#[derive(Debug, Parser)]
-struct DigestArgs {
-    /// Associated metadata to be included in the digest.
-    #[arg(short, long)]
-    metadata: Vec<String>,
+    /// Split the private key into the given number of shares.
+    #[arg(long, value_name = "N", requires = "threshold", requires = "shares_dir")]
+    shares: Option<u16>,
 
-    /// Compare the computed digest to a given digest.
-    #[arg(long, value_name = "DIGEST", group("out"))]
-    check: Option<Digest>,
+    /// The number of shares needed to decrypt messages for the private key.
+    #[arg(long, value_name = "T", requires = "shares")]
+    threshold: Option<u16>,
 
-    /// The path to the message file or '-' for stdin.
-    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
-    input: PathBuf,
+    /// The directory to write the shares to.
+    #[arg(long, value_hint = ValueHint::DirPath, value_name = "DIR", requires = "shares")]
+    shares_dir: Option<PathBuf>,
 
-    /// The path to the digest file or '-' for stdout.
-    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH", group("out"))]
-    output: PathBuf,
+    /// Use the passphrase even if it is easy to guess.
+    #[arg(long)]
+    force: bool,
+
+    #[command(flatten)]
+    key_output: KeyOutput,
 }
 
-impl Runnable for DigestArgs {
+impl Runnable for CeremonyArgs {
     fn run(self) -> Result<(), CliError> {
-        let input = open_input(&self.input)?;
-        let digest =
-            Digest::new(&self.metadata, input).map_err(|e| CliError::ReadIo(e, self.input))?;
-        if let Some(check) = self.check {
-            if check != digest {
-                return Err(CliError::DigestMismatch);
+        // Check the shares before the operator does anything.
+        let split = self.shares.zip(self.threshold);
+        if let Some((shares, threshold)) = split {
+            if threshold == 0 || threshold > shares {
+                return Err(CliError::InvalidThreshold);
             }
-        } else {
-            write!(open_output(&self.output, false)?, "{digest}").map_err(CliError::TermIo)?;
         }
-        Ok(())
+
+        let started = unix_time();
+        let mut ceremony = Ceremony::new();
+        if self.dice_rolls > 0 {
+            eprintln!("Roll a six-sided die {} times and enter the results:", self.dice_rolls);
+            ceremony.gather_dice(self.dice_rolls)?;
+        }
+        if self.keystrokes > 0 {
+            eprintln!("Press {} random keys:", self.keystrokes);
+            ceremony.gather_keystrokes(self.keystrokes)?;
+        }
+        let private_key = ceremony.generate();
+
+        // Show the fingerprint at the default length of `veil fingerprint`.
+        let public_key = private_key.public_key();
+        let fingerprint = public_key.fingerprint(11);
+        eprintln!("public key: {public_key}");
+        eprintln!("fingerprint: {fingerprint}");
+        eprintln!("fingerprint words: {}", fingerprint.to_words());
+        eprintln!("Transcribe the fingerprint, then press Enter.");
+        ceremony.read_line()?;
+
+        self.key_output.store(&private_key, self.output, self.force)?;
+
+        let mut transcript = format!(
+            "veil key ceremony\n\
+             veil version: {}\n\
+             started: {started}\n\
+             dice rolls: {}\n\
+             keystrokes: {}\n\
+             public key: {public_key}\n\
+             fingerprint: {fingerprint}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.dice_rolls,
+            self.keystrokes,
+        );
+        if let (Some((shares, threshold)), Some(dir)) = (split, &self.shares_dir) {
+            fs::create_dir_all(dir).map_err(|e| CliError::WriteIo(e, dir.clone()))?;
+            transcript.push_str(&format!("shares: {threshold} of {shares}\n"));
+            for share in threshold::split(OsRng, &private_key, threshold, shares)
+                .expect("threshold should be valid")
+            {
+                let path = dir.join(format!("share-{}", share.index()));
+                write_file(&path, &share.encode()).map_err(|e| CliError::WriteIo(e, path))?;
+                transcript.push_str(&format!(
+                    "share {}: {}\n",
+                    share.index(),
+                    share.verification_key()
+                ));
+            }
+        }
+        transcript.push_str(&format!("completed: {}\n", unix_time()));
+
+        let mut output = open_output(&self.transcript, true)?;
+        private_key
+            .sign_attached(OsRng, transcript.as_bytes(), &mut output)
+            .map_err(|e| CliError::WriteIo(e, self.transcript))?;
+        output.commit()
     }
 }
 
-/// Generate shell completion scripts.
-#[derive(Debug, Parser)]
-#[command(hide(true))]
-struct CompleteArgs {
-    /// The type of shell completion script to generate: bash, elvish, fish, powershell, or zsh.
-    #[arg(long)]
-    shell: Shell,
-
-    /// Output directory for shell completion scripts.
-    #[arg(short, long, value_hint = ValueHint::DirPath)]
-    output: PathBuf,
+/// The operator's side of a key ceremony, read key by key from the terminal or, if stdin isn't one,
+/// line by line from stdin.
+struct Ceremony {
+    term: Option<Term>,
+    stdin: io::StdinLock<'static>,
+    entropy: Protocol,
+    start: Instant,
 }
 
-impl Runnable for CompleteArgs {
-    fn run(self) -> Result<(), CliError> {
-        let mut app = Opts::command();
-        generate_to(self.shell, &mut app, "veil", &self.output)
-            .map_err(|e| CliError::WriteIo(e, self.output))?;
+impl Ceremony {
+    fn new() -> Ceremony {
+        let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+        let mut entropy = Protocol::new("veil.ceremony");
+        entropy.mix("random", &OsRng.gen::<[u8; 64]>());
+        Ceremony {
+            term: interactive.then(Term::stderr),
+            stdin: io::stdin().lock(),
+            entropy,
+            start: Instant::now(),
+        }
+    }
+
+    /// Mixes an event and the time at which it happened into the entropy pool.
+    fn mix(&mut self, label: &str, event: &[u8]) {
+        self.entropy.mix("time", &self.start.elapsed().as_nanos().to_le_bytes());
+        self.entropy.mix(label, event);
+    }
+
+    fn read_line(&mut self) -> Result<String, CliError> {
+        if let Some(term) = &self.term {
+            return term.read_line().map_err(CliError::TermIo);
+        }
+        let mut line = String::new();
+        if self.stdin.read_line(&mut line).map_err(|e| CliError::ReadIo(e, "-".into()))? == 0 {
+            return Err(CliError::IncompleteCeremony);
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn gather_dice(&mut self, rolls: usize) -> Result<(), CliError> {
+        let mut gathered = 0;
+        while gathered < rolls {
+            let line = self.read_line()?;
+            let line = line.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+            if !line.chars().all(|c| ('1'..='6').contains(&c)) {
+                eprintln!("Each roll must be a number from 1 to 6. Enter those rolls again:");
+                continue;
+            }
+            self.mix("dice", line.as_bytes());
+            gathered += line.len();
+        }
+        Ok(())
+    }
+
+    fn gather_keystrokes(&mut self, keystrokes: usize) -> Result<(), CliError> {
+        let mut gathered = 0;
+        while gathered < keystrokes {
+            let key =
+                self.term.as_ref().map(Term::read_char).transpose().map_err(CliError::TermIo)?;
+            if let Some(key) = key {
+                self.mix("keystroke", key.encode_utf8(&mut [0; 4]).as_bytes());
+                gathered += 1;
+            } else {
+                // Without a terminal, only the time at which each line arrives is known.
+                let line = self.read_line()?;
+                self.mix("keystrokes", line.as_bytes());
+                gathered += line.chars().count().max(1);
+            }
+        }
         Ok(())
     }
+
+    /// Derives a private key from the entropy pool.
+    fn generate(&mut self) -> PrivateKey {
+        self.mix("done", &[]);
+        let seed = Zeroizing::new(self.entropy.derive_array::<64>("seed"));
+        PrivateKey::from_seed(&seed)
+    }
 }
 
 #[derive(Debug, Parser)]
-struct PrivateKeyInput {
-    /// The path of the encrypted private key.
-    #[arg(short = 'k', long, value_hint = ValueHint::FilePath, value_name = "PATH")]
-    private_key: PathBuf,
+struct KeyOutput {
+    /// The key derivation function for encryption.
+    #[arg(long, value_enum, default_value_t = KdfArg::Balloon)]
+    kdf: KdfArg,
+
+    /// The time cost for encryption (in 2^t iterations, or t passes with Argon2id).
+    #[arg(long, default_value = "8")]
+    time_cost: u8,
+
+    /// The memory cost for encryption (in 2^m KiB).
+    #[arg(long, default_value = "8")]
+    memory_cost: u8,
+
+    /// The number of threads to use for encryption.
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u8).range(1..))]
+    parallelism: u8,
 
     #[command(flatten)]
     passphrase_input: PassphraseInput,
 }
 
-impl PrivateKeyInput {
-    fn decrypt(&self) -> Result<PrivateKey, CliError> {
-        let passphrase = self.passphrase_input.read_passphrase()?;
-        let ciphertext = File::open(&self.private_key)
-            .map_err(|e| CliError::ReadIo(e, self.private_key.to_path_buf()))?;
-        PrivateKey::load(ciphertext, &passphrase).map_err(CliError::BadPassphrase)
+impl KeyOutput {
+    fn kdf(&self) -> Kdf {
+        self.kdf.params(self.time_cost, self.memory_cost, self.parallelism)
     }
-}
 
-#[derive(Debug, Parser)]
-struct PassphraseInput {
-    /// Read the passphrase from the given file descriptor.
-    #[arg(long)]
-    #[cfg(unix)]
-    passphrase_fd: Option<std::os::unix::prelude::RawFd>,
-}
+    fn store(&self, private_key: &PrivateKey, path: PathBuf, force: bool) -> Result<(), CliError> {
+        let mut output = open_output(&path, true)?;
+        let passphrase = self.passphrase_input.read_new_passphrase(force)?;
+        private_key
+            .store_with(&mut output, OsRng, &passphrase, self.kdf())
+            .map_err(|e| CliError::WriteIo(e, path))?;
+        output.commit()
+    }
 
-impl PassphraseInput {
-    fn read_passphrase(&self) -> Result<Vec<u8>, CliError> {
-        if cfg!(unix) {
-            if let Some(fd) = self.passphrase_fd {
-                return Self::read_from_fd(fd);
-            }
+    fn store_hardened(
+        &self,
+        private_key: &PrivateKey,
+        path: PathBuf,
+        force: bool,
+    ) -> Result<(), CliError> {
+        if path.as_os_str() == "-" {
+            return Err(CliError::StdoutGuard);
         }
+        let passphrase = self.passphrase_input.read_new_passphrase(force)?;
+        let mut ciphertext = Vec::new();
+        private_key
+            .store_with(&mut ciphertext, OsRng, &passphrase, self.kdf())
+            .map_err(|e| CliError::WriteIo(e, path.clone()))?;
+        write_file(&path, &ciphertext).map_err(|e| CliError::WriteIo(e, path.clone()))?;
+        let guard = Guard::new(self.time_cost, self.memory_cost, self.parallelism);
+        store_guard(&guard, &path, &ciphertext)
+    }
 
-        self.prompt_for_passphrase()
+    fn store_keystore(
+        &self,
+        keystore: &KeyStore,
+        passphrase: &[u8],
+        path: PathBuf,
+    ) -> Result<(), CliError> {
+        // Replace the key store atomically, so a failed write can't destroy it.
+        let mut output = open_output_atomic(&path, true)?;
+        keystore
+            .store_with(&mut output, OsRng, passphrase, self.kdf())
+            .map_err(|e| CliError::WriteIo(e, path))?;
+        output.commit()
     }
+}
 
-    #[cfg(unix)]
-    fn read_from_fd(fd: i32) -> Result<Vec<u8>, CliError> {
-        use std::os::unix::prelude::FromRawFd;
+/// A key derivation function for encrypting private keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum KdfArg {
+    /// Veil's balloon hashing construction.
+    Balloon,
 
-        let mut out = Vec::new();
-        unsafe { File::from_raw_fd(fd) }
-            .read_to_end(&mut out)
-            .map_err(|e| CliError::FdIo(e, fd))?;
-        Ok(out)
-    }
+    /// Argon2id, as specified in RFC 9106.
+    Argon2id,
+}
 
-    fn prompt_for_passphrase(&self) -> Result<Vec<u8>, CliError> {
-        let mut term = Term::stderr();
-        let _ = term.write(b"Enter passphrase: ").map_err(CliError::TermIo)?;
-        let passphrase = term.read_secure_line().map_err(CliError::TermIo)?;
-        if passphrase.is_empty() {
-            return Err(CliError::EmptyPassphrase);
+impl KdfArg {
+    fn params(self, time_cost: u8, memory_cost: u8, parallelism: u8) -> Kdf {
+        match self {
+            KdfArg::Balloon => Kdf::Balloon { time_cost, memory_cost, parallelism },
+            KdfArg::Argon2id => Kdf::Argon2id {
+                time_cost: time_cost.into(),
+                memory_cost: 1u32.checked_shl(memory_cost.into()).unwrap_or(u32::MAX),
+                parallelism: parallelism.into(),
+            },
         }
-        Ok(passphrase.as_bytes().to_vec())
     }
 }
 
-fn open_input(path: &Path) -> Result<Box<dyn Read>, CliError> {
-    if path.as_os_str() == "-" {
-        if io::stdin().is_terminal() {
-            return Err(CliError::StdinTty);
-        }
-        Ok(Box::new(io::stdin().lock()))
-    } else {
-        let f = File::open(path).map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
-        Ok(Box::new(f))
-    }
+/// Manage a key store of named private keys.
+#[derive(Debug, Parser)]
+struct KeyStoreArgs {
+    #[command(subcommand)]
+    cmd: KeyStoreCmd,
 }
 
-fn open_output(path: &Path, binary: bool) -> Result<Box<dyn Write>, CliError> {
-    if path.as_os_str() == "-" {
-        if binary && io::stdout().is_terminal() {
-            return Err(CliError::StdoutTty);
+impl Runnable for KeyStoreArgs {
+    fn run(self) -> Result<(), CliError> {
+        match self.cmd {
+            KeyStoreCmd::Create(cmd) => cmd.run(),
+            KeyStoreCmd::List(cmd) => cmd.run(),
+            KeyStoreCmd::Generate(cmd) => cmd.run(),
+            KeyStoreCmd::Rename(cmd) => cmd.run(),
+            KeyStoreCmd::Delete(cmd) => cmd.run(),
         }
-        Ok(Box::new(io::stdout().lock()))
-    } else {
-        let f = File::create(path).map_err(|e| CliError::WriteIo(e, path.to_path_buf()))?;
-        Ok(Box::new(f))
     }
 }
 
-#[derive(Debug, Error)]
-enum CliError {
+#[derive(Debug, Subcommand)]
+enum KeyStoreCmd {
+    Create(KeyStoreCreateArgs),
+    List(KeyStoreListArgs),
+    Generate(KeyStoreGenerateArgs),
+    Rename(KeyStoreRenameArgs),
+    Delete(KeyStoreDeleteArgs),
+}
+
+/// Create an empty key store.
+#[derive(Debug, Parser)]
+struct KeyStoreCreateArgs {
+    /// The path of the key store.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    keystore: PathBuf,
+
+    /// Use the passphrase even if it is easy to guess.
+    #[arg(long)]
+    force: bool,
+
+    #[command(flatten)]
+    key_output: KeyOutput,
+}
+
+impl Runnable for KeyStoreCreateArgs {
+    fn run(self) -> Result<(), CliError> {
+        let passphrase = self.key_output.passphrase_input.read_new_passphrase(self.force)?;
+        self.key_output.store_keystore(&KeyStore::new(), &passphrase, self.keystore)
+    }
+}
+
+/// List the names and public keys of the private keys in a key store.
+#[derive(Debug, Parser)]
+struct KeyStoreListArgs {
+    /// The path of the key store.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    keystore: PathBuf,
+
+    #[command(flatten)]
+    passphrase_input: PassphraseInput,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+}
+
+impl Runnable for KeyStoreListArgs {
+    fn run(self) -> Result<(), CliError> {
+        let passphrase = self.passphrase_input.read_passphrase()?;
+        let keystore = load_keystore(&self.keystore, &passphrase)?;
+        let mut output = open_output(&self.output, false)?;
+        for (name, private_key) in keystore.iter() {
+            writeln!(output, "{name}\t{}", private_key.public_key())
+                .map_err(|e| CliError::WriteIo(e, self.output.clone()))?;
+        }
+        output.commit()
+    }
+}
+
+/// Generate a new private key in a key store.
+#[derive(Debug, Parser)]
+struct KeyStoreGenerateArgs {
+    /// The path of the key store.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    keystore: PathBuf,
+
+    /// The name of the new private key.
+    name: String,
+
+    #[command(flatten)]
+    key_output: KeyOutput,
+}
+
+impl Runnable for KeyStoreGenerateArgs {
+    fn run(self) -> Result<(), CliError> {
+        let passphrase = self.key_output.passphrase_input.read_passphrase()?;
+        let mut keystore = load_keystore(&self.keystore, &passphrase)?;
+        keystore.insert(&self.name, PrivateKey::random(OsRng)).map_err(CliError::KeyStore)?;
+        self.key_output.store_keystore(&keystore, &passphrase, self.keystore)
+    }
+}
+
+/// Rename a private key in a key store.
+#[derive(Debug, Parser)]
+struct KeyStoreRenameArgs {
+    /// The path of the key store.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    keystore: PathBuf,
+
+    /// The current name of the private key.
+    from: String,
+
+    /// The new name of the private key.
+    to: String,
+
+    #[command(flatten)]
+    key_output: KeyOutput,
+}
+
+impl Runnable for KeyStoreRenameArgs {
+    fn run(self) -> Result<(), CliError> {
+        let passphrase = self.key_output.passphrase_input.read_passphrase()?;
+        let mut keystore = load_keystore(&self.keystore, &passphrase)?;
+        keystore.rename(&self.from, &self.to).map_err(CliError::KeyStore)?;
+        self.key_output.store_keystore(&keystore, &passphrase, self.keystore)
+    }
+}
+
+/// Delete a private key from a key store.
+#[derive(Debug, Parser)]
+struct KeyStoreDeleteArgs {
+    /// The path of the key store.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    keystore: PathBuf,
+
+    /// The name of the private key.
+    name: String,
+
+    #[command(flatten)]
+    key_output: KeyOutput,
+}
+
+impl Runnable for KeyStoreDeleteArgs {
+    fn run(self) -> Result<(), CliError> {
+        let passphrase = self.key_output.passphrase_input.read_passphrase()?;
+        let mut keystore = load_keystore(&self.keystore, &passphrase)?;
+        keystore.remove(&self.name).map_err(CliError::KeyStore)?;
+        self.key_output.store_keystore(&keystore, &passphrase, self.keystore)
+    }
+}
+
+fn load_keystore(path: &Path, passphrase: &[u8]) -> Result<KeyStore, CliError> {
+    let ciphertext = File::open(path).map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+    KeyStore::load(ciphertext, passphrase).map_err(CliError::BadPassphrase)
+}
+
+/// Keep an unlocked private key available to other commands.
+#[cfg(unix)]
+#[derive(Debug, Parser)]
+struct AgentArgs {
+    #[command(subcommand)]
+    cmd: AgentCmd,
+}
+
+#[cfg(unix)]
+impl Runnable for AgentArgs {
+    fn run(self) -> Result<(), CliError> {
+        match self.cmd {
+            AgentCmd::Start(cmd) => cmd.run(),
+            AgentCmd::Stop(cmd) => cmd.run(),
+            AgentCmd::Status(cmd) => cmd.run(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[derive(Debug, Subcommand)]
+enum AgentCmd {
+    Start(AgentStartArgs),
+    Stop(AgentStopArgs),
+    Status(AgentStatusArgs),
+}
+
+/// Unlock a private key and use it on behalf of other commands over a Unix socket until stopped.
+///
+/// Anyone who can connect to the socket can use the private key, so the socket should be in a
+/// directory only you can access.
+#[cfg(unix)]
+#[derive(Debug, Parser)]
+struct AgentStartArgs {
+    /// The path of the socket to create.
+    #[arg(value_hint = ValueHint::FilePath, value_name = "SOCKET")]
+    socket: PathBuf,
+
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// Stop the agent after the given number of seconds.
+    #[arg(long, value_name = "SECS")]
+    lifetime: Option<u64>,
+}
+
+#[cfg(unix)]
+impl Runnable for AgentStartArgs {
+    fn run(self) -> Result<(), CliError> {
+        use std::os::unix::net::UnixListener;
+
+        use rustix::{fs::Mode, process::umask};
+
+        let agent = self.private_key.decrypt()?.into_agent();
+
+        // Create the socket readable and writable only by the owner, so that no one else can
+        // connect to it before its permissions could be changed.
+        let mask = umask(Mode::from_raw_mode(0o177));
+        let listener = UnixListener::bind(&self.socket);
+        umask(mask);
+        let listener = listener.map_err(|e| CliError::Agent(e, self.socket.clone()))?;
+
+        if let Some(lifetime) = self.lifetime {
+            let socket = self.socket.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_secs(lifetime));
+                agent::stop(socket)
+            });
+        }
+
+        let result = agent.serve(&listener);
+        fs::remove_file(&self.socket).map_err(|e| CliError::Agent(e, self.socket.clone()))?;
+        result.map_err(|e| CliError::Agent(e, self.socket))
+    }
+}
+
+/// Stop a running agent.
+#[cfg(unix)]
+#[derive(Debug, Parser)]
+struct AgentStopArgs {
+    /// The path of the agent's socket.
+    #[arg(value_hint = ValueHint::FilePath, value_name = "SOCKET")]
+    socket: PathBuf,
+}
+
+#[cfg(unix)]
+impl Runnable for AgentStopArgs {
+    fn run(self) -> Result<(), CliError> {
+        agent::stop(&self.socket).map_err(|e| CliError::Agent(e, self.socket))
+    }
+}
+
+/// Print the public key of a running agent's private key.
+#[cfg(unix)]
+#[derive(Debug, Parser)]
+struct AgentStatusArgs {
+    /// The path of the agent's socket.
+    #[arg(value_hint = ValueHint::FilePath, value_name = "SOCKET")]
+    socket: PathBuf,
+}
+
+#[cfg(unix)]
+impl Runnable for AgentStatusArgs {
+    fn run(self) -> Result<(), CliError> {
+        let public_key =
+            agent::public_key(&self.socket).map_err(|e| CliError::Agent(e, self.socket))?;
+        println!("{public_key}");
+        Ok(())
+    }
+}
+
+/// Derive a public key from a private key.
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct PublicKeyArgs {
+    #[command(subcommand)]
+    cmd: Option<PublicKeyCmd>,
+
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// The path to the public key file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+
+    /// Export the public key as a bundle signed by the private key.
+    #[arg(long)]
+    signed: bool,
+
+    /// A label for the signed bundle (e.g. an email address).
+    #[arg(long, default_value = "", requires = "signed")]
+    label: String,
+
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Also write the public key as a QR code to the given PNG file, for scanning in person.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    qr: Option<PathBuf>,
+}
+
+impl Runnable for PublicKeyArgs {
+    fn run(self) -> Result<(), CliError> {
+        match self.cmd {
+            Some(PublicKeyCmd::Fingerprint(cmd)) => return cmd.run(),
+            Some(PublicKeyCmd::Import(cmd)) => return cmd.run(),
+            None => {}
+        }
+
+        let mut output = open_output(&self.output, false)?;
+        let (public_key, bundle) = match self.private_key.agent_public_key()? {
+            Some(_) if self.signed => return Err(CliError::AgentUnsupported),
+            Some(public_key) => (public_key, None),
+            None => {
+                let private_key = self.private_key.decrypt()?;
+                let bundle = self.signed.then(|| {
+                    private_key.export_signed(OsRng, &self.label, unix_time()).to_string()
+                });
+                (private_key.public_key(), bundle)
+            }
+        };
+        if let Some(path) = self.qr {
+            let uri = uri::encode(&public_key);
+            let mut qr = open_output(&path, true)?;
+            qr::write(uri.as_bytes(), &mut qr).map_err(|e| CliError::WriteIo(e, path))?;
+            qr.commit()?;
+        }
+        Report::PublicKey { public_key, bundle }
+            .write(self.format, &mut output)
+            .map_err(|e| CliError::WriteIo(e, self.output))?;
+        output.commit()
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum PublicKeyCmd {
+    Fingerprint(FingerprintArgs),
+    Import(ImportArgs),
+}
+
+/// Verify a signed public key bundle and print its public key and metadata.
+#[derive(Debug, Parser)]
+struct ImportArgs {
+    /// The path to the key bundle file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+}
+
+impl Runnable for ImportArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut bundle = String::new();
+        open_input(&self.input)?
+            .read_to_string(&mut bundle)
+            .map_err(|e| CliError::ReadIo(e, self.input))?;
+        let bundle = PublicKey::import_verified(bundle.trim()).map_err(CliError::InvalidBundle)?;
+        println!("public key: {}", bundle.public_key());
+        println!("label: {}", bundle.label());
+        println!("created: {}", bundle.created());
+        Ok(())
+    }
+}
+
+/// Print a short fingerprint of a public key for comparing it out-of-band.
+#[derive(Debug, Parser)]
+struct FingerprintArgs {
+    /// The public key to fingerprint.
+    public_key: PublicKey,
+
+    /// Format the fingerprint as words instead of hex digits.
+    #[arg(long)]
+    words: bool,
+
+    /// The length of the fingerprint in bytes.
+    #[arg(long, default_value = "11", value_parser = clap::value_parser!(u8).range(1..=32))]
+    length: u8,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+}
+
+impl Runnable for FingerprintArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut output = open_output(&self.output, false)?;
+        let fingerprint = self.public_key.fingerprint(self.length.into());
+        let fingerprint = if self.words { fingerprint.to_words() } else { fingerprint.to_string() };
+        writeln!(output, "{fingerprint}").map_err(|e| CliError::WriteIo(e, self.output))?;
+        output.commit()
+    }
+}
+
+/// Encrypt a message for a set of receivers.
+#[derive(Debug, Parser)]
+struct EncryptArgs {
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// The path to the input file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    output: PathBuf,
+
+    /// The receivers' public keys, as keys, `veil:pk:` URIs, or `qr://` paths to PNG QR codes.
+    #[arg(
+        short = 'r',
+        long = "receiver",
+        value_name = "KEY",
+        value_parser = parse_receiver,
+        num_args(1..),
+        required_unless_present_any = ["receivers_file", "to_self"],
+        action(ArgAction::Append),
+    )]
+    receivers: Vec<PublicKey>,
+
+    /// Encrypt the message for your own public key, in addition to any other receivers.
+    #[arg(long = "self")]
+    to_self: bool,
+
+    /// The path to a file of receivers' public keys, one per line with an optional comment.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    receivers_file: Option<PathBuf>,
+
+    /// Add fake receivers.
+    #[arg(long, value_name = "COUNT")]
+    fakes: Option<usize>,
+
+    /// Add a header for a decoy's public key, which the decoy can't decrypt.
+    #[arg(long = "decoy", value_name = "KEY", action(ArgAction::Append))]
+    decoys: Vec<PublicKey>,
+
+    /// Add random bytes of padding.
+    #[arg(long, value_name = "BYTES")]
+    padding: Option<usize>,
+
+    /// Add enough random padding to round the message's length up to a PADMÉ length.
+    #[arg(long, conflicts_with = "padding")]
+    auto_padding: bool,
+
+    /// Compress the plaintext with zstd at the given level before encrypting it.
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(i32).range(1..=22))]
+    compress: Option<i32>,
+
+    /// Encrypt the message in chunks which can be decrypted individually with `decrypt --offset`.
+    #[arg(long, conflicts_with = "compress")]
+    seekable: bool,
+
+    /// Index the headers, so each receiver of a message with many receivers can find theirs
+    /// quickly. Anyone with your public key can tell the message is indexed and count its headers.
+    #[arg(long)]
+    indexed: bool,
+
+    /// Also let anyone with a passphrase decrypt the message with `pdecrypt --sender`.
+    #[arg(long, conflicts_with = "indexed")]
+    with_passphrase: bool,
+
+    /// Read the message passphrase from the given file descriptor.
+    #[arg(long, requires = "with_passphrase", conflicts_with = "message_passphrase_env")]
+    #[cfg(unix)]
+    message_passphrase_fd: Option<std::os::unix::prelude::RawFd>,
+
+    /// Read the message passphrase from the given environment variable, which is then removed from
+    /// the environment.
+    #[arg(long, requires = "with_passphrase", value_name = "VAR")]
+    message_passphrase_env: Option<String>,
+
+    /// The time cost for the message passphrase (in 2^t iterations).
+    #[arg(long, requires = "with_passphrase", default_value = "8")]
+    message_time_cost: u8,
+
+    /// The memory cost for the message passphrase (in 2^m KiB).
+    #[arg(long, requires = "with_passphrase", default_value = "8")]
+    message_memory_cost: u8,
+
+    /// Encode the ciphertext as ASCII armor.
+    #[arg(short, long)]
+    armor: bool,
+
+    /// Store the input file's name, modification time, and permissions in the message.
+    #[arg(long)]
+    store_metadata: bool,
+
+    /// Derive all randomness from the given seed, producing identical ciphertexts for identical
+    /// inputs. Never use the same seed for different plaintexts.
+    #[arg(long, value_name = "SEED")]
+    seed: Option<String>,
+}
+
+impl Runnable for EncryptArgs {
+    fn run(mut self) -> Result<(), CliError> {
+        if let Some(path) = &self.receivers_file {
+            let mut contents = String::new();
+            open_input(path)?
+                .read_to_string(&mut contents)
+                .map_err(|e| CliError::ReadIo(e, path.clone()))?;
+            let recipients = recipients::parse(&contents)
+                .map_err(|e| CliError::InvalidRecipients(path.clone(), e))?;
+            self.receivers.extend(recipients.into_iter().map(|r| r.public_key));
+        }
+        let mut input = Input::open(&self.input)?;
+        let mut envelope_len = 0;
+        if self.store_metadata {
+            let metadata = file_metadata(&self.input)?;
+            envelope_len = metadata.encode().len() as u64;
+            input = Input::Stream(Box::new(metadata.wrap(input.into_reader())));
+        }
+        let padding = if self.auto_padding && self.compress.is_some() {
+            // Compressed messages are padded according to their compressed length.
+            Padding::Automatic { plaintext_len: 0 }
+        } else if self.auto_padding {
+            if self.input.as_os_str() == "-" {
+                return Err(CliError::StdinPadding);
+            }
+            let len = fs::metadata(&self.input)
+                .map_err(|e| CliError::ReadIo(e, self.input.clone()))?
+                .len();
+            Padding::Automatic { plaintext_len: len + envelope_len }
+        } else {
+            Padding::Fixed(self.padding.unwrap_or_default())
+        };
+        let mut file = open_output(&self.output, !self.armor)?;
+        let private_key = self.private_key.decrypt()?;
+        let message_passphrase = if self.with_passphrase {
+            Some(read_new_passphrase(
+                self.message_passphrase_fd,
+                self.message_passphrase_env.as_deref(),
+                "Enter message passphrase: ",
+                false,
+            )?)
+        } else {
+            None
+        };
+        if self.to_self {
+            self.receivers.push(private_key.public_key());
+        }
+        let mut output = if self.armor {
+            Output::Armored(ArmorWriter::new(&mut file))
+        } else {
+            Output::Binary(&mut file)
+        };
+        let mut options = EncryptOptions::new(&self.receivers)
+            .decoys(&self.decoys)
+            .fakes(self.fakes.unwrap_or_default())
+            .padding_policy(padding);
+        if let Some(level) = self.compress {
+            options = options.compress(level);
+        }
+        if self.seekable {
+            options = options.seekable();
+        }
+        if self.indexed {
+            options = options.indexed();
+        }
+        if let Some(passphrase) = &message_passphrase {
+            options =
+                options.passphrase(passphrase, self.message_time_cost, self.message_memory_cost, 1);
+        }
+        let res = if let Some(seed) = &self.seed {
+            let input = input.into_reader();
+            private_key.encrypt_deterministic(seed.as_bytes(), input, &mut output, &options)
+        } else if let Some(plaintext) = input.as_slice() {
+            private_key.encrypt_slice(OsRng, plaintext, &mut output, &options)
+        } else {
+            private_key.encrypt(OsRng, input.into_reader(), &mut output, &options)
+        }
+        .map_err(|e| match e {
+            veil::EncryptError::ReadIo(e) => CliError::ReadIo(e, self.input),
+            e @ (veil::EncryptError::TooManyReceivers
+            | veil::EncryptError::TooMuchPadding
+            | veil::EncryptError::MessageTooLong) => CliError::LimitExceeded(e),
+            veil::EncryptError::WriteIo(e) => CliError::WriteIo(e, self.output.clone()),
+            e => CliError::Veil(e.into()),
+        })
+        .and_then(|_| output.finish().map_err(|e| CliError::WriteIo(e, self.output)));
+
+        // Remove a partial ciphertext rather than leave one which looks like a whole message.
+        if let Err(e) = res {
+            file.discard();
+            return Err(e);
+        }
+        file.commit()
+    }
+}
+
+/// Decrypt and verify a message.
+#[derive(Debug, Parser)]
+struct DecryptArgs {
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// The path to the input file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    output: PathBuf,
+
+    /// The sender's public key.
+    #[arg(short, long, value_name = "KEY")]
+    sender: PublicKey,
+
+    /// Decode the ciphertext from ASCII armor.
+    #[arg(short, long)]
+    armor: bool,
+
+    /// Restore the file's modification time and permissions stored in the message. If the output
+    /// is a directory, the file is written to it with its original name.
+    #[arg(long)]
+    restore_metadata: bool,
+
+    /// Decrypt the plaintext of a seekable message starting at the given offset, without verifying
+    /// the sender's signature.
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["armor", "restore_metadata"])]
+    offset: Option<u64>,
+
+    /// Decrypt at most the given number of bytes of the plaintext of a seekable message, without
+    /// verifying the sender's signature.
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["armor", "restore_metadata"])]
+    length: Option<u64>,
+
+    /// Decrypt as much of a damaged seekable message as possible, writing zeros in place of the
+    /// chunks which can't be decrypted, without verifying the sender's signature.
+    #[arg(long, conflicts_with_all = ["armor", "restore_metadata", "offset", "length"])]
+    recover: bool,
+}
+
+impl Runnable for DecryptArgs {
+    fn run(self) -> Result<(), CliError> {
+        if self.offset.is_some() || self.length.is_some() {
+            return self.decrypt_range();
+        }
+        if self.recover {
+            return self.recover();
+        }
+        let mut input = Input::open(&self.input)?;
+        if self.armor {
+            input = Input::Stream(Box::new(ArmorReader::new(BufReader::new(input.into_reader()))));
+        }
+        if self.restore_metadata {
+            return self.restore_metadata(input.into_reader());
+        }
+        if self.output.as_os_str() == "-" {
+            let mut output = open_output(&self.output, true)?;
+            self.private_key.with_receiver(|receiver| {
+                match input.as_slice() {
+                    Some(ciphertext) => {
+                        backend::decrypt(&receiver, ciphertext, &mut output, &self.sender, &[])
+                    }
+                    None => backend::decrypt(
+                        &receiver,
+                        input.into_reader(),
+                        &mut output,
+                        &self.sender,
+                        &[],
+                    ),
+                }
+                .map_err(|e| self.decrypt_error(e))
+            })?;
+            return output.commit();
+        }
+
+        // Decrypt to a file, scrubbing and truncating it if the message is invalid.
+        let mut output = open_output(&self.output, true)?;
+        let input = input.into_reader();
+        let res = self.private_key.with_receiver(|receiver| {
+            backend::decrypt_scrubbed(&receiver, input, &mut output, &self.sender, &[])
+                .map_err(|e| self.decrypt_error(e))
+        });
+        if let Err(e) = res {
+            if let Some(file) = output.file() {
+                file.set_len(0).map_err(|e| CliError::WriteIo(e, self.output.clone()))?;
+            }
+            return Err(e);
+        }
+        output.commit()
+    }
+}
+
+impl DecryptArgs {
+    fn decrypt_error(&self, e: DecryptError) -> CliError {
+        match e {
+            DecryptError::InvalidCiphertext => CliError::InvalidCiphertext,
+            DecryptError::NotAReceiver | DecryptError::SequenceMismatch => CliError::NotAReceiver,
+            DecryptError::Corrupt => CliError::CorruptMessage,
+            DecryptError::Truncated => CliError::TruncatedMessage,
+            DecryptError::UnsupportedVersion(v) => CliError::UnsupportedVersion(v),
+            DecryptError::NotSeekable => CliError::NotSeekable,
+            DecryptError::HeadersTooLong => CliError::HeadersTooLong,
+            DecryptError::PlaintextTooLong => CliError::PlaintextTooLong,
+            DecryptError::ReadIo(e) => CliError::ReadIo(e, self.input.clone()),
+            DecryptError::WriteIo(e) => CliError::WriteIo(e, self.output.clone()),
+            e => CliError::Veil(e.into()),
+        }
+    }
+
+    fn decrypt_range(&self) -> Result<(), CliError> {
+        if self.input.as_os_str() == "-" {
+            return Err(CliError::StdinRange);
+        }
+
+        let input = File::open(&self.input).map_err(|e| CliError::ReadIo(e, self.input.clone()))?;
+        let mut output = open_output(&self.output, true)?;
+        let mut message = self.private_key.with_receiver(|receiver| {
+            seekable::open(&receiver, BufReader::new(input), &self.sender, &[])
+                .map_err(|e| self.decrypt_error(e))
+        })?;
+
+        // Decrypt the range in blocks, reading only the chunks it overlaps.
+        let mut offset = self.offset.unwrap_or_default();
+        let end = offset.saturating_add(self.length.unwrap_or(u64::MAX)).min(message.len());
+        let mut buf = vec![0u8; 64 * 1024];
+        while offset < end {
+            let len = usize::try_from(end - offset).unwrap_or(usize::MAX).min(buf.len());
+            let n = message.read_at(offset, &mut buf[..len]).map_err(|e| self.decrypt_error(e))?;
+            output.write_all(&buf[..n]).map_err(|e| CliError::WriteIo(e, self.output.clone()))?;
+            offset += n as u64;
+        }
+        output.commit()
+    }
+
+    fn recover(&self) -> Result<(), CliError> {
+        if self.input.as_os_str() == "-" {
+            return Err(CliError::StdinRecover);
+        }
+
+        let input = File::open(&self.input).map_err(|e| CliError::ReadIo(e, self.input.clone()))?;
+        let mut output = open_output(&self.output, true)?;
+        let mut message = self.private_key.with_receiver(|receiver| {
+            seekable::open(&receiver, BufReader::new(input), &self.sender, &[])
+                .map_err(|e| self.decrypt_error(e))
+        })?;
+
+        // Report each range of the plaintext which was lost.
+        let recovery = message.recover(&mut output).map_err(|e| self.decrypt_error(e))?;
+        output.commit()?;
+        for range in &recovery.lost {
+            warn(format_args!("lost bytes {}..{} of {}", range.start, range.end, recovery.written));
+        }
+        Ok(())
+    }
+
+    fn restore_metadata(self, input: Box<dyn Read>) -> Result<(), CliError> {
+        if self.output.as_os_str() == "-" {
+            return Err(CliError::StdoutMetadata);
+        }
+
+        // Open the output file once the metadata has been decrypted.
+        let mut output = EnvelopeWriter::new(|metadata: &FileMetadata| {
+            let mut path = self.output.clone();
+            if path.is_dir() {
+                let name = metadata.name.as_deref().and_then(|name| Path::new(name).file_name());
+                path.push(name.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "missing file name")
+                })?);
+            }
+            OutputFile::create(&path)
+        });
+        self.private_key.with_receiver(|receiver| {
+            backend::decrypt(&receiver, input, &mut output, &self.sender, &[])
+                .map_err(|e| self.decrypt_error(e))
+        })?;
+
+        // Restore the file's modification time and permissions.
+        let (metadata, output) =
+            output.finish().map_err(|e| CliError::WriteIo(e, self.output.clone()))?;
+        let file = output.file().expect("output should be a file");
+        if let Some(modified) = metadata.modified {
+            file.set_modified(UNIX_EPOCH + Duration::from_secs(modified))
+                .map_err(|e| CliError::WriteIo(e, self.output.clone()))?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = metadata.mode {
+            use std::os::unix::fs::PermissionsExt;
+
+            file.set_permissions(fs::Permissions::from_mode(mode))
+                .map_err(|e| CliError::WriteIo(e, self.output.clone()))?;
+        }
+        output.commit()
+    }
+}
+
+/// Report the structure of an encrypted message without decrypting it to a file.
+#[derive(Debug, Parser)]
+struct InspectArgs {
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// The path to the input file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+
+    /// The sender's public key.
+    #[arg(short, long, value_name = "KEY")]
+    sender: PublicKey,
+
+    /// Decode the ciphertext from ASCII armor.
+    #[arg(short, long)]
+    armor: bool,
+}
+
+impl Runnable for InspectArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut input = open_input(&self.input)?;
+        if self.armor {
+            input = Box::new(ArmorReader::new(BufReader::new(input)));
+        }
+        let private_key = self.private_key.decrypt()?;
+        let inspection =
+            inspect::inspect(&private_key, input, &self.sender, &[]).map_err(|e| match e {
+                DecryptError::InvalidCiphertext => CliError::InvalidCiphertext,
+                DecryptError::NotAReceiver | DecryptError::SequenceMismatch => {
+                    CliError::NotAReceiver
+                }
+                DecryptError::Corrupt => CliError::CorruptMessage,
+                DecryptError::Truncated => CliError::TruncatedMessage,
+                DecryptError::UnsupportedVersion(v) => CliError::UnsupportedVersion(v),
+                DecryptError::NotSeekable => CliError::NotSeekable,
+                DecryptError::HeadersTooLong => CliError::HeadersTooLong,
+                DecryptError::PlaintextTooLong => CliError::PlaintextTooLong,
+                DecryptError::ReadIo(e) | DecryptError::WriteIo(e) => {
+                    CliError::ReadIo(e, self.input)
+                }
+                e => CliError::Veil(e.into()),
+            })?;
+
+        let status = match inspection.status {
+            Status::Valid => "valid",
+            Status::Truncated => "truncated",
+            Status::InvalidPayload => "invalid payload",
+            Status::InvalidSignature => "invalid signature",
+        };
+        println!("version: {}", inspection.version);
+        println!("header: {} of {}", inspection.header_index + 1, inspection.header_count);
+        println!("padding: {} bytes", inspection.padding_len);
+        println!("payload: {} bytes", inspection.payload_len);
+        println!("status: {status}");
+        Ok(())
+    }
+}
+
+/// Encrypt a message with a passphrase instead of a key pair.
+#[derive(Debug, Parser)]
+struct PEncryptArgs {
+    /// The path to the input file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    output: PathBuf,
+
+    #[command(flatten)]
+    params: KeyOutput,
+}
+
+impl Runnable for PEncryptArgs {
+    fn run(self) -> Result<(), CliError> {
+        let input = open_input(&self.input)?;
+        let mut output = open_output(&self.output, true)?;
+        if self.params.kdf != KdfArg::Balloon {
+            return Err(CliError::UnsupportedKdf);
+        }
+        let passphrase = self.params.passphrase_input.read_passphrase()?;
+        let res = passphrase::encrypt(
+            OsRng,
+            &passphrase,
+            self.params.time_cost,
+            self.params.memory_cost,
+            self.params.parallelism,
+            input,
+            &mut output,
+        )
+        .map_err(|e| match e {
+            veil::EncryptError::ReadIo(e) => CliError::ReadIo(e, self.input),
+            e @ (veil::EncryptError::TooManyReceivers
+            | veil::EncryptError::TooMuchPadding
+            | veil::EncryptError::MessageTooLong) => CliError::LimitExceeded(e),
+            veil::EncryptError::WriteIo(e) => CliError::WriteIo(e, self.output),
+            e => CliError::Veil(e.into()),
+        });
+
+        // Remove a partial ciphertext rather than leave one which looks like a whole message.
+        if let Err(e) = res {
+            output.discard();
+            return Err(e);
+        }
+        output.commit()
+    }
+}
+
+/// Decrypt a message encrypted with a passphrase.
+#[derive(Debug, Parser)]
+struct PDecryptArgs {
+    /// The path to the input file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    output: PathBuf,
+
+    /// The sender's public key, if the message was encrypted with `encrypt --with-passphrase`.
+    #[arg(short, long, value_name = "KEY")]
+    sender: Option<PublicKey>,
+
+    #[command(flatten)]
+    passphrase_input: PassphraseInput,
+}
+
+impl Runnable for PDecryptArgs {
+    fn run(self) -> Result<(), CliError> {
+        let input = open_input(&self.input)?;
+        let mut output = open_output(&self.output, true)?;
+        let passphrase = self.passphrase_input.read_passphrase()?;
+        match &self.sender {
+            Some(sender) => {
+                passphrase::decrypt_message(&passphrase, input, &mut output, sender, &[])
+            }
+            None => passphrase::decrypt(&passphrase, input, &mut output),
+        }
+        .map_err(|e| match e {
+            DecryptError::InvalidCiphertext
+            | DecryptError::NotAReceiver
+            | DecryptError::Corrupt
+            | DecryptError::Truncated
+            | DecryptError::NotSeekable
+            | DecryptError::SequenceMismatch => CliError::InvalidCiphertext,
+            DecryptError::HeadersTooLong => CliError::HeadersTooLong,
+            DecryptError::PlaintextTooLong => CliError::PlaintextTooLong,
+            DecryptError::UnsupportedVersion(v) => CliError::UnsupportedVersion(v),
+            DecryptError::ReadIo(e) => CliError::ReadIo(e, self.input),
+            DecryptError::WriteIo(e) => CliError::WriteIo(e, self.output),
+            e => CliError::Veil(e.into()),
+        })?;
+        output.commit()
+    }
+}
+
+/// Sign a message.
+#[derive(Debug, Parser)]
+struct SignArgs {
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// The path to the message file or '-' for stdin.
+    #[arg(
+        short,
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+        required_unless_present = "digest"
+    )]
+    input: Option<PathBuf>,
+
+    /// The path to the signature file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+
+    /// Write the message with the signature attached instead of a detached signature.
+    #[arg(long)]
+    attached: bool,
+
+    /// Sign a digest of the message, calculated with `veil digest`, instead of the message.
+    #[arg(long, value_name = "DIGEST", conflicts_with_all = ["input", "attached"])]
+    digest: Option<Digest>,
+
+    /// Include the current time in the signature, asserting when the message was signed.
+    #[arg(long, conflicts_with_all = ["attached", "digest"])]
+    timestamp: bool,
+
+    /// Write an artifact with the signature or digest, the signer's fingerprint, and the current
+    /// time, all signed, instead of a bare signature.
+    #[arg(long, conflicts_with_all = ["attached", "timestamp"])]
+    artifact: bool,
+
+    /// The context of the artifact, e.g. what the signature is for.
+    #[arg(long, value_name = "CONTEXT", requires = "artifact")]
+    context: Option<String>,
+
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text, conflicts_with = "attached")]
+    format: Format,
+}
+
+impl Runnable for SignArgs {
+    fn run(self) -> Result<(), CliError> {
+        if let Some(digest) = self.digest {
+            let mut output = open_output(&self.output, false)?;
+            let private_key = self.private_key.decrypt()?;
+            let (signature, timestamp) = if self.artifact {
+                let context = self.context.as_deref().unwrap_or_default();
+                let artifact = private_key.digest_artifact(OsRng, &digest, context, unix_time());
+                (artifact.to_string(), Some(artifact.created()))
+            } else {
+                (private_key.sign_digest(OsRng, &digest).to_string(), None)
+            };
+            Report::Signature {
+                signer: private_key.public_key(),
+                signature,
+                bytes: None,
+                timestamp,
+            }
+            .write(self.format, &mut output)
+            .map_err(|e| CliError::WriteIo(e, self.output))?;
+            return output.commit();
+        }
+
+        let path = self.input.expect("input should be required");
+        let mut input = CountingReader::new(open_input(&path)?);
+        let mut output = open_output(&self.output, self.attached)?;
+        #[cfg(unix)]
+        if let Some((client, socket)) = self.private_key.connect()? {
+            // The agent only makes plain detached signatures.
+            if self.attached || self.timestamp || self.artifact {
+                return Err(CliError::AgentUnsupported);
+            }
+            let sig =
+                client.sign(&mut input).map_err(|e| CliError::Agent(e, socket.to_path_buf()))?;
+            Report::Signature {
+                signer: client.public_key(),
+                signature: sig.to_string(),
+                bytes: Some(input.count),
+                timestamp: None,
+            }
+            .write(self.format, &mut output)
+            .map_err(|e| CliError::WriteIo(e, self.output))?;
+            return output.commit();
+        }
+        let private_key = self.private_key.decrypt()?;
+        if self.attached {
+            private_key
+                .sign_attached(OsRng, input, &mut output)
+                .map_err(|e| CliError::WriteIo(e, self.output))?;
+            return output.commit();
+        }
+        let (signature, timestamp) = if self.artifact {
+            let context = self.context.as_deref().unwrap_or_default();
+            let artifact = private_key
+                .sign_artifact(OsRng, &mut input, context, unix_time())
+                .map_err(|e| CliError::ReadIo(e, path))?;
+            (artifact.to_string(), Some(artifact.created()))
+        } else if self.timestamp {
+            let timestamp = unix_time();
+            let sig = private_key
+                .sign_timestamped(OsRng, &mut input, timestamp)
+                .map_err(|e| CliError::ReadIo(e, path))?;
+            (sig.to_string(), Some(timestamp))
+        } else {
+            let sig = private_key.sign(OsRng, &mut input).map_err(|e| CliError::ReadIo(e, path))?;
+            (sig.to_string(), None)
+        };
+        Report::Signature {
+            signer: private_key.public_key(),
+            signature,
+            bytes: Some(input.count),
+            timestamp,
+        }
+        .write(self.format, &mut output)
+        .map_err(|e| CliError::WriteIo(e, self.output))?;
+        output.commit()
+    }
+}
+
+/// Verify a signature.
+#[derive(Debug, Parser)]
+struct VerifyArgs {
+    /// The signer's public key.
+    #[arg(long, value_name = "KEY", required_unless_present_any = ["manifest", "policy"])]
+    signer: Option<PublicKey>,
+
+    /// The signature of the message.
+    #[arg(
+        long,
+        value_name = "SIG",
+        required_unless_present_any = ["attached", "manifest", "signature_file"]
+    )]
+    signature: Option<SignatureArg>,
+
+    /// The path to a file containing the signature of the message or '-' for stdin.
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+        conflicts_with_all = ["signature", "attached", "manifest"]
+    )]
+    signature_file: Option<PathBuf>,
+
+    /// The path to the message file or '-' for stdin.
+    #[arg(
+        short,
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+        required_unless_present_any = ["manifest", "digest"]
+    )]
+    input: Option<PathBuf>,
+
+    /// Verify a message with an attached signature instead of a detached signature.
+    #[arg(long, conflicts_with = "signature")]
+    attached: bool,
+
+    /// The path to write the verified message to or '-' for stdout, if the signature is attached.
+    #[arg(
+        short,
+        long,
+        value_hint = ValueHint::FilePath,
+        default_value = "-",
+        value_name = "PATH",
+        requires = "attached"
+    )]
+    output: PathBuf,
+
+    /// The path to a manifest of signatures to verify, with one `FILE SIGNATURE KEY` line each.
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+        conflicts_with_all = ["signer", "signature", "input", "attached"]
+    )]
+    manifest: Option<PathBuf>,
+
+    /// The path to a TOML policy of signers, any `threshold` of whom must have signed the message.
+    /// Their signatures are read from `--signature-file`, one per line.
+    #[arg(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+        requires = "signature_file",
+        conflicts_with_all = ["signer", "attached", "manifest", "digest", "timestamp", "format"]
+    )]
+    policy: Option<PathBuf>,
+
+    /// Verify a signature of a digest of the message, calculated with `veil digest`, instead of
+    /// the message.
+    #[arg(long, value_name = "DIGEST", conflicts_with_all = ["input", "attached", "manifest"])]
+    digest: Option<Digest>,
+
+    /// Require the signature to be timestamped.
+    #[arg(long, conflicts_with_all = ["attached", "manifest", "digest"])]
+    timestamp: bool,
+
+    /// The number of seconds a signature's timestamp may be ahead of the current time, to allow
+    /// for clock skew.
+    #[arg(long, value_name = "SECONDS", default_value = "300")]
+    max_skew: u64,
+
+    /// The maximum age of a signature's timestamp, in seconds.
+    #[arg(long, value_name = "SECONDS")]
+    max_age: Option<u64>,
+
+    /// The format of the output.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Format::Text,
+        conflicts_with_all = ["attached", "manifest"]
+    )]
+    format: Format,
+}
+
+impl Runnable for VerifyArgs {
+    fn run(self) -> Result<(), CliError> {
+        if let Some(manifest) = self.manifest {
+            return verify_manifest(manifest);
+        }
+        if let Some(policy) = &self.policy {
+            return self.verify_policy(policy);
+        }
+
+        let signer = self.signer.expect("signer should be required");
+        let signature = match &self.signature_file {
+            Some(path) => Some(self.read_signature(path)?),
+            None => self.signature.clone(),
+        };
+        if let Some(digest) = self.digest {
+            return match signature {
+                Some(SignatureArg::Plain(sig)) => {
+                    let valid = signer.verify_digest(&digest, &sig).is_ok();
+                    self.report(signer, valid, None, None)
+                }
+                Some(SignatureArg::Artifact(artifact)) => {
+                    let valid = artifact.verify_digest(&signer, &digest).is_ok();
+                    self.report(signer, valid, None, Some(artifact.created()))
+                }
+                _ => Err(CliError::InvalidSignature),
+            };
+        }
+        let path = self.input.clone().expect("input should be required");
+        let mut input = CountingReader::new(open_input(&path)?);
+        let result = match signature {
+            Some(SignatureArg::Plain(_)) if self.timestamp => return Err(CliError::NotTimestamped),
+            Some(SignatureArg::Plain(sig)) => signer.verify(&mut input, &sig),
+            Some(SignatureArg::Timestamped(sig)) => {
+                let timestamp = match signer.verify_timestamped(&mut input, &sig) {
+                    Ok(timestamp) => timestamp,
+                    Err(veil::VerifyError::ReadIo(e)) => return Err(CliError::ReadIo(e, path)),
+                    Err(_) => return self.report(signer, false, Some(input.count), None),
+                };
+                check_timestamp(timestamp, self.max_skew, self.max_age)?;
+                return self.report(signer, true, Some(input.count), Some(timestamp));
+            }
+            Some(SignatureArg::Artifact(artifact)) => {
+                match artifact.verify_message(&signer, &mut input) {
+                    Ok(()) => {}
+                    Err(veil::VerifyError::ReadIo(e)) => return Err(CliError::ReadIo(e, path)),
+                    Err(_) => return self.report(signer, false, Some(input.count), None),
+                }
+                check_timestamp(artifact.created(), self.max_skew, self.max_age)?;
+                return self.report(signer, true, Some(input.count), Some(artifact.created()));
+            }
+            None => {
+                let mut output = open_output(&self.output, true)?;
+                signer
+                    .verify_attached(&mut input, &mut output)
+                    .and_then(|_| output.finish().map_err(veil::VerifyError::WriteIo))
+            }
+        };
+        match result {
+            Ok(()) => self.report(signer, true, Some(input.count), None),
+            Err(veil::VerifyError::InvalidSignature) => {
+                self.report(signer, false, Some(input.count), None)
+            }
+            Err(veil::VerifyError::ReadIo(e)) => Err(CliError::ReadIo(e, path)),
+            Err(veil::VerifyError::WriteIo(e)) => Err(CliError::WriteIo(e, self.output)),
+            Err(e) => Err(CliError::Veil(e.into())),
+        }
+    }
+}
+
+impl VerifyArgs {
+    /// Reads a signature from the given file or stdin, ignoring surrounding whitespace.
+    fn read_signature(&self, path: &Path) -> Result<SignatureArg, CliError> {
+        if path.as_os_str() == "-" && self.input.as_ref().is_some_and(|p| p.as_os_str() == "-") {
+            return Err(CliError::StdinSignature);
+        }
+
+        let mut sig = String::new();
+        open_input(path)?
+            .read_to_string(&mut sig)
+            .map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+        sig.parse().map_err(|_| CliError::InvalidSignature)
+    }
+
+    /// Verifies the signatures in the signature file against the given policy file, printing the
+    /// signers whose signatures are valid.
+    fn verify_policy(&self, path: &Path) -> Result<(), CliError> {
+        let mut contents = String::new();
+        open_input(path)?
+            .read_to_string(&mut contents)
+            .map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+        let policy = toml::from_str::<PolicyFile>(&contents)
+            .map_err(|e| CliError::InvalidPolicy(path.to_path_buf(), Some(e)))?
+            .build()
+            .ok_or_else(|| CliError::InvalidPolicy(path.to_path_buf(), None))?;
+
+        let sig_path = self.signature_file.as_ref().expect("signature file should be required");
+        if sig_path.as_os_str() == "-" && self.input.as_ref().is_some_and(|p| p.as_os_str() == "-")
+        {
+            return Err(CliError::StdinSignature);
+        }
+        let mut sigs = String::new();
+        open_input(sig_path)?
+            .read_to_string(&mut sigs)
+            .map_err(|e| CliError::ReadIo(e, sig_path.clone()))?;
+        let sigs = sigs
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Signature>, _>>()
+            .map_err(|_| CliError::InvalidSignature)?;
+
+        let input = self.input.clone().expect("input should be required");
+        match policy.verify(open_input(&input)?, &sigs) {
+            Ok(signers) => {
+                for signer in signers {
+                    println!("{signer}: OK");
+                }
+                Ok(())
+            }
+            Err(PolicyError::NotEnoughSigners(n, k)) => Err(CliError::NotEnoughSigners(n, k)),
+            Err(PolicyError::ReadIo(e)) => Err(CliError::ReadIo(e, input)),
+            Err(PolicyError::InvalidPolicy) => unreachable!("policy should be valid"),
+            Err(e) => Err(CliError::Veil(e.into())),
+        }
+    }
+
+    /// Reports the result of verifying a signature, returning an error if it was invalid.
+    fn report(
+        &self,
+        signer: PublicKey,
+        valid: bool,
+        bytes: Option<u64>,
+        timestamp: Option<u64>,
+    ) -> Result<(), CliError> {
+        Report::Verification { signer, valid, bytes, timestamp }
+            .write(self.format, io::stdout().lock())
+            .map_err(CliError::TermIo)?;
+        if !valid {
+            return Err(CliError::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+/// Check a verified signature's timestamp against the current time, allowing it to be up to
+/// `max_skew` seconds in the future and, if given, at most `max_age` seconds in the past.
+fn check_timestamp(timestamp: u64, max_skew: u64, max_age: Option<u64>) -> Result<(), CliError> {
+    let now = unix_time();
+    if timestamp > now.saturating_add(max_skew) {
+        return Err(CliError::FutureTimestamp(timestamp));
+    }
+    if max_age.is_some_and(|max_age| now.saturating_sub(timestamp) > max_age) {
+        return Err(CliError::ExpiredTimestamp(timestamp));
+    }
+    Ok(())
+}
+
+/// A verification policy file, which lists the signers and how many of them must sign a message.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    threshold: PolicyThreshold,
+    signers: Vec<PublicKey>,
+}
+
+impl PolicyFile {
+    fn build(self) -> Option<VerificationPolicy> {
+        match self.threshold {
+            PolicyThreshold::Named(NamedThreshold::Any) => {
+                VerificationPolicy::any_of(&self.signers)
+            }
+            PolicyThreshold::Named(NamedThreshold::All) => {
+                VerificationPolicy::all_of(&self.signers)
+            }
+            PolicyThreshold::Count(k) => VerificationPolicy::k_of_n(k, &self.signers),
+        }
+        .ok()
+    }
+}
+
+/// A policy's threshold: `"any"`, `"all"`, or a number of signers.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PolicyThreshold {
+    Count(usize),
+    Named(NamedThreshold),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum NamedThreshold {
+    Any,
+    All,
+}
+
+/// A detached signature, with or without a timestamp, or an artifact.
+#[derive(Clone, Debug)]
+enum SignatureArg {
+    Plain(Signature),
+    Timestamped(TimestampedSignature),
+    Artifact(Box<Artifact>),
+}
+
+impl FromStr for SignatureArg {
+    type Err = ParseSignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(SignatureArg::Plain)
+            .or_else(|_| s.parse().map(SignatureArg::Timestamped))
+            .or_else(|e| s.parse().map(|a| SignatureArg::Artifact(Box::new(a))).map_err(|_| e))
+    }
+}
+
+fn verify_manifest(manifest: PathBuf) -> Result<(), CliError> {
+    let mut contents = String::new();
+    open_input(&manifest)?
+        .read_to_string(&mut contents)
+        .map_err(|e| CliError::ReadIo(e, manifest.clone()))?;
+
+    // Parse each `FILE SIGNATURE KEY` line, splitting from the right so file names may contain
+    // spaces. Blank lines and comments are skipped.
+    let mut batch = Batch::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.rsplitn(3, ' ');
+        let (Some(signer), Some(sig), Some(file)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(CliError::InvalidManifest(manifest, i + 1));
+        };
+        let (Ok(signer), Ok(sig)) = (signer.parse::<PublicKey>(), sig.parse::<Signature>()) else {
+            return Err(CliError::InvalidManifest(manifest, i + 1));
+        };
+        batch.add(PathBuf::from(file.trim_end()), signer, sig);
+    }
+
+    // Verify every signature, reading each file once, and report the results.
+    let mut failed = 0;
+    for (entry, result) in batch.verify(|path| File::open(path)) {
+        let path = entry.message.display();
+        failed += usize::from(result.is_err());
+        match result {
+            Ok(()) => println!("{path}: OK"),
+            Err(veil::VerifyError::ReadIo(e)) => println!("{path}: FAILED ({e})"),
+            Err(e) => println!("{path}: FAILED ({e})"),
+        }
+    }
+
+    if failed > 0 {
+        return Err(CliError::FailedSignatures(failed));
+    }
+    Ok(())
+}
+
+/// Create a signed statement that a private key has been revoked.
+#[derive(Debug, Parser)]
+struct RevokeArgs {
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// The reason the private key is being revoked.
+    #[arg(long, default_value = "")]
+    reason: String,
+
+    /// The path to the revocation file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+}
+
+impl Runnable for RevokeArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut output = open_output(&self.output, false)?;
+        let private_key = self.private_key.decrypt()?;
+        let revocation = private_key.revoke(OsRng, &self.reason, unix_time());
+        write!(output, "{revocation}").map_err(|e| CliError::WriteIo(e, self.output))?;
+        output.commit()
+    }
+}
+
+/// Check that a public key has been revoked by its owner.
+#[derive(Debug, Parser)]
+struct CheckRevocationArgs {
+    /// The public key to check.
+    #[arg(value_name = "KEY")]
+    public_key: PublicKey,
+
+    /// The path to the revocation file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+}
+
+impl Runnable for CheckRevocationArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut revocation = String::new();
+        open_input(&self.input)?
+            .read_to_string(&mut revocation)
+            .map_err(|e| CliError::ReadIo(e, self.input))?;
+        let revocation =
+            revocation.trim().parse::<Revocation>().map_err(|_| CliError::InvalidRevocation)?;
+        revocation.verify(&self.public_key).map_err(|_| CliError::InvalidRevocation)?;
+        println!("revoked at {}: {}", revocation.timestamp(), revocation.reason());
+        Ok(())
+    }
+}
+
+/// Create a signed statement that a public key belongs to an identity.
+#[derive(Debug, Parser)]
+struct CertifyArgs {
+    #[command(flatten)]
+    private_key: PrivateKeyInput,
+
+    /// The public key to certify.
+    #[arg(value_name = "KEY")]
+    subject: PublicKey,
+
+    /// The identity the public key belongs to (e.g. a name or email address).
+    #[arg(long)]
+    label: String,
+
+    /// The start of the validity period, in seconds since the Unix epoch. Defaults to now.
+    #[arg(long, value_name = "SECONDS")]
+    not_before: Option<u64>,
+
+    /// The end of the validity period, in seconds since the Unix epoch. Defaults to never.
+    #[arg(long, value_name = "SECONDS", default_value_t = u64::MAX, hide_default_value = true)]
+    not_after: u64,
+
+    /// The path to the certification file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+}
+
+impl Runnable for CertifyArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut output = open_output(&self.output, false)?;
+        let private_key = self.private_key.decrypt()?;
+        let not_before = self.not_before.unwrap_or_else(unix_time);
+        let certification =
+            private_key.certify(OsRng, &self.subject, &self.label, not_before, self.not_after);
+        write!(output, "{certification}").map_err(|e| CliError::WriteIo(e, self.output))?;
+        output.commit()
+    }
+}
+
+/// Check that a public key has been certified by an issuer.
+#[derive(Debug, Parser)]
+struct CheckCertArgs {
+    /// The public key of the issuer.
+    #[arg(value_name = "KEY")]
+    issuer: PublicKey,
+
+    /// The path to the certification file or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    input: PathBuf,
+
+    /// Check the certification at the given time, in seconds since the Unix epoch, instead of now.
+    #[arg(long, value_name = "SECONDS")]
+    at: Option<u64>,
+}
+
+impl Runnable for CheckCertArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut certification = String::new();
+        open_input(&self.input)?
+            .read_to_string(&mut certification)
+            .map_err(|e| CliError::ReadIo(e, self.input))?;
+        let certification = self
+            .issuer
+            .verify_certification(certification.trim(), self.at.unwrap_or_else(unix_time))
+            .map_err(CliError::InvalidCertification)?;
+        println!("{} certified as {}", certification.subject(), certification.label());
+        Ok(())
+    }
+}
+
+/// Calculate a message digest.
+#[derive(Debug, Parser)]
+struct DigestArgs {
+    /// Associated metadata to be included in the digest.
+    #[arg(short, long)]
+    metadata: Vec<String>,
+
+    /// Compare the computed digest to a given digest.
+    #[arg(long, value_name = "DIGEST", group("out"))]
+    check: Option<Digest>,
+
+    /// Digest the contents of the input directory instead of a single file.
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// The path to the message file, the directory if --recursive is given, or '-' for stdin.
+    #[arg(short, long, value_hint = ValueHint::AnyPath, value_name = "PATH")]
+    input: PathBuf,
+
+    /// The path to the digest file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH", group("out"))]
+    output: PathBuf,
+
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+impl Runnable for DigestArgs {
+    fn run(self) -> Result<(), CliError> {
+        let (digest, bytes) = if self.recursive {
+            let digest = Digest::for_dir(&self.input, &self.metadata)
+                .map_err(|e| CliError::ReadIo(e, self.input))?;
+            (digest, None)
+        } else {
+            let mut input = CountingReader::new(open_input(&self.input)?);
+            let digest = Digest::new(&self.metadata, &mut input)
+                .map_err(|e| CliError::ReadIo(e, self.input))?;
+            (digest, Some(input.count))
+        };
+        let matches = self.check.map(|check| check == digest);
+        let mut output = open_output(&self.output, false)?;
+        Report::Digest { digest, bytes, matches }
+            .write(self.format, &mut output)
+            .map_err(CliError::TermIo)?;
+        output.commit()?;
+        if matches == Some(false) {
+            return Err(CliError::DigestMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Generate shell completion scripts.
+#[derive(Debug, Parser)]
+#[command(hide(true))]
+struct CompleteArgs {
+    /// The type of shell completion script to generate: bash, elvish, fish, powershell, or zsh.
+    #[arg(long)]
+    shell: Shell,
+
+    /// Output directory for shell completion scripts.
+    #[arg(short, long, value_hint = ValueHint::DirPath)]
+    output: PathBuf,
+}
+
+impl Runnable for CompleteArgs {
+    fn run(self) -> Result<(), CliError> {
+        let mut app = Opts::command();
+        generate_to(self.shell, &mut app, "veil", &self.output)
+            .map_err(|e| CliError::WriteIo(e, self.output))?;
+        Ok(())
+    }
+}
+
+/// Generate or verify known-answer test vectors.
+#[derive(Debug, Parser)]
+#[command(hide(true))]
+struct VectorsArgs {
+    /// Verify the test vectors in the given file or '-' for stdin instead of generating them.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    verify: Option<PathBuf>,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+}
+
+impl Runnable for VectorsArgs {
+    fn run(self) -> Result<(), CliError> {
+        if let Some(path) = self.verify {
+            let mut s = String::new();
+            open_input(&path)?.read_to_string(&mut s).map_err(|e| CliError::ReadIo(e, path))?;
+            let vectors = testvectors::parse(&s).map_err(CliError::InvalidTestVectors)?;
+            return testvectors::verify(&vectors).map_err(CliError::InvalidTestVectors);
+        }
+
+        let mut output = open_output(&self.output, false)?;
+        write!(output, "{}", testvectors::format(&testvectors::generate()))
+            .map_err(|e| CliError::WriteIo(e, self.output))?;
+        output.commit()
+    }
+}
+
+/// Measure the speed of key derivation, encryption, decryption, and signing on this machine.
+///
+/// Writes one JSON object per measurement, with the median duration in nanoseconds.
+#[derive(Debug, Parser)]
+#[command(hide(true))]
+struct BenchArgs {
+    /// The key derivation function to measure.
+    #[arg(long, value_enum, default_value_t = KdfArg::Balloon)]
+    kdf: KdfArg,
+
+    /// The time costs to measure, separated by commas.
+    #[arg(long, value_delimiter = ',', default_value = "4,6,8", value_name = "COSTS")]
+    time_cost: Vec<u8>,
+
+    /// The memory costs to measure, separated by commas.
+    #[arg(long, value_delimiter = ',', default_value = "4,6,8", value_name = "COSTS")]
+    memory_cost: Vec<u8>,
+
+    /// The number of threads to use for key derivation.
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u8).range(1..))]
+    parallelism: u8,
+
+    /// The number of times to measure each operation.
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u32).range(1..))]
+    iterations: u32,
+
+    /// The path to the output file or '-' for stdout.
+    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "-", value_name = "PATH")]
+    output: PathBuf,
+}
+
+impl Runnable for BenchArgs {
+    fn run(self) -> Result<(), CliError> {
+        const LENS: [u64; 3] = [1024, 64 * 1024, 1024 * 1024];
+        const RECEIVERS: [usize; 3] = [1, 10, 100];
+
+        let mut output = open_output(&self.output, false)?;
+        let mut report = |line: String| {
+            writeln!(output, "{line}").map_err(|e| CliError::WriteIo(e, self.output.clone()))
+        };
+        let private_key = PrivateKey::random(OsRng);
+
+        // Measure key derivation across the grid of parameters.
+        let kdf_name = self.kdf.to_possible_value().expect("should have a value");
+        for &time_cost in &self.time_cost {
+            for &memory_cost in &self.memory_cost {
+                let kdf = self.kdf.params(time_cost, memory_cost, self.parallelism);
+                let ns = self.median_ns(|| {
+                    private_key
+                        .store_with(io::sink(), OsRng, b"passphrase", kdf)
+                        .expect("should write to sink");
+                });
+                report(format!(
+                    r#"{{"benchmark":"kdf","kdf":"{}","time_cost":{time_cost},"memory_cost":{memory_cost},"parallelism":{},"median_ns":{ns}}}"#,
+                    kdf_name.get_name(),
+                    self.parallelism,
+                ))?;
+            }
+        }
+
+        // Measure encryption and decryption of messages for the last of several receivers.
+        for receivers in RECEIVERS {
+            let receiver_keys =
+                (0..receivers).map(|_| PrivateKey::random(OsRng)).collect::<Vec<_>>();
+            let public_keys = receiver_keys.iter().map(PrivateKey::public_key).collect::<Vec<_>>();
+            let receiver = receiver_keys.last().expect("should have a receiver");
+            let options = EncryptOptions::new(&public_keys);
+            for len in LENS {
+                let mut ciphertext = Vec::new();
+                let ns = self.median_ns(|| {
+                    ciphertext.clear();
+                    private_key
+                        .encrypt(OsRng, io::repeat(0).take(len), &mut ciphertext, &options)
+                        .expect("should encrypt");
+                });
+                report(format!(
+                    r#"{{"benchmark":"encrypt","bytes":{len},"receivers":{receivers},"median_ns":{ns}}}"#
+                ))?;
+
+                let ns = self.median_ns(|| {
+                    receiver
+                        .decrypt(ciphertext.as_slice(), io::sink(), &private_key.public_key())
+                        .expect("should decrypt");
+                });
+                report(format!(
+                    r#"{{"benchmark":"decrypt","bytes":{len},"receivers":{receivers},"median_ns":{ns}}}"#
+                ))?;
+            }
+        }
+
+        // Measure signing and verification.
+        for len in LENS {
+            let sig = private_key.sign(OsRng, io::repeat(0).take(len)).expect("should sign");
+            let ns = self.median_ns(|| {
+                private_key.sign(OsRng, io::repeat(0).take(len)).expect("should sign");
+            });
+            report(format!(r#"{{"benchmark":"sign","bytes":{len},"median_ns":{ns}}}"#))?;
+
+            let ns = self.median_ns(|| {
+                private_key
+                    .public_key()
+                    .verify(io::repeat(0).take(len), &sig)
+                    .expect("should verify");
+            });
+            report(format!(r#"{{"benchmark":"verify","bytes":{len},"median_ns":{ns}}}"#))?;
+        }
+
+        output.commit()
+    }
+}
+
+impl BenchArgs {
+    /// Runs the operation the configured number of times and returns its median duration in
+    /// nanoseconds.
+    fn median_ns(&self, mut f: impl FnMut()) -> u128 {
+        let mut durations = (0..self.iterations)
+            .map(|_| {
+                let start = Instant::now();
+                f();
+                start.elapsed().as_nanos()
+            })
+            .collect::<Vec<_>>();
+        durations.sort_unstable();
+        durations[durations.len() / 2]
+    }
+}
+
+/// The format of a command's output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Plain text.
+    Text,
+
+    /// A JSON object, followed by a newline.
+    Json,
+}
+
+/// The result of a command, which is written as plain text or as a JSON object.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Report {
+    /// A public key, and a signed bundle of it if one was exported.
+    PublicKey {
+        public_key: PublicKey,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bundle: Option<String>,
+    },
+
+    /// A detached signature of a message or digest.
+    Signature {
+        signer: PublicKey,
+        signature: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bytes: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timestamp: Option<u64>,
+    },
+
+    /// A message digest, and whether it matched the expected digest if one was given.
+    Digest {
+        digest: Digest,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bytes: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        matches: Option<bool>,
+    },
+
+    /// The result of verifying a signature of a message or digest.
+    Verification {
+        signer: PublicKey,
+        valid: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bytes: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timestamp: Option<u64>,
+    },
+}
+
+impl Report {
+    /// Writes the report in the given format. As plain text, only the key, signature, or digest is
+    /// written, or the timestamp of a verified timestamped signature.
+    fn write(&self, format: Format, mut output: impl Write) -> io::Result<()> {
+        if format == Format::Json {
+            serde_json::to_writer(&mut output, self)?;
+            return writeln!(output);
+        }
+
+        match self {
+            Report::PublicKey { bundle: Some(bundle), .. } => write!(output, "{bundle}"),
+            Report::PublicKey { public_key, .. } => write!(output, "{public_key}"),
+            Report::Signature { signature, .. } => write!(output, "{signature}"),
+            Report::Digest { matches: Some(_), .. } => Ok(()),
+            Report::Digest { digest, .. } => write!(output, "{digest}"),
+            Report::Verification { valid: true, timestamp: Some(timestamp), .. } => {
+                writeln!(output, "signed at {timestamp}")
+            }
+            Report::Verification { .. } => Ok(()),
+        }
+    }
+}
+
+/// A reader which counts the bytes read from it.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    const fn new(inner: R) -> CountingReader<R> {
+        CountingReader { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+#[derive(Debug, Parser)]
+struct PrivateKeyInput {
+    /// The path of the encrypted private key.
+    #[arg(
+        short = 'k',
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH"
+    )]
+    #[cfg_attr(
+        unix,
+        arg(required_unless_present_any = ["key", "agent"], conflicts_with_all = ["key", "agent"])
+    )]
+    #[cfg_attr(not(unix), arg(required_unless_present = "key", conflicts_with = "key"))]
+    private_key: Option<PathBuf>,
+
+    /// The path of the socket of an agent holding the unlocked private key.
+    #[cfg(unix)]
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "SOCKET", conflicts_with = "key")]
+    agent: Option<PathBuf>,
+
+    /// The name of the private key in the key store.
+    #[arg(long, value_name = "NAME", requires = "keystore")]
+    key: Option<String>,
+
+    /// The path of the key store.
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "PATH")]
+    keystore: Option<PathBuf>,
+
+    #[command(flatten)]
+    passphrase_input: PassphraseInput,
+}
+
+impl PrivateKeyInput {
+    fn decrypt(&self) -> Result<PrivateKey, CliError> {
+        #[cfg(unix)]
+        if self.agent.is_some() {
+            return Err(CliError::AgentUnsupported);
+        }
+
+        let passphrase = self.passphrase_input.read_passphrase()?;
+        if let (Some(name), Some(path)) = (&self.key, &self.keystore) {
+            return load_keystore(path, &passphrase)?.remove(name).map_err(CliError::KeyStore);
+        }
+
+        let path = self.private_key.as_deref().expect("private key should be required");
+        let ciphertext = fs::read(path).map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+        unlock_private_key(path, &ciphertext, &passphrase).map(|(private_key, _)| private_key)
+    }
+
+    /// Returns the public key of the agent holding the private key, if one was given.
+    fn agent_public_key(&self) -> Result<Option<PublicKey>, CliError> {
+        #[cfg(unix)]
+        if let Some(socket) = &self.agent {
+            return agent::public_key(socket)
+                .map(Some)
+                .map_err(|e| CliError::Agent(e, socket.clone()));
+        }
+        Ok(None)
+    }
+
+    /// Connects to the agent holding the private key, if one was given. Returns the client and the
+    /// path of the agent's socket.
+    #[cfg(unix)]
+    fn connect(&self) -> Result<Option<(agent::Client, &Path)>, CliError> {
+        self.agent
+            .as_deref()
+            .map(|socket| {
+                agent::Client::connect(socket)
+                    .map(|client| (client, socket))
+                    .map_err(|e| CliError::Agent(e, socket.to_path_buf()))
+            })
+            .transpose()
+    }
+
+    /// Calls `f` with the private key, or with a client of the agent holding it. If the agent
+    /// couldn't be reached while `f` was running, returns that error instead of `f`'s.
+    fn with_receiver<T>(
+        &self,
+        f: impl FnOnce(&dyn KemBackend) -> Result<T, CliError>,
+    ) -> Result<T, CliError> {
+        #[cfg(unix)]
+        if let Some((client, socket)) = self.connect()? {
+            let res = f(&client);
+            return client
+                .take_error()
+                .map_or(res, |e| Err(CliError::Agent(e, socket.to_path_buf())));
+        }
+        f(&self.decrypt()?)
+    }
+}
+
+/// Returns the path of the guard for the private key file at the given path.
+fn guard_path(path: &Path) -> PathBuf {
+    let mut guard_path = path.as_os_str().to_owned();
+    guard_path.push(".guard");
+    guard_path.into()
+}
+
+/// Decrypts the given encrypted private key from the given path, through its guard if it has one.
+/// Returns the private key and the guard, if any.
+fn unlock_private_key(
+    path: &Path,
+    ciphertext: &[u8],
+    passphrase: &[u8],
+) -> Result<(PrivateKey, Option<Guard>), CliError> {
+    let guard_path = guard_path(path);
+    let mut guard = match File::open(&guard_path) {
+        Ok(f) => {
+            Guard::load(f, ciphertext).map_err(|e| CliError::InvalidGuard(guard_path.clone(), e))?
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let private_key =
+                PrivateKey::load(ciphertext, passphrase).map_err(CliError::BadPassphrase)?;
+            return Ok((private_key, None));
+        }
+        Err(e) => return Err(CliError::ReadIo(e, guard_path)),
+    };
+
+    // Record each attempt in the guard file before making it.
+    let private_key = guard
+        .unlock(ciphertext, passphrase, |guard| {
+            let mut b = Vec::new();
+            guard.store(&mut b, ciphertext)?;
+            write_file(&guard_path, &b)
+        })
+        .map_err(|e| match e {
+            DecryptError::WriteIo(e) => CliError::WriteIo(e, guard_path.clone()),
+            e => CliError::BadPassphrase(e),
+        })?;
+    Ok((private_key, Some(guard)))
+}
+
+/// Writes the given guard for the given encrypted private key next to the private key file.
+fn store_guard(guard: &Guard, path: &Path, ciphertext: &[u8]) -> Result<(), CliError> {
+    let guard_path = guard_path(path);
+    let mut b = Vec::new();
+    guard.store(&mut b, ciphertext).map_err(|e| CliError::WriteIo(e, guard_path.clone()))?;
+    write_file(&guard_path, &b).map_err(|e| CliError::WriteIo(e, guard_path))
+}
+
+#[derive(Debug, Parser)]
+struct PassphraseInput {
+    /// Read the passphrase from the given file descriptor.
+    #[arg(long, conflicts_with = "passphrase_env")]
+    #[cfg(unix)]
+    passphrase_fd: Option<std::os::unix::prelude::RawFd>,
+
+    /// Read the passphrase from the given environment variable, which is then removed from the
+    /// environment.
+    #[arg(long, value_name = "VAR")]
+    passphrase_env: Option<String>,
+}
+
+impl PassphraseInput {
+    fn read_passphrase(&self) -> Result<Zeroizing<Vec<u8>>, CliError> {
+        read_passphrase(self.passphrase_fd, self.passphrase_env.as_deref(), "Enter passphrase: ")
+    }
+
+    fn read_new_passphrase(&self, force: bool) -> Result<Zeroizing<Vec<u8>>, CliError> {
+        read_new_passphrase(
+            self.passphrase_fd,
+            self.passphrase_env.as_deref(),
+            "Enter passphrase: ",
+            force,
+        )
+    }
+}
+
+fn read_passphrase(
+    fd: Option<std::os::unix::prelude::RawFd>,
+    env: Option<&str>,
+    prompt: &str,
+) -> Result<Zeroizing<Vec<u8>>, CliError> {
+    if cfg!(unix) {
+        if let Some(fd) = fd {
+            return read_from_fd(fd);
+        }
+        if let Some(var) = env {
+            return read_from_env(var);
+        }
+    }
+
+    prompt_for_passphrase(prompt)
+        .map(|mut passphrase| Zeroizing::new(mem::take(&mut *passphrase).into_bytes()))
+}
+
+/// Reads a passphrase for a new private key. Passphrases entered interactively must be confirmed
+/// and, unless `force` is set, must be hard to guess.
+fn read_new_passphrase(
+    fd: Option<std::os::unix::prelude::RawFd>,
+    env: Option<&str>,
+    prompt: &str,
+    force: bool,
+) -> Result<Zeroizing<Vec<u8>>, CliError> {
+    if cfg!(unix) {
+        if let Some(fd) = fd {
+            return read_from_fd(fd);
+        }
+        if let Some(var) = env {
+            return read_from_env(var);
+        }
+    }
+
+    let mut passphrase = prompt_for_passphrase(prompt)?;
+    let strength = passphrase_policy::estimate(&passphrase);
+    if !strength.is_acceptable() {
+        if !force {
+            return Err(CliError::WeakPassphrase(strength));
+        }
+        warn(format_args!("weak passphrase: {strength}"));
+    }
+
+    let confirmation = prompt_for_passphrase("Confirm passphrase: ")?;
+    if !passphrase_policy::confirm(&passphrase, &confirmation) {
+        return Err(CliError::PassphraseMismatch);
+    }
+    Ok(Zeroizing::new(mem::take(&mut *passphrase).into_bytes()))
+}
+
+/// The number of bytes reserved for a passphrase read from a file descriptor.
+#[cfg(unix)]
+const PASSPHRASE_CAPACITY: usize = 4096;
+
+#[cfg(unix)]
+fn read_from_fd(fd: i32) -> Result<Zeroizing<Vec<u8>>, CliError> {
+    use std::os::unix::prelude::FromRawFd;
+
+    // Reserve enough space up front that reading a passphrase doesn't leave copies of it behind in
+    // reallocated buffers.
+    let mut out = Zeroizing::new(Vec::with_capacity(PASSPHRASE_CAPACITY));
+    unsafe { File::from_raw_fd(fd) }.read_to_end(&mut out).map_err(|e| CliError::FdIo(e, fd))?;
+    Ok(out)
+}
+
+/// Reads a passphrase from the given environment variable and removes it from the environment, so
+/// that it isn't inherited by child processes.
+#[cfg(unix)]
+fn read_from_env(var: &str) -> Result<Zeroizing<Vec<u8>>, CliError> {
+    use std::os::unix::ffi::OsStringExt;
+
+    let value = env::var_os(var).ok_or_else(|| CliError::MissingPassphraseEnv(var.to_string()))?;
+    env::remove_var(var);
+    let passphrase = Zeroizing::new(value.into_vec());
+    if passphrase.is_empty() {
+        return Err(CliError::EmptyPassphrase);
+    }
+    Ok(passphrase)
+}
+
+fn prompt_for_passphrase(prompt: &str) -> Result<Zeroizing<String>, CliError> {
+    let mut term = Term::stderr();
+    let _ = term.write(prompt.as_bytes()).map_err(CliError::TermIo)?;
+    let passphrase = Zeroizing::new(term.read_secure_line().map_err(CliError::TermIo)?);
+    if passphrase.is_empty() {
+        return Err(CliError::EmptyPassphrase);
+    }
+    Ok(passphrase)
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after the Unix epoch")
+        .as_secs()
+}
+
+fn file_metadata(path: &Path) -> Result<FileMetadata, CliError> {
+    if path.as_os_str() == "-" {
+        return Ok(FileMetadata::default());
+    }
+
+    let metadata = fs::metadata(path).map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+    let modified = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+    #[cfg(unix)]
+    let mode = Some(std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()));
+    #[cfg(not(unix))]
+    let mode = None;
+
+    Ok(FileMetadata {
+        name: path.file_name().and_then(|name| name.to_str()).map(Into::into),
+        modified: modified.map(|d| d.as_secs()),
+        mode,
+    })
+}
+
+/// Parses a receiver's public key, which may be given as a public key, a public key URI, or the
+/// path to a PNG image of a public key URI's QR code prefixed with `qr://`.
+fn parse_receiver(s: &str) -> Result<PublicKey, CliError> {
+    if let Some(path) = s.strip_prefix("qr://") {
+        let path = PathBuf::from(path);
+        let data = qr::read(open_input(&path)?).ok_or(CliError::InvalidQrCode(path.clone()))?;
+        let uri = String::from_utf8(data).map_err(|_| CliError::InvalidQrCode(path.clone()))?;
+        uri::decode(&uri).map_err(|e| CliError::InvalidQrPublicKey(path, e))
+    } else if s.contains(':') {
+        uri::decode(s).map_err(CliError::InvalidPublicKey)
+    } else {
+        s.parse().map_err(CliError::InvalidPublicKey)
+    }
+}
+
+fn open_input(path: &Path) -> Result<Box<dyn Read>, CliError> {
+    if path.as_os_str() == "-" {
+        if io::stdin().is_terminal() {
+            return Err(CliError::StdinTty);
+        }
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        let f = File::open(path).map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+        Ok(Box::new(f))
+    }
+}
+
+/// An input file, which is memory-mapped if it's a regular file and the `mmap` feature is enabled.
+enum Input {
+    Stream(Box<dyn Read>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Input {
+    fn open(path: &Path) -> Result<Input, CliError> {
+        #[cfg(feature = "mmap")]
+        if path.as_os_str() != "-" {
+            let f = File::open(path).map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+            let metadata = f.metadata().map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+            if metadata.is_file() && metadata.len() > 0 {
+                // SAFETY: The map is read-only. If the file is modified while it's mapped, the
+                // ciphertext will fail to decrypt or the plaintext will be whatever was read,
+                // just as with buffered reads; if it's truncated, the process may be killed.
+                let map = unsafe { memmap2::Mmap::map(&f) }
+                    .map_err(|e| CliError::ReadIo(e, path.to_path_buf()))?;
+                #[cfg(unix)]
+                let _ = map.advise(memmap2::Advice::Sequential);
+                return Ok(Input::Mapped(map));
+            }
+            return Ok(Input::Stream(Box::new(f)));
+        }
+        open_input(path).map(Input::Stream)
+    }
+
+    /// Returns the contents of the input if it's memory-mapped.
+    #[cfg_attr(not(feature = "mmap"), allow(clippy::missing_const_for_fn))]
+    fn as_slice(&self) -> Option<&[u8]> {
+        match self {
+            Input::Stream(_) => None,
+            #[cfg(feature = "mmap")]
+            Input::Mapped(map) => Some(map),
+        }
+    }
+
+    fn into_reader(self) -> Box<dyn Read> {
+        match self {
+            Input::Stream(r) => r,
+            #[cfg(feature = "mmap")]
+            Input::Mapped(map) => Box::new(io::Cursor::new(map)),
+        }
+    }
+}
+
+enum Output<'a> {
+    Binary(&'a mut OutputFile),
+    Armored(ArmorWriter<&'a mut OutputFile>),
+}
+
+impl Output<'_> {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Binary(w) => w.flush(),
+            Output::Armored(w) => w.finish().map(drop),
+        }
+    }
+}
+
+impl Write for Output<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Binary(w) => w.write(buf),
+            Output::Armored(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Binary(w) => w.flush(),
+            Output::Armored(w) => w.flush(),
+        }
+    }
+}
+
+/// Whether output files are written atomically, as set by `--atomic`.
+static ATOMIC: AtomicBool = AtomicBool::new(false);
+
+/// Whether warnings are suppressed, as set by `--quiet`.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints a warning to stderr, unless `--quiet` was passed.
+fn warn(message: fmt::Arguments<'_>) {
+    if !QUIET.load(Ordering::Relaxed) {
+        bunt::eprintln!("{[yellow+bold]}: {}", "warning", message);
+    }
+}
+
+/// An output file or stdout, written with [`Write`] and completed with [`OutputFile::commit`].
+///
+/// With `--atomic`, or for key files, a file is written to a temporary file in its destination's
+/// directory, which is synced and renamed over the destination when it's committed. If it's dropped without being
+/// committed, the temporary file is removed and the destination is left as it was.
+struct OutputFile {
+    sink: Sink,
+    path: PathBuf,
+    temp: Option<PathBuf>,
+}
+
+enum Sink {
+    Stdout(io::StdoutLock<'static>),
+    File(File),
+}
+
+impl OutputFile {
+    /// Creates the file at the given path, or a temporary file next to it with `--atomic`.
+    fn create(path: &Path) -> io::Result<OutputFile> {
+        OutputFile::create_with(path, ATOMIC.load(Ordering::Relaxed))
+    }
+
+    /// Creates a temporary file next to the given path, regardless of `--atomic`.
+    fn create_atomic(path: &Path) -> io::Result<OutputFile> {
+        OutputFile::create_with(path, true)
+    }
+
+    fn create_with(path: &Path, atomic: bool) -> io::Result<OutputFile> {
+        // Files which aren't regular files, like /dev/null or FIFOs, can't be replaced by renaming.
+        let atomic = atomic
+            && match fs::metadata(path) {
+                Ok(metadata) => metadata.is_file(),
+                Err(_) => true,
+            };
+        if !atomic {
+            let file = File::create(path)?;
+            return Ok(OutputFile { sink: Sink::File(file), path: path.to_path_buf(), temp: None });
+        }
+
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        loop {
+            let temp = dir.join(format!(".{name}.{:016x}.tmp", OsRng.gen::<u64>()));
+            match File::options().write(true).create_new(true).open(&temp) {
+                Ok(file) => {
+                    return Ok(OutputFile {
+                        sink: Sink::File(file),
+                        path: path.to_path_buf(),
+                        temp: Some(temp),
+                    })
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns the underlying file, unless the output is stdout.
+    const fn file(&self) -> Option<&File> {
+        match &self.sink {
+            Sink::Stdout(_) => None,
+            Sink::File(file) => Some(file),
+        }
+    }
+
+    /// Flushes the output and, with `--atomic`, syncs the temporary file and renames it over the
+    /// destination.
+    fn finish(mut self) -> io::Result<()> {
+        self.flush()?;
+        if let (Sink::File(file), Some(temp)) = (&self.sink, self.temp.take()) {
+            let res = file.sync_all().and_then(|()| fs::rename(&temp, &self.path));
+            if res.is_err() {
+                let _ = fs::remove_file(&temp);
+            }
+            res?;
+
+            // Sync the directory so the rename itself survives a power loss.
+            #[cfg(unix)]
+            if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                File::open(dir)?.sync_all()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Completes the output. See [`OutputFile::finish`].
+    fn commit(self) -> Result<(), CliError> {
+        let path = self.path.clone();
+        self.finish().map_err(|e| CliError::WriteIo(e, path))
+    }
+
+    /// Removes a partially-written output file. With `--atomic`, the destination is left as it was.
+    fn discard(self) {
+        if self.temp.is_none()
+            && self
+                .file()
+                .is_some_and(|file| file.metadata().is_ok_and(|metadata| metadata.is_file()))
+        {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if let Some(temp) = self.temp.take() {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.sink {
+            Sink::Stdout(w) => w.write(buf),
+            Sink::File(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Stdout(w) => w.flush(),
+            Sink::File(w) => w.flush(),
+        }
+    }
+}
+
+impl Seek for OutputFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match &mut self.sink {
+            Sink::Stdout(_) => Err(io::ErrorKind::Unsupported.into()),
+            Sink::File(w) => w.seek(pos),
+        }
+    }
+}
+
+fn open_output(path: &Path, binary: bool) -> Result<OutputFile, CliError> {
+    if path.as_os_str() == "-" {
+        if binary && io::stdout().is_terminal() {
+            return Err(CliError::StdoutTty);
+        }
+        Ok(OutputFile {
+            sink: Sink::Stdout(io::stdout().lock()),
+            path: path.to_path_buf(),
+            temp: None,
+        })
+    } else {
+        OutputFile::create(path).map_err(|e| CliError::WriteIo(e, path.to_path_buf()))
+    }
+}
+
+/// Opens an output as [`open_output`] does, but writes a file atomically regardless of `--atomic`,
+/// for key files which a crash or a full disk mustn't leave truncated.
+fn open_output_atomic(path: &Path, binary: bool) -> Result<OutputFile, CliError> {
+    if path.as_os_str() == "-" {
+        return open_output(path, binary);
+    }
+    OutputFile::create_atomic(path).map_err(|e| CliError::WriteIo(e, path.to_path_buf()))
+}
+
+/// Writes the given bytes to the file at the given path, atomically with `--atomic`.
+fn write_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut output = OutputFile::create(path)?;
+    output.write_all(contents)?;
+    output.finish()
+}
+
+#[derive(Debug, Error)]
+enum CliError {
     #[error("unable to read from stdin: is a tty")]
     StdinTty,
 
     #[error("unable to write to stdout: is a tty")]
     StdoutTty,
 
+    #[error("unable to pad a message from stdin automatically")]
+    StdinPadding,
+
+    #[error("unable to restore metadata to stdout")]
+    StdoutMetadata,
+
+    #[error("unable to decrypt a range of a message from stdin")]
+    StdinRange,
+
+    #[error("unable to recover a message from stdin")]
+    StdinRecover,
+
+    #[error("unable to read both a signature and a message from stdin")]
+    StdinSignature,
+
     #[error("terminal io error")]
     TermIo(#[source] io::Error),
 
     #[error("unable to read from file descriptor {1}")]
     FdIo(#[source] io::Error, i32),
 
+    #[error("environment variable {0} is not set")]
+    MissingPassphraseEnv(String),
+
     #[error("unable to read from {1:?}")]
     ReadIo(#[source] io::Error, PathBuf),
 
@@ -404,17 +3117,129 @@ enum CliError {
     #[error("no passphrase entered")]
     EmptyPassphrase,
 
+    #[error("passphrases do not match")]
+    PassphraseMismatch,
+
+    #[error("weak passphrase: {0} (use --force to use it anyway)")]
+    WeakPassphrase(passphrase_policy::Strength),
+
     #[error("unable to decrypt private key")]
     BadPassphrase(#[source] DecryptError),
 
+    #[error("invalid guard {0:?}")]
+    InvalidGuard(PathBuf, #[source] DecryptError),
+
+    #[error("unable to guard a private key written to stdout")]
+    StdoutGuard,
+
+    #[error("invalid seed: must be exactly 64 bytes")]
+    InvalidSeed,
+
+    #[error("invalid threshold: must be between 1 and the number of shares")]
+    InvalidThreshold,
+
+    #[error("key ceremony ended before it was complete")]
+    IncompleteCeremony,
+
+    #[error("invalid SSH key {0:?}")]
+    InvalidSshKey(PathBuf, #[source] ParseOpenSshKeyError),
+
+    #[error("invalid paper key")]
+    InvalidPaperKey(#[source] ParsePaperKeyError),
+
+    #[error("key store error")]
+    KeyStore(#[source] KeyStoreError),
+
+    #[cfg(unix)]
+    #[error("unable to communicate with agent at {1:?}")]
+    Agent(#[source] io::Error, PathBuf),
+
+    #[error("agents can only print public keys, sign messages, and decrypt messages")]
+    AgentUnsupported,
+
+    #[error("invalid test vectors")]
+    InvalidTestVectors(#[source] TestVectorError),
+
     #[error("digest mismatch")]
     DigestMismatch,
 
     #[error("invalid signature")]
     InvalidSignature,
 
+    #[error("signature is not timestamped")]
+    NotTimestamped,
+
+    #[error("signature timestamp {0} is in the future")]
+    FutureTimestamp(u64),
+
+    #[error("signature timestamp {0} is older than the maximum age")]
+    ExpiredTimestamp(u64),
+
+    #[error("unable to encrypt message")]
+    LimitExceeded(#[source] veil::EncryptError),
+
     #[error("invalid ciphertext")]
     InvalidCiphertext,
+
+    #[error("passphrase-encrypted messages only support balloon hashing")]
+    UnsupportedKdf,
+
+    #[error("message was not encrypted for this private key by the given sender")]
+    NotAReceiver,
+
+    #[error("message has been modified or corrupted")]
+    CorruptMessage,
+
+    #[error("message is truncated")]
+    TruncatedMessage,
+
+    #[error("unsupported message format version {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("message is not seekable")]
+    NotSeekable,
+
+    #[error("message headers are too long")]
+    HeadersTooLong,
+
+    #[error("message plaintext is too long")]
+    PlaintextTooLong,
+
+    #[error("invalid key bundle")]
+    InvalidBundle(#[source] ParseKeyBundleError),
+
+    #[error("invalid revocation")]
+    InvalidRevocation,
+
+    #[error("invalid certification")]
+    InvalidCertification(#[source] ParseCertificationError),
+
+    #[error(transparent)]
+    InvalidPublicKey(ParsePublicKeyError),
+
+    #[error("no readable QR code in {0:?}")]
+    InvalidQrCode(PathBuf),
+
+    #[error("QR code in {0:?} is not a public key")]
+    InvalidQrPublicKey(PathBuf, #[source] ParsePublicKeyError),
+
+    #[error("invalid recipients file {0:?}")]
+    InvalidRecipients(PathBuf, #[source] ParseRecipientsError),
+
+    #[error("invalid manifest {0:?} at line {1}")]
+    InvalidManifest(PathBuf, usize),
+
+    #[error("invalid policy {0:?}")]
+    InvalidPolicy(PathBuf, #[source] Option<toml::de::Error>),
+
+    #[error("only {0} of the required {1} signers signed the message")]
+    NotEnoughSigners(usize, usize),
+
+    #[error("{0} of the signatures in the manifest failed verification")]
+    FailedSignatures(usize),
+
+    #[error(transparent)]
+    Veil(#[from] veil::Error),
 }
 
 impl CliError {
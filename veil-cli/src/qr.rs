@@ -0,0 +1,311 @@
+//! QR codes of public key URIs, written and read as PNG images.
+//!
+//! Reading is limited to clean, upright images, like the ones written here or screenshots of them:
+//! modules are sampled on a grid fitted to the code's bounding box, and errors aren't corrected.
+
+use std::io::{self, Read, Write};
+
+use qrcode::{
+    bits::Bits,
+    canvas::{Canvas, MaskPattern, Module},
+    ec::construct_codewords,
+    types::{Color, EcLevel, Version},
+    QrCode,
+};
+
+/// The width and height of each module, in pixels.
+const MODULE_PIXELS: usize = 8;
+
+/// The width of the light border around the code, in modules.
+const QUIET_ZONE: usize = 4;
+
+/// Writes the given data as a QR code in a grayscale PNG image.
+pub fn write(data: &[u8], writer: impl Write) -> io::Result<()> {
+    let code = QrCode::with_error_correction_level(data, EcLevel::M)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let width = code.width();
+    let size = (width + 2 * QUIET_ZONE) * MODULE_PIXELS;
+
+    let mut pixels = vec![0xFF; size * size];
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let (x, y) = ((i % width + QUIET_ZONE) * MODULE_PIXELS, (i / width + QUIET_ZONE));
+            for row in y * MODULE_PIXELS..(y + 1) * MODULE_PIXELS {
+                pixels[row * size + x..][..MODULE_PIXELS].fill(0);
+            }
+        }
+    }
+
+    let size = u32::try_from(size).expect("should be a reasonable size");
+    let mut encoder = png::Encoder::new(writer, size, size);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(())
+}
+
+/// Reads the data of the QR code in the given PNG image, if it has a readable one.
+pub fn read(reader: impl Read) -> Option<Vec<u8>> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).ok()?;
+
+    // Threshold each pixel's luminance, treating transparent pixels as light.
+    let channels = info.color_type.samples();
+    let dark = buf[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|px| {
+            let (luma, alpha) = match px {
+                [l] => (u32::from(*l), 0xFF),
+                [l, a] => (u32::from(*l), *a),
+                [r, g, b] => ((u32::from(*r) + u32::from(*g) + u32::from(*b)) / 3, 0xFF),
+                [r, g, b, a, ..] => ((u32::from(*r) + u32::from(*g) + u32::from(*b)) / 3, *a),
+                [] => (0xFF, 0xFF),
+            };
+            alpha >= 0x80 && luma < 0x80
+        })
+        .collect::<Vec<_>>();
+
+    let modules = sample(&dark, info.width as usize, info.height as usize)?;
+    decode(&modules)
+}
+
+/// Fits a grid of modules to the bounding box of the dark pixels and samples the center of each.
+/// Returns the modules, row by row, if the grid has the width of a QR code.
+fn sample(dark: &[bool], width: usize, height: usize) -> Option<Vec<Vec<bool>>> {
+    let is_dark = |x: usize, y: usize| dark[y * width + x];
+    let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+    for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+        if is_dark(x, y) {
+            (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+        }
+    }
+    if left > right {
+        return None;
+    }
+
+    // The top edge of the top-left finder pattern is seven modules wide.
+    let finder = (left..=right).take_while(|&x| is_dark(x, top)).count();
+    let size = (right - left + 1) as f64;
+    let modules = (size / (finder as f64 / 7.0)).round() as usize;
+    if modules < 21
+        || !(modules - 17).is_multiple_of(4)
+        || (bottom - top + 1).abs_diff(right - left + 1) > 1
+    {
+        return None;
+    }
+
+    let pitch = size / modules as f64;
+    let center = |i: usize| ((i as f64 + 0.5) * pitch) as usize;
+    Some(
+        (0..modules)
+            .map(|y| (0..modules).map(|x| is_dark(left + center(x), top + center(y))).collect())
+            .collect(),
+    )
+}
+
+/// Decodes the data of a QR code from its modules.
+fn decode(modules: &[Vec<bool>]) -> Option<Vec<u8>> {
+    let version = Version::Normal(i16::try_from((modules.len() - 17) / 4).ok()?);
+    let width = version.width();
+    let is_dark = |x: i16, y: i16| modules[y as usize][x as usize];
+
+    // Data modules are the ones left empty after the functional patterns are drawn.
+    let mut blank = Canvas::new(version, EcLevel::L);
+    blank.draw_all_functional_patterns();
+    let coords = (0..width).flat_map(|y| (0..width).map(move |x| (x, y)));
+
+    // Find the error correction level and mask whose functional patterns, including the format
+    // info, best match the code's.
+    let (ec_level, masked) = [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H]
+        .into_iter()
+        .flat_map(|ec_level| MASK_PATTERNS.into_iter().map(move |mask| (ec_level, mask)))
+        .map(|(ec_level, mask)| {
+            let mut canvas = Canvas::new(version, ec_level);
+            canvas.draw_all_functional_patterns();
+            canvas.apply_mask(mask);
+            (ec_level, canvas)
+        })
+        .min_by_key(|(_, canvas)| {
+            coords
+                .clone()
+                .filter(|&(x, y)| blank.get(x, y) != Module::Empty)
+                .filter(|&(x, y)| canvas.get(x, y).is_dark() != is_dark(x, y))
+                .count()
+        })?;
+
+    // Read the data bits in placement order and unmask them.
+    let bits = placement_order(width)
+        .filter(|&(x, y)| blank.get(x, y) == Module::Empty)
+        .map(|(x, y)| masked.get(x, y).is_dark() != is_dark(x, y))
+        .collect::<Vec<_>>();
+    let codewords = bits
+        .as_chunks::<8>()
+        .0
+        .iter()
+        .map(|byte| byte.iter().fold(0u8, |acc, &bit| acc << 1 | u8::from(bit)))
+        .collect::<Vec<_>>();
+
+    // De-interleave the data codewords, finding each one's position by interleaving the positions.
+    let data_len = Bits::new(version).max_len(ec_level).ok()? / 8;
+    let interleave = |shift: usize| {
+        let positions = (0..data_len).map(|i| (i >> shift) as u8).collect::<Vec<_>>();
+        construct_codewords(&positions, version, ec_level).ok().map(|(data, _)| data)
+    };
+    let (low, high) = (interleave(0)?, interleave(8)?);
+    let mut data = vec![0u8; data_len];
+    for (i, &b) in codewords.get(..data_len)?.iter().enumerate() {
+        data[usize::from(high[i]) << 8 | usize::from(low[i])] = b;
+    }
+
+    read_segments(&data, version)
+}
+
+/// Reads the numeric, alphanumeric, and byte segments of a QR code's data.
+fn read_segments(data: &[u8], version: Version) -> Option<Vec<u8>> {
+    let mut bits = BitReader { data, pos: 0 };
+    let v = match version {
+        Version::Normal(v) | Version::Micro(v) => v,
+    };
+    let mut out = Vec::new();
+    while bits.remaining() >= 4 {
+        match bits.read(4)? {
+            0b0000 => break,
+            0b0001 => {
+                let mut len = bits.read(if v <= 9 {
+                    10
+                } else if v <= 26 {
+                    12
+                } else {
+                    14
+                })?;
+                while len > 0 {
+                    let digits = len.min(3);
+                    let n = bits.read([0, 4, 7, 10][digits])?;
+                    out.extend(format!("{n:0digits$}").bytes());
+                    len -= digits;
+                }
+            }
+            0b0010 => {
+                let mut len = bits.read(if v <= 9 {
+                    9
+                } else if v <= 26 {
+                    11
+                } else {
+                    13
+                })?;
+                while len > 0 {
+                    if len >= 2 {
+                        let n = bits.read(11)?;
+                        out.extend([*ALPHANUMERIC.get(n / 45)?, *ALPHANUMERIC.get(n % 45)?]);
+                        len -= 2;
+                    } else {
+                        out.push(*ALPHANUMERIC.get(bits.read(6)?)?);
+                        len -= 1;
+                    }
+                }
+            }
+            0b0100 => {
+                let len = bits.read(if v <= 9 { 8 } else { 16 })?;
+                for _ in 0..len {
+                    out.push(bits.read(8)? as u8);
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+/// Returns the coordinates of a QR code's modules in data placement order: upwards and downwards
+/// in alternating two-module columns from the right, skipping the vertical timing pattern.
+fn placement_order(width: i16) -> impl Iterator<Item = (i16, i16)> {
+    (0..width / 2).flat_map(move |column| {
+        let right = width - 1 - 2 * column;
+        let right = if right <= 6 { right - 1 } else { right };
+        (0..width).flat_map(move |i| {
+            let y = if column % 2 == 0 { width - 1 - i } else { i };
+            [(right, y), (right - 1, y)]
+        })
+    })
+}
+
+/// Reads big-endian bit fields from a byte slice.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    const fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, n: usize) -> Option<usize> {
+        if n > self.remaining() {
+            return None;
+        }
+        let value = (self.pos..self.pos + n)
+            .fold(0, |acc, i| acc << 1 | usize::from(self.data[i / 8] >> (7 - i % 8) & 1));
+        self.pos += n;
+        Some(value)
+    }
+}
+
+const MASK_PATTERNS: [MaskPattern; 8] = [
+    MaskPattern::Checkerboard,
+    MaskPattern::HorizontalLines,
+    MaskPattern::VerticalLines,
+    MaskPattern::DiagonalLines,
+    MaskPattern::LargeCheckerboard,
+    MaskPattern::Fields,
+    MaskPattern::Diamonds,
+    MaskPattern::Meadow,
+];
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for data in [
+            &b"veil:pk:TkUWybv8fAvsHPhauPj7edUTVdCHuCFHazA6RjnvwJa"[..],
+            b"HELLO WORLD 0123456789 hello world",
+            b"31415926535897932384626433832795028841971693993751058209749445923078164062862",
+            &[b'x'; 500],
+        ] {
+            let mut png = Vec::new();
+            write(data, &mut png).expect("should write");
+            assert_eq!(Some(data.to_vec()), read(png.as_slice()));
+        }
+    }
+
+    #[test]
+    fn placement() {
+        // The placement order visits every data module once, in the order the encoder uses.
+        let data = b"veil:pk:TkUWybv8fAvsHPhauPj7edUTVdCHuCFHazA6RjnvwJa";
+        let code = QrCode::with_error_correction_level(data, EcLevel::M).expect("should encode");
+        let modules = code
+            .to_colors()
+            .chunks(code.width())
+            .map(|row| row.iter().map(|&c| c == Color::Dark).collect())
+            .collect::<Vec<_>>();
+        assert_eq!(Some(data.to_vec()), decode(&modules));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(None, read(&b"not a png"[..]));
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 64, 64);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.write_header().expect("should write").write_image_data(&[0xFF; 64 * 64]).unwrap();
+        assert_eq!(None, read(png.as_slice()), "a blank image has no code");
+    }
+}
@@ -0,0 +1,282 @@
+#ifndef VEIL_H
+#define VEIL_H
+
+/* Generated by cbindgen. Run `UPDATE_EXPECT=1 cargo test -p veil-ffi` to update. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The result of a call to the library.
+ */
+typedef enum VeilStatus {
+  /**
+   * The call was successful.
+   */
+  VEIL_STATUS_OK = 0,
+  /**
+   * A required pointer was null.
+   */
+  VEIL_STATUS_NULL_POINTER,
+  /**
+   * A public key couldn't be parsed.
+   */
+  VEIL_STATUS_INVALID_PUBLIC_KEY,
+  /**
+   * A signature couldn't be decoded or didn't verify.
+   */
+  VEIL_STATUS_INVALID_SIGNATURE,
+  /**
+   * An encrypted private key couldn't be decrypted with the given passphrase.
+   */
+  VEIL_STATUS_INVALID_PASSPHRASE,
+  /**
+   * None of a message's headers could be decrypted by the receiver.
+   */
+  VEIL_STATUS_NOT_A_RECEIVER,
+  /**
+   * A message has been modified or corrupted.
+   */
+  VEIL_STATUS_CORRUPT,
+  /**
+   * A message is truncated.
+   */
+  VEIL_STATUS_TRUNCATED,
+  /**
+   * A message's format version isn't supported.
+   */
+  VEIL_STATUS_UNSUPPORTED_VERSION,
+  /**
+   * Any other error.
+   */
+  VEIL_STATUS_OTHER,
+  /**
+   * A message would have too many receivers, too much padding, or too long a plaintext.
+   */
+  VEIL_STATUS_LIMIT_EXCEEDED,
+} VeilStatus;
+
+/**
+ * A private key.
+ */
+typedef struct VeilPrivateKey VeilPrivateKey;
+
+/**
+ * A public key.
+ */
+typedef struct VeilPublicKey VeilPublicKey;
+
+/**
+ * A byte buffer allocated by the library. Must be freed with [`veil_buffer_free`].
+ */
+typedef struct VeilBuffer {
+  /**
+   * A pointer to the buffer's bytes.
+   */
+  uint8_t *data;
+  /**
+   * The number of bytes in the buffer.
+   */
+  size_t len;
+} VeilBuffer;
+
+/**
+ * A pointer to a sequence of bytes and its length.
+ */
+typedef struct VeilSlice {
+  /**
+   * A pointer to the bytes, which may be null if `len` is zero.
+   */
+  const uint8_t *data;
+  /**
+   * The number of bytes.
+   */
+  size_t len;
+} VeilSlice;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Generates a random private key and writes its handle to `out`.
+ *
+ * # Safety
+ *
+ * `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_private_key_generate(struct VeilPrivateKey **out);
+
+/**
+ * Decrypts a private key encrypted with a passphrase, like those written by `veil private-key`,
+ * and writes its handle to `out`.
+ *
+ * # Safety
+ *
+ * Each pointer must be null or valid for reads of its length, and `out` must be null or valid for
+ * writes.
+ */
+enum VeilStatus veil_private_key_load(const uint8_t *ciphertext,
+                                      size_t ciphertext_len,
+                                      const uint8_t *passphrase,
+                                      size_t passphrase_len,
+                                      struct VeilPrivateKey **out);
+
+/**
+ * Encrypts a private key with a passphrase and the given `veil.pbenc` costs and writes it to
+ * `out`.
+ *
+ * # Safety
+ *
+ * `private_key` must be null or a live handle, `passphrase` must be null or valid for reads of
+ * `passphrase_len` bytes, and `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_private_key_store(const struct VeilPrivateKey *private_key,
+                                       const uint8_t *passphrase,
+                                       size_t passphrase_len,
+                                       uint8_t time_cost,
+                                       uint8_t memory_cost,
+                                       struct VeilBuffer *out);
+
+/**
+ * Writes a handle to the public key of a private key to `out`.
+ *
+ * # Safety
+ *
+ * `private_key` must be null or a live handle, and `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_private_key_public_key(const struct VeilPrivateKey *private_key,
+                                            struct VeilPublicKey **out);
+
+/**
+ * Frees a private key handle.
+ *
+ * # Safety
+ *
+ * `private_key` must be null or a live handle, which must not be used afterwards.
+ */
+void veil_private_key_free(struct VeilPrivateKey *private_key);
+
+/**
+ * Parses a public key from its UTF-8 string form, which isn't NUL-terminated, and writes its
+ * handle to `out`.
+ *
+ * # Safety
+ *
+ * `s` must be null or valid for reads of `s_len` bytes, and `out` must be null or valid for
+ * writes.
+ */
+enum VeilStatus veil_public_key_parse(const uint8_t *s, size_t s_len, struct VeilPublicKey **out);
+
+/**
+ * Writes the UTF-8 string form of a public key, without a NUL terminator, to `out`.
+ *
+ * # Safety
+ *
+ * `public_key` must be null or a live handle, and `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_public_key_to_string(const struct VeilPublicKey *public_key,
+                                          struct VeilBuffer *out);
+
+/**
+ * Frees a public key handle.
+ *
+ * # Safety
+ *
+ * `public_key` must be null or a live handle, which must not be used afterwards.
+ */
+void veil_public_key_free(struct VeilPublicKey *public_key);
+
+/**
+ * Encrypts a message from the sender for the given receivers, with the given number of fake
+ * receivers and bytes of padding, and writes the ciphertext to `out`.
+ *
+ * # Safety
+ *
+ * `sender` must be null or a live handle, `receivers` must be null or valid for reads of
+ * `receivers_len` pointers which are each null or a live handle, `plaintext` must be null or valid
+ * for reads of `plaintext_len` bytes, and `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_encrypt(const struct VeilPrivateKey *sender,
+                             const struct VeilPublicKey *const *receivers,
+                             size_t receivers_len,
+                             size_t fakes,
+                             size_t padding,
+                             const uint8_t *plaintext,
+                             size_t plaintext_len,
+                             struct VeilBuffer *out);
+
+/**
+ * Decrypts a message from the sender and writes the plaintext to `out`.
+ *
+ * # Safety
+ *
+ * `receiver` and `sender` must each be null or a live handle, `ciphertext` must be null or valid
+ * for reads of `ciphertext_len` bytes, and `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_decrypt(const struct VeilPrivateKey *receiver,
+                             const struct VeilPublicKey *sender,
+                             const uint8_t *ciphertext,
+                             size_t ciphertext_len,
+                             struct VeilBuffer *out);
+
+/**
+ * Signs a message and writes the encoded signature to `out`.
+ *
+ * # Safety
+ *
+ * `signer` must be null or a live handle, `message` must be null or valid for reads of
+ * `message_len` bytes, and `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_sign(const struct VeilPrivateKey *signer,
+                          const uint8_t *message,
+                          size_t message_len,
+                          struct VeilBuffer *out);
+
+/**
+ * Verifies an encoded signature of a message by the signer. Returns [`VeilStatus::Ok`] iff the
+ * signature is valid.
+ *
+ * # Safety
+ *
+ * `signer` must be null or a live handle, and `message` and `sig` must each be null or valid for
+ * reads of their lengths.
+ */
+enum VeilStatus veil_verify(const struct VeilPublicKey *signer,
+                            const uint8_t *message,
+                            size_t message_len,
+                            const uint8_t *sig,
+                            size_t sig_len);
+
+/**
+ * Digests a message with the given metadata values and writes the encoded digest to `out`.
+ *
+ * # Safety
+ *
+ * `metadata` must be null or valid for reads of `metadata_len` slices which are each valid for
+ * reads of their lengths, `message` must be null or valid for reads of `message_len` bytes, and
+ * `out` must be null or valid for writes.
+ */
+enum VeilStatus veil_digest(const struct VeilSlice *metadata,
+                            size_t metadata_len,
+                            const uint8_t *message,
+                            size_t message_len,
+                            struct VeilBuffer *out);
+
+/**
+ * Frees a buffer allocated by the library.
+ *
+ * # Safety
+ *
+ * `buf` must have been returned by the library and not already freed.
+ */
+void veil_buffer_free(struct VeilBuffer buf);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VEIL_H */
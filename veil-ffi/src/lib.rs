@@ -0,0 +1,633 @@
+//! C bindings for Veil.
+//!
+//! This crate builds Veil as a shared or static library with a C ABI, so it can be called from
+//! languages like Swift (via a module map over `include/veil.h`) and Kotlin (via JNI or JNA). The
+//! header is generated by cbindgen and checked in; a test fails if it's out of date.
+//!
+//! The conventions are the same for every function:
+//!
+//! * Private and public keys are opaque handles which are created by the library and must be freed
+//!   with [`veil_private_key_free`] and [`veil_public_key_free`].
+//! * Input bytes are passed as a pointer and a length. The pointer may be null if the length is
+//!   zero.
+//! * Output bytes are returned in a [`VeilBuffer`] allocated by the library, which must be freed with
+//!   [`veil_buffer_free`].
+//! * Every function which can fail returns a [`VeilStatus`], and only writes to its output pointer
+//!   if it returns [`VeilStatus::Ok`].
+//!
+//! Panics abort the process instead of unwinding across the C ABI.
+
+use std::{ptr, slice};
+
+use rand::rngs::OsRng;
+use veil::{
+    DecryptError, Digest, EncryptError, EncryptOptions, PrivateKey, PublicKey, Signature,
+    VerifyError,
+};
+
+/// The result of a call to the library.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VeilStatus {
+    /// The call was successful.
+    Ok = 0,
+
+    /// A required pointer was null.
+    NullPointer,
+
+    /// A public key couldn't be parsed.
+    InvalidPublicKey,
+
+    /// A signature couldn't be decoded or didn't verify.
+    InvalidSignature,
+
+    /// An encrypted private key couldn't be decrypted with the given passphrase.
+    InvalidPassphrase,
+
+    /// None of a message's headers could be decrypted by the receiver.
+    NotAReceiver,
+
+    /// A message has been modified or corrupted.
+    Corrupt,
+
+    /// A message is truncated.
+    Truncated,
+
+    /// A message's format version isn't supported.
+    UnsupportedVersion,
+
+    /// Any other error.
+    Other,
+
+    /// A message would have too many receivers, too much padding, or too long a plaintext.
+    LimitExceeded,
+}
+
+/// A private key.
+#[derive(Debug)]
+pub struct VeilPrivateKey(PrivateKey);
+
+/// A public key.
+#[derive(Debug)]
+pub struct VeilPublicKey(PublicKey);
+
+/// A byte buffer allocated by the library. Must be freed with [`veil_buffer_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct VeilBuffer {
+    /// A pointer to the buffer's bytes.
+    pub data: *mut u8,
+
+    /// The number of bytes in the buffer.
+    pub len: usize,
+}
+
+impl VeilBuffer {
+    fn new(b: Vec<u8>) -> VeilBuffer {
+        let b = Box::into_raw(b.into_boxed_slice());
+        VeilBuffer { data: b.cast(), len: b.len() }
+    }
+}
+
+/// A pointer to a sequence of bytes and its length.
+#[repr(C)]
+#[derive(Debug)]
+pub struct VeilSlice {
+    /// A pointer to the bytes, which may be null if `len` is zero.
+    pub data: *const u8,
+
+    /// The number of bytes.
+    pub len: usize,
+}
+
+/// Generates a random private key and writes its handle to `out`.
+///
+/// # Safety
+///
+/// `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_private_key_generate(out: *mut *mut VeilPrivateKey) -> VeilStatus {
+    write_handle(out, VeilPrivateKey(PrivateKey::random(OsRng)))
+}
+
+/// Decrypts a private key encrypted with a passphrase, like those written by `veil private-key`,
+/// and writes its handle to `out`.
+///
+/// # Safety
+///
+/// Each pointer must be null or valid for reads of its length, and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_private_key_load(
+    ciphertext: *const u8,
+    ciphertext_len: usize,
+    passphrase: *const u8,
+    passphrase_len: usize,
+    out: *mut *mut VeilPrivateKey,
+) -> VeilStatus {
+    let (Some(ciphertext), Some(passphrase)) =
+        (bytes(ciphertext, ciphertext_len), bytes(passphrase, passphrase_len))
+    else {
+        return VeilStatus::NullPointer;
+    };
+    match PrivateKey::load(ciphertext, passphrase) {
+        Ok(private_key) => write_handle(out, VeilPrivateKey(private_key)),
+        Err(e) => decrypt_status(&e),
+    }
+}
+
+/// Encrypts a private key with a passphrase and the given `veil.pbenc` costs and writes it to
+/// `out`.
+///
+/// # Safety
+///
+/// `private_key` must be null or a live handle, `passphrase` must be null or valid for reads of
+/// `passphrase_len` bytes, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_private_key_store(
+    private_key: *const VeilPrivateKey,
+    passphrase: *const u8,
+    passphrase_len: usize,
+    time_cost: u8,
+    memory_cost: u8,
+    out: *mut VeilBuffer,
+) -> VeilStatus {
+    let (Some(private_key), Some(passphrase)) =
+        (private_key.as_ref(), bytes(passphrase, passphrase_len))
+    else {
+        return VeilStatus::NullPointer;
+    };
+    let mut b = Vec::new();
+    match private_key.0.store(&mut b, OsRng, passphrase, time_cost, memory_cost, 1) {
+        Ok(_) => write_buffer(out, b),
+        Err(_) => VeilStatus::Other,
+    }
+}
+
+/// Writes a handle to the public key of a private key to `out`.
+///
+/// # Safety
+///
+/// `private_key` must be null or a live handle, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_private_key_public_key(
+    private_key: *const VeilPrivateKey,
+    out: *mut *mut VeilPublicKey,
+) -> VeilStatus {
+    let Some(private_key) = private_key.as_ref() else {
+        return VeilStatus::NullPointer;
+    };
+    write_handle(out, VeilPublicKey(private_key.0.public_key()))
+}
+
+/// Frees a private key handle.
+///
+/// # Safety
+///
+/// `private_key` must be null or a live handle, which must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn veil_private_key_free(private_key: *mut VeilPrivateKey) {
+    if !private_key.is_null() {
+        drop(Box::from_raw(private_key));
+    }
+}
+
+/// Parses a public key from its UTF-8 string form, which isn't NUL-terminated, and writes its
+/// handle to `out`.
+///
+/// # Safety
+///
+/// `s` must be null or valid for reads of `s_len` bytes, and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_public_key_parse(
+    s: *const u8,
+    s_len: usize,
+    out: *mut *mut VeilPublicKey,
+) -> VeilStatus {
+    let Some(s) = bytes(s, s_len) else {
+        return VeilStatus::NullPointer;
+    };
+    match std::str::from_utf8(s).ok().and_then(|s| s.parse().ok()) {
+        Some(public_key) => write_handle(out, VeilPublicKey(public_key)),
+        None => VeilStatus::InvalidPublicKey,
+    }
+}
+
+/// Writes the UTF-8 string form of a public key, without a NUL terminator, to `out`.
+///
+/// # Safety
+///
+/// `public_key` must be null or a live handle, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_public_key_to_string(
+    public_key: *const VeilPublicKey,
+    out: *mut VeilBuffer,
+) -> VeilStatus {
+    let Some(public_key) = public_key.as_ref() else {
+        return VeilStatus::NullPointer;
+    };
+    write_buffer(out, public_key.0.to_string().into_bytes())
+}
+
+/// Frees a public key handle.
+///
+/// # Safety
+///
+/// `public_key` must be null or a live handle, which must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn veil_public_key_free(public_key: *mut VeilPublicKey) {
+    if !public_key.is_null() {
+        drop(Box::from_raw(public_key));
+    }
+}
+
+/// Encrypts a message from the sender for the given receivers, with the given number of fake
+/// receivers and bytes of padding, and writes the ciphertext to `out`.
+///
+/// # Safety
+///
+/// `sender` must be null or a live handle, `receivers` must be null or valid for reads of
+/// `receivers_len` pointers which are each null or a live handle, `plaintext` must be null or valid
+/// for reads of `plaintext_len` bytes, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_encrypt(
+    sender: *const VeilPrivateKey,
+    receivers: *const *const VeilPublicKey,
+    receivers_len: usize,
+    fakes: usize,
+    padding: usize,
+    plaintext: *const u8,
+    plaintext_len: usize,
+    out: *mut VeilBuffer,
+) -> VeilStatus {
+    let (Some(sender), Some(receivers), Some(plaintext)) =
+        (sender.as_ref(), items(receivers, receivers_len), bytes(plaintext, plaintext_len))
+    else {
+        return VeilStatus::NullPointer;
+    };
+    let Some(receivers) =
+        receivers.iter().map(|r| r.as_ref().map(|r| r.0)).collect::<Option<Vec<_>>>()
+    else {
+        return VeilStatus::NullPointer;
+    };
+
+    let mut ciphertext = Vec::new();
+    let options = EncryptOptions::new(&receivers).fakes(fakes).padding(padding);
+    match sender.0.encrypt_slice(OsRng, plaintext, &mut ciphertext, &options) {
+        Ok(_) => write_buffer(out, ciphertext),
+        Err(
+            EncryptError::TooManyReceivers
+            | EncryptError::TooMuchPadding
+            | EncryptError::MessageTooLong,
+        ) => VeilStatus::LimitExceeded,
+        Err(_) => VeilStatus::Other,
+    }
+}
+
+/// Decrypts a message from the sender and writes the plaintext to `out`.
+///
+/// # Safety
+///
+/// `receiver` and `sender` must each be null or a live handle, `ciphertext` must be null or valid
+/// for reads of `ciphertext_len` bytes, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_decrypt(
+    receiver: *const VeilPrivateKey,
+    sender: *const VeilPublicKey,
+    ciphertext: *const u8,
+    ciphertext_len: usize,
+    out: *mut VeilBuffer,
+) -> VeilStatus {
+    let (Some(receiver), Some(sender), Some(ciphertext)) =
+        (receiver.as_ref(), sender.as_ref(), bytes(ciphertext, ciphertext_len))
+    else {
+        return VeilStatus::NullPointer;
+    };
+
+    let mut plaintext = Vec::new();
+    match receiver.0.decrypt_slice(ciphertext, &mut plaintext, &sender.0, &[]) {
+        Ok(_) => write_buffer(out, plaintext),
+        Err(e) => decrypt_status(&e),
+    }
+}
+
+/// Signs a message and writes the encoded signature to `out`.
+///
+/// # Safety
+///
+/// `signer` must be null or a live handle, `message` must be null or valid for reads of
+/// `message_len` bytes, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_sign(
+    signer: *const VeilPrivateKey,
+    message: *const u8,
+    message_len: usize,
+    out: *mut VeilBuffer,
+) -> VeilStatus {
+    let (Some(signer), Some(message)) = (signer.as_ref(), bytes(message, message_len)) else {
+        return VeilStatus::NullPointer;
+    };
+    match signer.0.sign(OsRng, message) {
+        Ok(sig) => write_buffer(out, sig.encode().to_vec()),
+        Err(_) => VeilStatus::Other,
+    }
+}
+
+/// Verifies an encoded signature of a message by the signer. Returns [`VeilStatus::Ok`] iff the
+/// signature is valid.
+///
+/// # Safety
+///
+/// `signer` must be null or a live handle, and `message` and `sig` must each be null or valid for
+/// reads of their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn veil_verify(
+    signer: *const VeilPublicKey,
+    message: *const u8,
+    message_len: usize,
+    sig: *const u8,
+    sig_len: usize,
+) -> VeilStatus {
+    let (Some(signer), Some(message), Some(sig)) =
+        (signer.as_ref(), bytes(message, message_len), bytes(sig, sig_len))
+    else {
+        return VeilStatus::NullPointer;
+    };
+    let Some(sig) = Signature::decode(sig) else {
+        return VeilStatus::InvalidSignature;
+    };
+    match signer.0.verify(message, &sig) {
+        Ok(()) => VeilStatus::Ok,
+        Err(VerifyError::InvalidSignature) => VeilStatus::InvalidSignature,
+        Err(_) => VeilStatus::Other,
+    }
+}
+
+/// Digests a message with the given metadata values and writes the encoded digest to `out`.
+///
+/// # Safety
+///
+/// `metadata` must be null or valid for reads of `metadata_len` slices which are each valid for
+/// reads of their lengths, `message` must be null or valid for reads of `message_len` bytes, and
+/// `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn veil_digest(
+    metadata: *const VeilSlice,
+    metadata_len: usize,
+    message: *const u8,
+    message_len: usize,
+    out: *mut VeilBuffer,
+) -> VeilStatus {
+    let (Some(metadata), Some(message)) =
+        (items(metadata, metadata_len), bytes(message, message_len))
+    else {
+        return VeilStatus::NullPointer;
+    };
+    let Some(metadata) = metadata.iter().map(|m| bytes(m.data, m.len)).collect::<Option<Vec<_>>>()
+    else {
+        return VeilStatus::NullPointer;
+    };
+    match Digest::new(&metadata, message) {
+        Ok(digest) => write_buffer(out, digest.encode().to_vec()),
+        Err(_) => VeilStatus::Other,
+    }
+}
+
+/// Frees a buffer allocated by the library.
+///
+/// # Safety
+///
+/// `buf` must have been returned by the library and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn veil_buffer_free(buf: VeilBuffer) {
+    if !buf.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buf.data, buf.len)));
+    }
+}
+
+/// Returns the given bytes as a slice, or `None` if the pointer is null and the length isn't zero.
+const unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    items(data, len)
+}
+
+/// Returns the given items as a slice, or `None` if the pointer is null and the length isn't zero.
+const unsafe fn items<'a, T>(data: *const T, len: usize) -> Option<&'a [T]> {
+    match (data.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(data, len)),
+    }
+}
+
+/// Writes a handle for the given value to `out`, if it isn't null.
+unsafe fn write_handle<T>(out: *mut *mut T, value: T) -> VeilStatus {
+    if out.is_null() {
+        return VeilStatus::NullPointer;
+    }
+    *out = Box::into_raw(Box::new(value));
+    VeilStatus::Ok
+}
+
+/// Writes a buffer of the given bytes to `out`, if it isn't null.
+unsafe fn write_buffer(out: *mut VeilBuffer, b: Vec<u8>) -> VeilStatus {
+    if out.is_null() {
+        return VeilStatus::NullPointer;
+    }
+    *out = VeilBuffer::new(b);
+    VeilStatus::Ok
+}
+
+/// Returns the status for the given decryption error.
+const fn decrypt_status(e: &DecryptError) -> VeilStatus {
+    match e {
+        DecryptError::InvalidCiphertext => VeilStatus::InvalidPassphrase,
+        DecryptError::NotAReceiver => VeilStatus::NotAReceiver,
+        DecryptError::Corrupt => VeilStatus::Corrupt,
+        DecryptError::Truncated => VeilStatus::Truncated,
+        DecryptError::UnsupportedVersion(_) => VeilStatus::UnsupportedVersion,
+        _ => VeilStatus::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use expect_test::expect_file;
+
+    use super::*;
+
+    #[test]
+    fn header() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config = cbindgen::Config::from_file(dir.join("cbindgen.toml"))
+            .expect("should load cbindgen config");
+        let mut header = Vec::new();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(dir.join("src/lib.rs"))
+            .generate()
+            .expect("should generate header")
+            .write(&mut header);
+        expect_file!["../include/veil.h"]
+            .assert_eq(&String::from_utf8(header).expect("should be UTF-8"));
+    }
+
+    #[test]
+    fn round_trip() {
+        unsafe {
+            let (sender, receiver) = (generate(), generate());
+            let receiver_pk = public_key(receiver);
+            let sender_pk = public_key(sender);
+
+            // Encrypt a message for the receiver and decrypt it.
+            let mut ciphertext = empty();
+            assert_eq!(
+                VeilStatus::Ok,
+                veil_encrypt(
+                    sender,
+                    &receiver_pk.cast_const(),
+                    1,
+                    2,
+                    10,
+                    b"hello".as_ptr(),
+                    5,
+                    &mut ciphertext
+                )
+            );
+            let mut plaintext = empty();
+            assert_eq!(
+                VeilStatus::Ok,
+                veil_decrypt(receiver, sender_pk, ciphertext.data, ciphertext.len, &mut plaintext)
+            );
+            assert_eq!(b"hello", slice::from_raw_parts(plaintext.data, plaintext.len));
+
+            // The sender can't decrypt it.
+            assert_eq!(
+                VeilStatus::NotAReceiver,
+                veil_decrypt(sender, sender_pk, ciphertext.data, ciphertext.len, &mut empty())
+            );
+
+            // Too many fake receivers are rejected rather than overflowing.
+            assert_eq!(
+                VeilStatus::LimitExceeded,
+                veil_encrypt(
+                    sender,
+                    &receiver_pk.cast_const(),
+                    1,
+                    usize::MAX,
+                    10,
+                    b"hello".as_ptr(),
+                    5,
+                    &mut empty()
+                )
+            );
+
+            // Sign a message and verify the signature.
+            let mut sig = empty();
+            assert_eq!(VeilStatus::Ok, veil_sign(sender, b"hello".as_ptr(), 5, &mut sig));
+            assert_eq!(
+                VeilStatus::Ok,
+                veil_verify(sender_pk, b"hello".as_ptr(), 5, sig.data, sig.len)
+            );
+            assert_eq!(
+                VeilStatus::InvalidSignature,
+                veil_verify(sender_pk, b"world".as_ptr(), 5, sig.data, sig.len)
+            );
+
+            veil_buffer_free(ciphertext);
+            veil_buffer_free(plaintext);
+            veil_buffer_free(sig);
+            veil_public_key_free(receiver_pk);
+            veil_public_key_free(sender_pk);
+            veil_private_key_free(receiver);
+            veil_private_key_free(sender);
+        }
+    }
+
+    #[test]
+    fn keys() {
+        unsafe {
+            let private_key = generate();
+            let public_key = public_key(private_key);
+
+            // Store and load the private key.
+            let mut stored = empty();
+            assert_eq!(
+                VeilStatus::Ok,
+                veil_private_key_store(private_key, b"secret".as_ptr(), 6, 0, 0, &mut stored)
+            );
+            let mut loaded = ptr::null_mut();
+            assert_eq!(
+                VeilStatus::InvalidPassphrase,
+                veil_private_key_load(stored.data, stored.len, b"wrong".as_ptr(), 5, &mut loaded)
+            );
+            assert_eq!(
+                VeilStatus::Ok,
+                veil_private_key_load(stored.data, stored.len, b"secret".as_ptr(), 6, &mut loaded)
+            );
+            assert_eq!((*private_key).0.public_key(), (*loaded).0.public_key());
+
+            // Round-trip the public key through its string form.
+            let mut s = empty();
+            assert_eq!(VeilStatus::Ok, veil_public_key_to_string(public_key, &mut s));
+            let mut parsed = ptr::null_mut();
+            assert_eq!(VeilStatus::Ok, veil_public_key_parse(s.data, s.len, &mut parsed));
+            assert_eq!((*public_key).0, (*parsed).0);
+            assert_eq!(
+                VeilStatus::InvalidPublicKey,
+                veil_public_key_parse(b"nope".as_ptr(), 4, &mut parsed)
+            );
+
+            // Null pointers are rejected.
+            assert_eq!(
+                VeilStatus::NullPointer,
+                veil_private_key_public_key(ptr::null(), &mut parsed)
+            );
+            assert_eq!(VeilStatus::NullPointer, veil_private_key_generate(ptr::null_mut()));
+            assert_eq!(
+                VeilStatus::NullPointer,
+                veil_sign(private_key, ptr::null(), 1, &mut empty())
+            );
+
+            veil_buffer_free(stored);
+            veil_buffer_free(s);
+            veil_public_key_free(parsed);
+            veil_public_key_free(public_key);
+            veil_private_key_free(loaded);
+            veil_private_key_free(private_key);
+        }
+    }
+
+    #[test]
+    fn digest() {
+        unsafe {
+            let metadata = [VeilSlice { data: b"a".as_ptr(), len: 1 }];
+            let mut digest = empty();
+            assert_eq!(
+                VeilStatus::Ok,
+                veil_digest(metadata.as_ptr(), 1, b"hello".as_ptr(), 5, &mut digest)
+            );
+            let expected = Digest::new(&["a"], &b"hello"[..]).expect("should digest");
+            assert_eq!(expected.encode(), slice::from_raw_parts(digest.data, digest.len));
+            veil_buffer_free(digest);
+        }
+    }
+
+    unsafe fn generate() -> *mut VeilPrivateKey {
+        let mut private_key = ptr::null_mut();
+        assert_eq!(VeilStatus::Ok, veil_private_key_generate(&mut private_key));
+        private_key
+    }
+
+    unsafe fn public_key(private_key: *const VeilPrivateKey) -> *mut VeilPublicKey {
+        let mut public_key = ptr::null_mut();
+        assert_eq!(VeilStatus::Ok, veil_private_key_public_key(private_key, &mut public_key));
+        public_key
+    }
+
+    const fn empty() -> VeilBuffer {
+        VeilBuffer { data: ptr::null_mut(), len: 0 }
+    }
+}
@@ -0,0 +1,491 @@
+//! An agent which holds an unlocked private key and uses it on behalf of other processes.
+//!
+//! An [`Agent`] is created with [`PrivateKey::into_agent`] and serves requests over a Unix socket,
+//! so that a long-running process can unlock a private key once and the short-lived processes of a
+//! scripted workflow can use it without a passphrase. The agent never gives out its private key: a
+//! [`Client`] decrypts messages by asking the agent for Diffie-Hellman shared secrets, and signs
+//! messages by streaming them to the agent.
+//!
+//! ```no_run
+//! use std::{io, os::unix::net::UnixListener, thread};
+//!
+//! use veil::{agent, backend, PrivateKey};
+//!
+//! let agent = PrivateKey::random(rand::thread_rng()).into_agent();
+//! let listener = UnixListener::bind("/run/user/1000/veil/agent.sock")?;
+//! thread::spawn(move || agent.serve(&listener));
+//!
+//! let client = agent::Client::connect("/run/user/1000/veil/agent.sock")?;
+//! let sig = client.sign(&b"a message"[..])?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! Anyone who can connect to the agent's socket can use its private key, so the socket must only be
+//! accessible by the key's owner, e.g. by putting it in a directory only they can access. On Linux
+//! and Android, the agent also checks with `SO_PEERCRED` that each client is running as the same
+//! user as the agent, and closes the connection of any which isn't.
+//!
+//! Each connection is handled on its own thread, and is closed if the client sends nothing for
+//! [`TIMEOUT`]. The client begins each connection with a one-byte request:
+//!
+//! * `1` for the agent's public key, to which the agent responds with its 32-byte encoded form;
+//! * `2` for Diffie-Hellman key agreement, after which the client writes any number of 32-byte
+//!   encoded points and the agent responds to each with the 32-byte encoding of `[d]P`;
+//! * `3` to stop the agent, to which it responds with a single zero byte;
+//! * `4` to sign a message, after which the client writes the message in chunks, each preceded by
+//!   its length as a 32-bit little-endian integer and followed by a zero-length chunk, and the
+//!   agent responds with the 64-byte signature.
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::{self, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use rand::rngs::OsRng;
+
+use crate::{
+    keys::POINT_LEN, schnorr::SIGNATURE_LEN, KemBackend, PrivateKey, PublicKey, Signature,
+};
+
+/// How long the agent waits for a client to send something before closing its connection.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A request for the agent's public key.
+const PUBLIC_KEY: u8 = 1;
+
+/// A request for Diffie-Hellman key agreements with the agent's private key.
+const DIFFIE_HELLMAN: u8 = 2;
+
+/// A request for the agent to stop.
+const STOP: u8 = 3;
+
+/// A request for the agent to sign a message.
+const SIGN: u8 = 4;
+
+/// The largest chunk of a message a client may send in a single write.
+const MAX_CHUNK_LEN: usize = 64 * 1024;
+
+/// An unlocked private key which can be used by other processes. Created by
+/// [`PrivateKey::into_agent`].
+pub struct Agent(PrivateKey);
+
+impl Agent {
+    pub(crate) const fn new(private_key: PrivateKey) -> Agent {
+        Agent(private_key)
+    }
+
+    /// Returns the public key of the agent's private key.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        self.0.public_key()
+    }
+
+    /// Returns the agent's private key.
+    #[must_use]
+    pub const fn private_key(&self) -> &PrivateKey {
+        &self.0
+    }
+
+    /// Accepts connections from the given listener and responds to their requests, each on its own
+    /// thread, until a client asks the agent to stop. Returns once every connection has ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error accepting a connection. Errors reading requests or writing responses only
+    /// end the connection they happened on.
+    pub fn serve(&self, listener: &UnixListener) -> io::Result<()> {
+        let stopped = AtomicBool::new(false);
+        thread::scope(|scope| {
+            while !stopped.load(Ordering::SeqCst) {
+                let (stream, _) = listener.accept()?;
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let stopped = &stopped;
+                scope.spawn(move || {
+                    if let Ok(true) = self.respond(stream) {
+                        // Wake the accept loop, which is waiting for the next connection.
+                        stopped.store(true, Ordering::SeqCst);
+                        if let Ok(addr) = listener.local_addr() {
+                            let _ = UnixStream::connect_addr(&addr);
+                        }
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Responds to a single connection. Returns `true` if the client asked the agent to stop.
+    fn respond(&self, mut stream: UnixStream) -> io::Result<bool> {
+        if !is_owner(&stream)? {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "client is not the owner"));
+        }
+        stream.set_read_timeout(Some(TIMEOUT))?;
+
+        let mut request = [0u8; 1];
+        stream.read_exact(&mut request)?;
+        match request[0] {
+            PUBLIC_KEY => stream.write_all(&self.0.public_key().encode())?,
+            DIFFIE_HELLMAN => {
+                // Respond to each point until the client closes the connection.
+                let mut point = [0u8; POINT_LEN];
+                while read_or_eof(&mut stream, &mut point)? {
+                    let point = PublicKey::decode(point).ok_or_else(invalid_request)?;
+                    stream.write_all(&self.0.diffie_hellman(&point))?;
+                }
+            }
+            STOP => {
+                stream.write_all(&[0])?;
+                return Ok(true);
+            }
+            SIGN => {
+                let sig = self.0.sign(OsRng, ChunkReader { stream: &mut stream, remaining: 0 })?;
+                stream.write_all(&sig.encode())?;
+            }
+            _ => return Err(invalid_request()),
+        }
+        Ok(false)
+    }
+}
+
+impl Debug for Agent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Agent").field(&self.public_key()).finish()
+    }
+}
+
+/// A client of an agent, which uses the agent's private key without having it.
+///
+/// As a [`KemBackend`], a client decrypts messages with the agent's private key, so it can be
+/// passed to [`backend::decrypt`](crate::backend::decrypt) or
+/// [`seekable::open`](crate::seekable::open). Each key agreement is a round trip to the agent over
+/// a single connection, which is opened when it's first needed.
+///
+/// [`KemBackend::diffie_hellman`] can't fail, so if the agent can't be reached, the client returns
+/// a meaningless shared secret and the decryption fails with
+/// [`DecryptError::NotAReceiver`](crate::DecryptError::NotAReceiver). The error which caused it is
+/// kept, and returned by [`Client::take_error`].
+pub struct Client {
+    path: PathBuf,
+    public_key: PublicKey,
+    stream: Mutex<Option<UnixStream>>,
+    error: Mutex<Option<io::Error>>,
+}
+
+impl Client {
+    /// Connects to the agent listening on the socket at the given path and gets its public key.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error connecting to the agent or communicating with it.
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Client> {
+        let path = path.as_ref().to_path_buf();
+        let public_key = public_key(&path)?;
+        Ok(Client { path, public_key, stream: Mutex::new(None), error: Mutex::new(None) })
+    }
+
+    /// Returns the public key of the agent's private key.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// Has the agent read the contents of the reader and create a digital signature of it. See
+    /// [`PrivateKey::sign`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reading from `message`, connecting to the agent, or communicating with it.
+    pub fn sign(&self, mut message: impl Read) -> io::Result<Signature> {
+        let mut stream = UnixStream::connect(&self.path)?;
+        stream.write_all(&[SIGN])?;
+
+        // Send the message in chunks, followed by an empty chunk.
+        let mut buf = vec![0u8; 4 + MAX_CHUNK_LEN];
+        loop {
+            let n = match message.read(&mut buf[4..]) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            buf[..4]
+                .copy_from_slice(&u32::try_from(n).expect("chunk should be small").to_le_bytes());
+            stream.write_all(&buf[..4 + n])?;
+            if n == 0 {
+                break;
+            }
+        }
+
+        let response = read_response::<SIGNATURE_LEN>(&mut stream)?;
+        Signature::decode(response).ok_or_else(invalid_response)
+    }
+
+    /// Returns the error, if any, which made a key agreement with the agent fail since the last
+    /// time this was called.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().expect("lock should not be poisoned").take()
+    }
+
+    /// Asks the agent for `[d]P`, opening a connection for key agreements if there isn't one. If
+    /// the connection has been closed, e.g. by the agent's timeout, reconnects once.
+    fn agree(&self, point: &PublicKey) -> io::Result<[u8; POINT_LEN]> {
+        let mut stream = self.stream.lock().expect("lock should not be poisoned");
+        let reused = stream.is_some();
+        match self.agree_on(&mut stream, point) {
+            Err(_) if reused => self.agree_on(&mut stream, point),
+            res => res,
+        }
+    }
+
+    fn agree_on(
+        &self,
+        stream: &mut Option<UnixStream>,
+        point: &PublicKey,
+    ) -> io::Result<[u8; POINT_LEN]> {
+        let res = (|| {
+            if stream.is_none() {
+                let mut s = UnixStream::connect(&self.path)?;
+                s.write_all(&[DIFFIE_HELLMAN])?;
+                *stream = Some(s);
+            }
+            let s = stream.as_mut().expect("stream should be connected");
+            s.write_all(&point.encode())?;
+            read_response(s)
+        })();
+        if res.is_err() {
+            *stream = None;
+        }
+        res
+    }
+}
+
+impl KemBackend for Client {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+        self.agree(point).unwrap_or_else(|e| {
+            *self.error.lock().expect("lock should not be poisoned") = Some(e);
+            [0u8; POINT_LEN]
+        })
+    }
+}
+
+impl Debug for Client {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("path", &self.path)
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the public key of the agent listening on the socket at the given path.
+///
+/// # Errors
+///
+/// Returns any error connecting to the agent or communicating with it.
+pub fn public_key(path: impl AsRef<Path>) -> io::Result<PublicKey> {
+    let response = request::<32>(path.as_ref(), PUBLIC_KEY)?;
+    PublicKey::decode(response).ok_or_else(invalid_response)
+}
+
+/// Stops the agent listening on the socket at the given path.
+///
+/// # Errors
+///
+/// Returns any error connecting to the agent or communicating with it.
+pub fn stop(path: impl AsRef<Path>) -> io::Result<()> {
+    match request::<1>(path.as_ref(), STOP)? {
+        [0] => Ok(()),
+        _ => Err(invalid_response()),
+    }
+}
+
+/// Connects to the agent at the given path, sends the given request, and reads an `N`-byte
+/// response.
+fn request<const N: usize>(path: &Path, request: u8) -> io::Result<[u8; N]> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.write_all(&[request])?;
+    read_response(&mut stream)
+}
+
+/// Reads an `N`-byte response from the agent.
+fn read_response<const N: usize>(stream: &mut UnixStream) -> io::Result<[u8; N]> {
+    let mut response = [0u8; N];
+    stream.read_exact(&mut response).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            invalid_response()
+        } else {
+            e
+        }
+    })?;
+    Ok(response)
+}
+
+/// Fills `buf` from `stream`. Returns `false` if the stream ended before any of `buf` was read.
+fn read_or_eof(stream: &mut UnixStream, buf: &mut [u8]) -> io::Result<bool> {
+    let mut n = 0;
+    while n < buf.len() {
+        match stream.read(&mut buf[n..]) {
+            Ok(0) if n == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(k) => n += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Returns whether the client on the other end of `stream` is running as the same user as this
+/// process.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_owner(stream: &UnixStream) -> io::Result<bool> {
+    let cred = rustix::net::sockopt::get_socket_peercred(stream)?;
+    Ok(cred.uid == rustix::process::getuid())
+}
+
+/// Returns whether the client on the other end of `stream` is running as the same user as this
+/// process. Peer credentials aren't checked on this platform, so the socket's permissions must
+/// restrict who can connect.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+#[allow(clippy::unnecessary_wraps)]
+const fn is_owner(_stream: &UnixStream) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// A reader of a message sent to the agent in length-prefixed chunks, which ends at the first
+/// empty chunk.
+struct ChunkReader<'a> {
+    stream: &'a mut UnixStream,
+    remaining: usize,
+}
+
+impl Read for ChunkReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            let mut len = [0u8; 4];
+            self.stream.read_exact(&mut len)?;
+            self.remaining = usize::try_from(u32::from_le_bytes(len)).unwrap_or(usize::MAX);
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+            if self.remaining > MAX_CHUNK_LEN {
+                return Err(invalid_request());
+            }
+        }
+        let len = buf.len().min(self.remaining);
+        let n = self.stream.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= n;
+        Ok(n)
+    }
+}
+
+fn invalid_request() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid agent request")
+}
+
+fn invalid_response() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid agent response")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{backend, EncryptOptions};
+
+    fn start(name: &str) -> (PrivateKey, PathBuf, thread::JoinHandle<io::Result<()>>) {
+        let seed = ChaChaRng::seed_from_u64(0xDEADBEEF).gen::<[u8; 64]>();
+        let private_key = PrivateKey::from_seed(&seed);
+        let agent = PrivateKey::from_seed(&seed).into_agent();
+
+        let path = env::temp_dir().join(format!("veil-agent-{name}-{}.sock", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).expect("should bind");
+        let server = thread::spawn(move || agent.serve(&listener));
+        (private_key, path, server)
+    }
+
+    fn finish(path: &Path, server: thread::JoinHandle<io::Result<()>>) {
+        stop(path).expect("should stop");
+        server.join().expect("should not panic").expect("should serve");
+        std::fs::remove_file(path).expect("should remove socket");
+        assert!(public_key(path).is_err(), "should not connect");
+    }
+
+    #[test]
+    fn round_trip() {
+        let (private_key, path, server) = start("round-trip");
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let client = Client::connect(&path).expect("should connect");
+        assert_eq!(private_key.public_key(), client.public_key());
+
+        // The client signs messages with the agent's private key.
+        let message = vec![0xAB; 200 * 1024];
+        let sig = client.sign(message.as_slice()).expect("should sign");
+        private_key.public_key().verify(message.as_slice(), &sig).expect("should verify");
+
+        // The client decrypts messages with the agent's private key.
+        let sender = PrivateKey::random(&mut rng);
+        let receivers = [private_key.public_key()];
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(
+                &mut rng,
+                &b"a message"[..],
+                &mut ciphertext,
+                &EncryptOptions::new(&receivers).fakes(10),
+            )
+            .expect("should encrypt");
+        let mut plaintext = Vec::new();
+        backend::decrypt(&client, ciphertext.as_slice(), &mut plaintext, &sender.public_key(), &[])
+            .expect("should decrypt");
+        assert_eq!(b"a message".as_slice(), plaintext);
+        assert!(client.take_error().is_none());
+
+        drop(client);
+
+        // Unknown requests end the connection without a response.
+        let mut stream = UnixStream::connect(&path).expect("should connect");
+        stream.write_all(&[0xFF]).expect("should write");
+        assert_eq!(0, stream.read(&mut [0u8; 1]).expect("should read"));
+
+        finish(&path, server);
+    }
+
+    #[test]
+    fn idle_clients() {
+        let (private_key, path, server) = start("idle");
+
+        // A client which connects and sends nothing doesn't stop others from being served.
+        let idle = UnixStream::connect(&path).expect("should connect");
+        assert_eq!(private_key.public_key(), public_key(&path).expect("should connect"));
+        assert_eq!(
+            private_key.public_key(),
+            Client::connect(&path).expect("should connect").public_key()
+        );
+
+        drop(idle);
+        finish(&path, server);
+    }
+}
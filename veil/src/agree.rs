@@ -0,0 +1,98 @@
+//! Static-static key agreement.
+
+use core::fmt::{self, Debug, Formatter};
+
+use lockstitch::Protocol;
+
+use crate::{
+    ct,
+    keys::{PrivKey, PubKey},
+};
+
+/// The length of a shared secret, in bytes.
+pub const SHARED_SECRET_LEN: usize = 32;
+
+/// A symmetric secret shared between two parties, derived from one party's private key and the
+/// other party's public key.
+#[derive(Clone, Eq)]
+pub struct SharedSecret([u8; SHARED_SECRET_LEN]);
+
+impl SharedSecret {
+    /// Returns the shared secret as a byte array.
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8; SHARED_SECRET_LEN] {
+        &self.0
+    }
+}
+
+impl Debug for SharedSecret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedSecret(..)")
+    }
+}
+
+impl PartialEq for SharedSecret {
+    fn eq(&self, other: &Self) -> bool {
+        ct::eq(&self.0, &other.0)
+    }
+}
+
+/// Derive a shared secret for the given context from the given private key and the other party's
+/// public key. Both parties derive the same secret.
+pub fn agree(private_key: &PrivKey, public_key: &PubKey, context: &str) -> SharedSecret {
+    // Initialize a protocol.
+    let mut agree = Protocol::new("veil.agree");
+
+    // Mix both public keys into the protocol in lexicographic order, so both parties mix them in
+    // the same order.
+    let (a, b) = if private_key.pub_key.encoded <= public_key.encoded {
+        (&private_key.pub_key, public_key)
+    } else {
+        (public_key, &private_key.pub_key)
+    };
+    agree.mix("public-key", &a.encoded);
+    agree.mix("public-key", &b.encoded);
+
+    // Mix the application context into the protocol.
+    agree.mix("context", context.as_bytes());
+
+    // Mix the static ECDH shared secret into the protocol.
+    agree.mix("static-ecdh", &(private_key.d * public_key.q).encode());
+
+    // Derive the shared secret.
+    SharedSecret(agree.derive_array("shared-secret"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn both_parties_agree() {
+        let (_, a, b) = setup();
+        assert_eq!(agree(&a, &b.pub_key, "test"), agree(&b, &a.pub_key, "test"));
+    }
+
+    #[test]
+    fn different_contexts() {
+        let (_, a, b) = setup();
+        assert_ne!(agree(&a, &b.pub_key, "one"), agree(&b, &a.pub_key, "two"));
+    }
+
+    #[test]
+    fn different_parties() {
+        let (mut rng, a, b) = setup();
+        let c = PrivKey::random(&mut rng);
+        assert_ne!(agree(&a, &b.pub_key, "test"), agree(&c, &a.pub_key, "test"));
+    }
+
+    fn setup() -> (ChaChaRng, PrivKey, PrivKey) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivKey::random(&mut rng);
+        let b = PrivKey::random(&mut rng);
+        (rng, a, b)
+    }
+}
@@ -0,0 +1,302 @@
+//! ASCII armor for ciphertexts.
+//!
+//! Armored data is encoded as base64 between header and footer lines, followed by a CRC-24
+//! checksum, in the style of GPG:
+//!
+//! ```text
+//! -----BEGIN VEIL MESSAGE-----
+//! VGhpcyBpcyBub3QgYSByZWFsIFZlaWwgbWVzc2FnZSwgYnV0IGl0IGxvb2tzIGxp
+//! a2Ugb25lLg==
+//! =UtI+
+//! -----END VEIL MESSAGE-----
+//! ```
+//!
+//! Unlike binary Veil messages, armored messages are trivially distinguishable from random noise.
+
+use std::io::{self, BufRead, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// The header line of an armored message.
+pub const HEADER: &str = "-----BEGIN VEIL MESSAGE-----";
+
+/// The footer line of an armored message.
+pub const FOOTER: &str = "-----END VEIL MESSAGE-----";
+
+/// The number of bytes encoded in each full line of an armored message.
+const LINE_BYTES: usize = 48;
+
+/// A writer which encodes everything written to it as an armored message.
+///
+/// [`ArmorWriter::finish`] must be called to write the final line, checksum, and footer.
+#[derive(Debug)]
+pub struct ArmorWriter<W: Write> {
+    writer: W,
+    buf: Vec<u8>,
+    crc: u32,
+    header_written: bool,
+}
+
+impl<W: Write> ArmorWriter<W> {
+    /// Creates a new armor writer which writes encoded data to `writer`.
+    pub const fn new(writer: W) -> ArmorWriter<W> {
+        ArmorWriter { writer, buf: Vec::new(), crc: CRC24_INIT, header_written: false }
+    }
+
+    /// Writes any buffered data, the checksum, and the footer, returning the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error returned by operations on the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_header()?;
+        if !self.buf.is_empty() {
+            writeln!(self.writer, "{}", STANDARD.encode(&self.buf))?;
+        }
+        let crc = self.crc.to_be_bytes();
+        writeln!(self.writer, "={}", STANDARD.encode(&crc[1..]))?;
+        writeln!(self.writer, "{FOOTER}")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "{HEADER}")?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ArmorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_header()?;
+        self.crc = crc24(self.crc, buf);
+        self.buf.extend_from_slice(buf);
+
+        // Write all complete lines.
+        let full = self.buf.len() - self.buf.len() % LINE_BYTES;
+        for line in self.buf[..full].chunks(LINE_BYTES) {
+            writeln!(self.writer, "{}", STANDARD.encode(line))?;
+        }
+        self.buf.drain(..full);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A reader which decodes an armored message.
+///
+/// If the armored message is malformed or its checksum does not match, reads will return an
+/// [`io::ErrorKind::InvalidData`] error.
+#[derive(Debug)]
+pub struct ArmorReader<R: BufRead> {
+    reader: R,
+    line: String,
+    buf: Vec<u8>,
+    pos: usize,
+    crc: u32,
+    state: ReadState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadState {
+    Header,
+    Body,
+    Done,
+}
+
+impl<R: BufRead> ArmorReader<R> {
+    /// Creates a new armor reader which decodes data read from `reader`.
+    pub const fn new(reader: R) -> ArmorReader<R> {
+        ArmorReader {
+            reader,
+            line: String::new(),
+            buf: Vec::new(),
+            pos: 0,
+            crc: CRC24_INIT,
+            state: ReadState::Header,
+        }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next non-empty line into `self.line`, returning `false` at EOF.
+    fn next_line(&mut self) -> io::Result<bool> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(false);
+            }
+            if !self.line.trim().is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Decodes the next line of the armored message into `self.buf`.
+    fn fill(&mut self) -> io::Result<()> {
+        if self.state == ReadState::Header {
+            if !self.next_line()? || self.line.trim() != HEADER {
+                return Err(invalid("missing armor header"));
+            }
+            self.state = ReadState::Body;
+        }
+
+        if self.state == ReadState::Done {
+            return Ok(());
+        }
+
+        if !self.next_line()? {
+            return Err(invalid("missing armor footer"));
+        }
+
+        let line = self.line.trim();
+        if let Some(checksum) = line.strip_prefix('=') {
+            // Check the checksum and the footer.
+            let checksum =
+                STANDARD.decode(checksum).map_err(|_| invalid("invalid armor checksum"))?;
+            if checksum[..] != self.crc.to_be_bytes()[1..] {
+                return Err(invalid("armor checksum mismatch"));
+            }
+            if !self.next_line()? || self.line.trim() != FOOTER {
+                return Err(invalid("missing armor footer"));
+            }
+            self.state = ReadState::Done;
+        } else if line == FOOTER {
+            return Err(invalid("missing armor checksum"));
+        } else {
+            self.buf = STANDARD.decode(line).map_err(|_| invalid("invalid armor encoding"))?;
+            self.pos = 0;
+            self.crc = crc24(self.crc, &self.buf);
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> io::Read for ArmorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if self.state == ReadState::Done {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+const CRC24_INIT: u32 = 0xB7_04CE;
+const CRC24_POLY: u32 = 0x186_4CFB;
+
+/// Updates the given CRC-24 checksum (as used by GPG) with the given data.
+fn crc24(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= u32::from(b) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x100_0000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+    crc & 0xFF_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use assert_matches::assert_matches;
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for n in [0, 1, 47, 48, 49, 1000] {
+            let (data, armored) = setup(n);
+            let mut decoded = Vec::new();
+            ArmorReader::new(Cursor::new(armored))
+                .read_to_end(&mut decoded)
+                .expect("decoding should be ok");
+            assert_eq!(data, decoded, "invalid decoded data for length {n}");
+        }
+    }
+
+    #[test]
+    fn line_format() {
+        let (_, armored) = setup(100);
+        let armored = String::from_utf8(armored).expect("armor should be ASCII");
+        let lines = armored.lines().collect::<Vec<_>>();
+        assert_eq!(HEADER, lines[0]);
+        assert_eq!(64, lines[1].len());
+        assert_eq!(64, lines[2].len());
+        assert!(lines[4].starts_with('='), "missing checksum");
+        assert_eq!(FOOTER, lines[5]);
+    }
+
+    #[test]
+    fn crc24_kat() {
+        assert_eq!(0x21_CF02, crc24(CRC24_INIT, b"123456789"));
+    }
+
+    #[test]
+    fn modified_data() {
+        let (_, mut armored) = setup(100);
+        // Swap two base64 characters in the first line of data.
+        armored.swap(30, 31);
+        assert_matches!(
+            ArmorReader::new(Cursor::new(armored)).read_to_end(&mut Vec::new()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn missing_footer() {
+        let (_, armored) = setup(100);
+        let truncated = &armored[..armored.len() - FOOTER.len() - 1];
+        assert_matches!(
+            ArmorReader::new(Cursor::new(truncated)).read_to_end(&mut Vec::new()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn missing_header() {
+        assert_matches!(
+            ArmorReader::new(Cursor::new(b"not an armored message")).read_to_end(&mut Vec::new()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData
+        );
+    }
+
+    fn setup(n: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let mut data = vec![0u8; n];
+        rng.fill_bytes(&mut data);
+
+        let mut w = ArmorWriter::new(Vec::new());
+        w.write_all(&data).expect("writes should be infallible");
+        let armored = w.finish().expect("writes should be infallible");
+
+        (data, armored)
+    }
+}
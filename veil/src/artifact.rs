@@ -0,0 +1,475 @@
+//! Signatures and digests with authenticated metadata, encoded as canonical CBOR.
+//!
+//! A bare [`Signature`] or [`Digest`] says nothing about who made it or why. An [`Artifact`]
+//! carries one along with a context string, the fingerprint of the signer's public key, and a
+//! creation time, all signed by the signer, so it can be stored or passed around without losing
+//! track of which key it belongs to:
+//!
+//! ```
+//! use rand::rngs::OsRng;
+//! use veil::PrivateKey;
+//!
+//! let key = PrivateKey::random(OsRng);
+//! let artifact = key.sign_artifact(OsRng, &b"a message"[..], "release", 1_700_000_000)?;
+//! assert_eq!("release", artifact.context());
+//! assert_eq!(&key.public_key().fingerprint(veil::FINGERPRINT_LEN), artifact.signer());
+//!
+//! let artifact = artifact.to_string().parse::<veil::artifact::Artifact>()?;
+//! artifact.verify_message(&key.public_key(), &b"a message"[..])?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! An artifact is encoded as a CBOR map with unsigned integer keys, in the deterministic encoding
+//! of RFC 8949, section 4.2.1:
+//!
+//! | Key | Value                                                         |
+//! |-----|---------------------------------------------------------------|
+//! | 1   | The kind of artifact: `1` for a signature or `2` for a digest |
+//! | 2   | The signature or digest, as a byte string                     |
+//! | 3   | The context, as a text string                                 |
+//! | 4   | The signer's 11-byte fingerprint, as a byte string            |
+//! | 5   | The creation time, in seconds since the Unix epoch            |
+//! | 6   | A random 16-byte nonce, as a byte string                      |
+//! | 7   | The signer's signature of the other values, as a byte string  |
+//!
+//! Decoding rejects anything but exactly that encoding, so every artifact has one encoding. Its
+//! string form is the base58 encoding of its CBOR encoding.
+
+use std::{fmt, io::Read, str::FromStr};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    fingerprint::{self, Fingerprint, FINGERPRINT_LEN},
+    keys::PubKey,
+    schnorr::{self, DET_SIGNATURE_LEN},
+    sres::NONCE_LEN,
+    Digest, ParseArtifactError, PublicKey, Signature, SignerBackend, VerifyError,
+};
+
+/// The CBOR major type of unsigned integers.
+const UINT: u8 = 0;
+
+/// The CBOR major type of byte strings.
+const BYTES: u8 = 2;
+
+/// The CBOR major type of text strings.
+const TEXT: u8 = 3;
+
+/// The CBOR major type of maps.
+const MAP: u8 = 5;
+
+/// The number of entries in an artifact's map.
+const ENTRIES: u64 = 7;
+
+/// What an [`Artifact`] attests to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Payload {
+    /// A signature of a message, created in the artifact's context.
+    Signature(Signature),
+
+    /// A digest of a message.
+    Digest(Digest),
+}
+
+impl Payload {
+    const fn kind(&self) -> u64 {
+        match self {
+            Payload::Signature(_) => 1,
+            Payload::Digest(_) => 2,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Payload::Signature(sig) => sig.encode().to_vec(),
+            Payload::Digest(digest) => digest.encode().to_vec(),
+        }
+    }
+}
+
+/// A signature or digest with a context, its signer's fingerprint, and its creation time, all
+/// signed by the signer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Artifact {
+    payload: Payload,
+    context: String,
+    signer: Fingerprint,
+    created: u64,
+    nonce: [u8; NONCE_LEN],
+    sig: [u8; DET_SIGNATURE_LEN],
+}
+
+impl Artifact {
+    /// Returns the signature or digest.
+    #[must_use]
+    pub const fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    /// Returns the context the artifact was created in.
+    #[must_use]
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Returns the fingerprint of the signer's public key.
+    #[must_use]
+    pub const fn signer(&self) -> &Fingerprint {
+        &self.signer
+    }
+
+    /// Returns the time the artifact was created, in seconds since the Unix epoch.
+    #[must_use]
+    pub const fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Verifies that the artifact was created by the owner of the given public key. Returns
+    /// `Ok(())` if successful.
+    ///
+    /// This only checks the artifact's metadata; use [`Artifact::verify_message`] or
+    /// [`Artifact::verify_digest`] to check what it attests to as well.
+    ///
+    /// # Errors
+    ///
+    /// If the artifact has been modified or was not created by the owner of the public key, returns
+    /// [`VerifyError::InvalidSignature`].
+    pub fn verify(&self, signer: &PublicKey) -> Result<(), VerifyError> {
+        if self.signer != signer.fingerprint(FINGERPRINT_LEN) {
+            return Err(VerifyError::InvalidSignature);
+        }
+        let mut artifact = init(&signer.0, &self.payload, &self.context, self.created, &self.nonce);
+        schnorr::det_verify(&mut artifact, &signer.0, self.sig).ok_or(VerifyError::InvalidSignature)
+    }
+
+    /// Verifies that the artifact was created by the owner of the given public key and holds their
+    /// signature of the given message. Returns `Ok(())` if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the artifact is a digest, has been modified, or was not created by the owner of the
+    /// public key, or if the message is not the one which was signed, returns
+    /// [`VerifyError::InvalidSignature`]. If there is an error while reading from `message`, returns
+    /// [`VerifyError::ReadIo`].
+    pub fn verify_message(
+        &self,
+        signer: &PublicKey,
+        message: impl Read,
+    ) -> Result<(), VerifyError> {
+        let Payload::Signature(sig) = self.payload else {
+            return Err(VerifyError::InvalidSignature);
+        };
+        self.verify(signer)?;
+        signer.verify_in_context(message, &sig, &self.context)
+    }
+
+    /// Verifies that the artifact was created by the owner of the given public key and holds the
+    /// given digest. Returns `Ok(())` if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the artifact is a signature, has been modified, was not created by the owner of the
+    /// public key, or holds a different digest, returns [`VerifyError::InvalidSignature`].
+    pub fn verify_digest(&self, signer: &PublicKey, digest: &Digest) -> Result<(), VerifyError> {
+        match self.payload {
+            Payload::Digest(d) if d == *digest => self.verify(signer),
+            _ => Err(VerifyError::InvalidSignature),
+        }
+    }
+
+    /// Decodes an artifact from its canonical CBOR encoding.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<Artifact> {
+        let mut r = Reader(b.as_ref());
+        if r.head(MAP)? != ENTRIES {
+            return None;
+        }
+
+        let kind = r.entry(1, UINT)?;
+        let payload = r.entry(2, BYTES).and_then(|len| r.take(len))?;
+        let payload = match kind {
+            1 => Payload::Signature(Signature::decode(payload)?),
+            2 => Payload::Digest(Digest::decode(payload)?),
+            _ => return None,
+        };
+        let context = r.entry(3, TEXT).and_then(|len| r.take(len))?;
+        let context = String::from_utf8(context.to_vec()).ok()?;
+        let signer = r.entry(4, BYTES).and_then(|len| r.take(len))?;
+        let signer = Fingerprint::from_bytes(<[u8; FINGERPRINT_LEN]>::try_from(signer).ok()?);
+        let created = r.entry(5, UINT)?;
+        let nonce = r.entry(6, BYTES).and_then(|len| r.take(len))?.try_into().ok()?;
+        let sig = r.entry(7, BYTES).and_then(|len| r.take(len))?.try_into().ok()?;
+
+        r.0.is_empty().then_some(Artifact { payload, context, signer, created, nonce, sig })
+    }
+
+    /// Encodes the artifact in canonical CBOR.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::new();
+        head(&mut b, MAP, ENTRIES);
+        uint_entry(&mut b, 1, self.payload.kind());
+        string_entry(&mut b, 2, BYTES, &self.payload.encode());
+        string_entry(&mut b, 3, TEXT, self.context.as_bytes());
+        string_entry(&mut b, 4, BYTES, self.signer.as_bytes());
+        uint_entry(&mut b, 5, self.created);
+        string_entry(&mut b, 6, BYTES, &self.nonce);
+        string_entry(&mut b, 7, BYTES, &self.sig);
+        b
+    }
+}
+
+impl FromStr for Artifact {
+    type Err = ParseArtifactError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Artifact::decode(bs58::decode(s).into_vec()?).ok_or(ParseArtifactError::InvalidArtifact)
+    }
+}
+
+impl fmt::Display for Artifact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    }
+}
+
+/// Create an artifact of the given payload with the given context and creation time, signed by the
+/// given signer.
+pub fn create(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    payload: Payload,
+    context: &str,
+    created: u64,
+) -> Artifact {
+    let public_key = signer.public_key();
+
+    // Generate a random nonce.
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+    // Initialize a protocol with the artifact's contents and sign its state.
+    let mut artifact = init(&public_key.0, &payload, context, created, &nonce);
+    let sig = signer.sign(&mut artifact);
+
+    Artifact {
+        payload,
+        context: context.into(),
+        signer: fingerprint::fingerprint(&public_key.0, FINGERPRINT_LEN),
+        created,
+        nonce,
+        sig,
+    }
+}
+
+fn init(
+    public_key: &PubKey,
+    payload: &Payload,
+    context: &str,
+    created: u64,
+    nonce: &[u8],
+) -> Protocol {
+    // Initialize a protocol.
+    let mut artifact = Protocol::new("veil.artifact");
+
+    // Mix the signer's public key into the protocol.
+    artifact.mix("signer", &public_key.encoded);
+
+    // Mix the nonce into the protocol.
+    artifact.mix("nonce", nonce);
+
+    // Mix the payload, context, and creation time into the protocol.
+    artifact.mix("kind", &payload.kind().to_le_bytes());
+    artifact.mix("payload", &payload.encode());
+    artifact.mix("context", context.as_bytes());
+    artifact.mix("created", &created.to_le_bytes());
+
+    artifact
+}
+
+/// Appends a CBOR head with the given major type and argument, in its shortest form.
+fn head(b: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    match n {
+        0..=23 => b.push(major | n as u8),
+        24..=0xFF => b.extend_from_slice(&[major | 24, n as u8]),
+        0x100..=0xFFFF => {
+            b.push(major | 25);
+            b.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            b.push(major | 26);
+            b.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            b.push(major | 27);
+            b.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+/// Appends a map entry with the given key and unsigned integer value.
+fn uint_entry(b: &mut Vec<u8>, key: u64, n: u64) {
+    head(b, UINT, key);
+    head(b, UINT, n);
+}
+
+/// Appends a map entry with the given key and byte or text string value.
+fn string_entry(b: &mut Vec<u8>, key: u64, major: u8, value: &[u8]) {
+    head(b, UINT, key);
+    head(b, major, value.len() as u64);
+    b.extend_from_slice(value);
+}
+
+/// A reader of canonically-encoded CBOR.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Reads the given number of bytes.
+    fn take(&mut self, n: u64) -> Option<&'a [u8]> {
+        let n = usize::try_from(n).ok()?;
+        let (b, rest) = (self.0.get(..n)?, self.0.get(n..)?);
+        self.0 = rest;
+        Some(b)
+    }
+
+    /// Reads a head of the given major type and returns its argument, if it's in its shortest form.
+    fn head(&mut self, major: u8) -> Option<u64> {
+        let initial = self.take(1)?[0];
+        if initial >> 5 != major {
+            return None;
+        }
+        let (n, min) = match initial & 0x1F {
+            n @ 0..=23 => (u64::from(n), 0),
+            24 => (u64::from(self.take(1)?[0]), 24),
+            25 => (u64::from(u16::from_be_bytes(self.take(2)?.try_into().ok()?)), 0x100),
+            26 => (u64::from(u32::from_be_bytes(self.take(4)?.try_into().ok()?)), 0x1_0000),
+            27 => (u64::from_be_bytes(self.take(8)?.try_into().ok()?), 0x1_0000_0000),
+            _ => return None,
+        };
+        (n >= min).then_some(n)
+    }
+
+    /// Reads a map entry's key, which must be the given key, and the head of its value, which must
+    /// be of the given major type. Returns the value's argument.
+    fn entry(&mut self, key: u64, major: u8) -> Option<u64> {
+        (self.head(UINT)? == key).then_some(())?;
+        self.head(major)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn signature_round_trip() {
+        let (rng, key) = setup();
+        let artifact = key
+            .sign_artifact(rng, &b"a message"[..], "release", 1_700_000_000)
+            .expect("should sign");
+
+        assert_eq!("release", artifact.context());
+        assert_eq!(1_700_000_000, artifact.created());
+        assert_eq!(&key.public_key().fingerprint(FINGERPRINT_LEN), artifact.signer());
+        assert_matches!(artifact.verify(&key.public_key()), Ok(()));
+        assert_matches!(artifact.verify_message(&key.public_key(), &b"a message"[..]), Ok(()));
+        assert_matches!(
+            artifact.verify_message(&key.public_key(), &b"another message"[..]),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_matches!(
+            artifact.verify_digest(&key.public_key(), &digest()),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn digest_round_trip() {
+        let (rng, key) = setup();
+        let artifact = key.digest_artifact(rng, &digest(), "build", 1_700_000_000);
+
+        assert_eq!(&Payload::Digest(digest()), artifact.payload());
+        assert_matches!(artifact.verify_digest(&key.public_key(), &digest()), Ok(()));
+        assert_matches!(
+            artifact.verify_digest(
+                &key.public_key(),
+                &Digest::new(&["other"], &b"a message"[..]).expect("should digest")
+            ),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_matches!(
+            artifact.verify_message(&key.public_key(), &b"a message"[..]),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn wrong_signer() {
+        let (mut rng, key) = setup();
+        let artifact = key.digest_artifact(&mut rng, &digest(), "build", 1_700_000_000);
+        let other = PrivateKey::random(&mut rng).public_key();
+
+        assert_matches!(artifact.verify(&other), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn modified_metadata() {
+        let (rng, key) = setup();
+        let artifact = key.digest_artifact(rng, &digest(), "build", 1_700_000_000);
+
+        let mut modified = artifact.clone();
+        modified.context = "release".into();
+        assert_matches!(modified.verify(&key.public_key()), Err(VerifyError::InvalidSignature));
+
+        let mut modified = artifact;
+        modified.created += 1;
+        assert_matches!(modified.verify(&key.public_key()), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn encoding() {
+        let (rng, key) = setup();
+        let artifact = key.digest_artifact(rng, &digest(), "build", 1_700_000_000);
+
+        let b = artifact.encode();
+        assert_eq!(Some(&artifact), Artifact::decode(&b).as_ref());
+        assert_eq!(Ok(&artifact), artifact.to_string().parse::<Artifact>().as_ref());
+
+        // The encoding is a map of seven entries starting with the kind of artifact.
+        assert_eq!([0xA7, 0x01, 0x02], b[..3]);
+
+        // Trailing data, truncations, and non-canonical encodings are rejected.
+        assert!(Artifact::decode([&b[..], &[0]].concat()).is_none());
+        assert!(Artifact::decode(&b[..b.len() - 1]).is_none());
+        assert!(Artifact::decode([&[0xA7, 0x01, 0x18, 0x02], &b[3..]].concat()).is_none());
+        assert_matches!("woot".parse::<Artifact>(), Err(ParseArtifactError::InvalidArtifact));
+    }
+
+    #[test]
+    fn heads() {
+        for n in [0, 23, 24, 0xFF, 0x100, 0xFFFF, 0x1_0000, 0xFFFF_FFFF, 0x1_0000_0000, u64::MAX] {
+            let mut b = Vec::new();
+            head(&mut b, UINT, n);
+            let mut r = Reader(&b);
+            assert_eq!(Some(n), r.head(UINT), "{n} should round-trip");
+            assert!(r.0.is_empty());
+        }
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        (rng, key)
+    }
+
+    fn digest() -> Digest {
+        Digest::new(&["a.txt"], &b"a message"[..]).expect("should digest")
+    }
+}
@@ -0,0 +1,96 @@
+use std::{future::poll_fn, io, pin::Pin};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+/// Reads a `buf`-sized block of data, returning the number of bytes read into `buf`. If the
+/// returned count is less than the length of `buf`, an `EOF` was encountered.
+pub async fn read_block(
+    reader: &mut (impl AsyncRead + Unpin),
+    mut buf: &mut [u8],
+) -> io::Result<usize> {
+    let max = buf.len();
+    while !buf.is_empty() {
+        match poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, buf)).await {
+            Ok(0) => break,
+            Ok(n) => buf = &mut buf[n..],
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(max - buf.len())
+}
+
+/// Reads exactly enough data to fill `buf`, returning an [`io::ErrorKind::UnexpectedEof`] error if
+/// the reader ends first.
+pub async fn read_exact(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> io::Result<()> {
+    if read_block(reader, buf).await? < buf.len() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Writes all of `buf` to `writer`.
+pub async fn write_all(writer: &mut (impl AsyncWrite + Unpin), mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => buf = &buf[n..],
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    /// Polls the given future to completion. Only suitable for futures which never wait on IO, like
+    /// those reading from slices and writing to vectors.
+    pub fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = pin!(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(out) = f.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    #[test]
+    fn blockwise_reads() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let data = rng.gen::<[u8; 20]>();
+
+        let mut reader = &data[..];
+        let mut block = [0u8; 16];
+
+        let n = block_on(read_block(&mut reader, &mut block)).expect("slice reads are infallible");
+        assert_eq!(n, 16);
+        assert_eq!(&block, &data[..16]);
+
+        let n = block_on(read_block(&mut reader, &mut block)).expect("slice reads are infallible");
+        assert_eq!(n, 4);
+        assert_eq!(&block[..4], &data[16..]);
+    }
+
+    #[test]
+    fn short_read_exact() {
+        let mut reader = &[0u8; 4][..];
+        let mut buf = [0u8; 8];
+        assert_eq!(
+            io::ErrorKind::UnexpectedEof,
+            block_on(read_exact(&mut reader, &mut buf)).expect_err("should be short").kind()
+        );
+    }
+}
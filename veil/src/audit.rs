@@ -0,0 +1,256 @@
+//! Audit logging of private key usage.
+//!
+//! An [`AuditSink`] installed with [`set_sink`] is called after every encryption, decryption, and
+//! signature made with a [`PrivateKey`], whether or not it succeeded. Each [`AuditEvent`] carries
+//! only non-secret metadata: the operation, the key's public key, the number of receivers, the
+//! number of bytes read and written, and how long the operation took. A sink which needs a
+//! tamper-evident log can chain each record it writes to the digest of the previous one.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//!
+//! use rand::rngs::OsRng;
+//! use veil::{audit, audit::AuditEvent, PrivateKey};
+//!
+//! let events = Arc::new(Mutex::new(Vec::new()));
+//! let log = Arc::clone(&events);
+//! audit::set_sink(move |event: &AuditEvent| log.lock().unwrap().push(*event));
+//!
+//! let priv_key = PrivateKey::random(OsRng);
+//! priv_key.sign(OsRng, &b"an audited message"[..]).expect("should sign");
+//! audit::clear_sink();
+//!
+//! let events = events.lock().unwrap();
+//! assert!(events.iter().any(|e| e.public_key == priv_key.public_key() && e.success));
+//! ```
+//!
+//! [`PrivateKey`]: crate::PrivateKey
+
+use std::{
+    fmt::{Debug, Formatter},
+    io::{self, Read},
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "async")]
+use futures_io::AsyncRead;
+
+use crate::PublicKey;
+
+/// The currently installed sink, if any.
+static SINK: RwLock<Option<Arc<dyn AuditSink>>> = RwLock::new(None);
+
+/// An operation performed with a private key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// A message was encrypted.
+    Encrypt,
+    /// A message was decrypted.
+    Decrypt,
+    /// A message or digest was signed.
+    Sign,
+    /// A message was decrypted and encrypted again for new receivers.
+    Rekey,
+}
+
+/// The non-secret metadata of an operation performed with a private key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditEvent {
+    /// The operation performed.
+    pub operation: Operation,
+    /// The public key of the private key which performed the operation.
+    pub public_key: PublicKey,
+    /// The number of receivers of an encrypted message, not including fakes; zero for other
+    /// operations.
+    pub receivers: usize,
+    /// The number of bytes read from the input.
+    pub bytes_read: u64,
+    /// The number of bytes of output produced; zero if the operation failed.
+    pub bytes_written: u64,
+    /// How long the operation took. Always zero on `wasm32-unknown-unknown`, which has no clock.
+    pub duration: Duration,
+    /// Whether the operation succeeded.
+    pub success: bool,
+}
+
+/// A destination for audit events.
+///
+/// Sinks are called synchronously on the thread which performed the operation, so they should be
+/// fast. Closures which take an [`AuditEvent`] reference are sinks.
+pub trait AuditSink: Send + Sync {
+    /// Records the given event.
+    fn record(&self, event: &AuditEvent);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditEvent) + Send + Sync,
+{
+    fn record(&self, event: &AuditEvent) {
+        self(event);
+    }
+}
+
+/// Installs the given sink for all private key operations in the process, replacing any
+/// previously installed sink.
+pub fn set_sink(sink: impl AuditSink + 'static) {
+    *SINK.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(sink));
+}
+
+/// Removes the installed sink, if any.
+pub fn clear_sink() {
+    *SINK.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// An operation in progress, which is recorded when it finishes if a sink was installed when it
+/// started.
+pub(crate) struct Span {
+    sink: Option<Arc<dyn AuditSink>>,
+    operation: Operation,
+    public_key: PublicKey,
+    receivers: usize,
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    started: std::time::Instant,
+}
+
+impl Span {
+    /// Starts an operation with the given key and number of receivers.
+    pub(crate) fn start(operation: Operation, public_key: PublicKey, receivers: usize) -> Span {
+        Span {
+            sink: SINK.read().unwrap_or_else(PoisonError::into_inner).clone(),
+            operation,
+            public_key,
+            receivers,
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            started: std::time::Instant::now(),
+        }
+    }
+
+    /// Finishes the operation, having read the given number of bytes and written the given number
+    /// of bytes if it succeeded or `None` if it failed.
+    pub(crate) fn finish(self, bytes_read: u64, bytes_written: Option<u64>) {
+        let Some(sink) = self.sink else {
+            return;
+        };
+
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        let duration = self.started.elapsed();
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        let duration = Duration::ZERO;
+
+        sink.record(&AuditEvent {
+            operation: self.operation,
+            public_key: self.public_key,
+            receivers: self.receivers,
+            bytes_read,
+            bytes_written: bytes_written.unwrap_or(0),
+            duration,
+            success: bytes_written.is_some(),
+        });
+    }
+}
+
+impl Debug for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Span")
+            .field("operation", &self.operation)
+            .field("public_key", &self.public_key)
+            .field("receivers", &self.receivers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A reader which counts the bytes read from the inner reader.
+pub(crate) struct Metered<R>(pub(crate) R, pub(crate) u64);
+
+impl<R: Read> Read for Metered<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.0.read(buf)?;
+        self.1 += u64::try_from(n).expect("usize should be <= u64");
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for Metered<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.0).poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.1 += u64::try_from(n).expect("usize should be <= u64");
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::Mutex};
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{schnorr::SIGNATURE_LEN, EncryptOptions, PrivateKey};
+
+    #[test]
+    fn records_private_key_operations() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        let watched = [a.public_key(), b.public_key()];
+        set_sink(move |event: &AuditEvent| {
+            if watched.contains(&event.public_key) {
+                log.lock().expect("should lock").push(*event);
+            }
+        });
+
+        let mut ciphertext = Vec::new();
+        let ciphertext_len = a
+            .encrypt(
+                &mut rng,
+                &b"this is an audited message"[..],
+                &mut ciphertext,
+                &EncryptOptions::new(&[b.public_key()]).fakes(2),
+            )
+            .expect("should encrypt")
+            .written();
+        let mut plaintext = Vec::new();
+        b.decrypt(Cursor::new(&ciphertext), &mut plaintext, &a.public_key())
+            .expect("should decrypt");
+        *ciphertext.last_mut().expect("should not be empty") ^= 1;
+        b.decrypt(Cursor::new(&ciphertext), io::sink(), &a.public_key())
+            .expect_err("should not decrypt");
+        a.sign(&mut rng, &b"this is a signed message"[..]).expect("should sign");
+        clear_sink();
+
+        let events = events.lock().expect("should lock");
+        let summary = events
+            .iter()
+            .map(|e| {
+                (e.operation, e.public_key, e.receivers, e.bytes_read, e.bytes_written, e.success)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (Operation::Encrypt, a.public_key(), 1, 26, ciphertext_len, true),
+                (Operation::Decrypt, b.public_key(), 0, ciphertext_len, 26, true),
+                (Operation::Decrypt, b.public_key(), 0, ciphertext_len, 0, false),
+                (Operation::Sign, a.public_key(), 0, 24, SIGNATURE_LEN as u64, true),
+            ],
+            summary
+        );
+    }
+}
@@ -0,0 +1,671 @@
+//! Pluggable private key backends.
+//!
+//! Veil's constructions only use a private key for a handful of operations: Diffie-Hellman key
+//! agreement, deterministic Schnorr signatures, and designated-verifier Schnorr signatures. The
+//! [`KemBackend`] and [`SignerBackend`] traits abstract over those operations so that the private
+//! key can be held by an HSM, TPM, or other device while the rest of Veil's message framing runs in
+//! process. [`PrivateKey`] implements both traits in software.
+//!
+//! A backend can delegate to anything which can compute `[d]P` for a point `P`: a hardware token,
+//! a KMS, or an external process (like an age plugin). Backends of different types can be combined
+//! as trait objects, so that [`decrypt_any`] tries each of them against every header:
+//!
+//! ```
+//! use std::io;
+//!
+//! use rand::rngs::OsRng;
+//! use veil::{backend, backend::KemBackend, EncryptOptions, PrivateKey, PublicKey};
+//!
+//! /// A receiver whose private key is held by another process.
+//! struct Token(PrivateKey);
+//!
+//! impl KemBackend for Token {
+//!     fn public_key(&self) -> PublicKey {
+//!         self.0.public_key()
+//!     }
+//!
+//!     fn diffie_hellman(&self, point: &PublicKey) -> [u8; 32] {
+//!         // Send the point to the token and return its response.
+//!         self.0.diffie_hellman(point)
+//!     }
+//! }
+//!
+//! let sender = PrivateKey::random(OsRng);
+//! let token = Token(PrivateKey::random(OsRng));
+//! let software = PrivateKey::random(OsRng);
+//!
+//! let mut ciphertext = Vec::new();
+//! let receivers = [token.public_key()];
+//! sender.encrypt(OsRng, &b"a message"[..], &mut ciphertext, &EncryptOptions::new(&receivers))?;
+//!
+//! let receivers: [&dyn KemBackend; 2] = [&software, &token];
+//! let sender = sender.public_key();
+//! let (idx, _) = backend::decrypt_any(&receivers, &ciphertext[..], io::sink(), &sender, &[])?;
+//! assert_eq!(1, idx);
+//! # Ok::<(), veil::Error>(())
+//! ```
+//!
+//! Every backend holds a GLS254 private key. Recipients with other kinds of keys can't be
+//! supported: each header must be the same length and built with the same construction as every
+//! other, or the headers would reveal who the receivers are.
+
+#[cfg(feature = "std")]
+use std::{
+    io::{self, Read, Seek, Write},
+    slice,
+};
+
+use alloc::boxed::Box;
+#[cfg(feature = "async")]
+use futures_io::{AsyncRead, AsyncWrite};
+pub use lockstitch::Protocol;
+#[cfg(feature = "std")]
+use rand::{CryptoRng, Rng};
+
+#[cfg(feature = "std")]
+use crate::{
+    keys::PubKey,
+    mres::{self, HeaderKey, Report},
+    scrub::ScrubWriter,
+    DecryptError, DecryptOptions, EncryptError, EncryptOptions, HeaderSlot, Signature,
+    TimestampedSignature,
+};
+use crate::{
+    keys::{PrivKey, POINT_LEN},
+    schnorr, sres, PrivateKey, PublicKey,
+};
+
+/// The length of a deterministic signature created by [`SignerBackend::sign`], in bytes.
+pub const SIGNATURE_LEN: usize = schnorr::DET_SIGNATURE_LEN;
+
+/// The length of a designated-verifier signature created by [`SignerBackend::sign_designated`], in
+/// bytes.
+pub const DESIGNATED_SIGNATURE_LEN: usize = sres::DESIGNATED_SIGNATURE_LEN;
+
+/// A backend which holds a private key and can perform Diffie-Hellman key agreement with it.
+pub trait KemBackend {
+    /// Returns the public key corresponding to the backend's private key.
+    fn public_key(&self) -> PublicKey;
+
+    /// Returns the canonical encoding of the point `[d]P`, where `d` is the backend's private key
+    /// and `P` is the given point.
+    fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN];
+}
+
+/// A backend which holds a private key and can create signatures with it.
+pub trait SignerBackend {
+    /// Returns the public key corresponding to the backend's private key.
+    fn public_key(&self) -> PublicKey;
+
+    /// Deterministically creates a `veil.schnorr` signature of the given protocol's state, as
+    /// described in the design document's `DetSign` function.
+    fn sign(&self, protocol: &mut Protocol) -> [u8; SIGNATURE_LEN];
+
+    /// Deterministically creates a designated-verifier signature of the given protocol's state which
+    /// can only be verified by the owner of `verifier`, as described in the design document's
+    /// `EncryptHeader` function.
+    fn sign_designated(
+        &self,
+        protocol: &mut Protocol,
+        verifier: &PublicKey,
+    ) -> [u8; DESIGNATED_SIGNATURE_LEN];
+}
+
+impl<T: KemBackend + ?Sized> KemBackend for &T {
+    fn public_key(&self) -> PublicKey {
+        (**self).public_key()
+    }
+
+    fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+        (**self).diffie_hellman(point)
+    }
+}
+
+impl<T: KemBackend + ?Sized> KemBackend for Box<T> {
+    fn public_key(&self) -> PublicKey {
+        (**self).public_key()
+    }
+
+    fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+        (**self).diffie_hellman(point)
+    }
+}
+
+impl KemBackend for PrivKey {
+    fn public_key(&self) -> PublicKey {
+        PublicKey(self.pub_key)
+    }
+
+    fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+        (self.d * point.0.q).encode()
+    }
+}
+
+impl SignerBackend for PrivKey {
+    fn public_key(&self) -> PublicKey {
+        PublicKey(self.pub_key)
+    }
+
+    fn sign(&self, protocol: &mut Protocol) -> [u8; SIGNATURE_LEN] {
+        schnorr::det_sign(protocol, self)
+    }
+
+    fn sign_designated(
+        &self,
+        protocol: &mut Protocol,
+        verifier: &PublicKey,
+    ) -> [u8; DESIGNATED_SIGNATURE_LEN] {
+        sres::det_sign_designated(protocol, self, &verifier.0)
+    }
+}
+
+impl KemBackend for PrivateKey {
+    fn public_key(&self) -> PublicKey {
+        KemBackend::public_key(&self.0)
+    }
+
+    fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+        self.0.diffie_hellman(point)
+    }
+}
+
+impl SignerBackend for PrivateKey {
+    fn public_key(&self) -> PublicKey {
+        SignerBackend::public_key(&self.0)
+    }
+
+    fn sign(&self, protocol: &mut Protocol) -> [u8; SIGNATURE_LEN] {
+        self.0.sign(protocol)
+    }
+
+    fn sign_designated(
+        &self,
+        protocol: &mut Protocol,
+        verifier: &PublicKey,
+    ) -> [u8; DESIGNATED_SIGNATURE_LEN] {
+        self.0.sign_designated(protocol, verifier)
+    }
+}
+
+/// Encrypts the contents of the reader with the given backend's private key and writes the
+/// ciphertext to the writer. See [`PrivateKey::encrypt`].
+///
+/// When there are many receivers, their headers are encrypted on multiple threads, so the backend
+/// must be [`Sync`].
+///
+/// # Errors
+///
+/// If there is an error while reading from `reader` or writing to `writer`, an [`EncryptError`]
+/// will be returned. If the message would exceed [`MAX_RECEIVERS`](crate::MAX_RECEIVERS),
+/// [`MAX_PADDING`](crate::MAX_PADDING), or [`MAX_MESSAGE_LEN`](crate::MAX_MESSAGE_LEN), returns
+/// [`EncryptError::TooManyReceivers`], [`EncryptError::TooMuchPadding`], or
+/// [`EncryptError::MessageTooLong`].
+#[cfg(feature = "std")]
+pub fn encrypt(
+    sender: &(impl KemBackend + SignerBackend + Sync),
+    mut rng: impl Rng + CryptoRng,
+    reader: impl Read,
+    writer: impl Write,
+    options: &EncryptOptions<'_>,
+) -> Result<Report, EncryptError> {
+    options.check_limits()?;
+    let receivers = header_receivers(&mut rng, options);
+
+    #[cfg(feature = "zstd")]
+    if let Some(level) = options.compression {
+        let compressed = zstd::stream::encode_all(reader, level).map_err(EncryptError::ReadIo)?;
+        let compressed_len = u64::try_from(compressed.len()).expect("usize should be <= u64");
+        return mres::encrypt_version(
+            &mut rng,
+            compressed.as_slice(),
+            writer,
+            sender,
+            &receivers,
+            options.resolve_compressed_padding(compressed_len),
+            &options.resolve_associated_data(),
+            options.format_version(),
+            options.layout,
+        );
+    }
+
+    mres::encrypt_version(
+        &mut rng,
+        reader,
+        writer,
+        sender,
+        &receivers,
+        options.resolve_padding(),
+        &options.resolve_associated_data(),
+        options.format_version(),
+        options.layout,
+    )
+}
+
+/// Encrypts the contents of the reader with the given backend's private key and writes the
+/// ciphertext to the writer, asynchronously. See [`PrivateKey::encrypt_async`].
+///
+/// # Errors
+///
+/// If there is an error while reading from `reader` or writing to `writer`, an [`EncryptError`]
+/// will be returned. If the message would exceed [`MAX_RECEIVERS`](crate::MAX_RECEIVERS),
+/// [`MAX_PADDING`](crate::MAX_PADDING), or [`MAX_MESSAGE_LEN`](crate::MAX_MESSAGE_LEN), returns
+/// [`EncryptError::TooManyReceivers`], [`EncryptError::TooMuchPadding`], or
+/// [`EncryptError::MessageTooLong`].
+#[cfg(feature = "async")]
+pub async fn encrypt_async(
+    sender: &(impl KemBackend + SignerBackend + Sync),
+    mut rng: impl Rng + CryptoRng,
+    reader: impl AsyncRead + Unpin,
+    writer: impl AsyncWrite + Unpin,
+    options: &EncryptOptions<'_>,
+) -> Result<Report, EncryptError> {
+    options.check_limits()?;
+    let receivers = header_receivers(&mut rng, options);
+
+    #[cfg(feature = "zstd")]
+    if let Some(level) = options.compression {
+        // Read the entire plaintext, as the compressed length is needed to pad the message.
+        let mut reader = reader;
+        let mut plaintext = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = crate::asyncio::read_block(&mut reader, &mut buf)
+                .await
+                .map_err(EncryptError::ReadIo)?;
+            if n == 0 {
+                break;
+            }
+            plaintext.extend_from_slice(&buf[..n]);
+        }
+
+        let compressed =
+            zstd::stream::encode_all(plaintext.as_slice(), level).map_err(EncryptError::ReadIo)?;
+        let compressed_len = u64::try_from(compressed.len()).expect("usize should be <= u64");
+        return mres::encrypt_async(
+            &mut rng,
+            compressed.as_slice(),
+            writer,
+            sender,
+            &receivers,
+            options.resolve_compressed_padding(compressed_len),
+            &options.resolve_associated_data(),
+            options.format_version(),
+            options.layout,
+        )
+        .await;
+    }
+
+    mres::encrypt_async(
+        &mut rng,
+        reader,
+        writer,
+        sender,
+        &receivers,
+        options.resolve_padding(),
+        &options.resolve_associated_data(),
+        options.format_version(),
+        options.layout,
+    )
+    .await
+}
+
+/// Re-encrypts a message sent to the given backend's private key for a new set of receivers, with
+/// the backend's private key as the sender. See [`PrivateKey::rekey`].
+///
+/// # Errors
+///
+/// If the ciphertext was not sent by the sender or was not encrypted for the backend's private key,
+/// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+/// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If there
+/// was an error reading from `reader` or writing to `writer`, returns [`DecryptError::ReadIo`] or
+/// [`DecryptError::WriteIo`].
+#[cfg(feature = "std")]
+pub fn rekey(
+    key: &(impl KemBackend + SignerBackend + Sync),
+    mut rng: impl Rng + CryptoRng,
+    reader: impl Read,
+    writer: impl Write,
+    sender: &PublicKey,
+    options: &EncryptOptions<'_>,
+) -> Result<Report, DecryptError> {
+    options
+        .check_limits()
+        .map_err(|e| DecryptError::WriteIo(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+    let receivers = header_receivers(&mut rng, options);
+    mres::rekey(
+        &mut rng,
+        reader,
+        writer,
+        key,
+        &sender.0,
+        &receivers,
+        options.resolve_padding(),
+        &options.resolve_associated_data(),
+        options.layout,
+    )
+}
+
+/// Lays out the real receivers', decoys', and fake receivers' headers, generating a random public
+/// key for each fake.
+#[cfg(feature = "std")]
+fn header_receivers(mut rng: impl Rng + CryptoRng, options: &EncryptOptions<'_>) -> Vec<HeaderKey> {
+    options
+        .resolve_slots(&mut rng)
+        .into_iter()
+        .map(|slot| match slot {
+            HeaderSlot::Receiver(idx) => HeaderKey::Receiver(options.receivers[idx].0),
+            HeaderSlot::Decoy(idx) => HeaderKey::Decoy(options.decoys[idx].0),
+            HeaderSlot::Fake => HeaderKey::Receiver(PubKey::random(&mut rng)),
+        })
+        .collect()
+}
+
+/// Decrypts the contents of `reader` with the given backend's private key, if possible, and writes
+/// the plaintext to `writer`. See [`PrivateKey::decrypt_with_associated_data`].
+///
+/// # Errors
+///
+/// If the ciphertext was not sent by the sender or was not encrypted for the backend's private key,
+/// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+/// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If there
+/// was an error reading from `reader` or writing to `writer`, returns [`DecryptError::ReadIo`] or
+/// [`DecryptError::WriteIo`].
+#[cfg(feature = "std")]
+pub fn decrypt(
+    receiver: &impl KemBackend,
+    reader: impl Read,
+    writer: impl Write,
+    sender: &PublicKey,
+    associated_data: &[u8],
+) -> Result<Report, DecryptError> {
+    decrypt_with_options(
+        receiver,
+        reader,
+        writer,
+        sender,
+        &DecryptOptions::new().associated_data(associated_data),
+    )
+}
+
+/// Decrypts the contents of `reader` with the given backend's private key, if possible, and writes
+/// the plaintext to `writer`, within the limits given by `options`. See
+/// [`PrivateKey::decrypt_with_options`].
+///
+/// # Errors
+///
+/// If the ciphertext was not sent by the sender or was not encrypted for the backend's private key,
+/// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+/// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If its
+/// headers or plaintext exceed the limits, returns [`DecryptError::HeadersTooLong`] or
+/// [`DecryptError::PlaintextTooLong`]. If there was an error reading from `reader` or writing to
+/// `writer`, returns [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+#[cfg(feature = "std")]
+pub fn decrypt_with_options(
+    receiver: &impl KemBackend,
+    reader: impl Read,
+    writer: impl Write,
+    sender: &PublicKey,
+    options: &DecryptOptions<'_>,
+) -> Result<Report, DecryptError> {
+    mres::decrypt_with_limits(
+        reader,
+        writer,
+        slice::from_ref(receiver),
+        &sender.0,
+        options.associated_data,
+        options.limits(),
+    )
+    .map(|(_, report)| report)
+}
+
+/// Decrypts the contents of `reader` with the given backend's private key, if possible, and writes
+/// the plaintext to the seekable `writer`, overwriting it with zeros if decryption fails. See
+/// [`PrivateKey::decrypt_scrubbed`].
+///
+/// # Errors
+///
+/// If the ciphertext was not sent by the sender or was not encrypted for the backend's private key,
+/// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+/// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If there
+/// was an error reading from `reader` or writing to or scrubbing `writer`, returns
+/// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+#[cfg(feature = "std")]
+pub fn decrypt_scrubbed(
+    receiver: &impl KemBackend,
+    reader: impl Read,
+    writer: impl Write + Seek,
+    sender: &PublicKey,
+    associated_data: &[u8],
+) -> Result<Report, DecryptError> {
+    let mut writer = ScrubWriter::new(writer).map_err(DecryptError::WriteIo)?;
+    decrypt(receiver, reader, &mut writer, sender, associated_data).or_else(|e| {
+        writer.scrub().map_err(DecryptError::WriteIo)?;
+        Err(e)
+    })
+}
+
+/// Decrypts the contents of `reader` with the given backend's private key, if possible, and writes
+/// the plaintext to `writer`, asynchronously. See [`PrivateKey::decrypt_async`].
+///
+/// # Errors
+///
+/// If the ciphertext was not sent by the sender or was not encrypted for the backend's private key,
+/// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+/// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If there
+/// was an error reading from `reader` or writing to `writer`, returns [`DecryptError::ReadIo`] or
+/// [`DecryptError::WriteIo`].
+#[cfg(feature = "async")]
+pub async fn decrypt_async(
+    receiver: &impl KemBackend,
+    reader: impl AsyncRead + Unpin,
+    writer: impl AsyncWrite + Unpin,
+    sender: &PublicKey,
+    associated_data: &[u8],
+) -> Result<Report, DecryptError> {
+    decrypt_async_with_options(
+        receiver,
+        reader,
+        writer,
+        sender,
+        &DecryptOptions::new().associated_data(associated_data),
+    )
+    .await
+}
+
+/// Decrypts the contents of `reader` with the given backend's private key, if possible, and writes
+/// the plaintext to `writer` asynchronously, within the limits given by `options`. See
+/// [`PrivateKey::decrypt_async_with_options`].
+///
+/// # Errors
+///
+/// If the ciphertext was not sent by the sender or was not encrypted for the backend's private key,
+/// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+/// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If its
+/// headers or plaintext exceed the limits, returns [`DecryptError::HeadersTooLong`] or
+/// [`DecryptError::PlaintextTooLong`]. If there was an error reading from `reader` or writing to
+/// `writer`, returns [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+#[cfg(feature = "async")]
+pub async fn decrypt_async_with_options(
+    receiver: &impl KemBackend,
+    reader: impl AsyncRead + Unpin,
+    writer: impl AsyncWrite + Unpin,
+    sender: &PublicKey,
+    options: &DecryptOptions<'_>,
+) -> Result<Report, DecryptError> {
+    mres::decrypt_async(
+        reader,
+        writer,
+        slice::from_ref(receiver),
+        &sender.0,
+        options.associated_data,
+        options.limits(),
+    )
+    .await
+    .map(|(_, report)| report)
+}
+
+/// Decrypts the contents of `reader` with whichever of the given backends' private keys it was
+/// encrypted for, if any, and writes the plaintext to `writer`. See [`PrivateKey::decrypt_any`].
+///
+/// # Errors
+///
+/// If the ciphertext was not sent by the sender or was not encrypted for any of the backends'
+/// private keys, returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+/// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If there
+/// was an error reading from `reader` or writing to `writer`, returns [`DecryptError::ReadIo`] or
+/// [`DecryptError::WriteIo`].
+#[cfg(feature = "std")]
+pub fn decrypt_any<R: KemBackend>(
+    receivers: &[R],
+    reader: impl Read,
+    writer: impl Write,
+    sender: &PublicKey,
+    associated_data: &[u8],
+) -> Result<(usize, Report), DecryptError> {
+    mres::decrypt(reader, writer, receivers, &sender.0, associated_data)
+}
+
+/// Reads the contents of the reader and returns a digital signature created with the given
+/// backend's private key. See [`PrivateKey::sign`].
+///
+/// # Errors
+///
+/// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+#[cfg(feature = "std")]
+pub fn sign(
+    signer: &impl SignerBackend,
+    rng: impl Rng + CryptoRng,
+    message: impl Read,
+) -> io::Result<Signature> {
+    schnorr::sign(rng, signer, "", message)
+}
+
+/// Reads the contents of the reader and returns a digital signature of it and the given timestamp
+/// created with the given backend's private key. See [`PrivateKey::sign_timestamped`].
+///
+/// # Errors
+///
+/// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+#[cfg(feature = "std")]
+pub fn sign_timestamped(
+    signer: &impl SignerBackend,
+    rng: impl Rng + CryptoRng,
+    message: impl Read,
+    timestamp: u64,
+) -> io::Result<TimestampedSignature> {
+    schnorr::sign_timestamped(rng, signer, message, timestamp)
+}
+
+/// Reads the contents of the reader and writes it to the writer with an attached digital signature
+/// created with the given backend's private key. See [`PrivateKey::sign_attached`].
+///
+/// # Errors
+///
+/// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`] will
+/// be returned.
+#[cfg(feature = "std")]
+pub fn sign_attached(
+    signer: &impl SignerBackend,
+    rng: impl Rng + CryptoRng,
+    reader: impl Read,
+    writer: impl Write,
+) -> io::Result<u64> {
+    schnorr::sign_attached(rng, signer, reader, writer)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::Cursor;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    /// A backend which only exposes the trait operations of a wrapped private key.
+    struct Opaque(PrivateKey);
+
+    impl KemBackend for Opaque {
+        fn public_key(&self) -> PublicKey {
+            self.0.public_key()
+        }
+
+        fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+            self.0.diffie_hellman(point)
+        }
+    }
+
+    impl SignerBackend for Opaque {
+        fn public_key(&self) -> PublicKey {
+            self.0.public_key()
+        }
+
+        fn sign(&self, protocol: &mut Protocol) -> [u8; SIGNATURE_LEN] {
+            SignerBackend::sign(&self.0, protocol)
+        }
+
+        fn sign_designated(
+            &self,
+            protocol: &mut Protocol,
+            verifier: &PublicKey,
+        ) -> [u8; DESIGNATED_SIGNATURE_LEN] {
+            self.0.sign_designated(protocol, verifier)
+        }
+    }
+
+    #[test]
+    fn backend_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = Opaque(PrivateKey::random(&mut rng));
+        let b = Opaque(PrivateKey::random(&mut rng));
+
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &a,
+            &mut rng,
+            Cursor::new(b"a message"),
+            &mut ciphertext,
+            &EncryptOptions::new(&[b.0.public_key()]).fakes(3).associated_data(b"associated data"),
+        )
+        .expect("encryption should be ok");
+
+        let mut plaintext = Vec::new();
+        decrypt(&b, Cursor::new(ciphertext), &mut plaintext, &a.0.public_key(), b"associated data")
+            .expect("decryption should be ok");
+        assert_eq!(b"a message".to_vec(), plaintext, "invalid plaintext");
+    }
+
+    #[test]
+    fn mixed_backends() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = Opaque(PrivateKey::random(&mut rng));
+        let c = PrivateKey::random(&mut rng);
+
+        let mut ciphertext = Vec::new();
+        a.encrypt(
+            &mut rng,
+            Cursor::new(b"a message"),
+            &mut ciphertext,
+            &EncryptOptions::new(&[b.0.public_key()]).fakes(3),
+        )
+        .expect("encryption should be ok");
+
+        let receivers: [Box<dyn KemBackend>; 2] = [Box::new(c), Box::new(b)];
+        let mut plaintext = Vec::new();
+        let (idx, _) =
+            decrypt_any(&receivers, Cursor::new(ciphertext), &mut plaintext, &a.public_key(), &[])
+                .expect("decryption should be ok");
+        assert_eq!(1, idx, "invalid receiver index");
+        assert_eq!(b"a message".to_vec(), plaintext, "invalid plaintext");
+    }
+
+    #[test]
+    fn backend_signatures() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = Opaque(PrivateKey::random(&mut rng));
+
+        let sig = sign(&key, &mut rng, Cursor::new(b"a message")).expect("signing should be ok");
+        key.0.public_key().verify(Cursor::new(b"a message"), &sig).expect("should verify");
+    }
+}
@@ -0,0 +1,367 @@
+//! Containers of many messages with an encrypted table of contents.
+//!
+//! A [`BundleWriter`] encrypts any number of independent messages, e.g. one per file, into a single
+//! container, and a [`Bundle`] opens it and decrypts only the messages which are needed. Each
+//! message is prefixed with its length, and the last is a table of contents with the name of each
+//! message before it, encrypted for the same receivers:
+//!
+//! ```text
+//! container = (length || message)* || length || table of contents
+//! ```
+//!
+//! Each message is encrypted with associated data containing a random ID which is unique to the
+//! container, the message's position, and its name, so the messages of a container can't be
+//! reordered, renamed, or mixed with those of another container. The lengths are not encrypted, so
+//! they reveal the number and lengths of the messages, but nothing else.
+//!
+//! ```
+//! use std::io::Cursor;
+//!
+//! use veil::{bundle::{Bundle, BundleWriter}, EncryptOptions, PrivateKey};
+//!
+//! let sender = PrivateKey::random(rand::thread_rng());
+//! let receiver = PrivateKey::random(rand::thread_rng());
+//! let receivers = [receiver.public_key()];
+//! let options = EncryptOptions::new(&receivers);
+//!
+//! // Pack two files into a container.
+//! let container = Cursor::new(Vec::new());
+//! let mut bundle = BundleWriter::new(rand::thread_rng(), container, &sender, &options);
+//! bundle.add(rand::thread_rng(), "a.txt", &b"the first file"[..]).expect("should encrypt");
+//! bundle.add(rand::thread_rng(), "b.txt", &b"the second file"[..]).expect("should encrypt");
+//! let container = bundle.finish(rand::thread_rng()).expect("should encrypt").into_inner();
+//!
+//! // Extract only the second file.
+//! let mut bundle = Bundle::open(Cursor::new(container), &receiver, &sender.public_key())
+//!     .expect("should open");
+//! let index = bundle.position("b.txt").expect("should have b.txt");
+//! let mut plaintext = Vec::new();
+//! bundle.extract(index, &mut plaintext).expect("should decrypt");
+//! assert_eq!(b"the second file", plaintext.as_slice());
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use rand::{CryptoRng, Rng};
+
+use crate::{DecryptError, EncryptError, EncryptOptions, PrivateKey, PublicKey, Report};
+
+/// The length of a container's ID in bytes.
+const ID_LEN: usize = 32;
+
+/// The length of a message's length prefix in bytes.
+const PREFIX_LEN: u64 = 8;
+
+/// The associated data of a container's table of contents.
+const TOC_AD: &[u8] = b"veil.bundle.toc";
+
+/// Writes messages to a container.
+#[derive(Debug)]
+pub struct BundleWriter<'a, W> {
+    writer: W,
+    sender: &'a PrivateKey,
+    options: &'a EncryptOptions<'a>,
+    id: [u8; ID_LEN],
+    names: Vec<String>,
+}
+
+impl<'a, W: Write + Seek> BundleWriter<'a, W> {
+    /// Creates a writer which writes a container of messages from `sender` to `writer`, each
+    /// encrypted with `options`. Any associated data in `options` is replaced by the container's.
+    pub fn new(
+        mut rng: impl Rng + CryptoRng,
+        writer: W,
+        sender: &'a PrivateKey,
+        options: &'a EncryptOptions<'a>,
+    ) -> BundleWriter<'a, W> {
+        BundleWriter { writer, sender, options, id: rng.gen(), names: Vec::new() }
+    }
+
+    /// Encrypts the contents of `reader` as the next message in the container, with the given
+    /// name. Returns a [`Report`] of the message.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `reader` or writing to the container, an
+    /// [`io::Error`] will be returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is longer than 65,535 bytes.
+    pub fn add(
+        &mut self,
+        rng: impl Rng + CryptoRng,
+        name: &str,
+        reader: impl Read,
+    ) -> Result<Report, EncryptError> {
+        assert!(name.len() <= usize::from(u16::MAX), "name should be at most 65,535 bytes");
+        let ad = entry_ad(&self.id, self.names.len(), name);
+        let report = self.write_message(rng, reader, &ad)?;
+        self.names.push(name.to_string());
+        Ok(report)
+    }
+
+    /// Encrypts the container's table of contents and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while writing to the container, an [`io::Error`] will be returned.
+    pub fn finish(mut self, rng: impl Rng + CryptoRng) -> Result<W, EncryptError> {
+        let mut toc = self.id.to_vec();
+        for name in &self.names {
+            toc.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            toc.extend_from_slice(name.as_bytes());
+        }
+        self.write_message(rng, toc.as_slice(), TOC_AD)?;
+        Ok(self.writer)
+    }
+
+    /// Encrypts a message with the given associated data and a length prefix, which is filled in
+    /// once the message is written.
+    fn write_message(
+        &mut self,
+        rng: impl Rng + CryptoRng,
+        reader: impl Read,
+        associated_data: &[u8],
+    ) -> Result<Report, EncryptError> {
+        let start = self.writer.stream_position().map_err(EncryptError::WriteIo)?;
+        self.writer.write_all(&[0; PREFIX_LEN as usize]).map_err(EncryptError::WriteIo)?;
+        let options = self.options.clone().associated_data(associated_data);
+        let report = self.sender.encrypt(rng, reader, &mut self.writer, &options)?;
+        self.writer.seek(SeekFrom::Start(start)).map_err(EncryptError::WriteIo)?;
+        self.writer.write_all(&report.written().to_le_bytes()).map_err(EncryptError::WriteIo)?;
+        self.writer
+            .seek(SeekFrom::Start(start + PREFIX_LEN + report.written()))
+            .map_err(EncryptError::WriteIo)?;
+        Ok(report)
+    }
+}
+
+/// A message in a container.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    name: String,
+    offset: u64,
+    ciphertext_len: u64,
+}
+
+impl Entry {
+    /// Returns the message's name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the length of the encrypted message in bytes.
+    #[must_use]
+    pub const fn ciphertext_len(&self) -> u64 {
+        self.ciphertext_len
+    }
+}
+
+/// An opened container, which decrypts its messages as they're extracted.
+#[derive(Debug)]
+pub struct Bundle<'a, R> {
+    reader: R,
+    receiver: &'a PrivateKey,
+    sender: PublicKey,
+    id: [u8; ID_LEN],
+    entries: Vec<Entry>,
+}
+
+impl<'a, R: Read + Seek> Bundle<'a, R> {
+    /// Opens the container in `reader`, starting at its current position, by finding each message
+    /// and decrypting the table of contents.
+    ///
+    /// # Errors
+    ///
+    /// If the table of contents was not sent by the sender or was not encrypted for the receiver,
+    /// returns [`DecryptError::NotAReceiver`]. If the container has been modified, returns
+    /// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If
+    /// there was an error reading from `reader`, returns [`DecryptError::ReadIo`].
+    pub fn open(
+        mut reader: R,
+        receiver: &'a PrivateKey,
+        sender: &PublicKey,
+    ) -> Result<Bundle<'a, R>, DecryptError> {
+        // Find each message by its length prefix.
+        let mut pos = reader.stream_position().map_err(DecryptError::ReadIo)?;
+        let end = reader.seek(SeekFrom::End(0)).map_err(DecryptError::ReadIo)?;
+        let mut messages = Vec::new();
+        while pos < end {
+            let mut prefix = [0u8; PREFIX_LEN as usize];
+            reader.seek(SeekFrom::Start(pos)).map_err(DecryptError::ReadIo)?;
+            reader.read_exact(&mut prefix).map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => DecryptError::Truncated,
+                _ => DecryptError::ReadIo(e),
+            })?;
+            let len = u64::from_le_bytes(prefix);
+            let offset = pos + PREFIX_LEN;
+            pos = offset.checked_add(len).filter(|&n| n <= end).ok_or(DecryptError::Truncated)?;
+            messages.push((offset, len));
+        }
+
+        // Decrypt the table of contents, which is the last message.
+        let (offset, len) = messages.pop().ok_or(DecryptError::Truncated)?;
+        let mut toc = Vec::new();
+        reader.seek(SeekFrom::Start(offset)).map_err(DecryptError::ReadIo)?;
+        receiver.decrypt_with_associated_data((&mut reader).take(len), &mut toc, sender, TOC_AD)?;
+        let (id, mut names) = toc.split_at_checked(ID_LEN).ok_or(DecryptError::Corrupt)?;
+        let id = id.try_into().expect("should be an ID");
+
+        // Match each message with its name.
+        let mut entries = Vec::with_capacity(messages.len());
+        for (offset, ciphertext_len) in messages {
+            let (len, rest) = names.split_at_checked(2).ok_or(DecryptError::Corrupt)?;
+            let len = u16::from_le_bytes(len.try_into().expect("should be 2 bytes"));
+            let (name, rest) = rest.split_at_checked(len.into()).ok_or(DecryptError::Corrupt)?;
+            let name = String::from_utf8(name.to_vec()).map_err(|_| DecryptError::Corrupt)?;
+            entries.push(Entry { name, offset, ciphertext_len });
+            names = rest;
+        }
+        if !names.is_empty() {
+            return Err(DecryptError::Corrupt);
+        }
+
+        Ok(Bundle { reader, receiver, sender: *sender, id, entries })
+    }
+
+    /// Returns the messages in the container, in the order they were added.
+    #[must_use]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns the index of the first message with the given name, if any.
+    #[must_use]
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.name == name)
+    }
+
+    /// Decrypts the message at the given index and writes its plaintext to `writer`. Returns a
+    /// [`Report`] of the message.
+    ///
+    /// # Errors
+    ///
+    /// If the message was moved from another position or container, returns
+    /// [`DecryptError::NotAReceiver`]. If it has been modified, returns [`DecryptError::Corrupt`],
+    /// and if it is incomplete, returns [`DecryptError::Truncated`]. If there was an error reading
+    /// from the container or writing to `writer`, returns [`DecryptError::ReadIo`] or
+    /// [`DecryptError::WriteIo`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn extract(&mut self, index: usize, writer: impl Write) -> Result<Report, DecryptError> {
+        let entry = &self.entries[index];
+        let ad = entry_ad(&self.id, index, &entry.name);
+        self.reader.seek(SeekFrom::Start(entry.offset)).map_err(DecryptError::ReadIo)?;
+        self.receiver.decrypt_with_associated_data(
+            (&mut self.reader).take(entry.ciphertext_len),
+            writer,
+            &self.sender,
+            &ad,
+        )
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Returns the associated data of the message with the given index and name in the container with
+/// the given ID.
+fn entry_ad(id: &[u8; ID_LEN], index: usize, name: &str) -> Vec<u8> {
+    let mut ad = b"veil.bundle.entry".to_vec();
+    ad.extend_from_slice(id);
+    ad.extend_from_slice(&(index as u64).to_le_bytes());
+    ad.extend_from_slice(name.as_bytes());
+    ad
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (mut rng, sender, receiver, container) = setup();
+        let mut bundle = Bundle::open(Cursor::new(container), &receiver, &sender.public_key())
+            .expect("should open");
+        assert_eq!(
+            vec!["a", "b", "c"],
+            bundle.entries().iter().map(Entry::name).collect::<Vec<_>>()
+        );
+
+        let mut plaintext = Vec::new();
+        let report = bundle.extract(1, &mut plaintext).expect("should decrypt");
+        assert_eq!(b"the second message", plaintext.as_slice());
+        assert_eq!(bundle.entries()[1].ciphertext_len(), report.ciphertext_bytes());
+
+        // A receiver who isn't one can't open it.
+        let other = PrivateKey::random(&mut rng);
+        assert!(matches!(
+            Bundle::open(
+                Cursor::new(bundle.into_inner().into_inner()),
+                &other,
+                &sender.public_key()
+            ),
+            Err(DecryptError::NotAReceiver)
+        ));
+    }
+
+    #[test]
+    fn reordered_messages() {
+        let (_, sender, receiver, mut container) = setup();
+
+        // Swap the first two messages, which are the same length.
+        let len = usize::try_from(u64::from_le_bytes(container[..8].try_into().expect("8 bytes")))
+            .expect("should fit");
+        let (first, rest) = container.split_at_mut(8 + len);
+        first[8..].swap_with_slice(&mut rest[8..8 + len]);
+
+        let mut bundle = Bundle::open(Cursor::new(container), &receiver, &sender.public_key())
+            .expect("should open");
+        assert!(matches!(bundle.extract(0, io::sink()), Err(DecryptError::NotAReceiver)));
+    }
+
+    #[test]
+    fn truncated_container() {
+        let (_, sender, receiver, container) = setup();
+        assert!(matches!(
+            Bundle::open(
+                Cursor::new(&container[..container.len() - 1]),
+                &receiver,
+                &sender.public_key()
+            ),
+            Err(DecryptError::Truncated)
+        ));
+        assert!(matches!(
+            Bundle::open(Cursor::new(&[][..]), &receiver, &sender.public_key()),
+            Err(DecryptError::Truncated)
+        ));
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, PrivateKey, Vec<u8>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let receivers = [receiver.public_key()];
+        let options = EncryptOptions::new(&receivers);
+
+        let mut bundle = BundleWriter::new(&mut rng, Cursor::new(Vec::new()), &sender, &options);
+        for (name, message) in
+            [("a", "the first message!"), ("b", "the second message"), ("c", "the third")]
+        {
+            bundle.add(&mut rng, name, message.as_bytes()).expect("should encrypt");
+        }
+        let container = bundle.finish(&mut rng).expect("should encrypt").into_inner();
+        (rng, sender, receiver, container)
+    }
+}
@@ -0,0 +1,306 @@
+//! Signed statements that one identity vouches for another's public key.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::{PubKey, POINT_LEN},
+    schnorr::{self, DET_SIGNATURE_LEN},
+    sres::NONCE_LEN,
+    ParseCertificationError, PublicKey, SignerBackend, VerifyError,
+};
+
+/// The length of an encoded certification with an empty label, in bytes.
+const MIN_CERTIFICATION_LEN: usize =
+    POINT_LEN + POINT_LEN + size_of::<u64>() + size_of::<u64>() + NONCE_LEN + DET_SIGNATURE_LEN;
+
+/// A statement, signed by an issuer's private key, that a subject public key belongs to the
+/// identity described by a label during a validity period.
+///
+/// Consists of the issuer's public key, the subject's public key, the start and end of the validity
+/// period, a nonce, a signature, and a UTF-8 label. Certifications are the edges of a trust graph:
+/// anyone who trusts the issuer can use them to trust the subject.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Certification {
+    issuer: PublicKey,
+    subject: PublicKey,
+    not_before: u64,
+    not_after: u64,
+    nonce: [u8; NONCE_LEN],
+    sig: [u8; DET_SIGNATURE_LEN],
+    label: String,
+}
+
+impl Certification {
+    /// Returns the public key of the identity which issued the certification.
+    #[must_use]
+    pub const fn issuer(&self) -> PublicKey {
+        self.issuer
+    }
+
+    /// Returns the certified public key.
+    #[must_use]
+    pub const fn subject(&self) -> PublicKey {
+        self.subject
+    }
+
+    /// Returns the start of the validity period, in seconds since the Unix epoch.
+    #[must_use]
+    pub const fn not_before(&self) -> u64 {
+        self.not_before
+    }
+
+    /// Returns the end of the validity period, in seconds since the Unix epoch.
+    #[must_use]
+    pub const fn not_after(&self) -> u64 {
+        self.not_after
+    }
+
+    /// Returns the label the subject was certified with (e.g. a name or email address).
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns `true` if the given time, in seconds since the Unix epoch, is within the
+    /// certification's validity period.
+    #[must_use]
+    pub const fn is_valid_at(&self, timestamp: u64) -> bool {
+        self.not_before <= timestamp && timestamp <= self.not_after
+    }
+
+    /// Verifies that the certification was created by the owner of `issuer`. Returns `Ok(())` if
+    /// successful. The validity period is not checked; use [`Certification::is_valid_at`].
+    ///
+    /// # Errors
+    ///
+    /// If the certification was issued by a different public key or has been modified, returns
+    /// [`VerifyError::InvalidSignature`].
+    pub fn verify(&self, issuer: &PublicKey) -> Result<(), VerifyError> {
+        if self.issuer != *issuer {
+            return Err(VerifyError::InvalidSignature);
+        }
+
+        let mut certification = init(
+            &issuer.0,
+            &self.subject.0,
+            self.not_before,
+            self.not_after,
+            &self.nonce,
+            &self.label,
+        );
+        schnorr::det_verify(&mut certification, &issuer.0, self.sig)
+            .ok_or(VerifyError::InvalidSignature)
+    }
+
+    /// Decode a certification from a byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<Certification> {
+        let b = b.as_ref();
+        if b.len() < MIN_CERTIFICATION_LEN {
+            return None;
+        }
+
+        // Split the certification into components.
+        let (issuer, b) = b.split_at(POINT_LEN);
+        let (subject, b) = b.split_at(POINT_LEN);
+        let (not_before, b) = b.split_at(size_of::<u64>());
+        let (not_after, b) = b.split_at(size_of::<u64>());
+        let (nonce, b) = b.split_at(NONCE_LEN);
+        let (sig, label) = b.split_at(DET_SIGNATURE_LEN);
+
+        Some(Certification {
+            issuer: PublicKey::decode(issuer)?,
+            subject: PublicKey::decode(subject)?,
+            not_before: u64::from_le_bytes(not_before.try_into().expect("should be 8 bytes")),
+            not_after: u64::from_le_bytes(not_after.try_into().expect("should be 8 bytes")),
+            nonce: nonce.try_into().expect("should be nonce-sized"),
+            sig: sig.try_into().expect("should be signature-sized"),
+            label: String::from_utf8(label.to_vec()).ok()?,
+        })
+    }
+
+    /// Encode the certification as a byte vector.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(MIN_CERTIFICATION_LEN + self.label.len());
+        b.extend_from_slice(&self.issuer.encode());
+        b.extend_from_slice(&self.subject.encode());
+        b.extend_from_slice(&self.not_before.to_le_bytes());
+        b.extend_from_slice(&self.not_after.to_le_bytes());
+        b.extend_from_slice(&self.nonce);
+        b.extend_from_slice(&self.sig);
+        b.extend_from_slice(self.label.as_bytes());
+        b
+    }
+}
+
+impl FromStr for Certification {
+    type Err = ParseCertificationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Certification::decode(bs58::decode(s).into_vec()?)
+            .ok_or(ParseCertificationError::InvalidCertification)
+    }
+}
+
+impl fmt::Display for Certification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    }
+}
+
+/// Create a certification of the given subject by the given signer with the given label and
+/// validity period.
+pub fn certify(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    subject: &PublicKey,
+    label: &str,
+    not_before: u64,
+    not_after: u64,
+) -> Certification {
+    let issuer = signer.public_key();
+
+    // Generate a random nonce.
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+    // Initialize a protocol with the issuer, subject, validity period, nonce, and label and sign
+    // its state.
+    let mut certification = init(&issuer.0, &subject.0, not_before, not_after, &nonce, label);
+    let sig = signer.sign(&mut certification);
+
+    Certification {
+        issuer,
+        subject: *subject,
+        not_before,
+        not_after,
+        nonce,
+        sig,
+        label: label.into(),
+    }
+}
+
+fn init(
+    issuer: &PubKey,
+    subject: &PubKey,
+    not_before: u64,
+    not_after: u64,
+    nonce: &[u8],
+    label: &str,
+) -> Protocol {
+    // Initialize a protocol.
+    let mut certification = Protocol::new("veil.certification");
+
+    // Mix the issuer's and subject's public keys into the protocol.
+    certification.mix("issuer", &issuer.encoded);
+    certification.mix("subject", &subject.encoded);
+
+    // Mix the nonce into the protocol.
+    certification.mix("nonce", nonce);
+
+    // Mix the validity period and label into the protocol.
+    certification.mix("not-before", &not_before.to_le_bytes());
+    certification.mix("not-after", &not_after.to_le_bytes());
+    certification.mix("label", label.as_bytes());
+
+    certification
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn round_trip() {
+        let (_, issuer, subject, certification) = setup();
+
+        assert_matches!(certification.verify(&issuer.public_key()), Ok(()));
+        assert_eq!(issuer.public_key(), certification.issuer());
+        assert_eq!(subject, certification.subject());
+        assert_eq!("alice@example.com", certification.label());
+        assert!(!certification.is_valid_at(1_699_999_999));
+        assert!(certification.is_valid_at(1_700_000_000));
+        assert!(certification.is_valid_at(1_800_000_000));
+        assert!(!certification.is_valid_at(1_800_000_001));
+    }
+
+    #[test]
+    fn wrong_issuer() {
+        let (mut rng, _, _, certification) = setup();
+
+        let wrong_key = PrivateKey::random(&mut rng).public_key();
+        assert_matches!(certification.verify(&wrong_key), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn modified_subject() {
+        let (mut rng, issuer, _, mut certification) = setup();
+
+        certification.subject = PublicKey::random(&mut rng);
+        assert_matches!(
+            certification.verify(&issuer.public_key()),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn modified_label() {
+        let (_, issuer, _, mut certification) = setup();
+
+        certification.label = "mallory@example.com".into();
+        assert_matches!(
+            certification.verify(&issuer.public_key()),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn modified_validity() {
+        let (_, issuer, _, mut certification) = setup();
+
+        certification.not_after = u64::MAX;
+        assert_matches!(
+            certification.verify(&issuer.public_key()),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let (_, issuer, _, certification) = setup();
+
+        let decoded = certification.to_string().parse::<Certification>();
+        assert_eq!(Ok(&certification), decoded.as_ref(), "error parsing certification");
+        assert_matches!(decoded.expect("should parse").verify(&issuer.public_key()), Ok(()));
+
+        assert_eq!(
+            Err(ParseCertificationError::InvalidCertification),
+            bs58::encode([0u8; MIN_CERTIFICATION_LEN - 1]).into_string().parse::<Certification>()
+        );
+        assert_eq!(
+            Err(ParseCertificationError::InvalidEncoding(bs58::decode::Error::InvalidCharacter {
+                character: 'l',
+                index: 4,
+            })),
+            "invalid certification".parse::<Certification>()
+        );
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, PublicKey, Certification) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let issuer = PrivateKey::random(&mut rng);
+        let subject = PrivateKey::random(&mut rng).public_key();
+        let certification =
+            certify(&mut rng, &issuer, &subject, "alice@example.com", 1_700_000_000, 1_800_000_000);
+        (rng, issuer, subject, certification)
+    }
+}
@@ -0,0 +1,226 @@
+//! Constant-time comparisons and checked decoding of points and scalars.
+//!
+//! Every comparison of secret or secret-derived bytes in Veil goes through [`eq`], and every point
+//! and scalar is decoded with the helpers here, which only reveal whether decoding succeeded when
+//! the caller asks them to.
+
+use crate::{
+    curve::{Curve, Point, Scalar, Selected},
+    keys::PubKey,
+};
+
+/// Returns `true` iff the two slices are equal, in time which depends only on their lengths.
+#[must_use]
+pub fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && lockstitch::ct_eq(a, b)
+}
+
+/// Decodes the given slice as a canonically-encoded point in constant time. Returns the point and
+/// `true` if successful; otherwise, returns the neutral point and `false`.
+pub(crate) fn decode_point(b: &[u8]) -> (Point, bool) {
+    Selected::decode_point(b)
+}
+
+/// Decodes the given slice as a canonically-encoded scalar in constant time. Returns the scalar
+/// and `true` if successful; otherwise, returns zero and `false`.
+pub(crate) fn decode_scalar(b: &[u8]) -> (Scalar, bool) {
+    Selected::decode_scalar(b)
+}
+
+/// Decodes the given slice as a public key in constant time. Returns the public key and `true` if
+/// the slice is a canonically-encoded point other than the neutral point; otherwise, returns the
+/// generator as a public key and `false`, so that the result can always be safely passed to a
+/// [`crate::KemBackend`].
+pub(crate) fn decode_pub_key(b: &[u8]) -> (PubKey, bool) {
+    let mut q = Point::NEUTRAL;
+    let ok = q.set_decode(b) & !q.isneutral();
+    q.set_cond(&Point::BASE, !ok);
+    (PubKey { q, encoded: q.encode() }, ok != 0)
+}
+
+/// Decodes the given slice as a canonically-encoded point, if possible.
+pub(crate) fn point(b: &[u8]) -> Option<Point> {
+    let (q, ok) = decode_point(b);
+    ok.then_some(q)
+}
+
+/// Decodes the given slice as a canonically-encoded scalar, if possible.
+pub(crate) fn scalar(b: &[u8]) -> Option<Scalar> {
+    let (d, ok) = decode_scalar(b);
+    ok.then_some(d)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+    use crate::keys::POINT_LEN;
+
+    #[test]
+    fn equality() {
+        assert!(eq(b"a secret", b"a secret"));
+        assert!(!eq(b"a secret", b"a secreT"));
+        assert!(!eq(b"a secret", b"a secre"));
+        assert!(eq(b"", b""));
+    }
+
+    #[test]
+    fn points() {
+        let q = Point::mulgen(&Scalar::from_u64(7));
+        assert_matches!(point(&q.encode()), Some(p) if p.equals(q) != 0);
+        assert!(point(&q.encode()[1..]).is_none());
+        assert!(point(&[0xFF; POINT_LEN]).is_none());
+
+        let (pk, ok) = decode_pub_key(&q.encode());
+        assert!(ok);
+        assert_eq!(q.encode(), pk.encoded);
+
+        let (pk, ok) = decode_pub_key(&Point::NEUTRAL.encode());
+        assert!(!ok, "the neutral point should not be a valid public key");
+        assert_eq!(Point::BASE.encode(), pk.encoded);
+
+        assert!(!decode_pub_key(&[0xFF; POINT_LEN]).1);
+    }
+
+    #[test]
+    fn scalars() {
+        let d = Scalar::from_u64(7);
+        assert_matches!(scalar(&d.encode()), Some(s) if s.equals(d) != 0);
+        assert!(scalar(&[0xFF; 32]).is_none());
+        assert!(scalar(&d.encode()[1..]).is_none());
+    }
+}
+
+/// A dudect-style statistical test for timing leaks: operations are timed on inputs from two
+/// classes in random order, and Welch's t-test checks whether the classes' timings are
+/// distinguishable.
+///
+/// Run with `cargo test -p veil --release --features dudect ct::dudect`.
+#[cfg(all(test, feature = "dudect"))]
+mod dudect {
+    use std::{hint::black_box, io, time::Instant};
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{EncryptOptions, HeaderSlot, PrivateKey};
+
+    /// The value of `|t|` above which two classes' timings are considered distinguishable. This is
+    /// dudect's threshold for "definitely not constant time".
+    const T_THRESHOLD: f64 = 10.0;
+
+    #[test]
+    fn equality() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+
+        // Compare equal slices and slices which differ in their first byte.
+        let equal = (0..64)
+            .map(|_| {
+                let a = rng.gen::<[u8; 64]>();
+                (a, a)
+            })
+            .collect::<Vec<_>>();
+        let unequal = (0..64)
+            .map(|_| {
+                let (a, mut b) = (rng.gen::<[u8; 64]>(), rng.gen::<[u8; 64]>());
+                b[0] = !a[0];
+                (a, b)
+            })
+            .collect::<Vec<_>>();
+
+        let t = measure(&mut rng, [&equal, &unequal], 1_000_000, |(a, b)| {
+            black_box(eq(black_box(a), black_box(b)));
+        });
+        assert!(t.abs() < T_THRESHOLD, "t = {t}");
+    }
+
+    #[test]
+    fn header_scanning() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let receivers = [receiver.public_key()];
+
+        // Encrypt messages with the receiver's header first and messages with it last.
+        let mut messages = |slots: &dyn Fn() -> Vec<HeaderSlot>| {
+            (0..32)
+                .map(|_| {
+                    let mut ciphertext = Vec::new();
+                    sender
+                        .encrypt(
+                            &mut rng,
+                            &b"a message"[..],
+                            &mut ciphertext,
+                            &EncryptOptions::new(&receivers).slots(slots()),
+                        )
+                        .expect("should encrypt");
+                    ciphertext
+                })
+                .collect::<Vec<_>>()
+        };
+        let fakes = || vec![HeaderSlot::Fake; 7];
+        let first = messages(&|| [vec![HeaderSlot::Receiver(0)], fakes()].concat());
+        let last = messages(&|| [fakes(), vec![HeaderSlot::Receiver(0)]].concat());
+
+        let t = measure(&mut rng, [&first, &last], 4_000, |ciphertext| {
+            receiver
+                .decrypt(&ciphertext[..], io::sink(), &sender.public_key())
+                .expect("should decrypt");
+        });
+        assert!(t.abs() < T_THRESHOLD, "t = {t}");
+    }
+
+    /// Time the given operation on inputs picked at random from each of the two classes and return
+    /// Welch's t statistic for the two classes' timings.
+    fn measure<T>(
+        mut rng: impl Rng,
+        classes: [&[T]; 2],
+        samples: usize,
+        mut op: impl FnMut(&T),
+    ) -> f64 {
+        let mut timings = Vec::with_capacity(samples);
+        for _ in 0..samples {
+            let class = usize::from(rng.gen::<bool>());
+            let input = &classes[class][rng.gen_range(0..classes[class].len())];
+            let start = Instant::now();
+            op(input);
+            timings.push((class, start.elapsed().as_nanos() as f64));
+        }
+
+        // Discard the slowest tenth of the timings, which are dominated by interrupts and
+        // scheduling rather than by the operation.
+        let mut sorted = timings.iter().map(|&(_, t)| t).collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+        let cutoff = sorted[samples * 9 / 10];
+
+        let mut test = WelchTest::default();
+        for (class, t) in timings.into_iter().filter(|&(_, t)| t <= cutoff) {
+            test.push(class, t);
+        }
+        test.t()
+    }
+
+    /// Running means and variances of two classes' timings, using Welford's algorithm.
+    #[derive(Default)]
+    struct WelchTest {
+        n: [f64; 2],
+        mean: [f64; 2],
+        m2: [f64; 2],
+    }
+
+    impl WelchTest {
+        fn push(&mut self, class: usize, x: f64) {
+            self.n[class] += 1.0;
+            let delta = x - self.mean[class];
+            self.mean[class] += delta / self.n[class];
+            self.m2[class] += delta * (x - self.mean[class]);
+        }
+
+        fn t(&self) -> f64 {
+            let var = |c: usize| self.m2[c] / (self.n[c] - 1.0) / self.n[c];
+            (self.mean[0] - self.mean[1]) / (var(0) + var(1)).sqrt()
+        }
+    }
+}
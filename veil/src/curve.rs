@@ -0,0 +1,318 @@
+//! The prime-order groups Veil's protocols can be built on.
+//!
+//! Every protocol in Veil uses the points and scalars of a single group, selected when the crate
+//! is built: [`Gls254`] by default, or `Jq255e` with the `jq255e` feature. Both groups have
+//! prime order and 32-byte canonical encodings of points and scalars, so the protocols and their
+//! wire formats are otherwise the same, but keys, ciphertexts, and signatures made with one group
+//! can't be used with the other.
+//!
+//! The [`Curve`] trait describes what the protocols need from a group. Most of it is provided by
+//! both of `crrl`'s implementations; the rest, like how short challenge scalars are expanded and
+//! how they're multiplied in variable time, is where the groups differ.
+
+use core::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+#[cfg(feature = "jq255e")]
+use crrl::jq255e;
+use crrl::{field, gls254};
+
+use crate::keys::{POINT_LEN, SCALAR_LEN};
+
+/// The group Veil's protocols are built on.
+#[cfg(not(feature = "jq255e"))]
+pub type Selected = Gls254;
+
+/// The group Veil's protocols are built on.
+#[cfg(feature = "jq255e")]
+pub type Selected = Jq255e;
+
+/// A point of the selected group.
+pub(crate) type Point = <Selected as Curve>::Point;
+
+/// A scalar of the selected group.
+pub(crate) type Scalar = <Selected as Curve>::Scalar;
+
+/// A prime-order group with canonical 32-byte encodings of its points and scalars.
+pub trait Curve {
+    /// The name of the group.
+    const NAME: &'static str;
+
+    /// An element of the group.
+    type Point: Copy
+        + Add<Output = Self::Point>
+        + AddAssign
+        + Sub<Output = Self::Point>
+        + SubAssign
+        + Neg<Output = Self::Point>
+        + Mul<Self::Scalar, Output = Self::Point>;
+
+    /// An integer modulo the order of the group.
+    type Scalar: Copy
+        + Add<Output = Self::Scalar>
+        + Sub<Output = Self::Scalar>
+        + Mul<Output = Self::Scalar>;
+
+    /// The neutral element of the group.
+    const NEUTRAL: Self::Point;
+
+    /// The conventional generator of the group.
+    const BASE: Self::Point;
+
+    /// Returns the canonical encoding of the given point.
+    fn encode_point(p: &Self::Point) -> [u8; POINT_LEN];
+
+    /// Decodes the given slice as a canonically-encoded point in constant time. Returns the point
+    /// and `true` if successful; otherwise, returns the neutral point and `false`.
+    fn decode_point(b: &[u8]) -> (Self::Point, bool);
+
+    /// Returns `true` if the given slice is the canonical encoding of a field element, whether or
+    /// not it's the encoding of a point.
+    fn is_canonical(b: &[u8]) -> bool;
+
+    /// Returns `true` iff the given point is the neutral element, in constant time.
+    fn is_neutral(p: &Self::Point) -> bool;
+
+    /// Returns the canonical encoding of the given scalar.
+    fn encode_scalar(d: &Self::Scalar) -> [u8; SCALAR_LEN];
+
+    /// Decodes the given slice as a canonically-encoded scalar in constant time. Returns the
+    /// scalar and `true` if successful; otherwise, returns zero and `false`.
+    fn decode_scalar(b: &[u8]) -> (Self::Scalar, bool);
+
+    /// Decodes the given little-endian bytes as an integer and reduces it modulo the group's
+    /// order.
+    fn reduce_scalar(b: &[u8]) -> Self::Scalar;
+
+    /// Returns `[d]G`, in constant time.
+    fn mulgen(d: &Self::Scalar) -> Self::Point;
+
+    /// Hashes the given data to a point for which no discrete logarithm is known.
+    fn hash_to_point(data: &[u8]) -> Self::Point;
+
+    /// Expands a 128-bit challenge, given as two 64-bit halves, into a scalar.
+    fn challenge(r0: u64, r1: u64) -> Self::Scalar;
+
+    /// Returns `[r]Q + [s]G` for the scalar `r` of the given challenge, in variable time.
+    fn mul_challenge_add_mulgen_vartime(
+        q: Self::Point,
+        r0: u64,
+        r1: u64,
+        s: &Self::Scalar,
+    ) -> Self::Point;
+
+    /// Splits `[k]Q` into two products of points and integers of at most 128 bits, in variable
+    /// time.
+    fn split_vartime(q: Self::Point, k: &Self::Scalar) -> [(u128, Self::Point); 2];
+}
+
+/// GLS254, a binary elliptic curve with an efficient endomorphism, as implemented by `crrl`.
+#[derive(Clone, Copy, Debug)]
+pub struct Gls254;
+
+impl Curve for Gls254 {
+    const NAME: &'static str = "gls254";
+
+    type Point = gls254::Point;
+    type Scalar = gls254::Scalar;
+
+    const NEUTRAL: Self::Point = gls254::Point::NEUTRAL;
+    const BASE: Self::Point = gls254::Point::BASE;
+
+    fn encode_point(p: &Self::Point) -> [u8; POINT_LEN] {
+        p.encode()
+    }
+
+    fn decode_point(b: &[u8]) -> (Self::Point, bool) {
+        let mut q = Self::NEUTRAL;
+        let ok = q.set_decode(b);
+        (q, ok != 0)
+    }
+
+    fn is_canonical(b: &[u8]) -> bool {
+        field::GFb254::decode_ct(b).1 != 0
+    }
+
+    fn is_neutral(p: &Self::Point) -> bool {
+        p.isneutral() != 0
+    }
+
+    fn encode_scalar(d: &Self::Scalar) -> [u8; SCALAR_LEN] {
+        d.encode()
+    }
+
+    fn decode_scalar(b: &[u8]) -> (Self::Scalar, bool) {
+        let (d, ok) = Self::Scalar::decode_ct(b);
+        (d, ok != 0)
+    }
+
+    fn reduce_scalar(b: &[u8]) -> Self::Scalar {
+        Self::Scalar::decode_reduce(b)
+    }
+
+    fn mulgen(d: &Self::Scalar) -> Self::Point {
+        Self::Point::mulgen(d)
+    }
+
+    fn hash_to_point(data: &[u8]) -> Self::Point {
+        Self::Point::hash_to_curve("", data)
+    }
+
+    fn challenge(r0: u64, r1: u64) -> Self::Scalar {
+        // Expand the challenge as r₀ + r₁µ, where µ is the square root of -1 which the curve's
+        // endomorphism multiplies by, so it can be multiplied without doubling past 64 bits.
+        Self::Scalar::from_u64(r0) + Self::Scalar::MU * Self::Scalar::from_u64(r1)
+    }
+
+    fn mul_challenge_add_mulgen_vartime(
+        q: Self::Point,
+        r0: u64,
+        r1: u64,
+        s: &Self::Scalar,
+    ) -> Self::Point {
+        q.mul64mu_add_mulgen_vartime(r0, r1, s)
+    }
+
+    fn split_vartime(q: Self::Point, k: &Self::Scalar) -> [(u128, Self::Point); 2] {
+        // Split the scalar into [k₀]Q + [k₁µ]Q using the zeta endomorphism.
+        let (k0, neg0, k1, neg1) = Self::Point::split_mu(k);
+        let mut q0 = q;
+        q0.set_condneg(neg0);
+        [(k0, q0), (k1, q.zeta(neg1))]
+    }
+}
+
+/// jq255e, a double-odd elliptic curve in Jacobi quartic form, as implemented by `crrl`.
+#[cfg(feature = "jq255e")]
+#[derive(Clone, Copy, Debug)]
+pub struct Jq255e;
+
+#[cfg(feature = "jq255e")]
+impl Curve for Jq255e {
+    const NAME: &'static str = "jq255e";
+
+    type Point = jq255e::Point;
+    type Scalar = jq255e::Scalar;
+
+    const NEUTRAL: Self::Point = jq255e::Point::NEUTRAL;
+    const BASE: Self::Point = jq255e::Point::BASE;
+
+    fn encode_point(p: &Self::Point) -> [u8; POINT_LEN] {
+        p.encode()
+    }
+
+    fn decode_point(b: &[u8]) -> (Self::Point, bool) {
+        let mut q = Self::NEUTRAL;
+        let ok = q.set_decode(b);
+        (q, ok != 0)
+    }
+
+    fn is_canonical(b: &[u8]) -> bool {
+        field::GF255e::decode_ct(b).1 != 0
+    }
+
+    fn is_neutral(p: &Self::Point) -> bool {
+        p.isneutral() != 0
+    }
+
+    fn encode_scalar(d: &Self::Scalar) -> [u8; SCALAR_LEN] {
+        d.encode()
+    }
+
+    fn decode_scalar(b: &[u8]) -> (Self::Scalar, bool) {
+        let (d, ok) = Self::Scalar::decode_ct(b);
+        (d, ok != 0)
+    }
+
+    fn reduce_scalar(b: &[u8]) -> Self::Scalar {
+        Self::Scalar::decode_reduce(b)
+    }
+
+    fn mulgen(d: &Self::Scalar) -> Self::Point {
+        Self::Point::mulgen(d)
+    }
+
+    fn hash_to_point(data: &[u8]) -> Self::Point {
+        Self::Point::hash_to_curve("", data)
+    }
+
+    fn challenge(r0: u64, r1: u64) -> Self::Scalar {
+        Self::Scalar::from_u128(u128::from(r0) | u128::from(r1) << 64)
+    }
+
+    fn mul_challenge_add_mulgen_vartime(
+        q: Self::Point,
+        r0: u64,
+        r1: u64,
+        s: &Self::Scalar,
+    ) -> Self::Point {
+        q.mul128_add_mulgen_vartime(u128::from(r0) | u128::from(r1) << 64, s)
+    }
+
+    fn split_vartime(q: Self::Point, k: &Self::Scalar) -> [(u128, Self::Point); 2] {
+        // Split the scalar into its low and high 128 bits, as [k₀]Q + [k₁][2¹²⁸]Q.
+        let k = k.encode();
+        let k0 = u128::from_le_bytes(k[..16].try_into().expect("should be 16 bytes"));
+        let k1 = u128::from_le_bytes(k[16..].try_into().expect("should be 16 bytes"));
+        [(k0, q), (k1, q.xdouble(128))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn gls254() {
+        check::<Gls254>();
+    }
+
+    #[cfg(feature = "jq255e")]
+    #[test]
+    fn jq255e() {
+        check::<Jq255e>();
+    }
+
+    /// Checks that the group's encodings and the operations the protocols rely on agree.
+    fn check<C: Curve>() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let d = C::reduce_scalar(&rng.gen::<[u8; 64]>());
+        let q = C::mulgen(&d);
+
+        // Points and scalars round-trip through their canonical encodings.
+        let (q_p, ok) = C::decode_point(&C::encode_point(&q));
+        assert!(ok, "should decode a point");
+        assert_eq!(C::encode_point(&q), C::encode_point(&q_p));
+        assert!(C::is_canonical(&C::encode_point(&q)));
+        assert!(!C::decode_point(&[0xFF; POINT_LEN]).1, "should not decode an invalid point");
+        assert!(!C::is_canonical(&[0xFF; POINT_LEN]));
+        let (d_p, ok) = C::decode_scalar(&C::encode_scalar(&d));
+        assert!(ok, "should decode a scalar");
+        assert_eq!(C::encode_scalar(&d), C::encode_scalar(&d_p));
+        assert!(!C::decode_scalar(&[0xFF; SCALAR_LEN]).1, "should not decode an invalid scalar");
+
+        // The neutral point has its own encoding.
+        assert!(C::is_neutral(&C::NEUTRAL));
+        assert!(!C::is_neutral(&q));
+        assert!(C::is_neutral(&C::decode_point(&C::encode_point(&C::NEUTRAL)).0));
+
+        // [r]Q + [s]G is the same calculated either way.
+        let (r0, r1, s) = (rng.gen(), rng.gen(), C::reduce_scalar(&rng.gen::<[u8; 64]>()));
+        assert_eq!(
+            C::encode_point(&(q * C::challenge(r0, r1) + C::mulgen(&s))),
+            C::encode_point(&C::mul_challenge_add_mulgen_vartime(q, r0, r1, &s)),
+        );
+
+        // A split product is the same as the whole.
+        let [(k0, q0), (k1, q1)] = C::split_vartime(q, &d);
+        assert_eq!(
+            C::encode_point(&(q * d)),
+            C::encode_point(
+                &(q0 * C::reduce_scalar(&k0.to_le_bytes())
+                    + q1 * C::reduce_scalar(&k1.to_le_bytes()))
+            ),
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Hierarchical key derivation with proofs of derivation.
+
+use alloc::vec::Vec;
+use core::{fmt, str::FromStr};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::{PrivKey, PubKey, POINT_LEN},
+    schnorr::{self, DET_SIGNATURE_LEN},
+    sres::NONCE_LEN,
+    ParseProofOfDerivationError, PublicKey, VerifyError,
+};
+
+/// The length of a path commitment, in bytes.
+pub const PATH_COMMITMENT_LEN: usize = 32;
+
+/// The length of an encoded proof of derivation, in bytes.
+pub const PROOF_OF_DERIVATION_LEN: usize =
+    POINT_LEN + POINT_LEN + PATH_COMMITMENT_LEN + NONCE_LEN + DET_SIGNATURE_LEN + DET_SIGNATURE_LEN;
+
+/// A statement, signed by both a root private key and a private key derived from it, that the
+/// derived key belongs to the root key's hierarchy.
+///
+/// Consists of the root public key, the derived public key, a commitment to the derivation path, a
+/// nonce, and a signature from each key. The path commitment is derived from the root key's secret
+/// and the path, so it reveals nothing about the path or about any other derived key, but the same
+/// path always has the same commitment.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProofOfDerivation {
+    root: PublicKey,
+    derived: PublicKey,
+    commitment: [u8; PATH_COMMITMENT_LEN],
+    nonce: [u8; NONCE_LEN],
+    root_sig: [u8; DET_SIGNATURE_LEN],
+    derived_sig: [u8; DET_SIGNATURE_LEN],
+}
+
+impl ProofOfDerivation {
+    /// Returns the root public key.
+    #[must_use]
+    pub const fn root(&self) -> PublicKey {
+        self.root
+    }
+
+    /// Returns the derived public key.
+    #[must_use]
+    pub const fn derived(&self) -> PublicKey {
+        self.derived
+    }
+
+    /// Returns the commitment to the derivation path.
+    #[must_use]
+    pub const fn commitment(&self) -> [u8; PATH_COMMITMENT_LEN] {
+        self.commitment
+    }
+
+    /// Verifies that the proof was signed by the private keys of both the root and the derived
+    /// public keys. Returns `Ok(())` if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the proof has been modified, returns [`VerifyError::InvalidSignature`].
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let mut proof = init(&self.root.0, &self.derived.0, &self.commitment, &self.nonce);
+        schnorr::det_verify(&mut proof, &self.root.0, self.root_sig)
+            .and_then(|()| schnorr::det_verify(&mut proof, &self.derived.0, self.derived_sig))
+            .ok_or(VerifyError::InvalidSignature)
+    }
+
+    /// Decode a proof of derivation from a byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<ProofOfDerivation> {
+        let b = b.as_ref();
+        if b.len() != PROOF_OF_DERIVATION_LEN {
+            return None;
+        }
+
+        // Split the proof into components.
+        let (root, b) = b.split_at(POINT_LEN);
+        let (derived, b) = b.split_at(POINT_LEN);
+        let (commitment, b) = b.split_at(PATH_COMMITMENT_LEN);
+        let (nonce, b) = b.split_at(NONCE_LEN);
+        let (root_sig, derived_sig) = b.split_at(DET_SIGNATURE_LEN);
+
+        Some(ProofOfDerivation {
+            root: PublicKey::decode(root)?,
+            derived: PublicKey::decode(derived)?,
+            commitment: commitment.try_into().expect("should be commitment-sized"),
+            nonce: nonce.try_into().expect("should be nonce-sized"),
+            root_sig: root_sig.try_into().expect("should be signature-sized"),
+            derived_sig: derived_sig.try_into().expect("should be signature-sized"),
+        })
+    }
+
+    /// Encode the proof of derivation as a byte vector.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(PROOF_OF_DERIVATION_LEN);
+        b.extend_from_slice(&self.root.encode());
+        b.extend_from_slice(&self.derived.encode());
+        b.extend_from_slice(&self.commitment);
+        b.extend_from_slice(&self.nonce);
+        b.extend_from_slice(&self.root_sig);
+        b.extend_from_slice(&self.derived_sig);
+        b
+    }
+}
+
+impl FromStr for ProofOfDerivation {
+    type Err = ParseProofOfDerivationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ProofOfDerivation::decode(bs58::decode(s).into_vec()?)
+            .ok_or(ParseProofOfDerivationError::InvalidProof)
+    }
+}
+
+impl fmt::Display for ProofOfDerivation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    }
+}
+
+/// Derives the private key at the given path below the given root, and the commitment to the path.
+pub fn derive(root: &PrivKey, path: &str) -> (PrivKey, [u8; PATH_COMMITMENT_LEN]) {
+    // Initialize a protocol.
+    let mut derivation = Protocol::new("veil.derivation");
+
+    // Mix the root's secret into the protocol.
+    derivation.mix("secret", &root.secret);
+
+    // Mix the path into the protocol.
+    derivation.mix("path", path.as_bytes());
+
+    // Derive the path commitment and the derived key's secret.
+    let commitment = derivation.derive_array("commitment");
+    let derived = PrivKey::from_secret_bytes(derivation.derive_array("secret"));
+
+    (derived, commitment)
+}
+
+/// Creates a proof that the key at the given path was derived from the given root.
+pub fn prove(mut rng: impl Rng + CryptoRng, root: &PrivKey, path: &str) -> ProofOfDerivation {
+    let (derived, commitment) = derive(root, path);
+
+    // Generate a random nonce.
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+    // Initialize a protocol with both public keys, the path commitment, and the nonce, and sign its
+    // state first with the root key and then with the derived key.
+    let mut proof = init(&root.pub_key, &derived.pub_key, &commitment, &nonce);
+    let root_sig = schnorr::det_sign(&mut proof, root);
+    let derived_sig = schnorr::det_sign(&mut proof, &derived);
+
+    ProofOfDerivation {
+        root: PublicKey(root.pub_key),
+        derived: PublicKey(derived.pub_key),
+        commitment,
+        nonce,
+        root_sig,
+        derived_sig,
+    }
+}
+
+fn init(
+    root: &PubKey,
+    derived: &PubKey,
+    commitment: &[u8; PATH_COMMITMENT_LEN],
+    nonce: &[u8],
+) -> Protocol {
+    // Initialize a protocol.
+    let mut proof = Protocol::new("veil.proof-of-derivation");
+
+    // Mix both public keys into the protocol.
+    proof.mix("root", &root.encoded);
+    proof.mix("derived", &derived.encoded);
+
+    // Mix the nonce into the protocol.
+    proof.mix("nonce", nonce);
+
+    // Mix the path commitment into the protocol.
+    proof.mix("commitment", commitment);
+
+    proof
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn round_trip() {
+        let (_, root, proof) = setup();
+
+        assert_matches!(proof.verify(), Ok(()));
+        assert_eq!(root.public_key(), proof.root());
+        assert_eq!(root.derive_path("devices/laptop").public_key(), proof.derived());
+    }
+
+    #[test]
+    fn stable_commitments() {
+        let (mut rng, root, proof) = setup();
+
+        assert_eq!(proof.commitment(), prove(&mut rng, &root.0, "devices/laptop").commitment());
+        assert_ne!(proof.commitment(), prove(&mut rng, &root.0, "devices/phone").commitment());
+    }
+
+    #[test]
+    fn modified_derived_key() {
+        let (mut rng, _, mut proof) = setup();
+
+        proof.derived = PublicKey::random(&mut rng);
+        assert_matches!(proof.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn modified_commitment() {
+        let (_, _, mut proof) = setup();
+
+        proof.commitment[0] ^= 1;
+        assert_matches!(proof.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn other_root() {
+        let (mut rng, _, mut proof) = setup();
+
+        proof.root = PrivateKey::random(&mut rng).public_key();
+        assert_matches!(proof.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn encoding() {
+        let (_, _, proof) = setup();
+
+        let decoded = proof.to_string().parse::<ProofOfDerivation>();
+        assert_eq!(Ok(&proof), decoded.as_ref(), "error parsing proof of derivation");
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, ProofOfDerivation) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let root = PrivateKey::random(&mut rng);
+        let proof = root.prove_derivation(&mut rng, "devices/laptop");
+        (rng, root, proof)
+    }
+}
@@ -1,12 +1,22 @@
-use std::{fmt, io, io::Read, str::FromStr};
+use std::{
+    fmt, fs,
+    fs::File,
+    io,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
 
-use lockstitch::Protocol;
+use lockstitch::{MixWriter, Protocol};
 
-use crate::ParseDigestError;
+use crate::{ct, DigestLength, DigestOptions, ParseDigestError};
 
 /// The digest of a sequence of metadata values and a message.
 #[derive(Clone, Copy, Debug, Eq)]
-pub struct Digest([u8; DIGEST_LEN]);
+pub struct Digest {
+    bytes: [u8; MAX_DIGEST_LEN],
+    len: usize,
+}
 
 impl Digest {
     /// Create a digest from a sequence of metadata values and a reader.
@@ -14,34 +24,120 @@ impl Digest {
     /// # Errors
     ///
     /// Returns any error returned by operations on `reader`.
-    pub fn new(metadata: &[impl AsRef<[u8]>], mut reader: impl Read) -> io::Result<Digest> {
+    pub fn new(metadata: &[impl AsRef<[u8]>], reader: impl Read) -> io::Result<Digest> {
+        Digest::new_with(metadata, reader, &DigestOptions::new())
+    }
+
+    /// Create a digest from a sequence of metadata values and a reader with the given options,
+    /// which may key the digest and set its length. Both are bound into the digest, so digests
+    /// with different keys or lengths are unrelated. An unkeyed 32-byte digest is the same as the
+    /// one returned by [`Digest::new`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error returned by operations on `reader`.
+    pub fn new_with(
+        metadata: &[impl AsRef<[u8]>],
+        mut reader: impl Read,
+        options: &DigestOptions<'_>,
+    ) -> io::Result<Digest> {
+        // Mix the reader contents into the protocol.
+        let mut writer = Digest::writer_with(metadata, options);
+        io::copy(&mut reader, &mut writer)?;
+        Ok(writer.finalize())
+    }
+
+    /// Create a [`DigestWriter`] for a sequence of metadata values. The message is written to it
+    /// incrementally and [`DigestWriter::finalize`] returns the same digest as [`Digest::new`]
+    /// would for the concatenation of all written data.
+    #[must_use]
+    pub fn writer(metadata: &[impl AsRef<[u8]>]) -> DigestWriter {
+        Digest::writer_with(metadata, &DigestOptions::new())
+    }
+
+    /// Create a [`DigestWriter`] for a sequence of metadata values with the given options. See
+    /// [`Digest::new_with`].
+    #[must_use]
+    pub fn writer_with(metadata: &[impl AsRef<[u8]>], options: &DigestOptions<'_>) -> DigestWriter {
         // Initialize a protocol.
         let mut digest = Protocol::new("veil.digest");
 
+        // Mix the key, if any, into the protocol.
+        if let Some(key) = options.key {
+            digest.mix("key", key);
+        }
+
+        // Mix the length, if it's not the default, into the protocol. Unkeyed 32-byte digests are
+        // left unchanged from before digests could be keyed or lengthened.
+        if options.length != DigestLength::Bytes32 {
+            digest.mix("length", &[options.length.byte_len() as u8]);
+        }
+
         // Mix the metadata values in order into the protocol.
         for v in metadata {
             digest.mix("metadata", v.as_ref());
         }
 
-        // Mix the reader contents into the protocol.
-        let mut writer = digest.mix_writer("message", io::sink());
-        io::copy(&mut reader, &mut writer)?;
-        let (mut digest, _) = writer.into_inner();
+        DigestWriter(digest.mix_writer("message", io::sink()), options.length)
+    }
+
+    /// Create a digest from a sequence of metadata values and the contents of a directory.
+    ///
+    /// The directory is walked recursively with each directory's entries sorted by name, and the
+    /// digest covers the name, type, and contents of every file, subdirectory, and symbolic link
+    /// in it. Of a file's metadata, only whether it's executable is included, so the same tree
+    /// produces the same digest on every platform regardless of ownership, timestamps, or the
+    /// order in which the filesystem lists entries. Symbolic links are not followed; their targets
+    /// are digested instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error returned by filesystem operations. Entry names which aren't valid UTF-8
+    /// return [`io::ErrorKind::InvalidData`], and entries which aren't files, directories, or
+    /// symbolic links return [`io::ErrorKind::Unsupported`].
+    pub fn for_dir(path: impl AsRef<Path>, metadata: &[impl AsRef<[u8]>]) -> io::Result<Digest> {
+        // Initialize a protocol.
+        let mut digest = Protocol::new("veil.digest.tree");
+
+        // Mix the metadata values in order into the protocol.
+        for v in metadata {
+            digest.mix("metadata", v.as_ref());
+        }
+
+        // Mix the digest of the root directory into the protocol.
+        digest.mix("root", &dir_digest(path.as_ref())?);
 
         // Derive 32 bytes as a digest.
-        Ok(Digest(digest.derive_array("digest")))
+        Ok(Digest::derive(&mut digest, DigestLength::Bytes32))
     }
 
-    /// Create a digest from a 32-byte slice.
+    /// Create a digest from a 32- or 64-byte slice.
     #[must_use]
     pub fn decode(b: impl AsRef<[u8]>) -> Option<Digest> {
-        Some(Digest(b.as_ref().try_into().ok()?))
+        let b = b.as_ref();
+        if b.len() != DigestLength::Bytes32.byte_len()
+            && b.len() != DigestLength::Bytes64.byte_len()
+        {
+            return None;
+        }
+        let mut bytes = [0u8; MAX_DIGEST_LEN];
+        bytes[..b.len()].copy_from_slice(b);
+        Some(Digest { bytes, len: b.len() })
     }
 
-    /// Encode the digest as a 32-byte array.
+    /// Encode the digest as a 32- or 64-byte slice.
+    ///
+    /// This is the raw output of the digest's protocol, and is stable across versions.
     #[must_use]
-    pub const fn encode(&self) -> [u8; DIGEST_LEN] {
-        self.0
+    pub fn encode(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Derive a digest of the given length from the protocol.
+    fn derive(protocol: &mut Protocol, length: DigestLength) -> Digest {
+        let mut bytes = [0u8; MAX_DIGEST_LEN];
+        protocol.derive("digest", &mut bytes[..length.byte_len()]);
+        Digest { bytes, len: length.byte_len() }
     }
 }
 
@@ -56,17 +152,107 @@ impl FromStr for Digest {
 
 impl fmt::Display for Digest {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", bs58::encode(self.0).into_string())
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
     }
 }
 
 impl PartialEq for Digest {
     fn eq(&self, other: &Self) -> bool {
-        lockstitch::ct_eq(&self.0, &other.0)
+        ct::eq(self.encode(), other.encode())
+    }
+}
+
+/// A writer which incrementally digests a message. Created by [`Digest::writer`].
+#[derive(Debug)]
+pub struct DigestWriter(MixWriter<io::Sink>, DigestLength);
+
+impl DigestWriter {
+    /// Finish digesting the message and return its digest.
+    #[must_use]
+    pub fn finalize(self) -> Digest {
+        let (mut digest, _) = self.0.into_inner();
+
+        // Derive the digest.
+        Digest::derive(&mut digest, self.1)
+    }
+}
+
+impl Write for DigestWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Calculate the digest of a directory's entries, recursing into subdirectories.
+fn dir_digest(path: &Path) -> io::Result<[u8; 32]> {
+    // Read the directory's entries and sort them by name.
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| {
+            let entry = entry?;
+            let name = entry.file_name().into_string().map_err(|name| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("non-UTF-8 name: {name:?}"))
+            })?;
+            Ok((name, entry))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    // Initialize a protocol.
+    let mut dir = Protocol::new("veil.digest.dir");
+
+    for (name, entry) in entries {
+        // Mix the entry's name into the protocol.
+        dir.mix("name", name.as_bytes());
+
+        // Mix the entry's type and contents into the protocol.
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            dir.mix("type", b"directory");
+            dir.mix("contents", &dir_digest(&entry.path())?);
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            let target = target.to_str().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("non-UTF-8 target: {target:?}"))
+            })?;
+            dir.mix("type", b"symlink");
+            dir.mix("contents", target.as_bytes());
+        } else if file_type.is_file() {
+            dir.mix(
+                "type",
+                if is_executable(&entry.metadata()?) { b"executable" } else { b"file" },
+            );
+            let mut writer = dir.mix_writer("contents", io::sink());
+            io::copy(&mut File::open(entry.path())?, &mut writer)?;
+            (dir, _) = writer.into_inner();
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported file type: {:?}", entry.path()),
+            ));
+        }
     }
+
+    // Derive 32 bytes as the directory's digest.
+    Ok(dir.derive_array("digest"))
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
 }
 
-const DIGEST_LEN: usize = 32;
+#[cfg(not(unix))]
+const fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+const MAX_DIGEST_LEN: usize = 64;
 
 #[cfg(test)]
 mod tests {
@@ -123,10 +309,84 @@ mod tests {
         assert_ne!(a, b, "collision on message");
     }
 
+    #[test]
+    fn incremental() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let message = rng.gen::<[u8; 64]>();
+        let md_one = rng.gen::<[u8; 16]>();
+        let md_two = rng.gen::<[u8; 16]>();
+
+        let a = Digest::new(&[&md_one, &md_two], Cursor::new(&message))
+            .expect("cursor reads should be infallible");
+
+        let mut writer = Digest::writer(&[&md_one, &md_two]);
+        for chunk in message.chunks(7) {
+            writer.write_all(chunk).expect("writes should be infallible");
+        }
+        let b = writer.finalize();
+
+        assert_eq!(a, b, "inconsistent digests");
+    }
+
+    #[test]
+    fn keyed() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let message = rng.gen::<[u8; 64]>();
+        let key = rng.gen::<[u8; 32]>();
+        let other_key = rng.gen::<[u8; 32]>();
+
+        let digest = |options: &DigestOptions<'_>| {
+            Digest::new_with(&["a"], Cursor::new(&message), options)
+                .expect("cursor reads should be infallible")
+        };
+
+        let unkeyed = digest(&DigestOptions::new());
+        let a = digest(&DigestOptions::new().key(&key));
+        let b = digest(&DigestOptions::new().key(&key));
+        let c = digest(&DigestOptions::new().key(&other_key));
+
+        assert_eq!(a, b, "inconsistent digests");
+        assert_ne!(a, unkeyed, "collision between keyed and unkeyed digests");
+        assert_ne!(a, c, "collision on key");
+        assert_eq!(
+            Digest::new(&["a"], Cursor::new(&message)).expect("cursor reads should be infallible"),
+            unkeyed,
+            "default options should be unkeyed"
+        );
+    }
+
+    #[test]
+    fn lengths() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let message = rng.gen::<[u8; 64]>();
+
+        let short =
+            Digest::new(&["a"], Cursor::new(&message)).expect("cursor reads should be infallible");
+        let long = Digest::new_with(
+            &["a"],
+            Cursor::new(&message),
+            &DigestOptions::new().length(DigestLength::Bytes64),
+        )
+        .expect("cursor reads should be infallible");
+
+        assert_eq!(32, short.encode().len());
+        assert_eq!(64, long.encode().len());
+        assert_ne!(short.encode(), &long.encode()[..32], "long digest extends short digest");
+
+        let mut writer =
+            Digest::writer_with(&["a"], &DigestOptions::new().length(DigestLength::Bytes64));
+        writer.write_all(&message).expect("writes should be infallible");
+        assert_eq!(long, writer.finalize(), "inconsistent digests");
+
+        assert_eq!(Some(long), Digest::decode(long.encode()), "invalid decoded digest");
+        assert_eq!(Ok(long), long.to_string().parse::<Digest>(), "invalid parsed digest");
+        assert_eq!(None, Digest::decode([0u8; 48]), "decoded invalid length");
+    }
+
     #[test]
     fn encoding() {
         let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
-        let sig = Digest(rng.gen());
+        let sig = Digest::decode(rng.gen::<[u8; 32]>()).expect("should decode");
 
         assert_eq!(
             "9tKd8hrpibubFKGV6QELAQ9q5if5fWuGH2rfHML4vZyL",
@@ -0,0 +1,195 @@
+//! Designated-verifier Schnorr signatures.
+
+use std::{fmt, io, io::Read, str::FromStr};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::PubKey,
+    sres::{self, DESIGNATED_SIGNATURE_LEN, NONCE_LEN},
+    KemBackend, ParseSignatureError, PublicKey, SignerBackend, VerifyError,
+};
+
+/// The length of a designated-verifier signature, in bytes.
+pub const SIGNATURE_LEN: usize = NONCE_LEN + DESIGNATED_SIGNATURE_LEN;
+
+/// A designated-verifier Schnorr signature, which can only be verified by the owner of the
+/// verifier's private key.
+///
+/// Consists of a 16-byte nonce, an encrypted commitment point, and an encrypted proof point.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DesignatedSignature([u8; SIGNATURE_LEN]);
+
+impl DesignatedSignature {
+    /// Create a designated-verifier signature from a 80-byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<DesignatedSignature> {
+        Some(DesignatedSignature(b.as_ref().try_into().ok()?))
+    }
+
+    /// Encode the designated-verifier signature as a 80-byte array.
+    #[must_use]
+    pub const fn encode(&self) -> [u8; SIGNATURE_LEN] {
+        self.0
+    }
+}
+
+impl FromStr for DesignatedSignature {
+    type Err = ParseSignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DesignatedSignature::decode(bs58::decode(s).into_vec()?.as_slice())
+            .ok_or(ParseSignatureError::InvalidLength)
+    }
+}
+
+impl fmt::Display for DesignatedSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.0).into_string())
+    }
+}
+
+/// Create a randomized designated-verifier signature of the given message using the given signer,
+/// verifiable only by the owner of the given verifier's private key.
+pub fn sign(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    verifier: &PubKey,
+    message: impl Read,
+) -> io::Result<DesignatedSignature> {
+    // Allocate an output buffer.
+    let mut sig = [0u8; SIGNATURE_LEN];
+
+    // Generate a random nonce.
+    rng.fill_bytes(&mut sig[..NONCE_LEN]);
+
+    // Initialize a protocol with the signer, verifier, nonce, and message.
+    let mut dvsig = init(&signer.public_key().0, verifier, &sig[..NONCE_LEN], message)?;
+
+    // Calculate the encrypted commitment point and proof point.
+    sig[NONCE_LEN..].copy_from_slice(&signer.sign_designated(&mut dvsig, &PublicKey(*verifier)));
+    Ok(DesignatedSignature(sig))
+}
+
+/// Verify a designated-verifier signature of the given message using the verifier's private key
+/// and the signer's public key.
+pub fn verify(
+    verifier: &impl KemBackend,
+    signer: &PubKey,
+    message: impl Read,
+    sig: &DesignatedSignature,
+) -> Result<(), VerifyError> {
+    let mut sig = sig.0;
+    let (nonce, sig) = sig.split_at_mut(NONCE_LEN);
+
+    // Initialize a protocol with the signer, verifier, nonce, and message.
+    let mut dvsig = init(signer, &verifier.public_key().0, nonce, message)?;
+
+    // Verify the signature.
+    sres::det_verify_designated(&mut dvsig, verifier, signer, sig)
+        .ok_or(VerifyError::InvalidSignature)
+}
+
+fn init(
+    signer: &PubKey,
+    verifier: &PubKey,
+    nonce: &[u8],
+    mut message: impl Read,
+) -> io::Result<Protocol> {
+    // Initialize a protocol.
+    let mut dvsig = Protocol::new("veil.dvsig");
+
+    // Mix the signer's and verifier's public keys into the protocol.
+    dvsig.mix("signer", &signer.encoded);
+    dvsig.mix("verifier", &verifier.encoded);
+
+    // Mix the nonce into the protocol.
+    dvsig.mix("nonce", nonce);
+
+    // Mix the message into the protocol.
+    let mut writer = dvsig.mix_writer("message", io::sink());
+    io::copy(&mut message, &mut writer)?;
+    let (dvsig, _) = writer.into_inner();
+
+    Ok(dvsig)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::keys::PrivKey;
+
+    #[test]
+    fn sign_and_verify() {
+        let (_, signer, verifier, message, sig) = setup();
+        assert_matches!(
+            verify(&verifier, &signer.pub_key, Cursor::new(message), &sig),
+            Ok(()),
+            "should have verified a valid signature"
+        );
+    }
+
+    #[test]
+    fn modified_message() {
+        let (mut rng, signer, verifier, _, sig) = setup();
+        let wrong_message = rng.gen::<[u8; 64]>();
+        assert_matches!(
+            verify(&verifier, &signer.pub_key, Cursor::new(wrong_message), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn wrong_signer() {
+        let (mut rng, _, verifier, message, sig) = setup();
+        let wrong_signer = PubKey::random(&mut rng);
+        assert_matches!(
+            verify(&verifier, &wrong_signer, Cursor::new(message), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn wrong_verifier() {
+        let (mut rng, signer, _, message, sig) = setup();
+        let wrong_verifier = PrivKey::random(&mut rng);
+        assert_matches!(
+            verify(&wrong_verifier, &signer.pub_key, Cursor::new(message), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn modified_sig() {
+        let (_, signer, verifier, message, mut sig) = setup();
+        sig.0[22] ^= 1;
+        assert_matches!(
+            verify(&verifier, &signer.pub_key, Cursor::new(message), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn signature_decoding() {
+        let (_, _, _, _, sig) = setup();
+        let decoded = sig.to_string().parse::<DesignatedSignature>();
+        assert_eq!(Ok(sig), decoded, "error parsing signature");
+    }
+
+    fn setup() -> (ChaChaRng, PrivKey, PrivKey, Vec<u8>, DesignatedSignature) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let signer = PrivKey::random(&mut rng);
+        let verifier = PrivKey::random(&mut rng);
+        let message = rng.gen::<[u8; 64]>();
+        let sig = sign(&mut rng, &signer, &verifier.pub_key, Cursor::new(message))
+            .expect("signing should be ok");
+        (rng, signer, verifier, message.to_vec(), sig)
+    }
+}
@@ -0,0 +1,277 @@
+//! Confidential file metadata carried inside encrypted messages.
+//!
+//! An envelope is a length-prefixed header at the start of a plaintext which records the original
+//! file's name, modification time, and mode bits. Because the envelope is part of the plaintext, it
+//! is encrypted and authenticated along with the rest of the message:
+//!
+//! ```text
+//! [length: u32 LE][flags: u8][modified: u64 LE][mode: u32 LE][name: UTF-8]
+//! ```
+//!
+//! [`FileMetadata::wrap`] prepends an envelope to a reader before encryption, and
+//! [`EnvelopeWriter`] strips it from the plaintext after decryption.
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::{self, Cursor, Read, Write},
+    mem,
+};
+
+/// The length of the envelope's length prefix.
+const PREFIX_LEN: usize = size_of::<u32>();
+
+/// The length of the envelope's fixed-size fields.
+const FIXED_LEN: usize = 1 + size_of::<u64>() + size_of::<u32>();
+
+/// The maximum length of an envelope, excluding the length prefix.
+const MAX_LEN: usize = 64 * 1024;
+
+/// The flag indicating the modification time is present.
+const HAS_MODIFIED: u8 = 1 << 0;
+
+/// The flag indicating the mode bits are present.
+const HAS_MODE: u8 = 1 << 1;
+
+/// The metadata of a file stored in an envelope.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// The file's name, if any.
+    pub name: Option<String>,
+
+    /// The file's modification time, in seconds since the Unix epoch, if known.
+    pub modified: Option<u64>,
+
+    /// The file's Unix mode bits, if known.
+    pub mode: Option<u32>,
+}
+
+impl FileMetadata {
+    /// Encode the metadata as a length-prefixed envelope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file name is longer than 64KiB.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let name = self.name.as_deref().unwrap_or_default().as_bytes();
+        let len = FIXED_LEN + name.len();
+        assert!(len <= MAX_LEN, "file name should be <= 64KiB");
+
+        let mut flags = 0;
+        if self.modified.is_some() {
+            flags |= HAS_MODIFIED;
+        }
+        if self.mode.is_some() {
+            flags |= HAS_MODE;
+        }
+
+        let mut b = Vec::with_capacity(PREFIX_LEN + len);
+        b.extend_from_slice(&u32::try_from(len).expect("len should be <= u32").to_le_bytes());
+        b.push(flags);
+        b.extend_from_slice(&self.modified.unwrap_or_default().to_le_bytes());
+        b.extend_from_slice(&self.mode.unwrap_or_default().to_le_bytes());
+        b.extend_from_slice(name);
+        b
+    }
+
+    /// Decode metadata from an envelope, excluding the length prefix.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<FileMetadata> {
+        let b = b.as_ref();
+        if b.len() < FIXED_LEN {
+            return None;
+        }
+
+        // Split the envelope into components.
+        let (&flags, b) = b.split_first()?;
+        let (modified, b) = b.split_at(size_of::<u64>());
+        let (mode, name) = b.split_at(size_of::<u32>());
+
+        // Decode components.
+        let modified = u64::from_le_bytes(modified.try_into().expect("should be 8 bytes"));
+        let mode = u32::from_le_bytes(mode.try_into().expect("should be 4 bytes"));
+        let name = std::str::from_utf8(name).ok()?;
+
+        Some(FileMetadata {
+            name: (!name.is_empty()).then(|| name.into()),
+            modified: (flags & HAS_MODIFIED != 0).then_some(modified),
+            mode: (flags & HAS_MODE != 0).then_some(mode),
+        })
+    }
+
+    /// Returns a reader which reads the metadata's envelope followed by the contents of `reader`.
+    pub fn wrap<R: Read>(&self, reader: R) -> impl Read {
+        Cursor::new(self.encode()).chain(reader)
+    }
+}
+
+/// A writer which strips an envelope from the start of a plaintext and writes the rest to an inner
+/// writer.
+///
+/// The inner writer is opened with the decoded metadata once the envelope has been written, so the
+/// metadata can be used to decide where to write the plaintext. [`EnvelopeWriter::finish`] must be
+/// called to check that an envelope was written.
+pub struct EnvelopeWriter<W, F> {
+    header: Vec<u8>,
+    state: State<W, F>,
+}
+
+enum State<W, F> {
+    Header(F),
+    Body(FileMetadata, W),
+    Failed,
+}
+
+impl<W, F> EnvelopeWriter<W, F>
+where
+    W: Write,
+    F: FnOnce(&FileMetadata) -> io::Result<W>,
+{
+    /// Creates a new envelope writer which calls `open` with the decoded metadata to open the
+    /// inner writer.
+    pub const fn new(open: F) -> EnvelopeWriter<W, F> {
+        EnvelopeWriter { header: Vec::new(), state: State::Header(open) }
+    }
+
+    /// Returns the decoded metadata and the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::UnexpectedEof`] error if a complete envelope was not written.
+    pub fn finish(self) -> io::Result<(FileMetadata, W)> {
+        match self.state {
+            State::Body(metadata, writer) => Ok((metadata, writer)),
+            _ => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "missing metadata envelope")),
+        }
+    }
+
+    /// Returns the number of bytes of the envelope which have not yet been written.
+    fn remaining(&self) -> io::Result<usize> {
+        if self.header.len() < PREFIX_LEN {
+            return Ok(PREFIX_LEN - self.header.len());
+        }
+
+        let len =
+            u32::from_le_bytes(self.header[..PREFIX_LEN].try_into().expect("should be 4 bytes"));
+        match usize::try_from(len) {
+            Ok(len) if (FIXED_LEN..=MAX_LEN).contains(&len) => {
+                Ok(PREFIX_LEN + len - self.header.len())
+            }
+            _ => Err(invalid_envelope()),
+        }
+    }
+}
+
+impl<W, F> Write for EnvelopeWriter<W, F>
+where
+    W: Write,
+    F: FnOnce(&FileMetadata) -> io::Result<W>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.state {
+            State::Body(_, ref mut writer) => writer.write(buf),
+            State::Header(_) => {
+                // Buffer as much of the envelope as is available.
+                let n = buf.len().min(self.remaining()?);
+                self.header.extend_from_slice(&buf[..n]);
+
+                // If the envelope is complete, decode it and open the inner writer.
+                if self.remaining()? == 0 {
+                    let State::Header(open) = mem::replace(&mut self.state, State::Failed) else {
+                        unreachable!("should be reading the envelope");
+                    };
+                    let metadata = FileMetadata::decode(&self.header[PREFIX_LEN..])
+                        .ok_or_else(invalid_envelope)?;
+                    let writer = open(&metadata)?;
+                    self.state = State::Body(metadata, writer);
+                }
+
+                Ok(n)
+            }
+            State::Failed => Err(invalid_envelope()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Body(_, ref mut writer) => writer.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<W, F> Debug for EnvelopeWriter<W, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvelopeWriter").finish_non_exhaustive()
+    }
+}
+
+fn invalid_envelope() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid metadata envelope")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let metadata = FileMetadata {
+            name: Some("message.txt".into()),
+            modified: Some(1_700_000_000),
+            mode: Some(0o644),
+        };
+
+        let mut plaintext = Vec::new();
+        metadata.wrap(&b"a message"[..]).read_to_end(&mut plaintext).expect("should read");
+
+        let (decoded, body) = strip(&plaintext).expect("should strip envelope");
+        assert_eq!(metadata, decoded, "invalid metadata");
+        assert_eq!(b"a message".to_vec(), body, "invalid body");
+    }
+
+    #[test]
+    fn empty_metadata() {
+        let metadata = FileMetadata::default();
+        let encoded = metadata.encode();
+        assert_eq!(PREFIX_LEN + FIXED_LEN, encoded.len());
+        assert_eq!(Some(metadata), FileMetadata::decode(&encoded[PREFIX_LEN..]));
+    }
+
+    #[test]
+    fn byte_at_a_time() {
+        let metadata = FileMetadata { name: Some("a".into()), modified: None, mode: Some(0o600) };
+        let mut plaintext = metadata.encode();
+        plaintext.extend_from_slice(b"a message");
+
+        let mut writer = EnvelopeWriter::new(|_: &FileMetadata| Ok(Vec::new()));
+        for b in &plaintext {
+            writer.write_all(std::slice::from_ref(b)).expect("should write");
+        }
+        let (decoded, body) = writer.finish().expect("should finish");
+        assert_eq!(metadata, decoded, "invalid metadata");
+        assert_eq!(b"a message".to_vec(), body, "invalid body");
+    }
+
+    #[test]
+    fn truncated_envelope() {
+        let encoded = FileMetadata::default().encode();
+        assert_eq!(
+            io::ErrorKind::UnexpectedEof,
+            strip(&encoded[..encoded.len() - 1]).expect_err("should fail").kind()
+        );
+    }
+
+    #[test]
+    fn oversized_envelope() {
+        let mut plaintext = u32::MAX.to_le_bytes().to_vec();
+        plaintext.extend_from_slice(&[0u8; FIXED_LEN]);
+        assert_eq!(io::ErrorKind::InvalidData, strip(&plaintext).expect_err("should fail").kind());
+    }
+
+    fn strip(plaintext: &[u8]) -> io::Result<(FileMetadata, Vec<u8>)> {
+        let mut writer = EnvelopeWriter::new(|_: &FileMetadata| Ok(Vec::new()));
+        writer.write_all(plaintext)?;
+        writer.finish()
+    }
+}
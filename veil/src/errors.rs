@@ -1,9 +1,14 @@
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
 use std::io;
 
+#[cfg(feature = "std")]
 use thiserror::Error;
 
 /// An error returned when encrypting a message was unsuccessful.
+#[cfg(feature = "std")]
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum EncryptError {
     /// Encryption was unsuccessful due to an IO error reading the plaintext.
     #[error("error reading plaintext")]
@@ -12,18 +17,89 @@ pub enum EncryptError {
     /// Encryption was unsuccessful due to an IO error writing the ciphertext.
     #[error("error writing ciphertext")]
     WriteIo(#[source] io::Error),
+
+    /// Encryption was unsuccessful because the message would have more than [`MAX_RECEIVERS`]
+    /// headers for its receivers, decoys, and fake receivers.
+    ///
+    /// [`MAX_RECEIVERS`]: crate::MAX_RECEIVERS
+    #[error("too many receivers")]
+    TooManyReceivers,
+
+    /// Encryption was unsuccessful because the message would have more than [`MAX_PADDING`] bytes
+    /// of padding.
+    ///
+    /// [`MAX_PADDING`]: crate::MAX_PADDING
+    #[error("too much padding")]
+    TooMuchPadding,
+
+    /// Encryption was unsuccessful because the plaintext was longer than [`MAX_MESSAGE_LEN`] bytes.
+    ///
+    /// [`MAX_MESSAGE_LEN`]: crate::MAX_MESSAGE_LEN
+    #[error("message too long")]
+    MessageTooLong,
 }
 
 /// An error returned when decrypting a message was unsuccessful.
+#[cfg(feature = "std")]
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum DecryptError {
-    /// Decryption was unsuccessful due to a message/private key/public key mismatch.
+    /// Decryption was unsuccessful because the passphrase was incorrect or the ciphertext was
+    /// altered.
     ///
-    /// The ciphertext may have been altered, the message may not have been encrypted by the given
-    /// sender, or the message may not have been encrypted for the given receiver.
+    /// Messages encrypted with a key pair return [`DecryptError::NotAReceiver`],
+    /// [`DecryptError::Corrupt`], or [`DecryptError::Truncated`] instead.
     #[error("invalid ciphertext")]
     InvalidCiphertext,
 
+    /// Decryption was unsuccessful because none of the message's headers could be decrypted.
+    ///
+    /// The message may not have been encrypted for the given receiver, may not have been encrypted
+    /// by the given sender, or may have been encrypted with different associated data.
+    #[error("not a receiver of the message")]
+    NotAReceiver,
+
+    /// Decryption was unsuccessful because the message was altered after its header was decrypted:
+    /// a block of the message or its signature was invalid.
+    #[error("corrupt ciphertext")]
+    Corrupt,
+
+    /// Decryption was unsuccessful because the message ended before its headers, padding, payload,
+    /// or signature were complete.
+    #[error("truncated ciphertext")]
+    Truncated,
+
+    /// Decryption was unsuccessful because the message was encrypted with a version of the message
+    /// format which is not supported.
+    #[error("unsupported message format version {0}")]
+    UnsupportedVersion(u16),
+
+    /// Random access was unsuccessful because the message was not encrypted as a seekable message.
+    #[error("message is not seekable")]
+    NotSeekable,
+
+    /// Decryption was unsuccessful because none of the message's headers could be decrypted with
+    /// the expected channel ID and sequence number.
+    ///
+    /// The message may have been replayed or reordered, may belong to another channel, or may not
+    /// have been encrypted for the given receiver at all; these are indistinguishable.
+    #[error("message is out of sequence")]
+    SequenceMismatch,
+
+    /// Decryption was unsuccessful because the message's nonce, headers, and padding were longer
+    /// than the maximum set with [`DecryptOptions::max_header_len`].
+    ///
+    /// [`DecryptOptions::max_header_len`]: crate::DecryptOptions::max_header_len
+    #[error("headers too long")]
+    HeadersTooLong,
+
+    /// Decryption was unsuccessful because the plaintext was longer than the maximum set with
+    /// [`DecryptOptions::max_plaintext_len`]. The plaintext up to the maximum has been written.
+    ///
+    /// [`DecryptOptions::max_plaintext_len`]: crate::DecryptOptions::max_plaintext_len
+    #[error("plaintext too long")]
+    PlaintextTooLong,
+
     /// Decryption was unsuccessful due to an IO error reading the ciphertext.
     #[error("error reading ciphertext")]
     ReadIo(#[source] io::Error),
@@ -33,47 +109,436 @@ pub enum DecryptError {
     WriteIo(#[source] io::Error),
 }
 
-/// An error returned when verifying a signature was unsuccessful.
+/// An error returned when signing a message was unsuccessful.
+#[cfg(feature = "std")]
 #[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SignError {
+    /// Signing was cancelled before the entire message was read.
+    #[error("signing cancelled")]
+    Cancelled,
+
+    /// Signing was unsuccessful due to an IO error reading the message.
+    #[error("error reading message")]
+    ReadIo(#[source] io::Error),
+}
+
+/// An error returned when verifying a signature was unsuccessful.
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum VerifyError {
     /// Verification was unsuccessful due to a signature/message/public key mismatch.
     ///
     /// The message or signature may have been altered, or the message may not have been signed with
     /// the given key.
-    #[error("invalid signature")]
     InvalidSignature,
 
     /// Verification was unsuccessful due to an IO error reading the message.
-    #[error("error reading message")]
-    ReadIo(#[from] io::Error),
+    #[cfg(feature = "std")]
+    ReadIo(io::Error),
+
+    /// Verification was unsuccessful due to an IO error writing the message.
+    #[cfg(feature = "std")]
+    WriteIo(io::Error),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::InvalidSignature => f.write_str("invalid signature"),
+            #[cfg(feature = "std")]
+            VerifyError::ReadIo(_) => f.write_str("error reading message"),
+            #[cfg(feature = "std")]
+            VerifyError::WriteIo(_) => f.write_str("error writing message"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VerifyError::InvalidSignature => None,
+            VerifyError::ReadIo(err) | VerifyError::WriteIo(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for VerifyError {
+    fn from(err: io::Error) -> Self {
+        VerifyError::ReadIo(err)
+    }
 }
 
 /// An error returned when parsing a signature was unsuccessful.
-#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum ParseSignatureError {
     /// Parsing failed because the value was not the correct length.
-    #[error("invalid signature length")]
     InvalidLength,
 
     /// Parsing failed because the signature was not valid base58.
-    #[error("invalid base58 encoding")]
-    InvalidEncoding(#[from] bs58::decode::Error),
+    InvalidEncoding(bs58::decode::Error),
+}
+
+impl Display for ParseSignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSignatureError::InvalidLength => f.write_str("invalid signature length"),
+            ParseSignatureError::InvalidEncoding(_) => f.write_str("invalid base58 encoding"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseSignatureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseSignatureError::InvalidLength => None,
+            ParseSignatureError::InvalidEncoding(err) => Some(err),
+        }
+    }
+}
+
+impl From<bs58::decode::Error> for ParseSignatureError {
+    fn from(err: bs58::decode::Error) -> Self {
+        ParseSignatureError::InvalidEncoding(err)
+    }
 }
 
 /// An error returned when parsing a public key was unsuccessful.
-#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum ParsePublicKeyError {
-    /// Parsing failed because the value was not a valid public key.
-    #[error("invalid public key")]
+    /// Parsing failed because the value was not a public key, e.g. a URI of another kind.
     InvalidPublicKey,
 
     /// Parsing failed because the public key was not valid base58.
+    InvalidEncoding(bs58::decode::Error),
+
+    /// Parsing failed because the public key decoded to the given number of bytes instead of 32.
+    InvalidLength(usize),
+
+    /// Parsing failed because the public key was not a canonically-encoded field element.
+    NonCanonical,
+
+    /// Parsing failed because the public key did not encode a point on the curve.
+    NotOnCurve,
+
+    /// Parsing failed because the public key was the neutral point.
+    NeutralPoint,
+}
+
+impl Display for ParsePublicKeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePublicKeyError::InvalidPublicKey => f.write_str("invalid public key"),
+            ParsePublicKeyError::InvalidEncoding(_) => f.write_str("invalid base58 encoding"),
+            ParsePublicKeyError::InvalidLength(n) => {
+                write!(f, "invalid public key length: {n} bytes instead of 32")
+            }
+            ParsePublicKeyError::NonCanonical => f.write_str("non-canonical public key encoding"),
+            ParsePublicKeyError::NotOnCurve => {
+                f.write_str("public key is not a point on the curve")
+            }
+            ParsePublicKeyError::NeutralPoint => f.write_str("public key is the neutral point"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePublicKeyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParsePublicKeyError::InvalidEncoding(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<bs58::decode::Error> for ParsePublicKeyError {
+    fn from(err: bs58::decode::Error) -> Self {
+        ParsePublicKeyError::InvalidEncoding(err)
+    }
+}
+
+/// An error returned when parsing a revocation was unsuccessful.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParseRevocationError {
+    /// Parsing failed because the value was not a valid revocation.
+    InvalidRevocation,
+
+    /// Parsing failed because the revocation was not valid base58.
+    InvalidEncoding(bs58::decode::Error),
+}
+
+impl Display for ParseRevocationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRevocationError::InvalidRevocation => f.write_str("invalid revocation"),
+            ParseRevocationError::InvalidEncoding(_) => f.write_str("invalid base58 encoding"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseRevocationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseRevocationError::InvalidRevocation => None,
+            ParseRevocationError::InvalidEncoding(err) => Some(err),
+        }
+    }
+}
+
+impl From<bs58::decode::Error> for ParseRevocationError {
+    fn from(err: bs58::decode::Error) -> Self {
+        ParseRevocationError::InvalidEncoding(err)
+    }
+}
+
+/// An error returned when parsing or verifying a certification was unsuccessful.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParseCertificationError {
+    /// Parsing failed because the value was not a valid certification.
+    InvalidCertification,
+
+    /// Parsing failed because the certification was not valid base58.
+    InvalidEncoding(bs58::decode::Error),
+
+    /// Verification failed because the certification was not signed by the expected issuer.
+    InvalidSignature,
+
+    /// Verification failed because the certification is not yet valid or has expired.
+    OutsideValidityPeriod,
+}
+
+impl Display for ParseCertificationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseCertificationError::InvalidCertification => f.write_str("invalid certification"),
+            ParseCertificationError::InvalidEncoding(_) => f.write_str("invalid base58 encoding"),
+            ParseCertificationError::InvalidSignature => {
+                f.write_str("invalid certification signature")
+            }
+            ParseCertificationError::OutsideValidityPeriod => {
+                f.write_str("certification is not valid at this time")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseCertificationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseCertificationError::InvalidCertification
+            | ParseCertificationError::InvalidSignature
+            | ParseCertificationError::OutsideValidityPeriod => None,
+            ParseCertificationError::InvalidEncoding(err) => Some(err),
+        }
+    }
+}
+
+impl From<bs58::decode::Error> for ParseCertificationError {
+    fn from(err: bs58::decode::Error) -> Self {
+        ParseCertificationError::InvalidEncoding(err)
+    }
+}
+
+/// An error returned when parsing or verifying a proof of derivation was unsuccessful.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParseProofOfDerivationError {
+    /// Parsing failed because the value was not a valid proof of derivation.
+    InvalidProof,
+
+    /// Parsing failed because the proof was not valid base58.
+    InvalidEncoding(bs58::decode::Error),
+
+    /// Verification failed because the proof was not signed by the expected root key and its
+    /// derived key.
+    InvalidSignature,
+}
+
+impl Display for ParseProofOfDerivationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseProofOfDerivationError::InvalidProof => f.write_str("invalid proof of derivation"),
+            ParseProofOfDerivationError::InvalidEncoding(_) => {
+                f.write_str("invalid base58 encoding")
+            }
+            ParseProofOfDerivationError::InvalidSignature => {
+                f.write_str("invalid proof of derivation signature")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseProofOfDerivationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseProofOfDerivationError::InvalidProof
+            | ParseProofOfDerivationError::InvalidSignature => None,
+            ParseProofOfDerivationError::InvalidEncoding(err) => Some(err),
+        }
+    }
+}
+
+impl From<bs58::decode::Error> for ParseProofOfDerivationError {
+    fn from(err: bs58::decode::Error) -> Self {
+        ParseProofOfDerivationError::InvalidEncoding(err)
+    }
+}
+
+/// An error returned when parsing or verifying a set of subkeys was unsuccessful.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParseSubkeysError {
+    /// Parsing failed because the value was not a valid set of subkeys.
+    InvalidSubkeys,
+
+    /// Parsing failed because the subkeys were not valid base58.
+    InvalidEncoding(bs58::decode::Error),
+
+    /// Verification failed because the subkeys were not signed by the expected identity.
+    InvalidSignature,
+}
+
+impl Display for ParseSubkeysError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSubkeysError::InvalidSubkeys => f.write_str("invalid subkeys"),
+            ParseSubkeysError::InvalidEncoding(_) => f.write_str("invalid base58 encoding"),
+            ParseSubkeysError::InvalidSignature => f.write_str("invalid subkeys signature"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseSubkeysError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseSubkeysError::InvalidSubkeys | ParseSubkeysError::InvalidSignature => None,
+            ParseSubkeysError::InvalidEncoding(err) => Some(err),
+        }
+    }
+}
+
+impl From<bs58::decode::Error> for ParseSubkeysError {
+    fn from(err: bs58::decode::Error) -> Self {
+        ParseSubkeysError::InvalidEncoding(err)
+    }
+}
+
+/// An error returned when parsing or importing a key bundle was unsuccessful.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParseKeyBundleError {
+    /// Parsing failed because the value was not a valid key bundle.
+    InvalidBundle,
+
+    /// Parsing failed because the key bundle was not valid base58.
+    InvalidEncoding(bs58::decode::Error),
+
+    /// Importing failed because the key bundle's signature was invalid.
+    InvalidSignature,
+}
+
+impl Display for ParseKeyBundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseKeyBundleError::InvalidBundle => f.write_str("invalid key bundle"),
+            ParseKeyBundleError::InvalidEncoding(_) => f.write_str("invalid base58 encoding"),
+            ParseKeyBundleError::InvalidSignature => f.write_str("invalid key bundle signature"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseKeyBundleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseKeyBundleError::InvalidBundle | ParseKeyBundleError::InvalidSignature => None,
+            ParseKeyBundleError::InvalidEncoding(err) => Some(err),
+        }
+    }
+}
+
+impl From<bs58::decode::Error> for ParseKeyBundleError {
+    fn from(err: bs58::decode::Error) -> Self {
+        ParseKeyBundleError::InvalidEncoding(err)
+    }
+}
+
+/// An error returned when parsing an artifact was unsuccessful.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[non_exhaustive]
+pub enum ParseArtifactError {
+    /// Parsing failed because the value was not a canonically-encoded artifact.
+    #[error("invalid artifact")]
+    InvalidArtifact,
+
+    /// Parsing failed because the artifact was not valid base58.
     #[error("invalid base58 encoding")]
     InvalidEncoding(#[from] bs58::decode::Error),
 }
 
+/// An error returned when parsing a paper key was unsuccessful.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParsePaperKeyError {
+    /// Parsing failed because the paper key did not have the correct number of words.
+    InvalidLength,
+
+    /// Parsing failed because the word at the given zero-based index is not in the word
+    /// list.
+    InvalidWord(usize),
+
+    /// Parsing failed because the paper key's checksum did not match.
+    InvalidChecksum,
+}
+
+impl Display for ParsePaperKeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePaperKeyError::InvalidLength => f.write_str("invalid number of words"),
+            ParsePaperKeyError::InvalidWord(idx) => write!(f, "invalid word at index {idx}"),
+            ParsePaperKeyError::InvalidChecksum => f.write_str("invalid checksum"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePaperKeyError {}
+
+/// An error returned when parsing an OpenSSH private key was unsuccessful.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[non_exhaustive]
+pub enum ParseOpenSshKeyError {
+    /// Parsing failed because the key was not a valid OpenSSH private key.
+    #[error("invalid OpenSSH private key")]
+    InvalidFormat,
+
+    /// Parsing failed because the key is protected by a passphrase.
+    #[error("OpenSSH private key is encrypted")]
+    Encrypted,
+
+    /// Parsing failed because the key is not an Ed25519 key.
+    #[error("unsupported OpenSSH key type: only Ed25519 keys are supported")]
+    UnsupportedKeyType,
+}
+
 /// An error returned when parsing a digest was unsuccessful.
+#[cfg(feature = "std")]
 #[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[non_exhaustive]
 pub enum ParseDigestError {
     /// Parsing failed because the value was not the correct length.
     #[error("invalid digest length")]
@@ -83,3 +548,257 @@ pub enum ParseDigestError {
     #[error("invalid base58 encoding")]
     InvalidEncoding(#[from] bs58::decode::Error),
 }
+
+/// An error returned when parsing a list of recipients was unsuccessful.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[non_exhaustive]
+pub enum ParseRecipientsError {
+    /// Parsing failed because the given one-based line did not start with a valid public key.
+    #[error("invalid public key at line {0}")]
+    InvalidPublicKey(usize, #[source] ParsePublicKeyError),
+
+    /// Parsing failed because the public key on the given one-based line was already listed on an
+    /// earlier line.
+    #[error("duplicate public key at line {0} (first listed at line {1})")]
+    DuplicatePublicKey(usize, usize),
+}
+
+/// An error returned when creating an aggregated signature was unsuccessful.
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MultisigError {
+    /// The set of signers was empty, listed a public key more than once, or aggregated to an
+    /// invalid public key.
+    #[error("invalid set of signers")]
+    InvalidSigners,
+
+    /// The signer is not one of the aggregated signers.
+    #[error("unknown signer")]
+    UnknownSigner,
+
+    /// There was not exactly one nonce or partial signature for each signer, or a secret nonce
+    /// did not match its public nonce.
+    #[error("invalid nonces")]
+    InvalidNonces,
+
+    /// The partial signature at the given index was invalid.
+    #[error("invalid partial signature at index {0}")]
+    InvalidPartialSignature(usize),
+
+    /// An IO error occurred while reading the message.
+    #[error("error reading message")]
+    ReadIo(#[source] io::Error),
+}
+
+/// An error returned when splitting a key or combining key shares was unsuccessful.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[non_exhaustive]
+pub enum ThresholdError {
+    /// The threshold was zero or greater than the number of shares.
+    #[error("invalid threshold")]
+    InvalidThreshold,
+
+    /// There were no share holders, or a share holder's index was zero or duplicated.
+    #[error("invalid key shares")]
+    InvalidShares,
+}
+
+/// An error returned when creating or checking a verification policy was unsuccessful.
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PolicyError {
+    /// The set of signers was empty or listed a public key more than once, or the threshold was
+    /// zero or greater than the number of signers.
+    #[error("invalid policy")]
+    InvalidPolicy,
+
+    /// Fewer of the policy's signers than its threshold signed the message. Contains the number of
+    /// signers whose signatures were valid and the threshold.
+    #[error("only {0} of the required {1} signers signed the message")]
+    NotEnoughSigners(usize, usize),
+
+    /// An IO error occurred while reading the message.
+    #[error("error reading message")]
+    ReadIo(#[source] io::Error),
+}
+
+/// An error returned when modifying a key store was unsuccessful.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[non_exhaustive]
+pub enum KeyStoreError {
+    /// The key name was empty or longer than 255 bytes.
+    #[error("invalid key name")]
+    InvalidName,
+
+    /// A key with the given name already exists.
+    #[error("key {0:?} already exists")]
+    DuplicateName(String),
+
+    /// No key with the given name exists.
+    #[error("unknown key {0:?}")]
+    UnknownName(String),
+}
+
+/// An error returned when parsing or verifying test vectors was unsuccessful.
+#[cfg(feature = "testvectors")]
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[non_exhaustive]
+pub enum TestVectorError {
+    /// Parsing failed because the given one-based line was malformed.
+    #[error("malformed test vector at line {0}")]
+    Malformed(usize),
+
+    /// Verification failed because the test vector's name is unknown.
+    #[error("unknown test vector {0:?}")]
+    UnknownVector(String),
+
+    /// Verification failed because the test vector is missing a value.
+    #[error("test vector {0:?} is missing {1:?}")]
+    MissingValue(String, String),
+
+    /// Verification failed because the test vector's value did not match.
+    #[error("test vector {0:?} has invalid {1:?}")]
+    Mismatch(String, String),
+}
+
+/// Any error returned by this crate.
+///
+/// Each fallible operation returns its own error type, which describes exactly how that operation
+/// can fail. Applications which combine several operations can convert all of them into this type
+/// with `?` and match on the variants they handle. The original error is always available as the
+/// [`source`](std::error::Error::source) of this one.
+///
+/// This and every other error type in this crate are `#[non_exhaustive]`, so that new ways for an
+/// operation to fail can be added without breaking code which matches on them.
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Encrypting a message was unsuccessful.
+    #[error("unable to encrypt message")]
+    Encrypt(#[from] EncryptError),
+
+    /// Decrypting a message was unsuccessful.
+    #[error("unable to decrypt message")]
+    Decrypt(#[from] DecryptError),
+
+    /// Signing a message was unsuccessful.
+    #[error("unable to sign message")]
+    Sign(#[from] SignError),
+
+    /// Verifying a signature was unsuccessful.
+    #[error("unable to verify signature")]
+    Verify(#[from] VerifyError),
+
+    /// Parsing a signature was unsuccessful.
+    #[error("unable to parse signature")]
+    ParseSignature(#[from] ParseSignatureError),
+
+    /// Parsing a public key was unsuccessful.
+    #[error("unable to parse public key")]
+    ParsePublicKey(#[from] ParsePublicKeyError),
+
+    /// Parsing a revocation was unsuccessful.
+    #[error("unable to parse revocation")]
+    ParseRevocation(#[from] ParseRevocationError),
+
+    /// Parsing or verifying a certification was unsuccessful.
+    #[error("unable to parse certification")]
+    ParseCertification(#[from] ParseCertificationError),
+
+    /// Parsing or verifying a proof of derivation was unsuccessful.
+    #[error("unable to parse proof of derivation")]
+    ParseProofOfDerivation(#[from] ParseProofOfDerivationError),
+
+    /// Parsing or verifying a set of subkeys was unsuccessful.
+    #[error("unable to parse subkeys")]
+    ParseSubkeys(#[from] ParseSubkeysError),
+
+    /// Parsing or verifying a key bundle was unsuccessful.
+    #[error("unable to parse key bundle")]
+    ParseKeyBundle(#[from] ParseKeyBundleError),
+
+    /// Parsing a paper key was unsuccessful.
+    #[error("unable to parse paper key")]
+    ParsePaperKey(#[from] ParsePaperKeyError),
+
+    /// Parsing an OpenSSH private key was unsuccessful.
+    #[error("unable to parse OpenSSH private key")]
+    ParseOpenSshKey(#[from] ParseOpenSshKeyError),
+
+    /// Parsing a digest was unsuccessful.
+    #[error("unable to parse digest")]
+    ParseDigest(#[from] ParseDigestError),
+
+    /// Parsing a list of recipients was unsuccessful.
+    #[error("unable to parse recipients")]
+    ParseRecipients(#[from] ParseRecipientsError),
+
+    /// Creating an aggregated signature was unsuccessful.
+    #[error("unable to create aggregated signature")]
+    Multisig(#[from] MultisigError),
+
+    /// Splitting a key or combining key shares was unsuccessful.
+    #[error("unable to split or combine key shares")]
+    Threshold(#[from] ThresholdError),
+
+    /// Creating or checking a verification policy was unsuccessful.
+    #[error("unable to check verification policy")]
+    Policy(#[from] PolicyError),
+
+    /// Modifying a key store was unsuccessful.
+    #[error("unable to modify key store")]
+    KeyStore(#[from] KeyStoreError),
+
+    /// Parsing or verifying an artifact was unsuccessful.
+    #[cfg(feature = "cbor")]
+    #[error("unable to parse artifact")]
+    ParseArtifact(#[from] ParseArtifactError),
+
+    /// Parsing or verifying test vectors was unsuccessful.
+    #[cfg(feature = "testvectors")]
+    #[error("unable to check test vectors")]
+    TestVector(#[from] TestVectorError),
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::error::Error as _;
+
+    use assert_matches::assert_matches;
+
+    use super::*;
+    use crate::PublicKey;
+
+    #[test]
+    fn source_chains() {
+        fn parse(s: &str) -> Result<PublicKey, Error> {
+            Ok(s.parse::<PublicKey>()?)
+        }
+
+        let err = parse("invalid public key").expect_err("should not parse");
+        assert_matches!(err, Error::ParsePublicKey(ParsePublicKeyError::InvalidEncoding(_)));
+        assert_eq!("unable to parse public key", err.to_string());
+
+        let source = err.source().expect("should have a source");
+        assert_eq!("invalid base58 encoding", source.to_string());
+        assert!(source.source().is_some(), "should have the base58 error as a source");
+    }
+
+    #[test]
+    #[cfg(feature = "cbor")]
+    fn artifact_errors() {
+        fn parse(s: &str) -> Result<crate::artifact::Artifact, Error> {
+            Ok(s.parse()?)
+        }
+
+        let err = parse("invalid artifact").expect_err("should not parse");
+        assert_matches!(err, Error::ParseArtifact(ParseArtifactError::InvalidEncoding(_)));
+        assert_eq!("unable to parse artifact", err.to_string());
+    }
+}
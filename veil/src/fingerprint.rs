@@ -0,0 +1,94 @@
+//! Short, human-comparable public key fingerprints.
+
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+use lockstitch::Protocol;
+
+use crate::{keys::PubKey, paper};
+
+/// The default length of a fingerprint, in bytes. Eleven bytes encode as exactly eight words.
+pub const FINGERPRINT_LEN: usize = 11;
+
+/// A short digest of a public key, for comparing public keys out-of-band (e.g. over the phone).
+///
+/// The [`Display`] implementation formats the fingerprint as groups of four hex digits.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fingerprint(Vec<u8>);
+
+impl Fingerprint {
+    /// Creates a fingerprint from its bytes.
+    #[cfg(feature = "cbor")]
+    pub(crate) fn from_bytes(b: impl Into<Vec<u8>>) -> Fingerprint {
+        Fingerprint(b.into())
+    }
+
+    /// Returns the fingerprint as a byte slice.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Formats the fingerprint as a sequence of words from the BIP39 English word list, separated
+    /// by spaces. Each word encodes 11 bits of the fingerprint.
+    #[must_use]
+    pub fn to_words(&self) -> String {
+        paper::words(&self.0).collect::<Vec<_>>().join(" ")
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, group) in self.0.chunks(2).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            for b in group {
+                write!(f, "{b:02X}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Derive a `len`-byte fingerprint of the given public key.
+pub fn fingerprint(pub_key: &PubKey, len: usize) -> Fingerprint {
+    // Initialize a protocol and mix the public key into it.
+    let mut fingerprint = Protocol::new("veil.fingerprint");
+    fingerprint.mix("public-key", &pub_key.encoded);
+
+    // Derive the fingerprint.
+    let mut out = vec![0u8; len];
+    fingerprint.derive("fingerprint", &mut out);
+    Fingerprint(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let fp = fingerprint(&PubKey::random(&mut rng), FINGERPRINT_LEN);
+
+        // The pinned values are for GLS254's points.
+        if cfg!(not(feature = "jq255e")) {
+            expect!["FEF2 A529 3460 7533 9242 73"].assert_eq(&fp.to_string());
+            expect!["year news engage hand also oil empower example"].assert_eq(&fp.to_words());
+        }
+        assert_eq!(8, fp.to_words().split(' ').count());
+    }
+
+    #[test]
+    fn different_keys() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = fingerprint(&PubKey::random(&mut rng), FINGERPRINT_LEN);
+        let b = fingerprint(&PubKey::random(&mut rng), FINGERPRINT_LEN);
+        assert_ne!(a, b, "fingerprint collision");
+    }
+}
@@ -0,0 +1,72 @@
+//! Entry points for fuzzing constructions which aren't otherwise public.
+//!
+//! These exist only for the targets in `fuzz/`, which encrypt valid ciphertexts to mutate, and
+//! aren't part of the stable API.
+
+use rand::{CryptoRng, Rng};
+
+use crate::{keys::PrivKey, pbenc, sres, PrivateKey, PublicKey};
+
+/// The number of bytes [`sres_encrypt`] adds to a plaintext.
+pub const SRES_OVERHEAD: usize = sres::OVERHEAD;
+
+/// The number of bytes [`pbenc_encrypt`] adds to a plaintext.
+pub const PBENC_OVERHEAD: usize = pbenc::OVERHEAD;
+
+/// Encrypts a plaintext with `sres::encrypt` for `receiver` from `sender`, with a random
+/// ephemeral key.
+#[must_use]
+pub fn sres_encrypt(
+    rng: impl Rng + CryptoRng,
+    sender: &PrivateKey,
+    receiver: &PublicKey,
+    nonce: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let mut ciphertext = vec![0u8; plaintext.len() + SRES_OVERHEAD];
+    sres::encrypt(sender, &PrivKey::random(rng), &receiver.0, nonce, plaintext, &mut ciphertext);
+    ciphertext
+}
+
+/// Decrypts a ciphertext in place with `sres::decrypt`, returning the ephemeral public key and
+/// the plaintext iff it was encrypted for `receiver` by `sender`.
+#[must_use]
+pub fn sres_decrypt<'a>(
+    receiver: &PrivateKey,
+    sender: &PublicKey,
+    nonce: &[u8],
+    in_out: &'a mut [u8],
+) -> Option<(PublicKey, &'a [u8])> {
+    let (ephemeral, plaintext) = sres::decrypt(receiver, &sender.0, nonce, in_out)?;
+    Some((PublicKey(ephemeral), plaintext))
+}
+
+/// Encrypts a plaintext with `pbenc::encrypt` using the given passphrase and parameters.
+#[must_use]
+pub fn pbenc_encrypt(
+    rng: impl Rng + CryptoRng,
+    passphrase: &[u8],
+    time_cost: u8,
+    memory_cost: u8,
+    parallelism: u8,
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let mut ciphertext = vec![0u8; plaintext.len() + PBENC_OVERHEAD];
+    pbenc::encrypt(
+        rng,
+        passphrase,
+        time_cost,
+        memory_cost,
+        parallelism,
+        plaintext,
+        &mut ciphertext,
+    );
+    ciphertext
+}
+
+/// Decrypts a ciphertext in place with `pbenc::decrypt`, returning the plaintext iff the
+/// passphrase is correct.
+#[must_use]
+pub fn pbenc_decrypt<'a>(passphrase: &[u8], in_out: &'a mut [u8]) -> Option<&'a [u8]> {
+    pbenc::decrypt(passphrase, in_out)
+}
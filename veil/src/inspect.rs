@@ -0,0 +1,163 @@
+//! Structural inspection of encrypted messages.
+//!
+//! [`inspect`] reads a message the same way decryption does but discards the plaintext, reporting
+//! where the receiver's header was, how many headers and how much padding the message has, how
+//! much of the payload authenticated, and whether the signature is valid. This is useful for
+//! debugging interoperability issues and truncated files.
+
+use std::{io::Read, slice};
+
+use crate::{mres, DecryptError, KemBackend, PublicKey};
+
+/// The structure of an encrypted message, as seen by one of its receivers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Inspection {
+    /// The version of the message format.
+    pub version: u16,
+
+    /// The zero-based index of the header which the receiver decrypted.
+    pub header_index: u64,
+
+    /// The total number of headers in the message, including fake receivers.
+    pub header_count: u64,
+
+    /// The length of the padding after the headers, in bytes.
+    pub padding_len: u64,
+
+    /// The length of the plaintext which was successfully authenticated, in bytes.
+    pub payload_len: u64,
+
+    /// The status of the rest of the message.
+    pub status: Status,
+}
+
+/// The status of an encrypted message's padding, payload, and signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The message is complete and its signature is valid.
+    Valid,
+
+    /// The message ends before the end of its padding.
+    Truncated,
+
+    /// A block of the payload could not be authenticated. The message may have been modified or
+    /// truncated.
+    InvalidPayload,
+
+    /// The payload authenticated but the signature is invalid.
+    InvalidSignature,
+}
+
+/// Reads the encrypted message from `reader` with the given receiver's private key and reports its
+/// structure without writing the plaintext.
+///
+/// # Errors
+///
+/// If no header can be decrypted by `receiver` as sent by `sender`, returns
+/// [`DecryptError::NotAReceiver`]. If the headers or nonce are truncated, returns
+/// [`DecryptError::Truncated`]. If there was an error reading from `reader`, returns
+/// [`DecryptError::ReadIo`].
+pub fn inspect(
+    receiver: &impl KemBackend,
+    reader: impl Read,
+    sender: &PublicKey,
+    associated_data: &[u8],
+) -> Result<Inspection, DecryptError> {
+    mres::inspect(reader, slice::from_ref(receiver), &sender.0, associated_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{EncryptOptions, PrivateKey};
+
+    #[test]
+    fn valid_message() {
+        let (_, sender, receiver, ciphertext) = setup();
+
+        let inspection = inspect(&receiver, ciphertext.as_slice(), &sender.public_key(), &[])
+            .expect("should inspect");
+        assert_eq!(
+            Inspection {
+                version: 1,
+                header_index: inspection.header_index,
+                header_count: 6,
+                padding_len: 123,
+                payload_len: 200_000,
+                status: Status::Valid,
+            },
+            inspection
+        );
+        assert!(inspection.header_index < 6, "invalid header index");
+    }
+
+    #[test]
+    fn truncated_padding() {
+        let (_, sender, receiver, ciphertext) = setup();
+
+        let inspection = inspect(
+            &receiver,
+            &ciphertext[..ciphertext.len() - 200_150],
+            &sender.public_key(),
+            &[],
+        )
+        .expect("should inspect");
+        assert_eq!(Status::Truncated, inspection.status);
+    }
+
+    #[test]
+    fn truncated_payload() {
+        let (_, sender, receiver, ciphertext) = setup();
+
+        let inspection =
+            inspect(&receiver, &ciphertext[..ciphertext.len() - 100], &sender.public_key(), &[])
+                .expect("should inspect");
+        assert_eq!(Status::InvalidPayload, inspection.status);
+        assert_eq!(3 * 64 * 1024, inspection.payload_len);
+    }
+
+    #[test]
+    fn modified_signature() {
+        let (_, sender, receiver, mut ciphertext) = setup();
+
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 1;
+        let inspection = inspect(&receiver, ciphertext.as_slice(), &sender.public_key(), &[])
+            .expect("should inspect");
+        assert_eq!(Status::InvalidSignature, inspection.status);
+        assert_eq!(200_000, inspection.payload_len);
+    }
+
+    #[test]
+    fn wrong_receiver() {
+        let (mut rng, sender, _, ciphertext) = setup();
+
+        let wrong_receiver = PrivateKey::random(&mut rng);
+        assert_matches!(
+            inspect(&wrong_receiver, ciphertext.as_slice(), &sender.public_key(), &[]),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, PrivateKey, Vec<u8>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(
+                &mut rng,
+                vec![0u8; 200_000].as_slice(),
+                &mut ciphertext,
+                &EncryptOptions::new(&[receiver.public_key()]).fakes(5).padding(123),
+            )
+            .expect("should encrypt");
+
+        (rng, sender, receiver, ciphertext)
+    }
+}
@@ -0,0 +1,279 @@
+//! Passphrase-based encryption of stored private keys with a choice of key derivation function.
+//!
+//! Envelopes encrypted with `veil.pbenc` are written exactly as [`pbenc::encrypt`] produces them,
+//! beginning with [`pbenc::LANES_TAG`]. Envelopes encrypted with Argon2id begin with
+//! [`ARGON2ID_TAG`]. Untagged `veil.pbenc` envelopes never begin with either in practice: as a time
+//! cost, they would require 2^162 or more iterations. Untagged envelopes are decrypted with the
+//! single-lane `veil.pbenc`, so keys stored before lanes or Argon2id were supported can still be
+//! read.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use lockstitch::{Protocol, TAG_LEN};
+use rand::{CryptoRng, Rng};
+
+use crate::pbenc;
+
+/// The key derivation function used to encrypt a private key with a passphrase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    /// `veil.pbenc`, Veil's balloon hashing construction.
+    Balloon {
+        /// The time cost, in 2^t iterations.
+        time_cost: u8,
+        /// The memory cost, in 2^m KiB.
+        memory_cost: u8,
+        /// The number of lanes hashed concurrently. A parallelism of `0` is treated as `1`, and one
+        /// above 64 or 2^m as the lower of the two.
+        parallelism: u8,
+    },
+
+    /// Argon2id version 19, as specified in RFC 9106.
+    ///
+    /// Parameters below Argon2's minimums are raised to them.
+    Argon2id {
+        /// The number of passes over memory.
+        time_cost: u32,
+        /// The memory cost, in KiB.
+        memory_cost: u32,
+        /// The number of lanes.
+        parallelism: u32,
+    },
+}
+
+/// The first byte of an Argon2id envelope.
+pub const ARGON2ID_TAG: u8 = 0xA2;
+
+/// The length of the header of an Argon2id envelope: the tag, the memory cost, time cost, and
+/// parallelism parameters, and the salt.
+const ARGON2ID_HEADER_LEN: usize = size_of::<u8>() + 3 * size_of::<u32>() + SALT_LEN;
+
+/// Encrypt the given plaintext using the given passphrase and key derivation function.
+pub fn encrypt(
+    mut rng: impl Rng + CryptoRng,
+    passphrase: &[u8],
+    kdf: Kdf,
+    plaintext: &[u8],
+) -> Vec<u8> {
+    match kdf {
+        Kdf::Balloon { time_cost, memory_cost, parallelism } => {
+            let mut ciphertext = vec![0u8; plaintext.len() + pbenc::OVERHEAD];
+            pbenc::encrypt(
+                rng,
+                passphrase,
+                time_cost,
+                memory_cost,
+                parallelism,
+                plaintext,
+                &mut ciphertext,
+            );
+            ciphertext
+        }
+        Kdf::Argon2id { time_cost, memory_cost, parallelism } => {
+            // Raise the parameters to Argon2's minimums.
+            let p_cost = parallelism.clamp(Params::MIN_P_COST, Params::MAX_P_COST);
+            let t_cost = time_cost.max(Params::MIN_T_COST);
+            let m_cost = memory_cost.max(8 * p_cost);
+
+            // Encode the tag, parameters, and a random salt.
+            let mut ciphertext =
+                Vec::with_capacity(ARGON2ID_HEADER_LEN + plaintext.len() + TAG_LEN);
+            ciphertext.push(ARGON2ID_TAG);
+            ciphertext.extend_from_slice(&m_cost.to_le_bytes());
+            ciphertext.extend_from_slice(&t_cost.to_le_bytes());
+            ciphertext.extend_from_slice(&p_cost.to_le_bytes());
+            ciphertext.extend_from_slice(&rng.gen::<[u8; SALT_LEN]>());
+
+            // Derive a key from the header and encrypt the plaintext.
+            let mut argon2id = argon2id(passphrase, &ciphertext)
+                .expect("parameters should be at least Argon2's minimums");
+            ciphertext.extend_from_slice(plaintext);
+            ciphertext.resize(ciphertext.len() + TAG_LEN, 0);
+            argon2id.seal("secret", &mut ciphertext[ARGON2ID_HEADER_LEN..]);
+            ciphertext
+        }
+    }
+}
+
+/// Returns the length of an envelope of a plaintext of the given length, given the envelope's first
+/// byte.
+pub const fn envelope_len(first: u8, plaintext_len: usize) -> usize {
+    match first {
+        ARGON2ID_TAG => ARGON2ID_HEADER_LEN + plaintext_len + TAG_LEN,
+        pbenc::LANES_TAG => pbenc::OVERHEAD + plaintext_len,
+        _ => pbenc::LEGACY_OVERHEAD + plaintext_len,
+    }
+}
+
+/// Returns a decoy envelope of the same length and with the same parameters as `envelope`, but with
+/// a random salt and ciphertext, which no passphrase decrypts.
+pub fn decoy(mut rng: impl Rng + CryptoRng, envelope: &[u8]) -> Vec<u8> {
+    // Keep the tag, if any, and the parameters.
+    let params_len = match envelope.first() {
+        Some(&ARGON2ID_TAG) => size_of::<u8>() + 3 * size_of::<u32>(),
+        Some(&pbenc::LANES_TAG) => size_of::<u8>() + 3 * size_of::<u8>(),
+        _ => 2 * size_of::<u8>(),
+    };
+    let mut decoy = envelope.to_vec();
+    if let Some(rest) = decoy.get_mut(params_len..) {
+        rng.fill_bytes(rest);
+    }
+    decoy
+}
+
+/// Decrypt the given ciphertext using the given passphrase, selecting the key derivation function
+/// by the envelope's tag.
+#[must_use]
+pub fn decrypt<'a>(passphrase: &[u8], in_out: &'a mut [u8]) -> Option<&'a [u8]> {
+    if in_out.first() != Some(&ARGON2ID_TAG) {
+        return pbenc::decrypt(passphrase, in_out);
+    }
+
+    if in_out.len() < ARGON2ID_HEADER_LEN + TAG_LEN {
+        return None;
+    }
+
+    // Split up the input buffer, derive a key from the header, and decrypt the ciphertext.
+    let (header, ciphertext) = in_out.split_at_mut(ARGON2ID_HEADER_LEN);
+    argon2id(passphrase, header)?.open("secret", ciphertext)
+}
+
+/// Decode the parameters and salt in an Argon2id envelope header and return a protocol keyed with
+/// the given passphrase, or `None` if the parameters are invalid.
+fn argon2id(passphrase: &[u8], header: &[u8]) -> Option<Protocol> {
+    // Split up the header.
+    let (m_cost, rest) = header[size_of::<u8>()..].split_at(size_of::<u32>());
+    let (t_cost, rest) = rest.split_at(size_of::<u32>());
+    let (p_cost, salt) = rest.split_at(size_of::<u32>());
+
+    // Decode the parameters.
+    let params = Params::new(
+        u32::from_le_bytes(m_cost.try_into().expect("should be u32-sized")),
+        u32::from_le_bytes(t_cost.try_into().expect("should be u32-sized")),
+        u32::from_le_bytes(p_cost.try_into().expect("should be u32-sized")),
+        Some(KEY_LEN),
+    )
+    .ok()?;
+
+    // Derive a key from the passphrase and salt.
+    trace_span!(DEBUG, "argon2id", m_cost = params.m_cost(), t_cost = params.t_cost());
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key)
+        .ok()?;
+
+    // Key a protocol with the header and the derived key.
+    let mut argon2id = Protocol::new("veil.pbenc.argon2id");
+    argon2id.mix("header", header);
+    argon2id.mix("key", &key);
+    Some(argon2id)
+}
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    const ARGON2ID: Kdf = Kdf::Argon2id { time_cost: 1, memory_cost: 64, parallelism: 2 };
+
+    #[test]
+    fn argon2id_round_trip() {
+        let (_, passphrase, plaintext, mut ciphertext) = setup(ARGON2ID);
+        assert_eq!(ARGON2ID_TAG, ciphertext[0], "missing tag");
+        assert_eq!(
+            Some(plaintext.as_slice()),
+            decrypt(&passphrase, &mut ciphertext),
+            "invalid plaintext"
+        );
+    }
+
+    #[test]
+    fn balloon_round_trip() {
+        let (_, passphrase, plaintext, mut ciphertext) =
+            setup(Kdf::Balloon { time_cost: 1, memory_cost: 6, parallelism: 1 });
+        assert_eq!(
+            Some(plaintext.as_slice()),
+            decrypt(&passphrase, &mut ciphertext),
+            "invalid plaintext"
+        );
+    }
+
+    #[test]
+    fn minimum_parameters() {
+        let (_, passphrase, plaintext, mut ciphertext) =
+            setup(Kdf::Argon2id { time_cost: 0, memory_cost: 0, parallelism: 0 });
+        assert_eq!(
+            Some(plaintext.as_slice()),
+            decrypt(&passphrase, &mut ciphertext),
+            "invalid plaintext"
+        );
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let (mut rng, _, _, mut ciphertext) = setup(ARGON2ID);
+        let wrong_passphrase = rng.gen::<[u8; 32]>();
+        assert_eq!(
+            None,
+            decrypt(&wrong_passphrase, &mut ciphertext),
+            "decrypted an invalid ciphertext"
+        );
+    }
+
+    #[test]
+    fn modified_parameters() {
+        let (_, passphrase, _, ciphertext) = setup(ARGON2ID);
+        for i in [1, 5, 9] {
+            let mut ciphertext = ciphertext.clone();
+            ciphertext[i] ^= 1;
+            assert_eq!(
+                None,
+                decrypt(&passphrase, &mut ciphertext),
+                "decrypted an invalid ciphertext"
+            );
+        }
+    }
+
+    #[test]
+    fn flip_every_bit() {
+        let (_, passphrase, _, ciphertext) = setup(ARGON2ID);
+
+        // Start with the salt, as flipping high bits of the parameters makes Argon2id very slow.
+        for i in ARGON2ID_HEADER_LEN - SALT_LEN..ciphertext.len() {
+            for j in 0u8..8 {
+                let mut ciphertext = ciphertext.clone();
+                ciphertext[i] ^= 1 << j;
+                assert_eq!(
+                    None,
+                    decrypt(&passphrase, &mut ciphertext),
+                    "bit flip at byte {i}, bit {j} produced a valid message"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let (_, passphrase, _, ciphertext) = setup(ARGON2ID);
+        for len in [1, ARGON2ID_HEADER_LEN, ARGON2ID_HEADER_LEN + TAG_LEN, ciphertext.len() - 1] {
+            let mut ciphertext = ciphertext[..len].to_vec();
+            assert_eq!(
+                None,
+                decrypt(&passphrase, &mut ciphertext),
+                "decrypted a truncated ciphertext"
+            );
+        }
+    }
+
+    fn setup(kdf: Kdf) -> (ChaChaRng, [u8; 32], [u8; 64], Vec<u8>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let passphrase = rng.gen::<[u8; 32]>();
+        let plaintext = rng.gen::<[u8; 64]>();
+        let ciphertext = encrypt(&mut rng, &passphrase, kdf, &plaintext);
+        (rng, passphrase, plaintext, ciphertext)
+    }
+}
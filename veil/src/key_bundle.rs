@@ -0,0 +1,221 @@
+//! Self-signed public key bundles.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::{PubKey, POINT_LEN},
+    schnorr::{self, DET_SIGNATURE_LEN},
+    sres::NONCE_LEN,
+    ParseKeyBundleError, PublicKey, SignerBackend, VerifyError,
+};
+
+/// The length of an encoded key bundle with an empty label, in bytes.
+const MIN_BUNDLE_LEN: usize = POINT_LEN + size_of::<u64>() + NONCE_LEN + DET_SIGNATURE_LEN;
+
+/// A public key with metadata, signed by its private key.
+///
+/// Consists of the public key, a creation timestamp, a nonce, an encrypted commitment point, an
+/// encrypted proof scalar, and a UTF-8 label (e.g. an email address). Unlike a bare public key, a
+/// bundle's metadata cannot be modified in transit without invalidating its signature.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyBundle {
+    public_key: PublicKey,
+    created: u64,
+    nonce: [u8; NONCE_LEN],
+    sig: [u8; DET_SIGNATURE_LEN],
+    label: String,
+}
+
+impl KeyBundle {
+    /// Returns the bundled public key.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// Returns the time the bundle was created, in seconds since the Unix epoch.
+    #[must_use]
+    pub const fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Returns the bundle's label.
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Verifies that the bundle was signed by the private key of its public key. Returns `Ok(())`
+    /// if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the bundle has been modified, returns [`VerifyError::InvalidSignature`].
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let mut bundle = init(&self.public_key.0, self.created, &self.nonce, &self.label);
+        schnorr::det_verify(&mut bundle, &self.public_key.0, self.sig)
+            .ok_or(VerifyError::InvalidSignature)
+    }
+
+    /// Decode a key bundle from a byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<KeyBundle> {
+        let b = b.as_ref();
+        if b.len() < MIN_BUNDLE_LEN {
+            return None;
+        }
+
+        // Split the bundle into components.
+        let (public_key, b) = b.split_at(POINT_LEN);
+        let (created, b) = b.split_at(size_of::<u64>());
+        let (nonce, b) = b.split_at(NONCE_LEN);
+        let (sig, label) = b.split_at(DET_SIGNATURE_LEN);
+
+        Some(KeyBundle {
+            public_key: PublicKey::decode(public_key)?,
+            created: u64::from_le_bytes(created.try_into().expect("should be 8 bytes")),
+            nonce: nonce.try_into().expect("should be nonce-sized"),
+            sig: sig.try_into().expect("should be signature-sized"),
+            label: String::from_utf8(label.to_vec()).ok()?,
+        })
+    }
+
+    /// Encode the key bundle as a byte vector.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(MIN_BUNDLE_LEN + self.label.len());
+        b.extend_from_slice(&self.public_key.encode());
+        b.extend_from_slice(&self.created.to_le_bytes());
+        b.extend_from_slice(&self.nonce);
+        b.extend_from_slice(&self.sig);
+        b.extend_from_slice(self.label.as_bytes());
+        b
+    }
+}
+
+impl FromStr for KeyBundle {
+    type Err = ParseKeyBundleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyBundle::decode(bs58::decode(s).into_vec()?).ok_or(ParseKeyBundleError::InvalidBundle)
+    }
+}
+
+impl fmt::Display for KeyBundle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    }
+}
+
+/// Create a bundle of the given signer's public key with the given label and creation time.
+pub fn export(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    label: &str,
+    created: u64,
+) -> KeyBundle {
+    let public_key = signer.public_key();
+
+    // Generate a random nonce.
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+    // Initialize a protocol with the public key, creation time, nonce, and label and sign its
+    // state.
+    let mut bundle = init(&public_key.0, created, &nonce, label);
+    let sig = signer.sign(&mut bundle);
+
+    KeyBundle { public_key, created, nonce, sig, label: label.into() }
+}
+
+fn init(public_key: &PubKey, created: u64, nonce: &[u8], label: &str) -> Protocol {
+    // Initialize a protocol.
+    let mut bundle = Protocol::new("veil.bundle");
+
+    // Mix the public key into the protocol.
+    bundle.mix("public-key", &public_key.encoded);
+
+    // Mix the nonce into the protocol.
+    bundle.mix("nonce", nonce);
+
+    // Mix the creation time and label into the protocol.
+    bundle.mix("created", &created.to_le_bytes());
+    bundle.mix("label", label.as_bytes());
+
+    bundle
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn round_trip() {
+        let (key, bundle) = setup();
+
+        assert_matches!(bundle.verify(), Ok(()));
+        assert_eq!(key.public_key(), bundle.public_key());
+        assert_eq!(1_700_000_000, bundle.created());
+        assert_eq!("alice@example.com", bundle.label());
+    }
+
+    #[test]
+    fn modified_public_key() {
+        let (_, mut bundle) = setup();
+
+        bundle.public_key = PrivateKey::random(ChaChaRng::seed_from_u64(1)).public_key();
+        assert_matches!(bundle.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn modified_label() {
+        let (_, mut bundle) = setup();
+
+        bundle.label = "mallory@example.com".into();
+        assert_matches!(bundle.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn modified_created() {
+        let (_, mut bundle) = setup();
+
+        bundle.created += 1;
+        assert_matches!(bundle.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn encoding() {
+        let (_, bundle) = setup();
+
+        let decoded = bundle.to_string().parse::<KeyBundle>();
+        assert_eq!(Ok(&bundle), decoded.as_ref(), "error parsing key bundle");
+        assert_matches!(decoded.expect("should parse").verify(), Ok(()));
+
+        assert_eq!(
+            Err(ParseKeyBundleError::InvalidBundle),
+            bs58::encode([0u8; MIN_BUNDLE_LEN - 1]).into_string().parse::<KeyBundle>()
+        );
+        assert_eq!(
+            Err(ParseKeyBundleError::InvalidEncoding(bs58::decode::Error::InvalidCharacter {
+                character: 'l',
+                index: 4,
+            })),
+            "invalid bundle".parse::<KeyBundle>()
+        );
+    }
+
+    fn setup() -> (PrivateKey, KeyBundle) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let bundle = export(&mut rng, &key, "alice@example.com", 1_700_000_000);
+        (key, bundle)
+    }
+}
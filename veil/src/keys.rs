@@ -1,9 +1,11 @@
-use std::fmt::{Debug, Formatter};
+use core::fmt::{Debug, Formatter};
 
-use crrl::gls254::{Point, Scalar};
+use crate::curve::{Curve, Point, Scalar, Selected};
 use lockstitch::Protocol;
 use rand::{CryptoRng, Rng};
 
+use crate::ct;
+
 /// The length of a secret in bytes.
 pub const SECRET_LEN: usize = 64;
 
@@ -28,21 +30,20 @@ impl PubKey {
     /// canonically-encoded point or if it encodes the neutral point.
     #[must_use]
     pub fn from_canonical_bytes(b: impl AsRef<[u8]>) -> Option<PubKey> {
-        let encoded = <[u8; POINT_LEN]>::try_from(b.as_ref()).ok()?;
-        let q = Point::decode(&encoded)?;
-        (q.isneutral() == 0).then_some(PubKey { q, encoded })
+        let (pub_key, ok) = ct::decode_pub_key(b.as_ref());
+        ok.then_some(pub_key)
     }
 
     /// Generates a random public key for which no private key is known.
     #[must_use]
     pub fn random(mut rng: impl CryptoRng + Rng) -> PubKey {
-        let q = Point::hash_to_curve("", &rng.gen::<[u8; 64]>());
+        let q = Selected::hash_to_point(&rng.gen::<[u8; 64]>());
         PubKey { q, encoded: q.encode() }
     }
 }
 
 impl Debug for PubKey {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:02x?}", self.encoded)
     }
 }
@@ -51,7 +52,7 @@ impl Eq for PubKey {}
 
 impl PartialEq for PubKey {
     fn eq(&self, other: &Self) -> bool {
-        self.encoded == other.encoded
+        ct::eq(&self.encoded, &other.encoded)
     }
 }
 
@@ -86,6 +87,14 @@ impl PrivKey {
         PrivKey::from_secret_bytes(rng.gen())
     }
 
+    /// Derives the anonymous sender's private key. Its secret is derived from a fixed domain
+    /// separation string, so anyone can derive it.
+    #[must_use]
+    pub fn anonymous() -> PrivKey {
+        let mut anon = Protocol::new("veil.anonymous");
+        PrivKey::from_secret_bytes(anon.derive_array("secret"))
+    }
+
     /// Uses the key's nonce and a clone of the given protocol to deterministically create a
     /// commitment scalar for the protocol's state.
     #[must_use]
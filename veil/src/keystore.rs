@@ -0,0 +1,521 @@
+//! Passphrase-encrypted storage of private keys: a store of multiple named private keys, and a
+//! guard which makes repeated passphrase guesses increasingly expensive.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+};
+
+use lockstitch::{Protocol, TAG_LEN};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    ct, kdf,
+    keys::{PrivKey, SECRET_LEN},
+    pbenc, DecryptError, Kdf, KeyStoreError, PrivateKey,
+};
+
+/// The maximum length of a key name, in bytes.
+const MAX_NAME_LEN: usize = u8::MAX as usize;
+
+/// The length of an encoded guard, in bytes: the schedule's time cost, memory cost, and
+/// parallelism, the number of failed attempts, and a tag.
+pub const GUARD_LEN: usize = 3 * size_of::<u8>() + size_of::<u32>() + TAG_LEN;
+
+/// A set of named private keys which are encrypted together with a single passphrase.
+///
+/// Key names are non-empty UTF-8 strings of at most 255 bytes. Keys are kept in name order.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct KeyStore {
+    keys: BTreeMap<String, PrivateKey>,
+}
+
+impl KeyStore {
+    /// Creates an empty key store.
+    #[must_use]
+    pub const fn new() -> KeyStore {
+        KeyStore { keys: BTreeMap::new() }
+    }
+
+    /// Returns the number of keys in the key store.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the key store contains no keys.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns an iterator of the names and private keys in the key store, in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &PrivateKey)> {
+        self.keys.iter().map(|(name, key)| (name.as_str(), key))
+    }
+
+    /// Returns the private key with the given name, if any.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&PrivateKey> {
+        self.keys.get(name)
+    }
+
+    /// Adds the given private key to the key store with the given name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyStoreError::InvalidName`] if the name is empty or too long, or
+    /// [`KeyStoreError::DuplicateName`] if a key with the name already exists.
+    pub fn insert(&mut self, name: &str, private_key: PrivateKey) -> Result<(), KeyStoreError> {
+        check_name(name)?;
+        if self.keys.contains_key(name) {
+            return Err(KeyStoreError::DuplicateName(name.into()));
+        }
+        self.keys.insert(name.into(), private_key);
+        Ok(())
+    }
+
+    /// Renames the private key with the name `from` to `to`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyStoreError::UnknownName`] if no key is named `from`,
+    /// [`KeyStoreError::InvalidName`] if `to` is empty or too long, or
+    /// [`KeyStoreError::DuplicateName`] if a key named `to` already exists.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), KeyStoreError> {
+        check_name(to)?;
+        if self.keys.contains_key(to) {
+            return Err(KeyStoreError::DuplicateName(to.into()));
+        }
+        let private_key =
+            self.keys.remove(from).ok_or_else(|| KeyStoreError::UnknownName(from.into()))?;
+        self.keys.insert(to.into(), private_key);
+        Ok(())
+    }
+
+    /// Removes and returns the private key with the given name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyStoreError::UnknownName`] if no key has the given name.
+    pub fn remove(&mut self, name: &str) -> Result<PrivateKey, KeyStoreError> {
+        self.keys.remove(name).ok_or_else(|| KeyStoreError::UnknownName(name.into()))
+    }
+
+    /// Encrypts the key store with the given passphrase and `veil.pbenc` parameters and writes it to
+    /// the given writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error returned by operations on `writer`.
+    pub fn store(
+        &self,
+        writer: impl Write,
+        rng: impl Rng + CryptoRng,
+        passphrase: &[u8],
+        time_cost: u8,
+        memory_cost: u8,
+        parallelism: u8,
+    ) -> io::Result<usize> {
+        self.store_with(
+            writer,
+            rng,
+            passphrase,
+            Kdf::Balloon { time_cost, memory_cost, parallelism },
+        )
+    }
+
+    /// Encrypts the key store with the given passphrase and key derivation function and writes it
+    /// to the given writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error returned by operations on `writer`.
+    pub fn store_with(
+        &self,
+        mut writer: impl Write,
+        rng: impl Rng + CryptoRng,
+        passphrase: &[u8],
+        kdf: Kdf,
+    ) -> io::Result<usize> {
+        // Encode each key as its name length, name, and secret.
+        let mut plaintext = Vec::with_capacity(self.keys.len() * (1 + MAX_NAME_LEN + SECRET_LEN));
+        for (name, private_key) in &self.keys {
+            plaintext.push(u8::try_from(name.len()).expect("names should be <= 255 bytes"));
+            plaintext.extend_from_slice(name.as_bytes());
+            plaintext.extend_from_slice(&private_key.0.secret);
+        }
+
+        let ciphertext = kdf::encrypt(rng, passphrase, kdf, &plaintext);
+        writer.write_all(&ciphertext)?;
+        Ok(ciphertext.len())
+    }
+
+    /// Loads and decrypts a key store from the given reader with the given passphrase.
+    ///
+    /// # Errors
+    ///
+    /// If the passphrase is incorrect and/or the ciphertext has been modified, a
+    /// [`DecryptError::InvalidCiphertext`] error will be returned. If an error occurred while
+    /// reading, a [`DecryptError::ReadIo`] error will be returned.
+    pub fn load(mut reader: impl Read, passphrase: &[u8]) -> Result<KeyStore, DecryptError> {
+        let mut b = Vec::new();
+        reader.read_to_end(&mut b).map_err(DecryptError::ReadIo)?;
+
+        // Decrypt the ciphertext and decode the keys.
+        let mut plaintext =
+            kdf::decrypt(passphrase, &mut b).ok_or(DecryptError::InvalidCiphertext)?;
+        let mut store = KeyStore::new();
+        while let Some((&name_len, rest)) = plaintext.split_first() {
+            let name_len = usize::from(name_len);
+            if rest.len() < name_len + SECRET_LEN {
+                return Err(DecryptError::InvalidCiphertext);
+            }
+            let (name, rest) = rest.split_at(name_len);
+            let (secret, rest) = rest.split_at(SECRET_LEN);
+
+            let name = std::str::from_utf8(name).map_err(|_| DecryptError::InvalidCiphertext)?;
+            let secret = secret.try_into().expect("should be secret-sized");
+            store
+                .insert(name, PrivateKey(PrivKey::from_secret_bytes(secret)))
+                .map_err(|_| DecryptError::InvalidCiphertext)?;
+            plaintext = rest;
+        }
+        Ok(store)
+    }
+}
+
+/// A record of failed attempts to decrypt an encrypted private key, which makes each attempt after
+/// a failure twice as expensive as the one before it.
+///
+/// A guard stores a work factor escalation schedule: after `n` failed attempts, each attempt to
+/// decrypt the key first performs `veil.pbenc` balloon hashing of the passphrase with a time cost
+/// of `t + n - 1`, a memory cost of `m`, and a parallelism of `p`. Because the time cost is in
+/// 2^t iterations, the extra work doubles after every failure, and a successful attempt resets it.
+///
+/// The encoded guard is bound to the encrypted key it guards with a tag, so it can't be edited by
+/// hand or moved to another key. It is not a secret, though, and anyone who can write it can
+/// replace it with an older copy or delete it, so a guard throttles guessing through Veil itself
+/// (e.g. on an unlocked laptop) but not an attacker who copies the encrypted key and guesses
+/// offline. Against that, only a strong passphrase and high KDF costs help.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Guard {
+    time_cost: u8,
+    memory_cost: u8,
+    parallelism: u8,
+    failures: u32,
+}
+
+impl Guard {
+    /// Creates a guard with the given base `veil.pbenc` parameters and no failed attempts. A
+    /// parallelism of `0` is treated as `1`, and one above 64 or 2^m as the lower of the two.
+    #[must_use]
+    pub fn new(time_cost: u8, memory_cost: u8, parallelism: u8) -> Guard {
+        let parallelism = pbenc::lanes(memory_cost, parallelism);
+        Guard { time_cost, memory_cost, parallelism, failures: 0 }
+    }
+
+    /// Returns the number of failed attempts since the last successful one.
+    #[must_use]
+    pub const fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns the `veil.pbenc` time cost of the extra work performed before the next attempt, if
+    /// any.
+    #[must_use]
+    pub fn penalty(&self) -> Option<u8> {
+        let n = u8::try_from(self.failures.checked_sub(1)?).unwrap_or(u8::MAX);
+        Some(self.time_cost.saturating_add(n))
+    }
+
+    /// Decrypts the given encrypted private key with the given passphrase, after performing the
+    /// extra work required by the guard's failed attempts.
+    ///
+    /// The attempt is recorded as a failure and passed to `save` before the passphrase is checked,
+    /// so interrupting an attempt doesn't avoid recording it. If the attempt succeeds, the failures
+    /// are reset and the guard is passed to `save` again.
+    ///
+    /// # Errors
+    ///
+    /// If the passphrase is incorrect and/or the encrypted key has been modified, a
+    /// [`DecryptError::InvalidCiphertext`] error will be returned. If `save` returns an error, a
+    /// [`DecryptError::WriteIo`] error will be returned.
+    pub fn unlock(
+        &mut self,
+        encrypted_key: &[u8],
+        passphrase: &[u8],
+        mut save: impl FnMut(&Guard) -> io::Result<()>,
+    ) -> Result<PrivateKey, DecryptError> {
+        // Record the attempt as a failure before making it.
+        let penalty = self.penalty();
+        self.failures = self.failures.saturating_add(1);
+        save(self).map_err(DecryptError::WriteIo)?;
+
+        // Perform the extra work, if any, with a salt derived from the encrypted key.
+        if let Some(time_cost) = penalty {
+            let mut guard = Protocol::new("veil.guard.penalty");
+            guard.mix("key", encrypted_key);
+            let mut header = [0u8; pbenc::HEADER_LEN];
+            header[0] = time_cost;
+            header[1] = self.memory_cost;
+            header[2] = self.parallelism;
+            guard.derive("salt", &mut header[3..]);
+            let _ = pbenc::decrypt_header(passphrase, &header);
+        }
+
+        let private_key = PrivateKey::load(encrypted_key, passphrase)?;
+        self.failures = 0;
+        save(self).map_err(DecryptError::WriteIo)?;
+        Ok(private_key)
+    }
+
+    /// Loads a guard for the given encrypted private key from the given reader.
+    ///
+    /// # Errors
+    ///
+    /// If the guard is not the right length, was stored for a different encrypted key, or has
+    /// been modified, a [`DecryptError::InvalidCiphertext`] error will be returned. If an error
+    /// occurred while reading, a [`DecryptError::ReadIo`] error will be returned.
+    pub fn load(mut reader: impl Read, encrypted_key: &[u8]) -> Result<Guard, DecryptError> {
+        let mut b = Vec::with_capacity(GUARD_LEN);
+        reader.read_to_end(&mut b).map_err(DecryptError::ReadIo)?;
+        let b: [u8; GUARD_LEN] = b.try_into().map_err(|_| DecryptError::InvalidCiphertext)?;
+
+        let guard = Guard {
+            time_cost: b[0],
+            memory_cost: b[1],
+            parallelism: b[2],
+            failures: u32::from_le_bytes(b[3..7].try_into().expect("should be 4 bytes")),
+        };
+        ct::eq(&guard.encode(encrypted_key), &b)
+            .then_some(guard)
+            .ok_or(DecryptError::InvalidCiphertext)
+    }
+
+    /// Writes the guard for the given encrypted private key to the given writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error returned by operations on `writer`.
+    pub fn store(&self, mut writer: impl Write, encrypted_key: &[u8]) -> io::Result<usize> {
+        writer.write_all(&self.encode(encrypted_key))?;
+        Ok(GUARD_LEN)
+    }
+
+    fn encode(&self, encrypted_key: &[u8]) -> [u8; GUARD_LEN] {
+        let mut b = [0u8; GUARD_LEN];
+        let (schedule, rest) = b.split_at_mut(3);
+        let (failures, tag) = rest.split_at_mut(size_of::<u32>());
+        schedule.copy_from_slice(&[self.time_cost, self.memory_cost, self.parallelism]);
+        failures.copy_from_slice(&self.failures.to_le_bytes());
+
+        // Bind the schedule and failures to the encrypted key.
+        let mut guard = Protocol::new("veil.guard");
+        guard.mix("key", encrypted_key);
+        guard.mix("schedule", schedule);
+        guard.mix("failures", failures);
+        guard.derive("tag", tag);
+        b
+    }
+}
+
+fn check_name(name: &str) -> Result<(), KeyStoreError> {
+    (!name.is_empty() && name.len() <= MAX_NAME_LEN).then_some(()).ok_or(KeyStoreError::InvalidName)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (mut rng, store) = setup();
+
+        let mut ciphertext = Vec::new();
+        store.store(&mut ciphertext, &mut rng, b"passphrase", 0, 0, 1).expect("should store");
+
+        let loaded = KeyStore::load(ciphertext.as_slice(), b"passphrase").expect("should load");
+        assert_eq!(store, loaded, "invalid loaded key store");
+        assert_eq!(vec!["alice", "bea"], loaded.iter().map(|(name, _)| name).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn argon2id_round_trip() {
+        let (mut rng, store) = setup();
+
+        let mut ciphertext = Vec::new();
+        let kdf = Kdf::Argon2id { time_cost: 1, memory_cost: 64, parallelism: 1 };
+        store.store_with(&mut ciphertext, &mut rng, b"passphrase", kdf).expect("should store");
+
+        let loaded = KeyStore::load(ciphertext.as_slice(), b"passphrase").expect("should load");
+        assert_eq!(store, loaded, "invalid loaded key store");
+    }
+
+    #[test]
+    fn empty_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let mut ciphertext = Vec::new();
+        KeyStore::new()
+            .store(&mut ciphertext, &mut rng, b"passphrase", 0, 0, 1)
+            .expect("should store");
+
+        let loaded = KeyStore::load(ciphertext.as_slice(), b"passphrase").expect("should load");
+        assert!(loaded.is_empty(), "key store should be empty");
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let (mut rng, store) = setup();
+
+        let mut ciphertext = Vec::new();
+        store.store(&mut ciphertext, &mut rng, b"passphrase", 0, 0, 1).expect("should store");
+
+        assert_matches!(
+            KeyStore::load(ciphertext.as_slice(), b"wrong"),
+            Err(DecryptError::InvalidCiphertext)
+        );
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let (mut rng, mut store) = setup();
+        assert_matches!(
+            store.insert("alice", PrivateKey::random(&mut rng)),
+            Err(KeyStoreError::DuplicateName(name)) if name == "alice"
+        );
+        assert_matches!(store.rename("bea", "alice"), Err(KeyStoreError::DuplicateName(_)));
+    }
+
+    #[test]
+    fn invalid_names() {
+        let (mut rng, mut store) = setup();
+        assert_matches!(
+            store.insert("", PrivateKey::random(&mut rng)),
+            Err(KeyStoreError::InvalidName)
+        );
+        assert_matches!(
+            store.insert(&"a".repeat(256), PrivateKey::random(&mut rng)),
+            Err(KeyStoreError::InvalidName)
+        );
+    }
+
+    #[test]
+    fn rename_and_remove() {
+        let (_, mut store) = setup();
+        let public_key = store.get("alice").expect("should have alice").public_key();
+
+        store.rename("alice", "carol").expect("should rename");
+        assert!(store.get("alice").is_none(), "alice should be renamed");
+        assert_eq!(Some(public_key), store.get("carol").map(PrivateKey::public_key));
+
+        assert_eq!(public_key, store.remove("carol").expect("should remove").public_key());
+        assert_matches!(store.remove("carol"), Err(KeyStoreError::UnknownName(_)));
+        assert_eq!(1, store.len());
+    }
+
+    #[test]
+    fn guard_escalation() {
+        let (encrypted_key, private_key) = guarded_key();
+        let mut guard = Guard::new(2, 0, 1);
+        let mut saved = Vec::new();
+
+        // Each failed attempt is saved before the passphrase is checked.
+        for failures in 1..=3 {
+            assert_matches!(
+                guard.unlock(&encrypted_key, b"wrong", |g| {
+                    saved.push(g.failures());
+                    Ok(())
+                }),
+                Err(DecryptError::InvalidCiphertext)
+            );
+            assert_eq!(failures, guard.failures());
+            assert_eq!(Some(2 + failures as u8 - 1), guard.penalty());
+        }
+        assert_eq!(vec![1, 2, 3], saved);
+
+        // A successful attempt resets the failures.
+        saved.clear();
+        let unlocked = guard
+            .unlock(&encrypted_key, b"passphrase", |g| {
+                saved.push(g.failures());
+                Ok(())
+            })
+            .expect("should unlock");
+        assert_eq!(private_key, unlocked);
+        assert_eq!(vec![4, 0], saved);
+        assert_eq!(None, guard.penalty());
+    }
+
+    #[test]
+    fn guard_save_errors() {
+        let (encrypted_key, _) = guarded_key();
+        let mut guard = Guard::new(0, 0, 1);
+
+        // An attempt which can't be recorded isn't made.
+        assert_matches!(
+            guard.unlock(&encrypted_key, b"passphrase", |_| Err(io::ErrorKind::Other.into())),
+            Err(DecryptError::WriteIo(_))
+        );
+    }
+
+    #[test]
+    fn guard_round_trip() {
+        let (encrypted_key, _) = guarded_key();
+        let mut guard = Guard::new(4, 2, 1);
+        let _ = guard.unlock(&encrypted_key, b"wrong", |_| Ok(()));
+
+        let mut b = Vec::new();
+        guard.store(&mut b, &encrypted_key).expect("should store");
+        assert_eq!(GUARD_LEN, b.len());
+        assert_eq!(guard, Guard::load(b.as_slice(), &encrypted_key).expect("should load"));
+    }
+
+    #[test]
+    fn modified_guard() {
+        let (encrypted_key, _) = guarded_key();
+        let mut b = Vec::new();
+        Guard::new(4, 2, 1).store(&mut b, &encrypted_key).expect("should store");
+
+        // The failures can't be edited.
+        let mut modified = b.clone();
+        modified[3] ^= 1;
+        assert_matches!(
+            Guard::load(modified.as_slice(), &encrypted_key),
+            Err(DecryptError::InvalidCiphertext)
+        );
+
+        // The guard can't be moved to another key.
+        let mut rng = ChaChaRng::seed_from_u64(0xCAFEBABE);
+        let mut other_key = Vec::new();
+        PrivateKey::random(&mut rng)
+            .store(&mut other_key, &mut rng, b"passphrase", 0, 0, 1)
+            .expect("should store");
+        assert_matches!(
+            Guard::load(b.as_slice(), &other_key),
+            Err(DecryptError::InvalidCiphertext)
+        );
+    }
+
+    fn guarded_key() -> (Vec<u8>, PrivateKey) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let private_key = PrivateKey::random(&mut rng);
+        let mut encrypted_key = Vec::new();
+        private_key
+            .store(&mut encrypted_key, &mut rng, b"passphrase", 0, 0, 1)
+            .expect("should store");
+        (encrypted_key, private_key)
+    }
+
+    fn setup() -> (ChaChaRng, KeyStore) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let mut store = KeyStore::new();
+        store.insert("bea", PrivateKey::random(&mut rng)).expect("should insert");
+        store.insert("alice", PrivateKey::random(&mut rng)).expect("should insert");
+        (rng, store)
+    }
+}
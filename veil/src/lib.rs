@@ -15,7 +15,7 @@
 //! use std::io;
 //! use std::io::Cursor;
 //! use rand::rngs::OsRng;
-//! use veil::PrivateKey;
+//! use veil::{EncryptOptions, PrivateKey};
 //! # use std::error::Error;
 //! #
 //! # fn main() -> Result<(), Box<dyn Error>> {
@@ -35,9 +35,7 @@
 //!   OsRng,
 //!   Cursor::new("this is a secret message"),
 //!   &mut ciphertext,
-//!   &[bea_pub],
-//!   Some(20),
-//!   Some(1234),
+//!   &EncryptOptions::new(&[bea_pub]).fakes(20).padding(1234),
 //! )?;
 //!
 //! // Bea decrypts the message.
@@ -54,17 +52,222 @@
 //! #   Ok(())
 //! # }
 //! ```
+//!
+//! ## Features
+//!
+//! The `std` feature, enabled by default, provides everything which reads or writes streams:
+//! encryption, decryption, inspection, signing, digests, key storage, passphrase encryption, armor,
+//! and envelopes. Without it, the crate builds with `#![no_std]` and `alloc` and provides key
+//! generation, key agreement, fingerprints, paper keys, revocations, certifications, PADMÉ padding
+//! lengths, and the [`KemBackend`] and [`SignerBackend`] traits.
+//!
+//! The `async` feature adds `PrivateKey::encrypt_async` and `PrivateKey::decrypt_async`, which
+//! read and write with the runtime-agnostic `AsyncRead` and `AsyncWrite` traits from `futures-io`.
+//! Tokio's IO types can be adapted to them with `tokio-util`'s `compat` module.
+//!
+//! The `serde` feature implements `Serialize` and `Deserialize` for [`PublicKey`], [`Signature`],
+//! and `Digest`. Human-readable formats use base58 strings; binary formats use the byte strings
+//! returned by their `encode` methods.
+//!
+//! The `cbor` feature adds [`artifact`], which wraps a signature or digest with a context, its
+//! signer's fingerprint, and its creation time in a signed, canonically-encoded CBOR envelope, so
+//! it doesn't lose its association with the key which made it.
+//!
+//! The `zstd` feature adds `EncryptOptions::compress`, which compresses plaintexts with zstd
+//! before encrypting them, and the ability to decrypt compressed messages, which use format
+//! version `ZSTD_FORMAT_VERSION`.
+//!
+//! The `js` feature enables `getrandom`'s `js` feature, so that `rand::rngs::OsRng` works on
+//! `wasm32-unknown-unknown` in a browser or Node.js. With `std` enabled, the crate runs on
+//! WebAssembly without spawning threads, and `PrivateKey::seal_with` and
+//! `PrivateKey::open_with_associated_data` encrypt and decrypt byte slices without streaming IO.
+//!
+//! The `dudect` feature adds statistical timing tests, in the style of dudect, of [`ct::eq`] and of
+//! header scanning, which check that neither leaks secrets through timing. Run them with
+//! `cargo test -p veil --release --features dudect ct::dudect`.
+//!
+//! The `cross` feature adds tests which pin the output of `testvectors::generate` and of a
+//! deterministic encryption, so that ciphertexts can be checked to be the same on 32-bit and
+//! big-endian targets as on x86-64. Run them on another target with `cross`, e.g.
+//! `cross test -p veil --features cross --target armv7-unknown-linux-gnueabihf cross`.
+//!
+//! The `jq255e` feature builds every protocol on the jq255e curve instead of GLS254, for
+//! evaluating the two; see [`curve`]. Keys, messages, and signatures made with one curve can't be
+//! used with the other, so this isn't for production use.
+//!
+//! The `trace` feature records `tracing` spans of encryption, decryption, header scanning, block
+//! encryption and decryption, and passphrase-based key derivation, with non-secret metadata only,
+//! so a `tracing` subscriber can show where the time goes.
+//!
+//! The `fuzzing` feature exposes the `veil.sres` and `veil.pbenc` decryption functions in a hidden
+//! `fuzzing` module for the `cargo-fuzz` targets in `fuzz/`. It isn't part of the stable API.
+//!
+//! `EncryptOptions::seekable` writes messages in format version `SEEKABLE_FORMAT_VERSION`, ranges
+//! of which can be decrypted without reading the entire message with `seekable::open`.
+//! `bundle::BundleWriter` packs many messages into one container, from which `bundle::Bundle`
+//! extracts them selectively.
+//! `policy::VerificationPolicy` accepts messages signed by any, all, or a threshold of a set of
+//! signers.
+//!
+//! With `std` enabled, an `audit::AuditSink` installed with `audit::set_sink` is notified of every
+//! encryption, decryption, and signature made with a [`PrivateKey`], with non-secret metadata only.
+//!
+//! ## Building On Veil's Primitive
+//!
+//! Every protocol in Veil is built on the [`lockstitch`] crate's `Protocol`, which is re-exported
+//! so that downstream protocols can use the same version. Veil's protocols are domain-separated by
+//! the names they are initialized with, all of which begin with `veil.`, so a companion protocol
+//! initialized with a name from its own namespace can never produce outputs which collide with
+//! Veil's:
+//!
+//! ```rust
+//! use veil::lockstitch::Protocol;
+//!
+//! let mut protocol = Protocol::new("com.example.companion");
+//! protocol.mix("message", b"a message");
+//! let tag = protocol.derive_array::<16>("tag");
+//! # assert_ne!([0u8; 16], tag);
+//! ```
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 
-pub use self::{digest::*, errors::*, schnorr::Signature, veil::*};
+extern crate alloc;
+
+/// Enters a `tracing` span at the given level for the rest of the enclosing block, if the `trace`
+/// feature is enabled.
+macro_rules! trace_span {
+    ($level:ident, $($args:tt)+) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::span!(tracing::Level::$level, $($args)+).entered();
+    };
+}
+
+/// Records a `tracing` event at the given level, if the `trace` feature is enabled.
+#[cfg_attr(not(feature = "std"), allow(unused_macros))]
+macro_rules! trace_event {
+    ($level:ident, $($args:tt)+) => {
+        #[cfg(feature = "trace")]
+        tracing::event!(tracing::Level::$level, $($args)+);
+    };
+}
+
+#[cfg(feature = "zstd")]
+pub use self::mres::ZSTD_FORMAT_VERSION;
+pub use self::{
+    agree::SharedSecret,
+    backend::{KemBackend, SignerBackend},
+    certification::Certification,
+    derivation::{ProofOfDerivation, PATH_COMMITMENT_LEN, PROOF_OF_DERIVATION_LEN},
+    errors::*,
+    fingerprint::{Fingerprint, FINGERPRINT_LEN},
+    key_bundle::KeyBundle,
+    revocation::Revocation,
+    schnorr::{Signature, TimestampedSignature},
+    subkey::{DecryptionKey, SigningKey, Subkeys, SUBKEYS_LEN},
+    veil::*,
+};
+#[cfg(feature = "std")]
+pub use self::{
+    digest::*,
+    dvsig::DesignatedSignature,
+    kdf::Kdf,
+    keystore::KeyStore,
+    mres::{
+        Report, FORMAT_VERSION, MAX_MESSAGE_LEN, MAX_PADDING, MAX_RECEIVERS,
+        SEEKABLE_FORMAT_VERSION, SUPPORTED_VERSIONS,
+    },
+    options::{
+        DecryptOptions, DigestLength, DigestOptions, EncryptOptions, HeaderSlot, Padding,
+        SignOptions,
+    },
+    recipient_set::RecipientSet,
+    schnorr::VerifyingReader,
+    wrap::WRAPPED_KEY_OVERHEAD,
+};
+pub use lockstitch;
+
+#[cfg(all(feature = "std", unix))]
+pub mod agent;
+#[cfg(feature = "std")]
+pub mod armor;
+#[cfg(feature = "cbor")]
+pub mod artifact;
+#[cfg(feature = "std")]
+pub mod audit;
+pub mod backend;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bundle;
+pub mod ct;
+pub mod curve;
+pub mod ed25519;
+#[cfg(feature = "std")]
+pub mod envelope;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod keystore;
+#[cfg(feature = "std")]
+pub mod multisig;
+pub mod padme;
+#[cfg(feature = "std")]
+pub mod passphrase;
+#[cfg(feature = "std")]
+pub mod passphrase_policy;
+#[cfg(feature = "std")]
+pub mod policy;
+#[cfg(feature = "std")]
+pub mod recipients;
+#[cfg(feature = "std")]
+pub mod scrub;
+#[cfg(feature = "std")]
+pub mod seekable;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "testvectors")]
+pub mod testvectors;
+#[cfg(feature = "std")]
+pub mod threshold;
+pub mod uri;
 
+mod agree;
+#[cfg(feature = "async")]
+mod asyncio;
+#[cfg(feature = "std")]
 mod blockio;
+mod certification;
+mod derivation;
+#[cfg(feature = "std")]
 mod digest;
+#[cfg(feature = "std")]
+mod dvsig;
 mod errors;
+mod fingerprint;
+#[cfg(feature = "std")]
+mod kdf;
+mod key_bundle;
 mod keys;
+#[cfg(feature = "std")]
 mod mres;
+#[cfg(feature = "std")]
+mod options;
+mod paper;
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 mod pbenc;
+#[cfg(feature = "std")]
+mod recipient_set;
+mod revocation;
 mod schnorr;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 mod sres;
+mod subkey;
 mod veil;
+#[cfg(feature = "std")]
+mod wrap;
@@ -1,113 +1,827 @@
 //! A multi-receiver, hybrid cryptosystem.
 
 use std::{
+    borrow::Cow,
+    fmt::{self, Debug, Formatter},
     io::{self, Read, Write},
-    mem,
+    num::NonZeroUsize,
+    slice, thread,
 };
 
+#[cfg(feature = "async")]
+use futures_io::{AsyncRead, AsyncWrite};
 use lockstitch::{Protocol, TAG_LEN};
 use rand::{CryptoRng, Rng};
 
+#[cfg(feature = "async")]
+use crate::asyncio;
 use crate::{
     blockio::ReadBlock,
-    keys::{PrivKey, PubKey},
+    ct,
+    inspect::{Inspection, Status},
+    keys::{PrivKey, PubKey, POINT_LEN},
+    pbenc,
     schnorr::{self, DET_SIGNATURE_LEN},
     sres,
     sres::NONCE_LEN,
-    DecryptError, EncryptError,
+    DecryptError, EncryptError, KemBackend, PublicKey, SignerBackend,
 };
 
+/// The version of the message format written when encrypting.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// The version of the message format written when encrypting a compressed message. It is the same
+/// as [`FORMAT_VERSION`], except that the plaintext is a zstd frame.
+#[cfg(feature = "zstd")]
+pub const ZSTD_FORMAT_VERSION: u16 = 2;
+
+/// The version of the message format written when encrypting a seekable message, whose payload is
+/// split into independently sealed chunks followed by an index.
+pub const SEEKABLE_FORMAT_VERSION: u16 = 3;
+
+/// The versions of the message format which can be decrypted.
+#[cfg(not(feature = "zstd"))]
+pub const SUPPORTED_VERSIONS: &[u16] = &[FORMAT_VERSION, SEEKABLE_FORMAT_VERSION];
+
+/// The versions of the message format which can be decrypted.
+#[cfg(feature = "zstd")]
+pub const SUPPORTED_VERSIONS: &[u16] =
+    &[FORMAT_VERSION, ZSTD_FORMAT_VERSION, SEEKABLE_FORMAT_VERSION];
+
+/// The maximum number of headers a message can have, one for each of its receivers, decoys, and
+/// fake receivers. Encrypting a message with more returns [`EncryptError::TooManyReceivers`].
+pub const MAX_RECEIVERS: usize = 1 << 20;
+
+/// The maximum number of bytes of padding a message can have. Encrypting a message with more
+/// returns [`EncryptError::TooMuchPadding`].
+pub const MAX_PADDING: u64 = 1 << 56;
+
+/// The maximum number of bytes of plaintext a message can have. Encrypting a longer plaintext
+/// returns [`EncryptError::MessageTooLong`].
+///
+/// Together with [`MAX_RECEIVERS`] and [`MAX_PADDING`], this keeps the length of every message,
+/// and so every offset into one, well within a `u64`.
+pub const MAX_MESSAGE_LEN: u64 = 1 << 60;
+
+/// An account of the bytes of an encrypted message, returned by encrypting or decrypting it.
+///
+/// A message is a nonce and its headers, then random padding, then a payload of encrypted blocks
+/// (and, if the message is seekable, its encrypted index), then a signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Report {
+    header_bytes: u64,
+    padding_bytes: u64,
+    payload_bytes: u64,
+    signature_bytes: u64,
+    recipients_attempted: u64,
+    written: u64,
+}
+
+impl Report {
+    /// Returns a report of a message with the given number of headers, bytes of padding, and bytes
+    /// of payload, encrypting or decrypting which wrote the given number of bytes.
+    fn new(header_count: u64, padding: u64, payload: u64, written: u64) -> Report {
+        let header_len = u64::try_from(ENC_HEADER_LEN).expect("usize should be <= u64");
+        Report {
+            header_bytes: u64::try_from(NONCE_LEN).expect("usize should be <= u64")
+                + header_count * header_len,
+            padding_bytes: padding,
+            payload_bytes: payload,
+            signature_bytes: u64::try_from(DET_SIGNATURE_LEN).expect("usize should be <= u64"),
+            recipients_attempted: header_count,
+            written,
+        }
+    }
+
+    /// Returns the number of bytes of the message's nonce and headers, including any index or
+    /// passphrase header.
+    #[must_use]
+    pub const fn header_bytes(&self) -> u64 {
+        self.header_bytes
+    }
+
+    /// Returns the number of bytes of random padding in the message.
+    #[must_use]
+    pub const fn padding_bytes(&self) -> u64 {
+        self.padding_bytes
+    }
+
+    /// Returns the number of bytes of the message's encrypted blocks and authentication tags,
+    /// including a seekable message's encrypted index.
+    #[must_use]
+    pub const fn payload_bytes(&self) -> u64 {
+        self.payload_bytes
+    }
+
+    /// Returns the number of bytes of the message's signature.
+    #[must_use]
+    pub const fn signature_bytes(&self) -> u64 {
+        self.signature_bytes
+    }
+
+    /// Returns the number of headers in the message: when encrypting, those for receivers, decoys,
+    /// and fake receivers, and any index or passphrase header; when decrypting, those which were
+    /// searched for one which could be decrypted.
+    #[must_use]
+    pub const fn recipients_attempted(&self) -> u64 {
+        self.recipients_attempted
+    }
+
+    /// Returns the total length of the message in bytes.
+    #[must_use]
+    pub const fn ciphertext_bytes(&self) -> u64 {
+        self.header_bytes + self.padding_bytes + self.payload_bytes + self.signature_bytes
+    }
+
+    /// Returns the number of bytes written: of ciphertext when encrypting, or of plaintext when
+    /// decrypting.
+    #[must_use]
+    pub const fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl From<Report> for u64 {
+    /// Returns [`Report::written`], which is what encryption and decryption returned before they
+    /// returned reports.
+    fn from(report: Report) -> u64 {
+        report.written
+    }
+}
+
 /// The length of plaintext blocks which are encrypted.
-const BLOCK_LEN: usize = 64 * 1024;
+pub(crate) const BLOCK_LEN: usize = 64 * 1024;
 
 /// The length of an encrypted block and authentication tag.
-const ENC_BLOCK_LEN: usize = BLOCK_LEN + TAG_LEN;
+pub(crate) const ENC_BLOCK_LEN: usize = BLOCK_LEN + TAG_LEN;
+
+/// The length of a seekable message's encrypted index, which contains the plaintext length.
+pub(crate) const ENC_INDEX_LEN: usize = size_of::<u64>() + TAG_LEN;
 
 /// The length of the data encryption key.
-const DEK_LEN: usize = 32;
+pub(crate) const DEK_LEN: usize = 32;
 
 /// The length of an encoded header.
-const HEADER_LEN: usize = DEK_LEN + mem::size_of::<u64>() + mem::size_of::<u64>();
+const HEADER_LEN: usize = size_of::<u16>() + DEK_LEN + size_of::<u64>() + size_of::<u64>();
 
 /// The length of an encrypted header.
-const ENC_HEADER_LEN: usize = HEADER_LEN + sres::OVERHEAD;
+pub(crate) const ENC_HEADER_LEN: usize = HEADER_LEN + sres::OVERHEAD;
+
+/// The minimum number of headers each thread encrypts when encrypting headers in parallel.
+const MIN_HEADERS_PER_THREAD: usize = 32;
+
+/// The length of a header's hint in a message's index.
+const HINT_LEN: usize = 8;
 
-/// Encrypt the contents of `reader` such that they can be decrypted and verified by all members of
-/// `receivers` and write the ciphertext to `writer` with `padding` bytes of random data added.
+/// The length of the tag which begins a message's index.
+const INDEX_TAG_LEN: usize = 16;
+
+/// The number of hints in each header-sized slot of a message's index.
+const HINTS_PER_SLOT: usize = ENC_HEADER_LEN / HINT_LEN;
+
+/// The length of the tag which begins a message's passphrase header.
+const PASSPHRASE_TAG_LEN: usize = 16;
+
+/// The length of the sealed ephemeral public key and header in a message's passphrase header.
+const SEALED_HEADER_LEN: usize = POINT_LEN + HEADER_LEN + TAG_LEN;
+
+/// Encrypt the contents of `reader` as [`encrypt_version`] does, using [`FORMAT_VERSION`].
+#[cfg(any(test, feature = "testvectors"))]
 pub fn encrypt(
+    rng: impl Rng + CryptoRng,
+    reader: impl Read,
+    writer: impl Write,
+    sender: &(impl KemBackend + SignerBackend + Sync),
+    receivers: &[HeaderKey],
+    padding: usize,
+    associated_data: &[u8],
+) -> Result<Report, EncryptError> {
+    encrypt_version(
+        rng,
+        reader,
+        writer,
+        sender,
+        receivers,
+        padding,
+        associated_data,
+        FORMAT_VERSION,
+        Layout::Plain,
+    )
+}
+
+/// Encrypt the contents of `reader` such that they can be decrypted and verified by all receivers in
+/// `receivers` and write the ciphertext to `writer` with `padding` bytes of random data added,
+/// using the given format version. The ciphertext is bound to `associated_data`, which is not
+/// written. The headers are laid out as given by `layout`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn encrypt_version(
     mut rng: impl Rng + CryptoRng,
     reader: impl Read,
     mut writer: impl Write,
-    sender: &PrivKey,
-    receivers: &[PubKey],
+    sender: &(impl KemBackend + SignerBackend + Sync),
+    receivers: &[HeaderKey],
     padding: usize,
-) -> Result<u64, EncryptError> {
-    let padding = u64::try_from(padding).expect("usize should be <= u64");
+    associated_data: &[u8],
+    version: u16,
+    layout: Layout<'_>,
+) -> Result<Report, EncryptError> {
+    trace_span!(INFO, "encrypt", version, receivers = receivers.len(), padding);
 
-    // Initialize a protocol and mix the sender's public key into it.
-    let mut mres = Protocol::new("veil.mres");
-    mres.mix("sender", &sender.pub_key.encoded);
+    check_limits(receivers.len(), padding)?;
 
-    // Generate a random ephemeral key pair, DEK, and nonce.
-    let ephemeral = PrivKey::random(&mut rng);
-    let dek = rng.gen::<[u8; DEK_LEN]>();
-    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+    // Generate the nonce and encrypted headers, and write them and the padding.
+    let preamble =
+        Preamble::new(&mut rng, sender, receivers, padding, associated_data, version, layout);
+    let (header_count, padding) = (preamble.header_count(), preamble.padding);
+    let (mut mres, mut framing, ephemeral, written) =
+        preamble.write(&mut rng, &mut writer, version).map_err(EncryptError::WriteIo)?;
 
-    // Write the nonce and mix it into the protocol.
-    writer.write_all(&nonce).map_err(EncryptError::WriteIo)?;
-    let mut written = u64::try_from(NONCE_LEN).expect("usize should be <= u64");
-    mres.mix("nonce", &nonce);
+    // Encrypt the plaintext in blocks and write them.
+    let payload = encrypt_message(&mut mres, &mut framing, reader, &mut writer)?;
 
-    // Encode a header with the DEK, receiver count, and padding.
-    let header = Header::new(dek, receivers.len(), padding).encode();
+    // Deterministically sign the protocol's final state with the ephemeral private key and append
+    // the signature. The protocol's state is randomized with both the nonce and the ephemeral key,
+    // so the risk of e.g. fault attacks is minimal.
+    let sig = schnorr::det_sign(&mut mres, &ephemeral);
+    writer.write_all(&sig).map_err(EncryptError::WriteIo)?;
+
+    let written =
+        written + payload + u64::try_from(DET_SIGNATURE_LEN).expect("usize should be <= u64");
+    Ok(Report::new(header_count, padding, payload, written))
+}
+
+/// Returns the length of a message with the given format version, number of headers, bytes of
+/// padding, and bytes of plaintext.
+pub(crate) const fn ciphertext_len(
+    version: u16,
+    header_count: usize,
+    padding: u64,
+    plaintext_len: u64,
+) -> u64 {
+    let index_len = if version == SEEKABLE_FORMAT_VERSION { ENC_INDEX_LEN } else { 0 };
+    (NONCE_LEN + header_count * ENC_HEADER_LEN + index_len + DET_SIGNATURE_LEN) as u64
+        + padding
+        + payload_len(plaintext_len)
+}
+
+/// Returns the length of the encrypted blocks of a message with the given number of bytes of
+/// plaintext. Every message ends with an undersized block, which may be empty.
+pub(crate) const fn payload_len(plaintext_len: u64) -> u64 {
+    let blocks = plaintext_len / BLOCK_LEN as u64 + 1;
+    plaintext_len + blocks * TAG_LEN as u64
+}
+
+/// Returns an error if a message with the given number of headers and bytes of padding would
+/// exceed [`MAX_RECEIVERS`] or [`MAX_PADDING`].
+pub(crate) fn check_limits(header_count: usize, padding: usize) -> Result<(), EncryptError> {
+    if header_count > MAX_RECEIVERS {
+        return Err(EncryptError::TooManyReceivers);
+    }
+    if u64::try_from(padding).unwrap_or(u64::MAX) > MAX_PADDING {
+        return Err(EncryptError::TooMuchPadding);
+    }
+    Ok(())
+}
+
+/// The public key a header is encrypted for.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum HeaderKey {
+    /// A receiver's public key. The header is encrypted by the sender, so the receiver can decrypt
+    /// it and the message.
+    Receiver(PubKey),
+
+    /// A decoy's public key. A random header is encrypted by a throwaway sender key, so the decoy
+    /// can't decrypt it, but it is otherwise indistinguishable from a receiver's header.
+    Decoy(PubKey),
+}
+
+/// How the headers of a message are laid out.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Layout<'a> {
+    /// Every header is a receiver's, decoy's, or fake receiver's.
+    Plain,
+
+    /// The headers are preceded by an index of their hints.
+    Indexed,
+
+    /// The headers are preceded by a header which can be decrypted with the given passphrase,
+    /// stretched with `veil.pbenc` and the given parameters.
+    Passphrase { passphrase: &'a [u8], time_cost: u8, memory_cost: u8, parallelism: u8 },
+}
+
+impl Layout<'_> {
+    /// Returns the number of header-sized slots which precede the headers of a message with the
+    /// given number of headers.
+    pub(crate) const fn leading_slots(&self, header_count: usize) -> usize {
+        match self {
+            Layout::Plain => 0,
+            Layout::Indexed => index_slots(header_count),
+            Layout::Passphrase { .. } => 1,
+        }
+    }
+}
+
+impl Debug for Layout<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Plain => f.write_str("Plain"),
+            Layout::Indexed => f.write_str("Indexed"),
+            Layout::Passphrase { time_cost, memory_cost, parallelism, .. } => f
+                .debug_struct("Passphrase")
+                .field("time_cost", time_cost)
+                .field("memory_cost", memory_cost)
+                .field("parallelism", parallelism)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The beginning of an encrypted message, up to the padding.
+pub(crate) struct Preamble {
+    /// The protocol, with the nonce and encrypted headers mixed in.
+    pub mres: Protocol,
+
+    /// The ephemeral key pair which signs the message.
+    pub ephemeral: PrivKey,
+
+    /// The data encryption key.
+    pub dek: [u8; DEK_LEN],
+
+    /// The nonce, which is written first.
+    pub nonce: [u8; NONCE_LEN],
+
+    /// The encrypted headers, which are written after the nonce.
+    pub enc_headers: Vec<u8>,
+
+    /// The number of bytes of padding to write after the headers.
+    pub padding: u64,
+}
+
+impl Preamble {
+    /// Generate the ephemeral key pair, DEK, and nonce, and encrypt a header with the given format
+    /// version for each receiver, laid out as given by `layout`.
+    pub(crate) fn new(
+        mut rng: impl Rng + CryptoRng,
+        sender: &(impl KemBackend + SignerBackend + Sync),
+        receivers: &[HeaderKey],
+        padding: usize,
+        associated_data: &[u8],
+        version: u16,
+        layout: Layout<'_>,
+    ) -> Preamble {
+        let padding = u64::try_from(padding).expect("usize should be <= u64");
+
+        // Initialize a protocol and mix the sender's public key into it.
+        let mut mres = Protocol::new("veil.mres");
+        mres.mix("sender", &KemBackend::public_key(sender).0.encoded);
+
+        // Mix the associated data into the protocol. This binds all following outputs to it
+        // without including it in the ciphertext.
+        mres.mix("associated-data", associated_data);
+
+        // Generate a random ephemeral key pair, DEK, and nonce.
+        let ephemeral = PrivKey::random(&mut rng);
+        let dek = rng.gen::<[u8; DEK_LEN]>();
+        let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+        // Mix the nonce into the protocol.
+        mres.mix("nonce", &nonce);
+
+        // If there are any decoys, generate a throwaway sender key and a random header for them.
+        let decoy = receivers
+            .iter()
+            .any(|r| matches!(r, HeaderKey::Decoy(_)))
+            .then(|| (PrivKey::random(&mut rng), rng.gen::<[u8; HEADER_LEN]>()));
+
+        // If the message is indexed, order the headers by their hints and encode the index, which
+        // takes the place of the first headers.
+        let (receivers, mut enc_headers) = if layout == Layout::Indexed {
+            let (receivers, index) = encode_index(&mut rng, &mres, sender, receivers);
+            (Cow::Owned(receivers), index)
+        } else {
+            (Cow::Borrowed(receivers), Vec::new())
+        };
+        let first = layout.leading_slots(receivers.len());
+
+        // Encode a header with the format version, DEK, header count, and padding. The version is
+        // encrypted along with the rest of the header, so it is only visible to the receivers.
+        let header = Header::new(version, dek, first + receivers.len(), padding).encode();
+
+        // If the message has a passphrase, encrypt the ephemeral public key and the header with it
+        // in the first header.
+        if let Layout::Passphrase { passphrase, time_cost, memory_cost, parallelism } = layout {
+            enc_headers.extend_from_slice(&encrypt_passphrase_header(
+                &mut rng,
+                &mres,
+                passphrase,
+                (time_cost, memory_cost, parallelism),
+                &ephemeral.pub_key,
+                &header,
+            ));
+        }
+
+        // For each receiver, encrypt a copy of the header with veil.sres. Each header's nonce
+        // depends only on the protocol's current state and the header's index, so the headers can
+        // be encrypted in parallel.
+        enc_headers.append(&mut encrypt_headers(
+            &mres,
+            sender,
+            decoy.as_ref(),
+            &ephemeral,
+            &receivers,
+            &header,
+            first,
+        ));
+
+        // Mix each encrypted header into the protocol.
+        for enc_header in enc_headers.as_chunks::<ENC_HEADER_LEN>().0 {
+            mres.mix("header", enc_header);
+        }
 
-    // For each receiver, encrypt a copy of the header with veil.sres.
+        Preamble { mres, ephemeral, dek, nonce, enc_headers, padding }
+    }
+
+    /// Returns the number of encrypted headers, including any index or passphrase header.
+    fn header_count(&self) -> u64 {
+        u64::try_from(self.enc_headers.len() / ENC_HEADER_LEN).expect("usize should be <= u64")
+    }
+
+    /// Write the nonce, the encrypted headers, and the random padding to `writer`. Returns the
+    /// protocol with the format version and DEK mixed in, the framing of the payload, the ephemeral
+    /// private key, and the number of bytes written.
+    fn write(
+        self,
+        mut rng: impl Rng + CryptoRng,
+        mut writer: impl Write,
+        version: u16,
+    ) -> io::Result<(Protocol, Framing, PrivKey, u64)> {
+        let Preamble { mres, ephemeral, dek, nonce, enc_headers, padding } = self;
+
+        // Write the nonce and the encrypted headers.
+        writer.write_all(&nonce)?;
+        writer.write_all(&enc_headers)?;
+        let mut written =
+            u64::try_from(nonce.len() + enc_headers.len()).expect("usize should be <= u64");
+
+        // Frame the payload according to the format version.
+        let framing = Framing::new(version, &mres, &dek);
+
+        // Add random padding to the end of the headers, mixing it into the protocol.
+        let mut writer = mres.mix_writer("padding", writer);
+        written += io::copy(&mut RngRead(&mut rng).take(padding), &mut writer)?;
+        let (mut mres, _) = writer.into_inner();
+
+        // Mix the format version and the DEK into the protocol.
+        mres.mix("version", &version.to_le_bytes());
+        mres.mix("dek", &dek);
+
+        Ok((mres, framing, ephemeral, written))
+    }
+}
+
+/// Encrypt a copy of the header for each receiver, splitting the receivers between multiple threads
+/// if there are enough of them. Decoys get the random header in `decoy`, encrypted by its throwaway
+/// sender key instead of `sender`. The first header has the index `first` in the message. Returns
+/// the encrypted headers in receiver order.
+fn encrypt_headers(
+    mres: &Protocol,
+    sender: &(impl KemBackend + SignerBackend + Sync),
+    decoy: Option<&(PrivKey, [u8; HEADER_LEN])>,
+    ephemeral: &PrivKey,
+    receivers: &[HeaderKey],
+    header: &[u8; HEADER_LEN],
+    first: usize,
+) -> Vec<u8> {
+    trace_span!(DEBUG, "encrypt_headers", headers = receivers.len());
+    let mut enc_headers = vec![0u8; receivers.len() * ENC_HEADER_LEN];
+
+    // Use as many threads as are available, as long as each has enough headers to be worth it. On
+    // WebAssembly, the available parallelism is unsupported, so the headers are encrypted on the
+    // current thread.
+    let threads = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(receivers.len() / MIN_HEADERS_PER_THREAD)
+        .max(1);
+    let chunk_len = receivers.len().div_ceil(threads).max(1);
+
+    let encrypt_chunk = |offset: usize, receivers: &[HeaderKey], out: &mut [u8]| {
+        for (i, (receiver, enc_header)) in
+            receivers.iter().zip(out.as_chunks_mut::<ENC_HEADER_LEN>().0).enumerate()
+        {
+            let nonce =
+                header_nonce(mres, (offset + i).try_into().expect("usize should be <= u64"));
+            match receiver {
+                HeaderKey::Receiver(receiver) => {
+                    sres::encrypt(sender, ephemeral, receiver, &nonce, header, enc_header);
+                }
+                HeaderKey::Decoy(receiver) => {
+                    let (decoy_sender, decoy_header) =
+                        decoy.expect("decoys should have a throwaway sender");
+                    sres::encrypt(
+                        decoy_sender,
+                        ephemeral,
+                        receiver,
+                        &nonce,
+                        decoy_header,
+                        enc_header,
+                    );
+                }
+            }
+        }
+    };
+
+    if threads == 1 {
+        encrypt_chunk(first, receivers, &mut enc_headers);
+    } else {
+        thread::scope(|s| {
+            for (i, (receivers, out)) in receivers
+                .chunks(chunk_len)
+                .zip(enc_headers.chunks_mut(chunk_len * ENC_HEADER_LEN))
+                .enumerate()
+            {
+                s.spawn(move || encrypt_chunk(first + i * chunk_len, receivers, out));
+            }
+        });
+    }
+
+    enc_headers
+}
+
+/// Derive the nonce for the header with the given index from a copy of the protocol.
+fn header_nonce(mres: &Protocol, idx: u64) -> [u8; NONCE_LEN] {
+    let mut mres = mres.clone();
+    mres.mix("header-index", &idx.to_le_bytes());
+    mres.derive_array("header-nonce")
+}
+
+/// Returns the number of header-sized slots in the index of a message with the given number of
+/// headers: one for the tag and header count, and enough for the hints.
+pub(crate) const fn index_slots(header_count: usize) -> usize {
+    1 + header_count.div_ceil(HINTS_PER_SLOT)
+}
+
+/// Derive a hint for each header, order the headers by their hints, and encode an index of the
+/// hints. Returns the ordered headers and the index.
+///
+/// The index begins with a slot holding a tag and the number of headers, followed by slots holding
+/// the hints in order. Every slot is the size of an encrypted header, and any unused bytes are
+/// random, so the index looks like a series of fake receivers' headers to a receiver which doesn't
+/// look for it.
+fn encode_index(
+    mut rng: impl Rng + CryptoRng,
+    mres: &Protocol,
+    sender: &impl KemBackend,
+    receivers: &[HeaderKey],
+) -> (Vec<HeaderKey>, Vec<u8>) {
+    // Derive each header's hint and order the headers by them. Decoys' hints are derived the same
+    // way as receivers', so a decoy looking for its header finds one, just as a receiver would.
+    let mut hinted = receivers
+        .iter()
+        .map(|&receiver| {
+            let (HeaderKey::Receiver(q) | HeaderKey::Decoy(q)) = receiver;
+            (header_hint(mres, &q, &sender.diffie_hellman(&PublicKey(q))), receiver)
+        })
+        .collect::<Vec<_>>();
+    hinted.sort_unstable_by_key(|&(hint, _)| hint);
+
+    // Fill the index with random data, then write the tag, header count, and hints.
+    let mut index = vec![0u8; index_slots(receivers.len()) * ENC_HEADER_LEN];
+    rng.fill(index.as_mut_slice());
+    let (tag, hint_slots) = index.split_at_mut(ENC_HEADER_LEN);
+    let (tag, count) = tag.split_at_mut(INDEX_TAG_LEN);
+    tag.copy_from_slice(&index_tag(mres));
+    count[..size_of::<u64>()].copy_from_slice(
+        &u64::try_from(receivers.len()).expect("usize should be <= u64").to_le_bytes(),
+    );
+    for (slot, hints) in
+        hint_slots.as_chunks_mut::<ENC_HEADER_LEN>().0.iter_mut().zip(hinted.chunks(HINTS_PER_SLOT))
+    {
+        for (out, (hint, _)) in slot.as_chunks_mut::<HINT_LEN>().0.iter_mut().zip(hints) {
+            out.copy_from_slice(hint);
+        }
+    }
+
+    (hinted.into_iter().map(|(_, receiver)| receiver).collect(), index)
+}
+
+/// Encrypt the ephemeral public key and the header with the given passphrase and `veil.pbenc`
+/// parameters, returning the passphrase header.
+///
+/// The passphrase header begins with a tag, followed by the `veil.pbenc` parameters and salt, the
+/// sealed ephemeral public key and header, and random data to fill the slot. The tag lets a
+/// passphrase holder check for a passphrase header before stretching the passphrase.
+fn encrypt_passphrase_header(
+    mut rng: impl Rng + CryptoRng,
+    mres: &Protocol,
+    passphrase: &[u8],
+    (time_cost, memory_cost, parallelism): (u8, u8, u8),
+    ephemeral: &PubKey,
+    header: &[u8; HEADER_LEN],
+) -> [u8; ENC_HEADER_LEN] {
     let mut enc_header = [0u8; ENC_HEADER_LEN];
-    for receiver in receivers {
-        // Derive a nonce for each header.
-        let nonce = mres.derive_array::<NONCE_LEN>("header-nonce");
+    rng.fill(enc_header.as_mut_slice());
+
+    // Split up the header.
+    let (tag, params) = enc_header.split_at_mut(PASSPHRASE_TAG_LEN);
+    let (params, sealed) = params.split_at_mut(pbenc::HEADER_LEN);
+    let sealed = &mut sealed[..SEALED_HEADER_LEN];
+
+    // Write the tag, then encode the parameters and salt and stretch the passphrase.
+    tag.copy_from_slice(&passphrase_tag(mres));
+    let mut pbenc = pbenc::encrypt_header(
+        rng,
+        passphrase,
+        time_cost,
+        memory_cost,
+        parallelism,
+        params.try_into().expect("should be header-sized"),
+    );
+
+    // Mix the first header's nonce into the protocol, binding it to the message, and seal the
+    // ephemeral public key and the header.
+    pbenc.mix("header-nonce", &header_nonce(mres, 0));
+    sealed[..POINT_LEN].copy_from_slice(&ephemeral.encoded);
+    sealed[POINT_LEN..POINT_LEN + HEADER_LEN].copy_from_slice(header);
+    pbenc.seal("header", sealed);
+
+    enc_header
+}
+
+/// Decrypt the ephemeral public key and the header from a passphrase header, if the given encrypted
+/// header is one and the passphrase is correct.
+fn open_passphrase_header(
+    nonces: &Protocol,
+    enc_header: &[u8; ENC_HEADER_LEN],
+    passphrase: &[u8],
+) -> Option<(PubKey, Header)> {
+    // Split up the header.
+    let (tag, params) = enc_header.split_at(PASSPHRASE_TAG_LEN);
+    let (params, sealed) = params.split_at(pbenc::HEADER_LEN);
+
+    // Check the tag before stretching the passphrase.
+    if !ct::eq(tag, &passphrase_tag(nonces)) {
+        return None;
+    }
+    let mut pbenc = pbenc::decrypt_header(passphrase, params.try_into().ok()?)?;
 
-        // Encrypt the header for the given receiver.
-        sres::encrypt(sender, &ephemeral, receiver, &nonce, &header, &mut enc_header);
+    // Open the ephemeral public key and the header.
+    pbenc.mix("header-nonce", &header_nonce(nonces, 0));
+    let mut sealed = <[u8; SEALED_HEADER_LEN]>::try_from(sealed.get(..SEALED_HEADER_LEN)?).ok()?;
+    let (ephemeral, header) = pbenc.open("header", &mut sealed)?.split_at(POINT_LEN);
+    Some((PubKey::from_canonical_bytes(ephemeral)?, Header::decode(header)))
+}
 
-        // Mix the encrypted header into the protocol.
-        mres.mix("header", &enc_header);
+/// Derive the tag which begins a message's passphrase header from a copy of the protocol.
+fn passphrase_tag(mres: &Protocol) -> [u8; PASSPHRASE_TAG_LEN] {
+    let mut mres = mres.clone();
+    mres.derive_array("passphrase-tag")
+}
+
+/// Derive the tag which begins a message's index from a copy of the protocol.
+fn index_tag(mres: &Protocol) -> [u8; INDEX_TAG_LEN] {
+    let mut mres = mres.clone();
+    mres.derive_array("index-tag")
+}
+
+/// Derive the hint for a receiver's header from a copy of the protocol and the static ECDH shared
+/// secret of the sender and receiver. Only the sender and the receiver can derive it, and it is
+/// different for every message.
+fn header_hint(mres: &Protocol, receiver: &PubKey, static_ecdh: &[u8]) -> [u8; HINT_LEN] {
+    let mut mres = mres.clone();
+    mres.mix("hint-receiver", &receiver.encoded);
+    mres.mix("hint-static-ecdh", static_ecdh);
+    mres.derive_array("header-hint")
+}
+
+/// The framing of a message's payload, which depends on its format version.
+pub(crate) enum Framing {
+    /// Blocks are sealed in sequence with the message's protocol.
+    Stream,
+
+    /// Chunks are sealed independently with protocols derived from their indexes and followed by an
+    /// encrypted index containing the plaintext length, so any chunk can be decrypted on its own.
+    /// Each encrypted chunk and the index are also mixed into the message's protocol, so the
+    /// signature covers all of them.
+    Seekable {
+        /// The protocol from which the chunk and index protocols are derived.
+        chunks: Box<Protocol>,
+
+        /// The index of the next chunk.
+        next: u64,
+    },
+}
 
-        // Write the encrypted header.
-        writer.write_all(&enc_header).map_err(EncryptError::WriteIo)?;
-        written += u64::try_from(ENC_HEADER_LEN).expect("usize should be <= u64");
+impl Framing {
+    /// Returns the framing for the given format version, given a copy of the protocol's state
+    /// after the headers have been mixed in.
+    fn new(version: u16, headers: &Protocol, dek: &[u8; DEK_LEN]) -> Framing {
+        if version == SEEKABLE_FORMAT_VERSION {
+            Framing::Seekable { chunks: Box::new(seekable_protocol(headers, dek)), next: 0 }
+        } else {
+            Framing::Stream
+        }
     }
 
-    // Add random padding to the end of the headers, mixing it into the protocol.
-    let mut writer = mres.mix_writer("padding", writer);
-    written += io::copy(&mut RngRead(&mut rng).take(padding), &mut writer)
-        .map_err(EncryptError::WriteIo)?;
-    let (mut mres, mut writer) = writer.into_inner();
+    /// Returns the length of the encrypted data between the last block and the signature.
+    const fn index_len(&self) -> usize {
+        match self {
+            Framing::Stream => 0,
+            Framing::Seekable { .. } => ENC_INDEX_LEN,
+        }
+    }
 
-    // Mix the DEK into the protocol.
-    mres.mix("dek", &dek);
+    /// Seal the next block in place.
+    fn seal_block(&mut self, mres: &mut Protocol, block: &mut [u8]) {
+        match self {
+            Framing::Stream => mres.seal("block", block),
+            Framing::Seekable { chunks, next } => {
+                chunk_protocol(chunks, *next).seal("chunk", block);
+                mres.mix("chunk", block);
+                *next += 1;
+            }
+        }
+    }
 
-    // Encrypt the plaintext in blocks and write them.
-    written += encrypt_message(&mut mres, reader, &mut writer)?;
+    /// Open the next block in place, returning the plaintext if the block is authentic.
+    fn open_block<'a>(&mut self, mres: &mut Protocol, block: &'a mut [u8]) -> Option<&'a [u8]> {
+        match self {
+            Framing::Stream => mres.open("block", block),
+            Framing::Seekable { chunks, next } => {
+                mres.mix("chunk", block);
+                *next += 1;
+                chunk_protocol(chunks, *next - 1).open("chunk", block)
+            }
+        }
+    }
 
-    // Deterministically sign the protocol's final state with the ephemeral private key and append
-    // the signature. The protocol's state is randomized with both the nonce and the ephemeral key,
-    // so the risk of e.g. fault attacks is minimal.
-    let sig = schnorr::det_sign(&mut mres, &ephemeral);
-    writer.write_all(&sig).map_err(EncryptError::WriteIo)?;
+    /// Returns the encrypted index for a message with the given plaintext length, if any.
+    fn seal_index(&self, mres: &mut Protocol, plaintext_len: u64) -> Option<[u8; ENC_INDEX_LEN]> {
+        let Framing::Seekable { chunks, .. } = self else {
+            return None;
+        };
+        let mut enc_index = [0u8; ENC_INDEX_LEN];
+        enc_index[..size_of::<u64>()].copy_from_slice(&plaintext_len.to_le_bytes());
+        chunks.clone().seal("index", &mut enc_index);
+        mres.mix("index", &enc_index);
+        Some(enc_index)
+    }
+
+    /// Open the encrypted index, if any, and check that it matches the plaintext length.
+    fn open_index(
+        &self,
+        mres: &mut Protocol,
+        enc_index: &mut [u8],
+        plaintext_len: u64,
+    ) -> Result<(), DecryptError> {
+        let Framing::Seekable { chunks, .. } = self else {
+            return Ok(());
+        };
+        mres.mix("index", enc_index);
+        let enc_index = enc_index.try_into().map_err(|_| DecryptError::Truncated)?;
+        match open_index(chunks, enc_index) {
+            Some(len) if len == plaintext_len => Ok(()),
+            _ => Err(DecryptError::Corrupt),
+        }
+    }
+}
+
+/// Derive the protocol from which a seekable message's chunk and index protocols are derived from a
+/// copy of the protocol's state after the headers have been mixed in. Unlike the message's protocol,
+/// it does not depend on the padding, so a receiver can skip the padding to read a single chunk.
+pub(crate) fn seekable_protocol(headers: &Protocol, dek: &[u8; DEK_LEN]) -> Protocol {
+    let mut chunks = headers.clone();
+    chunks.mix("version", &SEEKABLE_FORMAT_VERSION.to_le_bytes());
+    chunks.mix("seekable-dek", dek);
+    chunks
+}
+
+/// Derive the protocol for the chunk with the given index.
+pub(crate) fn chunk_protocol(chunks: &Protocol, idx: u64) -> Protocol {
+    let mut chunk = chunks.clone();
+    chunk.mix("chunk-index", &idx.to_le_bytes());
+    chunk
+}
 
-    Ok(written + u64::try_from(DET_SIGNATURE_LEN).expect("usize should be <= u64"))
+/// Open a seekable message's encrypted index in place, returning the plaintext length if the index
+/// is authentic.
+pub(crate) fn open_index(chunks: &Protocol, enc_index: &mut [u8; ENC_INDEX_LEN]) -> Option<u64> {
+    let index = chunks.clone().open("index", enc_index)?;
+    index.try_into().ok().map(u64::from_le_bytes)
 }
 
 /// Given a protocol keyed with the DEK, read the entire contents of `reader` in blocks and write
-/// the encrypted blocks and authentication tags to `writer`.
+/// the encrypted blocks and authentication tags to `writer`, followed by the index if the framing
+/// has one.
 fn encrypt_message(
     mres: &mut Protocol,
+    framing: &mut Framing,
     mut reader: impl Read,
     mut writer: impl Write,
 ) -> Result<u64, EncryptError> {
+    trace_span!(DEBUG, "encrypt_blocks");
     let mut buf = [0u8; ENC_BLOCK_LEN];
     let mut written = 0;
+    let mut plaintext_len = 0;
 
     loop {
         // Read a block of data.
@@ -115,9 +829,832 @@ fn encrypt_message(
         let block = &mut buf[..n + TAG_LEN];
 
         // Seal the block and write it.
-        mres.seal("block", block);
+        framing.seal_block(mres, block);
         writer.write_all(block).map_err(EncryptError::WriteIo)?;
         written += u64::try_from(block.len()).expect("usize should be <= u64");
+        plaintext_len += u64::try_from(n).expect("usize should be <= u64");
+        if plaintext_len > MAX_MESSAGE_LEN {
+            return Err(EncryptError::MessageTooLong);
+        }
+
+        // If the block was undersized, we're at the end of the reader.
+        if n < BLOCK_LEN {
+            break;
+        }
+    }
+
+    // Write the index, if any.
+    if let Some(enc_index) = framing.seal_index(mres, plaintext_len) {
+        writer.write_all(&enc_index).map_err(EncryptError::WriteIo)?;
+        written += u64::try_from(ENC_INDEX_LEN).expect("usize should be <= u64");
+    }
+
+    // Return the number of ciphertext bytes written.
+    trace_event!(DEBUG, plaintext_len, written, "encrypted blocks");
+    Ok(written)
+}
+
+/// Decrypt the contents of `reader` iff they were originally encrypted by `q_s` for one of the given
+/// receivers with the given associated data and write the plaintext to `writer`. Returns the index
+/// of the matching receiver and a report of the message.
+pub fn decrypt<R: KemBackend>(
+    reader: impl Read,
+    writer: impl Write,
+    receivers: &[R],
+    sender: &PubKey,
+    associated_data: &[u8],
+) -> Result<(usize, Report), DecryptError> {
+    decrypt_with_passphrase(reader, writer, receivers, None, sender, associated_data, Limits::NONE)
+}
+
+/// Decrypt the contents of `reader` as [`decrypt`] does, within the given limits.
+pub(crate) fn decrypt_with_limits<R: KemBackend>(
+    reader: impl Read,
+    writer: impl Write,
+    receivers: &[R],
+    sender: &PubKey,
+    associated_data: &[u8],
+    limits: Limits,
+) -> Result<(usize, Report), DecryptError> {
+    decrypt_with_passphrase(reader, writer, receivers, None, sender, associated_data, limits)
+}
+
+/// Limits on how much of an untrusted message is read and written while decrypting it.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Limits {
+    /// The maximum number of bytes of the nonce, headers, and padding to read.
+    pub(crate) header_len: u64,
+
+    /// The maximum number of bytes of plaintext to write.
+    pub(crate) plaintext_len: u64,
+}
+
+impl Limits {
+    /// No limits at all.
+    pub(crate) const NONE: Limits = Limits { header_len: u64::MAX, plaintext_len: u64::MAX };
+}
+
+/// Decrypt the contents of `reader` as [`decrypt`] does, or with the given passphrase if the message
+/// was encrypted for it, within the given limits. Returns the index of the matching receiver, or the
+/// number of receivers if the message was decrypted with the passphrase, and a report of the
+/// message.
+pub(crate) fn decrypt_with_passphrase<R: KemBackend>(
+    reader: impl Read,
+    writer: impl Write,
+    receivers: &[R],
+    passphrase: Option<&[u8]>,
+    sender: &PubKey,
+    associated_data: &[u8],
+    limits: Limits,
+) -> Result<(usize, Report), DecryptError> {
+    trace_span!(INFO, "decrypt", receivers = receivers.len(), passphrase = passphrase.is_some());
+
+    // Count the bytes read, to report the length of each part of the message.
+    let mut reader = Counter(reader, 0);
+
+    // Initialize a protocol and mix the sender's public key into it.
+    let mut mres = Protocol::new("veil.mres");
+    mres.mix("sender", &sender.encoded);
+
+    // Mix the associated data into the protocol.
+    mres.mix("associated-data", associated_data);
+
+    // Read the nonce and mix it into the protocol.
+    let mut nonce = [0u8; NONCE_LEN];
+    reader.read_exact(&mut nonce).map_err(map_eof)?;
+    mres.mix("nonce", &nonce);
+
+    // Find a header, decrypt it, and mix the entirety of the headers and padding into the protocol.
+    let max_header_len = limits
+        .header_len
+        .checked_sub(u64::try_from(NONCE_LEN).expect("usize should be <= u64"))
+        .ok_or(DecryptError::HeadersTooLong)?;
+    let (mut mres, mut found) =
+        decrypt_header(mres, &mut reader, receivers, passphrase, sender, max_header_len)?;
+    if found.padding_len < found.header.padding {
+        return Err(DecryptError::Truncated);
+    }
+    let preamble_len = reader.1;
+    let header_count = (preamble_len
+        - u64::try_from(NONCE_LEN).expect("usize should be <= u64")
+        - found.padding_len)
+        / u64::try_from(ENC_HEADER_LEN).expect("usize should be <= u64");
+
+    // Mix the format version and the DEK into the protocol.
+    mres.mix("version", &found.header.version.to_le_bytes());
+    mres.mix("dek", &found.header.dek);
+
+    // Decrypt the message, decompressing it if it was compressed. The cap on the plaintext applies
+    // to the decompressed plaintext.
+    let framing = &mut found.framing;
+    let mut writer = Capped(writer, limits.plaintext_len);
+    let (written, sig) = match found.header.version {
+        #[cfg(feature = "zstd")]
+        ZSTD_FORMAT_VERSION => decrypt_compressed(&mut mres, framing, &mut reader, &mut writer),
+        _ => decrypt_message(&mut mres, framing, &mut reader, &mut writer),
+    }
+    .map_err(Capped::<()>::map_err)?;
+
+    // Verify the signature and return the receiver index and a report of the message.
+    let payload =
+        reader.1 - preamble_len - u64::try_from(DET_SIGNATURE_LEN).expect("usize should be <= u64");
+    schnorr::det_verify(&mut mres, &found.ephemeral, sig)
+        .and(Some((found.receiver, Report::new(header_count, found.padding_len, payload, written))))
+        .ok_or(DecryptError::Corrupt)
+}
+
+/// Decrypt the contents of `reader` as [`decrypt`] does and re-encrypt the payload for the given
+/// receivers as [`encrypt_version`] does, with the original message's format version, writing the
+/// new ciphertext to `writer` as each block is decrypted. Returns a report of the new message.
+///
+/// The plaintext is never written, but it can't be passed through encrypted: each block is sealed
+/// with a protocol which depends on the message's headers, so every block is opened and sealed again.
+/// If the original message's signature is invalid, the error is only returned after the new
+/// ciphertext has been written.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn rekey(
+    mut rng: impl Rng + CryptoRng,
+    mut reader: impl Read,
+    mut writer: impl Write,
+    key: &(impl KemBackend + SignerBackend + Sync),
+    sender: &PubKey,
+    receivers: &[HeaderKey],
+    padding: usize,
+    associated_data: &[u8],
+    layout: Layout<'_>,
+) -> Result<Report, DecryptError> {
+    // Initialize a protocol and mix the sender's public key and associated data into it.
+    let mut old = Protocol::new("veil.mres");
+    old.mix("sender", &sender.encoded);
+    old.mix("associated-data", associated_data);
+
+    // Read the nonce and mix it into the protocol.
+    let mut nonce = [0u8; NONCE_LEN];
+    reader.read_exact(&mut nonce).map_err(map_eof)?;
+    old.mix("nonce", &nonce);
+
+    // Find a header, decrypt it, and mix the entirety of the headers and padding into the protocol.
+    let (mut old, mut found) =
+        decrypt_header(old, &mut reader, slice::from_ref(key), None, sender, u64::MAX)?;
+    if found.padding_len < found.header.padding {
+        return Err(DecryptError::Truncated);
+    }
+    old.mix("version", &found.header.version.to_le_bytes());
+    old.mix("dek", &found.header.dek);
+
+    // Check the new message's limits before writing anything. The payload can't grow, as it's
+    // re-encrypted block for block.
+    check_limits(receivers.len(), padding)
+        .map_err(|e| DecryptError::WriteIo(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+
+    // Generate the new message's nonce and encrypted headers, and write them and the padding. The
+    // payload isn't decompressed, so the new message has the original's format version.
+    let version = found.header.version;
+    let preamble =
+        Preamble::new(&mut rng, key, receivers, padding, associated_data, version, layout);
+    let header_count = preamble.header_count();
+    let (mres, framing, ephemeral, written) =
+        preamble.write(&mut rng, &mut writer, version).map_err(DecryptError::WriteIo)?;
+
+    // Open each block of the original message and seal it again in the new message.
+    let mut blocks = BlockWriter::new(mres, framing, writer);
+    let (_, sig) = decrypt_message(&mut old, &mut found.framing, &mut reader, &mut blocks)?;
+    let (mut mres, mut writer, payload) = blocks.finish().map_err(DecryptError::WriteIo)?;
+
+    // Verify the original message's signature.
+    schnorr::det_verify(&mut old, &found.ephemeral, sig).ok_or(DecryptError::Corrupt)?;
+
+    // Sign the new message and append the signature.
+    let sig = schnorr::det_sign(&mut mres, &ephemeral);
+    writer.write_all(&sig).map_err(DecryptError::WriteIo)?;
+
+    let written =
+        written + payload + u64::try_from(DET_SIGNATURE_LEN).expect("usize should be <= u64");
+    let padding = u64::try_from(padding).expect("usize should be <= u64");
+    Ok(Report::new(header_count, padding, payload, written))
+}
+
+/// A writer which seals its input in blocks, as [`encrypt_message`] does, and writes the encrypted
+/// blocks to another writer.
+struct BlockWriter<W> {
+    mres: Protocol,
+    framing: Framing,
+    writer: W,
+    buf: Vec<u8>,
+    plaintext_len: u64,
+    written: u64,
+}
+
+impl<W: Write> BlockWriter<W> {
+    /// Create a writer which seals blocks with the given protocol and framing.
+    fn new(mres: Protocol, framing: Framing, writer: W) -> BlockWriter<W> {
+        let buf = Vec::with_capacity(ENC_BLOCK_LEN);
+        BlockWriter { mres, framing, writer, buf, plaintext_len: 0, written: 0 }
+    }
+
+    /// Seal the final, undersized block and write it, followed by the index if the framing has one.
+    /// Returns the protocol, the writer, and the number of bytes written.
+    fn finish(mut self) -> io::Result<(Protocol, W, u64)> {
+        self.seal_block()?;
+        if let Some(enc_index) = self.framing.seal_index(&mut self.mres, self.plaintext_len) {
+            self.writer.write_all(&enc_index)?;
+            self.written += u64::try_from(ENC_INDEX_LEN).expect("usize should be <= u64");
+        }
+        Ok((self.mres, self.writer, self.written))
+    }
+
+    /// Seal the buffered block and write it.
+    fn seal_block(&mut self) -> io::Result<()> {
+        self.plaintext_len += u64::try_from(self.buf.len()).expect("usize should be <= u64");
+        self.buf.resize(self.buf.len() + TAG_LEN, 0);
+        self.framing.seal_block(&mut self.mres, &mut self.buf);
+        self.writer.write_all(&self.buf)?;
+        self.written += u64::try_from(self.buf.len()).expect("usize should be <= u64");
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BlockWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Buffer as much of the input as fits in the block, sealing it once it's full. A full block
+        // is never the final block, which is always undersized.
+        let n = buf.len().min(BLOCK_LEN - self.buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+        if self.buf.len() == BLOCK_LEN {
+            self.seal_block()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Read the contents of `reader` as [`decrypt`] does, without writing the plaintext, and report the
+/// structure of the message.
+pub fn inspect<R: KemBackend>(
+    mut reader: impl Read,
+    receivers: &[R],
+    sender: &PubKey,
+    associated_data: &[u8],
+) -> Result<Inspection, DecryptError> {
+    // Initialize a protocol and mix the sender's public key and associated data into it.
+    let mut mres = Protocol::new("veil.mres");
+    mres.mix("sender", &sender.encoded);
+    mres.mix("associated-data", associated_data);
+
+    // Read the nonce and mix it into the protocol.
+    let mut nonce = [0u8; NONCE_LEN];
+    reader.read_exact(&mut nonce).map_err(map_eof)?;
+    mres.mix("nonce", &nonce);
+
+    // Find a header, decrypt it, and mix the entirety of the headers and padding into the protocol.
+    let (mut mres, mut found) =
+        decrypt_header(mres, &mut reader, receivers, None, sender, u64::MAX)?;
+    let mut inspection = Inspection {
+        version: found.header.version,
+        header_index: found.index,
+        header_count: found.header.recv_count,
+        padding_len: found.header.padding,
+        payload_len: 0,
+        status: Status::Valid,
+    };
+
+    // If the padding is truncated, the message is too.
+    if found.padding_len < found.header.padding {
+        inspection.status = Status::Truncated;
+        return Ok(inspection);
+    }
+
+    // Decrypt the message, counting the plaintext instead of writing it, and verify the signature.
+    mres.mix("version", &found.header.version.to_le_bytes());
+    mres.mix("dek", &found.header.dek);
+    let mut counter = Counter(io::sink(), 0);
+    inspection.status =
+        match decrypt_message(&mut mres, &mut found.framing, &mut reader, &mut counter) {
+            Ok((_, sig)) if schnorr::det_verify(&mut mres, &found.ephemeral, sig).is_some() => {
+                Status::Valid
+            }
+            Ok(_) => Status::InvalidSignature,
+            Err(DecryptError::Corrupt) => Status::InvalidPayload,
+            Err(DecryptError::Truncated) => Status::Truncated,
+            Err(e) => return Err(e),
+        };
+    inspection.payload_len = counter.1;
+    Ok(inspection)
+}
+
+/// Given a protocol keyed with the DEK, read the entire contents of `reader` in blocks and write
+/// the decrypted blocks `writer`. If the framing has an index, it is checked against the number of
+/// bytes written.
+fn decrypt_message(
+    mres: &mut Protocol,
+    framing: &mut Framing,
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> Result<(u64, [u8; 64]), DecryptError> {
+    trace_span!(DEBUG, "decrypt_blocks");
+    let trailer_len = framing.index_len() + DET_SIGNATURE_LEN;
+    let mut buf = [0u8; ENC_BLOCK_LEN + ENC_INDEX_LEN + DET_SIGNATURE_LEN];
+    let buf = &mut buf[..ENC_BLOCK_LEN + trailer_len];
+    let mut offset = 0;
+    let mut written = 0;
+    let mut final_block = false;
+
+    loop {
+        // Read a block, a possible index, and a possible signature, keeping in mind the unused bit
+        // of the buffer from the last iteration.
+        let n = reader.read_block(&mut buf[offset..]).map_err(DecryptError::ReadIo)?;
+
+        // If we're at the end of the reader, we only have the signature left to process. Break out
+        // of the read loop and go process the signature. Every message ends with an undersized
+        // block, so if the last block was full-sized, the ciphertext is truncated.
+        if n == 0 {
+            if !final_block {
+                return Err(DecryptError::Truncated);
+            }
+            break;
+        }
+
+        // Pretend we don't see the possible index and signature at the end. If there isn't room for
+        // them, the ciphertext is truncated.
+        let block_len = (n + offset).checked_sub(trailer_len).ok_or(DecryptError::Truncated)?;
+        let block = &mut buf[..block_len];
+        final_block = block_len < ENC_BLOCK_LEN;
+
+        // Open the block and write the plaintext. If the block cannot be decrypted, return an
+        // error.
+        let plaintext = framing.open_block(mres, block).ok_or(DecryptError::Corrupt)?;
+        writer.write_all(plaintext).map_err(DecryptError::WriteIo)?;
+        written += u64::try_from(plaintext.len()).expect("usize should be <= u64");
+
+        // Copy the unused part to the beginning of the buffer and set the offset for the next loop.
+        buf.copy_within(block_len.., 0);
+        offset = buf.len() - block_len;
+    }
+
+    // Check the index, if any, and return the number of bytes and the signature.
+    let (enc_index, sig) = buf[..trailer_len].split_at_mut(framing.index_len());
+    framing.open_index(mres, enc_index, written)?;
+    let sig = (&*sig).try_into().map_err(|_| DecryptError::Truncated)?;
+    trace_event!(DEBUG, written, "decrypted blocks");
+    Ok((written, sig))
+}
+
+/// A header which was decrypted by one of the receivers.
+struct FoundHeader {
+    /// The index of the receiver which decrypted the header.
+    receiver: usize,
+
+    /// The index of the header in the message.
+    index: u64,
+
+    /// The ephemeral public key.
+    ephemeral: PubKey,
+
+    /// The decrypted header.
+    header: Header,
+
+    /// The framing of the message's payload.
+    framing: Framing,
+
+    /// The number of bytes of padding read, which is less than the header's padding length if the
+    /// message is truncated.
+    padding_len: u64,
+}
+
+/// Iterate through the contents of `reader` looking for a header which was encrypted by the given
+/// sender for any of the given receivers or for the given passphrase, then read the padding.
+///
+/// Every header is searched before the padding is read and the payload is keyed, whichever one is
+/// decrypted, so the time it takes doesn't reveal a receiver's position in the header list. If the
+/// headers and padding are longer than `max_len` bytes, none of the padding is read.
+fn decrypt_header<R: KemBackend>(
+    mres: Protocol,
+    reader: impl Read,
+    receivers: &[R],
+    passphrase: Option<&[u8]>,
+    sender: &PubKey,
+    max_len: u64,
+) -> Result<(Protocol, FoundHeader), DecryptError> {
+    let mut reader = Counter(reader, 0);
+    let (mres, (receiver, index, ephemeral, header)) =
+        find_header(mres, &mut reader, receivers, passphrase, sender, max_len)?;
+    if reader.1.saturating_add(header.padding) > max_len {
+        return Err(DecryptError::HeadersTooLong);
+    }
+
+    // Frame the payload according to the format version.
+    let framing = Framing::new(header.version, &mres, &header.dek);
+
+    // Read the padding in blocks and mix it into the protocol. The padding is authenticated along
+    // with the headers, so it can't be skipped, but large reads keep the per-read overhead of
+    // heavily padded messages down. The buffer is only as large as the padding, so unpadded
+    // messages don't allocate one at all.
+    let mut writer = mres.mix_writer("padding", io::sink());
+    let mut buf = vec![0u8; usize::try_from(header.padding).unwrap_or(usize::MAX).min(BLOCK_LEN)];
+    let mut remaining = header.padding;
+    while remaining > 0 {
+        let len = usize::try_from(remaining).unwrap_or(usize::MAX).min(buf.len());
+        let n = reader.read_block(&mut buf[..len]).map_err(DecryptError::ReadIo)?;
+        if n == 0 {
+            // The padding is truncated; the message will fail to decrypt.
+            break;
+        }
+        writer.write_all(&buf[..n]).expect("writes to a sink should be infallible");
+        remaining -= u64::try_from(n).expect("usize should be <= u64");
+    }
+    let (mres, _) = writer.into_inner();
+
+    // Return the receiver index, header index, ephemeral public key, header, framing, and padding
+    // length.
+    let padding_len = header.padding - remaining;
+    trace_event!(DEBUG, padding = padding_len, "read padding");
+    Ok((mres, FoundHeader { receiver, index, ephemeral, header, framing, padding_len }))
+}
+
+/// Iterate through the contents of `reader` looking for a header which was encrypted by the given
+/// sender for any of the given receivers or for the given passphrase, reading all of the headers
+/// and none of the padding. Returns the protocol with the headers mixed in and the receiver index,
+/// header index, ephemeral public key, and header. If the headers are longer than `max_len` bytes,
+/// stops reading them and returns [`DecryptError::HeadersTooLong`].
+pub(crate) fn find_header<R: KemBackend>(
+    mut mres: Protocol,
+    mut reader: impl Read,
+    receivers: &[R],
+    passphrase: Option<&[u8]>,
+    sender: &PubKey,
+    max_len: u64,
+) -> Result<(Protocol, OpenedHeader), DecryptError> {
+    trace_span!(DEBUG, "header_scan", receivers = receivers.len());
+    let mut enc_header = [0u8; ENC_HEADER_LEN];
+
+    // Start the search with a copy of the protocol's state before any headers are mixed in, from
+    // which the header nonces are derived.
+    let mut search = HeaderSearch::new(mres.clone(), receivers, passphrase, sender);
+
+    // Iterate through blocks, looking for an encrypted header that can be decrypted.
+    let mut len = 0u64;
+    while !search.is_done() {
+        // Stop before reading more headers than the limit allows.
+        len += u64::try_from(ENC_HEADER_LEN).expect("usize should be <= u64");
+        if len > max_len {
+            return Err(DecryptError::HeadersTooLong);
+        }
+
+        // Read a potential encrypted header. If the header is short, we're at the end of the
+        // reader: either none of the headers could be decrypted or the headers are truncated.
+        reader.read_exact(&mut enc_header).map_err(|e| match map_eof(e) {
+            DecryptError::Truncated if !search.is_found() => DecryptError::NotAReceiver,
+            e => e,
+        })?;
+
+        // Mix the encrypted header into the protocol and search it.
+        mres.mix("header", &enc_header);
+        search.push(&enc_header);
+    }
+
+    // Unpack the header values, if any, and check that the message format is supported.
+    trace_event!(DEBUG, headers = search.next, found = search.is_found(), "scanned headers");
+    Ok((mres, search.finish()?))
+}
+
+/// A search for a header which was encrypted by the given sender for any of the given receivers or
+/// for the given passphrase, given a message's encrypted headers in order.
+///
+/// A message's passphrase header is always its first header. If the search has a passphrase, it
+/// tries to decrypt the first header with it, and the opened header's receiver index is the number
+/// of receivers.
+///
+/// If the message is indexed, each receiver only tries to decrypt the headers with its hint.
+/// Otherwise, each receiver tries to decrypt every header, including those after the one which is
+/// decrypted, so the time the search takes doesn't reveal which header that was.
+struct HeaderSearch<'a, R> {
+    nonces: Protocol,
+    receivers: &'a [R],
+    passphrase: Option<&'a [u8]>,
+    sender: &'a PubKey,
+    index: Index,
+    next: u64,
+    count: u64,
+    header: Option<OpenedHeader>,
+}
+
+/// What is known of a message's index during a [`HeaderSearch`].
+enum Index {
+    /// The first header hasn't been read yet.
+    Unknown,
+
+    /// The message isn't indexed.
+    Absent,
+
+    /// The message is indexed, and the hints of its given number of headers are being read.
+    Hints(u64, Vec<u8>),
+
+    /// The message is indexed, and the header with each given index has the hint of the receiver
+    /// with each given index.
+    Probes(Vec<(u64, usize)>),
+}
+
+impl<'a, R: KemBackend> HeaderSearch<'a, R> {
+    /// Start a search, given a copy of the protocol's state before any headers are mixed in.
+    const fn new(
+        nonces: Protocol,
+        receivers: &'a [R],
+        passphrase: Option<&'a [u8]>,
+        sender: &'a PubKey,
+    ) -> HeaderSearch<'a, R> {
+        HeaderSearch {
+            nonces,
+            receivers,
+            passphrase,
+            sender,
+            index: Index::Unknown,
+            next: 0,
+            count: u64::MAX,
+            header: None,
+        }
+    }
+
+    /// Returns `true` if all of the message's headers have been searched.
+    const fn is_done(&self) -> bool {
+        self.next >= self.count
+    }
+
+    /// Returns `true` if a header has been decrypted.
+    const fn is_found(&self) -> bool {
+        self.header.is_some()
+    }
+
+    /// Search the message's next encrypted header.
+    fn push(&mut self, enc_header: &[u8; ENC_HEADER_LEN]) {
+        let i = self.next;
+        self.next += 1;
+
+        let header = match &mut self.index {
+            Index::Unknown => {
+                // If the first header is an index's tag, read the number of headers and start
+                // reading the hints.
+                let (tag, count) = enc_header.split_at(INDEX_TAG_LEN);
+                if let (true, Some(count)) =
+                    (ct::eq(tag, &index_tag(&self.nonces)), count.first_chunk())
+                {
+                    let count = u64::from_le_bytes(*count);
+                    let hint_slots = count.div_ceil(HINTS_PER_SLOT as u64);
+                    self.count = hint_slots.saturating_add(count).saturating_add(1);
+                    self.index = Index::Hints(count, Vec::new());
+                    self.start_probes();
+                    return;
+                }
+
+                // Otherwise, search the header like any other, and with the passphrase, if any.
+                self.index = Index::Absent;
+                let header = open_header(&self.nonces, i, enc_header, self.receivers, self.sender);
+                header.or_else(|| {
+                    let (ephemeral, hdr) =
+                        open_passphrase_header(&self.nonces, enc_header, self.passphrase?)?;
+                    Some((self.receivers.len(), i, ephemeral, hdr))
+                })
+            }
+            Index::Absent => open_header(&self.nonces, i, enc_header, self.receivers, self.sender),
+            Index::Hints(_, hints) => {
+                hints.extend_from_slice(&enc_header[..HINTS_PER_SLOT * HINT_LEN]);
+                self.start_probes();
+                return;
+            }
+            Index::Probes(probes) => {
+                let mut header = None;
+                for &(_, receiver) in probes.iter().filter(|&&(idx, _)| idx == i) {
+                    let opened = open_header(
+                        &self.nonces,
+                        i,
+                        enc_header,
+                        slice::from_ref(&self.receivers[receiver]),
+                        self.sender,
+                    );
+                    if let Some((_, i, ephemeral, hdr)) = opened {
+                        header.get_or_insert((receiver, i, ephemeral, hdr));
+                    }
+                }
+                header
+            }
+        };
+
+        // If this is the first header to be decrypted, stop after the number of headers it gives.
+        if let (None, Some((_, _, _, hdr))) = (&self.header, &header) {
+            self.count = hdr.recv_count;
+            self.header = header;
+        }
+    }
+
+    /// If all of an index's hints have been read, find the headers with each receiver's hint.
+    fn start_probes(&mut self) {
+        let Index::Hints(count, hints) = &self.index else {
+            return;
+        };
+        let first = self.count - count;
+        if self.next < first {
+            return;
+        }
+
+        let hints = hints.as_chunks::<HINT_LEN>().0;
+        let hints = &hints[..usize::try_from(*count).unwrap_or(usize::MAX).min(hints.len())];
+        let mut probes = Vec::new();
+        for (receiver_idx, receiver) in self.receivers.iter().enumerate() {
+            let static_ecdh = receiver.diffie_hellman(&PublicKey(*self.sender));
+            let hint = header_hint(&self.nonces, &receiver.public_key().0, &static_ecdh);
+            probes.extend(hints.iter().enumerate().filter(|(_, h)| ct::eq(*h, &hint)).map(
+                |(idx, _)| {
+                    (first + u64::try_from(idx).expect("usize should be <= u64"), receiver_idx)
+                },
+            ));
+        }
+        self.index = Index::Probes(probes);
+    }
+
+    /// Finish the search, returning the decrypted header, if any, if its format is supported.
+    fn finish(self) -> Result<OpenedHeader, DecryptError> {
+        let header = self.header.ok_or(DecryptError::NotAReceiver)?;
+        header.3.check_version()?;
+        Ok(header)
+    }
+}
+
+/// Map an unexpected end of the reader to [`DecryptError::Truncated`].
+fn map_eof(e: io::Error) -> DecryptError {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        DecryptError::Truncated
+    } else {
+        DecryptError::ReadIo(e)
+    }
+}
+
+/// The receiver index, header index, ephemeral public key, and header of an opened header.
+pub(crate) type OpenedHeader = (usize, u64, PubKey, Header);
+
+/// Derive the nonce for the header with the given index and try to decrypt the header with each
+/// receiver. If successful, returns the receiver index, header index, ephemeral public key, and
+/// header.
+pub(crate) fn open_header<R: KemBackend>(
+    nonces: &Protocol,
+    i: u64,
+    enc_header: &[u8; ENC_HEADER_LEN],
+    receivers: &[R],
+    sender: &PubKey,
+) -> Option<OpenedHeader> {
+    // Try every receiver, even after one has decrypted the header, so the time it takes doesn't
+    // reveal which receiver that was.
+    let nonce = header_nonce(nonces, i);
+    let mut hdr_buf = [0u8; ENC_HEADER_LEN];
+    let mut opened = None;
+    for (idx, receiver) in receivers.iter().enumerate() {
+        hdr_buf.copy_from_slice(enc_header);
+        if let Some((ephemeral, hdr)) = sres::decrypt(receiver, sender, &nonce, &mut hdr_buf) {
+            opened.get_or_insert((idx, i, ephemeral, Header::decode(hdr)));
+        }
+    }
+    opened
+}
+
+pub(crate) struct Header {
+    pub version: u16,
+    pub dek: [u8; DEK_LEN],
+    pub recv_count: u64,
+    pub padding: u64,
+}
+
+impl Header {
+    fn new(version: u16, dek: [u8; DEK_LEN], recv_count: usize, padding: u64) -> Header {
+        let recv_count = recv_count.try_into().expect("usize should be <= u64");
+        Header { version, dek, recv_count, padding }
+    }
+
+    /// Returns an error unless the header's format version is supported.
+    pub(crate) fn check_version(&self) -> Result<(), DecryptError> {
+        if SUPPORTED_VERSIONS.contains(&self.version) {
+            Ok(())
+        } else {
+            Err(DecryptError::UnsupportedVersion(self.version))
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    fn decode(header: &[u8]) -> Header {
+        // Split header into components.
+        let (version, dek) = header.split_at(size_of::<u16>());
+        let (dek, recv_count) = dek.split_at(DEK_LEN);
+        let (recv_count, padding) = recv_count.split_at(size_of::<u64>());
+
+        // Decode components.
+        let version = u16::from_le_bytes(version.try_into().expect("should be 2 bytes"));
+        let dek = dek.try_into().expect("should be DEK-sized");
+        let recv_count = u64::from_le_bytes(recv_count.try_into().expect("should be 8 bytes"));
+        let padding = u64::from_le_bytes(padding.try_into().expect("should be 8 bytes"));
+
+        Header { version, dek, recv_count, padding }
+    }
+
+    #[inline]
+    #[must_use]
+    const fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        let (hdr_version, hdr_dek) = header.split_at_mut(size_of::<u16>());
+        let (hdr_dek, hdr_recv_count) = hdr_dek.split_at_mut(DEK_LEN);
+        let (hdr_recv_count, hdr_padding) = hdr_recv_count.split_at_mut(size_of::<u64>());
+        hdr_version.copy_from_slice(&self.version.to_le_bytes());
+        hdr_dek.copy_from_slice(&self.dek);
+        hdr_recv_count.copy_from_slice(&self.recv_count.to_le_bytes());
+        hdr_padding.copy_from_slice(&self.padding.to_le_bytes());
+        header
+    }
+}
+
+/// Decrypt the contents of `reader` as [`decrypt_message`] does, decompressing the plaintext as a
+/// zstd frame before writing it to `writer`. Returns the number of decompressed bytes written.
+#[cfg(feature = "zstd")]
+fn decrypt_compressed(
+    mres: &mut Protocol,
+    framing: &mut Framing,
+    reader: impl Read,
+    writer: impl Write,
+) -> Result<(u64, [u8; 64]), DecryptError> {
+    let mut decoder =
+        zstd::stream::write::Decoder::new(Counter(writer, 0)).map_err(DecryptError::WriteIo)?;
+    let (_, sig) = decrypt_message(mres, framing, reader, &mut decoder)?;
+    decoder.flush().map_err(DecryptError::WriteIo)?;
+    Ok((decoder.get_ref().1, sig))
+}
+
+/// Encrypt the contents of `reader` as [`encrypt_version`] does, reading and writing asynchronously.
+#[cfg(feature = "async")]
+#[allow(clippy::too_many_arguments)]
+pub async fn encrypt_async(
+    mut rng: impl Rng + CryptoRng,
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    sender: &(impl KemBackend + SignerBackend + Sync),
+    receivers: &[HeaderKey],
+    padding: usize,
+    associated_data: &[u8],
+    version: u16,
+    layout: Layout<'_>,
+) -> Result<Report, EncryptError> {
+    check_limits(receivers.len(), padding)?;
+
+    // Generate the nonce and encrypted headers.
+    let preamble =
+        Preamble::new(&mut rng, sender, receivers, padding, associated_data, version, layout);
+    let header_count = preamble.header_count();
+    let Preamble { mres, ephemeral, dek, nonce, enc_headers, padding } = preamble;
+
+    // Write the nonce and the encrypted headers.
+    asyncio::write_all(&mut writer, &nonce).await.map_err(EncryptError::WriteIo)?;
+    asyncio::write_all(&mut writer, &enc_headers).await.map_err(EncryptError::WriteIo)?;
+    let mut written =
+        u64::try_from(nonce.len() + enc_headers.len()).expect("usize should be <= u64");
+
+    // Frame the payload according to the format version.
+    let mut framing = Framing::new(version, &mres, &dek);
+
+    // Add random padding to the end of the headers in blocks, mixing it into the protocol.
+    let mut mixer = mres.mix_writer("padding", io::sink());
+    let mut buf = vec![0u8; ENC_BLOCK_LEN];
+    let mut remaining = padding;
+    while remaining > 0 {
+        let len = usize::try_from(remaining).unwrap_or(usize::MAX).min(BLOCK_LEN);
+        rng.fill_bytes(&mut buf[..len]);
+        mixer.write_all(&buf[..len]).expect("writes to a sink should be infallible");
+        asyncio::write_all(&mut writer, &buf[..len]).await.map_err(EncryptError::WriteIo)?;
+        remaining -= u64::try_from(len).expect("usize should be <= u64");
+    }
+    written += padding;
+    let (mut mres, _) = mixer.into_inner();
+
+    // Mix the format version and the DEK into the protocol.
+    mres.mix("version", &version.to_le_bytes());
+    mres.mix("dek", &dek);
+
+    // Encrypt the plaintext in blocks and write them.
+    let mut plaintext_len = 0;
+    let mut payload = 0;
+    loop {
+        // Read a block of data.
+        let n = asyncio::read_block(&mut reader, &mut buf[..BLOCK_LEN])
+            .await
+            .map_err(EncryptError::ReadIo)?;
+        let block = &mut buf[..n + TAG_LEN];
+
+        // Seal the block and write it.
+        framing.seal_block(&mut mres, block);
+        asyncio::write_all(&mut writer, block).await.map_err(EncryptError::WriteIo)?;
+        payload += u64::try_from(block.len()).expect("usize should be <= u64");
+        plaintext_len += u64::try_from(n).expect("usize should be <= u64");
+        if plaintext_len > MAX_MESSAGE_LEN {
+            return Err(EncryptError::MessageTooLong);
+        }
 
         // If the block was undersized, we're at the end of the reader.
         if n < BLOCK_LEN {
@@ -125,178 +1662,228 @@ fn encrypt_message(
         }
     }
 
-    // Return the number of ciphertext bytes written.
-    Ok(written)
+    // Write the index, if any.
+    if let Some(enc_index) = framing.seal_index(&mut mres, plaintext_len) {
+        asyncio::write_all(&mut writer, &enc_index).await.map_err(EncryptError::WriteIo)?;
+        payload += u64::try_from(ENC_INDEX_LEN).expect("usize should be <= u64");
+    }
+
+    // Sign the protocol's final state with the ephemeral private key and append the signature.
+    let sig = schnorr::det_sign(&mut mres, &ephemeral);
+    asyncio::write_all(&mut writer, &sig).await.map_err(EncryptError::WriteIo)?;
+
+    let written =
+        written + payload + u64::try_from(DET_SIGNATURE_LEN).expect("usize should be <= u64");
+    Ok(Report::new(header_count, padding, payload, written))
 }
 
-/// Decrypt the contents of `reader` iff they were originally encrypted by `q_s` for `q_r` and write
-/// the plaintext to `writer`.
-pub fn decrypt(
-    mut reader: impl Read,
-    mut writer: impl Write,
-    receiver: &PrivKey,
+/// Decrypt the contents of `reader` as [`decrypt_with_limits`] does, reading and writing
+/// asynchronously.
+#[cfg(feature = "async")]
+pub async fn decrypt_async<R: KemBackend>(
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    receivers: &[R],
     sender: &PubKey,
-) -> Result<u64, DecryptError> {
-    // Initialize a protocol and mix the sender's public key into it.
+    associated_data: &[u8],
+    limits: Limits,
+) -> Result<(usize, Report), DecryptError> {
+    // Initialize a protocol and mix the sender's public key and associated data into it.
     let mut mres = Protocol::new("veil.mres");
     mres.mix("sender", &sender.encoded);
+    mres.mix("associated-data", associated_data);
 
     // Read the nonce and mix it into the protocol.
     let mut nonce = [0u8; NONCE_LEN];
-    reader.read_exact(&mut nonce).map_err(DecryptError::ReadIo)?;
+    asyncio::read_exact(&mut reader, &mut nonce).await.map_err(map_eof)?;
     mres.mix("nonce", &nonce);
 
-    // Find a header, decrypt it, and mix the entirety of the headers and padding into the protocol.
-    let (mut mres, ephemeral, dek) = decrypt_header(mres, &mut reader, receiver, sender)?;
-
-    // Mix the DEK into the protocol.
-    mres.mix("dek", &dek);
-
-    // Decrypt the message.
-    let (written, sig) = decrypt_message(&mut mres, &mut reader, &mut writer)?;
-
-    // Verify the signature and return the number of bytes written.
-    schnorr::det_verify(&mut mres, &ephemeral, sig)
-        .and(Some(written))
-        .ok_or(DecryptError::InvalidCiphertext)
-}
+    // Iterate through the headers, looking for one which can be decrypted, without reading more
+    // of them than the limit allows.
+    let mut search = HeaderSearch::new(mres.clone(), receivers, None, sender);
+    let mut enc_header = [0u8; ENC_HEADER_LEN];
+    let mut header_len = u64::try_from(NONCE_LEN).expect("usize should be <= u64");
+    while !search.is_done() {
+        header_len += u64::try_from(ENC_HEADER_LEN).expect("usize should be <= u64");
+        if header_len > limits.header_len {
+            return Err(DecryptError::HeadersTooLong);
+        }
+        asyncio::read_exact(&mut reader, &mut enc_header).await.map_err(|e| match map_eof(e) {
+            DecryptError::Truncated if !search.is_found() => DecryptError::NotAReceiver,
+            e => e,
+        })?;
+        mres.mix("header", &enc_header);
+        search.push(&enc_header);
+    }
+    let header_count = search.next;
+    let (receiver, _, ephemeral, header) = search.finish()?;
+    if header_len.saturating_add(header.padding) > limits.header_len {
+        return Err(DecryptError::HeadersTooLong);
+    }
 
-/// Given a protocol keyed with the DEK, read the entire contents of `reader` in blocks and write
-/// the decrypted blocks `writer`.
-fn decrypt_message(
-    mres: &mut Protocol,
-    mut reader: impl Read,
-    mut writer: impl Write,
-) -> Result<(u64, [u8; 64]), DecryptError> {
-    let mut buf = [0u8; ENC_BLOCK_LEN + DET_SIGNATURE_LEN];
+    // Frame the payload according to the format version.
+    let mut framing = Framing::new(header.version, &mres, &header.dek);
+    let trailer_len = framing.index_len() + DET_SIGNATURE_LEN;
+
+    // Read the padding in blocks and mix it into the protocol.
+    let mut mixer = mres.mix_writer("padding", io::sink());
+    let mut buf = vec![0u8; ENC_BLOCK_LEN + trailer_len];
+    let mut remaining = header.padding;
+    while remaining > 0 {
+        let len = usize::try_from(remaining).unwrap_or(usize::MAX).min(BLOCK_LEN);
+        asyncio::read_exact(&mut reader, &mut buf[..len]).await.map_err(map_eof)?;
+        mixer.write_all(&buf[..len]).expect("writes to a sink should be infallible");
+        remaining -= u64::try_from(len).expect("usize should be <= u64");
+    }
+    let (mut mres, _) = mixer.into_inner();
+
+    // Mix the format version and the DEK into the protocol.
+    mres.mix("version", &header.version.to_le_bytes());
+    mres.mix("dek", &header.dek);
+
+    // If the message is compressed, decompress each block in bounded chunks as it's written.
+    #[cfg(feature = "zstd")]
+    let mut decoder = if header.version == ZSTD_FORMAT_VERSION {
+        Some(zstd::stream::raw::Decoder::new().map_err(DecryptError::WriteIo)?)
+    } else {
+        None
+    };
+    #[cfg(feature = "zstd")]
+    let mut chunk = vec![0u8; if decoder.is_some() { BLOCK_LEN } else { 0 }];
+
+    // Decrypt the message in blocks, holding back a possible index and signature at the end of each
+    // read. The cap on the plaintext applies to the decompressed plaintext.
     let mut offset = 0;
+    let mut read = 0;
     let mut written = 0;
-
+    let mut final_block = false;
     loop {
-        // Read a block and a possible signature, keeping in mind the unused bit of the buffer from
-        // the last iteration.
-        let n = reader.read_block(&mut buf[offset..]).map_err(DecryptError::ReadIo)?;
-
-        // If we're at the end of the reader, we only have the signature left to process. Break out
-        // of the read loop and go process the signature.
+        let n = asyncio::read_block(&mut reader, &mut buf[offset..])
+            .await
+            .map_err(DecryptError::ReadIo)?;
+        read += u64::try_from(n).expect("usize should be <= u64");
         if n == 0 {
+            if !final_block {
+                return Err(DecryptError::Truncated);
+            }
             break;
         }
 
-        // Pretend we don't see the possible signature at the end.
-        let block_len = n - DET_SIGNATURE_LEN + offset;
-        let block = &mut buf[..block_len];
-
-        // Open the block and write the plaintext. If the block cannot be decrypted, return an
-        // error.
-        let plaintext = mres.open("block", block).ok_or(DecryptError::InvalidCiphertext)?;
-        writer.write_all(plaintext).map_err(DecryptError::WriteIo)?;
-        written += u64::try_from(plaintext.len()).expect("usize should be <= u64");
+        let block_len = (n + offset).checked_sub(trailer_len).ok_or(DecryptError::Truncated)?;
+        final_block = block_len < ENC_BLOCK_LEN;
+        let plaintext =
+            framing.open_block(&mut mres, &mut buf[..block_len]).ok_or(DecryptError::Corrupt)?;
+        #[cfg(feature = "zstd")]
+        if let Some(decoder) = decoder.as_mut() {
+            use zstd::stream::raw::{InBuffer, Operation, OutBuffer};
+
+            // Decompress the block a chunk at a time, until all of it has been consumed and the
+            // decoder has no more output to give.
+            let mut input = InBuffer::around(plaintext);
+            loop {
+                let mut output = OutBuffer::around(chunk.as_mut_slice());
+                decoder.run(&mut input, &mut output).map_err(DecryptError::WriteIo)?;
+                let full = output.pos() == output.capacity();
+                let out = output.as_slice();
+                write_capped(&mut writer, out, &mut written, limits.plaintext_len).await?;
+                if input.pos() == plaintext.len() && !full {
+                    break;
+                }
+            }
+        } else {
+            write_capped(&mut writer, plaintext, &mut written, limits.plaintext_len).await?;
+        }
+        #[cfg(not(feature = "zstd"))]
+        write_capped(&mut writer, plaintext, &mut written, limits.plaintext_len).await?;
 
-        // Copy the unused part to the beginning of the buffer and set the offset for the next loop.
         buf.copy_within(block_len.., 0);
         offset = buf.len() - block_len;
     }
 
-    // Return the number of bytes and the signature.
-    Ok((written, buf[..DET_SIGNATURE_LEN].try_into().expect("should be signature-sized")))
+    // Check the index, if any, verify the signature, and return the receiver index and a report of
+    // the message.
+    let (enc_index, sig) = buf[..trailer_len].split_at_mut(framing.index_len());
+    framing.open_index(&mut mres, enc_index, written)?;
+    let sig = (&*sig).try_into().map_err(|_| DecryptError::Truncated)?;
+    let payload = read - u64::try_from(DET_SIGNATURE_LEN).expect("usize should be <= u64");
+    schnorr::det_verify(&mut mres, &ephemeral, sig)
+        .and(Some((receiver, Report::new(header_count, header.padding, payload, written))))
+        .ok_or(DecryptError::Corrupt)
 }
 
-/// Iterate through the contents of `reader` looking for a header which was encrypted by the given
-/// sender for the given receiver.
-fn decrypt_header(
-    mut mres: Protocol,
-    mut reader: impl Read,
-    receiver: &PrivKey,
-    sender: &PubKey,
-) -> Result<(Protocol, PubKey, [u8; DEK_LEN]), DecryptError> {
-    let mut enc_header = [0u8; ENC_HEADER_LEN];
-    let mut header = None;
-    let mut i = 0u64;
-    let mut recv_count = u64::MAX;
-
-    // Iterate through blocks, looking for an encrypted header that can be decrypted.
-    while i < recv_count {
-        // Read a potential encrypted header. If the header is short, we're at the end of the
-        // reader.
-        reader.read_exact(&mut enc_header).map_err(|e| {
-            if e.kind() == io::ErrorKind::UnexpectedEof {
-                DecryptError::InvalidCiphertext
-            } else {
-                DecryptError::ReadIo(e)
-            }
-        })?;
+/// Write `buf` to `writer` asynchronously, adding its length to `written`. If that would write more
+/// than `cap` bytes in all, writes only as much of `buf` as fits and returns
+/// [`DecryptError::PlaintextTooLong`].
+#[cfg(feature = "async")]
+async fn write_capped(
+    writer: &mut (impl AsyncWrite + Unpin),
+    buf: &[u8],
+    written: &mut u64,
+    cap: u64,
+) -> Result<(), DecryptError> {
+    let remaining = usize::try_from(cap - *written).unwrap_or(usize::MAX);
+    let n = buf.len().min(remaining);
+    asyncio::write_all(writer, &buf[..n]).await.map_err(DecryptError::WriteIo)?;
+    *written += u64::try_from(n).expect("usize should be <= u64");
+    if n < buf.len() {
+        return Err(DecryptError::PlaintextTooLong);
+    }
+    Ok(())
+}
 
-        // Derive a nonce regardless of whether we need to in order to keep the protocol state
-        // consistent.
-        let nonce = mres.derive_array::<NONCE_LEN>("header-nonce");
+/// A writer or reader which counts the bytes written to or read from the inner writer or reader.
+struct Counter<W>(W, u64);
 
-        // Mix the encrypted header into the protocol.
-        mres.mix("header", &enc_header);
+/// A writer which writes at most the given number of bytes to the inner writer, and fails rather
+/// than write any more.
+struct Capped<W>(W, u64);
 
-        // If a header hasn't been decrypted yet, try to decrypt this one.
-        if header.is_none() {
-            if let Some((ephemeral, hdr)) = sres::decrypt(receiver, sender, &nonce, &mut enc_header)
-            {
-                // If the header was successfully decrypted, keep the ephemeral public key, DEK, and
-                // padding and update the loop variable to not be effectively infinite.
-                let hdr = Header::decode(hdr);
-                recv_count = hdr.recv_count;
-                header = Some((ephemeral, hdr));
+impl<W> Capped<W> {
+    /// Replaces the IO error returned when the cap is exceeded with
+    /// [`DecryptError::PlaintextTooLong`].
+    fn map_err(e: DecryptError) -> DecryptError {
+        match e {
+            DecryptError::WriteIo(e) if e.get_ref().is_some_and(|e| e.is::<DecryptError>()) => {
+                DecryptError::PlaintextTooLong
             }
+            e => e,
         }
-
-        i += 1;
     }
-
-    // Unpack the header values, if any.
-    let (ephemeral, header) = header.ok_or(DecryptError::InvalidCiphertext)?;
-
-    // Read the padding and mix it into the protocol.
-    let mut writer = mres.mix_writer("padding", io::sink());
-    io::copy(&mut reader.take(header.padding), &mut writer).map_err(DecryptError::ReadIo)?;
-    let (mres, _) = writer.into_inner();
-
-    // Return the ephemeral public key and DEK.
-    Ok((mres, ephemeral, header.dek))
-}
-
-struct Header {
-    dek: [u8; DEK_LEN],
-    recv_count: u64,
-    padding: u64,
 }
 
-impl Header {
-    fn new(dek: [u8; DEK_LEN], recv_count: usize, padding: u64) -> Header {
-        Header { dek, recv_count: recv_count.try_into().expect("usize should be <= u64"), padding }
+impl<W: Write> Write for Capped<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.1 == 0 && !buf.is_empty() {
+            return Err(io::Error::other(DecryptError::PlaintextTooLong));
+        }
+        let len = usize::try_from(self.1).unwrap_or(usize::MAX).min(buf.len());
+        let n = self.0.write(&buf[..len])?;
+        self.1 -= u64::try_from(n).expect("usize should be <= u64");
+        Ok(n)
     }
 
-    #[inline]
-    #[must_use]
-    fn decode(header: &[u8]) -> Header {
-        // Split header into components.
-        let (dek, recv_count) = header.split_at(DEK_LEN);
-        let (recv_count, padding) = recv_count.split_at(mem::size_of::<u64>());
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
 
-        // Decode components.
-        let dek = dek.try_into().expect("should be DEK-sized");
-        let recv_count = u64::from_le_bytes(recv_count.try_into().expect("should be 8 bytes"));
-        let padding = u64::from_le_bytes(padding.try_into().expect("should be 8 bytes"));
+impl<R: Read> Read for Counter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.0.read(buf)?;
+        self.1 += u64::try_from(n).expect("usize should be <= u64");
+        Ok(n)
+    }
+}
 
-        Header { dek, recv_count, padding }
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.0.write(buf)?;
+        self.1 += u64::try_from(n).expect("usize should be <= u64");
+        Ok(n)
     }
 
-    #[inline]
-    #[must_use]
-    fn encode(&self) -> [u8; HEADER_LEN] {
-        let mut header = [0u8; HEADER_LEN];
-        let (hdr_dek, hdr_recv_count) = header.split_at_mut(DEK_LEN);
-        let (hdr_recv_count, hdr_padding) = hdr_recv_count.split_at_mut(mem::size_of::<u64>());
-        hdr_dek.copy_from_slice(&self.dek);
-        hdr_recv_count.copy_from_slice(&self.recv_count.to_le_bytes());
-        hdr_padding.copy_from_slice(&self.padding.to_le_bytes());
-        header
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
     }
 }
 
@@ -316,13 +1903,14 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::io::Cursor;
+    use std::{cell::Cell, io::Cursor, slice};
 
     use assert_matches::assert_matches;
     use rand::{RngCore, SeedableRng};
     use rand_chacha::ChaChaRng;
 
     use super::*;
+    use crate::keys::POINT_LEN;
 
     #[test]
     fn round_trip() {
@@ -330,13 +1918,54 @@ mod tests {
 
         let mut writer = Cursor::new(Vec::new());
 
-        let ptx_len = decrypt(Cursor::new(ciphertext), &mut writer, &receiver, &sender.pub_key)
-            .expect("decryption should be ok");
+        let (_, ptx_len) =
+            decrypt(Cursor::new(ciphertext), &mut writer, &[receiver], &sender.pub_key, &[])
+                .expect("decryption should be ok");
 
-        assert_eq!(writer.position(), ptx_len, "returned/observed plaintext length mismatch");
+        assert_eq!(
+            writer.position(),
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
         assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
     }
 
+    #[test]
+    fn unsupported_version() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receiver = PrivKey::random(&mut rng);
+
+        let mut ciphertext = Vec::new();
+        encrypt_version(
+            &mut rng,
+            &b"a message"[..],
+            &mut ciphertext,
+            &sender,
+            &[HeaderKey::Receiver(receiver.pub_key)],
+            0,
+            &[],
+            u16::MAX,
+            Layout::Plain,
+        )
+        .expect("encryption should be ok");
+
+        assert_matches!(
+            decrypt(
+                Cursor::new(&ciphertext),
+                io::sink(),
+                slice::from_ref(&receiver),
+                &sender.pub_key,
+                &[]
+            ),
+            Err(DecryptError::UnsupportedVersion(u16::MAX))
+        );
+        assert_matches!(
+            inspect(Cursor::new(&ciphertext), slice::from_ref(&receiver), &sender.pub_key, &[]),
+            Err(DecryptError::UnsupportedVersion(u16::MAX))
+        );
+    }
+
     #[test]
     fn wrong_sender() {
         let (mut rng, _, receiver, _, ciphertext) = setup(64);
@@ -344,8 +1973,14 @@ mod tests {
         let wrong_sender = PubKey::random(&mut rng);
 
         assert_matches!(
-            decrypt(Cursor::new(ciphertext), Cursor::new(Vec::new()), &receiver, &wrong_sender),
-            Err(DecryptError::InvalidCiphertext)
+            decrypt(
+                Cursor::new(ciphertext),
+                Cursor::new(Vec::new()),
+                &[receiver],
+                &wrong_sender,
+                &[]
+            ),
+            Err(DecryptError::NotAReceiver)
         );
     }
 
@@ -359,22 +1994,189 @@ mod tests {
             decrypt(
                 Cursor::new(ciphertext),
                 Cursor::new(Vec::new()),
-                &wrong_receiver,
-                &sender.pub_key
+                &[wrong_receiver],
+                &sender.pub_key,
+                &[]
+            ),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn any_receiver() {
+        let (mut rng, sender, receiver, plaintext, ciphertext) = setup(64);
+
+        let receivers = [PrivKey::random(&mut rng), receiver, PrivKey::random(&mut rng)];
+        let mut writer = Cursor::new(Vec::new());
+        let (idx, _) =
+            decrypt(Cursor::new(ciphertext), &mut writer, &receivers, &sender.pub_key, &[])
+                .expect("decryption should be ok");
+
+        assert_eq!(1, idx, "invalid receiver index");
+        assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
+    }
+
+    #[test]
+    fn associated_data() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receivers = [PrivKey::random(&mut rng)];
+        let plaintext = b"a message";
+
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut rng,
+            Cursor::new(plaintext),
+            &mut ciphertext,
+            &sender,
+            &[HeaderKey::Receiver(receivers[0].pub_key)],
+            0,
+            b"channel 1, message 2",
+        )
+        .expect("encryption should be ok");
+
+        let mut writer = Cursor::new(Vec::new());
+        decrypt(
+            Cursor::new(&ciphertext),
+            &mut writer,
+            &receivers,
+            &sender.pub_key,
+            b"channel 1, message 2",
+        )
+        .expect("decryption should be ok");
+        assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
+
+        assert_matches!(
+            decrypt(
+                Cursor::new(&ciphertext),
+                io::sink(),
+                &receivers,
+                &sender.pub_key,
+                b"channel 1, message 3"
             ),
-            Err(DecryptError::InvalidCiphertext)
+            Err(DecryptError::NotAReceiver)
         );
     }
 
+    #[test]
+    fn parallel_headers() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let ephemeral = PrivKey::random(&mut rng);
+        let receivers = (0..4 * MIN_HEADERS_PER_THREAD + 3)
+            .map(|_| PrivKey::random(&mut rng).pub_key)
+            .collect::<Vec<_>>();
+        let header = Header::new(FORMAT_VERSION, [7; DEK_LEN], receivers.len(), 0).encode();
+        let mres = Protocol::new("veil.mres");
+
+        let header_keys = receivers.iter().copied().map(HeaderKey::Receiver).collect::<Vec<_>>();
+        let enc_headers =
+            encrypt_headers(&mres, &sender, None, &ephemeral, &header_keys, &header, 0);
+
+        let mut expected = vec![0u8; enc_headers.len()];
+        for (i, (receiver, enc_header)) in
+            receivers.iter().zip(expected.as_chunks_mut::<ENC_HEADER_LEN>().0).enumerate()
+        {
+            let nonce = header_nonce(&mres, i as u64);
+            sres::encrypt(&sender, &ephemeral, receiver, &nonce, &header, enc_header);
+        }
+        assert_eq!(expected, enc_headers, "headers should be in receiver order");
+    }
+
+    #[test]
+    fn many_receivers() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receivers =
+            (0..2 * MIN_HEADERS_PER_THREAD).map(|_| PrivKey::random(&mut rng)).collect::<Vec<_>>();
+        let plaintext = b"a message";
+
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut rng,
+            Cursor::new(plaintext),
+            &mut ciphertext,
+            &sender,
+            &receivers.iter().map(|r| HeaderKey::Receiver(r.pub_key)).collect::<Vec<_>>(),
+            0,
+            &[],
+        )
+        .expect("encryption should be ok");
+
+        let mut writer = Cursor::new(Vec::new());
+        let (idx, _) = decrypt(
+            Cursor::new(&ciphertext),
+            &mut writer,
+            &receivers[receivers.len() - 1..],
+            &sender.pub_key,
+            &[],
+        )
+        .expect("decryption should be ok");
+        assert_eq!(0, idx, "invalid receiver index");
+        assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
+    }
+
+    #[test]
+    fn ciphertext_lengths() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receivers = [
+            HeaderKey::Receiver(PrivKey::random(&mut rng).pub_key),
+            HeaderKey::Decoy(PrivKey::random(&mut rng).pub_key),
+        ];
+
+        for len in [0, 1, BLOCK_LEN - 1, BLOCK_LEN, BLOCK_LEN + 1, 2 * BLOCK_LEN] {
+            let mut ciphertext = Vec::new();
+            encrypt(&mut rng, &vec![0u8; len][..], &mut ciphertext, &sender, &receivers, 17, &[])
+                .expect("encryption should be ok");
+            assert_eq!(
+                ciphertext.len() as u64,
+                ciphertext_len(FORMAT_VERSION, receivers.len(), 17, len as u64),
+                "invalid ciphertext length for {len} bytes of plaintext"
+            );
+        }
+    }
+
+    #[test]
+    fn large_padding() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receiver = PrivKey::random(&mut rng);
+        let plaintext = b"a message";
+        let padding = 3 * BLOCK_LEN + 17;
+
+        let mut ciphertext = Vec::new();
+        encrypt(
+            &mut rng,
+            Cursor::new(plaintext),
+            &mut ciphertext,
+            &sender,
+            &[HeaderKey::Receiver(receiver.pub_key)],
+            padding,
+            &[],
+        )
+        .expect("encryption should be ok");
+
+        let mut writer = Cursor::new(Vec::new());
+        decrypt(Cursor::new(&ciphertext), &mut writer, &[receiver], &sender.pub_key, &[])
+            .expect("decryption should be ok");
+        assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
+    }
+
     #[test]
     fn multi_block_message() {
         let (_, sender, receiver, plaintext, ciphertext) = setup(65 * 1024);
 
         let mut writer = Cursor::new(Vec::new());
-        let ptx_len = decrypt(Cursor::new(ciphertext), &mut writer, &receiver, &sender.pub_key)
-            .expect("decryption should be ok");
+        let (_, ptx_len) =
+            decrypt(Cursor::new(ciphertext), &mut writer, &[receiver], &sender.pub_key, &[])
+                .expect("decryption should be ok");
 
-        assert_eq!(writer.position(), ptx_len, "returned/observed plaintext length mismatch");
+        assert_eq!(
+            writer.position(),
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
         assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
     }
 
@@ -383,16 +2185,55 @@ mod tests {
         let (_, sender, receiver, plaintext, ciphertext) = setup(32 * 1024 - 37);
 
         let mut writer = Cursor::new(Vec::new());
-        let ptx_len = decrypt(Cursor::new(ciphertext), &mut writer, &receiver, &sender.pub_key)
-            .expect("decryption should be ok");
+        let (_, ptx_len) =
+            decrypt(Cursor::new(ciphertext), &mut writer, &[receiver], &sender.pub_key, &[])
+                .expect("decryption should be ok");
 
-        assert_eq!(writer.position(), ptx_len, "returned/observed plaintext length mismatch");
+        assert_eq!(
+            writer.position(),
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
         assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
     }
 
+    #[test]
+    fn truncated() {
+        let (_, sender, receiver, _, ciphertext) = setup(BLOCK_LEN + 100);
+        let receivers = [receiver];
+        let payload_start = NONCE_LEN + 2 * ENC_HEADER_LEN + 123;
+
+        // Truncated in the nonce, the padding, before the signature, or after a full-sized block.
+        for len in [
+            NONCE_LEN - 1,
+            payload_start - 50,
+            payload_start + 10,
+            payload_start + ENC_BLOCK_LEN + DET_SIGNATURE_LEN,
+        ] {
+            assert_matches!(
+                decrypt(&ciphertext[..len], io::sink(), &receivers, &sender.pub_key, &[]),
+                Err(DecryptError::Truncated),
+                "truncated to {len} bytes"
+            );
+        }
+
+        // A truncated final block can't be distinguished from a corrupt one.
+        assert_matches!(
+            decrypt(
+                &ciphertext[..ciphertext.len() - 1],
+                io::sink(),
+                &receivers,
+                &sender.pub_key,
+                &[]
+            ),
+            Err(DecryptError::Corrupt)
+        );
+    }
+
     #[test]
     fn flip_every_bit() {
         let (_, sender, receiver, _, ciphertext) = setup(16);
+        let receivers = [receiver];
 
         for i in 0..ciphertext.len() {
             for j in 0u8..8 {
@@ -400,8 +2241,8 @@ mod tests {
                 ciphertext[i] ^= 1 << j;
                 let mut src = Cursor::new(ciphertext);
 
-                match decrypt(&mut src, &mut io::sink(), &receiver, &sender.pub_key) {
-                    Err(DecryptError::InvalidCiphertext) => {}
+                match decrypt(&mut src, &mut io::sink(), &receivers, &sender.pub_key, &[]) {
+                    Err(DecryptError::NotAReceiver | DecryptError::Corrupt) => {}
                     Ok(_) => panic!("bit flip at byte {i}, bit {j} produced a valid message"),
                     Err(e) => panic!("unknown error: {e:?}"),
                 };
@@ -409,6 +2250,213 @@ mod tests {
         }
     }
 
+    #[test]
+    fn indexed() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receivers = (0..100).map(|_| PrivKey::random(&mut rng)).collect::<Vec<_>>();
+        let decoy = PrivKey::random(&mut rng);
+        let mut header_keys =
+            receivers.iter().map(|r| HeaderKey::Receiver(r.pub_key)).collect::<Vec<_>>();
+        header_keys.push(HeaderKey::Decoy(decoy.pub_key));
+        let plaintext = b"a message";
+
+        let mut ciphertext = Vec::new();
+        encrypt_version(
+            &mut rng,
+            Cursor::new(plaintext),
+            &mut ciphertext,
+            &sender,
+            &header_keys,
+            17,
+            &[],
+            FORMAT_VERSION,
+            Layout::Indexed,
+        )
+        .expect("encryption should be ok");
+        assert_eq!(
+            ciphertext_len(FORMAT_VERSION, index_slots(101) + 101, 17, plaintext.len() as u64),
+            ciphertext.len() as u64,
+            "invalid ciphertext length"
+        );
+
+        // Each receiver derives its hint once and tries to decrypt only the header with its hint.
+        for receiver in &receivers {
+            let counter = Counting(receiver, Cell::new(0));
+            let mut writer = Cursor::new(Vec::new());
+            decrypt(
+                Cursor::new(&ciphertext),
+                &mut writer,
+                slice::from_ref(&counter),
+                &sender.pub_key,
+                &[],
+            )
+            .expect("decryption should be ok");
+            assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
+            assert!(counter.1.get() <= 4, "too many scalar multiplications: {}", counter.1.get());
+        }
+
+        let inspection = inspect(Cursor::new(&ciphertext), &receivers[..1], &sender.pub_key, &[])
+            .expect("inspection should be ok");
+        assert_eq!(index_slots(101) as u64 + 101, inspection.header_count);
+
+        assert_matches!(
+            decrypt(Cursor::new(&ciphertext), io::sink(), &[decoy], &sender.pub_key, &[]),
+            Err(DecryptError::NotAReceiver)
+        );
+        assert_matches!(
+            decrypt(
+                Cursor::new(&ciphertext),
+                io::sink(),
+                &receivers[..1],
+                &PubKey::random(&mut rng),
+                &[]
+            ),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn passphrase() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receiver = PrivKey::random(&mut rng);
+        let header_keys = [
+            HeaderKey::Receiver(PrivKey::random(&mut rng).pub_key),
+            HeaderKey::Receiver(receiver.pub_key),
+        ];
+        let plaintext = b"a message";
+
+        let mut ciphertext = Vec::new();
+        encrypt_version(
+            &mut rng,
+            Cursor::new(plaintext),
+            &mut ciphertext,
+            &sender,
+            &header_keys,
+            17,
+            b"ad",
+            FORMAT_VERSION,
+            Layout::Passphrase {
+                passphrase: b"excelsior",
+                time_cost: 0,
+                memory_cost: 0,
+                parallelism: 1,
+            },
+        )
+        .expect("encryption should be ok");
+        assert_eq!(
+            ciphertext_len(FORMAT_VERSION, 3, 17, plaintext.len() as u64),
+            ciphertext.len() as u64,
+            "invalid ciphertext length"
+        );
+
+        // Both the receiver and anyone with the passphrase can decrypt the message.
+        let mut writer = Cursor::new(Vec::new());
+        decrypt(Cursor::new(&ciphertext), &mut writer, &[&receiver], &sender.pub_key, b"ad")
+            .expect("decryption should be ok");
+        assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
+
+        let mut writer = Cursor::new(Vec::new());
+        let (idx, _) = decrypt_with_passphrase::<PrivKey>(
+            Cursor::new(&ciphertext),
+            &mut writer,
+            &[],
+            Some(b"excelsior"),
+            &sender.pub_key,
+            b"ad",
+            Limits::NONE,
+        )
+        .expect("decryption should be ok");
+        assert_eq!(0, idx);
+        assert_eq!(plaintext.to_vec(), writer.into_inner(), "incorrect plaintext");
+
+        // The wrong passphrase, sender, or associated data can't decrypt it.
+        for (passphrase, sender, ad) in [
+            (&b"dingus"[..], sender.pub_key, &b"ad"[..]),
+            (b"excelsior", PubKey::random(&mut rng), b"ad"),
+            (b"excelsior", sender.pub_key, b"other"),
+        ] {
+            assert_matches!(
+                decrypt_with_passphrase::<PrivKey>(
+                    Cursor::new(&ciphertext),
+                    io::sink(),
+                    &[],
+                    Some(passphrase),
+                    &sender,
+                    ad,
+                    Limits::NONE,
+                ),
+                Err(DecryptError::NotAReceiver)
+            );
+        }
+
+        // A message without a passphrase header can't be decrypted with a passphrase.
+        let (_, sender, _, _, ciphertext) = setup(100);
+        assert_matches!(
+            decrypt_with_passphrase::<PrivKey>(
+                Cursor::new(&ciphertext),
+                io::sink(),
+                &[],
+                Some(b"excelsior"),
+                &sender.pub_key,
+                &[],
+                Limits::NONE,
+            ),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn uniform_header_scanning() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivKey::random(&mut rng);
+        let receivers = (0..8).map(|_| PrivKey::random(&mut rng)).collect::<Vec<_>>();
+        let header_keys =
+            receivers.iter().map(|r| HeaderKey::Receiver(r.pub_key)).collect::<Vec<_>>();
+
+        let mut ciphertext = Vec::new();
+        encrypt(&mut rng, &b"a message"[..], &mut ciphertext, &sender, &header_keys, 0, &[])
+            .expect("encryption should be ok");
+
+        // Each receiver's header is at a different position, but every receiver does the same work
+        // to find it: it tries to decrypt every header, including those after its own.
+        let counts = receivers
+            .iter()
+            .map(|receiver| {
+                let counter = Counting(receiver, Cell::new(0));
+                decrypt(
+                    Cursor::new(&ciphertext),
+                    io::sink(),
+                    slice::from_ref(&counter),
+                    &sender.pub_key,
+                    &[],
+                )
+                .expect("decryption should be ok");
+                counter.1.get()
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            counts.iter().all(|&n| n == counts[0]),
+            "uneven scalar multiplications: {counts:?}"
+        );
+        assert!(counts[0] >= 2 * receivers.len(), "too few scalar multiplications: {counts:?}");
+    }
+
+    /// A receiver which counts its scalar multiplications.
+    struct Counting<'a>(&'a PrivKey, Cell<usize>);
+
+    impl KemBackend for Counting<'_> {
+        fn public_key(&self) -> PublicKey {
+            KemBackend::public_key(self.0)
+        }
+
+        fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+            self.1.set(self.1.get() + 1);
+            self.0.diffie_hellman(point)
+        }
+    }
+
     fn setup(n: usize) -> (ChaChaRng, PrivKey, PrivKey, Vec<u8>, Vec<u8>) {
         let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
         let sender = PrivKey::random(&mut rng);
@@ -422,14 +2470,15 @@ mod tests {
             Cursor::new(&plaintext),
             Cursor::new(&mut ciphertext),
             &sender,
-            &[sender.pub_key, receiver.pub_key],
+            &[HeaderKey::Receiver(sender.pub_key), HeaderKey::Receiver(receiver.pub_key)],
             123,
+            &[],
         )
         .expect("encryption should be ok");
 
         assert_eq!(
             u64::try_from(ciphertext.len()).expect("usize should be <= u64"),
-            ctx_len,
+            ctx_len.written(),
             "returned/observed ciphertext length mismatch"
         );
 
@@ -0,0 +1,554 @@
+//! Aggregated n-of-n signatures.
+//!
+//! A group of signers can jointly produce a single [`Signature`] of a message which verifies with
+//! [`PublicKey::verify`] against their [`AggregateKey`], without any signer learning another's
+//! private key. This is a variant of `MuSig2` with two rounds:
+//!
+//! 1. Each signer calls [`commit`] and sends their [`PublicNonce`] to the others.
+//! 2. Each signer creates a [`Session`] with every signer's public nonce and the message, calls
+//!    [`Session::sign`] with their [`SecretNonce`], and sends the resulting [`PartialSignature`] to
+//!    whoever combines them.
+//!
+//! Finally, [`Session::combine`] checks each partial signature and combines them into a signature.
+//!
+//! ```
+//! use veil::{multisig, PrivateKey};
+//!
+//! let a = PrivateKey::random(rand::thread_rng());
+//! let b = PrivateKey::random(rand::thread_rng());
+//! let key = multisig::AggregateKey::new(&[a.public_key(), b.public_key()]).expect("should aggregate");
+//!
+//! // Round 1: each signer commits to a pair of nonces.
+//! let (secret_a, public_a) = multisig::commit(rand::thread_rng(), &a);
+//! let (secret_b, public_b) = multisig::commit(rand::thread_rng(), &b);
+//! let nonces = [(a.public_key(), public_a), (b.public_key(), public_b)];
+//!
+//! // Round 2: each signer creates a partial signature.
+//! let message = b"release v1.0.0";
+//! let session = multisig::Session::new(&key, &nonces, &message[..]).expect("should start");
+//! let partial_a = session.sign(&a, secret_a).expect("should sign");
+//! let partial_b = session.sign(&b, secret_b).expect("should sign");
+//!
+//! // The partial signatures are combined into a normal signature.
+//! let sig = session
+//!     .combine(&[(a.public_key(), partial_a), (b.public_key(), partial_b)])
+//!     .expect("should combine");
+//! assert!(key.public_key().verify(&message[..], &sig).is_ok());
+//! ```
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::{self, Read},
+};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    ct,
+    curve::{Curve, Point, Scalar, Selected},
+    keys::{PubKey, POINT_LEN, SCALAR_LEN},
+    schnorr::SIGNATURE_LEN,
+    sres::NONCE_LEN,
+    MultisigError, PrivateKey, PublicKey, Signature,
+};
+
+/// The length of an encoded public nonce in bytes.
+pub const PUBLIC_NONCE_LEN: usize = POINT_LEN * 2;
+
+/// The length of an encoded partial signature in bytes.
+pub const PARTIAL_SIGNATURE_LEN: usize = SCALAR_LEN;
+
+/// The aggregated public key of a set of signers.
+#[derive(Clone)]
+pub struct AggregateKey {
+    public_key: PublicKey,
+    signers: Vec<(PublicKey, Scalar)>,
+}
+
+impl AggregateKey {
+    /// Aggregates the given signers' public keys. The order of the public keys does not matter.
+    ///
+    /// # Errors
+    ///
+    /// If there are no public keys, a public key is listed more than once, or the public keys
+    /// aggregate to an invalid key, returns [`MultisigError::InvalidSigners`].
+    pub fn new(signers: &[PublicKey]) -> Result<AggregateKey, MultisigError> {
+        // Sort the public keys so every signer calculates the same coefficients.
+        let mut signers = signers.to_vec();
+        signers.sort_by_key(|pk| pk.0.encoded);
+        if signers.is_empty() || signers.windows(2).any(|w| w[0] == w[1]) {
+            return Err(MultisigError::InvalidSigners);
+        }
+
+        // Initialize a protocol and mix in the full set of public keys.
+        let mut multisig = Protocol::new("veil.multisig");
+        for pk in &signers {
+            multisig.mix("signer", &pk.0.encoded);
+        }
+
+        // Derive a coefficient for each public key and calculate the aggregate public key. The
+        // coefficients prevent a signer from choosing their key to cancel out the others'.
+        let signers = signers
+            .into_iter()
+            .map(|pk| {
+                let mut clone = multisig.clone();
+                clone.mix("coefficient-signer", &pk.0.encoded);
+                (pk, Scalar::decode_reduce(&clone.derive_array::<32>("key-coefficient")))
+            })
+            .collect::<Vec<_>>();
+        let q = signers.iter().fold(Point::NEUTRAL, |q, (pk, a)| q + pk.0.q * a);
+        let public_key = PubKey::from_canonical_bytes(q.encode())
+            .map(PublicKey)
+            .ok_or(MultisigError::InvalidSigners)?;
+
+        Ok(AggregateKey { public_key, signers })
+    }
+
+    /// Returns the aggregate public key, which verifies signatures created by all the signers.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// Returns the signers' public keys, in a canonical order.
+    pub fn signers(&self) -> impl Iterator<Item = PublicKey> + '_ {
+        self.signers.iter().map(|&(pk, _)| pk)
+    }
+
+    fn coefficient(&self, signer: &PublicKey) -> Option<Scalar> {
+        self.signers.iter().find_map(|(pk, a)| (pk == signer).then_some(*a))
+    }
+}
+
+impl Debug for AggregateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AggregateKey")
+            .field("public_key", &self.public_key)
+            .field("signers", &self.signers().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// A signer's secret nonces for a single signing session. Must never be reused.
+pub struct SecretNonce {
+    signer: PublicKey,
+    k: [Scalar; 2],
+}
+
+impl Debug for SecretNonce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretNonce").field("signer", &self.signer).finish_non_exhaustive()
+    }
+}
+
+/// A signer's commitment to their secret nonces, sent to the other signers.
+#[derive(Clone, Copy)]
+pub struct PublicNonce {
+    r: [Point; 2],
+    encoded: [u8; PUBLIC_NONCE_LEN],
+}
+
+impl PublicNonce {
+    /// Decode a public nonce from a 64-byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<PublicNonce> {
+        let encoded = <[u8; PUBLIC_NONCE_LEN]>::try_from(b.as_ref()).ok()?;
+        let (r0, r1) = encoded.split_at(POINT_LEN);
+        Some(PublicNonce { r: [ct::point(r0)?, ct::point(r1)?], encoded })
+    }
+
+    /// Encode the public nonce as a 64-byte array.
+    #[must_use]
+    pub const fn encode(&self) -> [u8; PUBLIC_NONCE_LEN] {
+        self.encoded
+    }
+}
+
+impl Debug for PublicNonce {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "PublicNonce({:02x?})", self.encoded)
+    }
+}
+
+impl Eq for PublicNonce {}
+
+impl PartialEq for PublicNonce {
+    fn eq(&self, other: &Self) -> bool {
+        ct::eq(&self.encoded, &other.encoded)
+    }
+}
+
+/// A signer's share of an aggregated signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialSignature([u8; PARTIAL_SIGNATURE_LEN]);
+
+impl PartialSignature {
+    /// Decode a partial signature from a 32-byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<PartialSignature> {
+        let b = <[u8; PARTIAL_SIGNATURE_LEN]>::try_from(b.as_ref()).ok()?;
+        ct::scalar(&b).map(|_| PartialSignature(b))
+    }
+
+    /// Encode the partial signature as a 32-byte array.
+    #[must_use]
+    pub const fn encode(&self) -> [u8; PARTIAL_SIGNATURE_LEN] {
+        self.0
+    }
+
+    fn scalar(&self) -> Scalar {
+        ct::scalar(&self.0).expect("should be a canonical scalar")
+    }
+}
+
+/// Generates a pair of secret nonces for the given signer and returns them with the public nonce
+/// which commits to them.
+#[must_use]
+pub fn commit(mut rng: impl Rng + CryptoRng, signer: &PrivateKey) -> (SecretNonce, PublicNonce) {
+    // Initialize a protocol with the signer's public key and a random value.
+    let signer = signer.public_key();
+    let mut nonce = Protocol::new("veil.multisig.nonce");
+    nonce.mix("signer", &signer.0.encoded);
+    nonce.mix("random", &rng.gen::<[u8; 64]>());
+
+    // Derive the two secret nonces and calculate their commitment points.
+    let k = [
+        Scalar::decode_reduce(&nonce.derive_array::<32>("secret-nonce")),
+        Scalar::decode_reduce(&nonce.derive_array::<32>("secret-nonce")),
+    ];
+    let r = [Point::mulgen(&k[0]), Point::mulgen(&k[1])];
+
+    let mut encoded = [0u8; PUBLIC_NONCE_LEN];
+    encoded[..POINT_LEN].copy_from_slice(&r[0].encode());
+    encoded[POINT_LEN..].copy_from_slice(&r[1].encode());
+    (SecretNonce { signer, k }, PublicNonce { r, encoded })
+}
+
+/// A session in which a set of signers signs a single message.
+pub struct Session<'a> {
+    key: &'a AggregateKey,
+    nonces: Vec<(PublicKey, PublicNonce)>,
+    schnorr: Protocol,
+    nonce: [u8; NONCE_LEN],
+    commitment: [u8; POINT_LEN],
+    b: Scalar,
+    r: Scalar,
+}
+
+impl<'a> Session<'a> {
+    /// Starts a signing session with every signer's public nonce, reading the message from
+    /// `message`.
+    ///
+    /// # Errors
+    ///
+    /// If there is not exactly one public nonce for each signer, returns
+    /// [`MultisigError::InvalidNonces`]. If there is an error reading from `message`, returns
+    /// [`MultisigError::ReadIo`].
+    pub fn new(
+        key: &'a AggregateKey,
+        nonces: &[(PublicKey, PublicNonce)],
+        mut message: impl Read,
+    ) -> Result<Session<'a>, MultisigError> {
+        if nonces.len() != key.signers.len() {
+            return Err(MultisigError::InvalidNonces);
+        }
+
+        // Order the public nonces the same way as the signers.
+        let nonces = key
+            .signers()
+            .map(|signer| {
+                let mut found = nonces.iter().filter(|(pk, _)| *pk == signer);
+                match (found.next(), found.next()) {
+                    (Some(&nonce), None) => Ok(nonce),
+                    _ => Err(MultisigError::InvalidNonces),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Aggregate the signers' public nonces.
+        let r = nonces
+            .iter()
+            .fold([Point::NEUTRAL; 2], |[r0, r1], (_, nonce)| [r0 + nonce.r[0], r1 + nonce.r[1]]);
+        let (r0, r1) = (r[0].encode(), r[1].encode());
+
+        // Derive the signature's nonce from the aggregate key and nonces.
+        let mut multisig = Protocol::new("veil.multisig");
+        multisig.mix("aggregate-key", &key.public_key.0.encoded);
+        multisig.mix("aggregate-nonce", &r0);
+        multisig.mix("aggregate-nonce", &r1);
+        let nonce = multisig.derive_array::<NONCE_LEN>("nonce");
+
+        // Initialize a veil.schnorr protocol, exactly as if the aggregate key were signing the
+        // message alone.
+        let mut schnorr = Protocol::new("veil.schnorr");
+        schnorr.mix("signer", &key.public_key.0.encoded);
+        schnorr.mix("nonce", &nonce);
+        let mut writer = schnorr.mix_writer("message", io::sink());
+        io::copy(&mut message, &mut writer).map_err(MultisigError::ReadIo)?;
+        let (mut schnorr, _) = writer.into_inner();
+
+        // Derive a coefficient for the second nonces which is bound to the message and the
+        // aggregate nonces, and use it to calculate the commitment point.
+        let mut clone = schnorr.clone();
+        clone.mix("aggregate-nonce", &r0);
+        clone.mix("aggregate-nonce", &r1);
+        let b = Scalar::decode_reduce(&clone.derive_array::<32>("nonce-coefficient"));
+        let i = r[0] + r[1] * b;
+
+        // Encrypt the commitment point and derive the challenge scalar, as in veil.schnorr.
+        let mut commitment = i.encode();
+        schnorr.encrypt("commitment-point", &mut commitment);
+        let rb = schnorr.derive_array::<16>("challenge-scalar");
+        let c0 = u64::from_le_bytes(rb[..8].try_into().expect("rb should be 16 bytes"));
+        let c1 = u64::from_le_bytes(rb[8..].try_into().expect("rb should be 16 bytes"));
+        let r = Selected::challenge(c0, c1);
+
+        Ok(Session { key, nonces, schnorr, nonce, commitment, b, r })
+    }
+
+    /// Creates the given signer's partial signature, consuming their secret nonce.
+    ///
+    /// # Errors
+    ///
+    /// If the signer is not one of the aggregated signers, returns
+    /// [`MultisigError::UnknownSigner`]. If the secret nonce was not created by the signer or does
+    /// not match the public nonce the session was started with, returns
+    /// [`MultisigError::InvalidNonces`].
+    pub fn sign(
+        &self,
+        signer: &PrivateKey,
+        nonce: SecretNonce,
+    ) -> Result<PartialSignature, MultisigError> {
+        let pk = signer.public_key();
+        let a = self.key.coefficient(&pk).ok_or(MultisigError::UnknownSigner)?;
+        let public_nonce = self.public_nonce(&pk).ok_or(MultisigError::UnknownSigner)?;
+        if nonce.signer != pk
+            || !ct::eq(&Point::mulgen(&nonce.k[0]).encode(), &public_nonce.encoded[..POINT_LEN])
+            || !ct::eq(&Point::mulgen(&nonce.k[1]).encode(), &public_nonce.encoded[POINT_LEN..])
+        {
+            return Err(MultisigError::InvalidNonces);
+        }
+
+        // Calculate the signer's share of the proof scalar.
+        let s = nonce.k[0] + nonce.k[1] * self.b + self.r * a * signer.0.d;
+        Ok(PartialSignature(s.encode()))
+    }
+
+    /// Checks that the given partial signature was created by the given signer in this session.
+    #[must_use]
+    pub fn verify(&self, signer: &PublicKey, partial: &PartialSignature) -> bool {
+        let (Some(a), Some(nonce)) = (self.key.coefficient(signer), self.public_nonce(signer))
+        else {
+            return false;
+        };
+
+        // Check that [s]G == R₀ + [b]R₁ + [ra]Q.
+        let expected = nonce.r[0] + nonce.r[1] * self.b + signer.0.q * (self.r * a);
+        Point::mulgen(&partial.scalar()).equals(expected) != 0
+    }
+
+    /// Checks every signer's partial signature and combines them into a signature of the message
+    /// which verifies with the aggregate public key.
+    ///
+    /// # Errors
+    ///
+    /// If there is not exactly one partial signature for each signer, returns
+    /// [`MultisigError::InvalidNonces`]. If a partial signature is invalid, returns
+    /// [`MultisigError::InvalidPartialSignature`] with its index in `partials`.
+    pub fn combine(
+        &self,
+        partials: &[(PublicKey, PartialSignature)],
+    ) -> Result<Signature, MultisigError> {
+        if partials.len() != self.nonces.len() {
+            return Err(MultisigError::InvalidNonces);
+        }
+
+        // Check and sum the partial signatures.
+        let mut s = Scalar::ZERO;
+        for (signer, _) in &self.nonces {
+            let mut found = partials.iter().enumerate().filter(|(_, (pk, _))| pk == signer);
+            let (Some((idx, (_, partial))), None) = (found.next(), found.next()) else {
+                return Err(MultisigError::InvalidNonces);
+            };
+            if !self.verify(signer, partial) {
+                return Err(MultisigError::InvalidPartialSignature(idx));
+            }
+            s += partial.scalar();
+        }
+
+        // Encrypt the proof scalar, as in veil.schnorr.
+        let mut schnorr = self.schnorr.clone();
+        let mut proof = s.encode();
+        schnorr.encrypt("proof-scalar", &mut proof);
+
+        let mut sig = [0u8; SIGNATURE_LEN];
+        sig[..NONCE_LEN].copy_from_slice(&self.nonce);
+        sig[NONCE_LEN..NONCE_LEN + POINT_LEN].copy_from_slice(&self.commitment);
+        sig[NONCE_LEN + POINT_LEN..].copy_from_slice(&proof);
+        Ok(Signature::decode(sig).expect("should be signature-sized"))
+    }
+
+    fn public_nonce(&self, signer: &PublicKey) -> Option<&PublicNonce> {
+        self.nonces.iter().find_map(|(pk, nonce)| (pk == signer).then_some(nonce))
+    }
+}
+
+impl Debug for Session<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("key", &self.key)
+            .field("nonces", &self.nonces)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        for n in 1..=4 {
+            let (mut rng, keys, key) = setup(n);
+            let (secrets, nonces) = commit_all(&mut rng, &keys);
+
+            let session = Session::new(&key, &nonces, &b"a message"[..]).expect("should start");
+            let partials = keys
+                .iter()
+                .zip(secrets)
+                .map(|(k, secret)| (k.public_key(), session.sign(k, secret).expect("should sign")))
+                .collect::<Vec<_>>();
+            let sig = session.combine(&partials).expect("should combine");
+
+            assert_matches!(key.public_key().verify(&b"a message"[..], &sig), Ok(()));
+            assert_matches!(
+                key.public_key().verify(&b"another message"[..], &sig),
+                Err(crate::VerifyError::InvalidSignature)
+            );
+        }
+    }
+
+    #[test]
+    fn key_order() {
+        let (_, keys, key) = setup(3);
+        let reversed = keys.iter().rev().map(PrivateKey::public_key).collect::<Vec<_>>();
+        let other = AggregateKey::new(&reversed).expect("should aggregate");
+        assert_eq!(key.public_key(), other.public_key(), "order of keys should not matter");
+    }
+
+    #[test]
+    fn invalid_signers() {
+        let (_, keys, _) = setup(2);
+        assert_matches!(AggregateKey::new(&[]), Err(MultisigError::InvalidSigners));
+        assert_matches!(
+            AggregateKey::new(&[keys[0].public_key(), keys[1].public_key(), keys[0].public_key()]),
+            Err(MultisigError::InvalidSigners)
+        );
+    }
+
+    #[test]
+    fn missing_nonce() {
+        let (mut rng, keys, key) = setup(2);
+        let (_, nonces) = commit_all(&mut rng, &keys);
+
+        assert_matches!(
+            Session::new(&key, &nonces[..1], &b"a message"[..]),
+            Err(MultisigError::InvalidNonces)
+        );
+        assert_matches!(
+            Session::new(&key, &[nonces[0], nonces[0], nonces[1]], &b"a message"[..]),
+            Err(MultisigError::InvalidNonces)
+        );
+    }
+
+    #[test]
+    fn unknown_signer() {
+        let (mut rng, keys, key) = setup(2);
+        let (_, nonces) = commit_all(&mut rng, &keys);
+        let session = Session::new(&key, &nonces, &b"a message"[..]).expect("should start");
+
+        let other = PrivateKey::random(&mut rng);
+        let (secret, _) = commit(&mut rng, &other);
+        assert_matches!(session.sign(&other, secret), Err(MultisigError::UnknownSigner));
+    }
+
+    #[test]
+    fn mismatched_nonce() {
+        let (mut rng, keys, key) = setup(2);
+        let (mut secrets, nonces) = commit_all(&mut rng, &keys);
+        let session = Session::new(&key, &nonces, &b"a message"[..]).expect("should start");
+
+        // A signer can't use another signer's secret nonce or a fresh one.
+        let secret_b = secrets.pop().expect("should have two secret nonces");
+        assert_matches!(session.sign(&keys[0], secret_b), Err(MultisigError::InvalidNonces));
+        let (fresh, _) = commit(&mut rng, &keys[0]);
+        assert_matches!(session.sign(&keys[0], fresh), Err(MultisigError::InvalidNonces));
+    }
+
+    #[test]
+    fn invalid_partial_signature() {
+        let (mut rng, keys, key) = setup(2);
+        let (mut secrets, nonces) = commit_all(&mut rng, &keys);
+        let session = Session::new(&key, &nonces, &b"a message"[..]).expect("should start");
+
+        let secret_b = secrets.pop().expect("should have two secret nonces");
+        let secret_a = secrets.pop().expect("should have two secret nonces");
+        let partial_a = session.sign(&keys[0], secret_a).expect("should sign");
+        let partial_b = session.sign(&keys[1], secret_b).expect("should sign");
+        assert!(session.verify(&keys[0].public_key(), &partial_a));
+        assert!(!session.verify(&keys[1].public_key(), &partial_a));
+
+        // A partial signature from a different session is rejected.
+        let (_, other_nonces) = commit_all(&mut rng, &keys);
+        let other = Session::new(&key, &other_nonces, &b"a message"[..]).expect("should start");
+        assert_matches!(
+            other.combine(&[(keys[0].public_key(), partial_a), (keys[1].public_key(), partial_b)]),
+            Err(MultisigError::InvalidPartialSignature(_))
+        );
+
+        // Each signer must provide exactly one partial signature.
+        assert_matches!(
+            session.combine(&[(keys[0].public_key(), partial_a)]),
+            Err(MultisigError::InvalidNonces)
+        );
+        assert_matches!(
+            session
+                .combine(&[(keys[0].public_key(), partial_a), (keys[0].public_key(), partial_a)]),
+            Err(MultisigError::InvalidNonces)
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let (mut rng, keys, _) = setup(1);
+        let (_, nonce) = commit(&mut rng, &keys[0]);
+        assert_eq!(Some(nonce), PublicNonce::decode(nonce.encode()));
+        assert_eq!(None, PublicNonce::decode([0xFF; PUBLIC_NONCE_LEN]));
+        assert_eq!(None, PartialSignature::decode([0xFF; PARTIAL_SIGNATURE_LEN]));
+    }
+
+    fn commit_all(
+        rng: &mut ChaChaRng,
+        keys: &[PrivateKey],
+    ) -> (Vec<SecretNonce>, Vec<(PublicKey, PublicNonce)>) {
+        keys.iter()
+            .map(|k| {
+                let (secret, public) = commit(&mut *rng, k);
+                (secret, (k.public_key(), public))
+            })
+            .unzip()
+    }
+
+    fn setup(n: usize) -> (ChaChaRng, Vec<PrivateKey>, AggregateKey) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let keys = (0..n).map(|_| PrivateKey::random(&mut rng)).collect::<Vec<_>>();
+        let key = AggregateKey::new(&keys.iter().map(PrivateKey::public_key).collect::<Vec<_>>())
+            .expect("should aggregate");
+        (rng, keys, key)
+    }
+}
@@ -0,0 +1,826 @@
+//! Options for encrypting, signing, and digesting messages.
+
+use std::{
+    borrow::Cow,
+    fmt::{self, Debug, Formatter},
+    io::{self, Read},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use lockstitch::Protocol;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    mres::{self, Layout},
+    padme, EncryptError, PublicKey,
+};
+
+/// A slot in an encrypted message's list of headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HeaderSlot {
+    /// A header for the receiver with the given index in the receivers passed to
+    /// [`EncryptOptions::new`].
+    Receiver(usize),
+
+    /// A header for the decoy with the given index in the decoys passed to
+    /// [`EncryptOptions::decoys`], which no one can decrypt.
+    Decoy(usize),
+
+    /// A header for a fake receiver, which no one can decrypt.
+    Fake,
+}
+
+/// The amount of random padding added to a message to disguise its length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Padding {
+    /// Add the given number of bytes of padding.
+    Fixed(usize),
+
+    /// Pad the message to its [PADMÉ length](padme), given the length of its plaintext.
+    ///
+    /// The padding accounts for the message's headers and other overhead, so the entire encrypted
+    /// message has a PADMÉ length. If the plaintext is not the given length, the message's length
+    /// may reveal more about it. If the message is [compressed](EncryptOptions::compress), the
+    /// compressed length is used instead.
+    Automatic {
+        /// The length of the plaintext in bytes.
+        plaintext_len: u64,
+    },
+}
+
+/// Options for encrypting a message, with a builder-style API.
+///
+/// By default, a message has no fake receivers or decoys, no padding, no associated data, and no
+/// compression, and its headers are randomly interleaved using the encryption RNG.
+///
+/// ```
+/// use veil::{EncryptOptions, PrivateKey};
+///
+/// let receiver = PrivateKey::random(rand::thread_rng()).public_key();
+/// let receivers = [receiver];
+/// let options = EncryptOptions::new(&receivers).fakes(4).padding(1024);
+/// ```
+///
+/// Rather than choosing an amount of padding by hand, which can leak more about a message's length
+/// than it hides, the padding can be chosen automatically:
+///
+/// ```
+/// use veil::{EncryptOptions, Padding, PrivateKey};
+///
+/// let receiver = PrivateKey::random(rand::thread_rng()).public_key();
+/// let receivers = [receiver];
+/// let plaintext = b"a message";
+/// let options = EncryptOptions::new(&receivers)
+///     .padding_policy(Padding::Automatic { plaintext_len: plaintext.len() as u64 });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptOptions<'a> {
+    pub(crate) receivers: &'a [PublicKey],
+    pub(crate) decoys: &'a [PublicKey],
+    pub(crate) fakes: usize,
+    padding: Padding,
+    associated_data: &'a [u8],
+    sequence: Option<(&'a [u8], u64)>,
+    slots: Option<Vec<HeaderSlot>>,
+    seekable: bool,
+    pub(crate) layout: Layout<'a>,
+    #[cfg(feature = "zstd")]
+    pub(crate) compression: Option<i32>,
+}
+
+impl<'a> EncryptOptions<'a> {
+    /// Creates options for encrypting a message for the given receivers.
+    #[must_use]
+    pub const fn new(receivers: &'a [PublicKey]) -> EncryptOptions<'a> {
+        EncryptOptions {
+            receivers,
+            decoys: &[],
+            fakes: 0,
+            padding: Padding::Fixed(0),
+            associated_data: &[],
+            sequence: None,
+            slots: None,
+            seekable: false,
+            layout: Layout::Plain,
+            #[cfg(feature = "zstd")]
+            compression: None,
+        }
+    }
+
+    /// Adds the given number of fake receivers to disguise the number of true receivers.
+    ///
+    /// Clears any header order set with [`EncryptOptions::shuffle`] or
+    /// [`EncryptOptions::slots`].
+    #[must_use]
+    pub fn fakes(mut self, fakes: usize) -> EncryptOptions<'a> {
+        self.fakes = fakes;
+        self.slots = None;
+        self
+    }
+
+    /// Adds a header for each of the given decoys to disguise the number and identity of the true
+    /// receivers.
+    ///
+    /// Unlike fake receivers, which use randomly generated public keys, decoys can be the public
+    /// keys of real correspondents. A decoy's header is encrypted by a throwaway key and contains
+    /// nothing of the message, so decoys cannot decrypt the message, but the header is otherwise
+    /// indistinguishable from a true receiver's header. Random public keys can be created with
+    /// [`PublicKey::random`].
+    ///
+    /// Clears any header order set with [`EncryptOptions::shuffle`] or
+    /// [`EncryptOptions::slots`].
+    #[must_use]
+    pub fn decoys(mut self, decoys: &'a [PublicKey]) -> EncryptOptions<'a> {
+        self.decoys = decoys;
+        self.slots = None;
+        self
+    }
+
+    /// Adds the given number of bytes of random padding to disguise the message length.
+    #[must_use]
+    pub const fn padding(mut self, padding: usize) -> EncryptOptions<'a> {
+        self.padding = Padding::Fixed(padding);
+        self
+    }
+
+    /// Sets the amount of random padding to add to disguise the message length.
+    #[must_use]
+    pub const fn padding_policy(mut self, padding: Padding) -> EncryptOptions<'a> {
+        self.padding = padding;
+        self
+    }
+
+    /// Binds the message to the given associated data (e.g. a channel ID or sequence number).
+    ///
+    /// The associated data is not included in the ciphertext, and the receivers must provide the
+    /// same associated data to decrypt it.
+    #[must_use]
+    pub const fn associated_data(mut self, associated_data: &'a [u8]) -> EncryptOptions<'a> {
+        self.associated_data = associated_data;
+        self
+    }
+
+    /// Binds the message to the given channel ID and sequence number, so that it can only be
+    /// decrypted with [`PrivateKey::decrypt_in_sequence`](crate::PrivateKey::decrypt_in_sequence)
+    /// and the same channel ID and sequence number.
+    ///
+    /// When Veil messages are used as a transport, this lets receivers reject replayed or reordered
+    /// messages as soon as the headers have been read, before any of the payload is decrypted. The
+    /// channel ID and sequence number take the place of any associated data set with
+    /// [`EncryptOptions::associated_data`].
+    #[must_use]
+    pub const fn sequence(mut self, channel: &'a [u8], sequence: u64) -> EncryptOptions<'a> {
+        self.sequence = Some((channel, sequence));
+        self
+    }
+
+    /// Compresses the plaintext with zstd at the given level before encrypting it.
+    ///
+    /// The compressed plaintext is buffered in memory, so its length is known before the padding is
+    /// written. Compression makes the message's length depend on its contents as well as its size,
+    /// so compressed messages should be padded with [`Padding::Automatic`], which pads the
+    /// compressed length.
+    ///
+    /// Compressed messages are not seekable, so this clears [`EncryptOptions::seekable`].
+    #[cfg(feature = "zstd")]
+    #[must_use]
+    pub const fn compress(mut self, level: i32) -> EncryptOptions<'a> {
+        self.compression = Some(level);
+        self.seekable = false;
+        self
+    }
+
+    /// Encrypts the message in independently sealed chunks, so receivers can decrypt arbitrary byte
+    /// ranges of it with [`seekable::open`](crate::seekable::open) without reading the entire
+    /// message. Seekable messages can also be decrypted as usual.
+    ///
+    /// Seekable messages are not compressed, so this clears any compression level set with
+    /// `EncryptOptions::compress`.
+    #[must_use]
+    pub const fn seekable(mut self) -> EncryptOptions<'a> {
+        self.seekable = true;
+        #[cfg(feature = "zstd")]
+        {
+            self.compression = None;
+        }
+        self
+    }
+
+    /// Precedes the headers with an index, so receivers of messages with thousands of headers can
+    /// find their header without trying to decrypt every header in the message.
+    ///
+    /// The index holds a short hint for each header, derived from the message's nonce and the
+    /// shared secret of the sender and the header's receiver, and the headers are ordered by their
+    /// hints. A receiver derives its hint and tries to decrypt only the headers with that hint,
+    /// instead of trying every header. Indexed messages can be decrypted as usual, and the hints
+    /// don't link a receiver's headers across messages.
+    ///
+    /// The index is a metadata tradeoff: anyone who knows the sender's public key and the
+    /// message's associated data can tell that the message is indexed and learn how many headers
+    /// it has, including fake receivers and decoys. Unindexed messages hide where their headers
+    /// end and their padding begins. Any header order set with [`EncryptOptions::shuffle`] or
+    /// [`EncryptOptions::slots`] is replaced by the order of the hints.
+    ///
+    /// Indexed messages can't be decrypted with a passphrase, so this clears any passphrase set
+    /// with [`EncryptOptions::passphrase`].
+    #[must_use]
+    pub const fn indexed(mut self) -> EncryptOptions<'a> {
+        self.layout = Layout::Indexed;
+        self
+    }
+
+    /// Precedes the headers with a header which can be decrypted with the given passphrase, so
+    /// the message can be decrypted with [`passphrase::decrypt_message`](crate::passphrase::decrypt_message)
+    /// by people without key pairs as well as by the receivers.
+    ///
+    /// The passphrase is stretched as [`passphrase::encrypt`](crate::passphrase::encrypt) does,
+    /// using `2^time_cost` iterations over `2^memory_cost` KiB of memory, split across
+    /// `parallelism` threads.
+    ///
+    /// The passphrase header is not signed by the sender, so anyone who knows the passphrase can
+    /// forge a message from the sender to everyone else who knows it. Like an index, the passphrase
+    /// header begins with a tag which anyone who knows the sender's public key and the message's
+    /// associated data can use to tell that the message has a passphrase. Messages with a
+    /// passphrase can't be indexed, so this clears [`EncryptOptions::indexed`].
+    #[must_use]
+    pub const fn passphrase(
+        mut self,
+        passphrase: &'a [u8],
+        time_cost: u8,
+        memory_cost: u8,
+        parallelism: u8,
+    ) -> EncryptOptions<'a> {
+        self.layout = Layout::Passphrase { passphrase, time_cost, memory_cost, parallelism };
+        self
+    }
+
+    /// Randomly interleaves the real and fake headers using the given RNG instead of the encryption
+    /// RNG.
+    #[must_use]
+    pub fn shuffle(mut self, rng: impl Rng) -> EncryptOptions<'a> {
+        self.slots = Some(self.shuffled_slots(rng));
+        self
+    }
+
+    /// Sets the order of the headers explicitly. The number of fake receivers is the number of
+    /// [`HeaderSlot::Fake`] slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless each receiver and decoy appears in exactly one slot.
+    #[must_use]
+    pub fn slots(mut self, slots: Vec<HeaderSlot>) -> EncryptOptions<'a> {
+        let mut seen = vec![false; self.receivers.len()];
+        let mut seen_decoys = vec![false; self.decoys.len()];
+        for slot in &slots {
+            match *slot {
+                HeaderSlot::Receiver(idx) => {
+                    assert!(idx < seen.len(), "receiver index {idx} should be in bounds");
+                    assert!(!seen[idx], "receiver {idx} should appear only once");
+                    seen[idx] = true;
+                }
+                HeaderSlot::Decoy(idx) => {
+                    assert!(idx < seen_decoys.len(), "decoy index {idx} should be in bounds");
+                    assert!(!seen_decoys[idx], "decoy {idx} should appear only once");
+                    seen_decoys[idx] = true;
+                }
+                HeaderSlot::Fake => {}
+            }
+        }
+        assert!(seen.iter().all(|&s| s), "each receiver should appear in a slot");
+        assert!(seen_decoys.iter().all(|&s| s), "each decoy should appear in a slot");
+
+        self.fakes = slots.len() - self.receivers.len() - self.decoys.len();
+        self.slots = Some(slots);
+        self
+    }
+
+    /// Returns the order of the headers, if set with [`EncryptOptions::shuffle`] or
+    /// [`EncryptOptions::slots`].
+    #[must_use]
+    pub fn header_slots(&self) -> Option<&[HeaderSlot]> {
+        self.slots.as_deref()
+    }
+
+    /// Returns the version of the message format to encrypt the message with.
+    pub(crate) const fn format_version(&self) -> u16 {
+        #[cfg(feature = "zstd")]
+        if self.compression.is_some() {
+            return mres::ZSTD_FORMAT_VERSION;
+        }
+
+        if self.seekable {
+            mres::SEEKABLE_FORMAT_VERSION
+        } else {
+            mres::FORMAT_VERSION
+        }
+    }
+
+    /// Returns the associated data to bind the message to, derived from the channel ID and sequence
+    /// number if they were set.
+    pub(crate) fn resolve_associated_data(&self) -> Cow<'a, [u8]> {
+        match self.sequence {
+            Some((channel, sequence)) => Cow::Owned(sequence_associated_data(channel, sequence)),
+            None => Cow::Borrowed(self.associated_data),
+        }
+    }
+
+    /// Returns an error if a message encrypted with these options would have more than
+    /// [`mres::MAX_RECEIVERS`] headers or would be padded for a plaintext longer than
+    /// [`mres::MAX_MESSAGE_LEN`]. This is checked before any headers are laid out.
+    pub(crate) fn check_limits(&self) -> Result<(), EncryptError> {
+        self.receivers
+            .len()
+            .checked_add(self.decoys.len())
+            .and_then(|n| n.checked_add(self.fakes))
+            .filter(|&n| n <= mres::MAX_RECEIVERS)
+            .ok_or(EncryptError::TooManyReceivers)?;
+        match self.padding {
+            Padding::Automatic { plaintext_len } if plaintext_len > mres::MAX_MESSAGE_LEN => {
+                Err(EncryptError::MessageTooLong)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the order of the headers, randomly interleaving the real and fake headers with the
+    /// given RNG if no order has been set.
+    pub(crate) fn resolve_slots(&self, rng: impl Rng) -> Vec<HeaderSlot> {
+        self.slots.clone().unwrap_or_else(|| self.shuffled_slots(rng))
+    }
+
+    /// Returns the number of bytes of padding to add to the message.
+    pub(crate) fn resolve_padding(&self) -> usize {
+        match self.padding {
+            Padding::Fixed(padding) => padding,
+            Padding::Automatic { plaintext_len } => self.automatic_padding(plaintext_len),
+        }
+    }
+
+    /// Returns the number of bytes of padding to add to a message with the given compressed
+    /// plaintext length.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn resolve_compressed_padding(&self, compressed_len: u64) -> usize {
+        match self.padding {
+            Padding::Fixed(padding) => padding,
+            Padding::Automatic { .. } => self.automatic_padding(compressed_len),
+        }
+    }
+
+    fn automatic_padding(&self, plaintext_len: u64) -> usize {
+        let mut header_count = self.receivers.len() + self.decoys.len() + self.fakes;
+        header_count += self.layout.leading_slots(header_count);
+        let len = mres::ciphertext_len(self.format_version(), header_count, 0, plaintext_len);
+        // On 32-bit targets, the padding for very long plaintexts may not fit in a usize.
+        usize::try_from(padme::padding_for(len)).unwrap_or(usize::MAX)
+    }
+
+    fn shuffled_slots(&self, mut rng: impl Rng) -> Vec<HeaderSlot> {
+        let mut slots = (0..self.receivers.len())
+            .map(HeaderSlot::Receiver)
+            .chain((0..self.decoys.len()).map(HeaderSlot::Decoy))
+            .chain((0..self.fakes).map(|_| HeaderSlot::Fake))
+            .collect::<Vec<_>>();
+        slots.shuffle(&mut rng);
+        slots
+    }
+}
+
+/// Derives the associated data for a message with the given channel ID and sequence number.
+pub(crate) fn sequence_associated_data(channel: &[u8], sequence: u64) -> Vec<u8> {
+    let mut ad = Protocol::new("veil.sequence");
+    ad.mix("channel", channel);
+    ad.mix("sequence", &sequence.to_le_bytes());
+    ad.derive_array::<32>("associated-data").to_vec()
+}
+
+/// Options for decrypting a message, with a builder-style API.
+///
+/// By default, the message has no associated data and there are no limits on the lengths of its
+/// headers or plaintext. When decrypting untrusted messages, e.g. uploads read from a network
+/// stream, the limits bound how much of the message is read before it can be rejected and how much
+/// plaintext is written.
+///
+/// ```
+/// use veil::{DecryptError, DecryptOptions, EncryptOptions, PrivateKey};
+///
+/// let alice = PrivateKey::random(rand::thread_rng());
+/// let bea = PrivateKey::random(rand::thread_rng());
+/// let receivers = [bea.public_key()];
+/// let mut ciphertext = Vec::new();
+/// let options = EncryptOptions::new(&receivers).fakes(10);
+/// alice
+///     .encrypt(rand::thread_rng(), &b"a message"[..], &mut ciphertext, &options)
+///     .expect("should encrypt");
+///
+/// let options = DecryptOptions::new().max_header_len(4096).max_plaintext_len(4);
+/// let mut plaintext = Vec::new();
+/// let res = bea.decrypt_with_options(&ciphertext[..], &mut plaintext, &alice.public_key(), &options);
+/// assert!(matches!(res, Err(DecryptError::PlaintextTooLong)));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DecryptOptions<'a> {
+    pub(crate) associated_data: &'a [u8],
+    max_header_len: Option<u64>,
+    max_plaintext_len: Option<u64>,
+}
+
+impl<'a> DecryptOptions<'a> {
+    /// Creates options for decrypting a message with no associated data and no limits.
+    #[must_use]
+    pub const fn new() -> DecryptOptions<'a> {
+        DecryptOptions { associated_data: &[], max_header_len: None, max_plaintext_len: None }
+    }
+
+    /// Decrypts a message which was encrypted with the given associated data.
+    #[must_use]
+    pub const fn associated_data(mut self, associated_data: &'a [u8]) -> DecryptOptions<'a> {
+        self.associated_data = associated_data;
+        self
+    }
+
+    /// Reads at most the given number of bytes of the message's nonce, headers, and padding, as
+    /// counted by [`Report::header_bytes`](crate::Report::header_bytes) and
+    /// [`Report::padding_bytes`](crate::Report::padding_bytes), returning
+    /// [`DecryptError::HeadersTooLong`](crate::DecryptError::HeadersTooLong) instead of scanning
+    /// any more headers or reading any padding past the limit.
+    ///
+    /// Every header costs a key agreement per receiver to scan, so this bounds the work a message
+    /// can cause before its payload is reached.
+    #[must_use]
+    pub const fn max_header_len(mut self, max_header_len: u64) -> DecryptOptions<'a> {
+        self.max_header_len = Some(max_header_len);
+        self
+    }
+
+    /// Writes at most the given number of bytes of plaintext, returning
+    /// [`DecryptError::PlaintextTooLong`](crate::DecryptError::PlaintextTooLong) instead of
+    /// writing any more. For a compressed message, this limits the decompressed plaintext.
+    #[must_use]
+    pub const fn max_plaintext_len(mut self, max_plaintext_len: u64) -> DecryptOptions<'a> {
+        self.max_plaintext_len = Some(max_plaintext_len);
+        self
+    }
+
+    /// Returns the limits to decrypt the message within.
+    pub(crate) fn limits(&self) -> mres::Limits {
+        mres::Limits {
+            header_len: self.max_header_len.unwrap_or(u64::MAX),
+            plaintext_len: self.max_plaintext_len.unwrap_or(u64::MAX),
+        }
+    }
+}
+
+/// Options for signing a message, with a builder-style API.
+///
+/// By default, the signature has no context, the message's length is unknown, no progress is
+/// reported, and signing can't be cancelled.
+///
+/// ```
+/// use std::sync::atomic::AtomicBool;
+///
+/// use veil::{PrivateKey, SignOptions};
+///
+/// let key = PrivateKey::random(rand::thread_rng());
+/// let message = b"a long message";
+/// let cancel = AtomicBool::new(false);
+/// let progress = |read: u64, total: Option<u64>| eprintln!("read {read} of {total:?} bytes");
+/// let options =
+///     SignOptions::new().len(message.len() as u64).progress(&progress).cancel(&cancel);
+/// let sig = key.sign_with(rand::thread_rng(), &message[..], &options).expect("should sign");
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct SignOptions<'a> {
+    pub(crate) context: &'a str,
+    len: Option<u64>,
+    progress: Option<&'a dyn Fn(u64, Option<u64>)>,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl<'a> SignOptions<'a> {
+    /// Creates options for signing a message of unknown length.
+    #[must_use]
+    pub const fn new() -> SignOptions<'a> {
+        SignOptions { context: "", len: None, progress: None, cancel: None }
+    }
+
+    /// Signs the message in the given context, which identifies the application or purpose of the
+    /// signature. A signature created in one context is only valid in that context, and must be
+    /// verified with [`PublicKey::verify_in_context`](crate::PublicKey::verify_in_context). An
+    /// empty context is the same as no context.
+    #[must_use]
+    pub const fn context(mut self, context: &'a str) -> SignOptions<'a> {
+        self.context = context;
+        self
+    }
+
+    /// Sets the total length of the message in bytes, which is passed to the progress callback.
+    #[must_use]
+    pub const fn len(mut self, len: u64) -> SignOptions<'a> {
+        self.len = Some(len);
+        self
+    }
+
+    /// Calls the given function with the number of bytes read so far and the total length, if
+    /// known, each time a block of the message is read.
+    #[must_use]
+    pub const fn progress(mut self, progress: &'a dyn Fn(u64, Option<u64>)) -> SignOptions<'a> {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Checks the given flag before each block of the message is read, stopping with
+    /// [`SignError::Cancelled`](crate::SignError::Cancelled) once it is set. The flag can be set
+    /// from another thread.
+    #[must_use]
+    pub const fn cancel(mut self, cancel: &'a AtomicBool) -> SignOptions<'a> {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Wraps the given reader so that it reports progress and checks for cancellation.
+    pub(crate) const fn reader<R>(&self, reader: R) -> ProgressReader<'a, R> {
+        ProgressReader { reader, options: *self, read: 0, cancelled: false }
+    }
+}
+
+impl Debug for SignOptions<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignOptions")
+            .field("context", &self.context)
+            .field("len", &self.len)
+            .field("cancel", &self.cancel)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A reader which reports its progress and checks for cancellation before each read.
+pub(crate) struct ProgressReader<'a, R> {
+    reader: R,
+    options: SignOptions<'a>,
+    read: u64,
+    pub(crate) cancelled: bool,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.options.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            self.cancelled = true;
+            return Err(io::Error::other("signing cancelled"));
+        }
+
+        let n = self.reader.read(buf)?;
+        if n > 0 {
+            self.read += u64::try_from(n).expect("usize should be <= u64");
+            if let Some(progress) = self.options.progress {
+                progress(self.read, self.options.len);
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// The length of a [`Digest`](crate::Digest).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DigestLength {
+    /// A 32-byte digest.
+    #[default]
+    Bytes32,
+
+    /// A 64-byte digest.
+    Bytes64,
+}
+
+impl DigestLength {
+    /// Returns the length in bytes.
+    #[must_use]
+    pub const fn byte_len(self) -> usize {
+        match self {
+            DigestLength::Bytes32 => 32,
+            DigestLength::Bytes64 => 64,
+        }
+    }
+}
+
+/// Options for digesting a message, with a builder-style API.
+///
+/// By default, a digest is unkeyed and 32 bytes long. A keyed digest is a message authentication
+/// code: without the key, it can't be calculated, so it can't be forged by whoever can modify both
+/// the message and its stored digest. The key can be any shared secret, or can be derived from a
+/// private key with [`PrivateKey::digest_key`](crate::PrivateKey::digest_key).
+///
+/// ```
+/// use veil::{Digest, DigestLength, DigestOptions, PrivateKey};
+///
+/// let key = PrivateKey::random(rand::thread_rng()).digest_key();
+/// let options = DigestOptions::new().key(&key).length(DigestLength::Bytes64);
+/// let digest = Digest::new_with(&["blob.bin"], &b"a stored blob"[..], &options)
+///     .expect("should digest");
+/// assert_eq!(64, digest.encode().len());
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct DigestOptions<'a> {
+    pub(crate) key: Option<&'a [u8]>,
+    pub(crate) length: DigestLength,
+}
+
+impl<'a> DigestOptions<'a> {
+    /// Creates options for an unkeyed, 32-byte digest.
+    #[must_use]
+    pub const fn new() -> DigestOptions<'a> {
+        DigestOptions { key: None, length: DigestLength::Bytes32 }
+    }
+
+    /// Keys the digest with the given secret, making it a message authentication code which can
+    /// only be calculated or checked with the same key.
+    #[must_use]
+    pub const fn key(mut self, key: &'a [u8]) -> DigestOptions<'a> {
+        self.key = Some(key);
+        self
+    }
+
+    /// Sets the length of the digest.
+    #[must_use]
+    pub const fn length(mut self, length: DigestLength) -> DigestOptions<'a> {
+        self.length = length;
+        self
+    }
+}
+
+impl Debug for DigestOptions<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DigestOptions")
+            .field("keyed", &self.key.is_some())
+            .field("length", &self.length)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{inspect::inspect, DecryptError, PrivateKey};
+
+    #[test]
+    fn shuffled_slots() {
+        let (mut rng, receivers) = setup();
+        let options = EncryptOptions::new(&receivers).fakes(10);
+
+        let slots = options.resolve_slots(&mut rng);
+        assert_eq!(13, slots.len());
+        assert_eq!(10, slots.iter().filter(|&&s| s == HeaderSlot::Fake).count());
+        for idx in 0..3 {
+            assert_eq!(1, slots.iter().filter(|&&s| s == HeaderSlot::Receiver(idx)).count());
+        }
+        assert_ne!(slots, options.resolve_slots(&mut rng), "slots should be random");
+    }
+
+    #[test]
+    fn shuffle_rng() {
+        let (_, receivers) = setup();
+
+        let a = EncryptOptions::new(&receivers).fakes(10).shuffle(ChaChaRng::seed_from_u64(1));
+        let b = EncryptOptions::new(&receivers).fakes(10).shuffle(ChaChaRng::seed_from_u64(1));
+        assert_eq!(a.header_slots(), b.header_slots());
+        assert_eq!(a.header_slots().map(<[_]>::to_vec), Some(a.resolve_slots(rand::thread_rng())));
+    }
+
+    #[test]
+    fn explicit_slots() {
+        let (_, receivers) = setup();
+
+        let slots = vec![
+            HeaderSlot::Fake,
+            HeaderSlot::Receiver(2),
+            HeaderSlot::Receiver(0),
+            HeaderSlot::Fake,
+            HeaderSlot::Receiver(1),
+        ];
+        let options = EncryptOptions::new(&receivers).slots(slots.clone());
+        assert_eq!(2, options.fakes);
+        assert_eq!(Some(slots.as_slice()), options.header_slots());
+    }
+
+    #[test]
+    fn encrypt_with_slots() {
+        let (mut rng, _) = setup();
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let receivers = [receiver.public_key()];
+
+        let options = EncryptOptions::new(&receivers).slots(vec![
+            HeaderSlot::Fake,
+            HeaderSlot::Fake,
+            HeaderSlot::Receiver(0),
+            HeaderSlot::Fake,
+        ]);
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(&mut rng, &b"a message"[..], &mut ciphertext, &options)
+            .expect("should encrypt");
+
+        let inspection = inspect(&receiver, ciphertext.as_slice(), &sender.public_key(), &[])
+            .expect("should inspect");
+        assert_eq!(2, inspection.header_index, "invalid header index");
+        assert_eq!(4, inspection.header_count, "invalid header count");
+    }
+
+    #[test]
+    fn encrypt_with_decoys() {
+        let (mut rng, _) = setup();
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let decoy = PrivateKey::random(&mut rng);
+        let receivers = [receiver.public_key()];
+        let decoys = [decoy.public_key(), PublicKey::random(&mut rng)];
+
+        let options = EncryptOptions::new(&receivers).decoys(&decoys).fakes(2);
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(&mut rng, &b"a message"[..], &mut ciphertext, &options)
+            .expect("should encrypt");
+
+        let inspection = inspect(&receiver, ciphertext.as_slice(), &sender.public_key(), &[])
+            .expect("should inspect");
+        assert_eq!(5, inspection.header_count, "invalid header count");
+
+        assert_matches!(
+            decoy.decrypt(ciphertext.as_slice(), io::sink(), &sender.public_key()),
+            Err(DecryptError::NotAReceiver),
+            "decoys should not be able to decrypt"
+        );
+    }
+
+    #[test]
+    fn decoy_slots() {
+        let (mut rng, receivers) = setup();
+        let decoys = (0..3).map(|_| PublicKey::random(&mut rng)).collect::<Vec<_>>();
+
+        let slots =
+            EncryptOptions::new(&receivers).decoys(&decoys).fakes(4).resolve_slots(&mut rng);
+        assert_eq!(10, slots.len());
+        for idx in 0..3 {
+            assert_eq!(1, slots.iter().filter(|&&s| s == HeaderSlot::Decoy(idx)).count());
+        }
+
+        let options = EncryptOptions::new(&receivers[..1]).decoys(&decoys[..1]).slots(vec![
+            HeaderSlot::Decoy(0),
+            HeaderSlot::Fake,
+            HeaderSlot::Receiver(0),
+        ]);
+        assert_eq!(1, options.fakes);
+    }
+
+    #[test]
+    fn automatic_padding() {
+        let (mut rng, receivers) = setup();
+        let sender = PrivateKey::random(&mut rng);
+
+        for len in [0, 1_000, 200_000] {
+            let plaintext = vec![0u8; len];
+            let options = EncryptOptions::new(&receivers)
+                .fakes(5)
+                .padding_policy(Padding::Automatic { plaintext_len: len as u64 });
+            let mut ciphertext = Vec::new();
+            sender
+                .encrypt(&mut rng, plaintext.as_slice(), &mut ciphertext, &options)
+                .expect("should encrypt");
+
+            let ctx_len = ciphertext.len() as u64;
+            assert_eq!(padme::padded_len(ctx_len), ctx_len, "should be a PADMÉ length");
+            assert!(options.resolve_padding() > 0, "should be padded");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "each decoy should appear in a slot")]
+    fn missing_decoy_slot() {
+        let (_, receivers) = setup();
+        let _ = EncryptOptions::new(&receivers[..1])
+            .decoys(&receivers[1..])
+            .slots(vec![HeaderSlot::Receiver(0), HeaderSlot::Decoy(1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "each receiver should appear in a slot")]
+    fn missing_receiver_slot() {
+        let (_, receivers) = setup();
+        let _ = EncryptOptions::new(&receivers).slots(vec![
+            HeaderSlot::Receiver(0),
+            HeaderSlot::Receiver(1),
+            HeaderSlot::Fake,
+        ]);
+    }
+
+    fn setup() -> (ChaChaRng, Vec<PublicKey>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let receivers = (0..3).map(|_| PrivateKey::random(&mut rng).public_key()).collect();
+        (rng, receivers)
+    }
+}
@@ -0,0 +1,81 @@
+//! The PADMÉ padding scheme for hiding message lengths.
+//!
+//! Adding a fixed or random amount of padding to a message does little to hide its length, as the
+//! padding can be averaged out or the original length bounded. PADMÉ instead rounds lengths up to a
+//! set of values which grows sparser as lengths grow, so each padded length is shared by a range of
+//! original lengths while the overhead is never more than about 12% (and much less for larger
+//! messages). A length of `L` bytes leaks only `O(log log L)` bits.
+//!
+//! See [_Reducing Metadata Leakage from Encrypted Files and Communication with
+//! PURBs_](https://arxiv.org/pdf/1806.03160).
+
+/// Returns the PADMÉ length of a message of `len` bytes, the smallest padded length which is at
+/// least `len`.
+///
+/// ```
+/// use veil::padme;
+///
+/// assert_eq!(1_024, padme::padded_len(1_000));
+/// assert_eq!(1_015_808, padme::padded_len(1_000_000));
+/// ```
+#[must_use]
+pub const fn padded_len(len: u64) -> u64 {
+    if len < 2 {
+        return len;
+    }
+
+    // Keep the S most significant bits of the length's exponent E and round up the remaining E - S
+    // bits of the length, where S is the number of bits needed to represent E.
+    let e = len.ilog2();
+    let s = e.ilog2() + 1;
+    let mask = (1u64 << (e - s)) - 1;
+    match len.checked_add(mask) {
+        Some(n) => n & !mask,
+        None => len,
+    }
+}
+
+/// Returns the number of bytes of padding to add to a message of `len` bytes to pad it to its
+/// PADMÉ length.
+#[must_use]
+pub const fn padding_for(len: u64) -> u64 {
+    padded_len(len) - len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_lengths() {
+        for len in 0..=8 {
+            assert_eq!(len, padded_len(len), "lengths under 9 bytes should not be padded");
+        }
+        assert_eq!(10, padded_len(9));
+        assert_eq!(12, padded_len(11));
+    }
+
+    #[test]
+    fn padded_lengths() {
+        for (len, padded) in [
+            (100, 104),
+            (1_000, 1_024),
+            (65_537, 67_584),
+            (1_000_000, 1_015_808),
+            (u64::MAX, u64::MAX),
+        ] {
+            assert_eq!(padded, padded_len(len), "invalid padded length for {len}");
+        }
+    }
+
+    #[test]
+    fn overhead() {
+        for len in (2..1_000_000).step_by(997) {
+            let padded = padded_len(len);
+            assert!(padded >= len, "padded length should be at least {len}");
+            assert!(padded - len <= len / 8 + 1, "too much padding for {len}");
+            assert_eq!(padded, padded_len(padded), "padded lengths should be fixed points");
+            assert_eq!(padded - len, padding_for(len));
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Mnemonic encoding of private key secrets for paper backups.
+
+use lockstitch::Protocol;
+
+use crate::{ct, keys::SECRET_LEN, ParsePaperKeyError};
+
+/// The number of words in an encoded paper key.
+pub const WORD_COUNT: usize = (SECRET_LEN + CHECKSUM_LEN) * 8 / BITS_PER_WORD;
+
+/// Encode the given secret as a sequence of words from the BIP39 English word list.
+///
+/// The secret is followed by a 16-bit checksum, giving 528 bits which are split into 48 11-bit
+/// indexes into the word list.
+#[must_use]
+pub fn encode(secret: &[u8; SECRET_LEN]) -> [&'static str; WORD_COUNT] {
+    // Append the checksum to the secret.
+    let mut data = [0u8; SECRET_LEN + CHECKSUM_LEN];
+    let (data_secret, data_checksum) = data.split_at_mut(SECRET_LEN);
+    data_secret.copy_from_slice(secret);
+    data_checksum.copy_from_slice(&checksum(secret));
+
+    // Map each 11-bit group to a word.
+    let mut out = [""; WORD_COUNT];
+    for (word, w) in out.iter_mut().zip(words(&data)) {
+        *word = w;
+    }
+    out
+}
+
+/// Map each 11-bit group of the given data, most significant bit first, to a word from the BIP39
+/// English word list. A final partial group is padded with zero bits.
+pub fn words(data: &[u8]) -> impl Iterator<Item = &'static str> + '_ {
+    let words = bip39::Language::English.word_list();
+    let bits = data.len() * 8;
+    (0..bits.div_ceil(BITS_PER_WORD)).map(move |i| {
+        let idx = (i * BITS_PER_WORD..(i + 1) * BITS_PER_WORD).fold(0usize, |idx, bit| {
+            let b = data.get(bit / 8).map_or(0, |b| (b >> (7 - bit % 8)) & 1);
+            (idx << 1) | usize::from(b)
+        });
+        words[idx]
+    })
+}
+
+/// Decode the given sequence of words into a secret, verifying its checksum.
+///
+/// Words are matched case-insensitively.
+pub fn decode<'a>(
+    words: impl IntoIterator<Item = &'a str>,
+) -> Result<[u8; SECRET_LEN], ParsePaperKeyError> {
+    let mut data = [0u8; SECRET_LEN + CHECKSUM_LEN];
+    let mut n = 0;
+
+    for (i, word) in words.into_iter().enumerate() {
+        if i >= WORD_COUNT {
+            return Err(ParsePaperKeyError::InvalidLength);
+        }
+
+        // Map the word back to its 11-bit index.
+        let idx = bip39::Language::English
+            .find_word(&word.to_ascii_lowercase())
+            .ok_or(ParsePaperKeyError::InvalidWord(i))?;
+
+        // Write the index's bits, most significant bit first.
+        for (j, bit) in (i * BITS_PER_WORD..(i + 1) * BITS_PER_WORD).enumerate() {
+            if (idx >> (BITS_PER_WORD - 1 - j)) & 1 == 1 {
+                data[bit / 8] |= 1 << (7 - bit % 8);
+            }
+        }
+        n += 1;
+    }
+
+    if n != WORD_COUNT {
+        return Err(ParsePaperKeyError::InvalidLength);
+    }
+
+    // Split the data into the secret and checksum and check the checksum.
+    let (secret, data_checksum) = data.split_at(SECRET_LEN);
+    let secret = secret.try_into().expect("should be secret-sized");
+    ct::eq(data_checksum, &checksum(&secret))
+        .then_some(secret)
+        .ok_or(ParsePaperKeyError::InvalidChecksum)
+}
+
+fn checksum(secret: &[u8; SECRET_LEN]) -> [u8; CHECKSUM_LEN] {
+    let mut paper = Protocol::new("veil.paper-key");
+    paper.mix("secret", secret);
+    paper.derive_array("checksum")
+}
+
+const CHECKSUM_LEN: usize = 2;
+const BITS_PER_WORD: usize = 11;
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (_, secret, words) = setup();
+        assert_eq!(Ok(secret), decode(words), "invalid secret");
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let (_, secret, words) = setup();
+        let upper = words.map(str::to_ascii_uppercase);
+        assert_eq!(Ok(secret), decode(upper.iter().map(String::as_str)), "invalid secret");
+    }
+
+    #[test]
+    fn invalid_word() {
+        let (_, _, mut words) = setup();
+        words[7] = "veil";
+        assert_eq!(Err(ParsePaperKeyError::InvalidWord(7)), decode(words));
+    }
+
+    #[test]
+    fn swapped_words() {
+        let (_, _, mut words) = setup();
+        words.swap(3, 4);
+        assert_eq!(Err(ParsePaperKeyError::InvalidChecksum), decode(words));
+    }
+
+    #[test]
+    fn too_few_words() {
+        let (_, _, words) = setup();
+        assert_eq!(Err(ParsePaperKeyError::InvalidLength), decode(words[1..].iter().copied()));
+    }
+
+    #[test]
+    fn too_many_words() {
+        let (_, _, words) = setup();
+        assert_eq!(
+            Err(ParsePaperKeyError::InvalidLength),
+            decode(words.iter().copied().chain(Some("abandon")))
+        );
+    }
+
+    fn setup() -> (ChaChaRng, [u8; SECRET_LEN], [&'static str; WORD_COUNT]) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let secret = rng.gen::<[u8; SECRET_LEN]>();
+        let words = encode(&secret);
+        (rng, secret, words)
+    }
+}
@@ -0,0 +1,314 @@
+//! Symmetric encryption of streams with a passphrase.
+//!
+//! Unlike messages encrypted with [`PrivateKey::encrypt`](crate::PrivateKey::encrypt), which
+//! require key pairs, these ciphertexts can be decrypted by anyone who knows the passphrase. The
+//! passphrase is stretched with the same memory-hard function used to protect private keys, and the
+//! plaintext is sealed in blocks so it can be streamed.
+//!
+//! Messages encrypted for receivers can also be encrypted for a passphrase with
+//! [`EncryptOptions::passphrase`](crate::EncryptOptions::passphrase), so that people with and without
+//! key pairs can decrypt the same message. Those messages are decrypted with [`decrypt_message`].
+
+use std::io::{self, Read, Write};
+
+use lockstitch::TAG_LEN;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    blockio::ReadBlock, keys::PrivKey, mres, pbenc, DecryptError, EncryptError, PublicKey,
+};
+
+/// The length of plaintext blocks which are encrypted.
+const BLOCK_LEN: usize = 64 * 1024;
+
+/// The length of an encrypted block and authentication tag.
+const ENC_BLOCK_LEN: usize = BLOCK_LEN + TAG_LEN;
+
+/// Encrypts the contents of `reader` with the given passphrase and writes the ciphertext to
+/// `writer`. Returns the number of bytes of ciphertext written.
+///
+/// The passphrase is stretched using `2^time_cost` iterations over `2^memory_cost` KiB of memory,
+/// split across `parallelism` threads. A parallelism of `0` is treated as `1`.
+///
+/// # Errors
+///
+/// If there is an error while reading from `reader`, returns [`EncryptError::ReadIo`]. If there is
+/// an error while writing to `writer`, returns [`EncryptError::WriteIo`].
+pub fn encrypt(
+    rng: impl Rng + CryptoRng,
+    passphrase: &[u8],
+    time_cost: u8,
+    memory_cost: u8,
+    parallelism: u8,
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> Result<u64, EncryptError> {
+    // Encode the parameters and salt, stretch the passphrase, and write the header.
+    let mut header = [0u8; pbenc::HEADER_LEN];
+    let mut pbenc =
+        pbenc::encrypt_header(rng, passphrase, time_cost, memory_cost, parallelism, &mut header);
+    writer.write_all(&header).map_err(EncryptError::WriteIo)?;
+    let mut written = u64::try_from(header.len()).expect("usize should be <= u64");
+
+    let mut buf = vec![0u8; ENC_BLOCK_LEN];
+    loop {
+        // Read a block of data.
+        let n = reader.read_block(&mut buf[..BLOCK_LEN]).map_err(EncryptError::ReadIo)?;
+        let block = &mut buf[..n + TAG_LEN];
+
+        // Seal the block and write it.
+        pbenc.seal("block", block);
+        writer.write_all(block).map_err(EncryptError::WriteIo)?;
+        written += u64::try_from(block.len()).expect("usize should be <= u64");
+
+        // If the block was undersized, we're at the end of the reader.
+        if n < BLOCK_LEN {
+            break;
+        }
+    }
+
+    // Return the number of ciphertext bytes written.
+    Ok(written)
+}
+
+/// Decrypts the contents of `reader` with the given passphrase and writes the plaintext to
+/// `writer`. Returns the number of bytes of plaintext written.
+///
+/// The plaintext is written as each block is authenticated. If an error is returned, the contents of
+/// `writer` must not be trusted.
+///
+/// # Errors
+///
+/// If the passphrase is incorrect, the ciphertext has been modified or truncated, or there is data
+/// after the ciphertext's final block, returns [`DecryptError::InvalidCiphertext`]. If there is an
+/// error while reading from `reader`, returns [`DecryptError::ReadIo`]. If there is an error while
+/// writing to `writer`, returns [`DecryptError::WriteIo`].
+pub fn decrypt(
+    passphrase: &[u8],
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> Result<u64, DecryptError> {
+    // Read the header and stretch the passphrase.
+    let mut header = [0u8; pbenc::HEADER_LEN];
+    reader.read_exact(&mut header).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            DecryptError::InvalidCiphertext
+        } else {
+            DecryptError::ReadIo(e)
+        }
+    })?;
+    let mut pbenc =
+        pbenc::decrypt_header(passphrase, &header).ok_or(DecryptError::InvalidCiphertext)?;
+
+    let mut buf = vec![0u8; ENC_BLOCK_LEN];
+    let mut written = 0;
+    loop {
+        // Read a block of ciphertext. The final block is always undersized but includes a tag, so a
+        // shorter read means the ciphertext was truncated.
+        let n = reader.read_block(&mut buf).map_err(DecryptError::ReadIo)?;
+        if n < TAG_LEN {
+            return Err(DecryptError::InvalidCiphertext);
+        }
+
+        // Open the block and write the plaintext. If the block cannot be decrypted, return an
+        // error.
+        let plaintext =
+            pbenc.open("block", &mut buf[..n]).ok_or(DecryptError::InvalidCiphertext)?;
+
+        // If the block was undersized, it's the final block, and nothing may follow it.
+        let last = n < ENC_BLOCK_LEN;
+        if last && reader.read_block(&mut [0u8; 1]).map_err(DecryptError::ReadIo)? != 0 {
+            return Err(DecryptError::InvalidCiphertext);
+        }
+
+        writer.write_all(plaintext).map_err(DecryptError::WriteIo)?;
+        written += u64::try_from(plaintext.len()).expect("usize should be <= u64");
+        if last {
+            break;
+        }
+    }
+
+    // Return the number of plaintext bytes written.
+    Ok(written)
+}
+
+/// Decrypts a message encrypted by `sender` with
+/// [`EncryptOptions::passphrase`](crate::EncryptOptions::passphrase) and the given associated data,
+/// using the given passphrase, and writes the plaintext to `writer`. Returns the number of bytes of
+/// plaintext written.
+///
+/// The message's passphrase header isn't signed by the sender, so a message which decrypts may have
+/// been forged by anyone else who knows the passphrase.
+///
+/// # Errors
+///
+/// If the message wasn't encrypted with the passphrase, was not sent by the sender, or was encrypted
+/// with different associated data, returns [`DecryptError::NotAReceiver`]. If it has been modified,
+/// returns [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`].
+/// If there was an error reading from `reader` or writing to `writer`, returns
+/// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+pub fn decrypt_message(
+    passphrase: &[u8],
+    reader: impl Read,
+    writer: impl Write,
+    sender: &PublicKey,
+    associated_data: &[u8],
+) -> Result<u64, DecryptError> {
+    mres::decrypt_with_passphrase::<PrivKey>(
+        reader,
+        writer,
+        &[],
+        Some(passphrase),
+        &sender.0,
+        associated_data,
+        mres::Limits::NONE,
+    )
+    .map(|(_, report)| report.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{EncryptOptions, PrivateKey};
+
+    #[test]
+    fn round_trip() {
+        let (plaintext, ciphertext) = setup(200_000);
+
+        let mut decrypted = Vec::new();
+        let len =
+            decrypt(b"excelsior", ciphertext.as_slice(), &mut decrypted).expect("should decrypt");
+        assert_eq!(plaintext.len() as u64, len, "returned/observed plaintext length mismatch");
+        assert_eq!(plaintext, decrypted, "invalid plaintext");
+    }
+
+    #[test]
+    fn block_sized_round_trip() {
+        let (plaintext, ciphertext) = setup(BLOCK_LEN * 2);
+
+        let mut decrypted = Vec::new();
+        decrypt(b"excelsior", ciphertext.as_slice(), &mut decrypted).expect("should decrypt");
+        assert_eq!(plaintext, decrypted, "invalid plaintext");
+    }
+
+    #[test]
+    fn empty_round_trip() {
+        let (plaintext, ciphertext) = setup(0);
+        assert_eq!(pbenc::HEADER_LEN + TAG_LEN, ciphertext.len(), "invalid ciphertext length");
+
+        let mut decrypted = Vec::new();
+        decrypt(b"excelsior", ciphertext.as_slice(), &mut decrypted).expect("should decrypt");
+        assert_eq!(plaintext, decrypted, "invalid plaintext");
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let (_, ciphertext) = setup(200_000);
+
+        assert_matches!(
+            decrypt(b"dingus", ciphertext.as_slice(), io::sink()),
+            Err(DecryptError::InvalidCiphertext)
+        );
+    }
+
+    #[test]
+    fn modified_ciphertext() {
+        let (_, mut ciphertext) = setup(200_000);
+        ciphertext[ENC_BLOCK_LEN + 100] ^= 1;
+
+        assert_matches!(
+            decrypt(b"excelsior", ciphertext.as_slice(), io::sink()),
+            Err(DecryptError::InvalidCiphertext)
+        );
+    }
+
+    #[test]
+    fn truncated_ciphertext() {
+        let (_, ciphertext) = setup(BLOCK_LEN * 2);
+
+        for len in [0, pbenc::HEADER_LEN, pbenc::HEADER_LEN + ENC_BLOCK_LEN, ciphertext.len() - 1] {
+            assert_matches!(
+                decrypt(b"excelsior", &ciphertext[..len], io::sink()),
+                Err(DecryptError::InvalidCiphertext),
+                "should not decrypt {len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn trailing_data() {
+        for len in [0, 200_000, BLOCK_LEN * 2] {
+            let (_, mut ciphertext) = setup(len);
+            ciphertext.push(0);
+
+            assert_matches!(
+                decrypt(b"excelsior", ciphertext.as_slice(), io::sink()),
+                Err(DecryptError::InvalidCiphertext),
+                "should not decrypt {len} bytes of plaintext with trailing data"
+            );
+        }
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let receivers = [receiver.public_key()];
+
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(
+                &mut rng,
+                &b"a message"[..],
+                &mut ciphertext,
+                &EncryptOptions::new(&receivers).fakes(3).passphrase(b"excelsior", 0, 0, 1),
+            )
+            .expect("should encrypt");
+
+        let mut decrypted = Vec::new();
+        decrypt_message(
+            b"excelsior",
+            ciphertext.as_slice(),
+            &mut decrypted,
+            &sender.public_key(),
+            &[],
+        )
+        .expect("should decrypt");
+        assert_eq!(b"a message".to_vec(), decrypted, "invalid plaintext");
+
+        let mut decrypted = Vec::new();
+        receiver
+            .decrypt(ciphertext.as_slice(), &mut decrypted, &sender.public_key())
+            .expect("should decrypt");
+        assert_eq!(b"a message".to_vec(), decrypted, "invalid plaintext");
+
+        assert_matches!(
+            decrypt_message(
+                b"dingus",
+                ciphertext.as_slice(),
+                io::sink(),
+                &sender.public_key(),
+                &[]
+            ),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    fn setup(len: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let mut plaintext = vec![0u8; len];
+        rng.fill_bytes(&mut plaintext);
+
+        let mut ciphertext = Vec::new();
+        let len = encrypt(&mut rng, b"excelsior", 0, 0, 1, plaintext.as_slice(), &mut ciphertext)
+            .expect("should encrypt");
+        assert_eq!(ciphertext.len() as u64, len, "returned/observed ciphertext length mismatch");
+
+        (plaintext, ciphertext)
+    }
+}
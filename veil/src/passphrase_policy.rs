@@ -0,0 +1,248 @@
+//! Checks for passphrases chosen when creating a private key.
+//!
+//! A typo in a passphrase chosen for a new private key produces a key which can never be unlocked,
+//! and a guessable passphrase makes the key's memory-hard encryption moot. [`confirm`] compares a
+//! passphrase with its confirmation after [NFC normalization](normalize), and [`estimate`] gives a
+//! rough, `zxcvbn`-style estimate of how hard a passphrase is to guess.
+//!
+//! ```
+//! use veil::passphrase_policy;
+//!
+//! assert!(passphrase_policy::confirm("cafe\u{301} au lait", "caf\u{e9} au lait"));
+//! assert!(!passphrase_policy::estimate("password1").is_acceptable());
+//! assert!(passphrase_policy::estimate("vat stubble orbit lanyard").is_acceptable());
+//! ```
+
+use std::fmt::{self, Display, Formatter};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::ct;
+
+/// The minimum score of an acceptable passphrase.
+pub const MIN_SCORE: u8 = 3;
+
+/// Passphrases which are among the most commonly used, in rough order of popularity.
+const COMMON: &[&str] = &[
+    "123456",
+    "password",
+    "123456789",
+    "12345678",
+    "qwerty",
+    "111111",
+    "abc123",
+    "password1",
+    "iloveyou",
+    "1q2w3e4r",
+    "000000",
+    "qwerty123",
+    "dragon",
+    "sunshine",
+    "princess",
+    "letmein",
+    "monkey",
+    "football",
+    "baseball",
+    "welcome",
+    "admin",
+    "master",
+    "shadow",
+    "superman",
+    "trustno1",
+    "passw0rd",
+    "starwars",
+    "whatever",
+    "freedom",
+    "secret",
+    "changeme",
+    "correcthorsebatterystaple",
+];
+
+/// Returns the NFC normalization of the given passphrase.
+///
+/// The same passphrase can be entered as different sequences of code points depending on the
+/// keyboard or input method used (e.g. `é` as one code point or as `e` and a combining accent).
+#[must_use]
+pub fn normalize(passphrase: &str) -> String {
+    passphrase.nfc().collect()
+}
+
+/// Returns `true` if the passphrase and its confirmation are the same after normalization.
+#[must_use]
+pub fn confirm(passphrase: &str, confirmation: &str) -> bool {
+    let (a, b) = (normalize(passphrase), normalize(confirmation));
+    ct::eq(a.as_bytes(), b.as_bytes())
+}
+
+/// Advice for making a weak passphrase harder to guess.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feedback {
+    /// The passphrase is one of the most commonly used passphrases.
+    Common,
+
+    /// Most of the passphrase is repeated characters or sequences like `abc` or `123`.
+    RepeatsAndSequences,
+
+    /// The passphrase is too short.
+    TooShort,
+}
+
+impl Display for Feedback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Feedback::Common => "this is a very commonly used passphrase",
+            Feedback::RepeatsAndSequences => "repeated characters and sequences are easy to guess",
+            Feedback::TooShort => "add another word or two",
+        })
+    }
+}
+
+/// An estimate of how hard a passphrase is to guess.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Strength {
+    bits: u32,
+    score: u8,
+    feedback: Option<Feedback>,
+}
+
+impl Strength {
+    /// Returns the estimated number of guesses needed to find the passphrase, as a power of two.
+    #[must_use]
+    pub const fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns a score from `0` (trivially guessable) to `4` (very hard to guess).
+    #[must_use]
+    pub const fn score(&self) -> u8 {
+        self.score
+    }
+
+    /// Returns advice for making the passphrase harder to guess, if it is not acceptable.
+    #[must_use]
+    pub const fn feedback(&self) -> Option<Feedback> {
+        self.feedback
+    }
+
+    /// Returns `true` if the passphrase's score is at least [`MIN_SCORE`].
+    #[must_use]
+    pub const fn is_acceptable(&self) -> bool {
+        self.score >= MIN_SCORE
+    }
+}
+
+impl Display for Strength {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "about 2^{} guesses (score {}/4)", self.bits, self.score)?;
+        if let Some(feedback) = self.feedback {
+            write!(f, ": {feedback}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Estimates how hard the given passphrase is to guess.
+///
+/// Common passphrases are estimated by their popularity. Otherwise, each character is estimated by
+/// the size of the character classes used in the passphrase, except for characters which repeat or
+/// continue a sequence from the previous character, which add a single bit. This is a rough lower
+/// bound for random strings of characters and an overestimate for passphrases made of dictionary
+/// words, so it should be used for feedback rather than as a security guarantee.
+#[must_use]
+pub fn estimate(passphrase: &str) -> Strength {
+    let passphrase = normalize(passphrase);
+
+    if let Some(rank) = COMMON.iter().position(|&p| p.eq_ignore_ascii_case(&passphrase)) {
+        let bits = usize::BITS - (rank + 1).leading_zeros();
+        return Strength { bits, score: 0, feedback: Some(Feedback::Common) };
+    }
+
+    // Calculate the size of the character classes used in the passphrase.
+    let charset = [
+        (passphrase.chars().any(|c| c.is_ascii_lowercase()), 26),
+        (passphrase.chars().any(|c| c.is_ascii_uppercase()), 26),
+        (passphrase.chars().any(|c| c.is_ascii_digit()), 10),
+        (passphrase.chars().any(|c| c.is_ascii_punctuation() || c == ' '), 33),
+        (!passphrase.is_ascii(), 100),
+    ]
+    .into_iter()
+    .filter_map(|(used, size)| used.then_some(size))
+    .sum::<u32>();
+    let char_bits = f64::from(charset.max(1)).log2();
+
+    // Estimate each character, counting repeats and sequences as a single bit.
+    let (mut bits, mut patterns, mut len) = (0.0, 0usize, 0usize);
+    let mut prev = None;
+    for c in passphrase.chars() {
+        let c = u32::from(c);
+        if prev.is_some_and(|p: u32| c == p || c == p + 1 || c + 1 == p) {
+            bits += 1.0;
+            patterns += 1;
+        } else {
+            bits += char_bits;
+        }
+        prev = Some(c);
+        len += 1;
+    }
+    let bits = bits as u32;
+
+    let score = match bits {
+        0..=19 => 0,
+        20..=34 => 1,
+        35..=49 => 2,
+        50..=64 => 3,
+        _ => 4,
+    };
+    let feedback = (score < MIN_SCORE).then_some(if patterns * 2 > len {
+        Feedback::RepeatsAndSequences
+    } else {
+        Feedback::TooShort
+    });
+
+    Strength { bits, score, feedback }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization() {
+        assert_eq!("caf\u{e9}", normalize("cafe\u{301}"));
+        assert!(confirm("cafe\u{301}", "caf\u{e9}"));
+        assert!(!confirm("cafe", "caf\u{e9}"));
+        assert!(!confirm("excelsior", "excelsio"));
+    }
+
+    #[test]
+    fn common_passphrases() {
+        let strength = estimate("Password");
+        assert_eq!(0, strength.score());
+        assert_eq!(Some(Feedback::Common), strength.feedback());
+        assert!(!strength.is_acceptable());
+    }
+
+    #[test]
+    fn repeats_and_sequences() {
+        let strength = estimate("aaaaaaaaaaaaabcdefghijklmnop");
+        assert_eq!(Some(Feedback::RepeatsAndSequences), strength.feedback());
+        assert!(!strength.is_acceptable());
+    }
+
+    #[test]
+    fn short_passphrases() {
+        let strength = estimate("excelsior");
+        assert_eq!(2, strength.score());
+        assert_eq!(Some(Feedback::TooShort), strength.feedback());
+        assert!(!strength.is_acceptable());
+    }
+
+    #[test]
+    fn strong_passphrases() {
+        let strength = estimate("vat stubble orbit lanyard");
+        assert_eq!(4, strength.score());
+        assert_eq!(None, strength.feedback());
+        assert!(strength.is_acceptable());
+        assert_eq!("about 2^132 guesses (score 4/4)", strength.to_string());
+    }
+}
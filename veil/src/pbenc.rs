@@ -1,38 +1,59 @@
 //! Passphrase-based encryption based on Balloon Hashing.
 
-use std::mem;
+use alloc::{vec, vec::Vec};
+#[cfg(all(feature = "std", not(target_family = "wasm")))]
+use std::thread;
 
 use lockstitch::{Protocol, TAG_LEN};
 use rand::{CryptoRng, Rng};
 
+/// The length of the header which encodes the passphrase-based encryption parameters.
+pub const HEADER_LEN: usize = size_of::<u8>() + size_of::<u8>() + size_of::<u8>() + SALT_LEN;
+
+/// The first byte of an envelope encrypted with [`encrypt`]. Envelopes encrypted before lanes were
+/// supported have no tag and never begin with it in practice: as a time cost, it would require
+/// 2^186 iterations.
+pub const LANES_TAG: u8 = 0xBA;
+
 /// The number of bytes encryption adds to a plaintext.
-pub const OVERHEAD: usize = mem::size_of::<u8>() + mem::size_of::<u8>() + SALT_LEN + TAG_LEN;
+pub const OVERHEAD: usize = size_of::<u8>() + HEADER_LEN + TAG_LEN;
+
+/// The number of bytes encryption added to a plaintext before lanes were supported.
+pub const LEGACY_OVERHEAD: usize = size_of::<u8>() + size_of::<u8>() + SALT_LEN + TAG_LEN;
+
+/// The largest number of lanes a header may encode. Each lane is hashed on its own thread.
+pub const MAX_PARALLELISM: u8 = 64;
 
 /// Encrypt the given plaintext using the given passphrase.
+///
+/// The memory cost is split across `parallelism` lanes which are hashed concurrently. The
+/// parallelism is raised to `1` and lowered to [`MAX_PARALLELISM`] and to the number of blocks the
+/// memory cost allows.
 pub fn encrypt(
-    mut rng: impl Rng + CryptoRng,
+    rng: impl Rng + CryptoRng,
     passphrase: &[u8],
     time_cost: u8,
     memory_cost: u8,
+    parallelism: u8,
     plaintext: &[u8],
     ciphertext: &mut [u8],
 ) {
     debug_assert_eq!(ciphertext.len(), plaintext.len() + OVERHEAD);
 
     // Split up the output buffer.
-    let (t, m) = ciphertext.split_at_mut(mem::size_of::<u8>());
-    let (m, salt) = m.split_at_mut(mem::size_of::<u8>());
-    let (salt, ciphertext) = salt.split_at_mut(SALT_LEN);
-
-    // Encode the time and memory cost parameters.
-    t[0] = time_cost;
-    m[0] = memory_cost;
-
-    // Generate a random salt.
-    rng.fill_bytes(salt);
-
-    // Perform the balloon hashing.
-    let mut pbenc = init(passphrase, salt, time_cost, memory_cost);
+    let (tag, ciphertext) = ciphertext.split_at_mut(size_of::<u8>());
+    let (header, ciphertext) = ciphertext.split_at_mut(HEADER_LEN);
+
+    // Write the tag, then encode the parameters and perform the balloon hashing.
+    tag[0] = LANES_TAG;
+    let mut pbenc = encrypt_header(
+        rng,
+        passphrase,
+        time_cost,
+        memory_cost,
+        parallelism,
+        header.try_into().expect("should be header-sized"),
+    );
 
     // Encrypt the plaintext.
     ciphertext[..plaintext.len()].copy_from_slice(plaintext);
@@ -40,25 +61,155 @@ pub fn encrypt(
 }
 
 /// Decrypt the given ciphertext using the given passphrase.
+///
+/// Untagged ciphertexts are decrypted as they were before lanes were supported.
 #[must_use]
 pub fn decrypt<'a>(passphrase: &[u8], in_out: &'a mut [u8]) -> Option<&'a [u8]> {
+    if in_out.first() != Some(&LANES_TAG) {
+        return decrypt_legacy(passphrase, in_out);
+    }
+
     if in_out.len() < OVERHEAD {
         return None;
     }
 
     // Split up the input buffer.
-    let (t, m) = in_out.split_at_mut(mem::size_of::<u8>());
-    let (m, salt) = m.split_at_mut(mem::size_of::<u8>());
+    let (header, ciphertext) = in_out[size_of::<u8>()..].split_at_mut(HEADER_LEN);
+
+    // Decode the parameters and perform the balloon hashing.
+    let mut pbenc = decrypt_header(passphrase, (&*header).try_into().ok()?)?;
+
+    // Decrypt the ciphertext.
+    pbenc.open("secret", ciphertext)
+}
+
+/// Decrypt the given untagged ciphertext, encrypted before lanes were supported, using the given
+/// passphrase.
+fn decrypt_legacy<'a>(passphrase: &[u8], in_out: &'a mut [u8]) -> Option<&'a [u8]> {
+    if in_out.len() < LEGACY_OVERHEAD {
+        return None;
+    }
+
+    // Split up the input buffer.
+    let (t, m) = in_out.split_at_mut(size_of::<u8>());
+    let (m, salt) = m.split_at_mut(size_of::<u8>());
     let (salt, ciphertext) = salt.split_at_mut(SALT_LEN);
 
-    // Perform the balloon hashing.
-    let mut pbenc = init(passphrase, salt, t[0], m[0]);
+    // Reject costs which would overflow.
+    if t[0] > MAX_TIME_COST || m[0] > MAX_MEMORY_COST {
+        return None;
+    }
+
+    // Perform the balloon hashing with a single, unnumbered lane.
+    let mut pbenc = Protocol::new("veil.pbenc");
+    pbenc.mix("expanded-key", &hash_lane(passphrase, salt, t[0], 1usize << m[0], None));
 
     // Decrypt the ciphertext.
     pbenc.open("secret", ciphertext)
 }
 
-fn init(passphrase: &[u8], salt: &[u8], time_cost: u8, memory_cost: u8) -> Protocol {
+/// Encode the given parameters and a random salt into `header` and return a protocol keyed with the
+/// given passphrase.
+pub fn encrypt_header(
+    mut rng: impl Rng + CryptoRng,
+    passphrase: &[u8],
+    time_cost: u8,
+    memory_cost: u8,
+    parallelism: u8,
+    header: &mut [u8; HEADER_LEN],
+) -> Protocol {
+    // Split up the header.
+    let (t, m) = header.split_at_mut(size_of::<u8>());
+    let (m, p) = m.split_at_mut(size_of::<u8>());
+    let (p, salt) = p.split_at_mut(size_of::<u8>());
+
+    // Encode the time cost, memory cost, and parallelism parameters.
+    t[0] = time_cost;
+    m[0] = memory_cost;
+    p[0] = lanes(memory_cost, parallelism);
+
+    // Generate a random salt.
+    rng.fill_bytes(salt);
+
+    // Perform the balloon hashing.
+    init(passphrase, salt, time_cost, memory_cost, p[0])
+}
+
+/// Decode the parameters and salt in `header` and return a protocol keyed with the given
+/// passphrase, or `None` if the parameters are invalid.
+#[must_use]
+pub fn decrypt_header(passphrase: &[u8], header: &[u8; HEADER_LEN]) -> Option<Protocol> {
+    // Split up the header.
+    let (t, m) = header.split_at(size_of::<u8>());
+    let (m, p) = m.split_at(size_of::<u8>());
+    let (p, salt) = p.split_at(size_of::<u8>());
+
+    // Reject costs which would overflow and parallelism parameters which encryption wouldn't
+    // encode.
+    if t[0] > MAX_TIME_COST || m[0] > MAX_MEMORY_COST || p[0] != lanes(m[0], p[0]) {
+        return None;
+    }
+
+    // Perform the balloon hashing.
+    Some(init(passphrase, salt, t[0], m[0], p[0]))
+}
+
+/// Returns the number of lanes to hash for the given memory cost and parallelism: at least one, and
+/// no more than [`MAX_PARALLELISM`] or the number of blocks in the buffer.
+pub fn lanes(memory_cost: u8, parallelism: u8) -> u8 {
+    let blocks = u8::try_from(1u64.checked_shl(memory_cost.into()).unwrap_or(u64::MAX));
+    parallelism.clamp(1, MAX_PARALLELISM).min(blocks.unwrap_or(u8::MAX))
+}
+
+fn init(
+    passphrase: &[u8],
+    salt: &[u8],
+    time_cost: u8,
+    memory_cost: u8,
+    parallelism: u8,
+) -> Protocol {
+    trace_span!(DEBUG, "pbenc", time_cost, memory_cost, parallelism);
+
+    // Split the buffer evenly across lanes. There are never more lanes than blocks.
+    let lane_len = (1usize << memory_cost) / usize::from(parallelism);
+
+    // Hash each lane on its own thread, keeping the last block of each.
+    #[cfg(all(feature = "std", not(target_family = "wasm")))]
+    let blocks = thread::scope(|s| {
+        let handles = (0..parallelism)
+            .map(|lane| {
+                s.spawn(move || hash_lane(passphrase, salt, time_cost, lane_len, Some(lane)))
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().expect("lane should not panic")).collect::<Vec<_>>()
+    });
+
+    // Without threads (e.g. in `no_std` builds and on WebAssembly, where spawning a thread panics),
+    // hash each lane in turn. The result is the same.
+    #[cfg(any(not(feature = "std"), target_family = "wasm"))]
+    let blocks = (0..parallelism)
+        .map(|lane| hash_lane(passphrase, salt, time_cost, lane_len, Some(lane)))
+        .collect::<Vec<_>>();
+
+    // Extract key from the last block of each lane, in order.
+    let mut pbenc = Protocol::new("veil.pbenc");
+    for block in &blocks {
+        pbenc.mix("expanded-key", block);
+    }
+    pbenc
+}
+
+/// Hash a lane of `lane_len` blocks and return its last block. Lanes are numbered unless they were
+/// encrypted before lanes were supported.
+fn hash_lane(
+    passphrase: &[u8],
+    salt: &[u8],
+    time_cost: u8,
+    lane_len: usize,
+    lane: Option<u8>,
+) -> [u8; N] {
+    trace_span!(DEBUG, "pbenc_lane", lane);
+
     // A macro for the common hash operations. This is a macro rather than a function so it can
     // accept both immutable references to blocks in the buffer as well as a mutable reference to a
     // block in the same buffer for output. Accepts a template protocol, a counter variable, an
@@ -88,18 +239,26 @@ fn init(passphrase: &[u8], salt: &[u8], time_cost: u8, memory_cost: u8) -> Proto
 
     // Allocate buffer, initialize counter and default protocol state.
     let mut ctr = 0u64;
-    let mut buf = vec![[0u8; N]; 1usize << memory_cost];
+    let mut buf = vec![[0u8; N]; lane_len];
     let buf_len = u64::try_from(buf.len()).expect("usize should be <= u64");
     let h = Protocol::new("veil.pbenc.iter");
 
     // Step 1: Expand input into buffer.
-    hash!(h, ctr, &mut buf[0], passphrase, salt);
+    match lane {
+        Some(lane) => {
+            hash!(h, ctr, &mut buf[0], passphrase, salt, &[lane]);
+        }
+        None => {
+            hash!(h, ctr, &mut buf[0], passphrase, salt);
+        }
+    }
     for m in 1..buf.len() {
         hash!(h, ctr, &mut buf[m], &buf[m - 1]);
     }
 
     // Step 2: Mix buffer contents.
     for t in 0..1u64 << time_cost {
+        trace_span!(TRACE, "pbenc_pass", pass = t);
         for m in 0..buf.len() {
             // Step 2a: Hash last and current blocks.
             let prev = (m + (buf.len() - 1)) % buf.len(); // wrap 0 to last block
@@ -108,7 +267,7 @@ fn init(passphrase: &[u8], salt: &[u8], time_cost: u8, memory_cost: u8) -> Proto
             // Step 2b: Hash in pseudo-randomly chosen blocks.
             for i in 0..DELTA {
                 // Hash the salt and the loop indexes as 64-bit integers.
-                let mut idx_block = [0u8; mem::size_of::<u64>()];
+                let mut idx_block = [0u8; size_of::<u64>()];
                 hash!(
                     h,
                     ctr,
@@ -129,12 +288,18 @@ fn init(passphrase: &[u8], salt: &[u8], time_cost: u8, memory_cost: u8) -> Proto
         }
     }
 
-    // Step 3: Extract key from buffer.
-    let mut pbenc = Protocol::new("veil.pbenc");
-    pbenc.mix("expanded-key", &buf[buf.len() - 1]);
-    pbenc
+    // Step 3: Return the last block.
+    buf[buf.len() - 1]
 }
 
+/// The largest time cost a header may encode. Larger costs would overflow the 64-bit iteration
+/// count.
+const MAX_TIME_COST: u8 = 63;
+
+/// The largest memory cost a header may encode. Larger costs would overflow the block count on
+/// 32-bit targets, so they're rejected on every target for headers to decode the same everywhere.
+const MAX_MEMORY_COST: u8 = 31;
+
 const SALT_LEN: usize = 16;
 const DELTA: u64 = 3;
 const N: usize = 1024;
@@ -168,23 +333,121 @@ mod tests {
     }
 
     #[test]
-    fn modified_time_cost() {
+    fn modified_envelope_tag() {
         let (_, passphrase, _, mut ciphertext) = setup();
         ciphertext[0] ^= 1;
         assert_eq!(None, decrypt(&passphrase, &mut ciphertext), "decrypted an invalid ciphertext");
     }
 
     #[test]
-    fn modified_memory_cost() {
+    fn modified_time_cost() {
         let (_, passphrase, _, mut ciphertext) = setup();
         ciphertext[1] ^= 1;
         assert_eq!(None, decrypt(&passphrase, &mut ciphertext), "decrypted an invalid ciphertext");
     }
 
+    #[test]
+    fn modified_memory_cost() {
+        let (_, passphrase, _, mut ciphertext) = setup();
+        ciphertext[2] ^= 1;
+        assert_eq!(None, decrypt(&passphrase, &mut ciphertext), "decrypted an invalid ciphertext");
+    }
+
+    #[test]
+    fn modified_parallelism() {
+        let (_, passphrase, _, mut ciphertext) = setup();
+        ciphertext[3] ^= 1;
+        assert_eq!(None, decrypt(&passphrase, &mut ciphertext), "decrypted an invalid ciphertext");
+    }
+
+    #[test]
+    fn zero_parallelism() {
+        let (_, passphrase, _, mut ciphertext) = setup();
+        ciphertext[3] = 0;
+        assert_eq!(None, decrypt(&passphrase, &mut ciphertext), "decrypted an invalid ciphertext");
+    }
+
+    #[test]
+    fn excessive_parallelism() {
+        let (_, passphrase, _, ciphertext) = setup();
+        for (memory_cost, parallelism) in [(6, MAX_PARALLELISM + 1), (6, u8::MAX), (1, 3)] {
+            let mut ciphertext = ciphertext.clone();
+            ciphertext[2] = memory_cost;
+            ciphertext[3] = parallelism;
+            assert_eq!(
+                None,
+                decrypt(&passphrase, &mut ciphertext),
+                "accepted {parallelism} lanes with memory cost {memory_cost}"
+            );
+        }
+    }
+
+    #[test]
+    fn bounded_parallelism() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let passphrase = rng.gen::<[u8; 32]>();
+        let plaintext = rng.gen::<[u8; 64]>();
+
+        for (memory_cost, parallelism, lanes) in
+            [(1, 0, 1), (1, 4, 2), (8, u8::MAX, MAX_PARALLELISM)]
+        {
+            let mut ciphertext = vec![0u8; plaintext.len() + OVERHEAD];
+            encrypt(
+                &mut rng,
+                &passphrase,
+                0,
+                memory_cost,
+                parallelism,
+                &plaintext,
+                &mut ciphertext,
+            );
+            assert_eq!(lanes, ciphertext[3], "invalid lanes for {parallelism}");
+            assert_eq!(
+                Some(plaintext.as_slice()),
+                decrypt(&passphrase, &mut ciphertext),
+                "invalid plaintext"
+            );
+        }
+    }
+
+    #[test]
+    fn overflowing_costs() {
+        let (_, passphrase, _, ciphertext) = setup();
+        for (i, cost) in [(1, 64), (1, u8::MAX), (2, 32), (2, u8::MAX)] {
+            let mut ciphertext = ciphertext.clone();
+            ciphertext[i] = cost;
+            assert_eq!(None, decrypt(&passphrase, &mut ciphertext), "accepted cost {cost} at {i}");
+        }
+        let mut short = [0u8; OVERHEAD - 1];
+        short[0] = LANES_TAG;
+        assert_eq!(None, decrypt(&passphrase, &mut short), "decrypted a short input");
+        assert_eq!(
+            None,
+            decrypt(&passphrase, &mut [0u8; LEGACY_OVERHEAD - 1]),
+            "decrypted a short legacy input"
+        );
+    }
+
+    #[test]
+    fn single_lane_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let passphrase = rng.gen::<[u8; 32]>();
+        let plaintext = rng.gen::<[u8; 64]>();
+
+        let mut ciphertext = vec![0u8; plaintext.len() + OVERHEAD];
+        encrypt(&mut rng, &passphrase, 1, 6, 1, &plaintext, &mut ciphertext);
+
+        assert_eq!(
+            Some(plaintext.as_slice()),
+            decrypt(&passphrase, &mut ciphertext),
+            "invalid plaintext"
+        );
+    }
+
     #[test]
     fn modified_salt() {
         let (_, passphrase, _, mut ciphertext) = setup();
-        ciphertext[9] ^= 1;
+        ciphertext[10] ^= 1;
         assert_eq!(None, decrypt(&passphrase, &mut ciphertext), "decrypted an invalid ciphertext");
     }
 
@@ -208,7 +471,7 @@ mod tests {
         let plaintext = rng.gen::<[u8; 64]>();
 
         let mut ciphertext = vec![0u8; plaintext.len() + OVERHEAD];
-        encrypt(&mut rng, &passphrase, 1, 6, &plaintext, &mut ciphertext);
+        encrypt(&mut rng, &passphrase, 1, 6, 4, &plaintext, &mut ciphertext);
 
         (rng, passphrase, plaintext, ciphertext)
     }
@@ -0,0 +1,199 @@
+//! Policies for accepting messages signed by some of a set of signers.
+//!
+//! A [`VerificationPolicy`] accepts a message if at least its threshold of its signers have signed
+//! it: any one of them, all of them, or some number in between. Signatures don't identify their
+//! signers, so [`VerificationPolicy::verify`] checks each signature against every signer, reading
+//! the message only once.
+//!
+//! ```
+//! use veil::{policy::VerificationPolicy, PrivateKey};
+//!
+//! let keys = [(); 5].map(|_| PrivateKey::random(rand::thread_rng()));
+//! let policy = VerificationPolicy::k_of_n(2, &keys.each_ref().map(PrivateKey::public_key))
+//!     .expect("should be a valid policy");
+//!
+//! let message = b"release v1.0.0";
+//! let sig_a = keys[0].sign(rand::thread_rng(), &message[..]).expect("should sign");
+//! let sig_b = keys[3].sign(rand::thread_rng(), &message[..]).expect("should sign");
+//!
+//! assert!(policy.verify(&message[..], &[sig_a]).is_err());
+//! assert!(policy.verify(&message[..], &[sig_a, sig_b]).is_ok());
+//! ```
+
+use std::io::Read;
+
+use crate::{schnorr, PolicyError, PublicKey, Signature};
+
+/// A policy which accepts a message signed by at least a threshold of a set of signers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationPolicy {
+    signers: Vec<PublicKey>,
+    threshold: usize,
+}
+
+impl VerificationPolicy {
+    /// Creates a policy which accepts a message signed by any one of the given signers.
+    ///
+    /// # Errors
+    ///
+    /// If there are no signers or a signer is listed more than once, returns
+    /// [`PolicyError::InvalidPolicy`].
+    pub fn any_of(signers: &[PublicKey]) -> Result<VerificationPolicy, PolicyError> {
+        VerificationPolicy::k_of_n(1, signers)
+    }
+
+    /// Creates a policy which accepts a message signed by every one of the given signers.
+    ///
+    /// # Errors
+    ///
+    /// If there are no signers or a signer is listed more than once, returns
+    /// [`PolicyError::InvalidPolicy`].
+    pub fn all_of(signers: &[PublicKey]) -> Result<VerificationPolicy, PolicyError> {
+        VerificationPolicy::k_of_n(signers.len(), signers)
+    }
+
+    /// Creates a policy which accepts a message signed by at least `k` of the given signers.
+    ///
+    /// # Errors
+    ///
+    /// If a signer is listed more than once, or `k` is zero or greater than the number of signers,
+    /// returns [`PolicyError::InvalidPolicy`].
+    pub fn k_of_n(k: usize, signers: &[PublicKey]) -> Result<VerificationPolicy, PolicyError> {
+        let mut sorted = signers.iter().map(|pk| pk.0.encoded).collect::<Vec<_>>();
+        sorted.sort_unstable();
+        if k == 0 || k > signers.len() || sorted.windows(2).any(|w| w[0] == w[1]) {
+            return Err(PolicyError::InvalidPolicy);
+        }
+        Ok(VerificationPolicy { signers: signers.to_vec(), threshold: k })
+    }
+
+    /// Returns the policy's signers, in the order they were given.
+    #[must_use]
+    pub fn signers(&self) -> &[PublicKey] {
+        &self.signers
+    }
+
+    /// Returns the number of signers who must sign a message for the policy to accept it.
+    #[must_use]
+    pub const fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Reads the message from `message` and checks the given signatures of it against the policy.
+    /// Returns the signers whose signatures were valid, in the policy's order.
+    ///
+    /// Signatures which aren't from any of the policy's signers are ignored, and a signer with
+    /// more than one valid signature counts once.
+    ///
+    /// # Errors
+    ///
+    /// If fewer than the policy's threshold of signers signed the message, returns
+    /// [`PolicyError::NotEnoughSigners`]. If there is an error while reading from `message`,
+    /// returns [`PolicyError::ReadIo`].
+    pub fn verify(
+        &self,
+        message: impl Read,
+        signatures: &[Signature],
+    ) -> Result<Vec<PublicKey>, PolicyError> {
+        // Check every signature against every signer in a single read of the message.
+        let pairs = self
+            .signers
+            .iter()
+            .flat_map(|signer| signatures.iter().map(move |sig| (&signer.0, sig)))
+            .collect::<Vec<_>>();
+        let valid = schnorr::verify_many(&pairs, message).map_err(PolicyError::ReadIo)?;
+
+        // A signer signed the message if any of the signatures was valid for them.
+        let n = signatures.len();
+        let signed = self
+            .signers
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| valid[i * n..(i + 1) * n].contains(&true))
+            .map(|(_, &signer)| signer)
+            .collect::<Vec<_>>();
+        if signed.len() < self.threshold {
+            return Err(PolicyError::NotEnoughSigners(signed.len(), self.threshold));
+        }
+        Ok(signed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::PrivateKey;
+
+    fn setup() -> (ChaChaRng, Vec<PrivateKey>, Vec<PublicKey>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let keys = (0..5).map(|_| PrivateKey::random(&mut rng)).collect::<Vec<_>>();
+        let signers = keys.iter().map(PrivateKey::public_key).collect();
+        (rng, keys, signers)
+    }
+
+    #[test]
+    fn thresholds() {
+        let (mut rng, keys, signers) = setup();
+        let message = b"release v1.0.0";
+        let sigs = keys
+            .iter()
+            .map(|k| k.sign(&mut rng, &message[..]).expect("should sign"))
+            .collect::<Vec<_>>();
+
+        let any = VerificationPolicy::any_of(&signers).expect("should be valid");
+        assert_eq!(1, any.threshold());
+        assert_eq!(vec![signers[2]], any.verify(&message[..], &sigs[2..3]).expect("should verify"));
+        assert_matches!(any.verify(&message[..], &[]), Err(PolicyError::NotEnoughSigners(0, 1)));
+
+        let two = VerificationPolicy::k_of_n(2, &signers).expect("should be valid");
+        assert_eq!(
+            vec![signers[1], signers[4]],
+            two.verify(&message[..], &[sigs[4], sigs[1]]).expect("should verify")
+        );
+        assert_matches!(
+            two.verify(&message[..], &[sigs[1], sigs[1]]),
+            Err(PolicyError::NotEnoughSigners(1, 2))
+        );
+        assert_matches!(
+            two.verify(&b"release v1.0.1"[..], &sigs),
+            Err(PolicyError::NotEnoughSigners(0, 2))
+        );
+
+        let all = VerificationPolicy::all_of(&signers).expect("should be valid");
+        assert_eq!(signers, all.verify(&message[..], &sigs).expect("should verify"));
+        assert_matches!(
+            all.verify(&message[..], &sigs[1..]),
+            Err(PolicyError::NotEnoughSigners(4, 5))
+        );
+    }
+
+    #[test]
+    fn unknown_signers() {
+        let (mut rng, keys, signers) = setup();
+        let message = b"release v1.0.0";
+        let other = PrivateKey::random(&mut rng);
+        let sigs = [&other, &keys[0]].map(|k| k.sign(&mut rng, &message[..]).expect("should sign"));
+
+        let policy = VerificationPolicy::k_of_n(2, &signers).expect("should be valid");
+        assert_matches!(
+            policy.verify(&message[..], &sigs),
+            Err(PolicyError::NotEnoughSigners(1, 2))
+        );
+    }
+
+    #[test]
+    fn invalid_policies() {
+        let (_, _, signers) = setup();
+        assert_matches!(VerificationPolicy::any_of(&[]), Err(PolicyError::InvalidPolicy));
+        assert_matches!(VerificationPolicy::k_of_n(0, &signers), Err(PolicyError::InvalidPolicy));
+        assert_matches!(VerificationPolicy::k_of_n(6, &signers), Err(PolicyError::InvalidPolicy));
+        assert_matches!(
+            VerificationPolicy::all_of(&[signers[0], signers[1], signers[0]]),
+            Err(PolicyError::InvalidPolicy)
+        );
+    }
+}
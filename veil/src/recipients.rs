@@ -0,0 +1,125 @@
+//! Lists of receivers' public keys.
+//!
+//! A recipients file lists one public key per line, optionally followed by a comment. Blank lines
+//! and lines starting with `#` are ignored:
+//!
+//! ```text
+//! # The release team.
+//! TkUWybv8fAvsHPhauPj7edUTVdCHuCFHazA6RjnvwJa alice@example.com
+//! 3ibPj4XuBvTbFPuXHDy6kDZPvn9R4CgYtuGjpgEd7zvb # bea
+//! ```
+
+use crate::{ParseRecipientsError, PublicKey};
+
+/// A public key listed in a recipients file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recipient {
+    /// The receiver's public key.
+    pub public_key: PublicKey,
+
+    /// The comment following the public key, if any.
+    pub comment: Option<String>,
+
+    /// The one-based line on which the public key was listed.
+    pub line: usize,
+}
+
+/// Parses a list of recipients, in the order they are listed.
+///
+/// ```
+/// use veil::{recipients, PrivateKey};
+///
+/// let pk = PrivateKey::random(rand::thread_rng()).public_key();
+/// let parsed = recipients::parse(&format!("# comment\n{pk} alice\n")).expect("should parse");
+/// assert_eq!(pk, parsed[0].public_key);
+/// assert_eq!(Some("alice"), parsed[0].comment.as_deref());
+/// ```
+///
+/// # Errors
+///
+/// If a line does not start with a valid public key, returns
+/// [`ParseRecipientsError::InvalidPublicKey`]. If a public key is listed more than once, returns
+/// [`ParseRecipientsError::DuplicatePublicKey`].
+pub fn parse(s: &str) -> Result<Vec<Recipient>, ParseRecipientsError> {
+    let mut recipients = Vec::<Recipient>::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, comment) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let public_key =
+            key.parse().map_err(|e| ParseRecipientsError::InvalidPublicKey(i + 1, e))?;
+        if let Some(first) = recipients.iter().find(|r| r.public_key == public_key) {
+            return Err(ParseRecipientsError::DuplicatePublicKey(i + 1, first.line));
+        }
+
+        let comment = comment.trim_start();
+        let comment = comment.strip_prefix('#').unwrap_or(comment).trim();
+        recipients.push(Recipient {
+            public_key,
+            comment: (!comment.is_empty()).then(|| comment.into()),
+            line: i + 1,
+        });
+    }
+    Ok(recipients)
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{ParsePublicKeyError, PrivateKey};
+
+    #[test]
+    fn round_trip() {
+        let (a, b, c) = setup();
+        let recipients =
+            parse(&format!("# The release team.\n\n{a} alice@example.com\n  {b}   # bea  \n{c}\n"))
+                .expect("should parse");
+
+        assert_eq!(
+            vec![
+                Recipient { public_key: a, comment: Some("alice@example.com".into()), line: 3 },
+                Recipient { public_key: b, comment: Some("bea".into()), line: 4 },
+                Recipient { public_key: c, comment: None, line: 5 },
+            ],
+            recipients
+        );
+    }
+
+    #[test]
+    fn invalid_public_key() {
+        let (a, _, _) = setup();
+        assert_matches!(
+            parse(&format!("{a}\n\nnot-a-key bea\n")),
+            Err(ParseRecipientsError::InvalidPublicKey(3, ParsePublicKeyError::InvalidEncoding(_)))
+        );
+        assert_matches!(
+            parse("11111111111111111111111111111111"),
+            Err(ParseRecipientsError::InvalidPublicKey(1, ParsePublicKeyError::NeutralPoint))
+        );
+    }
+
+    #[test]
+    fn duplicate_public_key() {
+        let (a, b, _) = setup();
+        assert_matches!(
+            parse(&format!("{a} alice\n{b} bea\n# again\n{a} alice")),
+            Err(ParseRecipientsError::DuplicatePublicKey(4, 1))
+        );
+    }
+
+    fn setup() -> (PublicKey, PublicKey, PublicKey) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        (
+            PrivateKey::random(&mut rng).public_key(),
+            PrivateKey::random(&mut rng).public_key(),
+            PrivateKey::random(&mut rng).public_key(),
+        )
+    }
+}
@@ -0,0 +1,225 @@
+//! Signed key revocation statements.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::{PubKey, POINT_LEN},
+    schnorr::{self, DET_SIGNATURE_LEN},
+    sres::NONCE_LEN,
+    ParseRevocationError, PublicKey, SignerBackend, VerifyError,
+};
+
+/// The length of an encoded revocation with an empty reason, in bytes.
+const MIN_REVOCATION_LEN: usize = POINT_LEN + size_of::<u64>() + NONCE_LEN + DET_SIGNATURE_LEN;
+
+/// A statement, signed by a private key, that the private key should no longer be used.
+///
+/// Consists of the revoked public key, a timestamp, a nonce, an encrypted commitment point, an
+/// encrypted proof scalar, and a UTF-8 reason.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Revocation {
+    public_key: PublicKey,
+    timestamp: u64,
+    nonce: [u8; NONCE_LEN],
+    sig: [u8; DET_SIGNATURE_LEN],
+    reason: String,
+}
+
+impl Revocation {
+    /// Returns the revoked public key.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// Returns the time the key was revoked, in seconds since the Unix epoch.
+    #[must_use]
+    pub const fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the reason the key was revoked.
+    #[must_use]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Verifies that the revocation was created by the owner of `public_key`. Returns `Ok(())` if
+    /// successful.
+    ///
+    /// # Errors
+    ///
+    /// If the revocation is for a different public key or has been modified, returns
+    /// [`VerifyError::InvalidSignature`].
+    pub fn verify(&self, public_key: &PublicKey) -> Result<(), VerifyError> {
+        if self.public_key != *public_key {
+            return Err(VerifyError::InvalidSignature);
+        }
+
+        let mut revocation = init(&public_key.0, self.timestamp, &self.nonce, &self.reason);
+        schnorr::det_verify(&mut revocation, &public_key.0, self.sig)
+            .ok_or(VerifyError::InvalidSignature)
+    }
+
+    /// Decode a revocation from a byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<Revocation> {
+        let b = b.as_ref();
+        if b.len() < MIN_REVOCATION_LEN {
+            return None;
+        }
+
+        // Split the revocation into components.
+        let (public_key, b) = b.split_at(POINT_LEN);
+        let (timestamp, b) = b.split_at(size_of::<u64>());
+        let (nonce, b) = b.split_at(NONCE_LEN);
+        let (sig, reason) = b.split_at(DET_SIGNATURE_LEN);
+
+        Some(Revocation {
+            public_key: PublicKey::decode(public_key)?,
+            timestamp: u64::from_le_bytes(timestamp.try_into().expect("should be 8 bytes")),
+            nonce: nonce.try_into().expect("should be nonce-sized"),
+            sig: sig.try_into().expect("should be signature-sized"),
+            reason: String::from_utf8(reason.to_vec()).ok()?,
+        })
+    }
+
+    /// Encode the revocation as a byte vector.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(MIN_REVOCATION_LEN + self.reason.len());
+        b.extend_from_slice(&self.public_key.encode());
+        b.extend_from_slice(&self.timestamp.to_le_bytes());
+        b.extend_from_slice(&self.nonce);
+        b.extend_from_slice(&self.sig);
+        b.extend_from_slice(self.reason.as_bytes());
+        b
+    }
+}
+
+impl FromStr for Revocation {
+    type Err = ParseRevocationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Revocation::decode(bs58::decode(s).into_vec()?)
+            .ok_or(ParseRevocationError::InvalidRevocation)
+    }
+}
+
+impl fmt::Display for Revocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    }
+}
+
+/// Create a revocation of the given signer's key with the given reason and timestamp.
+pub fn revoke(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    reason: &str,
+    timestamp: u64,
+) -> Revocation {
+    let public_key = signer.public_key();
+
+    // Generate a random nonce.
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+    // Initialize a protocol with the signer, timestamp, nonce, and reason and sign its state.
+    let mut revocation = init(&public_key.0, timestamp, &nonce, reason);
+    let sig = signer.sign(&mut revocation);
+
+    Revocation { public_key, timestamp, nonce, sig, reason: reason.into() }
+}
+
+fn init(signer: &PubKey, timestamp: u64, nonce: &[u8], reason: &str) -> Protocol {
+    // Initialize a protocol.
+    let mut revocation = Protocol::new("veil.revocation");
+
+    // Mix the signer's public key into the protocol.
+    revocation.mix("signer", &signer.encoded);
+
+    // Mix the nonce into the protocol.
+    revocation.mix("nonce", nonce);
+
+    // Mix the timestamp and reason into the protocol.
+    revocation.mix("timestamp", &timestamp.to_le_bytes());
+    revocation.mix("reason", reason.as_bytes());
+
+    revocation
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn round_trip() {
+        let (_, key, revocation) = setup();
+
+        assert_matches!(revocation.verify(&key.public_key()), Ok(()));
+        assert_eq!(key.public_key(), revocation.public_key());
+        assert_eq!(1_700_000_000, revocation.timestamp());
+        assert_eq!("key was stolen", revocation.reason());
+    }
+
+    #[test]
+    fn wrong_public_key() {
+        let (mut rng, _, revocation) = setup();
+
+        let wrong_key = PrivateKey::random(&mut rng).public_key();
+        assert_matches!(revocation.verify(&wrong_key), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn modified_reason() {
+        let (_, key, mut revocation) = setup();
+
+        revocation.reason = "key was lost".into();
+        assert_matches!(revocation.verify(&key.public_key()), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn modified_timestamp() {
+        let (_, key, mut revocation) = setup();
+
+        revocation.timestamp += 1;
+        assert_matches!(revocation.verify(&key.public_key()), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn encoding() {
+        let (_, key, revocation) = setup();
+
+        let decoded = revocation.to_string().parse::<Revocation>();
+        assert_eq!(Ok(&revocation), decoded.as_ref(), "error parsing revocation");
+        assert_matches!(decoded.expect("should parse").verify(&key.public_key()), Ok(()));
+
+        assert_eq!(
+            Err(ParseRevocationError::InvalidRevocation),
+            bs58::encode([0u8; MIN_REVOCATION_LEN - 1]).into_string().parse::<Revocation>()
+        );
+        assert_eq!(
+            Err(ParseRevocationError::InvalidEncoding(bs58::decode::Error::InvalidCharacter {
+                character: 'l',
+                index: 4,
+            })),
+            "invalid revocation".parse::<Revocation>()
+        );
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, Revocation) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let revocation = revoke(&mut rng, &key, "key was stolen", 1_700_000_000);
+        (rng, key, revocation)
+    }
+}
@@ -1,15 +1,27 @@
 //! Schnorr-variant digital signatures.
 
-use std::{fmt, io, io::Read, str::FromStr};
+use core::{fmt, str::FromStr};
+#[cfg(feature = "std")]
+use std::{
+    collections::BTreeMap,
+    io,
+    io::{Read, Write},
+};
 
-use crrl::gls254::{Point, Scalar};
+#[cfg(feature = "std")]
+use lockstitch::MixWriter;
 use lockstitch::Protocol;
+#[cfg(feature = "std")]
 use rand::{CryptoRng, Rng};
 
+#[cfg(feature = "std")]
+use crate::{blockio::ReadBlock, Digest, SignerBackend, VerifyError};
 use crate::{
+    ct,
+    curve::{Curve, Point, Scalar, Selected},
     keys::{PrivKey, PubKey, POINT_LEN, SCALAR_LEN},
     sres::NONCE_LEN,
-    ParseSignatureError, VerifyError,
+    ParseSignatureError,
 };
 
 /// The length of a deterministic signature, in bytes.
@@ -18,6 +30,18 @@ pub const DET_SIGNATURE_LEN: usize = POINT_LEN + SCALAR_LEN;
 /// The length of a signature, in bytes.
 pub const SIGNATURE_LEN: usize = NONCE_LEN + POINT_LEN + SCALAR_LEN;
 
+/// The length of a timestamped signature, in bytes.
+pub const TIMESTAMPED_SIGNATURE_LEN: usize = size_of::<u64>() + SIGNATURE_LEN;
+
+/// The length of the message blocks read when verifying attached or batched signatures.
+#[cfg(feature = "std")]
+const BLOCK_LEN: usize = 64 * 1024;
+
+/// The minimum number of signatures for which batch verification is faster than verifying each
+/// signature individually.
+#[cfg(feature = "std")]
+const MIN_BATCH_LEN: usize = 64;
+
 /// A Schnorr signature.
 ///
 /// Consists of a 16-byte nonce, an encrypted commitment point, and an encrypted proof scalar.
@@ -32,6 +56,9 @@ impl Signature {
     }
 
     /// Encode the signature as a 80-byte array.
+    ///
+    /// This is the 16-byte nonce followed by the 32-byte encrypted commitment point and the 32-byte
+    /// encrypted proof scalar, and is stable across versions.
     #[must_use]
     pub const fn encode(&self) -> [u8; SIGNATURE_LEN] {
         self.0
@@ -42,7 +69,7 @@ impl FromStr for Signature {
     type Err = ParseSignatureError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Signature::decode(bs58::decode(s).into_vec()?.as_slice())
+        Signature::decode(bs58::decode(s.trim()).into_vec()?.as_slice())
             .ok_or(ParseSignatureError::InvalidLength)
     }
 }
@@ -53,10 +80,59 @@ impl fmt::Display for Signature {
     }
 }
 
-/// Create a randomized Schnorr signature of the given message using the given key pair.
+/// A Schnorr signature which asserts the time at which it was created.
+///
+/// Consists of a little-endian 64-bit timestamp followed by a [`Signature`] of the timestamp and the
+/// message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimestampedSignature([u8; TIMESTAMPED_SIGNATURE_LEN]);
+
+impl TimestampedSignature {
+    /// Create a timestamped signature from a 88-byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<TimestampedSignature> {
+        Some(TimestampedSignature(b.as_ref().try_into().ok()?))
+    }
+
+    /// Encode the timestamped signature as a 88-byte array.
+    ///
+    /// This is the little-endian timestamp followed by the encoded signature, and is stable across
+    /// versions.
+    #[must_use]
+    pub const fn encode(&self) -> [u8; TIMESTAMPED_SIGNATURE_LEN] {
+        self.0
+    }
+
+    /// Returns the timestamp the signature claims to have been created at. It is only trustworthy
+    /// once the signature has been verified.
+    #[must_use]
+    pub fn timestamp(&self) -> u64 {
+        u64::from_le_bytes(self.0[..size_of::<u64>()].try_into().expect("should be u64-sized"))
+    }
+}
+
+impl FromStr for TimestampedSignature {
+    type Err = ParseSignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TimestampedSignature::decode(bs58::decode(s.trim()).into_vec()?.as_slice())
+            .ok_or(ParseSignatureError::InvalidLength)
+    }
+}
+
+impl fmt::Display for TimestampedSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.0).into_string())
+    }
+}
+
+/// Create a randomized Schnorr signature of the given message in the given context using the given
+/// key pair.
+#[cfg(feature = "std")]
 pub fn sign(
     mut rng: impl Rng + CryptoRng,
-    signer: &PrivKey,
+    signer: &impl SignerBackend,
+    context: &str,
     mut message: impl Read,
 ) -> io::Result<Signature> {
     // Allocate an output buffer.
@@ -66,24 +142,155 @@ pub fn sign(
     let mut schnorr = Protocol::new("veil.schnorr");
 
     // Mix the signer's public key into the protocol.
-    schnorr.mix("signer", &signer.pub_key.encoded);
+    schnorr.mix("signer", &signer.public_key().0.encoded);
 
     // Generate a random nonce and mix it into the protocol.
     rng.fill_bytes(&mut sig[..NONCE_LEN]);
     schnorr.mix("nonce", &sig[..NONCE_LEN]);
 
+    // Mix the context, if any, into the protocol.
+    mix_context(&mut schnorr, context);
+
     // Mix the message into the protocol.
     let mut writer = schnorr.mix_writer("message", io::sink());
     io::copy(&mut message, &mut writer)?;
     let (mut schnorr, _) = writer.into_inner();
 
     // Calculate the encrypted commitment point and proof scalar.
-    sig[NONCE_LEN..].copy_from_slice(&det_sign(&mut schnorr, signer));
+    sig[NONCE_LEN..].copy_from_slice(&signer.sign(&mut schnorr));
     Ok(Signature(sig))
 }
 
-/// Verify a randomized Schnorr signature of the given message using the given public key.
-pub fn verify(signer: &PubKey, mut message: impl Read, sig: &Signature) -> Result<(), VerifyError> {
+/// Verify a randomized Schnorr signature of the given message in the given context using the given
+/// public key.
+#[cfg(feature = "std")]
+pub fn verify(
+    signer: &PubKey,
+    context: &str,
+    message: impl Read,
+    sig: &Signature,
+) -> Result<(), VerifyError> {
+    let mut reader = VerifyingReader::new(signer, context, message, sig);
+    io::copy(&mut reader, &mut io::sink())?;
+    reader.finish()
+}
+
+/// A reader which verifies a randomized Schnorr signature of the message as it's read. Created by
+/// [`crate::PublicKey::verifying_reader`].
+///
+/// The message is returned from [`Read::read`] before the signature is verified, so it must not be
+/// trusted until [`VerifyingReader::finish`] returns `Ok(())`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct VerifyingReader<R> {
+    reader: R,
+    schnorr: MixWriter<io::Sink>,
+    signer: PubKey,
+    sig: Signature,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> VerifyingReader<R> {
+    pub(crate) fn new(
+        signer: &PubKey,
+        context: &str,
+        reader: R,
+        sig: &Signature,
+    ) -> VerifyingReader<R> {
+        // Initialize a protocol.
+        let mut schnorr = Protocol::new("veil.schnorr");
+
+        // Mix the signer's public key into the protocol.
+        schnorr.mix("signer", &signer.encoded);
+
+        // Mix the nonce into the protocol.
+        schnorr.mix("nonce", &sig.0[..NONCE_LEN]);
+
+        // Mix the context, if any, into the protocol.
+        mix_context(&mut schnorr, context);
+
+        // Mix the message into the protocol as it's read.
+        VerifyingReader {
+            reader,
+            schnorr: schnorr.mix_writer("message", io::sink()),
+            signer: *signer,
+            sig: *sig,
+        }
+    }
+
+    /// Reads the rest of the message, if any, and verifies the signature of the whole message.
+    ///
+    /// # Errors
+    ///
+    /// If the message has been modified or was not signed by the owner of the public key, returns
+    /// [`VerifyError::InvalidSignature`]. If there was an error reading the rest of the message,
+    /// returns [`VerifyError::ReadIo`].
+    pub fn finish(mut self) -> Result<(), VerifyError> {
+        io::copy(&mut self.reader, &mut self.schnorr)?;
+        let (mut schnorr, _) = self.schnorr.into_inner();
+
+        // Verify the signature.
+        det_verify(
+            &mut schnorr,
+            &self.signer,
+            self.sig.0[NONCE_LEN..].try_into().expect("should be 64 bytes"),
+        )
+        .ok_or(VerifyError::InvalidSignature)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for VerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.schnorr.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// Mix a non-empty signature context into the protocol. Signatures without a context are the same
+/// as those created before contexts were supported.
+#[cfg(feature = "std")]
+fn mix_context(schnorr: &mut Protocol, context: &str) {
+    if !context.is_empty() {
+        schnorr.mix("context", context.as_bytes());
+    }
+}
+
+/// Create a randomized Schnorr signature of the given digest using the given key pair.
+///
+/// The digest is mixed in with a different label than a message, so a signature of a digest can't
+/// be passed off as a signature of a message consisting of the digest's bytes, or vice versa.
+#[cfg(feature = "std")]
+pub fn sign_digest(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    digest: &Digest,
+) -> Signature {
+    // Allocate an output buffer.
+    let mut sig = [0u8; SIGNATURE_LEN];
+
+    // Initialize a protocol.
+    let mut schnorr = Protocol::new("veil.schnorr");
+
+    // Mix the signer's public key into the protocol.
+    schnorr.mix("signer", &signer.public_key().0.encoded);
+
+    // Generate a random nonce and mix it into the protocol.
+    rng.fill_bytes(&mut sig[..NONCE_LEN]);
+    schnorr.mix("nonce", &sig[..NONCE_LEN]);
+
+    // Mix the digest into the protocol.
+    schnorr.mix("digest", digest.encode());
+
+    // Calculate the encrypted commitment point and proof scalar.
+    sig[NONCE_LEN..].copy_from_slice(&signer.sign(&mut schnorr));
+    Signature(sig)
+}
+
+/// Verify a randomized Schnorr signature of the given digest using the given public key.
+#[cfg(feature = "std")]
+pub fn verify_digest(signer: &PubKey, digest: &Digest, sig: &Signature) -> Result<(), VerifyError> {
     // Initialize a protocol.
     let mut schnorr = Protocol::new("veil.schnorr");
 
@@ -93,14 +300,225 @@ pub fn verify(signer: &PubKey, mut message: impl Read, sig: &Signature) -> Resul
     // Mix the nonce into the protocol.
     schnorr.mix("nonce", &sig.0[..NONCE_LEN]);
 
+    // Mix the digest into the protocol.
+    schnorr.mix("digest", digest.encode());
+
+    // Verify the signature.
+    det_verify(&mut schnorr, signer, sig.0[NONCE_LEN..].try_into().expect("should be 64 bytes"))
+        .ok_or(VerifyError::InvalidSignature)
+}
+
+/// Create a randomized Schnorr signature of the given timestamp and message using the given key
+/// pair.
+///
+/// The timestamp is mixed in before the message with its own label, so a timestamped signature
+/// can't be passed off as a signature of a message without one, or vice versa.
+#[cfg(feature = "std")]
+pub fn sign_timestamped(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    mut message: impl Read,
+    timestamp: u64,
+) -> io::Result<TimestampedSignature> {
+    // Allocate an output buffer and encode the timestamp.
+    let mut sig = [0u8; TIMESTAMPED_SIGNATURE_LEN];
+    let (out_timestamp, out_sig) = sig.split_at_mut(size_of::<u64>());
+    out_timestamp.copy_from_slice(&timestamp.to_le_bytes());
+
+    // Initialize a protocol.
+    let mut schnorr = Protocol::new("veil.schnorr");
+
+    // Mix the signer's public key into the protocol.
+    schnorr.mix("signer", &signer.public_key().0.encoded);
+
+    // Generate a random nonce and mix it into the protocol.
+    rng.fill_bytes(&mut out_sig[..NONCE_LEN]);
+    schnorr.mix("nonce", &out_sig[..NONCE_LEN]);
+
+    // Mix the timestamp into the protocol.
+    schnorr.mix("timestamp", out_timestamp);
+
+    // Mix the message into the protocol.
+    let mut writer = schnorr.mix_writer("message", io::sink());
+    io::copy(&mut message, &mut writer)?;
+    let (mut schnorr, _) = writer.into_inner();
+
+    // Calculate the encrypted commitment point and proof scalar.
+    out_sig[NONCE_LEN..].copy_from_slice(&signer.sign(&mut schnorr));
+    Ok(TimestampedSignature(sig))
+}
+
+/// Verify a timestamped Schnorr signature of the given message using the given public key, returning
+/// the timestamp.
+#[cfg(feature = "std")]
+pub fn verify_timestamped(
+    signer: &PubKey,
+    mut message: impl Read,
+    sig: &TimestampedSignature,
+) -> Result<u64, VerifyError> {
+    // Initialize a protocol.
+    let mut schnorr = Protocol::new("veil.schnorr");
+
+    // Mix the signer's public key into the protocol.
+    schnorr.mix("signer", &signer.encoded);
+
+    // Split up the timestamp, nonce, and signature.
+    let (timestamp, sig) = sig.0.split_at(size_of::<u64>());
+    let (nonce, sig) = sig.split_at(NONCE_LEN);
+
+    // Mix the nonce into the protocol.
+    schnorr.mix("nonce", nonce);
+
+    // Mix the timestamp into the protocol.
+    schnorr.mix("timestamp", timestamp);
+
     // Mix the message into the protocol.
     let mut writer = schnorr.mix_writer("message", io::sink());
     io::copy(&mut message, &mut writer)?;
     let (mut schnorr, _) = writer.into_inner();
 
+    // Verify the signature and return the timestamp.
+    det_verify(&mut schnorr, signer, sig.try_into().expect("should be 64 bytes"))
+        .ok_or(VerifyError::InvalidSignature)?;
+    Ok(u64::from_le_bytes(timestamp.try_into().expect("should be u64-sized")))
+}
+
+/// Verify many randomized Schnorr signatures of the given message, reading the message once.
+/// Returns whether each signature is valid, in order.
+#[cfg(feature = "std")]
+pub fn verify_many(
+    signatures: &[(&PubKey, &Signature)],
+    mut message: impl Read,
+) -> io::Result<Vec<bool>> {
+    // Initialize a protocol for each signature with the signer's public key and the nonce.
+    let mut writers = signatures
+        .iter()
+        .map(|(signer, sig)| {
+            let mut schnorr = Protocol::new("veil.schnorr");
+            schnorr.mix("signer", &signer.encoded);
+            schnorr.mix("nonce", &sig.0[..NONCE_LEN]);
+            schnorr.mix_writer("message", io::sink())
+        })
+        .collect::<Vec<_>>();
+
+    // Read the message in blocks and mix each block into every protocol.
+    let mut buf = vec![0u8; BLOCK_LEN];
+    loop {
+        let n = message.read_block(&mut buf)?;
+        for writer in &mut writers {
+            writer.write_all(&buf[..n]).expect("writes to a sink should be infallible");
+        }
+        if n < buf.len() {
+            break;
+        }
+    }
+
+    // Verify each signature.
+    Ok(writers
+        .into_iter()
+        .zip(signatures)
+        .map(|(writer, (signer, sig))| {
+            let (mut schnorr, _) = writer.into_inner();
+            let det_sig = sig.0[NONCE_LEN..].try_into().expect("should be 64 bytes");
+            det_verify(&mut schnorr, signer, det_sig).is_some()
+        })
+        .collect())
+}
+
+/// Create a randomized Schnorr signature of the contents of `reader` using the given key pair and
+/// write the nonce, message, and signature to `writer`. Returns the number of bytes written.
+///
+/// The output is a detached signature's nonce, followed by the message, followed by the rest of the
+/// detached signature.
+#[cfg(feature = "std")]
+pub fn sign_attached(
+    mut rng: impl Rng + CryptoRng,
+    signer: &impl SignerBackend,
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> io::Result<u64> {
+    // Initialize a protocol.
+    let mut schnorr = Protocol::new("veil.schnorr");
+
+    // Mix the signer's public key into the protocol.
+    schnorr.mix("signer", &signer.public_key().0.encoded);
+
+    // Generate a random nonce, write it, and mix it into the protocol.
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+    writer.write_all(&nonce)?;
+    schnorr.mix("nonce", &nonce);
+
+    // Mix the message into the protocol as it's written.
+    let mut writer = schnorr.mix_writer("message", writer);
+    let n = io::copy(&mut reader, &mut writer)?;
+    let (mut schnorr, mut writer) = writer.into_inner();
+
+    // Calculate the encrypted commitment point and proof scalar and write them.
+    writer.write_all(&signer.sign(&mut schnorr))?;
+    Ok(n + u64::try_from(SIGNATURE_LEN).expect("usize should be <= u64"))
+}
+
+/// Verify an attached Schnorr signature read from `reader` using the given public key and write the
+/// message to `writer`. Returns the number of bytes of message written.
+///
+/// The message is written to `writer` as it is read, before the signature is verified.
+#[cfg(feature = "std")]
+pub fn verify_attached(
+    signer: &PubKey,
+    mut reader: impl Read,
+    writer: impl Write,
+) -> Result<u64, VerifyError> {
+    // Initialize a protocol.
+    let mut schnorr = Protocol::new("veil.schnorr");
+
+    // Mix the signer's public key into the protocol.
+    schnorr.mix("signer", &signer.encoded);
+
+    // Read the nonce and mix it into the protocol.
+    let mut nonce = [0u8; NONCE_LEN];
+    reader.read_exact(&mut nonce).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            VerifyError::InvalidSignature
+        } else {
+            VerifyError::ReadIo(e)
+        }
+    })?;
+    schnorr.mix("nonce", &nonce);
+
+    // Mix the message into the protocol as it's written, holding back the possible signature at
+    // the end of each read.
+    let mut writer = schnorr.mix_writer("message", writer);
+    let mut buf = vec![0u8; BLOCK_LEN + DET_SIGNATURE_LEN];
+    let mut offset = 0;
+    let mut written = 0;
+    loop {
+        // Read a block, keeping in mind the held-back part of the buffer from the last iteration.
+        let len = offset + reader.read_block(&mut buf[offset..])?;
+
+        // If there isn't room for a signature, the message is truncated.
+        let msg_len = len.checked_sub(DET_SIGNATURE_LEN).ok_or(VerifyError::InvalidSignature)?;
+
+        // Write the message and move the possible signature to the beginning of the buffer.
+        writer.write_all(&buf[..msg_len]).map_err(VerifyError::WriteIo)?;
+        written += u64::try_from(msg_len).expect("usize should be <= u64");
+        buf.copy_within(msg_len..len, 0);
+        offset = DET_SIGNATURE_LEN;
+
+        // If the block was undersized, we're at the end of the reader.
+        if len < buf.len() {
+            break;
+        }
+    }
+    let (mut schnorr, _) = writer.into_inner();
+
     // Verify the signature.
-    det_verify(&mut schnorr, signer, sig.0[NONCE_LEN..].try_into().expect("should be 64 bytes"))
-        .ok_or(VerifyError::InvalidSignature)
+    det_verify(
+        &mut schnorr,
+        signer,
+        buf[..DET_SIGNATURE_LEN].try_into().expect("should be 64 bytes"),
+    )
+    .ok_or(VerifyError::InvalidSignature)?;
+    Ok(written)
 }
 
 /// Create a deterministic Schnorr signature of the given protocol's state using the given private
@@ -121,7 +539,7 @@ pub fn det_sign(protocol: &mut Protocol, signer: &PrivKey) -> [u8; DET_SIGNATURE
     let rb = protocol.derive_array::<16>("challenge-scalar");
     let r0 = u64::from_le_bytes(rb[..8].try_into().expect("rb should be 16 bytes"));
     let r1 = u64::from_le_bytes(rb[8..].try_into().expect("rb should be 16 bytes"));
-    let r = Scalar::from_u64(r0) + Scalar::MU * Scalar::from_u64(r1);
+    let r = Selected::challenge(r0, r1);
 
     // Calculate, encode, and encrypt the proof scalar.
     let s = (signer.d * r) + k;
@@ -137,8 +555,95 @@ pub fn det_sign(protocol: &mut Protocol, signer: &PrivKey) -> [u8; DET_SIGNATURE
 pub fn det_verify(
     protocol: &mut Protocol,
     signer: &PubKey,
-    mut sig: [u8; DET_SIGNATURE_LEN],
+    sig: [u8; DET_SIGNATURE_LEN],
 ) -> Option<()> {
+    // Decrypt the signature and re-derive the challenge scalars.
+    let (i, r0_p, r1_p, s) = det_open(protocol, sig)?;
+
+    // Return true iff I and s are well-formed and I == [s]G - [r']Q. Here we compare the encoded
+    // form of I' with the encoded form of I from the signature. This is faster, as encoding a point
+    // is faster than decoding a point.
+    let i_p = Selected::mul_challenge_add_mulgen_vartime(-signer.q, r0_p, r1_p, &s);
+    (i == i_p.encode()).then_some(())
+}
+
+/// Verify many randomized Schnorr signatures of different messages at once. Returns `true` iff
+/// every signature is valid.
+///
+/// Rather than checking each signature's verification equation `I == [s]G - [r]Q` separately, this
+/// checks a random linear combination of all of them with a single multi-scalar multiplication. The
+/// coefficients are derived from the entire batch, so no signature can be crafted to cancel out
+/// another. If the batch is invalid, this does not identify which of its signatures are invalid.
+///
+/// The terms for each signer are combined, so the batch is fastest when its signatures come from
+/// few signers. If the batch is small or most of its signers are distinct, the signatures are
+/// verified individually.
+#[cfg(feature = "std")]
+#[must_use]
+pub fn verify_batch(signatures: &[(&PubKey, &[u8], &Signature)]) -> bool {
+    // Decrypt each signature.
+    let mut opened = Vec::with_capacity(signatures.len());
+    let mut signers = BTreeMap::new();
+    for &(signer, message, sig) in signatures {
+        let mut schnorr = Protocol::new("veil.schnorr");
+        schnorr.mix("signer", &signer.encoded);
+        schnorr.mix("nonce", &sig.0[..NONCE_LEN]);
+        schnorr.mix("message", message);
+
+        let det_sig = sig.0[NONCE_LEN..].try_into().expect("should be 64 bytes");
+        let Some((i, r0, r1, s)) = det_open(&mut schnorr, det_sig) else {
+            return false;
+        };
+        signers.insert(signer.encoded, (signer.q, Scalar::ZERO));
+        opened.push((signer, i, r0, r1, s));
+    }
+
+    // With few signatures or mostly distinct signers, a combined check would be slower than
+    // individual ones.
+    if opened.len() < MIN_BATCH_LEN || signers.len() * 2 > opened.len() {
+        return opened.into_iter().all(|(signer, i, r0, r1, s)| {
+            i == Selected::mul_challenge_add_mulgen_vartime(-signer.q, r0, r1, &s).encode()
+        });
+    }
+
+    // Initialize a protocol and mix every decrypted signature into it, so the coefficients depend
+    // on the entire batch.
+    let mut batch = Protocol::new("veil.schnorr.batch");
+    for (signer, i, _, _, s) in &opened {
+        batch.mix("signer", &signer.encoded);
+        batch.mix("commitment-point", i);
+        batch.mix("proof-scalar", &s.encode());
+    }
+
+    // Check that [Σzs]G == Σ[z]I + Σ[Σzr]Q for a random 128-bit coefficient z per signature,
+    // summing the challenge scalars for each signer.
+    let mut terms = Vec::with_capacity(opened.len() + signers.len() * 2);
+    let mut zs = Scalar::ZERO;
+    for (signer, i, r0, r1, s) in opened {
+        let Some(i) = ct::point(&i) else {
+            return false;
+        };
+        let z = u128::from_le_bytes(batch.derive_array("coefficient"));
+        let r = Selected::challenge(r0, r1);
+        zs += Scalar::from_u128(z) * s;
+        signers.get_mut(&signer.encoded).expect("should have signer").1 += Scalar::from_u128(z) * r;
+        terms.push((z, i));
+    }
+
+    // Split each signer's product, so every scalar in the multi-scalar multiplication is at most
+    // 128 bits long.
+    for (q, zr) in signers.into_values() {
+        terms.extend(Selected::split_vartime(q, &zr));
+    }
+    (multiscalar_mul_vartime(&terms) - Point::mulgen(&zs)).isneutral() != 0
+}
+
+/// Decrypt a deterministic Schnorr signature of the given protocol's state, returning the encoded
+/// commitment point, the short challenge scalars, and the decoded proof scalar.
+fn det_open(
+    protocol: &mut Protocol,
+    mut sig: [u8; DET_SIGNATURE_LEN],
+) -> Option<([u8; POINT_LEN], u64, u64, Scalar)> {
     // Split signature into components.
     let (i, s) = sig.split_at_mut(POINT_LEN);
 
@@ -152,21 +657,51 @@ pub fn det_verify(
 
     // Decrypt and decode the proof scalar.
     protocol.decrypt("proof-scalar", s);
-    let s = Scalar::decode(s)?;
+    let s = ct::scalar(s)?;
 
-    // Return true iff I and s are well-formed and I == [s]G - [r0']Q - [r1'µ]Q. Here we compare the
-    // encoded form of I' with the encoded form of I from the signature. This is faster, as encoding
-    // a point is faster than decoding a point.
-    let i_p = (-signer.q).mul64mu_add_mulgen_vartime(r0_p, r1_p, &s);
-    (i == i_p.encode()).then_some(())
+    Some((i.try_into().expect("should be 32 bytes"), r0_p, r1_p, s))
+}
+
+/// Calculate `Σ[k]P` for the given 128-bit scalars and points in variable time using Pippenger's
+/// bucket method, which needs far fewer point additions than multiplying each point separately.
+#[cfg(feature = "std")]
+fn multiscalar_mul_vartime(terms: &[(u128, Point)]) -> Point {
+    // Pick the window width which minimizes the number of point additions, both adding each point
+    // into a bucket and summing the buckets.
+    let width = (1..=16)
+        .min_by_key(|&width| 128usize.div_ceil(width) * (terms.len() + (2 << width)))
+        .expect("should have a window width");
+
+    // Process the scalars one window at a time, starting with the most significant bits.
+    let mut acc = Point::NEUTRAL;
+    let mut buckets = vec![Point::NEUTRAL; (1 << width) - 1];
+    for window in (0..128usize.div_ceil(width)).rev() {
+        acc.set_xdouble(width as u32);
+
+        // Add each point to the bucket for its scalar's digit in this window.
+        buckets.fill(Point::NEUTRAL);
+        for (k, p) in terms {
+            let digit = (k >> (window * width)) as usize & ((1 << width) - 1);
+            if digit > 0 {
+                buckets[digit - 1] += p;
+            }
+        }
+
+        // Calculate Σ[d]B_d with running sums, from the highest digit to the lowest.
+        let mut sum = Point::NEUTRAL;
+        for bucket in buckets.iter().rev() {
+            sum += bucket;
+            acc += sum;
+        }
+    }
+    acc
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use std::io::Cursor;
 
     use assert_matches::assert_matches;
-    use expect_test::expect;
     use rand::SeedableRng;
     use rand_chacha::ChaChaRng;
 
@@ -176,7 +711,7 @@ mod tests {
     fn sign_and_verify() {
         let (_, signer, message, sig) = setup();
         assert_matches!(
-            verify(&signer.pub_key, Cursor::new(message), &sig),
+            verify(&signer.pub_key, "", Cursor::new(message), &sig),
             Ok(()),
             "should have verified a valid signature"
         );
@@ -187,7 +722,7 @@ mod tests {
         let (mut rng, signer, _, sig) = setup();
         let wrong_message = rng.gen::<[u8; 64]>();
         assert_matches!(
-            verify(&signer.pub_key, Cursor::new(wrong_message), &sig),
+            verify(&signer.pub_key, "", Cursor::new(wrong_message), &sig),
             Err(VerifyError::InvalidSignature)
         );
     }
@@ -197,7 +732,7 @@ mod tests {
         let (mut rng, _, message, sig) = setup();
         let wrong_signer = PubKey::random(&mut rng);
         assert_matches!(
-            verify(&wrong_signer, Cursor::new(message), &sig),
+            verify(&wrong_signer, "", Cursor::new(message), &sig),
             Err(VerifyError::InvalidSignature)
         );
     }
@@ -207,15 +742,188 @@ mod tests {
         let (_, signer, message, mut sig) = setup();
         sig.0[22] ^= 1;
         assert_matches!(
-            verify(&signer.pub_key, Cursor::new(message), &sig),
+            verify(&signer.pub_key, "", Cursor::new(message), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn verifying_reader() {
+        let (mut rng, signer, message, sig) = setup();
+
+        // Read the message in small chunks, as from a network stream.
+        let mut reader = VerifyingReader::new(&signer.pub_key, "", Cursor::new(&message), &sig);
+        let mut read = Vec::new();
+        let mut buf = [0u8; 7];
+        loop {
+            let n = reader.read(&mut buf).expect("should read");
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(message, read, "invalid message");
+        assert_matches!(reader.finish(), Ok(()));
+
+        // Any unread part of the message is read by finish.
+        let mut reader = VerifyingReader::new(&signer.pub_key, "", Cursor::new(&message), &sig);
+        reader.read_exact(&mut buf).expect("should read");
+        assert_matches!(reader.finish(), Ok(()));
+
+        let wrong_message = rng.gen::<[u8; 64]>();
+        let reader = VerifyingReader::new(&signer.pub_key, "", Cursor::new(wrong_message), &sig);
+        assert_matches!(reader.finish(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn digest_sign_and_verify() {
+        let (mut rng, signer, message, message_sig) = setup();
+        let digest = Digest::new(&[b"image"], Cursor::new(&message)).expect("should digest");
+        let sig = sign_digest(&mut rng, &signer, &digest);
+
+        assert_matches!(verify_digest(&signer.pub_key, &digest, &sig), Ok(()));
+
+        let wrong_digest = Digest::new(&[b"other"], Cursor::new(&message)).expect("should digest");
+        assert_matches!(
+            verify_digest(&signer.pub_key, &wrong_digest, &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+
+        // Signatures of digests and of messages are not interchangeable.
+        assert_matches!(
+            verify(&signer.pub_key, "", Cursor::new(digest.encode()), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+        let sig = sign(&mut rng, &signer, "", Cursor::new(digest.encode())).expect("should sign");
+        assert_matches!(
+            verify_digest(&signer.pub_key, &digest, &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_matches!(
+            verify_digest(&signer.pub_key, &digest, &message_sig),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn timestamped_sign_and_verify() {
+        let (mut rng, signer, message, message_sig) = setup();
+        let sig = sign_timestamped(&mut rng, &signer, Cursor::new(&message), 1_700_000_000)
+            .expect("signing should be ok");
+        assert_eq!(1_700_000_000, sig.timestamp(), "invalid timestamp");
+        assert_eq!(
+            Some(sig),
+            sig.to_string().parse().ok(),
+            "should round-trip through its string encoding"
+        );
+
+        assert_matches!(
+            verify_timestamped(&signer.pub_key, Cursor::new(&message), &sig),
+            Ok(1_700_000_000)
+        );
+        assert_matches!(
+            verify_timestamped(&signer.pub_key, Cursor::new(b"other"), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+
+        // The timestamp can't be changed.
+        let mut modified = sig.encode();
+        modified[0] ^= 1;
+        let modified = TimestampedSignature::decode(modified).expect("should decode");
+        assert_matches!(
+            verify_timestamped(&signer.pub_key, Cursor::new(&message), &modified),
+            Err(VerifyError::InvalidSignature)
+        );
+
+        // Timestamped and untimestamped signatures are not interchangeable.
+        let untimestamped = Signature::decode(&sig.encode()[8..]).expect("should decode");
+        assert_matches!(
+            verify(&signer.pub_key, "", Cursor::new(&message), &untimestamped),
+            Err(VerifyError::InvalidSignature)
+        );
+        let mut timestamped = [0u8; TIMESTAMPED_SIGNATURE_LEN];
+        timestamped[8..].copy_from_slice(&message_sig.encode());
+        let timestamped = TimestampedSignature::decode(timestamped).expect("should decode");
+        assert_matches!(
+            verify_timestamped(&signer.pub_key, Cursor::new(&message), &timestamped),
             Err(VerifyError::InvalidSignature)
         );
     }
 
     #[test]
+    fn attached_sign_and_verify() {
+        let (mut rng, signer, _, _) = setup();
+        let message = vec![0xAB; BLOCK_LEN * 2 + 22];
+
+        let mut signed = Vec::new();
+        sign_attached(&mut rng, &signer, message.as_slice(), &mut signed)
+            .expect("signing should be ok");
+        assert_eq!(message.len() + SIGNATURE_LEN, signed.len(), "invalid signed message length");
+
+        let mut verified = Vec::new();
+        let len = verify_attached(&signer.pub_key, signed.as_slice(), &mut verified)
+            .expect("verification should be ok");
+        assert_eq!(message.len() as u64, len, "returned/observed message length mismatch");
+        assert_eq!(message, verified, "invalid message");
+    }
+
+    #[test]
+    fn attached_matches_detached() {
+        let (mut rng, signer, message, _) = setup();
+
+        let mut signed = Vec::new();
+        sign_attached(&mut rng, &signer, message.as_slice(), &mut signed)
+            .expect("signing should be ok");
+
+        let (nonce, rest) = signed.split_at(NONCE_LEN);
+        let (msg, det_sig) = rest.split_at(message.len());
+        let mut sig = [0u8; SIGNATURE_LEN];
+        sig[..NONCE_LEN].copy_from_slice(nonce);
+        sig[NONCE_LEN..].copy_from_slice(det_sig);
+        assert_matches!(
+            verify(&signer.pub_key, "", msg, &Signature(sig)),
+            Ok(()),
+            "attached signature should verify as a detached signature"
+        );
+    }
+
+    #[test]
+    fn attached_modified_message() {
+        let (mut rng, signer, message, _) = setup();
+
+        let mut signed = Vec::new();
+        sign_attached(&mut rng, &signer, message.as_slice(), &mut signed)
+            .expect("signing should be ok");
+        signed[NONCE_LEN + 2] ^= 1;
+
+        assert_matches!(
+            verify_attached(&signer.pub_key, signed.as_slice(), io::sink()),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn attached_truncated() {
+        let (mut rng, signer, message, _) = setup();
+
+        let mut signed = Vec::new();
+        sign_attached(&mut rng, &signer, message.as_slice(), &mut signed)
+            .expect("signing should be ok");
+
+        for len in [0, NONCE_LEN - 1, NONCE_LEN + DET_SIGNATURE_LEN - 1, signed.len() - 1] {
+            assert_matches!(
+                verify_attached(&signer.pub_key, &signed[..len], io::sink()),
+                Err(VerifyError::InvalidSignature),
+                "should not verify {len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "jq255e"))]
     fn signature_kat() {
         let (_, _, _, sig) = setup();
-        let expected = expect!["364axhz5SyMk6inmV3H7uLZv1eLBGLHEwapEA5rqpSoyeBTbrFELeQLbfAAfvgMR6RZWcMKjy6DA4zkx3Mr35G8bCf7Qzj5Wwtn2BejAzU7gNF"];
+        let expected = expect_test::expect!["364axhz5SyMk6inmV3H7uLZv1eLBGLHEwapEA5rqpSoyeBTbrFELeQLbfAAfvgMR6RZWcMKjy6DA4zkx3Mr35G8bCf7Qzj5Wwtn2BejAzU7gNF"];
         expected.assert_eq(&sig.to_string());
     }
 
@@ -224,6 +932,7 @@ mod tests {
         let (_, _, _, sig) = setup();
         let decoded = sig.to_string().parse::<Signature>();
         assert_eq!(Ok(sig), decoded, "error parsing signature");
+        assert_eq!(Ok(sig), format!(" {sig}\n").parse(), "error parsing signature with whitespace");
 
         assert_eq!(
             Err(ParseSignatureError::InvalidEncoding(bs58::decode::Error::InvalidCharacter {
@@ -235,11 +944,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn batch_verify() {
+        // Both with few signers, which are combined, and distinct signers, which are not.
+        for signer_count in [2, MIN_BATCH_LEN] {
+            let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+            let signers = (0..signer_count).map(|_| PrivKey::random(&mut rng)).collect::<Vec<_>>();
+            let messages = (0..MIN_BATCH_LEN).map(|_| rng.gen::<[u8; 64]>()).collect::<Vec<_>>();
+            let sigs = messages
+                .iter()
+                .enumerate()
+                .map(|(i, message)| {
+                    sign(&mut rng, &signers[i % signer_count], "", &message[..])
+                        .expect("signing should be ok")
+                })
+                .collect::<Vec<_>>();
+            let mut batch = messages
+                .iter()
+                .zip(&sigs)
+                .enumerate()
+                .map(|(i, (message, sig))| (&signers[i % signer_count].pub_key, &message[..], sig))
+                .collect::<Vec<_>>();
+
+            assert!(verify_batch(&[]), "should have verified an empty batch");
+            assert!(verify_batch(&batch), "should have verified a valid batch");
+
+            batch[3].1 = &messages[4];
+            assert!(
+                !verify_batch(&batch),
+                "should not have verified a batch with a modified message"
+            );
+
+            batch[3].1 = &messages[3];
+            batch[5].0 = &signers[(5 + 1) % signer_count].pub_key;
+            assert!(
+                !verify_batch(&batch),
+                "should not have verified a batch with the wrong signer"
+            );
+        }
+    }
+
+    #[test]
+    fn multiscalar_mul() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        for n in [0, 1, 100, 600] {
+            let terms = (0..n)
+                .map(|_| {
+                    let p = Point::mulgen(&Scalar::decode_reduce(&rng.gen::<[u8; 32]>()));
+                    (rng.gen::<u128>(), p)
+                })
+                .collect::<Vec<_>>();
+            let expected =
+                terms.iter().fold(Point::NEUTRAL, |acc, &(k, p)| acc + p * Scalar::from_u128(k));
+            assert_eq!(
+                expected.encode(),
+                multiscalar_mul_vartime(&terms).encode(),
+                "invalid product of {n} terms"
+            );
+        }
+    }
+
     fn setup() -> (ChaChaRng, PrivKey, Vec<u8>, Signature) {
         let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
         let signer = PrivKey::random(&mut rng);
         let message = rng.gen::<[u8; 64]>();
-        let sig = sign(&mut rng, &signer, Cursor::new(message)).expect("signing should be ok");
+        let sig = sign(&mut rng, &signer, "", Cursor::new(message)).expect("signing should be ok");
         (rng, signer, message.to_vec(), sig)
     }
 }
@@ -0,0 +1,121 @@
+//! Scrubbing of unauthenticated plaintext from seekable outputs.
+//!
+//! Messages are decrypted in a single streaming pass, so plaintext is written before the sender's
+//! signature at the end of the message has been verified. If verification fails, a forged or
+//! truncated message can leave unauthenticated plaintext in the output. A [`ScrubWriter`] tracks
+//! what has been written to a seekable output so it can be overwritten with zeros when decryption
+//! fails.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// The length of the blocks of zeros written when scrubbing.
+const BLOCK_LEN: usize = 64 * 1024;
+
+/// A writer which can overwrite everything written through it with zeros.
+///
+/// ```
+/// use std::io::{Cursor, Write};
+///
+/// use veil::scrub::ScrubWriter;
+///
+/// let mut writer = ScrubWriter::new(Cursor::new(Vec::new())).expect("should seek");
+/// writer.write_all(b"unauthenticated").expect("should write");
+/// writer.scrub().expect("should scrub");
+/// assert_eq!(vec![0u8; 15], writer.into_inner().into_inner());
+/// ```
+#[derive(Debug)]
+pub struct ScrubWriter<W> {
+    inner: W,
+    start: u64,
+    written: u64,
+}
+
+impl<W: Write + Seek> ScrubWriter<W> {
+    /// Creates a new scrubbing writer which writes to `inner` from its current position.
+    ///
+    /// # Errors
+    ///
+    /// If the current position of `inner` cannot be determined, returns an error.
+    pub fn new(mut inner: W) -> io::Result<ScrubWriter<W>> {
+        let start = inner.stream_position()?;
+        Ok(ScrubWriter { inner, start, written: 0 })
+    }
+
+    /// Returns the number of bytes written which have not been scrubbed.
+    #[must_use]
+    pub const fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Overwrites everything written so far with zeros and seeks back to the starting position.
+    ///
+    /// # Errors
+    ///
+    /// If the underlying writer cannot be seeked, written to, or flushed, returns an error. The
+    /// written data may not have been fully scrubbed.
+    pub fn scrub(&mut self) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(self.start))?;
+        let zeros = [0u8; BLOCK_LEN];
+        while self.written > 0 {
+            let n = self.written.min(BLOCK_LEN as u64);
+            let n = usize::try_from(n).expect("block length should be <= usize");
+            self.inner.write_all(&zeros[..n])?;
+            self.written -= n as u64;
+        }
+        self.inner.flush()?;
+        self.inner.seek(SeekFrom::Start(self.start))?;
+        Ok(())
+    }
+
+    /// Unwraps this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ScrubWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += u64::try_from(n).expect("usize should be <= u64");
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn scrub() {
+        let mut writer = ScrubWriter::new(Cursor::new(Vec::new())).expect("should seek");
+        writer.write_all(&[0xAB; BLOCK_LEN + 100]).expect("should write");
+        assert_eq!(BLOCK_LEN as u64 + 100, writer.written());
+
+        writer.scrub().expect("should scrub");
+        assert_eq!(0, writer.written());
+
+        let cursor = writer.into_inner();
+        assert_eq!(0, cursor.position(), "should seek back to the start");
+        assert_eq!(vec![0u8; BLOCK_LEN + 100], cursor.into_inner());
+    }
+
+    #[test]
+    fn scrub_from_offset() {
+        let mut cursor = Cursor::new(b"header".to_vec());
+        cursor.seek(SeekFrom::End(0)).expect("should seek");
+
+        let mut writer = ScrubWriter::new(cursor).expect("should seek");
+        writer.write_all(b"plaintext").expect("should write");
+        writer.scrub().expect("should scrub");
+        writer.write_all(b"more").expect("should write");
+
+        let cursor = writer.into_inner();
+        assert_eq!(b"headermore\0\0\0\0\0", cursor.get_ref().as_slice());
+    }
+}
@@ -0,0 +1,545 @@
+//! Random access to the plaintext of seekable messages.
+//!
+//! A message encrypted with [`EncryptOptions::seekable`](crate::EncryptOptions::seekable) splits
+//! its plaintext into 64 KiB chunks which are sealed independently, using keys derived from the
+//! DEK and each chunk's index, and ends with an encrypted index containing the plaintext length.
+//! [`open`] decrypts the receiver's header, skips the padding, and reads the index, after which a
+//! [`SeekableMessage`] decrypts only the chunks needed for each read.
+//!
+//! Chunks read this way are authenticated with the DEK, so they can't be forged by anyone but the
+//! sender and the message's other receivers. Only decrypting the entire message with
+//! [`PrivateKey::decrypt`](crate::PrivateKey::decrypt) verifies the sender's signature.
+//!
+//! Because each chunk is sealed independently at a fixed offset, a damaged chunk doesn't prevent
+//! the chunks after it from being decrypted. [`SeekableMessage::recover`] decrypts every intact
+//! chunk of a message, writing zeros in place of the chunks which can't be opened and reporting
+//! the plaintext ranges which were lost.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    slice,
+};
+
+use lockstitch::{Protocol, TAG_LEN};
+
+use crate::{
+    mres::{self, BLOCK_LEN, ENC_BLOCK_LEN, ENC_INDEX_LEN, SEEKABLE_FORMAT_VERSION},
+    schnorr::DET_SIGNATURE_LEN,
+    sres::NONCE_LEN,
+    DecryptError, KemBackend, PublicKey,
+};
+
+/// A seekable message, which decrypts chunks of its plaintext as they're read.
+///
+/// [`SeekableMessage`] implements [`Read`] and [`Seek`] over the plaintext. Errors decrypting a
+/// chunk are returned as [`io::ErrorKind::InvalidData`] errors wrapping a [`DecryptError`].
+#[derive(Debug)]
+pub struct SeekableMessage<R> {
+    reader: R,
+    chunks: Protocol,
+    payload_offset: u64,
+    len: u64,
+    pos: u64,
+    chunk: Option<u64>,
+    buf: Vec<u8>,
+}
+
+/// The outcome of recovering a damaged seekable message with [`SeekableMessage::recover`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovery {
+    /// The number of bytes of plaintext written, including the zeros written in place of lost
+    /// chunks.
+    pub written: u64,
+    /// The ranges of the plaintext which could not be decrypted and were written as zeros, in
+    /// order and with adjacent ranges merged.
+    pub lost: Vec<Range<u64>>,
+}
+
+impl Recovery {
+    /// Returns `true` if every chunk of the plaintext was decrypted.
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        self.lost.is_empty()
+    }
+}
+
+/// Decrypts the header of the seekable message in `reader` with the given receiver's private key
+/// and reads the message's index, returning a [`SeekableMessage`] from which ranges of the
+/// plaintext can be read.
+///
+/// The message may begin anywhere in `reader`; it is read from the current position to the end.
+///
+/// # Errors
+///
+/// If the message was not sent by the sender, was not encrypted for the receiver, or was encrypted
+/// with different associated data, returns [`DecryptError::NotAReceiver`]. If the message was not
+/// encrypted as a seekable message, returns [`DecryptError::NotSeekable`]. If it has been
+/// modified, returns [`DecryptError::Corrupt`], and if it is incomplete, returns
+/// [`DecryptError::Truncated`]. If there was an error reading from `reader`, returns
+/// [`DecryptError::ReadIo`].
+pub fn open<R: Read + Seek>(
+    receiver: &impl KemBackend,
+    mut reader: R,
+    sender: &PublicKey,
+    associated_data: &[u8],
+) -> Result<SeekableMessage<R>, DecryptError> {
+    // Initialize a protocol and mix the sender's public key and associated data into it.
+    let mut mres = Protocol::new("veil.mres");
+    mres.mix("sender", &sender.0.encoded);
+    mres.mix("associated-data", associated_data);
+
+    // Read the nonce and mix it into the protocol.
+    let mut nonce = [0u8; NONCE_LEN];
+    reader.read_exact(&mut nonce).map_err(map_eof)?;
+    mres.mix("nonce", &nonce);
+
+    // Find and decrypt a header, reading all of the headers and none of the padding.
+    let (headers, (_, _, _, header)) =
+        mres::find_header(mres, &mut reader, slice::from_ref(receiver), None, &sender.0, u64::MAX)?;
+    if header.version != SEEKABLE_FORMAT_VERSION {
+        return Err(DecryptError::NotSeekable);
+    }
+    let chunks = mres::seekable_protocol(&headers, &header.dek);
+
+    // Skip the padding and find the index at the end of the message, before the signature.
+    let payload_offset = reader
+        .stream_position()
+        .map_err(DecryptError::ReadIo)?
+        .checked_add(header.padding)
+        .ok_or(DecryptError::Truncated)?;
+    let end = reader.seek(SeekFrom::End(0)).map_err(DecryptError::ReadIo)?;
+    let index_offset = end
+        .checked_sub((ENC_INDEX_LEN + DET_SIGNATURE_LEN) as u64)
+        .filter(|&offset| offset >= payload_offset + TAG_LEN as u64)
+        .ok_or(DecryptError::Truncated)?;
+
+    // Read and open the index, then check that the payload is the length the index says it is.
+    let mut enc_index = [0u8; ENC_INDEX_LEN];
+    reader.seek(SeekFrom::Start(index_offset)).map_err(DecryptError::ReadIo)?;
+    reader.read_exact(&mut enc_index).map_err(map_eof)?;
+    let len = mres::open_index(&chunks, &mut enc_index).ok_or(DecryptError::Corrupt)?;
+    let payload_len = index_offset - payload_offset;
+    if len > payload_len || mres::payload_len(len) != payload_len {
+        return Err(DecryptError::Corrupt);
+    }
+
+    Ok(SeekableMessage {
+        reader,
+        chunks,
+        payload_offset,
+        len,
+        pos: 0,
+        chunk: None,
+        buf: Vec::with_capacity(ENC_BLOCK_LEN),
+    })
+}
+
+impl<R: Read + Seek> SeekableMessage<R> {
+    /// Returns the length of the plaintext in bytes.
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the plaintext is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Decrypts the plaintext starting at `offset` into `buf`, returning the number of bytes read.
+    /// Fewer than `buf.len()` bytes are read only if the end of the plaintext is reached.
+    ///
+    /// # Errors
+    ///
+    /// If a chunk has been modified, returns [`DecryptError::Corrupt`], and if it is incomplete,
+    /// returns [`DecryptError::Truncated`]. If there was an error reading the ciphertext, returns
+    /// [`DecryptError::ReadIo`].
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, DecryptError> {
+        let mut n = 0;
+        while n < buf.len() {
+            let pos = offset.saturating_add(n as u64);
+            if pos >= self.len {
+                break;
+            }
+
+            // Decrypt the chunk containing the position and copy as much of it as fits.
+            let chunk = self.chunk(pos / BLOCK_LEN as u64)?;
+            let start = (pos % BLOCK_LEN as u64) as usize;
+            let len = (chunk.len() - start).min(buf.len() - n);
+            buf[n..n + len].copy_from_slice(&chunk[start..start + len]);
+            n += len;
+        }
+        Ok(n)
+    }
+
+    /// Decrypts every chunk of the plaintext in order and writes it to `writer`, writing zeros in
+    /// place of any chunk which has been modified or is missing instead of stopping. Returns the
+    /// plaintext ranges which were lost, so the output has the same length and offsets as the
+    /// original plaintext.
+    ///
+    /// Like any range read from a [`SeekableMessage`], the recovered plaintext is authenticated by
+    /// the DEK but not by the sender's signature.
+    ///
+    /// # Errors
+    ///
+    /// If there was an error reading the ciphertext, returns [`DecryptError::ReadIo`]. If there was
+    /// an error writing the plaintext, returns [`DecryptError::WriteIo`].
+    pub fn recover(&mut self, mut writer: impl Write) -> Result<Recovery, DecryptError> {
+        let mut recovery = Recovery { written: 0, lost: Vec::new() };
+        for idx in 0..self.len.div_ceil(BLOCK_LEN as u64) {
+            let start = idx * BLOCK_LEN as u64;
+            match self.chunk(idx) {
+                Ok(chunk) => writer.write_all(chunk).map_err(DecryptError::WriteIo)?,
+                Err(DecryptError::Corrupt | DecryptError::Truncated) => {
+                    // Write zeros in place of the chunk and record it as lost, extending the
+                    // previous range if it was lost too.
+                    let end = (start + BLOCK_LEN as u64).min(self.len);
+                    io::copy(&mut io::repeat(0).take(end - start), &mut writer)
+                        .map_err(DecryptError::WriteIo)?;
+                    match recovery.lost.last_mut() {
+                        Some(range) if range.end == start => range.end = end,
+                        _ => recovery.lost.push(start..end),
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+            recovery.written = (start + BLOCK_LEN as u64).min(self.len);
+        }
+        writer.flush().map_err(DecryptError::WriteIo)?;
+        Ok(recovery)
+    }
+
+    /// Returns the underlying reader.
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns the plaintext of the chunk with the given index, decrypting it unless it was the
+    /// last chunk decrypted.
+    fn chunk(&mut self, idx: u64) -> Result<&[u8], DecryptError> {
+        if self.chunk != Some(idx) {
+            self.chunk = None;
+
+            // Read the encrypted chunk. Every chunk but the last is full-sized.
+            let len = (self.len - idx * BLOCK_LEN as u64).min(BLOCK_LEN as u64) as usize;
+            self.buf.resize(len + TAG_LEN, 0);
+            self.reader
+                .seek(SeekFrom::Start(self.payload_offset + idx * ENC_BLOCK_LEN as u64))
+                .map_err(DecryptError::ReadIo)?;
+            self.reader.read_exact(&mut self.buf).map_err(map_eof)?;
+
+            // Open the chunk with its own protocol.
+            mres::chunk_protocol(&self.chunks, idx)
+                .open("chunk", &mut self.buf)
+                .ok_or(DecryptError::Corrupt)?;
+            self.chunk = Some(idx);
+        }
+        Ok(&self.buf[..self.buf.len() - TAG_LEN])
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableMessage<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_at(self.pos, buf).map_err(|e| match e {
+            DecryptError::ReadIo(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        })?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableMessage<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Map an unexpected end of the reader to [`DecryptError::Truncated`].
+fn map_eof(e: io::Error) -> DecryptError {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        DecryptError::Truncated
+    } else {
+        DecryptError::ReadIo(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use assert_matches::assert_matches;
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{EncryptOptions, Padding, PrivateKey};
+
+    #[test]
+    fn read_ranges() {
+        let (_, sender, receiver, plaintext, ciphertext) = setup(200_000);
+
+        let mut message = open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[])
+            .expect("should open");
+        assert_eq!(200_000, message.len());
+
+        for (offset, len) in [(0, 10), (65_530, 20), (100_000, 100_000), (199_990, 100)] {
+            let mut buf = vec![0u8; len];
+            let n = message.read_at(offset, &mut buf).expect("should read");
+            let end = (offset as usize + len).min(plaintext.len());
+            assert_eq!(&plaintext[offset as usize..end], &buf[..n], "invalid range at {offset}");
+        }
+
+        let mut buf = [0u8; 10];
+        assert_eq!(0, message.read_at(200_000, &mut buf).expect("should read"));
+    }
+
+    #[test]
+    fn read_and_seek() {
+        let (_, sender, receiver, plaintext, ciphertext) = setup(200_000);
+
+        let mut message = open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[])
+            .expect("should open");
+        message.seek(SeekFrom::End(-1000)).expect("should seek");
+        let mut tail = Vec::new();
+        message.read_to_end(&mut tail).expect("should read");
+        assert_eq!(&plaintext[199_000..], tail.as_slice());
+
+        message.seek(SeekFrom::Start(0)).expect("should seek");
+        let mut all = Vec::new();
+        message.read_to_end(&mut all).expect("should read");
+        assert_eq!(plaintext, all);
+    }
+
+    #[test]
+    fn full_decryption() {
+        let (_, sender, receiver, plaintext, ciphertext) = setup(200_000);
+
+        let mut dst = Vec::new();
+        receiver
+            .decrypt(ciphertext.as_slice(), &mut dst, &sender.public_key())
+            .expect("should decrypt");
+        assert_eq!(plaintext, dst);
+    }
+
+    #[test]
+    fn empty_message() {
+        let (_, sender, receiver, _, ciphertext) = setup(0);
+
+        let message = open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[])
+            .expect("should open");
+        assert!(message.is_empty());
+        assert_eq!(
+            Vec::<u8>::new(),
+            receiver.open(&sender.public_key(), &ciphertext).expect("should decrypt")
+        );
+    }
+
+    #[test]
+    fn automatic_padding() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receivers = [PrivateKey::random(&mut rng).public_key()];
+        let options = EncryptOptions::new(&receivers)
+            .seekable()
+            .padding_policy(Padding::Automatic { plaintext_len: 1_000 });
+
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(&mut rng, &[0u8; 1_000][..], &mut ciphertext, &options)
+            .expect("should encrypt");
+        let len = ciphertext.len() as u64;
+        assert_eq!(crate::padme::padded_len(len), len, "message should have a PADMÉ length");
+    }
+
+    #[test]
+    fn not_seekable() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let ciphertext =
+            sender.seal(&mut rng, &[receiver.public_key()], b"a message").expect("should seal");
+
+        assert_matches!(
+            open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[]),
+            Err(DecryptError::NotSeekable)
+        );
+    }
+
+    #[test]
+    fn modified_chunk() {
+        let (_, sender, receiver, _, mut ciphertext) = setup(200_000);
+        let first_chunk = ciphertext.len()
+            - DET_SIGNATURE_LEN
+            - ENC_INDEX_LEN
+            - mres::payload_len(200_000) as usize;
+        ciphertext[first_chunk + ENC_BLOCK_LEN + 10] ^= 1;
+
+        let mut message = open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[])
+            .expect("should open");
+        let mut buf = [0u8; 10];
+        message.read_at(0, &mut buf).expect("unmodified chunks should be readable");
+        assert_matches!(message.read_at(BLOCK_LEN as u64, &mut buf), Err(DecryptError::Corrupt));
+
+        assert_matches!(
+            receiver.decrypt(ciphertext.as_slice(), io::sink(), &sender.public_key()),
+            Err(DecryptError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn recover_modified_chunks() {
+        let (_, sender, receiver, plaintext, mut ciphertext) = setup(200_000);
+        let first_chunk = ciphertext.len()
+            - DET_SIGNATURE_LEN
+            - ENC_INDEX_LEN
+            - mres::payload_len(200_000) as usize;
+        ciphertext[first_chunk + ENC_BLOCK_LEN + 10] ^= 1;
+        ciphertext[first_chunk + 3 * ENC_BLOCK_LEN + 10] ^= 1;
+
+        let mut message = open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[])
+            .expect("should open");
+        let mut dst = Vec::new();
+        let recovery = message.recover(&mut dst).expect("should recover");
+        assert_eq!(
+            Recovery { written: 200_000, lost: vec![65_536..131_072, 196_608..200_000] },
+            recovery
+        );
+        assert!(!recovery.is_complete());
+
+        // The intact chunks are at their original offsets and the lost chunks are zeroed.
+        assert_eq!(plaintext.len(), dst.len());
+        assert_eq!(&plaintext[..65_536], &dst[..65_536]);
+        assert_eq!(&plaintext[131_072..196_608], &dst[131_072..196_608]);
+        assert!(dst[65_536..131_072].iter().chain(&dst[196_608..]).all(|&b| b == 0));
+    }
+
+    #[test]
+    fn recover_intact_message() {
+        let (_, sender, receiver, plaintext, ciphertext) = setup(200_000);
+
+        let mut message = open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[])
+            .expect("should open");
+        let mut dst = Vec::new();
+        let recovery = message.recover(&mut dst).expect("should recover");
+        assert!(recovery.is_complete());
+        assert_eq!(plaintext, dst);
+    }
+
+    #[test]
+    fn modified_index() {
+        let (_, sender, receiver, _, mut ciphertext) = setup(200_000);
+        let index = ciphertext.len() - DET_SIGNATURE_LEN - ENC_INDEX_LEN;
+        ciphertext[index] ^= 1;
+
+        assert_matches!(
+            open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[]),
+            Err(DecryptError::Corrupt)
+        );
+        assert_matches!(
+            receiver.decrypt(ciphertext.as_slice(), io::sink(), &sender.public_key()),
+            Err(DecryptError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn truncated_chunks() {
+        let (_, sender, receiver, _, ciphertext) = setup(200_000);
+        let first_chunk = ciphertext.len()
+            - DET_SIGNATURE_LEN
+            - ENC_INDEX_LEN
+            - mres::payload_len(200_000) as usize;
+
+        // A message which ends before its index is truncated.
+        assert_matches!(
+            open(
+                &receiver,
+                Cursor::new(&ciphertext[..first_chunk + 10]),
+                &sender.public_key(),
+                &[]
+            ),
+            Err(DecryptError::Truncated)
+        );
+
+        // Removing a chunk from the middle changes the payload length.
+        let mut ciphertext = ciphertext;
+        ciphertext.drain(first_chunk..first_chunk + ENC_BLOCK_LEN);
+        assert_matches!(
+            open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[]),
+            Err(DecryptError::Corrupt)
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_round_trip() {
+        use crate::asyncio::tests::block_on;
+
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let plaintext = vec![7u8; 200_000];
+        let receivers = [receiver.public_key()];
+        let options = EncryptOptions::new(&receivers).seekable();
+
+        let mut ciphertext = Vec::new();
+        block_on(sender.encrypt_async(&mut rng, plaintext.as_slice(), &mut ciphertext, &options))
+            .expect("should encrypt");
+
+        let mut message = open(&receiver, Cursor::new(&ciphertext), &sender.public_key(), &[])
+            .expect("should open");
+        let mut buf = [0u8; 100];
+        assert_eq!(100, message.read_at(150_000, &mut buf).expect("should read"));
+        assert_eq!([7u8; 100], buf);
+
+        let mut dst = Vec::new();
+        block_on(receiver.decrypt_async(
+            ciphertext.as_slice(),
+            &mut dst,
+            &sender.public_key(),
+            &[],
+        ))
+        .expect("should decrypt");
+        assert_eq!(plaintext, dst);
+    }
+
+    #[test]
+    fn wrong_receiver() {
+        let (mut rng, sender, _, _, ciphertext) = setup(64);
+        let wrong_receiver = PrivateKey::random(&mut rng);
+
+        assert_matches!(
+            open(&wrong_receiver, Cursor::new(&ciphertext), &sender.public_key(), &[]),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    fn setup(n: usize) -> (ChaChaRng, PrivateKey, PrivateKey, Vec<u8>, Vec<u8>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+
+        let mut plaintext = vec![0u8; n];
+        rng.fill_bytes(&mut plaintext);
+
+        let receivers = [receiver.public_key()];
+        let options = EncryptOptions::new(&receivers).fakes(3).padding(80).seekable();
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(&mut rng, plaintext.as_slice(), &mut ciphertext, &options)
+            .expect("should encrypt");
+
+        (rng, sender, receiver, plaintext, ciphertext)
+    }
+}
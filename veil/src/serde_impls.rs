@@ -0,0 +1,199 @@
+//! `serde` integration for public keys, signatures, and digests.
+//!
+//! Human-readable formats (e.g. JSON) represent values as base58 strings, the same as their
+//! `Display` and `FromStr` implementations. Binary formats (e.g. CBOR) represent values as byte
+//! strings of their stable binary encodings: [`PublicKey::encode`], [`Signature::encode`], and
+//! [`Digest::encode`](crate::Digest::encode).
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display, Formatter},
+    marker::PhantomData,
+    str::FromStr,
+};
+
+use serde::{
+    de::{self, SeqAccess, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{schnorr::SIGNATURE_LEN, PublicKey, Signature};
+
+/// The maximum length of a binary encoding, past which sequences of bytes are rejected.
+const MAX_LEN: usize = SIGNATURE_LEN;
+
+/// A value with both a base58 string representation and a fixed-length binary encoding.
+trait Encoded: Display + FromStr + Sized {
+    /// A description of the value's expected form.
+    const EXPECTING: &'static str;
+
+    /// Decodes the value from its binary encoding.
+    fn decode_bytes(b: &[u8]) -> Option<Self>;
+}
+
+impl Encoded for PublicKey {
+    const EXPECTING: &'static str = "a public key";
+
+    fn decode_bytes(b: &[u8]) -> Option<Self> {
+        PublicKey::decode(b)
+    }
+}
+
+impl Encoded for Signature {
+    const EXPECTING: &'static str = "a signature";
+
+    fn decode_bytes(b: &[u8]) -> Option<Self> {
+        Signature::decode(b)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Encoded for crate::Digest {
+    const EXPECTING: &'static str = "a digest";
+
+    fn decode_bytes(b: &[u8]) -> Option<Self> {
+        crate::Digest::decode(b)
+    }
+}
+
+fn serialize<S: Serializer>(
+    serializer: S,
+    value: &impl Display,
+    encoded: &[u8],
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.collect_str(value)
+    } else {
+        serializer.serialize_bytes(encoded)
+    }
+}
+
+fn deserialize<'de, T: Encoded, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_str(EncodedVisitor(PhantomData))
+    } else {
+        deserializer.deserialize_bytes(EncodedVisitor(PhantomData))
+    }
+}
+
+struct EncodedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Encoded> Visitor<'de> for EncodedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(T::EXPECTING)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        T::decode_bytes(v).ok_or_else(|| E::invalid_value(Unexpected::Bytes(v), &self))
+    }
+
+    // Some binary formats represent byte strings as sequences of integers.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut b = Vec::with_capacity(MAX_LEN);
+        while let Some(v) = seq.next_element()? {
+            if b.len() == MAX_LEN {
+                return Err(de::Error::invalid_length(b.len() + 1, &self));
+            }
+            b.push(v);
+        }
+        self.visit_bytes(&b)
+    }
+}
+
+impl Serialize for PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(serializer, self, &self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer)
+    }
+}
+
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(serializer, self, &self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Serialize for crate::Digest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(serializer, self, self.encode())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for crate::Digest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use serde_test::{assert_de_tokens_error, assert_tokens, Configure, Token};
+
+    use super::*;
+    use crate::{Digest, PrivateKey};
+
+    #[test]
+    fn public_key() {
+        let pk = PrivateKey::random(ChaChaRng::seed_from_u64(0xDEADBEEF)).public_key();
+        assert_tokens(&pk.readable(), &[Token::Str(pk.to_string().leak())]);
+        assert_tokens(&pk.compact(), &[Token::Bytes(pk.encode().to_vec().leak())]);
+    }
+
+    #[test]
+    fn signature() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let sig = key.sign(&mut rng, &b"a message"[..]).expect("should sign");
+        assert_tokens(&sig.readable(), &[Token::Str(sig.to_string().leak())]);
+        assert_tokens(&sig.compact(), &[Token::Bytes(sig.encode().to_vec().leak())]);
+    }
+
+    #[test]
+    fn digest() {
+        let digest = Digest::new(&["a"], &b"a message"[..]).expect("should digest");
+        assert_tokens(&digest.readable(), &[Token::Str(digest.to_string().leak())]);
+        assert_tokens(&digest.compact(), &[Token::Bytes(digest.encode().to_vec().leak())]);
+    }
+
+    #[test]
+    fn byte_sequences() {
+        let digest = Digest::new(&["a"], &b"a message"[..]).expect("should digest");
+        let mut tokens = vec![Token::Seq { len: Some(32) }];
+        tokens.extend(digest.encode().iter().map(|&b| Token::U8(b)));
+        tokens.push(Token::SeqEnd);
+        serde_test::assert_de_tokens(&digest.compact(), &tokens);
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_de_tokens_error::<serde_test::Readable<PublicKey>>(
+            &[Token::Str("woot")],
+            "invalid value: string \"woot\", expected a public key",
+        );
+        assert_de_tokens_error::<serde_test::Compact<Signature>>(
+            &[Token::Bytes(&[1, 2, 3])],
+            "invalid value: byte array, expected a signature",
+        );
+    }
+}
@@ -0,0 +1,372 @@
+//! Interactive two-party sessions with forward secrecy.
+//!
+//! A session is established with a two-message handshake. The initiator sends a `veil.sres`
+//! ciphertext carrying its ephemeral public key to the responder, and the responder replies with
+//! one carrying its own. Both parties then derive a pair of chain keys, one per direction, from the
+//! ephemeral Diffie-Hellman shared secret and the handshake transcript.
+//!
+//! Each message is sealed with a protocol keyed with the sending direction's chain key, which is
+//! then replaced with a new chain key derived from the same protocol. Once a message has been sent
+//! or received, the chain key which protected it is gone, so a compromise of either party's state
+//! or private key does not reveal earlier messages. Messages must be decrypted in the order they
+//! were encrypted; a message which can't be decrypted doesn't advance the chain.
+//!
+//! ```
+//! use rand::rngs::OsRng;
+//! use veil::{session::Session, PrivateKey};
+//!
+//! let alice = PrivateKey::random(OsRng);
+//! let bea = PrivateKey::random(OsRng);
+//!
+//! // Alice starts a handshake with Bea, and Bea responds.
+//! let (handshake, request) = Session::initiate(OsRng, &alice, &bea.public_key());
+//! let (mut bea_session, response) =
+//!     Session::respond(OsRng, &bea, &alice.public_key(), &request).expect("should respond");
+//! let mut alice_session = handshake.finish(&alice, &response).expect("should finish");
+//!
+//! // Alice and Bea exchange messages.
+//! let ciphertext = alice_session.encrypt_message(b"hello, Bea");
+//! assert_eq!(b"hello, Bea".to_vec(), bea_session.decrypt_message(&ciphertext).unwrap());
+//! let ciphertext = bea_session.encrypt_message(b"hello, Alice");
+//! assert_eq!(b"hello, Alice".to_vec(), alice_session.decrypt_message(&ciphertext).unwrap());
+//! ```
+
+use std::fmt::{self, Debug, Formatter};
+
+use lockstitch::{Protocol, TAG_LEN};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::{PrivKey, PubKey},
+    sres, DecryptError, PrivateKey, PublicKey,
+};
+
+/// The length of a handshake message, in bytes.
+pub const HANDSHAKE_LEN: usize = sres::NONCE_LEN + sres::OVERHEAD;
+
+/// The number of bytes added to each plaintext by [`Session::encrypt_message`].
+pub const OVERHEAD: usize = TAG_LEN;
+
+/// The length of a chain key, in bytes.
+const CHAIN_KEY_LEN: usize = 32;
+
+/// An established session with another party.
+pub struct Session {
+    send: [u8; CHAIN_KEY_LEN],
+    receive: [u8; CHAIN_KEY_LEN],
+}
+
+impl Session {
+    /// Starts a handshake with the owner of `responder`, returning the initiator's handshake state
+    /// and the handshake message to send to the responder.
+    #[must_use]
+    pub fn initiate(
+        mut rng: impl Rng + CryptoRng,
+        private_key: &PrivateKey,
+        responder: &PublicKey,
+    ) -> (Handshake, Vec<u8>) {
+        let ephemeral = PrivKey::random(&mut rng);
+        let nonce = rng.gen::<[u8; sres::NONCE_LEN]>();
+        let request = handshake_message(private_key, &ephemeral, &responder.0, &nonce, &nonce);
+        let handshake = Handshake {
+            initiator: private_key.0.pub_key,
+            responder: responder.0,
+            ephemeral,
+            request: request.clone(),
+        };
+        (handshake, request)
+    }
+
+    /// Responds to a handshake message from the owner of `initiator`, returning the established
+    /// session and the handshake message to send back to the initiator.
+    ///
+    /// # Errors
+    ///
+    /// If the handshake message was not sent to this private key by the owner of `initiator`,
+    /// returns [`DecryptError::NotAReceiver`].
+    pub fn respond(
+        mut rng: impl Rng + CryptoRng,
+        private_key: &PrivateKey,
+        initiator: &PublicKey,
+        request: &[u8],
+    ) -> Result<(Session, Vec<u8>), DecryptError> {
+        let (nonce_i, q_ie) = open_handshake_message(private_key, &initiator.0, request, &[])?;
+
+        // Bind the response to the request by including the initiator's nonce.
+        let ephemeral = PrivKey::random(&mut rng);
+        let nonce_r = rng.gen::<[u8; sres::NONCE_LEN]>();
+        let response = handshake_message(
+            private_key,
+            &ephemeral,
+            &initiator.0,
+            &nonce_r,
+            &[nonce_i.as_slice(), &nonce_r].concat(),
+        );
+
+        let (i_chain, r_chain) = chain_keys(
+            &initiator.0,
+            &private_key.0.pub_key,
+            request,
+            &response,
+            &(ephemeral.d * q_ie.q).encode(),
+        );
+        Ok((Session { send: r_chain, receive: i_chain }, response))
+    }
+
+    /// Encrypts the given plaintext and returns the ciphertext, which is [`OVERHEAD`] bytes longer.
+    #[must_use]
+    pub fn encrypt_message(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let mut message = ratchet(&mut self.send);
+        let mut ciphertext = Vec::with_capacity(plaintext.len() + OVERHEAD);
+        ciphertext.extend_from_slice(plaintext);
+        ciphertext.resize(plaintext.len() + OVERHEAD, 0);
+        message.seal("message", &mut ciphertext);
+        ciphertext
+    }
+
+    /// Decrypts the next message from the other party, if possible, and returns the plaintext.
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext is shorter than [`OVERHEAD`], returns [`DecryptError::Truncated`]. If it
+    /// has been modified, replayed, or received out of order, returns [`DecryptError::Corrupt`].
+    pub fn decrypt_message(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        if ciphertext.len() < OVERHEAD {
+            return Err(DecryptError::Truncated);
+        }
+
+        // Ratchet a copy of the chain key, so it only advances if the message is authentic.
+        let mut receive = self.receive;
+        let mut message = ratchet(&mut receive);
+        let mut plaintext = ciphertext.to_vec();
+        let len = message.open("message", &mut plaintext).ok_or(DecryptError::Corrupt)?.len();
+        plaintext.truncate(len);
+        self.receive = receive;
+        Ok(plaintext)
+    }
+}
+
+impl Debug for Session {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Session(..)")
+    }
+}
+
+/// The initiator's state while waiting for the responder's handshake message.
+pub struct Handshake {
+    initiator: PubKey,
+    responder: PubKey,
+    ephemeral: PrivKey,
+    request: Vec<u8>,
+}
+
+impl Handshake {
+    /// Finishes the handshake with the responder's handshake message, returning the established
+    /// session.
+    ///
+    /// # Errors
+    ///
+    /// If the handshake message was not sent to this private key by the responder in response to
+    /// this handshake, returns [`DecryptError::NotAReceiver`].
+    pub fn finish(
+        self,
+        private_key: &PrivateKey,
+        response: &[u8],
+    ) -> Result<Session, DecryptError> {
+        let nonce_i = &self.request[..sres::NONCE_LEN];
+        let (_, q_re) = open_handshake_message(private_key, &self.responder, response, nonce_i)?;
+
+        let (i_chain, r_chain) = chain_keys(
+            &self.initiator,
+            &self.responder,
+            &self.request,
+            response,
+            &(self.ephemeral.d * q_re.q).encode(),
+        );
+        Ok(Session { send: i_chain, receive: r_chain })
+    }
+}
+
+impl Debug for Handshake {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handshake")
+            .field("initiator", &self.initiator)
+            .field("responder", &self.responder)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Encrypts a handshake message carrying the given ephemeral public key, prefixed with the given
+/// nonce and bound to the given `veil.sres` nonce.
+fn handshake_message(
+    private_key: &PrivateKey,
+    ephemeral: &PrivKey,
+    receiver: &PubKey,
+    nonce: &[u8; sres::NONCE_LEN],
+    sres_nonce: &[u8],
+) -> Vec<u8> {
+    let mut message = vec![0u8; HANDSHAKE_LEN];
+    let (out_nonce, out_ciphertext) = message.split_at_mut(sres::NONCE_LEN);
+    out_nonce.copy_from_slice(nonce);
+    sres::encrypt(private_key, ephemeral, receiver, sres_nonce, &[], out_ciphertext);
+    message
+}
+
+/// Decrypts a handshake message from the given sender, with its nonce prefixed by the given bytes,
+/// returning its nonce and the sender's ephemeral public key.
+fn open_handshake_message(
+    private_key: &PrivateKey,
+    sender: &PubKey,
+    message: &[u8],
+    nonce_prefix: &[u8],
+) -> Result<([u8; sres::NONCE_LEN], PubKey), DecryptError> {
+    if message.len() != HANDSHAKE_LEN {
+        return Err(DecryptError::NotAReceiver);
+    }
+    let (nonce, ciphertext) = message.split_at(sres::NONCE_LEN);
+    let nonce = <[u8; sres::NONCE_LEN]>::try_from(nonce).expect("should be nonce-sized");
+    let mut ciphertext = ciphertext.to_vec();
+    let (q_e, _) =
+        sres::decrypt(private_key, sender, &[nonce_prefix, &nonce].concat(), &mut ciphertext)
+            .ok_or(DecryptError::NotAReceiver)?;
+    Ok((nonce, q_e))
+}
+
+/// Derives the initiator's and responder's chain keys from the handshake.
+fn chain_keys(
+    initiator: &PubKey,
+    responder: &PubKey,
+    request: &[u8],
+    response: &[u8],
+    ephemeral_ecdh: &[u8],
+) -> ([u8; CHAIN_KEY_LEN], [u8; CHAIN_KEY_LEN]) {
+    // Initialize a protocol.
+    let mut session = Protocol::new("veil.session");
+
+    // Mix both parties' public keys into the protocol.
+    session.mix("initiator", &initiator.encoded);
+    session.mix("responder", &responder.encoded);
+
+    // Mix the handshake transcript into the protocol.
+    session.mix("request", request);
+    session.mix("response", response);
+
+    // Mix the ephemeral ECDH shared secret into the protocol. This makes all following outputs
+    // forward-secure: once both ephemeral private keys are discarded, neither party's private key
+    // can recover them.
+    session.mix("ephemeral-ecdh", ephemeral_ecdh);
+
+    // Derive a chain key for each direction.
+    (session.derive_array("initiator-chain-key"), session.derive_array("responder-chain-key"))
+}
+
+/// Creates a protocol for the next message with the given chain key and replaces the chain key
+/// with the next one.
+fn ratchet(chain_key: &mut [u8; CHAIN_KEY_LEN]) -> Protocol {
+    let mut message = Protocol::new("veil.session.message");
+    message.mix("chain-key", chain_key);
+    *chain_key = message.derive_array("next-chain-key");
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (_, _, _, mut a, mut b) = setup();
+
+        for i in 0..3 {
+            let plaintext = format!("message {i} from a");
+            let ciphertext = a.encrypt_message(plaintext.as_bytes());
+            assert_eq!(plaintext.len() + OVERHEAD, ciphertext.len());
+            assert_eq!(
+                plaintext.as_bytes(),
+                b.decrypt_message(&ciphertext).expect("should decrypt")
+            );
+        }
+
+        let ciphertext = b.encrypt_message(b"a reply");
+        assert_eq!(b"a reply".as_slice(), a.decrypt_message(&ciphertext).expect("should decrypt"));
+    }
+
+    #[test]
+    fn wrong_responder() {
+        let (mut rng, a, b, _, _) = setup();
+        let c = PrivateKey::random(&mut rng);
+
+        let (_, request) = Session::initiate(&mut rng, &a, &b.public_key());
+        assert_matches!(
+            Session::respond(&mut rng, &c, &a.public_key(), &request),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn replayed_response() {
+        let (mut rng, a, b, _, _) = setup();
+
+        let (_, request) = Session::initiate(&mut rng, &a, &b.public_key());
+        let (_, response) =
+            Session::respond(&mut rng, &b, &a.public_key(), &request).expect("should respond");
+
+        // A response to one handshake can't finish another.
+        let (handshake, _) = Session::initiate(&mut rng, &a, &b.public_key());
+        assert_matches!(handshake.finish(&a, &response), Err(DecryptError::NotAReceiver));
+    }
+
+    #[test]
+    fn modified_message() {
+        let (_, _, _, mut a, mut b) = setup();
+
+        let mut ciphertext = a.encrypt_message(b"this is a message");
+        ciphertext[0] ^= 1;
+        assert_matches!(b.decrypt_message(&ciphertext), Err(DecryptError::Corrupt));
+
+        // A forged message doesn't advance the chain.
+        let ciphertext = a.encrypt_message(b"this is a message");
+        assert_matches!(b.decrypt_message(&ciphertext), Err(DecryptError::Corrupt));
+    }
+
+    #[test]
+    fn replayed_message() {
+        let (_, _, _, mut a, mut b) = setup();
+
+        let ciphertext = a.encrypt_message(b"this is a message");
+        b.decrypt_message(&ciphertext).expect("should decrypt");
+        assert_matches!(b.decrypt_message(&ciphertext), Err(DecryptError::Corrupt));
+    }
+
+    #[test]
+    fn reflected_message() {
+        let (_, _, _, mut a, _) = setup();
+
+        let ciphertext = a.encrypt_message(b"this is a message");
+        assert_matches!(a.decrypt_message(&ciphertext), Err(DecryptError::Corrupt));
+    }
+
+    #[test]
+    fn truncated_message() {
+        let (_, _, _, _, mut b) = setup();
+
+        assert_matches!(b.decrypt_message(&[0u8; OVERHEAD - 1]), Err(DecryptError::Truncated));
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, PrivateKey, Session, Session) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let (handshake, request) = Session::initiate(&mut rng, &a, &b.public_key());
+        let (session_b, response) =
+            Session::respond(&mut rng, &b, &a.public_key(), &request).expect("should respond");
+        let session_a = handshake.finish(&a, &response).expect("should finish");
+
+        (rng, a, b, session_a, session_b)
+    }
+}
@@ -1,20 +1,27 @@
 //! An insider-secure hybrid signcryption implementation.
 
-use crrl::gls254::{Point, Scalar};
+use crate::curve::{Point, Scalar};
 use lockstitch::Protocol;
 
-use crate::keys::{PrivKey, PubKey, POINT_LEN};
+use crate::{
+    ct,
+    keys::{PrivKey, PubKey, POINT_LEN},
+    KemBackend, PublicKey, SignerBackend,
+};
 
 /// The recommended size of the nonce passed to [encrypt].
 pub const NONCE_LEN: usize = 16;
 
+/// The length of a designated-verifier signature, in bytes.
+pub const DESIGNATED_SIGNATURE_LEN: usize = POINT_LEN + POINT_LEN;
+
 /// The number of bytes added to plaintext by [encrypt].
-pub const OVERHEAD: usize = POINT_LEN + POINT_LEN + POINT_LEN;
+pub const OVERHEAD: usize = POINT_LEN + DESIGNATED_SIGNATURE_LEN;
 
 /// Given the sender's key pair, the ephemeral key pair, the receiver's public key, a nonce, and a
 /// plaintext, encrypts the given plaintext and returns the ciphertext.
 pub fn encrypt(
-    sender: &PrivKey,
+    sender: &(impl KemBackend + SignerBackend),
     ephemeral: &PrivKey,
     receiver: &PubKey,
     nonce: &[u8],
@@ -25,19 +32,19 @@ pub fn encrypt(
 
     // Split up the output buffer.
     let (out_q_e, out_ciphertext) = ciphertext.split_at_mut(POINT_LEN);
-    let (out_ciphertext, out_i) = out_ciphertext.split_at_mut(plaintext.len());
-    let (out_i, out_x) = out_i.split_at_mut(POINT_LEN);
+    let (out_ciphertext, out_sig) = out_ciphertext.split_at_mut(plaintext.len());
+    let receiver = PublicKey(*receiver);
 
     // Initialize a protocol.
     let mut sres = Protocol::new("veil.sres");
 
     // Mix the sender's public key into the protocol. This binds all following outputs to the
     // sender's identity, preventing unknown key-share attacks with respect to the sender.
-    sres.mix("sender", &sender.pub_key.encoded);
+    sres.mix("sender", &KemBackend::public_key(sender).0.encoded);
 
     // Mix the receiver's public key into the protocol. This binds all following outputs to the
     // receiver's identity, preventing unknown key-share attacks with respect to the receiver.
-    sres.mix("receiver", &receiver.encoded);
+    sres.mix("receiver", &receiver.0.encoded);
 
     // Mix the nonce into the protocol. This makes all following outputs probabilistic with respect
     // to the nonce.
@@ -46,7 +53,7 @@ pub fn encrypt(
     // Mix the static ECDH shared secret `[d_S]Q_R` into the protocol. This makes all following
     // outputs confidential against passive outsider adversaries (i.e. in possession of the sender
     // and receiver's public keys but no private keys) but not active outsider adversaries.
-    sres.mix("static-ecdh", &(sender.d * receiver.q).encode());
+    sres.mix("static-ecdh", &sender.diffie_hellman(&receiver));
 
     // Encrypt the ephemeral public key. An insider adversary (i.e. in possession of either the
     // sender or the receiver's private key) can recover this value. While this does represent a
@@ -58,7 +65,7 @@ pub fn encrypt(
     // Mix the ephemeral ECDH shared secret `[d_E]Q_R` into the protocol. This makes all following
     // outputs confidential against passive insider adversaries (i.e. an adversary in possession of
     // the sender's private key) a.k.a sender forward-secure.
-    sres.mix("ephemeral-ecdh", &(ephemeral.d * receiver.q).encode());
+    sres.mix("ephemeral-ecdh", &ephemeral.diffie_hellman(&receiver));
 
     // Encrypt the plaintext. By itself, this is confidential against passive insider adversaries
     // and implicitly authenticated as being from either the sender or the receiver but vulnerable
@@ -67,26 +74,43 @@ pub fn encrypt(
     out_ciphertext.copy_from_slice(plaintext);
     sres.encrypt("message", out_ciphertext);
 
-    // Deterministically generate a commitment scalar. The protocol's state is randomized with both
-    // the nonce and the ephemeral key, so the risk of e.g. fault attacks is minimal.
-    let k = sender.commitment(&sres);
+    // Create a designated-verifier signature of the protocol's state. The protocol's state is
+    // randomized with both the nonce and the ephemeral key, so the risk of e.g. fault attacks is
+    // minimal.
+    out_sig.copy_from_slice(&sender.sign_designated(&mut sres, &receiver));
+}
+
+/// Create a deterministic designated-verifier Schnorr signature of the given protocol's state using
+/// the given private key, verifiable only by the owner of the given verifier's private key.
+pub fn det_sign_designated(
+    protocol: &mut Protocol,
+    signer: &PrivKey,
+    verifier: &PubKey,
+) -> [u8; DESIGNATED_SIGNATURE_LEN] {
+    let mut sig = [0u8; DESIGNATED_SIGNATURE_LEN];
+    let (out_i, out_x) = sig.split_at_mut(POINT_LEN);
+
+    // Deterministically generate a commitment scalar.
+    let k = signer.commitment(protocol);
 
     // Calculate and encrypt the commitment point.
     out_i.copy_from_slice(&Point::mulgen(&k).encode());
-    sres.encrypt("commitment-point", out_i);
+    protocol.encrypt("commitment-point", out_i);
 
     // Derive a challenge scalar. This closes over all previous inputs and outputs: sender identity,
     // receiver identity, nonce, static ECDH shared secret, ephemeral public key, ephemeral ECDH
     // shared secret, message, and commitment point.
-    let r = Scalar::decode_reduce(&sres.derive_array::<32>("challenge-scalar"));
+    let r = Scalar::decode_reduce(&protocol.derive_array::<32>("challenge-scalar"));
 
     // Calculate and encrypt the designated proof point `[d_S*r+k]Q_R`. The final resulting
     // ciphertext is confidential against both passive and active insider adversaries and
     // authenticated against both passive and active insider adversaries. In addition, no one not in
     // possession of the receiver's private key `d_R` will be able to verify the signature.
-    let x = ((sender.d * r) + k) * receiver.q;
+    let x = ((signer.d * r) + k) * verifier.q;
     out_x.copy_from_slice(&x.encode());
-    sres.encrypt("proof-point", out_x);
+    protocol.encrypt("proof-point", out_x);
+
+    sig
 }
 
 /// Given the receiver's key pair, the sender's public key, a nonce, and a ciphertext, decrypts the
@@ -94,7 +118,7 @@ pub fn encrypt(
 /// encrypted for the receiver by the sender.
 #[must_use]
 pub fn decrypt<'a>(
-    receiver: &PrivKey,
+    receiver: &impl KemBackend,
     sender: &PubKey,
     nonce: &[u8],
     in_out: &'a mut [u8],
@@ -106,8 +130,7 @@ pub fn decrypt<'a>(
 
     // Split the ciphertext into its components.
     let (ephemeral, ciphertext) = in_out.split_at_mut(POINT_LEN);
-    let (ciphertext, i) = ciphertext.split_at_mut(ciphertext.len() - POINT_LEN - POINT_LEN);
-    let (i, x) = i.split_at_mut(POINT_LEN);
+    let (ciphertext, sig) = ciphertext.split_at_mut(ciphertext.len() - DESIGNATED_SIGNATURE_LEN);
 
     // Initialize a protocol.
     let mut sres = Protocol::new("veil.sres");
@@ -116,40 +139,60 @@ pub fn decrypt<'a>(
     sres.mix("sender", &sender.encoded);
 
     // Mix the receiver's public key into the protocol.
-    sres.mix("receiver", &receiver.pub_key.encoded);
+    sres.mix("receiver", &receiver.public_key().0.encoded);
 
     // Mix the nonce into the protocol.
     sres.mix("nonce", nonce);
 
     // Mix the static ECDH shared secret into the protocol: [d_R]Q_S
-    sres.mix("static-ecdh", &(receiver.d * sender.q).encode());
+    sres.mix("static-ecdh", &receiver.diffie_hellman(&PublicKey(*sender)));
 
-    // Decrypt and decode the ephemeral public key.
+    // Decrypt and decode the ephemeral public key. If it's invalid, carry on with a stand-in so that
+    // failing to decrypt a ciphertext takes as long as decrypting it; otherwise, the time it takes to
+    // scan a message's headers would reveal which one was decrypted.
     sres.decrypt("ephemeral-key", ephemeral);
-    let ephemeral = PubKey::from_canonical_bytes(ephemeral)?;
+    let (ephemeral, valid) = ct::decode_pub_key(ephemeral);
 
     // Mix the ephemeral ECDH shared secret into the protocol: [d_R]Q_E
-    sres.mix("ephemeral-ecdh", &(receiver.d * ephemeral.q).encode());
+    sres.mix("ephemeral-ecdh", &receiver.diffie_hellman(&PublicKey(ephemeral)));
 
     // Decrypt the plaintext.
     sres.decrypt("message", ciphertext);
 
-    // Decrypt and decode the commitment point.
-    sres.decrypt("commitment-point", i);
-    let i = Point::decode(i)?;
+    // Verify the designated-verifier signature and return the ephemeral public key and plaintext
+    // iff both it and the ephemeral public key are valid.
+    let verified = det_verify_designated(&mut sres, receiver, sender, sig).is_some();
+    (valid & verified).then_some((ephemeral, &*ciphertext))
+}
+
+/// Verify a deterministic designated-verifier Schnorr signature of the given protocol's state
+/// using the verifier's private key and the signer's public key.
+#[must_use]
+pub fn det_verify_designated(
+    protocol: &mut Protocol,
+    verifier: &impl KemBackend,
+    signer: &PubKey,
+    sig: &mut [u8],
+) -> Option<()> {
+    let (i, x) = sig.split_at_mut(POINT_LEN);
+
+    // Decrypt and decode the commitment point, carrying on with the neutral point if it's invalid.
+    protocol.decrypt("commitment-point", i);
+    let (i, valid) = ct::decode_point(i);
 
     // Re-derive the challenge scalar.
-    let r_p = Scalar::decode_reduce(&sres.derive_array::<32>("challenge-scalar"));
+    let r_p = Scalar::decode_reduce(&protocol.derive_array::<32>("challenge-scalar"));
 
     // Decrypt the designated proof point.
-    sres.decrypt("proof-point", x);
+    protocol.decrypt("proof-point", x);
 
     // Re-calculate the proof point: X' = [d_R](I + [r']Q_R)
-    let x_p = receiver.d * (i + (r_p * sender.q));
+    let p = i + (r_p * signer.q);
+    let x_p = verifier.diffie_hellman(&PublicKey(PubKey { q: p, encoded: p.encode() }));
 
-    // Return the ephemeral public key and plaintext iff the canonical encoding of the re-calculated
-    // proof point matches the encoding of the decrypted proof point.
-    lockstitch::ct_eq(x, &x_p.encode()).then_some((ephemeral, ciphertext))
+    // Return `Some` iff the commitment point is valid and the canonical encoding of the
+    // re-calculated proof point matches the encoding of the decrypted proof point.
+    (valid & ct::eq(x, &x_p)).then_some(())
 }
 
 #[cfg(test)]
@@ -0,0 +1,359 @@
+//! Purpose-separated subkeys of an identity.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Debug, Formatter},
+    str::FromStr,
+};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::{PrivKey, PubKey, POINT_LEN},
+    schnorr::{self, DET_SIGNATURE_LEN},
+    sres::NONCE_LEN,
+    ParseSubkeysError, PrivateKey, PublicKey, VerifyError,
+};
+#[cfg(feature = "std")]
+use crate::{
+    DecryptError, Digest, EncryptError, EncryptOptions, Report, Signature, TimestampedSignature,
+};
+
+/// The length of an encoded set of subkeys, in bytes.
+pub const SUBKEYS_LEN: usize = POINT_LEN * 3 + NONCE_LEN + DET_SIGNATURE_LEN;
+
+/// The purpose of a signing subkey.
+const SIGN: &str = "/sign";
+
+/// The purpose of an encryption subkey.
+const ENC: &str = "/enc";
+
+/// A subkey which can only be used to sign messages. See [`PrivateKey::signing_key`].
+pub struct SigningKey(PrivateKey);
+
+impl SigningKey {
+    /// Returns the signing public key, with which signatures are verified.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        self.0.public_key()
+    }
+
+    /// Reads the contents of the reader and returns a digital signature. See
+    /// [`PrivateKey::sign`].
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+    #[cfg(feature = "std")]
+    pub fn sign(&self, rng: impl Rng + CryptoRng, message: impl Read) -> io::Result<Signature> {
+        self.0.sign(rng, message)
+    }
+
+    /// Reads the contents of the reader and returns a digital signature which asserts that the
+    /// message was signed at the given timestamp. See [`PrivateKey::sign_timestamped`].
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+    #[cfg(feature = "std")]
+    pub fn sign_timestamped(
+        &self,
+        rng: impl Rng + CryptoRng,
+        message: impl Read,
+        timestamp: u64,
+    ) -> io::Result<TimestampedSignature> {
+        self.0.sign_timestamped(rng, message, timestamp)
+    }
+
+    /// Returns a digital signature of the given digest. See [`PrivateKey::sign_digest`].
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn sign_digest(&self, rng: impl Rng + CryptoRng, digest: &Digest) -> Signature {
+        self.0.sign_digest(rng, digest)
+    }
+}
+
+impl Debug for SigningKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SigningKey").field(&self.public_key()).finish()
+    }
+}
+
+/// A subkey which can only be used to encrypt and decrypt messages. See
+/// [`PrivateKey::decryption_key`].
+///
+/// Messages are encrypted with the sender's decryption key, so their receivers decrypt them with
+/// the sender's encryption public key.
+pub struct DecryptionKey(PrivateKey);
+
+impl DecryptionKey {
+    /// Returns the encryption public key, for which messages are encrypted.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        self.0.public_key()
+    }
+
+    /// Encrypts the contents of the reader and write the ciphertext to the writer. See
+    /// [`PrivateKey::encrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
+    /// will be returned.
+    #[cfg(feature = "std")]
+    pub fn encrypt(
+        &self,
+        rng: impl Rng + CryptoRng,
+        reader: impl Read,
+        writer: impl Write,
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, EncryptError> {
+        self.0.encrypt(rng, reader, writer, options)
+    }
+
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`. See
+    /// [`PrivateKey::decrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender or was not encrypted for this key, returns
+    /// [`DecryptError::NotAReceiver`]. If it has been modified, returns [`DecryptError::Corrupt`],
+    /// and if it is incomplete, returns [`DecryptError::Truncated`]. If there was an error reading
+    /// from `reader` or writing to `writer`, returns [`DecryptError::ReadIo`] or
+    /// [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt(
+        &self,
+        reader: impl Read,
+        writer: impl Write,
+        sender: &PublicKey,
+    ) -> Result<Report, DecryptError> {
+        self.0.decrypt(reader, writer, sender)
+    }
+}
+
+impl Debug for DecryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DecryptionKey").field(&self.public_key()).finish()
+    }
+}
+
+/// An identity's subkey public keys, signed by the identity's private key.
+///
+/// Consists of the identity public key, the signing public key, the encryption public key, a
+/// nonce, an encrypted commitment point, and an encrypted proof scalar.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Subkeys {
+    identity: PublicKey,
+    signing: PublicKey,
+    encryption: PublicKey,
+    nonce: [u8; NONCE_LEN],
+    sig: [u8; DET_SIGNATURE_LEN],
+}
+
+impl Subkeys {
+    /// Returns the public key of the identity which signed the subkeys.
+    #[must_use]
+    pub const fn identity(&self) -> PublicKey {
+        self.identity
+    }
+
+    /// Returns the signing public key, with which the identity's signatures are verified.
+    #[must_use]
+    pub const fn signing_key(&self) -> PublicKey {
+        self.signing
+    }
+
+    /// Returns the encryption public key, for which the identity's messages are encrypted.
+    #[must_use]
+    pub const fn encryption_key(&self) -> PublicKey {
+        self.encryption
+    }
+
+    /// Verifies that the subkeys were signed by the private key of their identity. Returns `Ok(())`
+    /// if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the subkeys have been modified, returns [`VerifyError::InvalidSignature`].
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let mut subkeys = init(&self.identity.0, &self.signing.0, &self.encryption.0, &self.nonce);
+        schnorr::det_verify(&mut subkeys, &self.identity.0, self.sig)
+            .ok_or(VerifyError::InvalidSignature)
+    }
+
+    /// Decode a set of subkeys from a byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<Subkeys> {
+        let b = b.as_ref();
+        if b.len() != SUBKEYS_LEN {
+            return None;
+        }
+
+        // Split the subkeys into components.
+        let (identity, b) = b.split_at(POINT_LEN);
+        let (signing, b) = b.split_at(POINT_LEN);
+        let (encryption, b) = b.split_at(POINT_LEN);
+        let (nonce, sig) = b.split_at(NONCE_LEN);
+
+        Some(Subkeys {
+            identity: PublicKey::decode(identity)?,
+            signing: PublicKey::decode(signing)?,
+            encryption: PublicKey::decode(encryption)?,
+            nonce: nonce.try_into().expect("should be nonce-sized"),
+            sig: sig.try_into().expect("should be signature-sized"),
+        })
+    }
+
+    /// Encode the subkeys as a byte vector.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(SUBKEYS_LEN);
+        b.extend_from_slice(&self.identity.encode());
+        b.extend_from_slice(&self.signing.encode());
+        b.extend_from_slice(&self.encryption.encode());
+        b.extend_from_slice(&self.nonce);
+        b.extend_from_slice(&self.sig);
+        b
+    }
+}
+
+impl FromStr for Subkeys {
+    type Err = ParseSubkeysError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Subkeys::decode(bs58::decode(s).into_vec()?).ok_or(ParseSubkeysError::InvalidSubkeys)
+    }
+}
+
+impl fmt::Display for Subkeys {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    }
+}
+
+/// Derives the signing subkey of the given identity.
+pub fn signing_key(identity: &PrivKey) -> SigningKey {
+    SigningKey(PrivateKey(derive(identity, SIGN)))
+}
+
+/// Derives the decryption subkey of the given identity.
+pub fn decryption_key(identity: &PrivKey) -> DecryptionKey {
+    DecryptionKey(PrivateKey(derive(identity, ENC)))
+}
+
+/// Creates a set of the given identity's subkey public keys, signed by the identity.
+pub fn export(mut rng: impl Rng + CryptoRng, identity: &PrivKey) -> Subkeys {
+    let signing = PublicKey(derive(identity, SIGN).pub_key);
+    let encryption = PublicKey(derive(identity, ENC).pub_key);
+
+    // Generate a random nonce.
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+    // Initialize a protocol with the identity, subkeys, and nonce and sign its state.
+    let mut subkeys = init(&identity.pub_key, &signing.0, &encryption.0, &nonce);
+    let sig = schnorr::det_sign(&mut subkeys, identity);
+
+    Subkeys { identity: PublicKey(identity.pub_key), signing, encryption, nonce, sig }
+}
+
+/// Derives the subkey of the given identity for the given purpose.
+fn derive(identity: &PrivKey, purpose: &str) -> PrivKey {
+    // Initialize a protocol.
+    let mut subkey = Protocol::new("veil.subkey");
+
+    // Mix the identity's secret into the protocol.
+    subkey.mix("secret", &identity.secret);
+
+    // Mix the purpose into the protocol, so each purpose has an independent secret.
+    subkey.mix("purpose", purpose.as_bytes());
+
+    PrivKey::from_secret_bytes(subkey.derive_array("secret"))
+}
+
+fn init(identity: &PubKey, signing: &PubKey, encryption: &PubKey, nonce: &[u8]) -> Protocol {
+    // Initialize a protocol.
+    let mut subkeys = Protocol::new("veil.subkeys");
+
+    // Mix the identity's public key into the protocol.
+    subkeys.mix("identity", &identity.encoded);
+
+    // Mix the nonce into the protocol.
+    subkeys.mix("nonce", nonce);
+
+    // Mix the subkeys' public keys into the protocol.
+    subkeys.mix("signing-key", &signing.encoded);
+    subkeys.mix("encryption-key", &encryption.encoded);
+
+    subkeys
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn independent_subkeys() {
+        let (_, key, subkeys) = setup();
+
+        let signing = key.signing_key().public_key();
+        let encryption = key.decryption_key().public_key();
+        assert_ne!(key.public_key(), signing);
+        assert_ne!(key.public_key(), encryption);
+        assert_ne!(signing, encryption);
+        assert_eq!(signing, subkeys.signing_key());
+        assert_eq!(encryption, subkeys.encryption_key());
+        assert_eq!(key.public_key(), subkeys.identity());
+    }
+
+    #[test]
+    fn round_trip() {
+        let (_, _, subkeys) = setup();
+
+        assert_matches!(subkeys.verify(), Ok(()));
+    }
+
+    #[test]
+    fn modified_subkey() {
+        let (mut rng, _, mut subkeys) = setup();
+
+        subkeys.encryption = PublicKey::random(&mut rng);
+        assert_matches!(subkeys.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn swapped_subkeys() {
+        let (_, _, mut subkeys) = setup();
+
+        (subkeys.signing, subkeys.encryption) = (subkeys.encryption, subkeys.signing);
+        assert_matches!(subkeys.verify(), Err(VerifyError::InvalidSignature));
+    }
+
+    #[test]
+    fn encoding() {
+        let (_, _, subkeys) = setup();
+
+        let decoded = subkeys.to_string().parse::<Subkeys>();
+        assert_eq!(Ok(&subkeys), decoded.as_ref(), "error parsing subkeys");
+    }
+
+    #[test]
+    fn invalid_encoding() {
+        assert_matches!("woot woot".parse::<Subkeys>(), Err(ParseSubkeysError::InvalidEncoding(_)));
+        assert_matches!("woot".parse::<Subkeys>(), Err(ParseSubkeysError::InvalidSubkeys));
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, Subkeys) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let subkeys = key.export_subkeys(&mut rng);
+        (rng, key, subkeys)
+    }
+}
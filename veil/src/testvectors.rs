@@ -0,0 +1,352 @@
+//! Known-answer test vectors for independent implementations.
+//!
+//! [`generate`] produces a canonical set of test vectors for `veil.sres`, `veil.mres`, `veil.pbenc`,
+//! and `veil.schnorr` from a fixed RNG seed. The vectors are formatted as sections of hex-encoded
+//! values:
+//!
+//! ```text
+//! [sres]
+//! sender-secret = 0f1e...
+//! receiver-secret = 2d3c...
+//! ```
+//!
+//! [`verify`] checks a set of parsed vectors against this implementation, so vectors produced by
+//! another implementation can be checked as well as vice versa.
+
+use std::{
+    fmt::{self, Display, Formatter, Write as _},
+    io::Cursor,
+};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+use crate::{
+    keys::{PrivKey, SECRET_LEN},
+    mres::{self, HeaderKey},
+    pbenc,
+    schnorr::{self, Signature},
+    sres, TestVectorError,
+};
+
+/// The seed of the RNG used to generate the canonical test vectors.
+pub const SEED: u64 = 0xDEADBEEF;
+
+/// A named set of hex-encoded values for a single known-answer test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestVector {
+    /// The name of the construction the test vector is for.
+    pub name: String,
+
+    /// The named values of the test vector, in order.
+    pub values: Vec<(String, Vec<u8>)>,
+}
+
+impl TestVector {
+    fn new(name: &str) -> TestVector {
+        TestVector { name: name.into(), values: Vec::new() }
+    }
+
+    fn push(&mut self, key: &str, value: impl AsRef<[u8]>) {
+        self.values.push((key.into(), value.as_ref().to_vec()));
+    }
+
+    fn get(&self, key: &str) -> Result<&[u8], TestVectorError> {
+        self.values
+            .iter()
+            .find_map(|(k, v)| (k == key).then_some(v.as_slice()))
+            .ok_or_else(|| TestVectorError::MissingValue(self.name.clone(), key.into()))
+    }
+
+    fn secret(&self, key: &str) -> Result<PrivKey, TestVectorError> {
+        let secret = <[u8; SECRET_LEN]>::try_from(self.get(key)?)
+            .map_err(|_| TestVectorError::Mismatch(self.name.clone(), key.into()))?;
+        Ok(PrivKey::from_secret_bytes(secret))
+    }
+
+    fn check(&self, key: &str, ok: bool) -> Result<(), TestVectorError> {
+        ok.then_some(()).ok_or_else(|| TestVectorError::Mismatch(self.name.clone(), key.into()))
+    }
+}
+
+impl Display for TestVector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[{}]", self.name)?;
+        for (k, v) in &self.values {
+            write!(f, "{k} = ")?;
+            for b in v {
+                write!(f, "{b:02x}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Generate the canonical set of test vectors.
+#[must_use]
+pub fn generate() -> Vec<TestVector> {
+    let mut rng = ChaChaRng::seed_from_u64(SEED);
+
+    // A single-receiver signcryption of a short plaintext.
+    let mut sres = TestVector::new("sres");
+    let sender = PrivKey::random(&mut rng);
+    let receiver = PrivKey::random(&mut rng);
+    let ephemeral = PrivKey::random(&mut rng);
+    let nonce = rng.gen::<[u8; sres::NONCE_LEN]>();
+    let plaintext = rng.gen::<[u8; 64]>();
+    let mut ciphertext = vec![0u8; plaintext.len() + sres::OVERHEAD];
+    sres::encrypt(&sender, &ephemeral, &receiver.pub_key, &nonce, &plaintext, &mut ciphertext);
+    sres.push("sender-secret", sender.secret);
+    sres.push("receiver-secret", receiver.secret);
+    sres.push("ephemeral-secret", ephemeral.secret);
+    sres.push("nonce", nonce);
+    sres.push("plaintext", plaintext);
+    sres.push("ciphertext", ciphertext);
+
+    // A multi-receiver encryption with padding.
+    let mut mres = TestVector::new("mres");
+    let sender = PrivKey::random(&mut rng);
+    let receiver = PrivKey::random(&mut rng);
+    let plaintext = rng.gen::<[u8; 64]>();
+    let associated_data = rng.gen::<[u8; 16]>();
+    let mut ciphertext = Vec::new();
+    mres::encrypt(
+        &mut rng,
+        Cursor::new(plaintext),
+        &mut ciphertext,
+        &sender,
+        &[HeaderKey::Receiver(sender.pub_key), HeaderKey::Receiver(receiver.pub_key)],
+        123,
+        &associated_data,
+    )
+    .expect("writes to a Vec should be infallible");
+    mres.push("sender-secret", sender.secret);
+    mres.push("receiver-secret", receiver.secret);
+    mres.push("plaintext", plaintext);
+    mres.push("associated-data", associated_data);
+    mres.push("ciphertext", ciphertext);
+
+    // A passphrase-based encryption with small costs.
+    let mut pbenc = TestVector::new("pbenc");
+    let passphrase = b"this is a passphrase";
+    let plaintext = rng.gen::<[u8; 64]>();
+    let mut ciphertext = vec![0u8; plaintext.len() + pbenc::OVERHEAD];
+    pbenc::encrypt(&mut rng, passphrase, 2, 4, 2, &plaintext, &mut ciphertext);
+    pbenc.push("passphrase", passphrase);
+    pbenc.push("plaintext", plaintext);
+    pbenc.push("ciphertext", ciphertext);
+
+    // A randomized signature.
+    let mut schnorr = TestVector::new("schnorr");
+    let signer = PrivKey::random(&mut rng);
+    let message = rng.gen::<[u8; 64]>();
+    let sig = schnorr::sign(&mut rng, &signer, "", Cursor::new(message))
+        .expect("reads from a Cursor should be infallible");
+    schnorr.push("signer-secret", signer.secret);
+    schnorr.push("message", message);
+    schnorr.push("signature", sig.encode());
+
+    vec![sres, mres, pbenc, schnorr]
+}
+
+/// Verify the given test vectors against this implementation.
+///
+/// # Errors
+///
+/// Returns a [`TestVectorError`] if a test vector is unknown, is missing a value, or does not match
+/// this implementation's output.
+pub fn verify(vectors: &[TestVector]) -> Result<(), TestVectorError> {
+    vectors.iter().try_for_each(|v| match v.name.as_str() {
+        "sres" => verify_sres(v),
+        "mres" => verify_mres(v),
+        "pbenc" => verify_pbenc(v),
+        "schnorr" => verify_schnorr(v),
+        name => Err(TestVectorError::UnknownVector(name.into())),
+    })
+}
+
+fn verify_sres(v: &TestVector) -> Result<(), TestVectorError> {
+    let sender = v.secret("sender-secret")?;
+    let receiver = v.secret("receiver-secret")?;
+    let ephemeral = v.secret("ephemeral-secret")?;
+    let nonce = v.get("nonce")?;
+    let plaintext = v.get("plaintext")?;
+    let ciphertext = v.get("ciphertext")?;
+
+    // Encryption is deterministic given the ephemeral key and nonce.
+    let mut expected = vec![0u8; plaintext.len() + sres::OVERHEAD];
+    sres::encrypt(&sender, &ephemeral, &receiver.pub_key, nonce, plaintext, &mut expected);
+    v.check("ciphertext", expected == ciphertext)?;
+
+    let mut in_out = ciphertext.to_vec();
+    let ptx = sres::decrypt(&receiver, &sender.pub_key, nonce, &mut in_out);
+    v.check("plaintext", ptx == Some((ephemeral.pub_key, plaintext)))
+}
+
+fn verify_mres(v: &TestVector) -> Result<(), TestVectorError> {
+    let sender = v.secret("sender-secret")?;
+    let receiver = v.secret("receiver-secret")?;
+    let plaintext = v.get("plaintext")?;
+    let associated_data = v.get("associated-data")?;
+    let ciphertext = v.get("ciphertext")?;
+
+    let mut ptx = Vec::new();
+    let res = mres::decrypt(ciphertext, &mut ptx, &[receiver], &sender.pub_key, associated_data);
+    v.check("plaintext", res.is_ok() && ptx == plaintext)
+}
+
+fn verify_pbenc(v: &TestVector) -> Result<(), TestVectorError> {
+    let passphrase = v.get("passphrase")?;
+    let plaintext = v.get("plaintext")?;
+    let ciphertext = v.get("ciphertext")?;
+
+    let mut in_out = ciphertext.to_vec();
+    v.check("plaintext", pbenc::decrypt(passphrase, &mut in_out) == Some(plaintext))
+}
+
+fn verify_schnorr(v: &TestVector) -> Result<(), TestVectorError> {
+    let signer = v.secret("signer-secret")?;
+    let message = v.get("message")?;
+    let sig = Signature::decode(v.get("signature")?);
+
+    v.check(
+        "signature",
+        sig.is_some_and(|sig| schnorr::verify(&signer.pub_key, "", message, &sig).is_ok()),
+    )
+}
+
+/// Format the given test vectors as text.
+#[must_use]
+pub fn format(vectors: &[TestVector]) -> String {
+    vectors.iter().fold(String::new(), |mut out, v| {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        write!(out, "{v}").expect("writes to a String should be infallible");
+        out
+    })
+}
+
+/// Parse test vectors from text.
+///
+/// # Errors
+///
+/// Returns [`TestVectorError::Malformed`] with the one-based line number of the first line which
+/// could not be parsed.
+pub fn parse(s: &str) -> Result<Vec<TestVector>, TestVectorError> {
+    let mut vectors = Vec::<TestVector>::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            vectors.push(TestVector::new(name.trim()));
+        } else {
+            let (key, value) = line.split_once('=').ok_or(TestVectorError::Malformed(i + 1))?;
+            let value = decode_hex(value.trim()).ok_or(TestVectorError::Malformed(i + 1))?;
+            vectors.last_mut().ok_or(TestVectorError::Malformed(i + 1))?.push(key.trim(), value);
+        }
+    }
+    Ok(vectors)
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let vectors = generate();
+        let parsed = parse(&format(&vectors)).expect("should parse");
+        assert_eq!(vectors, parsed, "invalid parsed vectors");
+        assert_matches!(verify(&parsed), Ok(()));
+    }
+
+    #[test]
+    fn deterministic() {
+        assert_eq!(format(&generate()), format(&generate()), "non-deterministic vectors");
+    }
+
+    #[test]
+    fn modified_vector() {
+        let mut vectors = generate();
+        for v in &mut vectors {
+            let (_, ciphertext) = v.values.last_mut().expect("should have values");
+            ciphertext[0] ^= 1;
+        }
+
+        for v in vectors {
+            assert_matches!(verify(&[v]), Err(TestVectorError::Mismatch(..)));
+        }
+    }
+
+    #[test]
+    fn missing_value() {
+        let mut vectors = generate();
+        vectors[0].values.pop();
+        assert_matches!(verify(&vectors), Err(TestVectorError::MissingValue(..)));
+    }
+
+    #[test]
+    fn malformed() {
+        assert_matches!(parse("nonce = 00"), Err(TestVectorError::Malformed(1)));
+        assert_matches!(parse("[sres]\nnonce = 0"), Err(TestVectorError::Malformed(2)));
+        assert_matches!(parse("[sres]\nnonce 00"), Err(TestVectorError::Malformed(2)));
+    }
+}
+
+/// Tests which pin the canonical test vectors and a deterministic encryption, so that ciphertexts
+/// produced on one target can be checked against every other: 32-bit targets like i686 and armv7
+/// and big-endian targets like s390x must produce byte-for-byte the same output as x86-64.
+///
+/// Run with `cross test -p veil --features cross --target s390x-unknown-linux-gnu cross`, or
+/// update the pinned values on x86-64 with `UPDATE_EXPECT=1`. The pinned values are for GLS254, so
+/// the tests are skipped with the `jq255e` feature.
+#[cfg(all(test, feature = "cross", not(feature = "jq255e")))]
+mod cross {
+    use assert_matches::assert_matches;
+    use expect_test::{expect, expect_file};
+
+    use super::*;
+    use crate::{Digest, EncryptOptions, PrivateKey};
+
+    #[test]
+    fn pinned_vectors() {
+        let vectors = format(&generate());
+        expect_file!["testvectors.txt"].assert_eq(&vectors);
+        assert_matches!(verify(&parse(&vectors).expect("should parse")), Ok(()));
+    }
+
+    #[test]
+    fn deterministic_encryption() {
+        let mut rng = ChaChaRng::seed_from_u64(SEED);
+        let sender = PrivateKey::random(&mut rng);
+        let receivers = [PrivateKey::random(&mut rng).public_key(), sender.public_key()];
+
+        // Shuffling headers, generating fakes, and padding all consume the seeded RNG.
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt_deterministic(
+                b"a seed",
+                &b"a message"[..],
+                &mut ciphertext,
+                &EncryptOptions::new(&receivers).fakes(3).padding(1234),
+            )
+            .expect("should encrypt");
+
+        let digest = Digest::new(&[b"ciphertext"], ciphertext.as_slice()).expect("should digest");
+        expect!["Fvmtt7gVf1UfwDSjW7ZAZsN1DG4NR1otMebnuRT32Bq8"].assert_eq(&digest.to_string());
+    }
+}
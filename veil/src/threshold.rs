@@ -0,0 +1,420 @@
+//! Threshold decryption of encrypted messages.
+//!
+//! A private key can be split into `n` [`KeyShare`]s, any `t` of which can jointly decrypt messages
+//! encrypted for the key's public key. Each share is a point on a random polynomial of degree `t-1`
+//! over the scalar field whose constant term is the private key, so fewer than `t` shares reveal
+//! nothing about the key.
+//!
+//! Decryption only uses the private key for Diffie-Hellman key agreement, so no single machine
+//! needs to reconstruct it. Instead, each share holder calculates a [`DecryptionShare`] of each
+//! point, and a [`Combiner`] interpolates `t` of them into the Diffie-Hellman shared secret. A
+//! [`Combiner`] is a [`KemBackend`], so it can be passed to [`crate::backend::decrypt`] like any
+//! other private key backend. Share holders on other machines are reached by implementing
+//! [`ShareHolder`] over whatever transport connects them.
+//!
+//! ```
+//! use veil::{backend, threshold, EncryptOptions, PrivateKey};
+//!
+//! // Split a key into five shares, any three of which can decrypt.
+//! let key = PrivateKey::random(rand::thread_rng());
+//! let shares = threshold::split(rand::thread_rng(), &key, 3, 5).expect("should split");
+//!
+//! // Encrypt a message for the key's public key.
+//! let sender = PrivateKey::random(rand::thread_rng());
+//! let mut ciphertext = Vec::new();
+//! sender
+//!     .encrypt(
+//!         rand::thread_rng(),
+//!         &b"a secret"[..],
+//!         &mut ciphertext,
+//!         &EncryptOptions::new(&[key.public_key()]),
+//!     )
+//!     .expect("should encrypt");
+//!
+//! // Decrypt it with three of the shares.
+//! let holders = vec![shares[0].clone(), shares[2].clone(), shares[4].clone()];
+//! let combiner = threshold::Combiner::new(key.public_key(), holders).expect("should combine");
+//! let mut plaintext = Vec::new();
+//! backend::decrypt(&combiner, &ciphertext[..], &mut plaintext, &sender.public_key(), &[])
+//!     .expect("should decrypt");
+//! assert_eq!(b"a secret", plaintext.as_slice());
+//! ```
+
+use std::fmt::{self, Debug, Formatter};
+
+use crate::curve::{Point, Scalar};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    ct,
+    keys::{PubKey, POINT_LEN, SCALAR_LEN},
+    KemBackend, PrivateKey, PublicKey, ThresholdError,
+};
+
+/// The length of an encoded key share in bytes.
+pub const KEY_SHARE_LEN: usize = 2 + 2 + POINT_LEN + SCALAR_LEN;
+
+/// The length of an encoded decryption share in bytes.
+pub const DECRYPTION_SHARE_LEN: usize = POINT_LEN;
+
+/// One share of a private key which has been split with [`split`].
+#[derive(Clone)]
+pub struct KeyShare {
+    index: u16,
+    threshold: u16,
+    public_key: PubKey,
+    d: Scalar,
+}
+
+impl KeyShare {
+    /// Returns the share's one-based index.
+    #[must_use]
+    pub const fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the number of shares required to decrypt.
+    #[must_use]
+    pub const fn threshold(&self) -> u16 {
+        self.threshold
+    }
+
+    /// Returns the public key of the private key which was split.
+    #[must_use]
+    pub const fn public_key(&self) -> PublicKey {
+        PublicKey(self.public_key)
+    }
+
+    /// Returns the public key corresponding to the share itself, with which its holder can be
+    /// identified.
+    #[must_use]
+    pub fn verification_key(&self) -> PublicKey {
+        let q = Point::mulgen(&self.d);
+        PublicKey(PubKey { q, encoded: q.encode() })
+    }
+
+    /// Decode a key share from a 68-byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<KeyShare> {
+        let b = <[u8; KEY_SHARE_LEN]>::try_from(b.as_ref()).ok()?;
+        let (index, b) = b.split_at(2);
+        let (threshold, b) = b.split_at(2);
+        let (public_key, d) = b.split_at(POINT_LEN);
+
+        let index = u16::from_le_bytes(index.try_into().expect("should be 2 bytes"));
+        let threshold = u16::from_le_bytes(threshold.try_into().expect("should be 2 bytes"));
+        if index == 0 || threshold == 0 {
+            return None;
+        }
+
+        let public_key = PubKey::from_canonical_bytes(public_key)?;
+        let d = ct::scalar(d)?;
+        Some(KeyShare { index, threshold, public_key, d })
+    }
+
+    /// Encode the key share as a 68-byte array.
+    #[must_use]
+    pub fn encode(&self) -> [u8; KEY_SHARE_LEN] {
+        let mut b = [0u8; KEY_SHARE_LEN];
+        let (index, rest) = b.split_at_mut(2);
+        let (threshold, rest) = rest.split_at_mut(2);
+        let (public_key, d) = rest.split_at_mut(POINT_LEN);
+        index.copy_from_slice(&self.index.to_le_bytes());
+        threshold.copy_from_slice(&self.threshold.to_le_bytes());
+        public_key.copy_from_slice(&self.public_key.encoded);
+        d.copy_from_slice(&self.d.encode());
+        b
+    }
+}
+
+impl Debug for KeyShare {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("index", &self.index)
+            .field("threshold", &self.threshold)
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Eq for KeyShare {}
+
+impl PartialEq for KeyShare {
+    fn eq(&self, other: &Self) -> bool {
+        ct::eq(&self.encode(), &other.encode())
+    }
+}
+
+/// A share holder's contribution to a Diffie-Hellman shared secret.
+#[derive(Clone, Copy)]
+pub struct DecryptionShare {
+    p: Point,
+    encoded: [u8; DECRYPTION_SHARE_LEN],
+}
+
+impl DecryptionShare {
+    /// Decode a decryption share from a 32-byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<DecryptionShare> {
+        let encoded = <[u8; DECRYPTION_SHARE_LEN]>::try_from(b.as_ref()).ok()?;
+        Some(DecryptionShare { p: ct::point(&encoded)?, encoded })
+    }
+
+    /// Encode the decryption share as a 32-byte array.
+    #[must_use]
+    pub const fn encode(&self) -> [u8; DECRYPTION_SHARE_LEN] {
+        self.encoded
+    }
+}
+
+impl Debug for DecryptionShare {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "DecryptionShare({:02x?})", self.encoded)
+    }
+}
+
+impl Eq for DecryptionShare {}
+
+impl PartialEq for DecryptionShare {
+    fn eq(&self, other: &Self) -> bool {
+        ct::eq(&self.encoded, &other.encoded)
+    }
+}
+
+/// A holder of a [`KeyShare`], which may be on another machine.
+pub trait ShareHolder {
+    /// Returns the one-based index of the holder's key share.
+    fn index(&self) -> u16;
+
+    /// Returns the holder's decryption share of the given point, `[d_i]P`.
+    fn decryption_share(&self, point: &PublicKey) -> DecryptionShare;
+}
+
+impl ShareHolder for KeyShare {
+    fn index(&self) -> u16 {
+        self.index
+    }
+
+    fn decryption_share(&self, point: &PublicKey) -> DecryptionShare {
+        let p = self.d * point.0.q;
+        DecryptionShare { p, encoded: p.encode() }
+    }
+}
+
+/// A [`KemBackend`] which combines the decryption shares of a set of share holders.
+///
+/// Each Diffie-Hellman operation asks every holder for a decryption share, and decrypting a message
+/// requires up to three operations per header, so holders on other machines should be reached over
+/// a low-latency transport.
+pub struct Combiner<S> {
+    public_key: PublicKey,
+    holders: Vec<(S, Scalar)>,
+}
+
+impl<S: ShareHolder> Combiner<S> {
+    /// Creates a combiner for the given public key from a set of share holders.
+    ///
+    /// If there are fewer holders than the key's threshold, the combined shared secrets will be
+    /// incorrect and decryption will fail with [`crate::DecryptError::NotAReceiver`].
+    ///
+    /// # Errors
+    ///
+    /// If there are no holders, or if any holder's index is zero or duplicated, returns
+    /// [`ThresholdError::InvalidShares`].
+    pub fn new(public_key: PublicKey, holders: Vec<S>) -> Result<Combiner<S>, ThresholdError> {
+        let mut indexes = holders.iter().map(ShareHolder::index).collect::<Vec<_>>();
+        indexes.sort_unstable();
+        if indexes.is_empty() || indexes[0] == 0 || indexes.windows(2).any(|w| w[0] == w[1]) {
+            return Err(ThresholdError::InvalidShares);
+        }
+
+        // Pre-calculate the Lagrange coefficient of each holder for interpolating at zero.
+        let holders = holders
+            .into_iter()
+            .map(|holder| {
+                let i = Scalar::from_u64(holder.index().into());
+                let lambda = indexes.iter().filter(|&&j| j != holder.index()).fold(
+                    Scalar::ONE,
+                    |lambda, &j| {
+                        let j = Scalar::from_u64(j.into());
+                        lambda * j / (j - i)
+                    },
+                );
+                (holder, lambda)
+            })
+            .collect();
+
+        Ok(Combiner { public_key, holders })
+    }
+}
+
+impl<S> Debug for Combiner<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Combiner")
+            .field("public_key", &self.public_key)
+            .field("holders", &self.holders.len())
+            .finish()
+    }
+}
+
+impl<S: ShareHolder> KemBackend for Combiner<S> {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    fn diffie_hellman(&self, point: &PublicKey) -> [u8; POINT_LEN] {
+        self.holders
+            .iter()
+            .fold(Point::NEUTRAL, |x, (holder, lambda)| {
+                x + holder.decryption_share(point).p * lambda
+            })
+            .encode()
+    }
+}
+
+/// Splits the given private key into `shares` key shares, any `threshold` of which can decrypt
+/// messages encrypted for it.
+///
+/// # Errors
+///
+/// If `threshold` is zero or greater than `shares`, returns [`ThresholdError::InvalidThreshold`].
+pub fn split(
+    mut rng: impl Rng + CryptoRng,
+    key: &PrivateKey,
+    threshold: u16,
+    shares: u16,
+) -> Result<Vec<KeyShare>, ThresholdError> {
+    if threshold == 0 || threshold > shares {
+        return Err(ThresholdError::InvalidThreshold);
+    }
+
+    // Generate a random polynomial of degree t-1 whose constant term is the private key.
+    let coefficients = [key.0.d]
+        .into_iter()
+        .chain((1..threshold).map(|_| Scalar::decode_reduce(&rng.gen::<[u8; 64]>())))
+        .collect::<Vec<_>>();
+
+    // Evaluate the polynomial at each share's index.
+    Ok((1..=shares)
+        .map(|index| {
+            let x = Scalar::from_u64(index.into());
+            let d = coefficients.iter().rev().fold(Scalar::ZERO, |y, a| y * x + a);
+            KeyShare { index, threshold, public_key: key.0.pub_key, d }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::{backend, DecryptError, EncryptOptions};
+
+    fn setup() -> (ChaChaRng, PrivateKey, PrivateKey, Vec<u8>) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        let mut ciphertext = Vec::new();
+        sender
+            .encrypt(
+                &mut rng,
+                &b"a secret"[..],
+                &mut ciphertext,
+                &EncryptOptions::new(&[receiver.public_key()]).fakes(3),
+            )
+            .expect("should encrypt");
+        (rng, sender, receiver, ciphertext)
+    }
+
+    fn decrypt(
+        holders: Vec<KeyShare>,
+        receiver: &PrivateKey,
+        sender: &PrivateKey,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, DecryptError> {
+        let combiner = Combiner::new(receiver.public_key(), holders).expect("should combine");
+        let mut plaintext = Vec::new();
+        backend::decrypt(&combiner, ciphertext, &mut plaintext, &sender.public_key(), &[])?;
+        Ok(plaintext)
+    }
+
+    #[test]
+    fn threshold_decryption() {
+        let (mut rng, sender, receiver, ciphertext) = setup();
+        let shares = split(&mut rng, &receiver, 3, 5).expect("should split");
+        assert_eq!(5, shares.len());
+
+        for holders in [&[0, 1, 2][..], &[4, 2, 0], &[1, 3, 4], &[0, 1, 2, 3, 4]] {
+            let holders = holders.iter().map(|&i| shares[i].clone()).collect();
+            let plaintext = decrypt(holders, &receiver, &sender, &ciphertext);
+            assert_matches!(plaintext, Ok(p) if p == b"a secret");
+        }
+    }
+
+    #[test]
+    fn too_few_shares() {
+        let (mut rng, sender, receiver, ciphertext) = setup();
+        let shares = split(&mut rng, &receiver, 3, 5).expect("should split");
+
+        let holders = vec![shares[0].clone(), shares[3].clone()];
+        assert_matches!(
+            decrypt(holders, &receiver, &sender, &ciphertext),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn one_of_one() {
+        let (mut rng, sender, receiver, ciphertext) = setup();
+        let shares = split(&mut rng, &receiver, 1, 1).expect("should split");
+        assert_eq!(receiver.public_key(), shares[0].verification_key());
+
+        let plaintext = decrypt(shares, &receiver, &sender, &ciphertext);
+        assert_matches!(plaintext, Ok(p) if p == b"a secret");
+    }
+
+    #[test]
+    fn invalid_threshold() {
+        let (mut rng, _, receiver, _) = setup();
+        assert_matches!(split(&mut rng, &receiver, 0, 3), Err(ThresholdError::InvalidThreshold));
+        assert_matches!(split(&mut rng, &receiver, 4, 3), Err(ThresholdError::InvalidThreshold));
+    }
+
+    #[test]
+    fn invalid_shares() {
+        let (mut rng, _, receiver, _) = setup();
+        let shares = split(&mut rng, &receiver, 2, 3).expect("should split");
+
+        assert_matches!(
+            Combiner::<KeyShare>::new(receiver.public_key(), vec![]),
+            Err(ThresholdError::InvalidShares)
+        );
+        assert_matches!(
+            Combiner::new(receiver.public_key(), vec![shares[1].clone(), shares[1].clone()]),
+            Err(ThresholdError::InvalidShares)
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let (mut rng, _, receiver, _) = setup();
+        let shares = split(&mut rng, &receiver, 2, 3).expect("should split");
+
+        let share = &shares[2];
+        assert_eq!(Some(share), KeyShare::decode(share.encode()).as_ref());
+        assert_eq!(3, share.index());
+        assert_eq!(2, share.threshold());
+        assert_eq!(receiver.public_key(), share.public_key());
+
+        let mut zero_index = share.encode();
+        zero_index[..2].copy_from_slice(&[0, 0]);
+        assert_eq!(None, KeyShare::decode(zero_index));
+        assert_eq!(None, KeyShare::decode(&share.encode()[1..]));
+
+        let dec = share.decryption_share(&receiver.public_key());
+        assert_eq!(Some(dec), DecryptionShare::decode(dec.encode()));
+    }
+}
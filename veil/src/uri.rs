@@ -0,0 +1,74 @@
+//! URIs for exchanging public keys, e.g. as QR codes.
+//!
+//! A public key URI is the prefix `veil:pk:` followed by the public key in base58:
+//!
+//! ```text
+//! veil:pk:TkUWybv8fAvsHPhauPj7edUTVdCHuCFHazA6RjnvwJa
+//! ```
+
+use alloc::{format, string::String};
+
+use crate::{ParsePublicKeyError, PublicKey};
+
+/// The prefix of a public key URI.
+pub const PUBLIC_KEY_PREFIX: &str = "veil:pk:";
+
+/// Encodes the given public key as a URI.
+///
+/// ```
+/// use veil::{uri, PrivateKey};
+///
+/// let pk = PrivateKey::random(rand::thread_rng()).public_key();
+/// let uri = uri::encode(&pk);
+/// assert_eq!(format!("veil:pk:{pk}"), uri);
+/// assert_eq!(Ok(pk), uri::decode(&uri));
+/// ```
+#[must_use]
+pub fn encode(public_key: &PublicKey) -> String {
+    format!("{PUBLIC_KEY_PREFIX}{public_key}")
+}
+
+/// Decodes a public key from the given URI. The scheme and type are case-insensitive, but the
+/// public key is not.
+///
+/// # Errors
+///
+/// If the URI doesn't begin with `veil:pk:`, returns [`ParsePublicKeyError::InvalidPublicKey`]. If
+/// the rest of the URI isn't a valid public key, returns the error from parsing it.
+pub fn decode(uri: &str) -> Result<PublicKey, ParsePublicKeyError> {
+    let uri = uri.trim();
+    match uri.get(..PUBLIC_KEY_PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(PUBLIC_KEY_PREFIX) => {
+            uri[PUBLIC_KEY_PREFIX.len()..].parse()
+        }
+        _ => Err(ParsePublicKeyError::InvalidPublicKey),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn round_trip() {
+        let pk = PrivateKey::random(ChaChaRng::seed_from_u64(0xDEADBEEF)).public_key();
+        let uri = encode(&pk);
+        assert_eq!(Ok(pk), decode(&uri));
+        assert_eq!(Ok(pk), decode(&uri.replacen("veil:pk", "VEIL:PK", 1)));
+        assert_eq!(Ok(pk), decode(&format!("{uri}\n")));
+    }
+
+    #[test]
+    fn invalid() {
+        let pk = PrivateKey::random(ChaChaRng::seed_from_u64(0xDEADBEEF)).public_key();
+        assert_eq!(Err(ParsePublicKeyError::InvalidPublicKey), decode(&pk.to_string()));
+        assert_eq!(Err(ParsePublicKeyError::InvalidPublicKey), decode(&format!("veil:sk:{pk}")));
+        assert_eq!(Err(ParsePublicKeyError::InvalidPublicKey), decode("veil:"));
+        assert_matches!(decode("veil:pk:0OIl"), Err(ParsePublicKeyError::InvalidEncoding(_)));
+    }
+}
@@ -1,24 +1,54 @@
 //! The Veil hybrid cryptosystem.
 
-use std::{
+use alloc::string::{String, ToString};
+use core::{
     fmt,
     fmt::{Debug, Formatter},
-    io,
-    io::{Read, Write},
-    iter,
     str::FromStr,
 };
+#[cfg(feature = "std")]
+use std::{
+    io,
+    io::{Read, Seek, Write},
+    slice,
+};
 
-use rand::{prelude::SliceRandom, CryptoRng, Rng};
+#[cfg(all(feature = "std", unix))]
+use crate::agent::Agent;
+#[cfg(feature = "cbor")]
+use crate::artifact::{self, Artifact, Payload};
+#[cfg(feature = "async")]
+use futures_io::{AsyncRead, AsyncWrite};
+use lockstitch::Protocol;
+#[cfg(feature = "std")]
+use rand::RngCore;
+use rand::{CryptoRng, Rng};
 
 use crate::{
-    keys::{PrivKey, PubKey, POINT_LEN, SECRET_LEN},
-    mres, pbenc, schnorr, DecryptError, EncryptError, ParsePublicKeyError, Signature, VerifyError,
+    agree, certification, ct,
+    curve::{Curve, Selected},
+    derivation, fingerprint, key_bundle,
+    keys::{PrivKey, PubKey, POINT_LEN},
+    paper, revocation, subkey, Certification, DecryptionKey, Fingerprint, KeyBundle,
+    ParseCertificationError, ParseKeyBundleError, ParsePaperKeyError, ParseProofOfDerivationError,
+    ParsePublicKeyError, ParseSubkeysError, ProofOfDerivation, Revocation, SharedSecret,
+    SigningKey, Subkeys,
+};
+#[cfg(feature = "std")]
+use crate::{
+    audit::{Metered, Operation, Span},
+    backend, dvsig, kdf,
+    keys::SECRET_LEN,
+    options, pbenc,
+    recipient_set::CachedSender,
+    schnorr, wrap, DecryptError, DecryptOptions, DesignatedSignature, Digest, EncryptError,
+    EncryptOptions, Kdf, Padding, RecipientSet, Report, SignError, SignOptions, Signature,
+    TimestampedSignature, VerifyError, VerifyingReader,
 };
 
 /// A private key, used to encrypt, decrypt, and sign messages.
 #[derive(PartialEq, Eq)]
-pub struct PrivateKey(PrivKey);
+pub struct PrivateKey(pub(crate) PrivKey);
 
 impl PrivateKey {
     /// Creates a randomly generated private key.
@@ -27,252 +57,2774 @@ impl PrivateKey {
         PrivateKey(PrivKey::random(rng))
     }
 
+    /// Derives a private key from the given 64-byte seed.
+    ///
+    /// The same seed always produces the same private key, so an identity can be recreated from an
+    /// external source of entropy (e.g. dice rolls or a key management service). The seed is the
+    /// private key's secret: it must be uniformly random and kept as secret as the private key.
+    #[must_use]
+    pub fn from_seed(seed: &[u8; 64]) -> PrivateKey {
+        PrivateKey(PrivKey::from_secret_bytes(*seed))
+    }
+
     /// Returns the corresponding public key.
     #[must_use]
     pub const fn public_key(&self) -> PublicKey {
         PublicKey(self.0.pub_key)
     }
 
+    /// Converts the private key into an [`Agent`], which can use it on behalf of other processes over
+    /// a Unix socket. See [`crate::agent`].
+    #[cfg(all(feature = "std", unix))]
+    #[must_use]
+    pub const fn into_agent(self) -> Agent {
+        Agent::new(self)
+    }
+
     /// Encrypts the private key with the given passphrase and `veil.pbenc` parameters and writes it
     /// to the given writer.
     ///
+    /// The memory cost is split across `parallelism` lanes which are hashed on separate threads, or
+    /// in turn on WebAssembly. A parallelism of `0` is treated as `1`.
+    ///
     /// # Errors
     ///
-    /// If any of the `m_cost`, `t_cost`, or `p_cost` parameters are invalid, returns an error with
-    /// more details. Returns any error returned by operations on `writer`.
+    /// Returns any error returned by operations on `writer`.
+    #[cfg(feature = "std")]
     pub fn store(
         &self,
-        mut writer: impl Write,
+        writer: impl Write,
         rng: impl Rng + CryptoRng,
         passphrase: &[u8],
         time_cost: u8,
         memory_cost: u8,
+        parallelism: u8,
+    ) -> io::Result<usize> {
+        self.store_with(
+            writer,
+            rng,
+            passphrase,
+            Kdf::Balloon { time_cost, memory_cost, parallelism },
+        )
+    }
+
+    /// Encrypts the private key with the given passphrase and key derivation function and writes it
+    /// to the given writer.
+    ///
+    /// The encrypted private key is written alongside a decoy with the same parameters and random
+    /// contents, in a random order, so that it is the same length as a private key stored with
+    /// [`PrivateKey::store_with_duress`].
+    ///
+    /// Private keys encrypted with [`Kdf::Argon2id`] can be loaded with [`PrivateKey::load`], but
+    /// not by versions of Veil which predate Argon2id support.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error returned by operations on `writer`.
+    #[cfg(feature = "std")]
+    pub fn store_with(
+        &self,
+        writer: impl Write,
+        mut rng: impl Rng + CryptoRng,
+        passphrase: &[u8],
+        kdf: Kdf,
+    ) -> io::Result<usize> {
+        let enc_key = kdf::encrypt(&mut rng, passphrase, kdf, &self.0.secret);
+        let decoy = kdf::decoy(&mut rng, &enc_key);
+        write_envelopes(writer, rng, [enc_key, decoy])
+    }
+
+    /// Encrypts the private key with the given passphrase and a duress private key with a duress
+    /// passphrase, both with the given key derivation function, and writes them to the given writer
+    /// in a random order.
+    ///
+    /// [`PrivateKey::load`] returns this private key when given `passphrase` and the duress key
+    /// when given `duress_passphrase`, instead of an error, so that someone coerced into revealing
+    /// a passphrase can reveal the duress passphrase. Both keys are decrypted on every load, so the
+    /// time it takes doesn't reveal which passphrase was given. The duress key should be a
+    /// plausible identity of its own.
+    ///
+    /// The result is the same length as a private key stored with [`PrivateKey::store_with`], which
+    /// writes a decoy in place of the duress key, so it doesn't reveal that a duress key exists.
+    /// [`PrivateKey::change_passphrase`] only keeps the key which was decrypted.
+    ///
+    /// # Errors
+    ///
+    /// If the passphrases are the same, returns an [`io::ErrorKind::InvalidInput`] error. Returns
+    /// any error returned by operations on `writer`.
+    #[cfg(feature = "std")]
+    pub fn store_with_duress(
+        &self,
+        writer: impl Write,
+        mut rng: impl Rng + CryptoRng,
+        passphrase: &[u8],
+        duress_key: &PrivateKey,
+        duress_passphrase: &[u8],
+        kdf: Kdf,
     ) -> io::Result<usize> {
-        let mut enc_key = [0u8; SECRET_LEN + pbenc::OVERHEAD];
-        pbenc::encrypt(rng, passphrase, time_cost, memory_cost, &self.0.secret, &mut enc_key);
-        writer.write_all(&enc_key)?;
-        Ok(enc_key.len())
+        if passphrase == duress_passphrase {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "duress passphrase should differ",
+            ));
+        }
+
+        let enc_key = kdf::encrypt(&mut rng, passphrase, kdf, &self.0.secret);
+        let duress_enc_key = kdf::encrypt(&mut rng, duress_passphrase, kdf, &duress_key.0.secret);
+        write_envelopes(writer, rng, [enc_key, duress_enc_key])
     }
 
     /// Loads and decrypts the private key from the given reader with the given passphrase.
     ///
+    /// Private keys encrypted with either `veil.pbenc` or Argon2id can be loaded, as can either key
+    /// stored with [`PrivateKey::store_with_duress`] and private keys stored without a decoy.
+    ///
     /// # Errors
     ///
     /// If the passphrase is incorrect and/or the ciphertext has been modified, a
     /// [`DecryptError::InvalidCiphertext`] error will be returned. If an error occurred while
-    /// reading, a [`DecryptError::IoError`] error will be returned.
+    /// reading, a [`DecryptError::ReadIo`] error will be returned.
+    #[cfg(feature = "std")]
     pub fn load(mut reader: impl Read, passphrase: &[u8]) -> Result<PrivateKey, DecryptError> {
         let mut b = Vec::with_capacity(SECRET_LEN + pbenc::OVERHEAD);
         reader.read_to_end(&mut b).map_err(DecryptError::ReadIo)?;
 
-        // Decrypt the ciphertext and use the plaintext as the private key.
-        pbenc::decrypt(passphrase, &mut b)
-            .and_then(|b| b.try_into().ok())
+        // Decrypt the ciphertext and use the plaintext as the private key. If the ciphertext holds
+        // two envelopes, decrypt both halves, regardless of which succeeds.
+        let envelope_len = kdf::envelope_len(b.first().copied().unwrap_or_default(), SECRET_LEN);
+        let secret = if b.len() == 2 * envelope_len {
+            let (first, second) = b.split_at_mut(envelope_len);
+            let first = decrypt_secret(passphrase, first);
+            let second = decrypt_secret(passphrase, second);
+            first.or(second)
+        } else {
+            decrypt_secret(passphrase, &mut b)
+        };
+        secret
             .map(PrivKey::from_secret_bytes)
             .map(PrivateKey)
             .ok_or(DecryptError::InvalidCiphertext)
     }
 
+    /// Decrypts the given encrypted private key with `old_passphrase` and re-encrypts it with
+    /// `new_passphrase` and the given key derivation function, returning the new ciphertext.
+    ///
+    /// The private key itself is unchanged, so its public key remains the same.
+    ///
+    /// # Errors
+    ///
+    /// If the old passphrase is incorrect and/or the ciphertext has been modified, a
+    /// [`DecryptError::InvalidCiphertext`] error will be returned.
+    #[cfg(feature = "std")]
+    pub fn change_passphrase(
+        rng: impl Rng + CryptoRng,
+        ciphertext: &[u8],
+        old_passphrase: &[u8],
+        new_passphrase: &[u8],
+        kdf: Kdf,
+    ) -> Result<Vec<u8>, DecryptError> {
+        let private_key = PrivateKey::load(ciphertext, old_passphrase)?;
+        let mut out = Vec::with_capacity(SECRET_LEN + pbenc::OVERHEAD);
+        private_key
+            .store_with(&mut out, rng, new_passphrase, kdf)
+            .expect("writes to a Vec should be infallible");
+        Ok(out)
+    }
+
+    /// Exports the private key as a paper key: a sequence of 48 words from the BIP39 English word
+    /// list which can be written down and later used to recover the private key.
+    ///
+    /// The paper key is not encrypted and must be stored securely.
+    #[must_use]
+    pub fn export_paper_key(&self) -> String {
+        paper::encode(&self.0.secret).join(" ")
+    }
+
+    /// Recovers a private key from a paper key created with [`PrivateKey::export_paper_key`].
+    ///
+    /// Words may be separated by any whitespace and are matched case-insensitively.
+    ///
+    /// # Errors
+    ///
+    /// If the paper key has the wrong number of words, contains a word which is not in the word
+    /// list, or has an invalid checksum, returns a [`ParsePaperKeyError`].
+    pub fn import_paper_key(words: &str) -> Result<PrivateKey, ParsePaperKeyError> {
+        paper::decode(words.split_whitespace()).map(PrivKey::from_secret_bytes).map(PrivateKey)
+    }
+
+    /// Derives a group private key with the given name from this private key.
+    ///
+    /// Messages encrypted for a group's public key need only a single header, regardless of the
+    /// number of members. The group's owner gives the group private key to each member with
+    /// [`PrivateKey::invite`]. Because every member holds the same key, removing a member requires
+    /// deriving a new group with a different name and inviting the remaining members.
+    #[must_use]
+    pub fn derive_group(&self, name: &str) -> PrivateKey {
+        let mut group = Protocol::new("veil.group");
+        group.mix("secret", &self.0.secret);
+        group.mix("name", name.as_bytes());
+        PrivateKey(PrivKey::from_secret_bytes(group.derive_array("secret")))
+    }
+
+    /// Creates an invitation which allows the owner of `member` to recover this group private key
+    /// with [`PrivateKey::accept_invite`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from [`PrivateKey::seal`].
+    #[cfg(feature = "std")]
+    pub fn invite(
+        &self,
+        rng: impl Rng + CryptoRng,
+        member: &PublicKey,
+    ) -> Result<Vec<u8>, EncryptError> {
+        self.seal(rng, slice::from_ref(member), &self.0.secret)
+    }
+
+    /// Recovers a group private key from an invitation created by the owner of `group` with
+    /// [`PrivateKey::invite`].
+    ///
+    /// # Errors
+    ///
+    /// If the invitation was not created by the owner of the group's private key or was not created
+    /// for this private key, returns [`DecryptError::NotAReceiver`]. If it has been modified or does
+    /// not contain the group's private key, returns [`DecryptError::Corrupt`] or
+    /// [`DecryptError::Truncated`].
+    #[cfg(feature = "std")]
+    pub fn accept_invite(
+        &self,
+        group: &PublicKey,
+        invitation: &[u8],
+    ) -> Result<PrivateKey, DecryptError> {
+        let secret = self.open(group, invitation)?;
+        let secret = secret.try_into().map_err(|_| DecryptError::Corrupt)?;
+        let group_key = PrivateKey(PrivKey::from_secret_bytes(secret));
+        if &group_key.public_key() != group {
+            return Err(DecryptError::Corrupt);
+        }
+        Ok(group_key)
+    }
+
     /// Encrypts the contents of the reader and write the ciphertext to the writer.
     ///
-    /// Optionally add a number of fake receivers to disguise the number of true receivers and/or
-    /// random padding to disguise the message length.
+    /// The receivers, fake receivers, padding, associated data, and header order are given by
+    /// `options`. Fake receivers disguise the number of true receivers and random padding disguises
+    /// the message length. Unless the header order is set explicitly, the real and fake headers are
+    /// randomly interleaved using `rng`.
     ///
-    /// Returns the number of bytes of ciphertext written to `writer`.
+    /// Returns a [`Report`] of the lengths of the message's headers, padding, payload, and
+    /// signature. Its [`Report::written`], which `u64::from` also returns, is the number of bytes of
+    /// ciphertext written to `writer`.
     ///
     /// # Errors
     ///
     /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
-    /// will be returned.
+    /// will be returned. If the message would have more than
+    /// [`MAX_RECEIVERS`](crate::MAX_RECEIVERS) headers, more than
+    /// [`MAX_PADDING`](crate::MAX_PADDING) bytes of padding, or more than
+    /// [`MAX_MESSAGE_LEN`](crate::MAX_MESSAGE_LEN) bytes of plaintext, returns
+    /// [`EncryptError::TooManyReceivers`], [`EncryptError::TooMuchPadding`], or
+    /// [`EncryptError::MessageTooLong`].
+    #[cfg(feature = "std")]
     pub fn encrypt(
         &self,
-        mut rng: impl Rng + CryptoRng,
+        rng: impl Rng + CryptoRng,
         reader: impl Read,
         writer: impl Write,
-        receivers: &[PublicKey],
-        fakes: Option<usize>,
-        padding: Option<usize>,
-    ) -> Result<u64, EncryptError> {
-        let mut receivers = receivers
-            .iter()
-            .map(|pk| pk.0)
-            .chain(iter::repeat_with(|| PubKey::random(&mut rng)).take(fakes.unwrap_or_default()))
-            .collect::<Vec<PubKey>>();
-
-        // Shuffle the receivers list.
-        receivers.shuffle(&mut rng);
-
-        // Finally, encrypt.
-        mres::encrypt(&mut rng, reader, writer, &self.0, &receivers, padding.unwrap_or_default())
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, EncryptError> {
+        let span = Span::start(Operation::Encrypt, self.public_key(), options.receivers.len());
+        let mut reader = Metered(reader, 0);
+        let res = backend::encrypt(self, rng, &mut reader, writer, options);
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
     }
 
-    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`.
+    /// Encrypts the given plaintext, which may be a memory-mapped file, and writes the ciphertext
+    /// to the writer. See [`PrivateKey::encrypt`].
+    ///
+    /// This is a convenience wrapper which reads the slice with [`PrivateKey::encrypt`]; it's no
+    /// faster than encrypting any other reader.
     ///
-    /// Returns the number of bytes of plaintext written to `writer`.
+    /// Returns a [`Report`] of the message.
     ///
     /// # Errors
     ///
-    /// If the ciphertext has been modified, was not sent by the sender, or was not encrypted for
-    /// this private key, returns [`DecryptError::InvalidCiphertext`]. If there was an error reading
-    /// from `reader` or writing to `writer`, returns [`DecryptError::IoError`].
-    pub fn decrypt(
+    /// If there is an error while writing to `writer`, an [`io::Error`] will be returned. If the
+    /// message would exceed the limits on its receivers, padding, or plaintext, returns
+    /// [`EncryptError::TooManyReceivers`], [`EncryptError::TooMuchPadding`], or
+    /// [`EncryptError::MessageTooLong`].
+    #[cfg(feature = "std")]
+    pub fn encrypt_slice(
         &self,
-        reader: impl Read,
+        rng: impl Rng + CryptoRng,
+        plaintext: &[u8],
         writer: impl Write,
-        sender: &PublicKey,
-    ) -> Result<u64, DecryptError> {
-        mres::decrypt(reader, writer, &self.0, &sender.0)
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, EncryptError> {
+        self.encrypt(rng, plaintext, writer, options)
     }
 
-    /// Reads the contents of the reader and returns a digital signature.
+    /// Encrypts the contents of the reader and write the ciphertext to the writer, asynchronously.
+    /// See [`PrivateKey::encrypt`].
     ///
     /// # Errors
     ///
-    /// If there is an error while reading from `message`, an [`io::Error`] will be returned.
-    pub fn sign(&self, rng: impl Rng + CryptoRng, message: impl Read) -> io::Result<Signature> {
-        schnorr::sign(rng, &self.0, message)
-    }
-}
-
-impl Debug for PrivateKey {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        self.public_key().fmt(f)
+    /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
+    /// will be returned.
+    #[cfg(feature = "async")]
+    pub async fn encrypt_async(
+        &self,
+        rng: impl Rng + CryptoRng,
+        reader: impl AsyncRead + Unpin,
+        writer: impl AsyncWrite + Unpin,
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, EncryptError> {
+        let span = Span::start(Operation::Encrypt, self.public_key(), options.receivers.len());
+        let mut reader = Metered(reader, 0);
+        let res = backend::encrypt_async(self, rng, &mut reader, writer, options).await;
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
     }
-}
 
-/// A public key, used to verify messages.
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub struct PublicKey(PubKey);
-
-impl PublicKey {
-    /// Decode a public key from a 32-byte slice.
+    /// Precomputes the state for encrypting many messages to the given receivers with
+    /// [`PrivateKey::encrypt_with_set`].
+    #[cfg(feature = "std")]
     #[must_use]
-    pub fn decode(b: impl AsRef<[u8]>) -> Option<PublicKey> {
-        PubKey::from_canonical_bytes(b).map(PublicKey)
+    pub fn recipient_set(&self, receivers: &[PublicKey]) -> RecipientSet {
+        RecipientSet::new(self, receivers)
     }
 
-    /// Encode the public key as a 32-byte array.
-    #[must_use]
-    pub const fn encode(&self) -> [u8; POINT_LEN] {
-        self.0.encoded
+    /// Encrypts the contents of the reader and writes the ciphertext to the writer, like
+    /// [`PrivateKey::encrypt`], using the precomputed state in `set` for any receivers in it.
+    ///
+    /// The receivers and other options are given by `options`, which is usually created with
+    /// [`RecipientSet::options`]. Receivers not in the set are encrypted for as usual, and a set
+    /// created by a different private key is ignored.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
+    /// will be returned.
+    #[cfg(feature = "std")]
+    pub fn encrypt_with_set(
+        &self,
+        rng: impl Rng + CryptoRng,
+        reader: impl Read,
+        writer: impl Write,
+        set: &RecipientSet,
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, EncryptError> {
+        let span = Span::start(Operation::Encrypt, self.public_key(), options.receivers.len());
+        let mut reader = Metered(reader, 0);
+        let sender = CachedSender::new(self, set);
+        let res = backend::encrypt(&sender, rng, &mut reader, writer, options);
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
     }
 
-    /// Verifies that the given signature was created by the owner of this public key for the exact
-    /// contents of `message`. Returns `Ok(())` if successful.
+    /// Encrypts the contents of the reader for this private key's own public key and writes the
+    /// ciphertext to the writer, e.g. for personal backups.
+    ///
+    /// The message has a single header and no fake receivers, so decrypting it with this private
+    /// key opens the first header it tries. Returns a [`Report`] of the message.
     ///
     /// # Errors
     ///
-    /// If the message has been modified or was not signed by the owner of this public key, returns
-    /// [`VerifyError::InvalidSignature`]. If there was an error reading from `message` or writing
-    /// to `writer`, returns [`VerifyError::IoError`].
-    pub fn verify(&self, message: impl Read, sig: &Signature) -> Result<(), VerifyError> {
-        schnorr::verify(&self.0, message, sig)
+    /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
+    /// will be returned.
+    #[cfg(feature = "std")]
+    pub fn encrypt_self(
+        &self,
+        rng: impl Rng + CryptoRng,
+        reader: impl Read,
+        writer: impl Write,
+        padding: Padding,
+    ) -> Result<Report, EncryptError> {
+        let receivers = [self.public_key()];
+        self.encrypt(rng, reader, writer, &EncryptOptions::new(&receivers).padding_policy(padding))
     }
-}
 
-impl Debug for PublicKey {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.to_string())
+    /// Encrypts the contents of the reader and write the ciphertext to the writer, deriving all
+    /// randomness from the given seed.
+    ///
+    /// The ephemeral key, DEK, nonce, fake receivers, header order, and padding are all derived from
+    /// this private key, the seed, and the receivers, so encrypting the same plaintext with the same
+    /// seed produces the same ciphertext. Encrypting different plaintexts with the same seed reveals
+    /// information about them, so each plaintext must have its own seed (e.g. a digest of the
+    /// plaintext). See [`PrivateKey::encrypt`].
+    ///
+    /// Each header's nonce is derived from the message's state and the receiver's index rather
+    /// than drawn from the RNG, so the ciphertext does not depend on how many threads encrypt the
+    /// headers. Re-encrypting a plaintext with the same seed and options on any machine produces a
+    /// byte-identical ciphertext.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
+    /// will be returned.
+    #[cfg(feature = "std")]
+    pub fn encrypt_deterministic(
+        &self,
+        seed: &[u8],
+        reader: impl Read,
+        writer: impl Write,
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, EncryptError> {
+        let rng = SeededRng::new(&self.0, seed, options.receivers);
+        self.encrypt(rng, reader, writer, options)
     }
-}
 
-impl fmt::Display for PublicKey {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    /// Encrypts the contents of the reader and write the ciphertext to the writer as the anonymous
+    /// sender.
+    ///
+    /// Instead of a private key, the headers are encrypted with the anonymous sender's private key,
+    /// which anyone can derive, and the message is keyed by its ephemeral key alone. The receivers
+    /// decrypt it with [`PublicKey::anonymous`] as the sender. The message is confidential, but
+    /// it is not authenticated: its receivers can't tell who sent it, nor prove it to anyone else.
+    /// See [`PrivateKey::encrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
+    /// will be returned.
+    #[cfg(feature = "std")]
+    pub fn encrypt_anonymous(
+        rng: impl Rng + CryptoRng,
+        reader: impl Read,
+        writer: impl Write,
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, EncryptError> {
+        PrivateKey(PrivKey::anonymous()).encrypt(rng, reader, writer, options)
     }
-}
-
-impl FromStr for PublicKey {
-    type Err = ParsePublicKeyError;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        PublicKey::decode(bs58::decode(s).into_vec()?.as_slice())
-            .ok_or(ParsePublicKeyError::InvalidPublicKey)
+    /// Encrypts the given plaintext for the given receivers and returns the ciphertext.
+    ///
+    /// This is a convenience for small messages which uses no fake receivers and no padding. See
+    /// [`PrivateKey::encrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If there are more than [`MAX_RECEIVERS`](crate::MAX_RECEIVERS) receivers or the plaintext is
+    /// longer than [`MAX_MESSAGE_LEN`](crate::MAX_MESSAGE_LEN) bytes, returns
+    /// [`EncryptError::TooManyReceivers`] or [`EncryptError::MessageTooLong`].
+    #[cfg(feature = "std")]
+    pub fn seal(
+        &self,
+        rng: impl Rng + CryptoRng,
+        receivers: &[PublicKey],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptError> {
+        self.seal_with(rng, plaintext, &EncryptOptions::new(receivers))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::io::Cursor;
-
-    use assert_matches::assert_matches;
-    use expect_test::expect;
-    use rand::{RngCore, SeedableRng};
-    use rand_chacha::ChaChaRng;
 
-    use super::*;
-
-    #[test]
-    fn public_key_encoding() {
-        let rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
-        let pk = PrivateKey::random(rng).public_key();
+    /// Encrypts the given plaintext with the given options and returns the ciphertext.
+    ///
+    /// This is a convenience for environments without streaming IO, like WebAssembly in a browser.
+    /// See [`PrivateKey::encrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If the message would exceed the limits on its receivers, padding, or plaintext, returns
+    /// [`EncryptError::TooManyReceivers`], [`EncryptError::TooMuchPadding`], or
+    /// [`EncryptError::MessageTooLong`].
+    #[cfg(feature = "std")]
+    pub fn seal_with(
+        &self,
+        rng: impl Rng + CryptoRng,
+        plaintext: &[u8],
+        options: &EncryptOptions<'_>,
+    ) -> Result<Vec<u8>, EncryptError> {
+        let mut ciphertext = Vec::new();
+        self.encrypt(rng, plaintext, &mut ciphertext, options)?;
+        Ok(ciphertext)
+    }
 
-        expect!["CNYNfYzLm22yHDwpJJAe2yo6S3nEcFzge873NCRce8kF"].assert_eq(&pk.to_string());
+    /// Decrypts the given ciphertext, if possible, and returns the plaintext. See
+    /// [`PrivateKey::decrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender or was not encrypted for this private key,
+    /// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+    /// [`DecryptError::Corrupt`] or [`DecryptError::Truncated`].
+    #[cfg(feature = "std")]
+    pub fn open(&self, sender: &PublicKey, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        self.open_with_associated_data(sender, ciphertext, &[])
+    }
 
-        let decoded = pk.to_string().parse::<PublicKey>();
-        assert_eq!(Ok(pk), decoded, "error parsing public key");
+    /// Decrypts the given ciphertext, which must have been encrypted with the given associated data,
+    /// if possible, and returns the plaintext. See [`PrivateKey::decrypt_with_associated_data`].
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender, was not encrypted for this private key, or was
+    /// encrypted with different associated data, returns [`DecryptError::NotAReceiver`]. If it has
+    /// been modified, returns [`DecryptError::Corrupt`] or [`DecryptError::Truncated`].
+    #[cfg(feature = "std")]
+    pub fn open_with_associated_data(
+        &self,
+        sender: &PublicKey,
+        ciphertext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, DecryptError> {
+        let mut plaintext = Vec::with_capacity(ciphertext.len());
+        self.decrypt_with_associated_data(ciphertext, &mut plaintext, sender, associated_data)?;
+        Ok(plaintext)
+    }
 
-        assert_eq!(
-            Err(ParsePublicKeyError::InvalidEncoding(bs58::decode::Error::InvalidCharacter {
-                character: 'l',
-                index: 4,
-            })),
-            "invalid key".parse::<PublicKey>(),
-            "decoded invalid public key"
-        );
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`.
+    ///
+    /// Returns a [`Report`] of the lengths of the message's headers, padding, payload, and
+    /// signature. Its [`Report::written`], which `u64::from` also returns, is the number of bytes of
+    /// plaintext written to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender or was not encrypted for this private key,
+    /// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+    /// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If
+    /// there was an error reading from `reader` or writing to `writer`, returns
+    /// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt(
+        &self,
+        reader: impl Read,
+        writer: impl Write,
+        sender: &PublicKey,
+    ) -> Result<Report, DecryptError> {
+        self.decrypt_with_associated_data(reader, writer, sender, &[])
     }
 
-    #[test]
-    fn round_trip() {
-        let (_, a, b, plaintext, ciphertext) = setup(64);
-        let mut dst = Cursor::new(Vec::new());
-        let ptx_len = b
-            .decrypt(Cursor::new(ciphertext), &mut dst, &a.public_key())
-            .expect("decryption should be ok");
-        assert_eq!(dst.position(), ptx_len, "returned/observed plaintext length mismatch");
-        assert_eq!(plaintext.to_vec(), dst.into_inner(), "incorrect plaintext");
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`,
+    /// asynchronously. The ciphertext must have been encrypted with the given associated data, if
+    /// any. See [`PrivateKey::decrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender or was not encrypted for this private key,
+    /// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+    /// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If
+    /// there was an error reading from `reader` or writing to `writer`, returns
+    /// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+    #[cfg(feature = "async")]
+    pub async fn decrypt_async(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        writer: impl AsyncWrite + Unpin,
+        sender: &PublicKey,
+        associated_data: &[u8],
+    ) -> Result<Report, DecryptError> {
+        let span = Span::start(Operation::Decrypt, self.public_key(), 0);
+        let mut reader = Metered(reader, 0);
+        let res = backend::decrypt_async(self, &mut reader, writer, sender, associated_data).await;
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
     }
 
-    #[test]
-    fn wrong_sender() {
-        let (rng, _, b, _, ciphertext) = setup(64);
-        let c = PrivateKey::random(rng);
-        assert_matches!(
-            b.decrypt(Cursor::new(ciphertext), io::sink(), &c.public_key()),
-            Err(DecryptError::InvalidCiphertext)
-        );
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`,
+    /// asynchronously, within the limits given by `options`. See
+    /// [`PrivateKey::decrypt_with_options`].
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender, was not encrypted for this private key, or was
+    /// encrypted with different associated data, returns [`DecryptError::NotAReceiver`]. If it has
+    /// been modified, returns [`DecryptError::Corrupt`], and if it is incomplete, returns
+    /// [`DecryptError::Truncated`]. If its headers and padding or its plaintext are longer than
+    /// the limits, returns [`DecryptError::HeadersTooLong`] or [`DecryptError::PlaintextTooLong`].
+    /// If there was an error reading from `reader` or writing to `writer`, returns
+    /// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+    #[cfg(feature = "async")]
+    pub async fn decrypt_async_with_options(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        writer: impl AsyncWrite + Unpin,
+        sender: &PublicKey,
+        options: &DecryptOptions<'_>,
+    ) -> Result<Report, DecryptError> {
+        let span = Span::start(Operation::Decrypt, self.public_key(), 0);
+        let mut reader = Metered(reader, 0);
+        let res =
+            backend::decrypt_async_with_options(self, &mut reader, writer, sender, options).await;
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
     }
 
-    #[test]
-    fn wrong_receiver() {
-        let (rng, a, _, _, ciphertext) = setup(64);
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`. The
+    /// ciphertext must have been encrypted with the same associated data. See
+    /// [`PrivateKey::decrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender, was not encrypted for this private key, or was
+    /// encrypted with different associated data, returns [`DecryptError::NotAReceiver`]. If it has
+    /// been modified, returns [`DecryptError::Corrupt`], and if it is incomplete, returns
+    /// [`DecryptError::Truncated`]. If there was an error reading from `reader` or writing to
+    /// `writer`, returns [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt_with_associated_data(
+        &self,
+        reader: impl Read,
+        writer: impl Write,
+        sender: &PublicKey,
+        associated_data: &[u8],
+    ) -> Result<Report, DecryptError> {
+        let span = Span::start(Operation::Decrypt, self.public_key(), 0);
+        let mut reader = Metered(reader, 0);
+        let res = backend::decrypt(self, &mut reader, writer, sender, associated_data);
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
+    }
+
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`, within
+    /// the limits on the lengths of the message's headers and plaintext given by `options`. The
+    /// ciphertext must have been encrypted with the associated data given by `options`, if any. See
+    /// [`PrivateKey::decrypt`].
+    ///
+    /// The plaintext written before a limit is exceeded has not been authenticated, and should be
+    /// discarded along with the rest of the output.
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender, was not encrypted for this private key, or was
+    /// encrypted with different associated data, returns [`DecryptError::NotAReceiver`]. If it has
+    /// been modified, returns [`DecryptError::Corrupt`], and if it is incomplete, returns
+    /// [`DecryptError::Truncated`]. If its headers and padding or its plaintext are longer than
+    /// the limits, returns [`DecryptError::HeadersTooLong`] or [`DecryptError::PlaintextTooLong`].
+    /// If there was an error reading from `reader` or writing to `writer`, returns
+    /// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt_with_options(
+        &self,
+        reader: impl Read,
+        writer: impl Write,
+        sender: &PublicKey,
+        options: &DecryptOptions<'_>,
+    ) -> Result<Report, DecryptError> {
+        let span = Span::start(Operation::Decrypt, self.public_key(), 0);
+        let mut reader = Metered(reader, 0);
+        let res = backend::decrypt_with_options(self, &mut reader, writer, sender, options);
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
+    }
+
+    /// Re-encrypts a message sent to this private key by the owner of `sender` for the receivers
+    /// given by `options`, reading the original ciphertext from `reader` and writing the new
+    /// ciphertext to `writer`. The new message is sent by this private key.
+    ///
+    /// The plaintext is never written: each block of the original message is decrypted in memory
+    /// and immediately encrypted again, so rekeying a message of any size uses a constant amount
+    /// of memory. The payload can't be copied without decrypting it, as each block's encryption
+    /// depends on the message's headers. The new message keeps the original's format version, so a
+    /// compressed message stays compressed and a seekable message stays seekable, and both
+    /// messages use the associated data given by `options`.
+    ///
+    /// The original message's signature is only verified after the last block is re-encrypted. If
+    /// an error is returned, the output must be discarded.
+    ///
+    /// Returns a [`Report`] of the new message, whose [`Report::written`] is the number of bytes of
+    /// ciphertext written to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender or was not encrypted for this private key,
+    /// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+    /// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If
+    /// there was an error reading from `reader` or writing to `writer`, returns
+    /// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn rekey(
+        &self,
+        rng: impl Rng + CryptoRng,
+        reader: impl Read,
+        writer: impl Write,
+        sender: &PublicKey,
+        options: &EncryptOptions<'_>,
+    ) -> Result<Report, DecryptError> {
+        let span = Span::start(Operation::Rekey, self.public_key(), options.receivers.len());
+        let mut reader = Metered(reader, 0);
+        let res = backend::rekey(self, rng, &mut reader, writer, sender, options);
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
+    }
+
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to `writer`. The
+    /// ciphertext must have been encrypted with the given channel ID and sequence number, set with
+    /// [`EncryptOptions::sequence`]. See [`PrivateKey::decrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not encrypted with the given channel ID and sequence number (e.g. it
+    /// was replayed or reordered), was not sent by the sender, or was not encrypted for this
+    /// private key, returns [`DecryptError::SequenceMismatch`] before decrypting any of the
+    /// payload. If it has been modified, returns [`DecryptError::Corrupt`], and if it is
+    /// incomplete, returns [`DecryptError::Truncated`]. If there was an error reading from
+    /// `reader` or writing to `writer`, returns [`DecryptError::ReadIo`] or
+    /// [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt_in_sequence(
+        &self,
+        reader: impl Read,
+        writer: impl Write,
+        sender: &PublicKey,
+        channel: &[u8],
+        sequence: u64,
+    ) -> Result<Report, DecryptError> {
+        let associated_data = options::sequence_associated_data(channel, sequence);
+        match self.decrypt_with_associated_data(reader, writer, sender, &associated_data) {
+            Err(DecryptError::NotAReceiver) => Err(DecryptError::SequenceMismatch),
+            res => res,
+        }
+    }
+
+    /// Decrypts the given ciphertext, which may be a memory-mapped file, if possible, and writes
+    /// the plaintext to `writer`. The ciphertext must have been encrypted with the given associated
+    /// data, if any. See [`PrivateKey::decrypt`].
+    ///
+    /// This is a convenience wrapper which reads the slice with
+    /// [`PrivateKey::decrypt_with_associated_data`]; it's no faster than decrypting any other
+    /// reader.
+    ///
+    /// Returns a [`Report`] of the message.
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender, was not encrypted for this private key, or was
+    /// encrypted with different associated data, returns [`DecryptError::NotAReceiver`]. If it has
+    /// been modified, returns [`DecryptError::Corrupt`], and if it is incomplete, returns
+    /// [`DecryptError::Truncated`]. If there was an error writing to `writer`, returns
+    /// [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt_slice(
+        &self,
+        ciphertext: &[u8],
+        writer: impl Write,
+        sender: &PublicKey,
+        associated_data: &[u8],
+    ) -> Result<Report, DecryptError> {
+        self.decrypt_with_associated_data(ciphertext, writer, sender, associated_data)
+    }
+
+    /// Decrypts the contents of `reader`, if possible, and writes the plaintext to the seekable
+    /// `writer`. The ciphertext must have been encrypted with the given associated data, if any. See
+    /// [`PrivateKey::decrypt`].
+    ///
+    /// Plaintext is written before the message's signature is verified. If decryption fails, every
+    /// byte written to `writer` is overwritten with zeros before the error is returned, so a forged
+    /// or truncated message never leaves unauthenticated plaintext in the output.
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender, was not encrypted for this private key, or was
+    /// encrypted with different associated data, returns [`DecryptError::NotAReceiver`]. If it has
+    /// been modified, returns [`DecryptError::Corrupt`], and if it is incomplete, returns
+    /// [`DecryptError::Truncated`]. If there was an error reading from `reader` or writing to
+    /// `writer`, returns [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`]. If the
+    /// plaintext written to `writer` cannot be scrubbed, returns [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt_scrubbed(
+        &self,
+        reader: impl Read,
+        writer: impl Write + Seek,
+        sender: &PublicKey,
+        associated_data: &[u8],
+    ) -> Result<Report, DecryptError> {
+        let span = Span::start(Operation::Decrypt, self.public_key(), 0);
+        let mut reader = Metered(reader, 0);
+        let res = backend::decrypt_scrubbed(self, &mut reader, writer, sender, associated_data);
+        span.finish(reader.1, res.as_ref().ok().map(Report::written));
+        res
+    }
+
+    /// Decrypts the contents of `reader` with whichever of the given private keys it was encrypted
+    /// for, if any, and writes the plaintext to `writer`.
+    ///
+    /// The headers are scanned in a single pass, so this is much cheaper than calling
+    /// [`PrivateKey::decrypt`] once per key.
+    ///
+    /// Returns the index of the private key the message was encrypted for and a [`Report`] of the
+    /// message.
+    ///
+    /// # Errors
+    ///
+    /// If the ciphertext was not sent by the sender or was not encrypted for any of the private keys,
+    /// returns [`DecryptError::NotAReceiver`]. If it has been modified, returns
+    /// [`DecryptError::Corrupt`], and if it is incomplete, returns [`DecryptError::Truncated`]. If
+    /// there was an error reading from `reader` or writing to `writer`, returns
+    /// [`DecryptError::ReadIo`] or [`DecryptError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn decrypt_any(
+        keys: &[PrivateKey],
+        reader: impl Read,
+        writer: impl Write,
+        sender: &PublicKey,
+    ) -> Result<(usize, Report), DecryptError> {
+        // Every key is used to scan the headers, so each key's use is recorded.
+        let spans = keys
+            .iter()
+            .map(|k| Span::start(Operation::Decrypt, k.public_key(), 0))
+            .collect::<Vec<_>>();
+        let mut reader = Metered(reader, 0);
+        let res = backend::decrypt_any(keys, &mut reader, writer, sender, &[]);
+        for (i, span) in spans.into_iter().enumerate() {
+            let written = res.as_ref().ok().filter(|&&(idx, _)| idx == i).map(|(_, r)| r.written());
+            span.finish(reader.1, written);
+        }
+        res
+    }
+
+    /// Derives a symmetric secret shared with the owner of the given public key, for use in other
+    /// protocols. The owner of the public key will derive the same secret from their private key and
+    /// this private key's public key.
+    ///
+    /// The `context` string should uniquely identify the application and purpose of the secret;
+    /// different contexts produce unrelated secrets.
+    #[must_use]
+    pub fn agree(&self, public_key: &PublicKey, context: &str) -> SharedSecret {
+        agree::agree(&self.0, &public_key.0, context)
+    }
+
+    /// Encrypts a symmetric key (e.g. a database or backup encryption key) for the owner of the
+    /// given public key and returns the wrapped key, which is [`WRAPPED_KEY_OVERHEAD`] bytes
+    /// longer than the key.
+    ///
+    /// Unlike [`PrivateKey::seal`], the wrapped key has a single `veil.sres` header and no
+    /// message signature, so wrapping a 32-byte key produces a 144-byte result. The wrapped key
+    /// reveals the length of the key.
+    ///
+    /// [`WRAPPED_KEY_OVERHEAD`]: crate::WRAPPED_KEY_OVERHEAD
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn wrap_key(&self, rng: impl Rng + CryptoRng, receiver: &PublicKey, key: &[u8]) -> Vec<u8> {
+        wrap::wrap(rng, self, &receiver.0, key)
+    }
+
+    /// Decrypts a key wrapped for this private key by the owner of the given public key with
+    /// [`PrivateKey::wrap_key`] and returns the key.
+    ///
+    /// # Errors
+    ///
+    /// If the wrapped key is too short to be valid, returns [`DecryptError::Truncated`]. If it was
+    /// not wrapped for this private key by the sender or has been modified, returns
+    /// [`DecryptError::NotAReceiver`].
+    #[cfg(feature = "std")]
+    pub fn unwrap_key(&self, sender: &PublicKey, wrapped: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        wrap::unwrap(self, &sender.0, wrapped)
+    }
+
+    /// Reads the contents of the reader and returns a digital signature.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+    #[cfg(feature = "std")]
+    pub fn sign(&self, rng: impl Rng + CryptoRng, message: impl Read) -> io::Result<Signature> {
+        let span = Span::start(Operation::Sign, self.public_key(), 0);
+        let mut message = Metered(message, 0);
+        let res = backend::sign(self, rng, &mut message);
+        span.finish(message.1, res.as_ref().ok().map(|_| schnorr::SIGNATURE_LEN as u64));
+        res
+    }
+
+    /// Reads the contents of the reader and returns a digital signature in the context set in
+    /// `options`, reporting progress and checking for cancellation as set in `options`. See
+    /// [`PrivateKey::sign`].
+    ///
+    /// # Errors
+    ///
+    /// If the cancellation flag is set before the entire message is read, returns
+    /// [`SignError::Cancelled`]. If there is an error while reading from `message`, returns
+    /// [`SignError::ReadIo`].
+    #[cfg(feature = "std")]
+    pub fn sign_with(
+        &self,
+        rng: impl Rng + CryptoRng,
+        message: impl Read,
+        options: &SignOptions<'_>,
+    ) -> Result<Signature, SignError> {
+        let span = Span::start(Operation::Sign, self.public_key(), 0);
+        let mut message = Metered(options.reader(message), 0);
+        let res = schnorr::sign(rng, self, options.context, &mut message);
+        span.finish(message.1, res.as_ref().ok().map(|_| schnorr::SIGNATURE_LEN as u64));
+        res.map_err(
+            |e| {
+                if message.0.cancelled {
+                    SignError::Cancelled
+                } else {
+                    SignError::ReadIo(e)
+                }
+            },
+        )
+    }
+
+    /// Reads the contents of the reader and returns a digital signature which asserts that the
+    /// message was signed at the given timestamp, typically in seconds since the Unix epoch.
+    ///
+    /// A timestamped signature can only be verified with [`PublicKey::verify_timestamped`], not as
+    /// a signature without a timestamp.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+    #[cfg(feature = "std")]
+    pub fn sign_timestamped(
+        &self,
+        rng: impl Rng + CryptoRng,
+        message: impl Read,
+        timestamp: u64,
+    ) -> io::Result<TimestampedSignature> {
+        let span = Span::start(Operation::Sign, self.public_key(), 0);
+        let mut message = Metered(message, 0);
+        let res = backend::sign_timestamped(self, rng, &mut message, timestamp);
+        span.finish(
+            message.1,
+            res.as_ref().ok().map(|_| schnorr::TIMESTAMPED_SIGNATURE_LEN as u64),
+        );
+        res
+    }
+
+    /// Derives a secret key from this private key for keyed digests, for use with
+    /// [`DigestOptions::key`](crate::DigestOptions::key). Only the holder of this private key can
+    /// calculate or check digests keyed with it.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn digest_key(&self) -> [u8; 32] {
+        let mut key = Protocol::new("veil.digest.key");
+        key.mix("secret", &self.0.secret);
+        key.derive_array("key")
+    }
+
+    /// Returns a digital signature of the given digest, which may have been calculated elsewhere
+    /// with [`Digest::new`]. A signature of a digest can only be verified with
+    /// [`PublicKey::verify_digest`], not as a signature of the message itself.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn sign_digest(&self, rng: impl Rng + CryptoRng, digest: &Digest) -> Signature {
+        let span = Span::start(Operation::Sign, self.public_key(), 0);
+        let sig = schnorr::sign_digest(rng, self, digest);
+        span.finish(digest.encode().len() as u64, Some(schnorr::SIGNATURE_LEN as u64));
+        sig
+    }
+
+    /// Reads the contents of the reader and returns an [`Artifact`] with a signature of it in the
+    /// given context, the fingerprint of this private key's public key, and the given creation
+    /// time, in seconds since the Unix epoch. See [`crate::artifact`].
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+    #[cfg(feature = "cbor")]
+    pub fn sign_artifact(
+        &self,
+        mut rng: impl Rng + CryptoRng,
+        message: impl Read,
+        context: &str,
+        created: u64,
+    ) -> io::Result<Artifact> {
+        let sig = self.sign_with(&mut rng, message, &SignOptions::new().context(context)).map_err(
+            |e| match e {
+                SignError::ReadIo(e) => e,
+                SignError::Cancelled => unreachable!("signing without a flag can't be cancelled"),
+            },
+        )?;
+        Ok(artifact::create(rng, self, Payload::Signature(sig), context, created))
+    }
+
+    /// Returns an [`Artifact`] with the given digest, context, the fingerprint of this private
+    /// key's public key, and the given creation time, in seconds since the Unix epoch. See
+    /// [`crate::artifact`].
+    #[cfg(feature = "cbor")]
+    #[must_use]
+    pub fn digest_artifact(
+        &self,
+        rng: impl Rng + CryptoRng,
+        digest: &Digest,
+        context: &str,
+        created: u64,
+    ) -> Artifact {
+        artifact::create(rng, self, Payload::Digest(*digest), context, created)
+    }
+
+    /// Reads the contents of the reader and writes it to the writer with an attached digital
+    /// signature, producing a single artifact which can be verified with
+    /// [`PublicKey::verify_attached`]. Returns the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `reader` or writing to `writer`, an [`io::Error`]
+    /// will be returned.
+    #[cfg(feature = "std")]
+    pub fn sign_attached(
+        &self,
+        rng: impl Rng + CryptoRng,
+        reader: impl Read,
+        writer: impl Write,
+    ) -> io::Result<u64> {
+        let span = Span::start(Operation::Sign, self.public_key(), 0);
+        let mut reader = Metered(reader, 0);
+        let res = backend::sign_attached(self, rng, &mut reader, writer);
+        span.finish(reader.1, res.as_ref().ok().copied());
+        res
+    }
+
+    /// Reads the contents of the reader and returns a designated-verifier signature which can only
+    /// be verified by the owner of `verifier`'s private key.
+    ///
+    /// Because the verifier could have created the same signature themselves, the signature does
+    /// not convince anyone else that this private key signed the message.
+    ///
+    /// # Errors
+    ///
+    /// If there is an error while reading from `message`, an [`io::Error`] will be returned.
+    #[cfg(feature = "std")]
+    pub fn sign_for(
+        &self,
+        rng: impl Rng + CryptoRng,
+        verifier: &PublicKey,
+        message: impl Read,
+    ) -> io::Result<DesignatedSignature> {
+        let span = Span::start(Operation::Sign, self.public_key(), 0);
+        let mut message = Metered(message, 0);
+        let res = dvsig::sign(rng, self, &verifier.0, &mut message);
+        span.finish(message.1, res.as_ref().ok().map(|sig| sig.encode().len() as u64));
+        res
+    }
+
+    /// Creates a signed statement that this private key has been revoked for the given reason at
+    /// the given time, in seconds since the Unix epoch. Anyone with the public key can verify it.
+    #[must_use]
+    pub fn revoke(&self, rng: impl Rng + CryptoRng, reason: &str, timestamp: u64) -> Revocation {
+        revocation::revoke(rng, self, reason, timestamp)
+    }
+
+    /// Creates a signed statement that the given public key belongs to the identity described by
+    /// `label` (e.g. a name or email address) between `not_before` and `not_after`, in seconds
+    /// since the Unix epoch. Anyone with this private key's public key can verify it with
+    /// [`PublicKey::verify_certification`].
+    #[must_use]
+    pub fn certify(
+        &self,
+        rng: impl Rng + CryptoRng,
+        subject: &PublicKey,
+        label: &str,
+        not_before: u64,
+        not_after: u64,
+    ) -> Certification {
+        certification::certify(rng, self, subject, label, not_before, not_after)
+    }
+
+    /// Derives the private key at the given path (e.g. `devices/laptop`) below this private key.
+    ///
+    /// Each path produces an independent private key. Prove that a derived key belongs to this
+    /// private key's hierarchy with [`PrivateKey::prove_derivation`].
+    #[must_use]
+    pub fn derive_path(&self, path: &str) -> PrivateKey {
+        PrivateKey(derivation::derive(&self.0, path).0)
+    }
+
+    /// Creates a proof, signed by both this private key and the private key at the given path
+    /// below it, that the derived key belongs to this private key's hierarchy.
+    ///
+    /// The proof contains a commitment to the path instead of the path itself, so it reveals
+    /// nothing about the path or any other derived key. Anyone with this private key's public key
+    /// can verify it with [`PublicKey::verify_derivation`].
+    #[must_use]
+    pub fn prove_derivation(&self, rng: impl Rng + CryptoRng, path: &str) -> ProofOfDerivation {
+        derivation::prove(rng, &self.0, path)
+    }
+
+    /// Derives the subkey of this private key which can only sign messages.
+    ///
+    /// The signing and decryption subkeys are derived independently from this private key's secret,
+    /// so neither the subkeys nor this private key share a point. Advertise the subkeys' public
+    /// keys with [`PrivateKey::export_subkeys`].
+    #[must_use]
+    pub fn signing_key(&self) -> SigningKey {
+        subkey::signing_key(&self.0)
+    }
+
+    /// Derives the subkey of this private key which can only encrypt and decrypt messages. See
+    /// [`PrivateKey::signing_key`].
+    #[must_use]
+    pub fn decryption_key(&self) -> DecryptionKey {
+        subkey::decryption_key(&self.0)
+    }
+
+    /// Creates a statement of this private key's subkey public keys, signed by this private key.
+    /// Anyone with this private key's public key can verify it with [`PublicKey::verify_subkeys`].
+    #[must_use]
+    pub fn export_subkeys(&self, rng: impl Rng + CryptoRng) -> Subkeys {
+        subkey::export(rng, &self.0)
+    }
+
+    /// Creates a bundle of this private key's public key with the given label (e.g. an email
+    /// address) and creation time, in seconds since the Unix epoch, signed by this private key.
+    /// Anyone receiving the bundle can check that it has not been modified.
+    #[must_use]
+    pub fn export_signed(&self, rng: impl Rng + CryptoRng, label: &str, created: u64) -> KeyBundle {
+        key_bundle::export(rng, self, label, created)
+    }
+
+    /// Verifies that the given designated-verifier signature was created by the owner of `signer`
+    /// for this private key and the exact contents of `message`. Returns `Ok(())` if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the message has been modified, was not signed by the owner of `signer`, or was not
+    /// signed for this private key, returns [`VerifyError::InvalidSignature`]. If there was an
+    /// error reading from `message`, returns [`VerifyError::ReadIo`].
+    #[cfg(feature = "std")]
+    pub fn verify_designated(
+        &self,
+        signer: &PublicKey,
+        message: impl Read,
+        sig: &DesignatedSignature,
+    ) -> Result<(), VerifyError> {
+        dvsig::verify(self, &signer.0, message, sig)
+    }
+}
+
+/// Writes the given envelopes to the given writer in a random order.
+#[cfg(feature = "std")]
+fn write_envelopes(
+    mut writer: impl Write,
+    mut rng: impl Rng + CryptoRng,
+    mut envelopes: [Vec<u8>; 2],
+) -> io::Result<usize> {
+    if rng.gen::<bool>() {
+        envelopes.swap(0, 1);
+    }
+    let enc_keys = envelopes.concat();
+    writer.write_all(&enc_keys)?;
+    Ok(enc_keys.len())
+}
+
+/// Decrypts a private key's secret from an envelope with the given passphrase.
+#[cfg(feature = "std")]
+fn decrypt_secret(passphrase: &[u8], envelope: &mut [u8]) -> Option<[u8; SECRET_LEN]> {
+    kdf::decrypt(passphrase, envelope).and_then(|secret| secret.try_into().ok())
+}
+
+/// A deterministic RNG which derives its output from a private key, a seed, and a set of receivers.
+#[cfg(feature = "std")]
+struct SeededRng(Protocol);
+
+#[cfg(feature = "std")]
+impl SeededRng {
+    fn new(sender: &PrivKey, seed: &[u8], receivers: &[PublicKey]) -> SeededRng {
+        // Initialize a protocol and mix the sender's secret into it. This hedges against seeds which
+        // are shared between senders or known to an adversary.
+        let mut rng = Protocol::new("veil.seeded-rng");
+        rng.mix("sender-secret", &sender.secret);
+
+        // Mix the seed and the receivers' public keys into the protocol.
+        rng.mix("seed", seed);
+        for receiver in receivers {
+            rng.mix("receiver", &receiver.0.encoded);
+        }
+
+        SeededRng(rng)
+    }
+}
+
+#[cfg(feature = "std")]
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.0.derive_array("output"))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.0.derive_array("output"))
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.derive("output", dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl CryptoRng for SeededRng {}
+
+impl Debug for PrivateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.public_key().fmt(f)
+    }
+}
+
+/// A public key, used to verify messages.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey(pub(crate) PubKey);
+
+impl PublicKey {
+    /// Generates a random public key for which no private key is known.
+    ///
+    /// Random public keys are indistinguishable from real ones, so they can be used as decoys (see
+    /// [`EncryptOptions::decoys`]) or as placeholders.
+    #[must_use]
+    pub fn random(rng: impl Rng + CryptoRng) -> PublicKey {
+        PublicKey(PubKey::random(rng))
+    }
+
+    /// Returns the public key of the anonymous sender, which is the sender of messages encrypted
+    /// with [`PrivateKey::encrypt_anonymous`].
+    ///
+    /// Its private key is public, so a message from the anonymous sender could have been sent by
+    /// anyone.
+    #[must_use]
+    pub fn anonymous() -> PublicKey {
+        PublicKey(PrivKey::anonymous().pub_key)
+    }
+
+    /// Decode a public key from a 32-byte slice.
+    #[must_use]
+    pub fn decode(b: impl AsRef<[u8]>) -> Option<PublicKey> {
+        PubKey::from_canonical_bytes(b).map(PublicKey)
+    }
+
+    /// Parses a base58-encoded public key, like [`str::parse`], but with an error which says why
+    /// the string isn't a valid public key.
+    ///
+    /// ```
+    /// use veil::{ParsePublicKeyError, PublicKey};
+    ///
+    /// assert_eq!(
+    ///     Err(ParsePublicKeyError::InvalidLength(3)),
+    ///     PublicKey::validate_str("2S7Ay"),
+    /// );
+    /// assert_eq!(
+    ///     Err(ParsePublicKeyError::NeutralPoint),
+    ///     PublicKey::validate_str("11111111111111111111111111111111"),
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParsePublicKeyError::InvalidEncoding`] if the string isn't base58,
+    /// [`ParsePublicKeyError::InvalidLength`] if it doesn't decode to 32 bytes,
+    /// [`ParsePublicKeyError::NonCanonical`] if the bytes aren't a canonical encoding of a field
+    /// element, [`ParsePublicKeyError::NotOnCurve`] if they don't encode a point, and
+    /// [`ParsePublicKeyError::NeutralPoint`] if they encode the neutral point. The group has prime
+    /// order, so the neutral point is its only low-order point.
+    pub fn validate_str(s: &str) -> Result<PublicKey, ParsePublicKeyError> {
+        let b = bs58::decode(s).into_vec()?;
+        let b = <[u8; POINT_LEN]>::try_from(b.as_slice())
+            .map_err(|_| ParsePublicKeyError::InvalidLength(b.len()))?;
+        if !Selected::is_canonical(&b) {
+            return Err(ParsePublicKeyError::NonCanonical);
+        }
+        let q = ct::point(&b).ok_or(ParsePublicKeyError::NotOnCurve)?;
+        if q.isneutral() != 0 {
+            return Err(ParsePublicKeyError::NeutralPoint);
+        }
+        Ok(PublicKey(PubKey { q, encoded: b }))
+    }
+
+    /// Encode the public key as a 32-byte array.
+    ///
+    /// This is the canonical encoding of the public key's point, and is stable across versions.
+    #[must_use]
+    pub const fn encode(&self) -> [u8; POINT_LEN] {
+        self.0.encoded
+    }
+
+    /// Verifies that the given signature was created by the owner of this public key for the exact
+    /// contents of `message`. Returns `Ok(())` if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the message has been modified or was not signed by the owner of this public key, returns
+    /// [`VerifyError::InvalidSignature`]. If there was an error reading from `message` or writing
+    /// to `writer`, returns [`VerifyError::ReadIo`] or [`VerifyError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn verify(&self, message: impl Read, sig: &Signature) -> Result<(), VerifyError> {
+        schnorr::verify(&self.0, "", message, sig)
+    }
+
+    /// Verifies that the given signature was created by the owner of this public key for the exact
+    /// contents of `message` in the given context, set with [`SignOptions::context`]. Returns
+    /// `Ok(())` if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the message has been modified, was signed in a different context, or was not signed by
+    /// the owner of this public key, returns [`VerifyError::InvalidSignature`]. If there was an
+    /// error reading from `message`, returns [`VerifyError::ReadIo`].
+    #[cfg(feature = "std")]
+    pub fn verify_in_context(
+        &self,
+        message: impl Read,
+        sig: &Signature,
+        context: &str,
+    ) -> Result<(), VerifyError> {
+        schnorr::verify(&self.0, context, message, sig)
+    }
+
+    /// Returns a reader which verifies that the given signature was created by the owner of this
+    /// public key for the exact contents of `reader` as they're read, so a message can be verified
+    /// as it's downloaded without storing it first. Once the message has been read,
+    /// [`VerifyingReader::finish`] returns whether the signature is valid.
+    ///
+    /// ```
+    /// use std::io::{self, Read};
+    ///
+    /// use veil::PrivateKey;
+    ///
+    /// let sender = PrivateKey::random(rand::thread_rng());
+    /// let sig = sender.sign(rand::thread_rng(), &b"a message"[..]).expect("should sign");
+    ///
+    /// let mut reader = sender.public_key().verifying_reader(&b"a message"[..], &sig);
+    /// let mut message = Vec::new();
+    /// reader.read_to_end(&mut message).expect("should read");
+    /// assert!(reader.finish().is_ok());
+    /// ```
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn verifying_reader<R: Read>(&self, reader: R, sig: &Signature) -> VerifyingReader<R> {
+        VerifyingReader::new(&self.0, "", reader, sig)
+    }
+
+    /// Verifies that the given signature was created with [`PrivateKey::sign_timestamped`] by the
+    /// owner of this public key for the exact contents of `message`. Returns the signed timestamp if
+    /// successful.
+    ///
+    /// The timestamp is asserted by the signer, not by a trusted third party, so it is only as
+    /// accurate as the signer's clock and honesty. Callers should check it against their own clock.
+    ///
+    /// # Errors
+    ///
+    /// If the message or timestamp has been modified or the message was not signed by the owner of
+    /// this public key, returns [`VerifyError::InvalidSignature`]. If there was an error reading
+    /// from `message`, returns [`VerifyError::ReadIo`].
+    #[cfg(feature = "std")]
+    pub fn verify_timestamped(
+        &self,
+        message: impl Read,
+        sig: &TimestampedSignature,
+    ) -> Result<u64, VerifyError> {
+        schnorr::verify_timestamped(&self.0, message, sig)
+    }
+
+    /// Verifies that the given signature was created with [`PrivateKey::sign_digest`] by the owner
+    /// of this public key for the given digest. Returns `Ok(())` if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the digest is different or the signature was not created by the owner of this public
+    /// key, returns [`VerifyError::InvalidSignature`].
+    #[cfg(feature = "std")]
+    pub fn verify_digest(&self, digest: &Digest, sig: &Signature) -> Result<(), VerifyError> {
+        schnorr::verify_digest(&self.0, digest, sig)
+    }
+
+    /// Reads a message with an attached signature from `reader`, verifies that it was signed by the
+    /// owner of this public key, and writes the message to `writer`. Returns the number of bytes of
+    /// message written.
+    ///
+    /// The message is written to `writer` as it is read. If an error is returned, the contents of
+    /// `writer` must not be trusted.
+    ///
+    /// # Errors
+    ///
+    /// If the message has been modified or was not signed by the owner of this public key, returns
+    /// [`VerifyError::InvalidSignature`]. If there was an error reading from `reader` or writing
+    /// to `writer`, returns [`VerifyError::ReadIo`] or [`VerifyError::WriteIo`].
+    #[cfg(feature = "std")]
+    pub fn verify_attached(
+        &self,
+        reader: impl Read,
+        writer: impl Write,
+    ) -> Result<u64, VerifyError> {
+        schnorr::verify_attached(&self.0, reader, writer)
+    }
+
+    /// Parses a key bundle created with [`PrivateKey::export_signed`] and verifies its signature.
+    /// Returns the bundle, which contains the public key, if successful.
+    ///
+    /// A valid bundle proves only that its metadata was signed by the owner of its public key, not
+    /// that the public key belongs to any particular person. Compare fingerprints out-of-band for
+    /// that.
+    ///
+    /// # Errors
+    ///
+    /// If the bundle cannot be parsed, returns [`ParseKeyBundleError::InvalidBundle`] or
+    /// [`ParseKeyBundleError::InvalidEncoding`]. If the bundle has been modified, returns
+    /// [`ParseKeyBundleError::InvalidSignature`].
+    pub fn import_verified(bundle: &str) -> Result<KeyBundle, ParseKeyBundleError> {
+        let bundle = bundle.parse::<KeyBundle>()?;
+        bundle.verify().map_err(|_| ParseKeyBundleError::InvalidSignature)?;
+        Ok(bundle)
+    }
+
+    /// Parses a proof of derivation created with [`PrivateKey::prove_derivation`] and verifies that
+    /// its derived key belongs to the hierarchy of this public key. Returns the proof, which
+    /// contains the derived public key, if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the proof cannot be parsed, returns [`ParseProofOfDerivationError::InvalidProof`] or
+    /// [`ParseProofOfDerivationError::InvalidEncoding`]. If it is for a different root key or has
+    /// been modified, returns [`ParseProofOfDerivationError::InvalidSignature`].
+    pub fn verify_derivation(
+        &self,
+        proof: &str,
+    ) -> Result<ProofOfDerivation, ParseProofOfDerivationError> {
+        let proof = proof.parse::<ProofOfDerivation>()?;
+        if proof.root() != *self || proof.verify().is_err() {
+            return Err(ParseProofOfDerivationError::InvalidSignature);
+        }
+        Ok(proof)
+    }
+
+    /// Parses a set of subkeys created with [`PrivateKey::export_subkeys`] and verifies that they
+    /// were signed by the owner of this public key. Returns the subkeys if successful.
+    ///
+    /// # Errors
+    ///
+    /// If the subkeys cannot be parsed, returns [`ParseSubkeysError::InvalidSubkeys`] or
+    /// [`ParseSubkeysError::InvalidEncoding`]. If they belong to a different identity or have
+    /// been modified, returns [`ParseSubkeysError::InvalidSignature`].
+    pub fn verify_subkeys(&self, subkeys: &str) -> Result<Subkeys, ParseSubkeysError> {
+        let subkeys = subkeys.parse::<Subkeys>()?;
+        if subkeys.identity() != *self || subkeys.verify().is_err() {
+            return Err(ParseSubkeysError::InvalidSignature);
+        }
+        Ok(subkeys)
+    }
+
+    /// Parses a certification created with [`PrivateKey::certify`] and verifies that it was issued
+    /// by the owner of this public key and is valid at the given time, in seconds since the Unix
+    /// epoch. Returns the certification, which contains the certified public key and its label, if
+    /// successful.
+    ///
+    /// # Errors
+    ///
+    /// If the certification cannot be parsed, returns
+    /// [`ParseCertificationError::InvalidCertification`] or
+    /// [`ParseCertificationError::InvalidEncoding`]. If it was issued by a different key or has been
+    /// modified, returns [`ParseCertificationError::InvalidSignature`]. If `timestamp` is outside
+    /// its validity period, returns [`ParseCertificationError::OutsideValidityPeriod`].
+    pub fn verify_certification(
+        &self,
+        certification: &str,
+        timestamp: u64,
+    ) -> Result<Certification, ParseCertificationError> {
+        let certification = certification.parse::<Certification>()?;
+        if certification.verify(self).is_err() {
+            return Err(ParseCertificationError::InvalidSignature);
+        }
+        if !certification.is_valid_at(timestamp) {
+            return Err(ParseCertificationError::OutsideValidityPeriod);
+        }
+        Ok(certification)
+    }
+
+    /// Returns a `len`-byte fingerprint of the public key, for comparing public keys out-of-band.
+    /// [`FINGERPRINT_LEN`](crate::FINGERPRINT_LEN) bytes is a reasonable default.
+    #[must_use]
+    pub fn fingerprint(&self, len: usize) -> Fingerprint {
+        fingerprint::fingerprint(&self.0, len)
+    }
+}
+
+impl Debug for PublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(self.encode()).into_string())
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = ParsePublicKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PublicKey::validate_str(s)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use assert_matches::assert_matches;
+    use expect_test::expect;
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    const BALLOON: Kdf = Kdf::Balloon { time_cost: 0, memory_cost: 0, parallelism: 1 };
+
+    #[test]
+    fn public_key_encoding() {
+        let rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let pk = PrivateKey::random(rng).public_key();
+
+        // The pinned value is for GLS254's points.
+        if cfg!(not(feature = "jq255e")) {
+            expect!["CNYNfYzLm22yHDwpJJAe2yo6S3nEcFzge873NCRce8kF"].assert_eq(&pk.to_string());
+        }
+
+        let decoded = pk.to_string().parse::<PublicKey>();
+        assert_eq!(Ok(pk), decoded, "error parsing public key");
+
+        assert_eq!(
+            Err(ParsePublicKeyError::InvalidEncoding(bs58::decode::Error::InvalidCharacter {
+                character: 'l',
+                index: 4,
+            })),
+            "invalid key".parse::<PublicKey>(),
+            "decoded invalid public key"
+        );
+    }
+
+    #[test]
+    fn public_key_validation() {
+        let rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let pk = PrivateKey::random(rng).public_key();
+        assert_eq!(Ok(pk), PublicKey::validate_str(&pk.to_string()));
+
+        let b58 = |b: &[u8]| bs58::encode(b).into_string();
+        assert_matches!(
+            PublicKey::validate_str("0OIl"),
+            Err(ParsePublicKeyError::InvalidEncoding(_))
+        );
+        assert_eq!(
+            Err(ParsePublicKeyError::InvalidLength(31)),
+            PublicKey::validate_str(&b58(&pk.encode()[1..]))
+        );
+        assert_eq!(
+            Err(ParsePublicKeyError::NonCanonical),
+            PublicKey::validate_str(&b58(&[0xFF; 32]))
+        );
+        assert_eq!(Err(ParsePublicKeyError::NeutralPoint), PublicKey::validate_str(&b58(&[0; 32])));
+
+        // Find a canonical encoding which isn't a point.
+        let off_curve = (1..=u8::MAX)
+            .map(|i| [[i].as_slice(), &[0; 31]].concat())
+            .find(|b| PublicKey::decode(b).is_none())
+            .expect("should find an invalid point");
+        assert_eq!(Err(ParsePublicKeyError::NotOnCurve), PublicKey::validate_str(&b58(&off_curve)));
+    }
+
+    #[test]
+    fn prove_and_verify_derivation() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let root = PrivateKey::random(&mut rng);
+        let proof = root.prove_derivation(&mut rng, "devices/laptop").to_string();
+
+        let verified = root.public_key().verify_derivation(&proof).expect("should verify");
+        assert_eq!(root.derive_path("devices/laptop").public_key(), verified.derived());
+
+        // A proof can't be attributed to a different root.
+        let other = PrivateKey::random(&mut rng);
+        assert_matches!(
+            other.public_key().verify_derivation(&proof),
+            Err(ParseProofOfDerivationError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn export_and_verify_subkeys() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let subkeys = key.export_subkeys(&mut rng).to_string();
+
+        let verified = key.public_key().verify_subkeys(&subkeys).expect("should verify");
+        assert_eq!(key.signing_key().public_key(), verified.signing_key());
+        assert_eq!(key.decryption_key().public_key(), verified.encryption_key());
+
+        // Subkeys can't be attributed to a different identity.
+        let other = PrivateKey::random(&mut rng);
+        assert_matches!(
+            other.public_key().verify_subkeys(&subkeys),
+            Err(ParseSubkeysError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn certify_and_verify_certification() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let issuer = PrivateKey::random(&mut rng);
+        let subject = PrivateKey::random(&mut rng).public_key();
+        let certification =
+            issuer.certify(&mut rng, &subject, "bob", 1_700_000_000, 1_800_000_000).to_string();
+
+        let verified = issuer
+            .public_key()
+            .verify_certification(&certification, 1_750_000_000)
+            .expect("should verify");
+        assert_eq!(subject, verified.subject());
+        assert_eq!("bob", verified.label());
+
+        // A certification is only valid during its validity period.
+        assert_matches!(
+            issuer.public_key().verify_certification(&certification, 1_800_000_001),
+            Err(ParseCertificationError::OutsideValidityPeriod)
+        );
+
+        // A certification can't be attributed to a different issuer.
+        let other = PrivateKey::random(&mut rng);
+        assert_matches!(
+            other.public_key().verify_certification(&certification, 1_750_000_000),
+            Err(ParseCertificationError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn export_and_import() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let bundle = key.export_signed(&mut rng, "alice@example.com", 1_700_000_000).to_string();
+
+        let imported = PublicKey::import_verified(&bundle).expect("should import");
+        assert_eq!(key.public_key(), imported.public_key());
+        assert_eq!("alice@example.com", imported.label());
+
+        // Replace the label with a different one.
+        let mut b = bs58::decode(&bundle).into_vec().expect("should be base58");
+        let label = b.len() - "alice@example.com".len();
+        b[label] = b'b';
+        assert_eq!(
+            Err(ParseKeyBundleError::InvalidSignature),
+            PublicKey::import_verified(&bs58::encode(b).into_string())
+        );
+        assert_eq!(
+            Err(ParseKeyBundleError::InvalidBundle),
+            PublicKey::import_verified(&key.public_key().to_string())
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let (_, a, b, plaintext, ciphertext) = setup(64);
+        let mut dst = Cursor::new(Vec::new());
+        let ptx_len = b
+            .decrypt(Cursor::new(ciphertext), &mut dst, &a.public_key())
+            .expect("decryption should be ok");
+        assert_eq!(
+            dst.position(),
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
+        assert_eq!(plaintext.to_vec(), dst.into_inner(), "incorrect plaintext");
+    }
+
+    #[test]
+    fn slice_round_trip() {
+        let (mut rng, a, b, _, _) = setup(0);
+        let plaintext = vec![0x55u8; 200 * 1024];
+        let receivers = [b.public_key()];
+        let mut ciphertext = Vec::new();
+        let ctx_len = a
+            .encrypt_slice(&mut rng, &plaintext, &mut ciphertext, &EncryptOptions::new(&receivers))
+            .expect("should encrypt");
+        assert_eq!(
+            ciphertext.len() as u64,
+            ctx_len.written(),
+            "returned/observed ciphertext length mismatch"
+        );
+
+        let mut dst = Vec::new();
+        let ptx_len =
+            b.decrypt_slice(&ciphertext, &mut dst, &a.public_key(), &[]).expect("should decrypt");
+        assert_eq!(
+            dst.len() as u64,
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
+        assert_eq!(plaintext, dst, "incorrect plaintext");
+    }
+
+    #[test]
+    fn report() {
+        let (mut rng, a, b, _, _) = setup(0);
+        let plaintext = b"this is a reported message";
+        let mut ciphertext = Vec::new();
+        let encrypted = a
+            .encrypt(
+                &mut rng,
+                &plaintext[..],
+                &mut ciphertext,
+                &EncryptOptions::new(&[b.public_key()]).fakes(2).padding(123),
+            )
+            .expect("should encrypt");
+        assert_eq!(3, encrypted.recipients_attempted());
+        assert_eq!(123, encrypted.padding_bytes());
+        assert_eq!(ciphertext.len() as u64, encrypted.ciphertext_bytes());
+        assert_eq!(ciphertext.len() as u64, u64::from(encrypted));
+
+        let mut dst = Vec::new();
+        let decrypted =
+            b.decrypt(Cursor::new(&ciphertext), &mut dst, &a.public_key()).expect("should decrypt");
+        assert!((1..=3).contains(&decrypted.recipients_attempted()));
+        assert_eq!(encrypted.header_bytes(), decrypted.header_bytes());
+        assert_eq!(encrypted.padding_bytes(), decrypted.padding_bytes());
+        assert_eq!(encrypted.payload_bytes(), decrypted.payload_bytes());
+        assert_eq!(encrypted.signature_bytes(), decrypted.signature_bytes());
+        assert_eq!(plaintext.len() as u64, u64::from(decrypted));
+    }
+
+    #[test]
+    fn limits() {
+        let (mut rng, a, b, plaintext, _) = setup(64);
+        let receivers = [b.public_key()];
+        let mut ciphertext = Vec::new();
+
+        for fakes in [crate::MAX_RECEIVERS, usize::MAX] {
+            let options = EncryptOptions::new(&receivers).fakes(fakes);
+            assert_matches!(
+                a.encrypt(&mut rng, plaintext.as_slice(), &mut ciphertext, &options),
+                Err(EncryptError::TooManyReceivers)
+            );
+        }
+
+        // On 32-bit targets, no amount of padding is too much.
+        if let Ok(padding) = usize::try_from(crate::MAX_PADDING + 1) {
+            let options = EncryptOptions::new(&receivers).padding(padding);
+            assert_matches!(
+                a.encrypt(&mut rng, plaintext.as_slice(), &mut ciphertext, &options),
+                Err(EncryptError::TooMuchPadding)
+            );
+        }
+
+        let options = EncryptOptions::new(&receivers)
+            .padding_policy(Padding::Automatic { plaintext_len: crate::MAX_MESSAGE_LEN + 1 });
+        assert_matches!(
+            a.encrypt(&mut rng, plaintext.as_slice(), &mut ciphertext, &options),
+            Err(EncryptError::MessageTooLong)
+        );
+        assert_matches!(
+            a.rekey(&mut rng, io::empty(), io::sink(), &b.public_key(), &options),
+            Err(DecryptError::WriteIo(_))
+        );
+
+        assert!(ciphertext.is_empty(), "nothing should be written");
+    }
+
+    #[test]
+    fn decrypt_with_options() {
+        let (mut rng, a, b, plaintext, _) = setup(100_000);
+        let receivers = [b.public_key()];
+        let mut ciphertext = Vec::new();
+        let encrypted = a
+            .encrypt(
+                &mut rng,
+                plaintext.as_slice(),
+                &mut ciphertext,
+                &EncryptOptions::new(&receivers).fakes(10).padding(1000).associated_data(b"ad"),
+            )
+            .expect("should encrypt");
+        let header_len = encrypted.header_bytes() + encrypted.padding_bytes();
+        let plaintext_len = plaintext.len() as u64;
+
+        let options = DecryptOptions::new()
+            .associated_data(b"ad")
+            .max_header_len(header_len)
+            .max_plaintext_len(plaintext_len);
+        let mut dst = Vec::new();
+        let decrypted = b
+            .decrypt_with_options(ciphertext.as_slice(), &mut dst, &a.public_key(), &options)
+            .expect("should decrypt within the limits");
+        assert_eq!(encrypted.header_bytes(), decrypted.header_bytes());
+        assert_eq!(plaintext.to_vec(), dst, "incorrect plaintext");
+
+        // The headers are too long, whether the limit is reached in the headers or the padding.
+        for max_header_len in [0, encrypted.header_bytes() - 1, header_len - 1] {
+            assert_matches!(
+                b.decrypt_with_options(
+                    ciphertext.as_slice(),
+                    io::sink(),
+                    &a.public_key(),
+                    &options.max_header_len(max_header_len),
+                ),
+                Err(DecryptError::HeadersTooLong)
+            );
+        }
+
+        // The plaintext is written up to the maximum, and no further.
+        let mut dst = Vec::new();
+        assert_matches!(
+            b.decrypt_with_options(
+                ciphertext.as_slice(),
+                &mut dst,
+                &a.public_key(),
+                &options.max_plaintext_len(plaintext_len - 1),
+            ),
+            Err(DecryptError::PlaintextTooLong)
+        );
+        assert_eq!(
+            &plaintext[..plaintext.len() - 1],
+            dst,
+            "plaintext should be written up to the limit"
+        );
+
+        // Without the associated data, the message can't be decrypted.
+        assert_matches!(
+            b.decrypt_with_options(
+                ciphertext.as_slice(),
+                io::sink(),
+                &a.public_key(),
+                &DecryptOptions::new(),
+            ),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn decrypt_compressed_with_options() {
+        let (mut rng, a, b, _, _) = setup(0);
+        let plaintext = vec![0u8; 1_000_000];
+        let receivers = [b.public_key()];
+        let mut ciphertext = Vec::new();
+        a.encrypt(
+            &mut rng,
+            plaintext.as_slice(),
+            &mut ciphertext,
+            &EncryptOptions::new(&receivers).compress(3),
+        )
+        .expect("should encrypt");
+        assert!(ciphertext.len() < 1000, "plaintext should be compressed");
+
+        // The limit applies to the decompressed plaintext.
+        let mut dst = Vec::new();
+        assert_matches!(
+            b.decrypt_with_options(
+                ciphertext.as_slice(),
+                &mut dst,
+                &a.public_key(),
+                &DecryptOptions::new().max_plaintext_len(100_000),
+            ),
+            Err(DecryptError::PlaintextTooLong)
+        );
+        assert_eq!(100_000, dst.len(), "plaintext should be written up to the limit");
+    }
+
+    #[test]
+    fn encrypt_to_self() {
+        let (mut rng, a, b, plaintext, _) = setup(64);
+        let mut ciphertext = Vec::new();
+        a.encrypt_self(&mut rng, plaintext.as_slice(), &mut ciphertext, Padding::Fixed(16))
+            .expect("should encrypt");
+
+        let mut dst = Vec::new();
+        a.decrypt(ciphertext.as_slice(), &mut dst, &a.public_key()).expect("should decrypt");
+        assert_eq!(plaintext.to_vec(), dst, "incorrect plaintext");
+
+        assert_matches!(
+            b.decrypt(ciphertext.as_slice(), io::sink(), &a.public_key()),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn wrong_sender() {
+        let (rng, _, b, _, ciphertext) = setup(64);
+        let c = PrivateKey::random(rng);
+        assert_matches!(
+            b.decrypt(Cursor::new(ciphertext), io::sink(), &c.public_key()),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn wrong_receiver() {
+        let (rng, a, _, _, ciphertext) = setup(64);
         let c = PrivateKey::random(rng);
 
         assert_matches!(
-            c.decrypt(Cursor::new(ciphertext), io::sink(), &a.public_key()),
+            c.decrypt(Cursor::new(ciphertext), io::sink(), &a.public_key()),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn modified_ciphertext() {
+        let (_, a, b, _, mut ciphertext) = setup(64);
+        ciphertext[200] ^= 1;
+        assert_matches!(
+            b.decrypt(Cursor::new(ciphertext), io::sink(), &a.public_key()),
+            Err(DecryptError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn scrubbed_decryption() {
+        let (_, a, b, plaintext, ciphertext) = setup(64);
+        let mut dst = Cursor::new(Vec::new());
+        b.decrypt_scrubbed(Cursor::new(&ciphertext), &mut dst, &a.public_key(), &[])
+            .expect("decryption should be ok");
+        assert_eq!(plaintext, dst.into_inner(), "incorrect plaintext");
+
+        // A message with a forged signature is decrypted but its plaintext is scrubbed.
+        let mut ciphertext = ciphertext;
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 1;
+        let mut dst = Cursor::new(Vec::new());
+        assert_matches!(
+            b.decrypt_scrubbed(Cursor::new(&ciphertext), &mut dst, &a.public_key(), &[]),
+            Err(DecryptError::Corrupt)
+        );
+        assert_eq!(0, dst.position(), "should seek back to the start");
+        assert_eq!(vec![0u8; plaintext.len()], dst.into_inner(), "plaintext should be scrubbed");
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let message = rng.gen::<[u8; 64]>();
+
+        let sig = key.sign(&mut rng, Cursor::new(message)).expect("signing should be ok");
+
+        key.public_key().verify(Cursor::new(message), &sig).expect("verification should be ok");
+    }
+
+    #[test]
+    fn signature_contexts() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let message = rng.gen::<[u8; 64]>();
+
+        let options = SignOptions::new().context("com.example.billing");
+        let sig = key.sign_with(&mut rng, &message[..], &options).expect("should sign");
+        key.public_key()
+            .verify_in_context(&message[..], &sig, "com.example.billing")
+            .expect("verification should be ok");
+        assert_matches!(
+            key.public_key().verify_in_context(&message[..], &sig, "com.example.shipping"),
+            Err(VerifyError::InvalidSignature)
+        );
+        assert_matches!(
+            key.public_key().verify(&message[..], &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+
+        // An empty context is no context.
+        let sig = key.sign(&mut rng, &message[..]).expect("should sign");
+        key.public_key().verify_in_context(&message[..], &sig, "").expect("should verify");
+    }
+
+    #[test]
+    fn sign_with_progress() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let message = vec![7u8; 100_000];
+
+        let reported = std::cell::RefCell::new(Vec::new());
+        let progress = |read, total| reported.borrow_mut().push((read, total));
+        let cancel = AtomicBool::new(false);
+        let options = SignOptions::new().len(100_000).progress(&progress).cancel(&cancel);
+        let sig = key.sign_with(&mut rng, message.as_slice(), &options).expect("should sign");
+        key.public_key().verify(message.as_slice(), &sig).expect("verification should be ok");
+
+        let reported = reported.into_inner();
+        assert!(reported.len() > 1, "should report progress for each block");
+        assert!(reported.windows(2).all(|w| w[0].0 < w[1].0), "progress should increase");
+        assert_eq!(Some(&(100_000, Some(100_000))), reported.last());
+    }
+
+    #[test]
+    fn cancelled_signing() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let message = vec![7u8; 100_000];
+
+        // Cancel signing once the first block has been read.
+        let cancel = AtomicBool::new(false);
+        let progress = |_, _| cancel.store(true, Ordering::Relaxed);
+        let options = SignOptions::new().progress(&progress).cancel(&cancel);
+        assert_matches!(
+            key.sign_with(&mut rng, message.as_slice(), &options),
+            Err(SignError::Cancelled)
+        );
+
+        // A failing reader is an IO error, not a cancellation.
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken pipe"))
+            }
+        }
+
+        assert_matches!(
+            key.sign_with(&mut rng, FailingReader, &SignOptions::new()),
+            Err(SignError::ReadIo(_))
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_round_trip() {
+        use crate::asyncio::tests::block_on;
+
+        let (mut rng, a, b, plaintext, _) = setup(200_000);
+        let receivers = [b.public_key()];
+        let options = EncryptOptions::new(&receivers).fakes(3).padding(100_000);
+
+        let mut ciphertext = Vec::new();
+        let ctx_len =
+            block_on(a.encrypt_async(&mut rng, plaintext.as_slice(), &mut ciphertext, &options))
+                .expect("encryption should be ok");
+        assert_eq!(
+            ciphertext.len() as u64,
+            ctx_len.written(),
+            "returned/observed ciphertext length mismatch"
+        );
+
+        // Messages encrypted asynchronously can be decrypted synchronously and vice versa.
+        let mut dst = Vec::new();
+        b.decrypt(ciphertext.as_slice(), &mut dst, &a.public_key())
+            .expect("decryption should be ok");
+        assert_eq!(plaintext, dst, "incorrect plaintext");
+
+        let mut ciphertext = Vec::new();
+        a.encrypt(&mut rng, plaintext.as_slice(), &mut ciphertext, &options)
+            .expect("encryption should be ok");
+        let mut dst = Vec::new();
+        let ptx_len =
+            block_on(b.decrypt_async(ciphertext.as_slice(), &mut dst, &a.public_key(), &[]))
+                .expect("decryption should be ok");
+        assert_eq!(
+            dst.len() as u64,
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
+        assert_eq!(plaintext, dst, "incorrect plaintext");
+
+        ciphertext[300] ^= 1;
+        assert_matches!(
+            block_on(b.decrypt_async(ciphertext.as_slice(), &mut dst, &a.public_key(), &[])),
+            Err(DecryptError::Corrupt)
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn compressed_round_trip() {
+        let (mut rng, a, b, _, _) = setup(0);
+        let plaintext = b"a very repetitive message. ".repeat(10_000);
+        let receivers = [b.public_key()];
+
+        let mut uncompressed = Vec::new();
+        a.encrypt(
+            &mut rng,
+            plaintext.as_slice(),
+            &mut uncompressed,
+            &EncryptOptions::new(&receivers),
+        )
+        .expect("encryption should be ok");
+
+        let mut ciphertext = Vec::new();
+        a.encrypt(
+            &mut rng,
+            plaintext.as_slice(),
+            &mut ciphertext,
+            &EncryptOptions::new(&receivers).compress(3),
+        )
+        .expect("encryption should be ok");
+        assert!(ciphertext.len() < uncompressed.len() / 10, "message should be compressed");
+
+        let mut dst = Vec::new();
+        let ptx_len =
+            b.decrypt(ciphertext.as_slice(), &mut dst, &a.public_key()).expect("should decrypt");
+        assert_eq!(
+            dst.len() as u64,
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
+        assert_eq!(plaintext, dst, "incorrect plaintext");
+
+        ciphertext[300] ^= 1;
+        assert_matches!(
+            b.decrypt(ciphertext.as_slice(), io::sink(), &a.public_key()),
+            Err(DecryptError::Corrupt)
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn compressed_automatic_padding() {
+        let (mut rng, a, b, _, _) = setup(0);
+        let plaintext = b"a very repetitive message. ".repeat(10_000);
+        let receivers = [b.public_key()];
+        let options = EncryptOptions::new(&receivers)
+            .compress(3)
+            .padding_policy(Padding::Automatic { plaintext_len: plaintext.len() as u64 });
+
+        let mut ciphertext = Vec::new();
+        a.encrypt(&mut rng, plaintext.as_slice(), &mut ciphertext, &options)
+            .expect("encryption should be ok");
+        assert_eq!(
+            ciphertext.len() as u64,
+            crate::padme::padded_len(ciphertext.len() as u64),
+            "message should have a PADMÉ length"
+        );
+        assert!(
+            ciphertext.len() < plaintext.len() / 10,
+            "padding should fit the compressed length"
+        );
+    }
+
+    #[cfg(all(feature = "async", feature = "zstd"))]
+    #[test]
+    fn async_compressed_round_trip() {
+        use crate::asyncio::tests::block_on;
+
+        let (mut rng, a, b, plaintext, _) = setup(200_000);
+        let receivers = [b.public_key()];
+        let options = EncryptOptions::new(&receivers).compress(3);
+
+        let mut ciphertext = Vec::new();
+        let ctx_len =
+            block_on(a.encrypt_async(&mut rng, plaintext.as_slice(), &mut ciphertext, &options))
+                .expect("encryption should be ok");
+        assert_eq!(
+            ciphertext.len() as u64,
+            ctx_len.written(),
+            "returned/observed ciphertext length mismatch"
+        );
+
+        let mut dst = Vec::new();
+        b.decrypt(ciphertext.as_slice(), &mut dst, &a.public_key())
+            .expect("decryption should be ok");
+        assert_eq!(plaintext, dst, "incorrect plaintext");
+
+        let mut dst = Vec::new();
+        let ptx_len =
+            block_on(b.decrypt_async(ciphertext.as_slice(), &mut dst, &a.public_key(), &[]))
+                .expect("decryption should be ok");
+        assert_eq!(
+            dst.len() as u64,
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
+        assert_eq!(plaintext, dst, "incorrect plaintext");
+    }
+
+    #[cfg(all(feature = "async", feature = "zstd"))]
+    #[test]
+    fn async_decrypt_compressed_with_options() {
+        use crate::asyncio::tests::block_on;
+
+        let (mut rng, a, b, _, _) = setup(0);
+        let plaintext = vec![0u8; 1_000_000];
+        let receivers = [b.public_key()];
+        let mut ciphertext = Vec::new();
+        a.encrypt(
+            &mut rng,
+            plaintext.as_slice(),
+            &mut ciphertext,
+            &EncryptOptions::new(&receivers).compress(3),
+        )
+        .expect("should encrypt");
+        assert!(ciphertext.len() < 1000, "plaintext should be compressed");
+
+        // A block which decompresses to many times its size is written in chunks.
+        let mut dst = Vec::new();
+        block_on(b.decrypt_async(ciphertext.as_slice(), &mut dst, &a.public_key(), &[]))
+            .expect("should decrypt");
+        assert_eq!(plaintext, dst, "incorrect plaintext");
+
+        // The limit applies to the decompressed plaintext.
+        let mut dst = Vec::new();
+        assert_matches!(
+            block_on(b.decrypt_async_with_options(
+                ciphertext.as_slice(),
+                &mut dst,
+                &a.public_key(),
+                &DecryptOptions::new().max_plaintext_len(100_000),
+            )),
+            Err(DecryptError::PlaintextTooLong)
+        );
+        assert_eq!(100_000, dst.len(), "plaintext should be written up to the limit");
+
+        assert_matches!(
+            block_on(b.decrypt_async_with_options(
+                ciphertext.as_slice(),
+                &mut Vec::new(),
+                &a.public_key(),
+                &DecryptOptions::new().max_header_len(100),
+            )),
+            Err(DecryptError::HeadersTooLong)
+        );
+    }
+
+    #[test]
+    fn group_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let owner = PrivateKey::random(&mut rng);
+        let member = PrivateKey::random(&mut rng);
+        let sender = PrivateKey::random(&mut rng);
+
+        let group = owner.derive_group("team");
+        let invitation = group.invite(&mut rng, &member.public_key()).expect("should invite");
+        let group_key = member
+            .accept_invite(&group.public_key(), &invitation)
+            .expect("should accept invitation");
+        assert_eq!(group.public_key(), group_key.public_key(), "invalid group key");
+
+        let ciphertext =
+            sender.seal(&mut rng, &[group.public_key()], b"hello, team").expect("should seal");
+        assert_eq!(
+            b"hello, team".to_vec(),
+            group_key.open(&sender.public_key(), &ciphertext).expect("should decrypt"),
+            "invalid plaintext"
+        );
+    }
+
+    #[test]
+    fn group_derivation() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let owner = PrivateKey::random(&mut rng);
+
+        assert_eq!(owner.derive_group("team"), owner.derive_group("team"));
+        assert_ne!(owner.derive_group("team"), owner.derive_group("team-2"));
+        assert_ne!(owner.derive_group("team"), PrivateKey::random(&mut rng).derive_group("team"));
+    }
+
+    #[test]
+    fn invitation_for_another_member() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let group = PrivateKey::random(&mut rng).derive_group("team");
+        let member = PrivateKey::random(&mut rng);
+        let other = PrivateKey::random(&mut rng);
+
+        let invitation = group.invite(&mut rng, &member.public_key()).expect("should invite");
+        assert_matches!(
+            other.accept_invite(&group.public_key(), &invitation),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn invitation_from_another_group() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let owner = PrivateKey::random(&mut rng);
+        let member = PrivateKey::random(&mut rng);
+
+        let invitation = owner
+            .derive_group("team")
+            .invite(&mut rng, &member.public_key())
+            .expect("should invite");
+        assert_matches!(
+            member.accept_invite(&owner.derive_group("other").public_key(), &invitation),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn attached_sign_and_verify() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+        let message = rng.gen::<[u8; 64]>();
+
+        let mut signed = Vec::new();
+        let len = key
+            .sign_attached(&mut rng, Cursor::new(message), &mut signed)
+            .expect("signing should be ok");
+        assert_eq!(signed.len() as u64, len, "returned/observed length mismatch");
+
+        let mut verified = Vec::new();
+        let len = key
+            .public_key()
+            .verify_attached(signed.as_slice(), &mut verified)
+            .expect("verification should be ok");
+        assert_eq!(64, len, "invalid message length");
+        assert_eq!(message.to_vec(), verified, "invalid message");
+    }
+
+    #[test]
+    fn designated_sign_and_verify() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let signer = PrivateKey::random(&mut rng);
+        let verifier = PrivateKey::random(&mut rng);
+        let message = rng.gen::<[u8; 64]>();
+
+        let sig = signer
+            .sign_for(&mut rng, &verifier.public_key(), Cursor::new(message))
+            .expect("signing should be ok");
+
+        verifier
+            .verify_designated(&signer.public_key(), Cursor::new(message), &sig)
+            .expect("verification should be ok");
+        assert_matches!(
+            signer.verify_designated(&signer.public_key(), Cursor::new(message), &sig),
+            Err(VerifyError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn paper_key_round_trip() {
+        let rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(rng);
+
+        let words = key.export_paper_key();
+        assert_eq!(Ok(key), PrivateKey::import_paper_key(&words), "invalid recovered key");
+    }
+
+    #[test]
+    fn deterministic_encryption() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let encrypt = |seed: &[u8]| {
+            let mut ciphertext = Vec::new();
+            a.encrypt_deterministic(
+                seed,
+                Cursor::new(b"a release artifact"),
+                &mut ciphertext,
+                &EncryptOptions::new(&[b.public_key()]).fakes(3).padding(100),
+            )
+            .expect("encryption should be ok");
+            ciphertext
+        };
+
+        let ciphertext = encrypt(b"seed 1");
+        assert_eq!(ciphertext, encrypt(b"seed 1"), "ciphertexts should be identical");
+        assert_ne!(ciphertext, encrypt(b"seed 2"), "ciphertexts should differ");
+
+        let mut plaintext = Vec::new();
+        b.decrypt(Cursor::new(&ciphertext), &mut plaintext, &a.public_key())
+            .expect("decryption should be ok");
+        assert_eq!(b"a release artifact".to_vec(), plaintext, "invalid plaintext");
+    }
+
+    #[test]
+    fn deterministic_encryption_with_many_receivers() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let receivers = (0..100).map(|_| PrivateKey::random(&mut rng)).collect::<Vec<_>>();
+        let public_keys = receivers.iter().map(PrivateKey::public_key).collect::<Vec<_>>();
+
+        let encrypt = || {
+            let mut ciphertext = Vec::new();
+            a.encrypt_deterministic(
+                b"seed",
+                Cursor::new(b"an audited artifact"),
+                &mut ciphertext,
+                &EncryptOptions::new(&public_keys).fakes(20).padding(100),
+            )
+            .expect("encryption should be ok");
+            ciphertext
+        };
+
+        let ciphertext = encrypt();
+        assert_eq!(ciphertext, encrypt(), "ciphertexts should be identical");
+
+        for receiver in [&receivers[0], &receivers[50], &receivers[99]] {
+            let mut plaintext = Vec::new();
+            receiver
+                .decrypt(Cursor::new(&ciphertext), &mut plaintext, &a.public_key())
+                .expect("decryption should be ok");
+            assert_eq!(b"an audited artifact".to_vec(), plaintext, "invalid plaintext");
+        }
+    }
+
+    #[test]
+    fn sequence_binding() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let receivers = [b.public_key()];
+        let mut ciphertext = Vec::new();
+        a.encrypt(
+            &mut rng,
+            &b"message 7"[..],
+            &mut ciphertext,
+            &EncryptOptions::new(&receivers).sequence(b"channel 1", 7),
+        )
+        .expect("encryption should be ok");
+
+        let mut plaintext = Vec::new();
+        b.decrypt_in_sequence(
+            ciphertext.as_slice(),
+            &mut plaintext,
+            &a.public_key(),
+            b"channel 1",
+            7,
+        )
+        .expect("decryption should be ok");
+        assert_eq!(b"message 7".to_vec(), plaintext, "invalid plaintext");
+
+        // Replayed, reordered, and misdirected messages are rejected before any plaintext is
+        // written.
+        for (channel, sequence) in
+            [(&b"channel 1"[..], 8), (&b"channel 1"[..], 6), (b"channel 2", 7)]
+        {
+            let mut plaintext = Vec::new();
+            assert_matches!(
+                b.decrypt_in_sequence(
+                    ciphertext.as_slice(),
+                    &mut plaintext,
+                    &a.public_key(),
+                    channel,
+                    sequence
+                ),
+                Err(DecryptError::SequenceMismatch)
+            );
+            assert!(plaintext.is_empty(), "should not write any plaintext");
+        }
+
+        // A sequenced message can't be decrypted without its sequence.
+        assert_matches!(
+            b.decrypt(ciphertext.as_slice(), io::sink(), &a.public_key()),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn rekey() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+        let c = PrivateKey::random(&mut rng);
+
+        // Plaintexts which fill an exact number of blocks and which end with a partial block.
+        for len in [0, 65 * 1024, 128 * 1024] {
+            let mut message = vec![0u8; len];
+            rng.fill_bytes(&mut message);
+
+            for seekable in [false, true] {
+                let receivers = [b.public_key()];
+                let mut options = EncryptOptions::new(&receivers).fakes(2);
+                if seekable {
+                    options = options.seekable();
+                }
+                let ciphertext = a.seal_with(&mut rng, &message, &options).expect("should seal");
+
+                // Bea re-encrypts the message for Cam.
+                let receivers = [c.public_key()];
+                let mut rekeyed = Vec::new();
+                let report = b
+                    .rekey(
+                        &mut rng,
+                        ciphertext.as_slice(),
+                        &mut rekeyed,
+                        &a.public_key(),
+                        &EncryptOptions::new(&receivers).fakes(3).padding(100),
+                    )
+                    .expect("rekeying should be ok");
+                assert_eq!(rekeyed.len() as u64, report.written(), "invalid written length");
+                assert_eq!(report.written(), report.ciphertext_bytes(), "invalid message length");
+                assert_eq!(4, report.recipients_attempted(), "invalid header count");
+                assert_eq!(100, report.padding_bytes(), "invalid padding length");
+
+                // Cam can decrypt the new message from Bea; Bea can't decrypt it.
+                assert_eq!(message, c.open(&b.public_key(), &rekeyed).expect("should decrypt"));
+                assert_matches!(b.open(&b.public_key(), &rekeyed), Err(DecryptError::NotAReceiver));
+                assert_eq!(
+                    seekable,
+                    matches!(
+                        crate::inspect::inspect(&c, rekeyed.as_slice(), &b.public_key(), &[]),
+                        Ok(inspection) if inspection.version == crate::SEEKABLE_FORMAT_VERSION
+                    )
+                );
+
+                // A modified message isn't re-encrypted.
+                let mut modified = ciphertext.clone();
+                let last = modified.len() - 1;
+                modified[last] ^= 1;
+                assert_matches!(
+                    b.rekey(&mut rng, modified.as_slice(), io::sink(), &a.public_key(), &options),
+                    Err(DecryptError::Corrupt)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn associated_data() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let mut ciphertext = Vec::new();
+        a.encrypt(
+            &mut rng,
+            Cursor::new(b"a message"),
+            &mut ciphertext,
+            &EncryptOptions::new(&[b.public_key()]).associated_data(b"sequence 1"),
+        )
+        .expect("encryption should be ok");
+
+        let mut plaintext = Vec::new();
+        b.decrypt_with_associated_data(
+            Cursor::new(&ciphertext),
+            &mut plaintext,
+            &a.public_key(),
+            b"sequence 1",
+        )
+        .expect("decryption should be ok");
+        assert_eq!(b"a message".to_vec(), plaintext, "invalid plaintext");
+
+        assert_matches!(
+            b.decrypt_with_associated_data(
+                Cursor::new(&ciphertext),
+                io::sink(),
+                &a.public_key(),
+                b"sequence 2",
+            ),
+            Err(DecryptError::NotAReceiver)
+        );
+        assert_matches!(
+            b.decrypt(Cursor::new(&ciphertext), io::sink(), &a.public_key()),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let ciphertext =
+            a.seal(&mut rng, &[b.public_key()], b"a small message").expect("should seal");
+        let plaintext = b.open(&a.public_key(), &ciphertext).expect("decryption should be ok");
+        assert_eq!(b"a small message".to_vec(), plaintext, "invalid plaintext");
+        assert_matches!(a.open(&b.public_key(), &ciphertext), Err(DecryptError::NotAReceiver));
+    }
+
+    #[test]
+    fn from_seed() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let seed = rng.gen::<[u8; 64]>();
+
+        let key = PrivateKey::from_seed(&seed);
+        assert_eq!(key, PrivateKey::from_seed(&seed), "should be deterministic");
+        assert_ne!(
+            key.public_key(),
+            PrivateKey::from_seed(&rng.gen()).public_key(),
+            "should depend on the seed"
+        );
+        assert_eq!(
+            Ok(key),
+            PrivateKey::import_paper_key(&PrivateKey::from_seed(&seed).export_paper_key()),
+            "paper key should recover the same key"
+        );
+    }
+
+    #[test]
+    fn seal_with_options() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let receivers = [b.public_key()];
+        let options = EncryptOptions::new(&receivers).fakes(3).padding(40).associated_data(b"ad");
+        let ciphertext = a.seal_with(&mut rng, b"a small message", &options).expect("should seal");
+        let plaintext = b
+            .open_with_associated_data(&a.public_key(), &ciphertext, b"ad")
+            .expect("decryption should be ok");
+        assert_eq!(b"a small message".to_vec(), plaintext, "invalid plaintext");
+        assert_matches!(b.open(&a.public_key(), &ciphertext), Err(DecryptError::NotAReceiver));
+    }
+
+    #[test]
+    fn seal_with_too_many_receivers() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let receivers = [b.public_key()];
+        let options = EncryptOptions::new(&receivers).fakes(crate::MAX_RECEIVERS);
+        assert_matches!(
+            a.seal_with(&mut rng, b"a small message", &options),
+            Err(EncryptError::TooManyReceivers)
+        );
+    }
+
+    #[test]
+    fn anonymous_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        let mut ciphertext = Vec::new();
+        PrivateKey::encrypt_anonymous(
+            &mut rng,
+            &b"this is an anonymous message"[..],
+            &mut ciphertext,
+            &EncryptOptions::new(&[b.public_key()]).fakes(3),
+        )
+        .expect("encryption should be ok");
+
+        let plaintext =
+            b.open(&PublicKey::anonymous(), &ciphertext).expect("decryption should be ok");
+        assert_eq!(b"this is an anonymous message".to_vec(), plaintext, "invalid plaintext");
+        assert_matches!(b.open(&a.public_key(), &ciphertext), Err(DecryptError::NotAReceiver));
+        assert_matches!(
+            a.open(&PublicKey::anonymous(), &ciphertext),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn decrypt_any() {
+        let (mut rng, a, b, plaintext, ciphertext) = setup(64);
+        let keys = [PrivateKey::random(&mut rng), PrivateKey::random(&mut rng), b];
+
+        let mut dst = Cursor::new(Vec::new());
+        let (idx, ptx_len) =
+            PrivateKey::decrypt_any(&keys, Cursor::new(&ciphertext), &mut dst, &a.public_key())
+                .expect("decryption should be ok");
+        assert_eq!(2, idx, "invalid key index");
+        assert_eq!(
+            dst.position(),
+            ptx_len.written(),
+            "returned/observed plaintext length mismatch"
+        );
+        assert_eq!(plaintext.to_vec(), dst.into_inner(), "incorrect plaintext");
+
+        assert_matches!(
+            PrivateKey::decrypt_any(
+                &keys[..2],
+                Cursor::new(&ciphertext),
+                io::sink(),
+                &a.public_key()
+            ),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn change_passphrase() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+
+        let mut old = Vec::new();
+        key.store(&mut old, &mut rng, b"old", 0, 0, 1).expect("writes should be infallible");
+
+        assert_matches!(
+            PrivateKey::change_passphrase(&mut rng, &old, b"wrong", b"new", BALLOON),
+            Err(DecryptError::InvalidCiphertext)
+        );
+
+        let new = PrivateKey::change_passphrase(&mut rng, &old, b"old", b"new", BALLOON)
+            .expect("should change passphrase");
+        assert_matches!(
+            PrivateKey::load(new.as_slice(), b"old"),
             Err(DecryptError::InvalidCiphertext)
         );
+        assert_eq!(Some(key), PrivateKey::load(new.as_slice(), b"new").ok(), "invalid key");
     }
 
     #[test]
-    fn modified_ciphertext() {
-        let (_, a, b, _, mut ciphertext) = setup(64);
-        ciphertext[200] ^= 1;
+    fn load_legacy_key() {
+        // The first key generated from the test seed, stored with `veil.pbenc` before lanes were
+        // supported, with a time cost of 1 and a memory cost of 6.
+        let hex = concat!(
+            "0106c5f6e35af79c5dee5e934ea557e5354c934bfcad29b2dcc6fb777ed7598a1dbb6fb579dc76c71f509c",
+            "18ea89ccca8f23962020e6d71107854eb5006280e88ebb8d4aa6ba9b2273cb5481360459fa37bfd34fd22d",
+            "998e73ae827ee7f7e7f39d1e",
+        );
+        let enc_key = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("should be valid hex"))
+            .collect::<Vec<u8>>();
+
+        let key = PrivateKey::load(enc_key.as_slice(), b"this is a secret")
+            .expect("should load a legacy key");
+        assert_eq!(PrivateKey::random(ChaChaRng::seed_from_u64(0xDEADBEEF)), key, "invalid key");
         assert_matches!(
-            b.decrypt(Cursor::new(ciphertext), io::sink(), &a.public_key()),
+            PrivateKey::load(enc_key.as_slice(), b"wrong"),
             Err(DecryptError::InvalidCiphertext)
         );
     }
 
     #[test]
-    fn sign_and_verify() {
+    fn duress_storage() {
         let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
         let key = PrivateKey::random(&mut rng);
-        let message = rng.gen::<[u8; 64]>();
+        let duress_key = PrivateKey::random(&mut rng);
 
-        let sig = key.sign(&mut rng, Cursor::new(message)).expect("signing should be ok");
+        for kdf in [BALLOON, Kdf::Argon2id { time_cost: 1, memory_cost: 64, parallelism: 1 }] {
+            let mut enc_keys = Vec::new();
+            key.store_with_duress(&mut enc_keys, &mut rng, b"real", &duress_key, b"duress", kdf)
+                .expect("writes should be infallible");
 
-        key.public_key().verify(Cursor::new(message), &sig).expect("verification should be ok");
+            // A key stored with a decoy is the same length as one stored with a duress key.
+            let mut decoyed = Vec::new();
+            key.store_with(&mut decoyed, &mut rng, b"real", kdf)
+                .expect("writes should be infallible");
+            assert_eq!(enc_keys.len(), decoyed.len(), "should not reveal a duress key");
+            assert_eq!(Some(&key), PrivateKey::load(decoyed.as_slice(), b"real").ok().as_ref());
+
+            assert_eq!(Some(&key), PrivateKey::load(enc_keys.as_slice(), b"real").ok().as_ref());
+            assert_eq!(
+                Some(&duress_key),
+                PrivateKey::load(enc_keys.as_slice(), b"duress").ok().as_ref()
+            );
+            assert_matches!(
+                PrivateKey::load(enc_keys.as_slice(), b"wrong"),
+                Err(DecryptError::InvalidCiphertext)
+            );
+        }
+
+        // A single key, stored without a decoy, is not mistaken for two.
+        let single = kdf::encrypt(&mut rng, b"real", BALLOON, &key.0.secret);
+        assert_eq!(Some(&key), PrivateKey::load(single.as_slice(), b"real").ok().as_ref());
+
+        // The passphrases must differ.
+        assert_matches!(
+            key.store_with_duress(io::sink(), &mut rng, b"real", &duress_key, b"real", BALLOON),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn argon2id_storage() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let key = PrivateKey::random(&mut rng);
+
+        let mut balloon = Vec::new();
+        key.store(&mut balloon, &mut rng, b"old", 0, 0, 1).expect("writes should be infallible");
+
+        let argon2id = Kdf::Argon2id { time_cost: 1, memory_cost: 64, parallelism: 1 };
+        let new = PrivateKey::change_passphrase(&mut rng, &balloon, b"old", b"new", argon2id)
+            .expect("should change passphrase");
+        assert_ne!(balloon.len(), new.len(), "should use a different envelope");
+        assert_eq!(
+            Some(&key),
+            PrivateKey::load(new.as_slice(), b"new").ok().as_ref(),
+            "invalid key"
+        );
+
+        let old = PrivateKey::change_passphrase(&mut rng, &new, b"new", b"old", BALLOON)
+            .expect("should change passphrase");
+        assert_eq!(balloon.len(), old.len(), "should use the veil.pbenc envelope");
+        assert_eq!(Some(key), PrivateKey::load(old.as_slice(), b"old").ok(), "invalid key");
+    }
+
+    #[test]
+    fn key_agreement() {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let a = PrivateKey::random(&mut rng);
+        let b = PrivateKey::random(&mut rng);
+
+        assert_eq!(
+            a.agree(&b.public_key(), "example"),
+            b.agree(&a.public_key(), "example"),
+            "shared secret mismatch"
+        );
     }
 
     fn setup(n: usize) -> (rand_chacha::ChaCha20Rng, PrivateKey, PrivateKey, Vec<u8>, Vec<u8>) {
@@ -291,14 +2843,12 @@ mod tests {
                 &mut rng,
                 Cursor::new(&plaintext),
                 Cursor::new(&mut ciphertext),
-                &[b.public_key()],
-                Some(20),
-                Some(123),
+                &EncryptOptions::new(&[b.public_key()]).fakes(20).padding(123),
             )
             .expect("encryption should be ok");
         assert_eq!(
             u64::try_from(ciphertext.len()).expect("usize should be <= u64"),
-            ctx_len,
+            ctx_len.written(),
             "returned/observed ciphertext length mismatch"
         );
 
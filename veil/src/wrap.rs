@@ -0,0 +1,145 @@
+//! Compact encryption of symmetric keys for a single receiver.
+
+use lockstitch::Protocol;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    keys::{PrivKey, PubKey},
+    sres::{self, NONCE_LEN},
+    DecryptError, PrivateKey,
+};
+
+/// The number of bytes added to a key by [`PrivateKey::wrap_key`].
+pub const WRAPPED_KEY_OVERHEAD: usize = NONCE_LEN + sres::OVERHEAD;
+
+/// Encrypt the given key for the given receiver and return the wrapped key.
+pub fn wrap(
+    mut rng: impl Rng + CryptoRng,
+    sender: &PrivateKey,
+    receiver: &PubKey,
+    key: &[u8],
+) -> Vec<u8> {
+    let ephemeral = PrivKey::random(&mut rng);
+    let nonce = rng.gen::<[u8; NONCE_LEN]>();
+
+    let mut wrapped = vec![0u8; key.len() + WRAPPED_KEY_OVERHEAD];
+    let (out_nonce, out_ciphertext) = wrapped.split_at_mut(NONCE_LEN);
+    out_nonce.copy_from_slice(&nonce);
+    sres::encrypt(sender, &ephemeral, receiver, &sres_nonce(&nonce), key, out_ciphertext);
+    wrapped
+}
+
+/// Decrypt the given wrapped key from the given sender and return the key.
+pub fn unwrap(
+    receiver: &PrivateKey,
+    sender: &PubKey,
+    wrapped: &[u8],
+) -> Result<Vec<u8>, DecryptError> {
+    if wrapped.len() < WRAPPED_KEY_OVERHEAD {
+        return Err(DecryptError::Truncated);
+    }
+
+    let (nonce, ciphertext) = wrapped.split_at(NONCE_LEN);
+    let mut ciphertext = ciphertext.to_vec();
+    let (_, key) = sres::decrypt(receiver, sender, &sres_nonce(nonce), &mut ciphertext)
+        .ok_or(DecryptError::NotAReceiver)?;
+    Ok(key.to_vec())
+}
+
+/// Derive the `veil.sres` nonce for a wrapped key from its random nonce.
+///
+/// This keeps wrapped keys and the headers of `veil.mres` messages, which are also `veil.sres`
+/// ciphertexts, from being substituted for each other: a header's nonce is derived from the
+/// message, and finding a wrapped key nonce which derives the same value is infeasible.
+fn sres_nonce(nonce: &[u8]) -> [u8; NONCE_LEN] {
+    // Initialize a protocol.
+    let mut wrap = Protocol::new("veil.wrap");
+
+    // Mix the random nonce into the protocol.
+    wrap.mix("nonce", nonce);
+
+    // Derive a nonce for the `veil.sres` ciphertext.
+    wrap.derive_array("sres-nonce")
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (mut rng, sender, receiver) = setup();
+
+        let key = rng.gen::<[u8; 32]>();
+        let wrapped = sender.wrap_key(&mut rng, &receiver.public_key(), &key);
+        assert_eq!(key.len() + WRAPPED_KEY_OVERHEAD, wrapped.len());
+        assert_eq!(
+            key.to_vec(),
+            receiver.unwrap_key(&sender.public_key(), &wrapped).expect("should unwrap")
+        );
+    }
+
+    #[test]
+    fn wrong_sender() {
+        let (mut rng, sender, receiver) = setup();
+
+        let wrapped = sender.wrap_key(&mut rng, &receiver.public_key(), &[7; 32]);
+        assert_matches!(
+            receiver.unwrap_key(&PrivateKey::random(&mut rng).public_key(), &wrapped),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn wrong_receiver() {
+        let (mut rng, sender, receiver) = setup();
+
+        let wrapped = sender.wrap_key(&mut rng, &receiver.public_key(), &[7; 32]);
+        assert_matches!(
+            PrivateKey::random(&mut rng).unwrap_key(&sender.public_key(), &wrapped),
+            Err(DecryptError::NotAReceiver)
+        );
+    }
+
+    #[test]
+    fn modified() {
+        let (mut rng, sender, receiver) = setup();
+
+        let mut wrapped = sender.wrap_key(&mut rng, &receiver.public_key(), &[7; 32]);
+        for i in 0..wrapped.len() {
+            wrapped[i] ^= 1;
+            assert_matches!(
+                receiver.unwrap_key(&sender.public_key(), &wrapped),
+                Err(DecryptError::NotAReceiver),
+                "modified byte {i}"
+            );
+            wrapped[i] ^= 1;
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let (mut rng, sender, receiver) = setup();
+
+        let wrapped = sender.wrap_key(&mut rng, &receiver.public_key(), &[]);
+        assert_matches!(
+            receiver.unwrap_key(&sender.public_key(), &wrapped),
+            Ok(key) if key.is_empty()
+        );
+        assert_matches!(
+            receiver.unwrap_key(&sender.public_key(), &wrapped[1..]),
+            Err(DecryptError::Truncated)
+        );
+    }
+
+    fn setup() -> (ChaChaRng, PrivateKey, PrivateKey) {
+        let mut rng = ChaChaRng::seed_from_u64(0xDEADBEEF);
+        let sender = PrivateKey::random(&mut rng);
+        let receiver = PrivateKey::random(&mut rng);
+        (rng, sender, receiver)
+    }
+}
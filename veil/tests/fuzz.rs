@@ -1,9 +1,11 @@
+#![cfg(feature = "std")]
+
 use std::io::{self, Cursor};
 
 use bolero::TypeGenerator;
 use rand::SeedableRng;
 use rand_chacha::{rand_core::OsRng, ChaChaRng};
-use veil::{PrivateKey, Signature};
+use veil::{EncryptOptions, PrivateKey, Signature};
 
 #[test]
 fn decrypt() {
@@ -17,8 +19,13 @@ fn decrypt() {
 fn encrypt() {
     bolero::check!().with_type::<(u64, Vec<u8>)>().for_each(|(seed, data)| {
         let key = PrivateKey::random(ChaChaRng::seed_from_u64(*seed));
-        key.encrypt(OsRng, Cursor::new(data), io::sink(), &[key.public_key()], None, None)
-            .expect("should encrypt without error");
+        key.encrypt(
+            OsRng,
+            Cursor::new(data),
+            io::sink(),
+            &EncryptOptions::new(&[key.public_key()]),
+        )
+        .expect("should encrypt without error");
     });
 }
 